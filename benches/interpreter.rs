@@ -0,0 +1,55 @@
+//! How fast the interpreter runs arithmetic-heavy loops, method calls and field accesses
+use criterion::{criterion_group, criterion_main, Criterion};
+use watertower::exec::interpreter::Interpreter;
+
+/// The methods of `etc/bench.java`, what they are called with, and what they return
+const BENCHES: &[(&str, i32, i32)] = &[
+    ("loop", 10_000, 19_999),
+    ("collatz", 1_000, 178),
+    ("integrate", 10_000, 3_141_592),
+    ("sieve", 10_000, 1_229),
+    ("fib", 15, 610),
+    ("fields", 10_000, 15_000),
+];
+
+fn interpreter() -> Interpreter {
+    let data = std::fs::read("./etc/bench.class").unwrap();
+    let mut vm = Interpreter::default();
+    vm.load_class_from_reader(&mut data.as_slice()).unwrap();
+    vm
+}
+
+fn interpret(c: &mut Criterion) {
+    for &(method, arg, expected) in BENCHES {
+        let mut vm = interpreter();
+        let result = vm.invoke::<_, i32>("bench", method, (arg,)).unwrap();
+        assert_eq!(result, expected, "{}({})", method, arg);
+        c.bench_function(method, |b| {
+            b.iter(|| vm.invoke::<_, i32>("bench", method, (arg,)).unwrap())
+        });
+    }
+}
+
+/// The same methods once they have been compiled
+#[cfg(feature = "jit")]
+fn compile(c: &mut Criterion) {
+    use watertower::exec::jit::Jit;
+
+    for &(method, arg, expected) in BENCHES {
+        let mut vm = interpreter();
+        let mut jit = Jit::new().unwrap();
+        jit.call_threshold(1);
+        vm.set_jit(Some(jit));
+        let result = vm.invoke::<_, i32>("bench", method, (arg,)).unwrap();
+        assert_eq!(result, expected, "{}({})", method, arg);
+        c.bench_function(&format!("{} (jit)", method), |b| {
+            b.iter(|| vm.invoke::<_, i32>("bench", method, (arg,)).unwrap())
+        });
+    }
+}
+
+#[cfg(feature = "jit")]
+criterion_group!(benches, interpret, compile);
+#[cfg(not(feature = "jit"))]
+criterion_group!(benches, interpret);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+public class bench {
+    int counter;
+
+    static int loop(int n) {
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            sum += i * i % 7;
+        }
+        return sum;
+    }
+
+    static int collatz(int n) {
+        int longest = 0;
+        for (int start = 1; start < n; start++) {
+            long x = start;
+            int steps = 0;
+            while (x != 1) {
+                x = (x & 1) == 0 ? x >> 1 : 3 * x + 1;
+                steps++;
+            }
+            longest = Math.max(longest, steps);
+        }
+        return longest;
+    }
+
+    static int integrate(int steps) {
+        double sum = 0;
+        double dx = 1.0 / steps;
+        for (int i = 0; i < steps; i++) {
+            double x = (i + 0.5) * dx;
+            sum += 4.0 / (1.0 + x * x);
+        }
+        return (int) (sum * dx * 1000000);
+    }
+
+    static int sieve(int n) {
+        boolean[] composite = new boolean[n + 1];
+        int count = 0;
+        for (int i = 2; i <= n; i++) {
+            if (!composite[i]) {
+                count++;
+                for (int j = i * 2; j <= n; j += i) {
+                    composite[j] = true;
+                }
+            }
+        }
+        return count;
+    }
+
+    static int fib(int n) {
+        return n < 2 ? n : fib(n - 1) + fib(n - 2);
+    }
+
+    static int fields(int n) {
+        bench b = new bench();
+        for (int i = 0; i < n; i++) {
+            b.counter += i & 3;
+        }
+        return b.counter;
+    }
+}
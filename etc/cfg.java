@@ -0,0 +1,44 @@
+public class cfg {
+    static int classify(int x) {
+        switch (x) {
+            case 1:
+                return 10;
+            case 2:
+            case 3:
+                return 20;
+            default:
+                return 0;
+        }
+    }
+
+    static int sparse(int x) {
+        switch (x) {
+            case 1:
+                return 1;
+            case 1000:
+                return 2;
+            default:
+                return 3;
+        }
+    }
+
+    static int nested(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            for (int j = 0; j < i; j++) {
+                total += j;
+            }
+        }
+        return total;
+    }
+
+    static int guarded(int[] values) {
+        try {
+            return values[0];
+        } catch (ArrayIndexOutOfBoundsException e) {
+            return -1;
+        } catch (NullPointerException e) {
+            return -2;
+        }
+    }
+}
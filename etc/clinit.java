@@ -0,0 +1,91 @@
+class Log {
+    static int value;
+
+    static void add(int digit) {
+        value = value * 10 + digit;
+    }
+}
+
+class Parent {
+    static int parent = 5;
+
+    static {
+        Log.add(1);
+    }
+}
+
+class Child extends Parent {
+    static {
+        Log.add(2);
+    }
+
+    static int child() {
+        return parent + 2;
+    }
+}
+
+class Created {
+    static {
+        Log.add(3);
+    }
+}
+
+class Constants {
+    static final int ANSWER = 42;
+    static final long BIG = 1L << 40;
+    static final double HALF = 0.5;
+    static final String NAME = "constants";
+    static final int[] SQUARES = new int[10];
+
+    static {
+        for (int i = 0; i < SQUARES.length; i++) {
+            SQUARES[i] = i * i;
+        }
+    }
+
+    static int square(int i) {
+        return SQUARES[i];
+    }
+}
+
+class First {
+    static int x = Second.y + 1;
+}
+
+class Second {
+    static int y = First.x + 10;
+}
+
+class Broken {
+    static int zero;
+    static int value = 1 / zero;
+}
+
+public class clinit {
+    static int order() {
+        Child.child();
+        new Created();
+        Child.child();
+        new Created();
+        return Log.value;
+    }
+
+    static int recursive() {
+        return First.x * 100 + Second.y;
+    }
+
+    static int broken() {
+        int result = 0;
+        try {
+            result = Broken.value;
+        } catch (ExceptionInInitializerError e) {
+            result += e.getCause() instanceof ArithmeticException ? 1 : 2;
+        }
+        try {
+            result = Broken.value;
+        } catch (NoClassDefFoundError e) {
+            result += 10;
+        }
+        return result;
+    }
+}
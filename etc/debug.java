@@ -0,0 +1,26 @@
+public class debug {
+    static class Point {
+        int x;
+        int y;
+
+        Point(int x, int y) {
+            this.x = x;
+            this.y = y;
+        }
+
+        int sum() {
+            return x + y;
+        }
+    }
+
+    static int run() {
+        Point point = new Point(3, 4);
+        int sum = point.sum();
+        int twice = sum * 2;
+        return twice - sum;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(run());
+    }
+}
@@ -0,0 +1,102 @@
+public class decompile {
+    private Comparable<String> last = "";
+    private int count;
+    static final int LIMIT = 10;
+
+    static int sum(int[] values) {
+        int total = 0;
+        for (int i = 0; i < values.length; i++) {
+            total += values[i];
+        }
+        return total;
+    }
+
+    static int countdown(int n) {
+        int steps = 0;
+        do {
+            n /= 2;
+            steps++;
+        } while (n > 0);
+        return steps;
+    }
+
+    static String sign(int x, boolean strict) {
+        if (x > 0 && strict) {
+            return "positive";
+        } else if (x < 0 || !strict) {
+            return "negative";
+        }
+        return "zero";
+    }
+
+    static int pick(boolean flag, int a, int b) {
+        return flag ? a : b;
+    }
+
+    static String name(int day) {
+        String result;
+        switch (day) {
+            case 0:
+                result = "sunday";
+                break;
+            case 6:
+                result = "saturday";
+                break;
+            default:
+                result = "weekday";
+        }
+        return result;
+    }
+
+    static int parse(String s) {
+        try {
+            return Integer.parseInt(s);
+        } catch (NumberFormatException e) {
+            return -1;
+        }
+    }
+
+    static int attempt(String s) {
+        int result;
+        try {
+            result = Integer.parseInt(s);
+        } catch (NumberFormatException e) {
+            result = -1;
+        } finally {
+            System.out.println("done");
+        }
+        return result;
+    }
+
+    int locked(String name) {
+        synchronized (this) {
+            last = name;
+            return count;
+        }
+    }
+
+    static String greet(String name, int times) {
+        return "hello " + name + " x" + times;
+    }
+
+    static int[] primes() {
+        return new int[] {2, 3, 5, 7};
+    }
+
+    static <T extends Comparable<T>> T max(T[] items) {
+        T best = null;
+        for (T item : items) {
+            if (best == null || item.compareTo(best) > 0) {
+                best = item;
+            }
+        }
+        return best;
+    }
+
+    void add(String name) {
+        if (count < LIMIT) {
+            last = name;
+            count++;
+        }
+    }
+}
@@ -0,0 +1,73 @@
+public class embed {
+    static int counter;
+
+    static int add(int a, int b) {
+        return a + b;
+    }
+
+    static double mean(long a, float b) {
+        return (a + b) / 2;
+    }
+
+    static boolean isUpper(char c) {
+        return c >= 'A' && c <= 'Z';
+    }
+
+    static String greet(String name) {
+        if (name == null) {
+            return null;
+        }
+        return "hello, " + name;
+    }
+
+    static int[] range(int n) {
+        int[] range = new int[n];
+        for (int i = 0; i < n; i++) {
+            range[i] = i;
+        }
+        return range;
+    }
+
+    static String join(String[] words) {
+        StringBuilder joined = new StringBuilder();
+        for (int i = 0; i < words.length; i++) {
+            if (i > 0) {
+                joined.append('-');
+            }
+            joined.append(words[i]);
+        }
+        return joined.toString();
+    }
+
+    static String[] split(String s) {
+        int count = 1;
+        for (int i = 0; i < s.length(); i++) {
+            if (s.charAt(i) == ',') {
+                count++;
+            }
+        }
+        String[] parts = new String[count];
+        int start = 0;
+        for (int i = 0; i < count; i++) {
+            int end = start;
+            while (end < s.length() && s.charAt(end) != ',') {
+                end++;
+            }
+            parts[i] = s.substring(start, end);
+            start = end + 1;
+        }
+        return parts;
+    }
+
+    static void count() {
+        counter++;
+    }
+
+    static int counter() {
+        return counter;
+    }
+
+    static int divide(int a, int b) {
+        return a / b;
+    }
+}
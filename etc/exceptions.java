@@ -0,0 +1,118 @@
+class Oops extends Exception {
+    final int code;
+
+    Oops(int code) {
+        this.code = code;
+    }
+}
+
+public class exceptions {
+    static int thrower(int depth) throws Oops {
+        if (depth == 0) {
+            throw new Oops(42);
+        }
+        return thrower(depth - 1) + 1;
+    }
+
+    static int caught() {
+        try {
+            return thrower(3);
+        } catch (Oops e) {
+            return e.code;
+        }
+    }
+
+    static int subtype() {
+        try {
+            throw new IllegalStateException();
+        } catch (IllegalArgumentException e) {
+            return 1;
+        } catch (RuntimeException e) {
+            return 2;
+        }
+    }
+
+    static int arithmetic(int d) {
+        try {
+            return 10 / d;
+        } catch (ArithmeticException e) {
+            return e.getMessage() != null ? -1 : -2;
+        }
+    }
+
+    static int nullPointer() {
+        Object o = null;
+        try {
+            return o.hashCode();
+        } catch (NullPointerException e) {
+            return -3;
+        }
+    }
+
+    static int classCast() {
+        Object o = new int[1];
+        try {
+            return ((Runnable) o) == null ? 0 : 1;
+        } catch (ClassCastException e) {
+            return -4;
+        }
+    }
+
+    static int index() {
+        int[] a = new int[2];
+        try {
+            return a[5];
+        } catch (IndexOutOfBoundsException e) {
+            return -5;
+        }
+    }
+
+    static int counter;
+
+    static int finallyBlock() {
+        counter = 0;
+        try {
+            try {
+                thrower(1);
+            } finally {
+                counter += 10;
+            }
+        } catch (Oops e) {
+            counter += e.code;
+        }
+        return counter;
+    }
+
+    static int divide(int a, int b) {
+        return a / b;
+    }
+
+    static int wrap() {
+        try {
+            return divide(1, 0);
+        } catch (ArithmeticException e) {
+            throw new IllegalStateException(e);
+        }
+    }
+
+    static void uncaught() {
+        wrap();
+    }
+
+    static int depth;
+
+    static void recurse() {
+        depth++;
+        recurse();
+    }
+
+    static int overflow() {
+        depth = 0;
+        try {
+            recurse();
+            return -1;
+        } catch (StackOverflowError e) {
+            return depth;
+        }
+    }
+}
@@ -0,0 +1,51 @@
+class gc {
+    static Node kept;
+
+    static class Node {
+        Node next;
+        int[] data;
+
+        Node(Node next, int size) {
+            this.next = next;
+            this.data = new int[size];
+        }
+    }
+
+    // allocates far more than the heap holds, keeping only the last node alive
+    static int garbage(int rounds) {
+        int sum = 0;
+        for (int i = 0; i < rounds; i++) {
+            Node a = new Node(null, 1000);
+            Node b = new Node(a, 1000);
+            a.next = b;
+            kept = b;
+            sum += b.data.length;
+        }
+        System.gc();
+        return sum;
+    }
+
+    // keeps everything alive, until the heap runs out
+    static int exhaust() {
+        Node head = null;
+        int count = 0;
+        try {
+            while (true) {
+                head = new Node(head, 1000);
+                count++;
+            }
+        } catch (OutOfMemoryError e) {
+            head = null;
+            return count;
+        }
+    }
+
+    static boolean huge() {
+        try {
+            long[][] arrays = new long[1 << 20][1 << 20];
+            return arrays.length > 0;
+        } catch (OutOfMemoryError e) {
+            return false;
+        }
+    }
+}
@@ -0,0 +1,93 @@
+import java.lang.invoke.MethodHandle;
+import java.lang.invoke.MethodHandles;
+import java.lang.invoke.MethodType;
+import java.lang.invoke.WrongMethodTypeException;
+
+class Handles {
+    static int total;
+    int value;
+
+    Handles(int value) {
+        this.value = value;
+    }
+
+    static int add(int a, int b) {
+        return a + b;
+    }
+
+    int scale(int by) {
+        return value * by;
+    }
+
+    static MethodHandle adder() throws Throwable {
+        MethodType type = MethodType.methodType(int.class, int.class, int.class);
+        return MethodHandles.lookup().findStatic(Handles.class, "add", type);
+    }
+
+    static int exact() throws Throwable {
+        return (int) adder().invokeExact(2, 3);
+    }
+
+    // byte widens to int, Integer unboxes to int and the int result widens to long
+    static long adapted() throws Throwable {
+        return (long) adder().invoke((byte) 2, Integer.valueOf(40));
+    }
+
+    static int virtual(int value) throws Throwable {
+        MethodType type = MethodType.methodType(int.class, int.class);
+        MethodHandle scale = MethodHandles.lookup().findVirtual(Handles.class, "scale", type);
+        Object scaled = scale.invoke(new Handles(value), 3);
+        return (Integer) scaled;
+    }
+
+    static int constructor(int value) throws Throwable {
+        MethodType type = MethodType.methodType(void.class, int.class);
+        MethodHandle make = MethodHandles.lookup().findConstructor(Handles.class, type);
+        Handles made = (Handles) make.invokeExact(value);
+        return made.value;
+    }
+
+    static int fields(int value) throws Throwable {
+        MethodHandles.Lookup lookup = MethodHandles.lookup();
+        MethodHandle set = lookup.findStaticSetter(Handles.class, "total", int.class);
+        MethodHandle get = lookup.findGetter(Handles.class, "value", int.class);
+        set.invokeExact(value);
+        return total + (int) get.invokeExact(new Handles(1));
+    }
+
+    static int withArguments() throws Throwable {
+        return (Integer) adder().invokeWithArguments(4, 5);
+    }
+
+    static String type() throws Throwable {
+        return adder().toString();
+    }
+
+    static String wrongType() throws Throwable {
+        try {
+            long sum = (long) adder().invokeExact(1, 2);
+            return "no exception";
+        } catch (WrongMethodTypeException e) {
+            return e.getClass().getName();
+        }
+    }
+
+    static String missing() throws Throwable {
+        try {
+            MethodHandles.lookup().findStatic(Handles.class, "nope", MethodType.methodType(void.class));
+            return "found";
+        } catch (NoSuchMethodException e) {
+            return e.getClass().getName();
+        }
+    }
+
+    static String notStatic() throws Throwable {
+        try {
+            MethodType type = MethodType.methodType(int.class, int.class);
+            MethodHandles.lookup().findStatic(Handles.class, "scale", type);
+            return "found";
+        } catch (IllegalAccessException e) {
+            return e.getClass().getName();
+        }
+    }
+}
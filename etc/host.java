@@ -0,0 +1,69 @@
+public class host {
+    int left;
+    int right;
+
+    host(int left, int right) {
+        this.left = left;
+        this.right = right;
+    }
+
+    static native int add(int a, int b);
+    static native String greet(String name);
+    static native int[] range(int n);
+    static native void fail(String message);
+    static native int twice(int n);
+    static native String describe(Object o);
+    static native void missing();
+    native int sum();
+
+    static int square(int n) {
+        return n * n;
+    }
+
+    public String toString() {
+        return "host(" + left + ", " + right + ")";
+    }
+
+    static int arithmetic() {
+        return add(5, 8) + twice(7);
+    }
+
+    static int strings() {
+        String greeting = greet("world");
+        System.out.println(greeting);
+        System.out.println(describe(new host(3, 4)));
+        return greeting.compareTo("hello, world");
+    }
+
+    static int arrays() {
+        int total = 0;
+        for (int i : range(10)) {
+            total += i;
+        }
+        return total;
+    }
+
+    static int exceptions() {
+        try {
+            fail("bad state");
+            return 0;
+        } catch (IllegalStateException e) {
+            System.out.println(e.getMessage());
+            return -1;
+        }
+    }
+
+    static int instance() {
+        return new host(3, 4).sum();
+    }
+
+    static int unbound() {
+        try {
+            missing();
+            return 0;
+        } catch (UnsatisfiedLinkError e) {
+            System.out.println(e);
+            return -2;
+        }
+    }
+}
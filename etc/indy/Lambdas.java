@@ -0,0 +1,90 @@
+interface Op {
+    int apply(int a, int b);
+}
+
+interface Fn<T, R> {
+    R apply(T t);
+}
+
+interface BiFn<T, U, R> {
+    R apply(T t, U u);
+}
+
+interface Source {
+    int get();
+}
+
+interface Marker {}
+
+class Lambdas {
+    private int base;
+
+    Lambdas(int base) {
+        this.base = base;
+    }
+
+    static int combine(Op op, int a, int b) {
+        return op.apply(a, b);
+    }
+
+    static int add(int a, int b) {
+        return a + b;
+    }
+
+    int offset(int x) {
+        return x + base;
+    }
+
+    static int nonCapturing() {
+        return combine((a, b) -> a * b, 6, 7);
+    }
+
+    static int capturing(int k) {
+        return combine((a, b) -> a + b + k, 1, 2);
+    }
+
+    static int staticReference() {
+        return combine(Lambdas::add, 2, 3);
+    }
+
+    int doubled() {
+        Source source = () -> base * 2;
+        return source.get();
+    }
+
+    static int instance(int base) {
+        return new Lambdas(base).doubled();
+    }
+
+    static int unbound(int base, int x) {
+        BiFn<Lambdas, Integer, Integer> offset = Lambdas::offset;
+        return offset.apply(new Lambdas(base), x);
+    }
+
+    static int constructor(int base) {
+        Fn<Integer, Lambdas> make = Lambdas::new;
+        return make.apply(base).base;
+    }
+
+    static long widened(int x) {
+        Fn<Integer, Long> widen = Long::valueOf;
+        return widen.apply(x);
+    }
+
+    static int marked() {
+        Op op = (Op & Marker) (a, b) -> a - b;
+        return op instanceof Marker ? op.apply(5, 3) : -1;
+    }
+
+    public String toString() {
+        return "Lambdas(" + base + ")";
+    }
+
+    static String concat(String name, int n, char c, double d, boolean b, long l) {
+        return "name=" + name + ", n=" + n + c + d + b + l + " " + new Lambdas(n) + null;
+    }
+
+    static String control(int n) {
+        return "\u0001" + n + "\u0002";
+    }
+}
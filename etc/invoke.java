@@ -0,0 +1,123 @@
+interface Shape {
+    int area();
+
+    default int sides() {
+        return 0;
+    }
+}
+
+abstract class Base implements Shape {
+    protected int scale = 1;
+
+    public int sides() {
+        return 4;
+    }
+
+    abstract int kind();
+
+    int describe() {
+        return kind() * 100 + area();
+    }
+
+    private int secret() {
+        return 7;
+    }
+
+    int callSecret() {
+        return secret();
+    }
+}
+
+class Square extends Base {
+    private final int side;
+
+    Square(int side) {
+        this.side = side;
+    }
+
+    public int area() {
+        return side * side * scale;
+    }
+
+    int kind() {
+        return 1;
+    }
+}
+
+class Triangle extends Base {
+    private final int b, h;
+
+    Triangle(int b, int h) {
+        this.b = b;
+        this.h = h;
+    }
+
+    public int area() {
+        return b * h / 2;
+    }
+
+    public int sides() {
+        return 3;
+    }
+
+    int kind() {
+        return 3;
+    }
+}
+
+class Circle implements Shape {
+    private final int r;
+
+    Circle(int r) {
+        this.r = r;
+    }
+
+    public int area() {
+        return 3 * r * r;
+    }
+}
+
+class Cube extends Square {
+    Cube(int side) {
+        super(side);
+    }
+
+    public int area() {
+        return 6 * super.area();
+    }
+
+    int kind() {
+        return 2;
+    }
+}
+
+public class invoke {
+    static int fib(int n) {
+        return n < 2 ? n : fib(n - 1) + fib(n - 2);
+    }
+
+    static long addLong(long a, int b, long c) {
+        return a + b + c;
+    }
+
+    static double mix(int a, double b, float c, long d) {
+        return a + b + c + d;
+    }
+
+    static int total(Shape[] shapes) {
+        int total = 0;
+        for (Shape shape : shapes) {
+            total += shape.area() + shape.sides();
+        }
+        return total;
+    }
+
+    static int shapes() {
+        return total(new Shape[] { new Square(3), new Triangle(4, 5), new Circle(2), new Cube(2) });
+    }
+
+    static int describe() {
+        Base cube = new Cube(2);
+        return cube.describe() + cube.callSecret();
+    }
+}
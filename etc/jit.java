@@ -0,0 +1,65 @@
+public class jit {
+    static long ints(int a, int b) {
+        long h = a + b;
+        h = h * 31 + (a - b);
+        h = h * 31 + a * b;
+        h = h * 31 + (a << b) + (a >> b) + (a >>> b);
+        h = h * 31 + (a & b) + (a | b) + (a ^ b) + -a;
+        h = h * 31 + (byte) a + (char) a + (short) a;
+        h = h * 31 + (a < b ? 1 : a == b ? 2 : 3);
+        return h;
+    }
+
+    static long longs(long a, long b) {
+        long h = a + b;
+        h = h * 31 + (a - b) + a * b;
+        h = h * 31 + (a << b) + (a >> b) + (a >>> b);
+        h = h * 31 + (a & b) + (a | b) + (a ^ b) + -a;
+        h = h * 31 + (int) a + (a < b ? 1 : a == b ? 2 : 3);
+        long c, d;
+        c = d = h * 3;
+        return c + d;
+    }
+
+    static double floats(float a, float b) {
+        float f = a + b - a * b / (b - 1);
+        double h = f + -a;
+        h = h * 31 + (int) a + (long) b + (int) (a * 1e10f);
+        h = h * 31 + (a < b ? 1 : a > b ? 2 : a == b ? 3 : 4);
+        h = h * 31 + (a <= b ? 1 : 5);
+        return h;
+    }
+
+    static double doubles(double a, double b) {
+        double h = a + b - a * b / (b - 1);
+        h = h * 31 + (int) a + (long) b + (float) a + (long) (a * 1e20);
+        h = h * 31 + (a < b ? 1 : a > b ? 2 : a == b ? 3 : 4);
+        h = h * 31 + (a >= b ? 1 : 5);
+        return h;
+    }
+
+    static int divide(int a, int b) {
+        return a / b + a % b + (int) ((long) a / b);
+    }
+
+    static int cases(int key) {
+        int sum = 0;
+        switch (key) {
+            case 0: sum += 1;
+            case 1: sum += 2; break;
+            case 2: sum += 4; break;
+            case 3: sum += 8; break;
+            default: sum += 16;
+        }
+        switch (key * 1000) {
+            case -5000: return sum + 100;
+            case 1000: return sum + 200;
+            case 100000: return sum + 300;
+            default: return sum;
+        }
+    }
+
+    static int depth(int n) {
+        return n == 0 ? 0 : 1 + depth(n - 1);
+    }
+}
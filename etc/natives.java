@@ -0,0 +1,87 @@
+public class natives {
+    static void strings() {
+        StringBuilder sb = new StringBuilder();
+        sb.append("x=").append(42).append(',').append(1.5).append(true).append((Object) null);
+        System.out.println(sb);
+
+        String s = "Hello, World";
+        System.out.println(s.length() + " " + s.charAt(4) + " " + s.indexOf("World") + " "
+                + s.substring(7).toUpperCase());
+        System.out.println(s.equals("Hello, " + "World") + " " + "abc".compareTo("abd") + " "
+                + "abc".hashCode() + " " + "  trim me ".trim() + "|");
+        System.out.println(new StringBuilder("abc").reverse().toString().concat("!"));
+    }
+
+    static void math() {
+        System.out.println(Math.max(3, 7) + " " + Math.abs(-2.5) + " " + Math.sqrt(16.0) + " "
+                + Math.round(2.5) + " " + Math.round(-2.5) + " " + Math.floorMod(-7, 3) + " "
+                + Math.min(-0.0, 0.0) + " " + (1.0 / 3) + " " + 1e10 + " " + 100.0f);
+    }
+
+    static void boxing() {
+        Integer a = 127, b = 127, c = 1000, d = 1000;
+        System.out.println((a == b) + " " + (c == d) + " " + c.equals(d) + " "
+                + Integer.parseInt("-123") + " " + Integer.toHexString(-1));
+
+        Object o = Double.valueOf(0.1);
+        Integer x = 5;
+        int sum = x + c;
+        System.out.println(o + " " + Long.valueOf(1L << 40) + " " + Boolean.TRUE + " "
+                + Character.valueOf('q') + " " + sum + " " + Double.parseDouble("2.5e3"));
+    }
+
+    static int badNumber() {
+        try {
+            return Integer.parseInt("12x");
+        } catch (NumberFormatException e) {
+            System.err.println(e.getMessage());
+            return -1;
+        }
+    }
+
+    static void arrays() {
+        int[] src = {1, 2, 3, 4, 5};
+        int[] dst = new int[5];
+        System.arraycopy(src, 1, dst, 0, 3);
+        // overlapping copies behave as if through a temporary array
+        System.arraycopy(src, 0, src, 1, 4);
+        StringBuilder sb = new StringBuilder();
+        for (int i = 0; i < 5; i++) {
+            sb.append(dst[i]).append(src[i]);
+        }
+        System.out.println(sb);
+
+        Object[] objects = {"a", 1, "c"};
+        String[] strings = new String[3];
+        try {
+            System.arraycopy(objects, 0, strings, 0, 3);
+        } catch (ArrayStoreException e) {
+            System.out.println("stored " + strings[0] + " " + strings[1]);
+        }
+        try {
+            System.arraycopy(src, 3, dst, 0, 3);
+        } catch (IndexOutOfBoundsException e) {
+            System.out.println("out of bounds");
+        }
+    }
+
+    static void objects() {
+        Object o = new natives();
+        System.out.println(o.toString().startsWith("natives@") + " " + new Pair(1, "one"));
+    }
+}
+
+class Pair {
+    private final int left;
+    private final String right;
+
+    Pair(int left, String right) {
+        this.left = left;
+        this.right = right;
+    }
+
+    @Override
+    public String toString() {
+        return "(" + left + ", " + right + ")";
+    }
+}
@@ -0,0 +1,19 @@
+public class Nest {
+    private int count;
+
+    static class Counter {
+        private final Nest nest = new Nest();
+
+        int add(int times) {
+            // nestmates reach each other's private members without accessors
+            for (int i = 0; i < times; i++) {
+                nest.count++;
+            }
+            return nest.count;
+        }
+    }
+
+    static int count(int times) {
+        return new Counter().add(times);
+    }
+}
@@ -0,0 +1,28 @@
+public class profile {
+    static int fib(int n) {
+        return n < 2 ? n : fib(n - 1) + fib(n - 2);
+    }
+
+    static int sum(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            total += i;
+        }
+        return total;
+    }
+
+    static int run() {
+        return fib(10) + sum(100);
+    }
+
+    static int copy() {
+        int[] from = new int[100000];
+        int[] to = new int[from.length];
+        System.arraycopy(from, 0, to, 0, from.length);
+        return to.length;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(run());
+    }
+}
@@ -0,0 +1,88 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.List;
+import java.util.function.IntSupplier;
+
+@Retention(RetentionPolicy.RUNTIME)
+@interface Tag {
+    String value() default "none";
+
+    Level level() default Level.LOW;
+
+    Class<?> type() default Object.class;
+}
+
+@Target(ElementType.TYPE_USE)
+@Retention(RetentionPolicy.RUNTIME)
+@interface Checked {
+}
+
+enum Level {
+    LOW,
+    HIGH
+}
+
+@Tag(value = "counter", level = Level.HIGH, type = Counter.class)
+class Counter<T extends Counter<T>> {
+    private int count;
+    List<@Checked Counter<T>> children;
+
+    class Step {
+        int size = 2;
+    }
+
+    int increment(Step step) {
+        count += step.size;
+        return count;
+    }
+
+    int step() {
+        return increment(new Step());
+    }
+
+    IntSupplier supplier() {
+        return () -> increment(new Step());
+    }
+
+    Object anonymous() {
+        return new Object() {
+            public int hashCode() {
+                return count;
+            }
+        };
+    }
+
+    static int count(int times, @Tag("times") int start) {
+        @Checked Counter counter = new @Checked Counter();
+        counter.count = start;
+        int last = start;
+        for (int i = 0; i < times; i++) {
+            last = counter.step();
+        }
+        return last;
+    }
+
+    static int supplied(int times) {
+        IntSupplier supplier = new Counter<>().supplier();
+        int last = 0;
+        for (int i = 0; i < times; i++) {
+            last = supplier.getAsInt();
+        }
+        return last;
+    }
+}
+
+class Tally extends Counter<Tally> {
+    int count;
+
+    static int count(int times, int start) {
+        Tally tally = new Tally();
+        tally.count = start;
+        for (int i = 0; i < times; i++) {
+            tally.count += tally.step();
+        }
+        return tally.count;
+    }
+}
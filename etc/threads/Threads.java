@@ -0,0 +1,217 @@
+public class Threads {
+    static class Counter implements Runnable {
+        int value;
+        final boolean blocks;
+
+        Counter(boolean blocks) {
+            this.blocks = blocks;
+        }
+
+        synchronized void increment() {
+            int value = this.value;
+            this.value = value + 1;
+        }
+
+        public void run() {
+            for (int i = 0; i < 10000; i++) {
+                if (blocks) {
+                    synchronized (this) {
+                        int value = this.value;
+                        this.value = value + 1;
+                    }
+                } else {
+                    increment();
+                }
+            }
+        }
+    }
+
+    static class Queue {
+        int item;
+        boolean full;
+
+        synchronized void put(int item) throws InterruptedException {
+            while (full) {
+                wait();
+            }
+            this.item = item;
+            full = true;
+            notifyAll();
+        }
+
+        synchronized int take() throws InterruptedException {
+            while (!full) {
+                wait();
+            }
+            full = false;
+            notifyAll();
+            return item;
+        }
+    }
+
+    static class Locker extends Thread {
+        final Object first, second;
+        final String label;
+        final StringBuilder out;
+        final int millis;
+
+        Locker(Object first, Object second, String label, StringBuilder out, int millis) {
+            this.first = first;
+            this.second = second;
+            this.label = label;
+            this.out = out;
+            this.millis = millis;
+        }
+
+        public void run() {
+            try {
+                synchronized (first) {
+                    Thread.sleep(millis);
+                    synchronized (second) {
+                        out.append(label);
+                    }
+                }
+            } catch (InterruptedException e) {
+            }
+        }
+    }
+
+    static class Worker implements Runnable {
+        final StringBuilder out;
+        final String name;
+
+        Worker(StringBuilder out, String name) {
+            this.out = out;
+            this.name = name;
+        }
+
+        public void run() {
+            if (name == null) {
+                throw new IllegalStateException("boom");
+            }
+            for (int i = 0; i < 10; i++) {
+                int work = 0;
+                for (int j = 0; j < 100; j++) {
+                    work += j;
+                }
+                synchronized (out) {
+                    out.append(name);
+                }
+            }
+        }
+    }
+
+    static int counters() throws InterruptedException {
+        return count(false);
+    }
+
+    static int blocks() throws InterruptedException {
+        return count(true);
+    }
+
+    static int count(boolean blocks) throws InterruptedException {
+        Counter counter = new Counter(blocks);
+        Thread[] threads = new Thread[4];
+        for (int i = 0; i < threads.length; i++) {
+            threads[i] = new Thread(counter);
+            threads[i].start();
+        }
+        for (Thread thread : threads) {
+            thread.join();
+        }
+        return counter.value;
+    }
+
+    static int producerConsumer() throws InterruptedException {
+        final Queue queue = new Queue();
+        Thread producer = new Thread() {
+            public void run() {
+                try {
+                    for (int i = 1; i <= 100; i++) {
+                        queue.put(i);
+                    }
+                } catch (InterruptedException e) {
+                }
+            }
+        };
+        producer.start();
+        int sum = 0;
+        for (int i = 0; i < 100; i++) {
+            sum += queue.take();
+        }
+        return sum;
+    }
+
+    static String notOwner() {
+        try {
+            new Object().notify();
+            return "no exception";
+        } catch (IllegalMonitorStateException e) {
+            return e.getClass().getName();
+        }
+    }
+
+    static String sleepers() throws InterruptedException {
+        StringBuilder out = new StringBuilder();
+        Thread c = new Locker(new Object(), new Object(), "c", out, 30);
+        Thread a = new Locker(new Object(), new Object(), "a", out, 10);
+        Thread b = new Locker(new Object(), new Object(), "b", out, 20);
+        c.start();
+        a.start();
+        b.start();
+        c.join();
+        return out.toString();
+    }
+
+    static boolean sleepAdvancesClock() throws InterruptedException {
+        long millis = System.currentTimeMillis();
+        long nanos = System.nanoTime();
+        Thread.sleep(50);
+        return System.currentTimeMillis() - millis >= 50 && System.nanoTime() - nanos >= 50_000_000;
+    }
+
+    static boolean joined() throws InterruptedException {
+        Thread thread = new Locker(new Object(), new Object(), "", new StringBuilder(), 5);
+        boolean before = thread.isAlive();
+        thread.start();
+        boolean started = thread.isAlive();
+        thread.join();
+        return !before && started && !thread.isAlive();
+    }
+
+    static void deadlock() throws InterruptedException {
+        Object first = new Object(), second = new Object();
+        StringBuilder out = new StringBuilder();
+        Thread a = new Locker(first, second, "a", out, 10);
+        Thread b = new Locker(second, first, "b", out, 10);
+        a.start();
+        b.start();
+        a.join();
+        b.join();
+    }
+
+    static boolean uncaught() throws InterruptedException {
+        Thread thread = new Thread(new Worker(null, null), "worker");
+        thread.start();
+        thread.join();
+        return !thread.isAlive();
+    }
+
+    static String interleaving() throws InterruptedException {
+        StringBuilder out = new StringBuilder();
+        Thread a = new Thread(new Worker(out, "a"));
+        Thread b = new Thread(new Worker(out, "b"));
+        Thread c = new Thread(new Worker(out, "c"));
+        a.start();
+        b.start();
+        c.start();
+        a.join();
+        b.join();
+        c.join();
+        return out.toString();
+    }
+
+    public static void main(String[] args) throws InterruptedException {
+        deadlock();
+    }
+}
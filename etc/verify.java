@@ -0,0 +1,40 @@
+public class verify {
+    int value;
+
+    verify(int value) {
+        this.value = value;
+    }
+
+    static int id(int x) {
+        return x;
+    }
+
+    static int sum(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            total += i;
+        }
+        return total;
+    }
+
+    static verify create(int value) {
+        return new verify(value);
+    }
+
+    static Object pick(boolean first, String a, Integer b) {
+        return first ? a : b;
+    }
+
+    static long wide(long a, double b) {
+        long[] values = { a, (long) b };
+        return values[0] + values[1];
+    }
+
+    static int guarded(int[] values) {
+        try {
+            return values[0];
+        } catch (RuntimeException e) {
+            return -1;
+        }
+    }
+}
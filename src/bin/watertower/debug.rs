@@ -0,0 +1,255 @@
+//! `watertower debug`: running a program one bytecode or source line at a time
+use std::io::{BufRead, Write};
+use std::net::TcpListener;
+
+use watertower::exec::debug::{Breakpoint, Debugger, Resume, Step, Stop, Unit};
+use watertower::exec::error::Error;
+use watertower::exec::interpreter::Interpreter;
+use watertower::exec::jdwp::Session;
+use watertower::exec::value::Value;
+
+use crate::report;
+
+const HELP: &str = "break <class>.<method>[@<pc>]  stop in a method, at its start or a pc
+break <class>:<line>            stop at a source line
+watch <class>.<field>           stop when a field is written
+awatch <class>.<field>          stop when a field is read or written
+delete <id>                     remove a breakpoint or watchpoint
+info                            list breakpoints and watchpoints
+step, s / stepi, si             step to the next line / instruction, into calls
+next, n / nexti, ni             step to the next line / instruction, over calls
+finish                          run until the method returns
+continue, c                     run until the next breakpoint
+where, bt                       print the call stack
+locals [<frame>]                print the local variables
+stack [<frame>]                 print the operand stack
+print <local>|@<object>, p      print a local by name or index, or an object's fields
+quit, q                         stop the program";
+
+/// Runs `main` of `class` under the debugger, reading commands from stdin
+pub fn run(mut vm: Interpreter, class: &str) -> i32 {
+    println!("type `help` for the commands");
+    vm.set_debugger(Some(Debugger::new(stopped)));
+    report(vm.run_main(class))
+}
+
+/// Waits for a JDWP client like `jdb -attach` to connect to `address`, and runs `main` of
+/// `class` for it to debug
+pub fn serve(mut vm: Interpreter, class: &str, address: &str) -> i32 {
+    let session = TcpListener::bind(address)
+        .map_err(Error::from)
+        .and_then(|listener| {
+            let address = listener.local_addr()?;
+            eprintln!("listening for a JDWP client on {}", address);
+            Session::accept(&listener)
+        })
+        .and_then(|session| session.attach(&mut vm).map(|_| session));
+    let session = match session {
+        Ok(session) => session,
+        Err(err) => {
+            eprintln!("{}: {}", address, err);
+            return 1;
+        }
+    };
+    let code = report(vm.run_main(class));
+    if let Err(err) = session.finish() {
+        eprintln!("{}", err);
+    }
+    code
+}
+
+/// Shows where the program stopped, and takes commands until one of them runs it again
+fn stopped(vm: &mut Interpreter, stop: &Stop) -> Result<Resume, Error> {
+    match stop {
+        Stop::Step | Stop::Pause => {}
+        Stop::Breakpoint(id) => print!("breakpoint {}, ", id),
+        Stop::Field { id, object, value } => {
+            print!("watchpoint {}", id);
+            if let Some(object) = object {
+                print!(" on {}", vm.describe(Value::Reference(*object)));
+            }
+            match value {
+                Some(value) => print!(", writing {}, ", vm.describe(*value)),
+                None => print!(", "),
+            }
+        }
+    }
+    if let Some(frame) = vm.call_stack(vm.running_thread()).first() {
+        println!("{}", frame);
+    }
+
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    loop {
+        print!("(wdb) ");
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(..) => return Ok(Resume::Quit),
+            Ok(..) => {}
+        }
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let resume = match words.as_slice() {
+            [] => continue,
+            ["continue"] | ["c"] => Resume::Continue,
+            ["step"] | ["s"] => Resume::Step(Step::Into(Unit::Line)),
+            ["stepi"] | ["si"] => Resume::Step(Step::Into(Unit::Instruction)),
+            ["next"] | ["n"] => Resume::Step(Step::Over(Unit::Line)),
+            ["nexti"] | ["ni"] => Resume::Step(Step::Over(Unit::Instruction)),
+            ["finish"] => Resume::Step(Step::Out),
+            ["quit"] | ["q"] => Resume::Quit,
+            words => {
+                if let Err(err) = command(vm, words) {
+                    println!("{}", err);
+                }
+                continue;
+            }
+        };
+        return Ok(resume);
+    }
+}
+
+/// Runs a command that doesn't resume the program
+fn command(vm: &mut Interpreter, words: &[&str]) -> Result<(), String> {
+    let thread = vm.running_thread();
+    let frame = |arg: Option<&&str>| match arg {
+        Some(arg) => arg.parse().map_err(|_| format!("not a frame: {}", arg)),
+        None => Ok(0),
+    };
+    match words {
+        ["help"] => println!("{}", HELP),
+        ["break", location] | ["b", location] => {
+            let breakpoint = parse_breakpoint(location)?;
+            add(vm, breakpoint)?
+        }
+        ["watch", field] | ["awatch", field] => {
+            let (class, field) = match field.rfind('.') {
+                Some(dot) => (&field[..dot], &field[dot + 1..]),
+                None => return Err(format!("expected <class>.<field>: {}", field)),
+            };
+            let breakpoint = Breakpoint::Field {
+                class: class.to_string(),
+                field: field.to_string(),
+                reads: words[0] == "awatch",
+            };
+            add(vm, breakpoint)?
+        }
+        ["delete", id] => {
+            let id = id.parse().map_err(|_| format!("not an id: {}", id))?;
+            let removed = debugger(vm)?.remove_breakpoint(id);
+            if removed.is_none() {
+                return Err(format!("no breakpoint {}", id));
+            }
+        }
+        ["info"] => {
+            for (id, breakpoint) in debugger(vm)?.breakpoints() {
+                println!("{}: {}", id, breakpoint);
+            }
+        }
+        ["where"] | ["bt"] => {
+            for (i, frame) in vm.call_stack(thread).iter().enumerate() {
+                println!("#{} {}", i, frame);
+            }
+        }
+        ["locals", rest @ ..] => {
+            let locals = vm
+                .locals(thread, frame(rest.first())?)
+                .ok_or("no such frame")?;
+            for local in locals {
+                let name = local.name.unwrap_or_default();
+                println!(
+                    "{:>3} {:<12} {}",
+                    local.index,
+                    name,
+                    vm.describe(local.value)
+                );
+            }
+        }
+        ["stack", rest @ ..] => {
+            let stack = vm
+                .operand_stack(thread, frame(rest.first())?)
+                .ok_or("no such frame")?;
+            for (i, &value) in stack.iter().enumerate().rev() {
+                println!("{:>3} {}", i, vm.describe(value));
+            }
+        }
+        ["print", what] | ["p", what] => print(vm, what)?,
+        _ => return Err(format!("unknown command: {}, see `help`", words.join(" "))),
+    }
+    Ok(())
+}
+
+fn debugger(vm: &mut Interpreter) -> Result<&mut Debugger, String> {
+    vm.debugger()
+        .ok_or_else(|| "the debugger isn't attached".to_string())
+}
+
+fn add(vm: &mut Interpreter, breakpoint: Breakpoint) -> Result<(), String> {
+    let description = breakpoint.to_string();
+    let id = debugger(vm)?.add_breakpoint(breakpoint);
+    println!("{}: {}", id, description);
+    Ok(())
+}
+
+/// `<class>:<line>`, or `<class>.<method>[@<pc>]` where the method can have a descriptor
+fn parse_breakpoint(location: &str) -> Result<Breakpoint, String> {
+    let invalid = || {
+        format!(
+            "expected <class>:<line> or <class>.<method>[@<pc>]: {}",
+            location
+        )
+    };
+    if let Some((class, line)) = split_last(location, ':') {
+        let line = line.parse().map_err(|_| invalid())?;
+        return Ok(Breakpoint::Line {
+            class: class.to_string(),
+            line,
+        });
+    }
+
+    let (location, pc) = match split_last(location, '@') {
+        Some((location, pc)) => (location, Some(pc.parse().map_err(|_| invalid())?)),
+        None => (location, None),
+    };
+    // descriptors have no dots, so the method starts after the last one before them
+    let name_end = location.find('(').unwrap_or(location.len());
+    let (class, method) = match location[..name_end].rfind('.') {
+        Some(dot) => (&location[..dot], &location[dot + 1..]),
+        None => return Err(invalid()),
+    };
+    Ok(Breakpoint::Method {
+        class: class.to_string(),
+        method: method.to_string(),
+        pc,
+    })
+}
+
+fn split_last(s: &str, c: char) -> Option<(&str, &str)> {
+    s.rfind(c).map(|i| (&s[..i], &s[i + 1..]))
+}
+
+/// Prints a local of the current frame, by name or index, or the fields of an object by
+/// its `@` reference
+fn print(vm: &mut Interpreter, what: &str) -> Result<(), String> {
+    if let Some(reference) = what.strip_prefix('@') {
+        let index = usize::from_str_radix(reference, 16).map_err(|_| "not a reference")?;
+        let object = vm
+            .heap()
+            .reference(index)
+            .ok_or_else(|| format!("no object @{}", reference))?;
+        let fields = vm.object_fields(object).map_err(|err| err.to_string())?;
+        println!("{}", vm.describe(Value::Reference(object)));
+        for (name, value) in fields {
+            println!("  {} = {}", name, vm.describe(value));
+        }
+        return Ok(());
+    }
+
+    let locals = vm.locals(vm.running_thread(), 0).ok_or("no frame")?;
+    let local = locals
+        .iter()
+        .find(|local| local.name.as_deref() == Some(what) || local.index.to_string() == what)
+        .ok_or_else(|| format!("no local {}", what))?;
+    println!("{} = {}", what, vm.describe(local.value));
+    Ok(())
+}
@@ -0,0 +1,375 @@
+use std::rc::Rc;
+
+use watertower::exec::error::Error;
+use watertower::exec::exception::JavaException;
+use watertower::exec::interpreter::Interpreter;
+use watertower::exec::profile::{Profiler, Weight};
+use watertower::exec::{asm, decompile, remap};
+use watertower::parse::types::ClassFile;
+
+mod debug;
+
+const USAGE: &str = "usage: watertower decompile <file.class>... [--method <name>]
+       watertower disassemble <file.class>...
+       watertower assemble <file.j> [-o <file.class>]
+       watertower remap <mappings> <file.class>... -o <dir> [--reverse]
+       watertower run <file.class>... [--main <class>] [--profile] [--flamegraph <file>] [--jit]
+       watertower debug <file.class>... [--main <class>] [--jdwp <address>]";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let code = match args.first().map(String::as_str) {
+        Some("decompile") => decompile(&args[1..]),
+        Some("disassemble") => disassemble(&args[1..]),
+        Some("assemble") => assemble(&args[1..]),
+        Some("remap") => remap(&args[1..]),
+        Some("run") => run(&args[1..]),
+        Some("debug") => debug(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    };
+    std::process::exit(code)
+}
+
+fn read_class(path: &str) -> Result<ClassFile, String> {
+    let data = std::fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+    ClassFile::read(&mut data.as_slice()).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn disassemble(files: &[String]) -> i32 {
+    if files.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+    let mut code = 0;
+    for path in files {
+        match read_class(path)
+            .and_then(|file| asm::disassemble(&file).map_err(|err| format!("{}: {}", path, err)))
+        {
+            Ok(out) => print!("{}", out),
+            Err(err) => {
+                eprintln!("{}", err);
+                code = 1;
+            }
+        }
+    }
+    code
+}
+
+fn assemble(args: &[String]) -> i32 {
+    let (path, out) = match args {
+        [path] => (path, None),
+        [path, flag, out] if flag == "-o" => (path, Some(out)),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let file = std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| asm::assemble(&source).map_err(|err| err.to_string()));
+    let file = match file {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return 1;
+        }
+    };
+
+    // next to the source, named after the class, unless it is given
+    let out = match out {
+        Some(out) => std::path::PathBuf::from(out),
+        None => std::path::Path::new(path)
+            .with_file_name(file.get_class_name().rsplit('/').next().unwrap_or_default())
+            .with_extension("class"),
+    };
+    let mut data = vec![];
+    let written = file
+        .write(&mut data)
+        .map_err(|err| err.to_string())
+        .and_then(|_| std::fs::write(&out, data).map_err(|err| err.to_string()));
+    match written {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}: {}", out.display(), err);
+            1
+        }
+    }
+}
+
+/// Remaps the classes together, and writes them under `dir` by their new names
+fn remap(args: &[String]) -> i32 {
+    let (mut files, mut dir, mut reverse) = (vec![], None, false);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => dir = args.next(),
+            "--reverse" => reverse = true,
+            file => files.push(file),
+        }
+    }
+    let (mappings, dir) = match (files.first(), dir) {
+        (Some(mappings), Some(dir)) if files.len() > 1 => (*mappings, dir),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    let parsed = std::fs::read_to_string(mappings)
+        .map_err(|err| err.to_string())
+        .and_then(|text| remap::Mappings::parse(&text).map_err(|err| err.to_string()));
+    let mappings = match parsed {
+        Ok(parsed) if reverse => parsed.reverse(),
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}: {}", mappings, err);
+            return 1;
+        }
+    };
+    let mut classes = vec![];
+    for path in &files[1..] {
+        match read_class(path) {
+            Ok(file) => classes.push(file),
+            Err(err) => {
+                eprintln!("{}", err);
+                return 1;
+            }
+        }
+    }
+    if let Err(err) = remap::remap(&mut classes, &mappings) {
+        eprintln!("{}", err);
+        return 1;
+    }
+
+    let mut code = 0;
+    for file in classes {
+        let out = std::path::Path::new(dir).join(format!("{}.class", file.get_class_name()));
+        let mut data = vec![];
+        let written = file
+            .write(&mut data)
+            .map_err(|err| err.to_string())
+            .and_then(|_| match out.parent() {
+                Some(parent) => std::fs::create_dir_all(parent).map_err(|err| err.to_string()),
+                None => Ok(()),
+            })
+            .and_then(|_| std::fs::write(&out, data).map_err(|err| err.to_string()));
+        if let Err(err) = written {
+            eprintln!("{}: {}", out.display(), err);
+            code = 1;
+        }
+    }
+    code
+}
+
+/// Loads the classes and runs `main` of the first one, or the `--main` one. `--profile`
+/// prints where the time went once it exits, and `--flamegraph` writes the call stacks it
+/// ran in for flame graph tools. `--jit` compiles hot methods, in builds with the `jit`
+/// feature
+fn run(args: &[String]) -> i32 {
+    let (mut files, mut main, mut profile, mut flamegraph) = (vec![], None, false, None);
+    let mut jit = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--main" => &mut main,
+            "--flamegraph" => &mut flamegraph,
+            "--profile" => {
+                profile = true;
+                continue;
+            }
+            "--jit" => {
+                jit = true;
+                continue;
+            }
+            file => {
+                files.push(file);
+                continue;
+            }
+        };
+        match args.next() {
+            Some(arg) => *value = Some(arg.clone()),
+            None => {
+                eprintln!("{}", USAGE);
+                return 2;
+            }
+        }
+    }
+    let (mut vm, main) = match load(&files, main) {
+        Ok(loaded) => loaded,
+        Err(code) => return code,
+    };
+
+    if profile || flamegraph.is_some() {
+        vm.set_profiler(Some(Profiler::new()));
+    }
+    if jit {
+        if let Err(code) = enable_jit(&mut vm) {
+            return code;
+        }
+    }
+    let mut code = report(vm.run_main(&main));
+    let profiler = match vm.profiler() {
+        Some(profiler) => profiler,
+        None => return code,
+    };
+    if profile {
+        eprint!("{}", profiler.report());
+    }
+    if let Some(path) = flamegraph {
+        if let Err(err) = std::fs::write(&path, profiler.collapsed(Weight::Time)) {
+            eprintln!("{}: {}", path, err);
+            code = 1;
+        }
+    }
+    code
+}
+
+#[cfg(feature = "jit")]
+fn enable_jit(vm: &mut Interpreter) -> Result<(), i32> {
+    match watertower::exec::jit::Jit::new() {
+        Ok(jit) => {
+            vm.set_jit(Some(jit));
+            Ok(())
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            Err(1)
+        }
+    }
+}
+
+#[cfg(not(feature = "jit"))]
+fn enable_jit(_: &mut Interpreter) -> Result<(), i32> {
+    eprintln!("--jit needs watertower to be built with the `jit` feature");
+    Err(2)
+}
+
+/// Loads the classes and runs `main` of the first one, or the `--main` one, in the debugger,
+/// or for a JDWP client to debug once it connects to `--jdwp`
+fn debug(args: &[String]) -> i32 {
+    let (mut files, mut main, mut jdwp) = (vec![], None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--main" => &mut main,
+            "--jdwp" => &mut jdwp,
+            file => {
+                files.push(file);
+                continue;
+            }
+        };
+        match args.next() {
+            Some(arg) => *value = Some(arg.clone()),
+            None => {
+                eprintln!("{}", USAGE);
+                return 2;
+            }
+        }
+    }
+    let (vm, main) = match load(&files, main) {
+        Ok(loaded) => loaded,
+        Err(code) => return code,
+    };
+    match jdwp {
+        Some(address) => debug::serve(vm, &main, &address),
+        None => debug::run(vm, &main),
+    }
+}
+
+/// An interpreter with the classes loaded, and the internal name of the main class: `main`,
+/// or else the first class. Fails with the exit code
+fn load(files: &[&str], main: Option<String>) -> Result<(Interpreter, String), i32> {
+    let mut main = main.map(|class| class.replace('.', "/"));
+    if files.is_empty() {
+        eprintln!("{}", USAGE);
+        return Err(2);
+    }
+
+    let mut vm = Interpreter::default();
+    for path in files {
+        match read_class(path) {
+            Ok(file) => {
+                if main.is_none() {
+                    main = Some(file.get_class_name().to_string());
+                }
+                vm.load_class(Rc::new(file));
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                return Err(1);
+            }
+        }
+    }
+    Ok((vm, main.unwrap_or_default()))
+}
+
+/// The exit code of the program, after printing how it failed
+fn report(result: Result<Result<(), JavaException>, Error>) -> i32 {
+    match result {
+        Ok(Ok(())) => 0,
+        Ok(Err(exception)) => {
+            eprintln!("Exception in thread \"main\" {}", exception);
+            1
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+fn decompile(args: &[String]) -> i32 {
+    let mut files = vec![];
+    let mut method = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--method" => match args.next() {
+                Some(name) => method = Some(name.as_str()),
+                None => {
+                    eprintln!("{}", USAGE);
+                    return 2;
+                }
+            },
+            file => files.push(file),
+        }
+    }
+    if files.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+
+    let mut code = 0;
+    for path in files {
+        let file = match read_class(path) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("{}", err);
+                code = 1;
+                continue;
+            }
+        };
+
+        let out = match method {
+            None => decompile::decompile_class(&file),
+            Some(name) => {
+                let methods = file.methods.iter().filter(|m| m.name() == name);
+                methods
+                    .map(|m| decompile::decompile_method(&file, m))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|methods| methods.join("\n"))
+            }
+        };
+        match out {
+            Ok(out) => print!("{}", out),
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                code = 1;
+            }
+        }
+    }
+    code
+}
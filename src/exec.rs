@@ -3,10 +3,29 @@
 #[macro_use]
 pub mod error;
 
+pub mod asm;
 pub mod cache;
+pub mod cfg;
+pub mod class;
+pub mod convert;
+pub mod debug;
+pub mod decompile;
+pub mod exception;
+pub mod ffi;
+pub mod handle;
+pub mod heap;
 pub mod instructions;
 pub mod interpreter;
+pub mod jdwp;
+#[cfg(feature = "jit")]
+pub mod jit;
+pub mod native;
+pub mod profile;
+pub mod remap;
+pub mod stream;
+pub mod trace;
 pub mod value;
+pub mod verify;
 
 use crate::parse::types as ty;
 use crate::parse::types::attribute as attr;
@@ -0,0 +1,591 @@
+//! A textual assembly language for class files, in the style of Jasmin and Krakatau
+//!
+//! ```text
+//! .version 52 0
+//! .class public super Hello
+//! .super java/lang/Object
+//!
+//! .method public static main ([Ljava/lang/String;)V
+//!     .limit stack 2
+//!     .limit locals 1
+//!     getstatic Field java/lang/System out Ljava/io/PrintStream;
+//!     ldc "hello"
+//!     invokevirtual Method java/io/PrintStream println (Ljava/lang/String;)V
+//!     return
+//! .end method
+//! ```
+//!
+//! Instructions use the mnemonics of the `instruction!` table in lower case, branches
+//! jump to labels, and constants are written as literals: `10`, `10L`, `1.5f`, `1.5d`,
+//! `"text"`, `Class java/lang/Object`, `Method java/lang/Object <init> ()V`, and so on.
+//! The bootstrap methods that `InvokeDynamic` constants refer to by index are written as
+//! `.bootstrap <index> <method handle> <arguments>...`, with constants in the same way.
+//! `disassemble` writes the same format, so a class file can round-trip through text.
+//! Attributes it doesn't know are written as `.attribute <name> <bytes in hex>`, or
+//! `.codeattribute` in code, and are assembled into the same bytes. Constant indices in
+//! them aren't renumbered along with the constant pool.
+//!
+//! `ClassBuilder` makes class files from Rust instead, with the same mnemonics.
+use super::*;
+
+mod assemble;
+mod builder;
+mod disassemble;
+mod lexer;
+mod pool;
+
+pub use assemble::assemble;
+pub use builder::{ClassBuilder, Label, Literal, MethodBuilder};
+pub use disassemble::{disassemble, disassemble_instruction};
+pub use pool::ConstantPool;
+
+use lexer::quote;
+
+use std::borrow::Borrow;
+use std::collections::hash_map::{Entry, HashMap};
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::sync::OnceLock;
+
+/// The kind of operands an instruction takes, by opcode
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Shape {
+    None,
+    /// `bipush`
+    Byte,
+    /// `sipush`
+    Short,
+    /// `ldc`
+    Constant,
+    /// `ldc_w` and `ldc2_w`
+    WideConstant,
+    /// Loads, stores and `ret`
+    Local,
+    Iinc,
+    Branch,
+    BranchWide,
+    /// The field and method instructions, except for `invokeinterface`
+    Member,
+    Interface,
+    Dynamic,
+    Class,
+    NewArray,
+    MultiArray,
+    TableSwitch,
+    LookupSwitch,
+    Wide,
+}
+
+impl Shape {
+    fn of(opcode: u8) -> Self {
+        match opcode {
+            0x10 => Shape::Byte,
+            0x11 => Shape::Short,
+            0x12 => Shape::Constant,
+            0x13 | 0x14 => Shape::WideConstant,
+            0x15..=0x19 | 0x36..=0x3A | 0xA9 => Shape::Local,
+            0x84 => Shape::Iinc,
+            0x99..=0xA8 | 0xC6 | 0xC7 => Shape::Branch,
+            0xC8 | 0xC9 => Shape::BranchWide,
+            0xAA => Shape::TableSwitch,
+            0xAB => Shape::LookupSwitch,
+            0xB2..=0xB8 => Shape::Member,
+            0xB9 => Shape::Interface,
+            0xBA => Shape::Dynamic,
+            0xBB | 0xBD | 0xC0 | 0xC1 => Shape::Class,
+            0xBC => Shape::NewArray,
+            0xC4 => Shape::Wide,
+            0xC5 => Shape::MultiArray,
+            _ => Shape::None,
+        }
+    }
+}
+
+/// The mnemonic of `opcode`, as the assembler reads it
+fn mnemonic(opcode: u8) -> Option<String> {
+    Instruction::lookup(opcode).map(|instruction| instruction.to_string().to_lowercase())
+}
+
+/// The opcode of `mnemonic`
+fn opcode(mnemonic: &str) -> Option<u8> {
+    static OPCODES: OnceLock<HashMap<String, u8>> = OnceLock::new();
+    let opcodes = OPCODES.get_or_init(|| {
+        (0..=255u8)
+            .filter_map(|opcode| Some((self::mnemonic(opcode)?, opcode)))
+            .collect()
+    });
+    opcodes.get(mnemonic).copied()
+}
+
+/// The labels of code as it is written, and the branches to them, whose offsets are
+/// filled in by `resolve` once every label is placed. Each branch is from an `S`, which
+/// errors point to
+#[derive(Debug)]
+struct Labels<L, S> {
+    pcs: HashMap<L, usize>,
+    /// Where the branch is from, the pc of the instruction, where the offset goes, the
+    /// label and whether the offset takes four bytes
+    branches: Vec<(S, usize, usize, L, bool)>,
+}
+
+/// Why a label has no pc that can be used
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Unresolved {
+    /// It isn't placed
+    Missing,
+    /// It is too far to branch to
+    TooFar,
+    /// Its pc doesn't fit in two bytes
+    TooLong,
+}
+
+impl<L, S> Default for Labels<L, S> {
+    fn default() -> Self {
+        Self {
+            pcs: HashMap::new(),
+            branches: vec![],
+        }
+    }
+}
+
+impl<L: Eq + Hash, S> Labels<L, S> {
+    /// Places `label` at `pc`, unless it has been placed already
+    fn place(&mut self, label: L, pc: usize) -> bool {
+        match self.pcs.entry(label) {
+            Entry::Occupied(..) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(pc);
+                true
+            }
+        }
+    }
+
+    /// Leaves room at the end of `code` for the offset from `pc` to `label`
+    fn branch(&mut self, code: &mut Vec<u8>, from: S, pc: usize, label: L, wide: bool) {
+        self.branches.push((from, pc, code.len(), label, wide));
+        let len = if wide { 4 } else { 2 };
+        code.extend(std::iter::repeat_n(0, len));
+    }
+
+    fn pc<Q: Eq + Hash + ?Sized>(&self, label: &Q) -> std::result::Result<usize, Unresolved>
+    where
+        L: Borrow<Q>,
+    {
+        self.pcs.get(label).copied().ok_or(Unresolved::Missing)
+    }
+
+    fn pc_u16<Q: Eq + Hash + ?Sized>(&self, label: &Q) -> std::result::Result<u16, Unresolved>
+    where
+        L: Borrow<Q>,
+    {
+        u16::try_from(self.pc(label)?).map_err(|_| Unresolved::TooLong)
+    }
+
+    /// Fills in the offsets of the branches in `code`, or the first branch that can't be
+    fn resolve(&mut self, code: &mut [u8]) -> std::result::Result<(), (S, L, Unresolved)> {
+        for (from, pc, at, label, wide) in std::mem::take(&mut self.branches) {
+            let offset = match self.pc(&label) {
+                Ok(target) => target as i64 - pc as i64,
+                Err(err) => return Err((from, label, err)),
+            };
+            if wide {
+                code[at..at + 4].copy_from_slice(&(offset as i32).to_be_bytes());
+            } else {
+                match i16::try_from(offset) {
+                    Ok(offset) => code[at..at + 2].copy_from_slice(&offset.to_be_bytes()),
+                    Err(..) => return Err((from, label, Unresolved::TooFar)),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pads a switch to the next multiple of four
+fn pad(code: &mut Vec<u8>) {
+    while !code.len().is_multiple_of(4) {
+        code.push(0);
+    }
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-6.html#jvms-6.5.newarray
+const ARRAY_TYPES: [(&str, u8); 8] = [
+    ("boolean", 4),
+    ("char", 5),
+    ("float", 6),
+    ("double", 7),
+    ("byte", 8),
+    ("short", 9),
+    ("int", 10),
+    ("long", 11),
+];
+
+const METHOD_HANDLE_KINDS: [&str; 9] = [
+    "getField",
+    "getStatic",
+    "putField",
+    "putStatic",
+    "invokeVirtual",
+    "invokeStatic",
+    "invokeSpecial",
+    "newInvokeSpecial",
+    "invokeInterface",
+];
+
+const CLASS_FLAGS: [(&str, u16); 8] = [
+    ("public", 0x0001),
+    ("final", 0x0010),
+    ("super", 0x0020),
+    ("interface", 0x0200),
+    ("abstract", 0x0400),
+    ("synthetic", 0x1000),
+    ("annotation", 0x2000),
+    ("enum", 0x4000),
+];
+
+const INNER_CLASS_FLAGS: [(&str, u16); 10] = [
+    ("public", 0x0001),
+    ("private", 0x0002),
+    ("protected", 0x0004),
+    ("static", 0x0008),
+    ("final", 0x0010),
+    ("interface", 0x0200),
+    ("abstract", 0x0400),
+    ("synthetic", 0x1000),
+    ("annotation", 0x2000),
+    ("enum", 0x4000),
+];
+
+const FIELD_FLAGS: [(&str, u16); 9] = [
+    ("public", 0x0001),
+    ("private", 0x0002),
+    ("protected", 0x0004),
+    ("static", 0x0008),
+    ("final", 0x0010),
+    ("volatile", 0x0040),
+    ("transient", 0x0080),
+    ("synthetic", 0x1000),
+    ("enum", 0x4000),
+];
+
+const METHOD_FLAGS: [(&str, u16); 12] = [
+    ("public", 0x0001),
+    ("private", 0x0002),
+    ("protected", 0x0004),
+    ("static", 0x0008),
+    ("final", 0x0010),
+    ("synchronized", 0x0020),
+    ("bridge", 0x0040),
+    ("varargs", 0x0080),
+    ("native", 0x0100),
+    ("abstract", 0x0400),
+    ("strict", 0x0800),
+    ("synthetic", 0x1000),
+];
+
+const PARAMETER_FLAGS: [(&str, u16); 3] = [
+    ("final", 0x0010),
+    ("synthetic", 0x1000),
+    ("mandated", 0x8000),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exec::interpreter::Interpreter;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    fn read(name: &str) -> ty::ClassFile {
+        let data = std::fs::read(format!("./etc/{}.class", name)).unwrap();
+        ty::ClassFile::read(&mut data.as_slice()).unwrap()
+    }
+
+    /// Assembles the text and reads the class back from its bytes
+    fn round_trip(text: &str) -> ty::ClassFile {
+        let file = assemble(text).unwrap();
+        let mut data = vec![];
+        file.write(&mut data).unwrap();
+        ty::ClassFile::read(&mut data.as_slice()).unwrap()
+    }
+
+    /// The class files in `dir` and the directories in it
+    fn class_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(class_files(&path));
+            } else if path.extension().is_some_and(|ext| ext == "class") {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    /// Disassembles the class, and disassembles it again after assembling the text
+    fn text_round_trip(file: &ty::ClassFile) -> Result<(String, String)> {
+        let text = disassemble(file)?;
+        let mut data = vec![];
+        assemble(&text)?.write(&mut data)?;
+        let again = disassemble(&ty::ClassFile::read(&mut data.as_slice())?)?;
+        Ok((text, again))
+    }
+
+    #[test]
+    fn fixtures_round_trip() {
+        for path in class_files(Path::new("./etc")) {
+            let data = std::fs::read(&path).unwrap();
+            let file = ty::ClassFile::read(&mut data.as_slice()).unwrap();
+
+            let (text, again) = text_round_trip(&file).unwrap();
+            assert!(text == again, "{} changed:\n{}", path.display(), again);
+        }
+    }
+
+    #[test]
+    fn unknown_attributes() {
+        let data = std::fs::read("./etc/nest/Nest.class").unwrap();
+        let nest = ty::ClassFile::read(&mut data.as_slice()).unwrap();
+        let mut written = vec![];
+        nest.write(&mut written).unwrap();
+        assert_eq!(written, data);
+
+        // `NestMembers` lists `Nest$Counter`, by its constant index
+        let text = disassemble(&nest).unwrap();
+        assert!(text.contains("\n.attribute NestMembers 0001"), "{}", text);
+        let counter = read("nest/Nest$Counter");
+        let text = disassemble(&counter).unwrap();
+        assert!(text.contains("\n.attribute NestHost "), "{}", text);
+
+        let file = round_trip(".class Tagged\n.attribute Tag 00ff\n.attribute \"Two words\"\n");
+        match &file.attributes[..] {
+            [attr::Attribute::Unknown(tag), attr::Attribute::Unknown(empty)] => {
+                assert_eq!(file.utf8(tag.attribute_name).unwrap(), "Tag");
+                assert_eq!(tag.info, [0x00, 0xff]);
+                assert_eq!(file.utf8(empty.attribute_name).unwrap(), "Two words");
+                assert!(empty.info.is_empty());
+            }
+            attributes => panic!("{:?}", attributes),
+        }
+        let error = assemble(".class Tagged\n.attribute Tag 0f0\n")
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "line 2: expected bytes in hex, got: 0f0");
+
+        let mut vm = Interpreter::default();
+        vm.load_class(Rc::new(nest));
+        vm.load_class(Rc::new(counter));
+        assert_eq!(vm.invoke::<_, i32>("Nest", "count", (3,)).unwrap(), 3);
+    }
+
+    /// The classes of a JDK, as `jimage extract` writes them to the directory in
+    /// `JDK_CLASSES`. Strings in modified UTF-8, with `C0 80` for NUL or surrogate pairs,
+    /// can't be read yet, so the classes with those are the only ones left out, along with
+    /// the `module-info` of the module, whose constants aren't read
+    #[test]
+    #[ignore = "needs JDK_CLASSES"]
+    fn jdk_round_trip() {
+        let dir = std::env::var_os("JDK_CLASSES").expect("JDK_CLASSES isn't set");
+        let (mut failed, mut modified_utf8, mut read) = (vec![], 0, 0);
+        for path in class_files(&PathBuf::from(dir)) {
+            if path.ends_with("module-info.class") {
+                continue;
+            }
+            let data = std::fs::read(&path).unwrap();
+            let file = match ty::ClassFile::read(&mut data.as_slice()) {
+                Ok(file) => file,
+                Err(crate::parse::Error::InvalidString { .. }) => {
+                    modified_utf8 += 1;
+                    continue;
+                }
+                Err(err) => {
+                    failed.push(format!("{}: {}", path.display(), err));
+                    continue;
+                }
+            };
+            read += 1;
+            match text_round_trip(&file) {
+                Ok((text, again)) if text == again => {}
+                Ok(..) => failed.push(format!("{}: changed", path.display())),
+                Err(err) => failed.push(format!("{}: {}", path.display(), err)),
+            }
+        }
+        assert!(failed.is_empty(), "{:#?}", failed);
+        assert!(
+            read > modified_utf8,
+            "{} read, {} left out",
+            read,
+            modified_utf8
+        );
+    }
+
+    #[test]
+    fn hand_written() {
+        let text = r#"
+            .version 52 0
+            .class public super Counter
+            .super java/lang/Object
+
+            .field private static final START J = 5L
+
+            ; counts down from n, and adds up what it counted
+            .method public static count (I)I
+                .limit stack 2
+                .limit locals 2
+                iconst_0
+                istore_1
+            Loop:
+                .stack append Integer
+                iload_0
+                ifle Done
+                iload_1
+                iload_0
+                iadd
+                istore_1
+                iinc 0 -1
+                goto Loop
+            Done:
+                .stack same
+                iload_1
+                ireturn
+            .end method
+
+            .method public static pick (I)Ljava/lang/String;
+                .limit stack 1
+                .limit locals 1
+                iload_0
+                tableswitch 1
+                    One
+                    Two
+                    default: Other
+            One:
+                .stack same
+                ldc "one"
+                areturn
+            Two:
+                .stack same
+                ldc "two"
+                areturn
+            Other:
+                .stack same
+                ldc "many\n"
+                areturn
+            .end method
+        "#;
+        let file = round_trip(text);
+        assert_eq!(file.get_class_name(), "Counter");
+        assert_eq!(file.methods.len(), 2);
+        let start = &file.fields[0];
+        assert_eq!(file.utf8(start.descriptor).unwrap(), "J");
+        match &start.attributes[..] {
+            [attr::Attribute::ConstantValue(value)] => {
+                let constant = value.constant_value.lookup(&file.constant_pool).unwrap();
+                assert_eq!(*constant, ty::Constant::Long(5));
+            }
+            attributes => panic!("{:?}", attributes),
+        }
+
+        let mut interpreter = Interpreter::default();
+        interpreter.load_class(Rc::new(file));
+        let sum: i32 = interpreter.invoke("Counter", "count", (4,)).unwrap();
+        assert_eq!(sum, 10);
+    }
+
+    #[test]
+    fn computed_limits() {
+        let text = r#"
+            .class Square
+            .method static square (J)J
+                lload_0
+                lload_0
+                lmul
+                lreturn
+            .end method
+        "#;
+        let mut file = assemble(text).unwrap();
+        assert_eq!(file.major_version, 49);
+        assert_eq!(file.super_class_name().unwrap(), Some("java/lang/Object"));
+        let code = file.methods[0].get_code().unwrap();
+        assert_eq!((code.max_stack, code.max_locals), (0, 2));
+
+        let mut interpreter = Interpreter::default();
+        verify::compute_frames(&mut file, &mut interpreter).unwrap();
+        let code = file.methods[0].get_code().unwrap();
+        assert_eq!((code.max_stack, code.max_locals), (4, 2));
+
+        interpreter.load_class(Rc::new(file));
+        let square: i64 = interpreter.invoke("Square", "square", (-3i64,)).unwrap();
+        assert_eq!(square, 9);
+    }
+
+    #[test]
+    fn disassembled() {
+        let text = disassemble(&read("cfg")).unwrap();
+        let expected = "
+.method static classify (I)I
+    .limit stack 1
+    .limit locals 1
+    .line 3
+    iload_0
+    tableswitch 1
+        L28
+        L31
+        L31
+        default: L34
+L28:
+    .line 5
+    .stack same
+    bipush 10
+    ireturn
+L31:
+    .line 8
+    .stack same
+    bipush 20
+    ireturn
+L34:
+    .line 10
+    .stack same
+    iconst_0
+    ireturn
+.end method
+";
+        assert!(text.contains(expected), "{}", text);
+        assert!(text.contains("    lookupswitch\n        1: L28\n        1000: L30\n"));
+        assert!(text.contains(
+            "    .catch java/lang/ArrayIndexOutOfBoundsException from L0 to L3 using L4\n"
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text: &str| assemble(text).err().unwrap().to_string();
+        let method = |code: &str| {
+            format!(
+                ".class Broken\n.method static broken ()V\n.limit stack 1\n{}\n.end method\n",
+                code
+            )
+        };
+        assert_eq!(
+            error(&method("frobnicate")),
+            "line 4: unknown instruction: frobnicate"
+        );
+        assert_eq!(
+            error(&method("goto Nowhere")),
+            "line 4: unknown label: Nowhere"
+        );
+        assert_eq!(
+            error(&method("bipush 1000")),
+            "line 4: expected a byte, got: 1000"
+        );
+        assert_eq!(
+            error(&method("ldc 1.5d")),
+            "line 4: ldc can't load a long or a double, use ldc2_w"
+        );
+        assert_eq!(
+            error(".method static m ()V"),
+            "line 1: expected .class first"
+        );
+    }
+}
@@ -0,0 +1,1503 @@
+//! Turning the text into a class file
+use super::lexer::{lex, Line, Token};
+use super::*;
+
+use attr::{Attribute, ElementValue, StackMapFrame, VerificationType};
+use std::convert::TryFrom;
+use std::iter::Peekable;
+use ty::{Constant, ConstantIndex};
+
+/// Fails with the line the error was found on
+macro_rules! fail {
+    ($line:expr, $msg:literal $(, $args:expr)* $(,)?) => {
+        generic_error!("line {}: {}", $line, format_args!($msg $(, $args)*))
+    };
+}
+
+type Lines<'a> = Peekable<std::slice::Iter<'a, Line>>;
+
+/// Assembles the text into a class file. Without a `.version` the class file is made
+/// for version 49, where the verifier infers the types instead of needing `.stack`
+/// frames. A method without `.limit`s gets room for just its parameters, which
+/// `verify::compute_frames` can then work out properly
+pub fn assemble(source: &str) -> Result<ty::ClassFile> {
+    let lines = lex(source)?;
+    let assembler = Assembler::default().run(&lines)?;
+    if !assembler.widened {
+        return assembler.finish();
+    }
+
+    // the constants of `ldc`s go first when they don't all fit in its one byte index
+    let mut again = Assembler::default();
+    for line in &assembler.loaded {
+        again.constant(&mut Words::new(line))?;
+    }
+    again.run(&lines)?.finish()
+}
+
+/// The words of a line, read from left to right
+struct Words<'a> {
+    line: usize,
+    tokens: &'a [Token],
+}
+
+impl<'a> Words<'a> {
+    fn new(line: &'a Line) -> Self {
+        Self {
+            line: line.number,
+            tokens: &line.tokens,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        match self.tokens.first() {
+            Some(Token::Word(word)) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn token(&mut self) -> Result<&'a Token> {
+        match self.tokens.split_first() {
+            Some((token, rest)) => {
+                self.tokens = rest;
+                Ok(token)
+            }
+            None => fail!(self.line, "unexpected end of line"),
+        }
+    }
+
+    fn word(&mut self) -> Result<&'a str> {
+        match self.token()? {
+            Token::Word(word) => Ok(word),
+            token => fail!(self.line, "expected a word, got: {}", token),
+        }
+    }
+
+    /// A word, or a string for a name that would be read as a flag otherwise
+    fn name(&mut self) -> Result<&'a str> {
+        match self.token()? {
+            Token::Word(word) | Token::Str(word) => Ok(word),
+        }
+    }
+
+    fn string(&mut self) -> Result<&'a str> {
+        match self.token()? {
+            Token::Str(s) => Ok(s),
+            token => fail!(self.line, "expected a string, got: {}", token),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, what: &str) -> Result<T> {
+        let word = self.word()?;
+        match word.parse() {
+            Ok(n) => Ok(n),
+            Err(..) => fail!(self.line, "expected {}, got: {}", what, word),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<()> {
+        match self.word()? {
+            word if word == keyword => Ok(()),
+            word => fail!(self.line, "expected {}, got: {}", keyword, word),
+        }
+    }
+
+    /// Flags, for as long as the words are in `table`
+    fn flags(&mut self, table: &[(&str, u16)]) -> u16 {
+        let mut flags = 0;
+        while let Some(&(_, flag)) = self
+            .peek()
+            .and_then(|word| table.iter().find(|(name, _)| *name == word))
+        {
+            flags |= flag;
+            self.tokens = &self.tokens[1..];
+        }
+        flags
+    }
+
+    /// `visible` or `invisible`, for annotations
+    fn visible(&mut self) -> Result<bool> {
+        match self.word()? {
+            "visible" => Ok(true),
+            "invisible" => Ok(false),
+            word => fail!(self.line, "expected visible or invisible, got: {}", word),
+        }
+    }
+
+    fn end(&self) -> Result<()> {
+        match self.tokens.first() {
+            Some(token) => fail!(self.line, "unexpected: {}", token),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Assembler {
+    constant_pool: ConstantPool,
+    version: Option<(u16, u16)>,
+    flags: u16,
+    this_class: Option<ConstantIndex>,
+    super_class: Option<ConstantIndex>,
+    interfaces: Vec<ConstantIndex>,
+    fields: Vec<ty::Field>,
+    methods: Vec<ty::Method>,
+    attributes: Vec<Attribute>,
+    inner_classes: Vec<ty::InnerClassInfo>,
+    bootstrap_methods: Vec<attr::BootstrapMethod>,
+    /// The operands of each `ldc`
+    loaded: Vec<Line>,
+    /// Whether an `ldc` was made into an `ldc_w`
+    widened: bool,
+}
+
+impl Assembler {
+    fn run(mut self, lines: &[Line]) -> Result<Self> {
+        let mut lines = lines.iter().peekable();
+        while let Some(line) = lines.next() {
+            self.directive(line, &mut lines)?;
+        }
+        Ok(self)
+    }
+
+    fn finish(mut self) -> Result<ty::ClassFile> {
+        let this_class = match self.this_class {
+            Some(this_class) => this_class,
+            None => generic_error!("missing .class"),
+        };
+        // only java/lang/Object has no super class
+        let super_class = match self.super_class {
+            Some(super_class) => super_class,
+            None if self.class_name(this_class) == "java/lang/Object" => ConstantIndex(0),
+            None => self.class("java/lang/Object")?,
+        };
+        if !self.inner_classes.is_empty() {
+            let attribute_name = self.utf8("InnerClasses")?;
+            self.attributes
+                .push(Attribute::InnerClasses(attr::InnerClasses {
+                    attribute_name,
+                    classes: std::mem::take(&mut self.inner_classes),
+                }));
+        }
+        if !self.bootstrap_methods.is_empty() {
+            let attribute_name = self.utf8("BootstrapMethods")?;
+            self.attributes
+                .push(Attribute::BootstrapMethods(attr::BootstrapMethods {
+                    attribute_name,
+                    methods: std::mem::take(&mut self.bootstrap_methods),
+                }));
+        }
+        let (major_version, minor_version) = self.version.unwrap_or((49, 0));
+        Ok(ty::ClassFile {
+            minor_version,
+            major_version,
+            constant_pool: self.constant_pool.into_vec(),
+            flags: ty::ClassFlags::from_bits_truncate(self.flags),
+            this_class,
+            super_class,
+            interfaces: self.interfaces,
+            fields: self.fields,
+            methods: self.methods,
+            attributes: self.attributes,
+        })
+    }
+
+    fn directive(&mut self, line: &Line, lines: &mut Lines<'_>) -> Result<()> {
+        let mut words = Words::new(line);
+        let directive = words.word()?;
+        if self.this_class.is_none() && directive != ".version" && directive != ".class" {
+            fail!(line.number, "expected .class first");
+        }
+
+        match directive {
+            ".version" => {
+                let major = words.number("a major version")?;
+                let minor = words.number("a minor version")?;
+                self.version = Some((major, minor));
+            }
+            ".class" => {
+                if self.this_class.is_some() {
+                    fail!(line.number, "there can only be one .class");
+                }
+                self.flags = words.flags(&CLASS_FLAGS);
+                self.this_class = Some(self.class(words.name()?)?);
+            }
+            ".super" => self.super_class = Some(self.class(words.word()?)?),
+            ".implements" => {
+                let interface = self.class(words.word()?)?;
+                self.interfaces.push(interface);
+            }
+            ".source" => {
+                let attribute_name = self.utf8("SourceFile")?;
+                let source_file = self.utf8(words.string()?)?;
+                self.attributes
+                    .push(Attribute::SourceFile(attr::SourceFile {
+                        attribute_name,
+                        source_file,
+                    }));
+            }
+            ".innerclass" => {
+                let flags = words.flags(&INNER_CLASS_FLAGS);
+                let mut class = |words: &mut Words<'_>| match words.name()? {
+                    "none" => Ok(ConstantIndex(0)),
+                    name => self.class(name),
+                };
+                let inner_class = class(&mut words)?;
+                let outer_class = class(&mut words)?;
+                let inner_class_name = match words.word()? {
+                    "none" => ConstantIndex(0),
+                    name => self.utf8(name)?,
+                };
+                self.inner_classes.push(ty::InnerClassInfo {
+                    inner_class,
+                    outer_class,
+                    inner_class_name,
+                    flags: ty::InnerClassFlags::from_bits_truncate(flags),
+                });
+            }
+            ".bootstrap" => {
+                let index = words.number::<usize>("a bootstrap method index")?;
+                if index != self.bootstrap_methods.len() {
+                    fail!(
+                        line.number,
+                        "expected bootstrap method {}",
+                        self.bootstrap_methods.len()
+                    );
+                }
+                let method_ref = self.constant(&mut words)?;
+                if !matches!(
+                    self.constant_pool.get(method_ref),
+                    Constant::MethodHandleRef(..)
+                ) {
+                    fail!(line.number, "a bootstrap method is a MethodHandle");
+                }
+                let mut arguments = vec![];
+                while !words.tokens.is_empty() {
+                    arguments.push(self.constant(&mut words)?);
+                }
+                self.bootstrap_methods.push(attr::BootstrapMethod {
+                    method_ref,
+                    arguments,
+                });
+            }
+            directive if MEMBER_ATTRIBUTES.contains(&directive) => {
+                let mut attributes = std::mem::take(&mut self.attributes);
+                let added = self.member_attribute(directive, &mut words, &mut attributes);
+                self.attributes = attributes;
+                added?;
+            }
+            ".field" => return self.field(words, lines),
+            ".method" => return self.method(words, lines),
+            directive => fail!(line.number, "unknown directive: {}", directive),
+        }
+        words.end()
+    }
+
+    /// One of `MEMBER_ATTRIBUTES`, which classes and members share. The annotations,
+    /// parameters and the like of a member go in one attribute, which the first of them
+    /// adds to `attributes`. Annotations are `visible` at run time or `invisible`:
+    ///
+    /// ```text
+    /// .enclosing <class> [<name> <descriptor>]
+    /// .debugextension "<text>"
+    /// .annotationdefault <element value>
+    /// .parameter <flags> <name | none>
+    /// .annotation <visibility> <type> ( <name> = <element value> ... )
+    /// .paramannotation <visibility> <parameter> <annotation> ...
+    /// .typeannotation <visibility> <target type> <target> [path <kind>:<index> ...] <annotation>
+    /// .attribute <name> [<bytes in hex>]
+    /// ```
+    fn member_attribute(
+        &mut self,
+        directive: &str,
+        words: &mut Words<'_>,
+        attributes: &mut Vec<Attribute>,
+    ) -> Result<()> {
+        let line = words.line;
+        let attribute = match directive {
+            ".signature" => Attribute::Signature(attr::Signature {
+                attribute_name: self.utf8("Signature")?,
+                signature: self.utf8(words.string()?)?,
+            }),
+            ".deprecated" => Attribute::Deprecated(attr::Deprecated {
+                attribute_name: self.utf8("Deprecated")?,
+            }),
+            ".synthetic" => Attribute::Synthetic(attr::Synthetic {
+                attribute_name: self.utf8("Synthetic")?,
+            }),
+            ".enclosing" => {
+                let attribute_name = self.utf8("EnclosingMethod")?;
+                let class = self.class(words.word()?)?;
+                let method = match words.peek() {
+                    Some(name) => {
+                        words.word()?;
+                        self.name_and_type(name, words.word()?)?
+                    }
+                    None => ConstantIndex(0),
+                };
+                Attribute::EnclosingMethod(attr::EnclosingMethod {
+                    attribute_name,
+                    class,
+                    method,
+                })
+            }
+            ".debugextension" => Attribute::SourceDebugExtension(attr::SourceDebugExtension {
+                attribute_name: self.utf8("SourceDebugExtension")?,
+                debug_extension: words.string()?.as_bytes().to_vec(),
+            }),
+            ".attribute" => self.unknown(words)?,
+            ".annotationdefault" => Attribute::AnnotationDefault(attr::AnnotationDefault {
+                attribute_name: self.utf8("AnnotationDefault")?,
+                value: self.element_value(words)?,
+            }),
+            ".parameter" => {
+                let flags = words.flags(&PARAMETER_FLAGS);
+                let name = match words.name()? {
+                    "none" => ConstantIndex(0),
+                    name => self.utf8(name)?,
+                };
+                let parameter = attr::MethodParameter {
+                    name,
+                    flags: attr::MethodParameterFlags::from_bits_truncate(flags),
+                };
+                let parameters = attributes.iter_mut().find_map(|attribute| match attribute {
+                    Attribute::MethodParameters(parameters) => Some(&mut parameters.parameters),
+                    _ => None,
+                });
+                match parameters {
+                    Some(parameters) => parameters.push(parameter),
+                    None => attributes.push(Attribute::MethodParameters(attr::MethodParameters {
+                        attribute_name: self.utf8("MethodParameters")?,
+                        parameters: vec![parameter],
+                    })),
+                }
+                return Ok(());
+            }
+            ".annotation" => {
+                let visible = words.visible()?;
+                let annotation = self.annotation(words)?;
+                let annotations = attributes.iter_mut().find_map(|attribute| match attribute {
+                    Attribute::RuntimeVisibleAnnotations(table) if visible => {
+                        Some(&mut table.annotations)
+                    }
+                    Attribute::RuntimeInvisibleAnnotations(table) if !visible => {
+                        Some(&mut table.annotations)
+                    }
+                    _ => None,
+                });
+                match annotations {
+                    Some(annotations) => annotations.push(annotation),
+                    None if visible => attributes.push(Attribute::RuntimeVisibleAnnotations(
+                        attr::RuntimeVisibleAnnotations {
+                            attribute_name: self.utf8("RuntimeVisibleAnnotations")?,
+                            annotations: vec![annotation],
+                        },
+                    )),
+                    None => attributes.push(Attribute::RuntimeInvisibleAnnotations(
+                        attr::RuntimeInvisibleAnnotations {
+                            attribute_name: self.utf8("RuntimeInvisibleAnnotations")?,
+                            annotations: vec![annotation],
+                        },
+                    )),
+                }
+                return Ok(());
+            }
+            ".paramannotation" => {
+                let visible = words.visible()?;
+                let index = words.number::<usize>("a parameter index")?;
+                let mut annotations = vec![];
+                while !words.tokens.is_empty() {
+                    annotations.push(self.annotation(words)?);
+                }
+                let parameter = attr::ParameterAnnotation(annotations);
+                let parameters = attributes.iter_mut().find_map(|attribute| match attribute {
+                    Attribute::RuntimeVisibleParameterAnnotations(table) if visible => {
+                        Some(&mut table.annotations_by_param_index)
+                    }
+                    Attribute::RuntimeInvisibleParameterAnnotations(table) if !visible => {
+                        Some(&mut table.annotations_by_param_index)
+                    }
+                    _ => None,
+                });
+                match parameters {
+                    Some(parameters) if index == parameters.len() => parameters.push(parameter),
+                    Some(parameters) => fail!(line, "expected parameter {}", parameters.len()),
+                    None if index != 0 => fail!(line, "expected parameter 0"),
+                    None if visible => {
+                        attributes.push(Attribute::RuntimeVisibleParameterAnnotations(
+                            attr::RuntimeVisibleParameterAnnotations {
+                                attribute_name: self.utf8("RuntimeVisibleParameterAnnotations")?,
+                                annotations_by_param_index: vec![parameter],
+                            },
+                        ))
+                    }
+                    None => attributes.push(Attribute::RuntimeInvisibleParameterAnnotations(
+                        attr::RuntimeInvisibleParameterAnnotations {
+                            attribute_name: self.utf8("RuntimeInvisibleParameterAnnotations")?,
+                            annotations_by_param_index: vec![parameter],
+                        },
+                    )),
+                }
+                return Ok(());
+            }
+            _ => {
+                let (visible, annotation) = self.type_annotation(words)?;
+                if annotation.target_type >= 0x40 {
+                    fail!(line, "only code can have this type annotation");
+                }
+                let annotation = annotation.resolve(&Code::default())?;
+                return self.add_type_annotation(attributes, visible, annotation);
+            }
+        };
+        attributes.push(attribute);
+        Ok(())
+    }
+
+    /// `<name> [<bytes in hex>]`, an attribute that is kept as the bytes it is made of
+    fn unknown(&mut self, words: &mut Words<'_>) -> Result<Attribute> {
+        let attribute_name = self.utf8(words.name()?)?;
+        let hex = match words.peek() {
+            Some(..) => words.word()?,
+            None => "",
+        };
+        if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            fail!(words.line, "expected bytes in hex, got: {}", hex);
+        }
+        let info = (0..hex.len())
+            .step_by(2)
+            .map(|at| u8::from_str_radix(&hex[at..at + 2], 16).unwrap_or_default())
+            .collect();
+        Ok(Attribute::Unknown(attr::Unknown {
+            attribute_name,
+            info,
+        }))
+    }
+
+    /// Adds `annotation` to the `Runtime(In)VisibleTypeAnnotations` of `attributes`
+    fn add_type_annotation(
+        &mut self,
+        attributes: &mut Vec<Attribute>,
+        visible: bool,
+        annotation: attr::TypeAnnotation,
+    ) -> Result<()> {
+        let annotations = attributes.iter_mut().find_map(|attribute| match attribute {
+            Attribute::RuntimeVisibleTypeAnnotations(table) if visible => {
+                Some(&mut table.annotations)
+            }
+            Attribute::RuntimeInvisibleTypeAnnotations(table) if !visible => {
+                Some(&mut table.annotations)
+            }
+            _ => None,
+        });
+        match annotations {
+            Some(annotations) => annotations.push(annotation),
+            None if visible => attributes.push(Attribute::RuntimeVisibleTypeAnnotations(
+                attr::RuntimeVisibleTypeAnnotations {
+                    attribute_name: self.utf8("RuntimeVisibleTypeAnnotations")?,
+                    annotations: vec![annotation],
+                },
+            )),
+            None => attributes.push(Attribute::RuntimeInvisibleTypeAnnotations(
+                attr::RuntimeInvisibleTypeAnnotations {
+                    attribute_name: self.utf8("RuntimeInvisibleTypeAnnotations")?,
+                    annotations: vec![annotation],
+                },
+            )),
+        }
+        Ok(())
+    }
+
+    /// `<type> ( <name> = <element value> ... )`
+    fn annotation(&mut self, words: &mut Words<'_>) -> Result<attr::Annotation> {
+        let type_index = self.utf8(words.word()?)?;
+        words.keyword("(")?;
+        let mut indices_with_values = vec![];
+        while words.peek() != Some(")") {
+            let name = self.utf8(words.word()?)?;
+            words.keyword("=")?;
+            indices_with_values.push((name, self.element_value(words)?));
+        }
+        words.word()?;
+        Ok(attr::Annotation {
+            type_index,
+            indices_with_values,
+        })
+    }
+
+    /// The tag of the kind of value, followed by the value: `I 1`, `s "text"`,
+    /// `e <type> <name>`, `c <descriptor>`, `@ <annotation>` or `[ <element value> ... ]`
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.16.1
+    fn element_value(&mut self, words: &mut Words<'_>) -> Result<ElementValue> {
+        Ok(match words.word()? {
+            "B" => ElementValue::Byte(self.constant(words)?),
+            "C" => ElementValue::Char(self.constant(words)?),
+            "D" => ElementValue::Double(self.constant(words)?),
+            "F" => ElementValue::Float(self.constant(words)?),
+            "I" => ElementValue::Integer(self.constant(words)?),
+            "J" => ElementValue::Long(self.constant(words)?),
+            "S" => ElementValue::Short(self.constant(words)?),
+            "Z" => ElementValue::Boolean(self.constant(words)?),
+            "s" => ElementValue::String(self.utf8(words.string()?)?),
+            "e" => ElementValue::Enum {
+                ty: self.utf8(words.word()?)?,
+                val: self.utf8(words.word()?)?,
+            },
+            "c" => ElementValue::Class(self.utf8(words.word()?)?),
+            "@" => ElementValue::Anotation(self.annotation(words)?),
+            "[" => {
+                let mut values = vec![];
+                while words.peek() != Some("]") {
+                    values.push(self.element_value(words)?);
+                }
+                words.word()?;
+                ElementValue::Array(values)
+            }
+            tag => fail!(words.line, "unknown element value: {}", tag),
+        })
+    }
+
+    /// A `.typeannotation`, and whether it is visible
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.20
+    fn type_annotation(&mut self, words: &mut Words<'_>) -> Result<(bool, TypeAnnotation)> {
+        use attr::TypeAnnotationTarget::*;
+        let line = words.line;
+        let visible = words.visible()?;
+        let word = words.word()?;
+        let target_type = match word.strip_prefix("0x").map(|ty| u8::from_str_radix(ty, 16)) {
+            Some(Ok(target_type)) => target_type,
+            _ => fail!(line, "expected a target type, like 0x13, got: {}", word),
+        };
+        let target = match target_type {
+            0x00 | 0x01 => Target::Known(TypeParameter(words.number("a type parameter")?)),
+            0x10 => Target::Known(Supertype(words.number("a supertype")?)),
+            0x11 | 0x12 => Target::Known(TypeParameterBound {
+                type_parameter: words.number("a type parameter")?,
+                bound: words.number("a bound")?,
+            }),
+            0x13..=0x15 => Target::Known(Empty),
+            0x16 => Target::Known(FormalParameter(words.number("a parameter")?)),
+            0x17 => Target::Known(Throws(words.number("an exception")?)),
+            0x40 | 0x41 => {
+                let mut ranges = vec![];
+                for _ in 0..words.number::<u16>("a number of ranges")? {
+                    let start = words.word()?.to_string();
+                    let end = words.word()?.to_string();
+                    ranges.push(([start, end], words.number("a local variable index")?));
+                }
+                Target::LocalVariable(ranges)
+            }
+            0x42 => Target::Known(Catch(words.number("an exception table index")?)),
+            0x43..=0x46 => Target::Offset(words.word()?.to_string()),
+            0x47..=0x4B => {
+                let label = words.word()?.to_string();
+                Target::TypeArgument(label, words.number("a type argument")?)
+            }
+            ty => fail!(line, "unknown target type: {:#04x}", ty),
+        };
+        let mut target_path = vec![];
+        if words.peek() == Some("path") {
+            words.word()?;
+            while let Some(step) = words.peek().and_then(path_step) {
+                target_path.push(step);
+                words.word()?;
+            }
+        }
+        let annotation = TypeAnnotation {
+            line,
+            target_type,
+            target,
+            target_path,
+            annotation: self.annotation(words)?,
+        };
+        Ok((visible, annotation))
+    }
+
+    /// `.field <flags> <name> <descriptor> [= <constant>]`, which is followed by its
+    /// attributes and an `.end field` when it has any
+    fn field(&mut self, mut words: Words<'_>, lines: &mut Lines<'_>) -> Result<()> {
+        let flags = words.flags(&FIELD_FLAGS);
+        let name = self.utf8(words.name()?)?;
+        let descriptor = self.utf8(words.word()?)?;
+        let mut attributes = vec![];
+        if words.peek() == Some("=") {
+            words.word()?;
+            let attribute_name = self.utf8("ConstantValue")?;
+            let constant_value = self.constant(&mut words)?;
+            attributes.push(Attribute::ConstantValue(attr::ConstantValue {
+                attribute_name,
+                constant_value,
+            }));
+        }
+        words.end()?;
+
+        let is_attribute = |line: &&Line| match line.tokens.first() {
+            Some(Token::Word(word)) => MEMBER_ATTRIBUTES.contains(&word.as_str()),
+            _ => false,
+        };
+        if lines.peek().is_some_and(is_attribute) {
+            for line in lines.by_ref() {
+                let mut words = Words::new(line);
+                match words.word()? {
+                    ".end" => {
+                        words.keyword("field")?;
+                        words.end()?;
+                        break;
+                    }
+                    directive if MEMBER_ATTRIBUTES.contains(&directive) => {
+                        self.member_attribute(directive, &mut words, &mut attributes)?;
+                        words.end()?;
+                    }
+                    directive => fail!(line.number, "unexpected in a field: {}", directive),
+                }
+            }
+        }
+
+        self.fields.push(ty::Field {
+            flags: ty::FieldFlags::from_bits_truncate(flags),
+            name,
+            descriptor,
+            attributes,
+        });
+        Ok(())
+    }
+
+    /// `.method <flags> <name> <descriptor>`, up to its `.end method`
+    fn method(&mut self, mut words: Words<'_>, lines: &mut Lines<'_>) -> Result<()> {
+        let start = words.line;
+        let flags = words.flags(&METHOD_FLAGS);
+        let name = self.utf8(words.name()?)?;
+        let descriptor_str = words.word()?;
+        let descriptor = self.utf8(descriptor_str)?;
+        words.end()?;
+
+        let mut code = Code::default();
+        let mut throws = vec![];
+        let mut attributes = vec![];
+        loop {
+            let line = match lines.next() {
+                Some(line) => line,
+                None => fail!(start, "missing .end method"),
+            };
+            let mut words = Words::new(line);
+            let mut first = words.word()?;
+            if let Some(label) = first.strip_suffix(':') {
+                code.label(label, line.number)?;
+                if words.tokens.is_empty() {
+                    continue;
+                }
+                first = words.word()?;
+            }
+
+            match first {
+                ".end" => {
+                    words.keyword("method")?;
+                    words.end()?;
+                    break;
+                }
+                ".limit" => match words.word()? {
+                    "stack" => code.max_stack = Some(words.number("a stack size")?),
+                    "locals" => code.max_locals = Some(words.number("a number of locals")?),
+                    limit => fail!(line.number, "unknown limit: {}", limit),
+                },
+                ".throws" => throws.push(self.class(words.word()?)?),
+                ".codeattribute" => {
+                    let attribute = self.unknown(&mut words)?;
+                    code.attributes.push(attribute);
+                }
+                ".typeannotation" => {
+                    let (visible, annotation) = self.type_annotation(&mut words)?;
+                    if annotation.target_type >= 0x40 {
+                        code.type_annotations.push((visible, annotation));
+                    } else {
+                        let annotation = annotation.resolve(&Code::default())?;
+                        self.add_type_annotation(&mut attributes, visible, annotation)?;
+                    }
+                }
+                directive if MEMBER_ATTRIBUTES.contains(&directive) => {
+                    self.member_attribute(directive, &mut words, &mut attributes)?;
+                }
+                ".catch" => {
+                    let catch_type = match words.word()? {
+                        "all" => ConstantIndex(0),
+                        class => self.class(class)?,
+                    };
+                    words.keyword("from")?;
+                    let start = words.word()?.to_string();
+                    words.keyword("to")?;
+                    let end = words.word()?.to_string();
+                    words.keyword("using")?;
+                    let handler = words.word()?.to_string();
+                    code.catches
+                        .push((line.number, catch_type, [start, end, handler]));
+                }
+                ".line" => {
+                    let number = words.number("a line number")?;
+                    code.lines.push((code.bytes.len(), number));
+                }
+                ".var" | ".vartype" => {
+                    let index = words.number("a local variable index")?;
+                    words.keyword("is")?;
+                    let name = self.utf8(words.word()?)?;
+                    let descriptor = match words.token()? {
+                        Token::Word(word) | Token::Str(word) => self.utf8(word)?,
+                    };
+                    words.keyword("from")?;
+                    let start = words.word()?.to_string();
+                    words.keyword("to")?;
+                    let end = words.word()?.to_string();
+                    code.variables.push(Variable {
+                        line: line.number,
+                        index,
+                        name,
+                        descriptor,
+                        range: [start, end],
+                        generic: first == ".vartype",
+                    });
+                }
+                ".stack" => {
+                    let frame = self.frame(&mut words)?;
+                    code.frames.push((line.number, code.bytes.len(), frame));
+                }
+                directive if directive.starts_with('.') => {
+                    fail!(line.number, "unknown directive: {}", directive)
+                }
+                mnemonic => self.instruction(&mut code, mnemonic, &mut words, lines)?,
+            }
+            words.end()?;
+        }
+
+        if !code.is_empty() {
+            let is_static = flags & 0x0008 != 0;
+            let params = match ty::MethodDescriptor::parse(descriptor_str) {
+                Ok(descriptor) => descriptor.param_slots() + if is_static { 0 } else { 1 },
+                Err(..) => fail!(start, "invalid descriptor: {}", descriptor_str),
+            };
+            let code = code.finish(self, params as u16)?;
+            attributes.insert(0, Attribute::Code(code));
+        }
+        if !throws.is_empty() {
+            let attribute_name = self.utf8("Exceptions")?;
+            attributes.push(Attribute::Exceptions(attr::Exceptions {
+                attribute_name,
+                index_table: throws,
+            }));
+        }
+
+        let method = ty::Method::new(
+            ty::MethodFlags::from_bits_truncate(flags),
+            name,
+            descriptor,
+            attributes,
+            self.constant_pool.as_slice(),
+        )?;
+        self.methods.push(method);
+        Ok(())
+    }
+
+    /// The frame of a `.stack` directive
+    fn frame(&mut self, words: &mut Words<'_>) -> Result<Frame> {
+        Ok(match words.word()? {
+            "same" => Frame::Same { extended: false },
+            "same_extended" => Frame::Same { extended: true },
+            "same_locals_1_stack_item" => Frame::SameLocals {
+                extended: false,
+                item: self.verification_type(words)?,
+            },
+            "same_locals_1_stack_item_extended" => Frame::SameLocals {
+                extended: true,
+                item: self.verification_type(words)?,
+            },
+            "chop" => match words.number("a number of locals")? {
+                n @ 1..=3 => Frame::Chop(n),
+                n => fail!(words.line, "can't chop {} locals", n),
+            },
+            "append" => match self.verification_types(words, None)? {
+                locals if (1..=3).contains(&locals.len()) => Frame::Append(locals),
+                locals => fail!(words.line, "can't append {} locals", locals.len()),
+            },
+            "full" => {
+                words.keyword("locals")?;
+                let locals = self.verification_types(words, Some("stack"))?;
+                words.keyword("stack")?;
+                let stack = self.verification_types(words, None)?;
+                Frame::Full(locals, stack)
+            }
+            kind => fail!(words.line, "unknown stack frame: {}", kind),
+        })
+    }
+
+    /// Verification types up to the end of the line, or up to the word `until`
+    fn verification_types(
+        &mut self,
+        words: &mut Words<'_>,
+        until: Option<&str>,
+    ) -> Result<Vec<Type>> {
+        let mut types = vec![];
+        while !words.tokens.is_empty() && words.peek() != until {
+            types.push(self.verification_type(words)?);
+        }
+        Ok(types)
+    }
+
+    fn verification_type(&mut self, words: &mut Words<'_>) -> Result<Type> {
+        use VerificationType::*;
+        Ok(Type::Known(match words.word()? {
+            "Top" => Top,
+            "Integer" => Integer,
+            "Float" => Float,
+            "Long" => Long,
+            "Double" => Double,
+            "Null" => Null,
+            "UninitializedThis" => UninitializedThis,
+            "Object" => Object(self.class(words.word()?)?),
+            "Uninitialized" => return Ok(Type::Uninitialized(words.word()?.to_string())),
+            ty => fail!(words.line, "unknown verification type: {}", ty),
+        }))
+    }
+
+    fn instruction(
+        &mut self,
+        code: &mut Code,
+        mnemonic: &str,
+        words: &mut Words<'_>,
+        lines: &mut Lines<'_>,
+    ) -> Result<()> {
+        let line = words.line;
+        let (mnemonic, wide) = match mnemonic {
+            "wide" => (words.word()?, true),
+            mnemonic => (mnemonic, false),
+        };
+        let opcode = match opcode(mnemonic) {
+            Some(opcode) => opcode,
+            None => fail!(line, "unknown instruction: {}", mnemonic),
+        };
+        let shape = Shape::of(opcode);
+        if wide && shape != Shape::Local && shape != Shape::Iinc {
+            fail!(line, "{} can't be wide", mnemonic);
+        }
+
+        let pc = code.bytes.len();
+        match shape {
+            Shape::None => code.bytes.push(opcode),
+            Shape::Wide => fail!(line, "wide goes before the instruction it widens"),
+            Shape::Byte => {
+                let n = words.number::<i8>("a byte")?;
+                code.bytes.extend(&[opcode, n as u8]);
+            }
+            Shape::Short => {
+                let n = words.number::<i16>("a short")?;
+                code.bytes.push(opcode);
+                code.bytes.extend(&n.to_be_bytes());
+            }
+            Shape::Constant | Shape::WideConstant => {
+                if opcode == 0x12 {
+                    self.loaded.push(Line {
+                        number: line,
+                        tokens: words.tokens.to_vec(),
+                    });
+                }
+                let index = self.constant(words)?;
+                let is_wide = matches!(
+                    self.constant_pool.get(index),
+                    Constant::Long(..) | Constant::Double(..)
+                );
+                match opcode {
+                    0x14 if !is_wide => fail!(line, "ldc2_w can only load a long or a double"),
+                    0x12 | 0x13 if is_wide => {
+                        fail!(
+                            line,
+                            "{} can't load a long or a double, use ldc2_w",
+                            mnemonic
+                        )
+                    }
+                    // an ldc that doesn't fit is made into an ldc_w
+                    0x12 if index.0 <= 0xFF => code.bytes.extend(&[opcode, index.0 as u8]),
+                    0x12 => {
+                        self.widened = true;
+                        code.bytes.push(0x13)
+                    }
+                    _ => code.bytes.push(opcode),
+                }
+                if code.bytes.len() == pc + 1 {
+                    code.bytes.extend(&index.0.to_be_bytes());
+                }
+            }
+            Shape::Local => {
+                let index = words.number::<u16>("a local variable index")?;
+                if wide || index > 0xFF {
+                    code.bytes.extend(&[0xC4, opcode]);
+                    code.bytes.extend(&index.to_be_bytes());
+                } else {
+                    code.bytes.extend(&[opcode, index as u8]);
+                }
+            }
+            Shape::Iinc => {
+                let index = words.number::<u16>("a local variable index")?;
+                let value = words.number::<i16>("an increment")?;
+                if wide || index > 0xFF || i8::try_from(value).is_err() {
+                    code.bytes.extend(&[0xC4, opcode]);
+                    code.bytes.extend(&index.to_be_bytes());
+                    code.bytes.extend(&value.to_be_bytes());
+                } else {
+                    code.bytes.extend(&[opcode, index as u8, value as u8]);
+                }
+            }
+            Shape::Branch | Shape::BranchWide => {
+                let wide = shape == Shape::BranchWide;
+                code.bytes.push(opcode);
+                let label = words.word()?.to_string();
+                code.labels.branch(&mut code.bytes, line, pc, label, wide);
+            }
+            Shape::Member | Shape::Interface | Shape::Dynamic => {
+                let index = self.constant(words)?;
+                let constant = self.constant_pool.get(index);
+                let fits = matches!(
+                    (opcode, constant),
+                    (0xB2..=0xB5, Constant::FieldRef(..))
+                        | (0xB6..=0xB8, Constant::MethodRef(..))
+                        | (0xB7..=0xB9, Constant::InterfaceMethodRef(..))
+                        | (0xBA, Constant::InvokeDynamicRef(..))
+                );
+                if !fits {
+                    fail!(line, "{} can't be used with this constant", mnemonic);
+                }
+                code.bytes.push(opcode);
+                code.bytes.extend(&index.0.to_be_bytes());
+                match shape {
+                    Shape::Interface => {
+                        let count = match words.peek() {
+                            Some(..) => words.number("an argument count")?,
+                            None => self.argument_count(index, line)?,
+                        };
+                        code.bytes.extend(&[count, 0]);
+                    }
+                    Shape::Dynamic => code.bytes.extend(&[0, 0]),
+                    _ => {}
+                }
+            }
+            Shape::Class | Shape::MultiArray => {
+                let index = self.class_operand(words)?;
+                code.bytes.push(opcode);
+                code.bytes.extend(&index.0.to_be_bytes());
+                if shape == Shape::MultiArray {
+                    code.bytes.push(words.number("a number of dimensions")?);
+                }
+            }
+            Shape::NewArray => {
+                let name = words.word()?;
+                match ARRAY_TYPES.iter().find(|(ty, _)| *ty == name) {
+                    Some(&(_, ty)) => code.bytes.extend(&[opcode, ty]),
+                    None => fail!(line, "unknown array type: {}", name),
+                }
+            }
+            Shape::TableSwitch => {
+                let low = words.number::<i32>("the lowest key")?;
+                words.end()?;
+                let (targets, default) = switch(lines, line, |words| {
+                    let label = words.word()?;
+                    Ok(((), label))
+                })?;
+                let high = low + targets.len() as i32 - 1;
+                code.bytes.push(opcode);
+                pad(&mut code.bytes);
+                code.labels
+                    .branch(&mut code.bytes, line, pc, default.to_string(), true);
+                code.bytes.extend(&low.to_be_bytes());
+                code.bytes.extend(&high.to_be_bytes());
+                for ((), label) in targets {
+                    code.labels
+                        .branch(&mut code.bytes, line, pc, label.to_string(), true);
+                }
+            }
+            Shape::LookupSwitch => {
+                words.end()?;
+                let (mut pairs, default) = switch(lines, line, |words| {
+                    let key = words.word()?;
+                    match key.strip_suffix(':').map(str::parse::<i32>) {
+                        Some(Ok(key)) => Ok((key, words.word()?)),
+                        _ => fail!(words.line, "expected a key, like `1:`, got: {}", key),
+                    }
+                })?;
+                pairs.sort_by_key(|&(key, _)| key);
+                if pairs.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+                    fail!(line, "lookupswitch has the same key twice");
+                }
+                code.bytes.push(opcode);
+                pad(&mut code.bytes);
+                code.labels
+                    .branch(&mut code.bytes, line, pc, default.to_string(), true);
+                code.bytes.extend(&(pairs.len() as i32).to_be_bytes());
+                for (key, label) in pairs {
+                    code.bytes.extend(&key.to_be_bytes());
+                    code.labels
+                        .branch(&mut code.bytes, line, pc, label.to_string(), true);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The class of `new`, `checkcast` and the like, with or without `Class` before it
+    fn class_operand(&mut self, words: &mut Words<'_>) -> Result<ConstantIndex> {
+        if words.peek() == Some("Class") {
+            words.word()?;
+        }
+        self.class(words.word()?)
+    }
+
+    /// The count operand of `invokeinterface`, from the descriptor of the method
+    fn argument_count(&self, index: ConstantIndex, line: usize) -> Result<u8> {
+        let descriptor = self
+            .constant_pool
+            .descriptor(index)
+            .expect("checked to be an InterfaceMethodRef");
+        match ty::MethodDescriptor::parse(descriptor) {
+            Ok(descriptor) => Ok(descriptor.param_slots() as u8 + 1),
+            Err(..) => fail!(line, "invalid descriptor: {}", descriptor),
+        }
+    }
+
+    /// A constant literal, which is interned in the constant pool
+    fn constant(&mut self, words: &mut Words<'_>) -> Result<ConstantIndex> {
+        let line = words.line;
+        let word = match words.token()? {
+            Token::Str(s) => {
+                let s = self.utf8(s)?;
+                return self.intern(Constant::StringRef(s));
+            }
+            Token::Word(word) => word.as_str(),
+        };
+
+        let mut member = |words: &mut Words<'_>| -> Result<_> {
+            let class = self.class(words.word()?)?;
+            let name = words.word()?;
+            let descriptor = words.word()?;
+            Ok((class, self.name_and_type(name, descriptor)?))
+        };
+        let constant = match word {
+            "Class" => return self.class(words.word()?),
+            "String" => Constant::StringRef(self.utf8(words.string()?)?),
+            "MethodType" => Constant::MethodType(self.utf8(words.word()?)?),
+            "Field" => {
+                let (class, name_and_type) = member(words)?;
+                Constant::FieldRef(ty::constant::FieldRef {
+                    class,
+                    name_and_type,
+                })
+            }
+            "Method" => {
+                let (class, name_and_type) = member(words)?;
+                Constant::MethodRef(ty::constant::MethodRef {
+                    class,
+                    name_and_type,
+                })
+            }
+            "InterfaceMethod" => {
+                let (class, name_and_type) = member(words)?;
+                Constant::InterfaceMethodRef(ty::constant::InterfaceMethodRef {
+                    class,
+                    name_and_type,
+                })
+            }
+            "InvokeDynamic" => {
+                let bootstrap = ty::MethodIndex(words.number("a bootstrap method index")?);
+                let name = words.word()?;
+                let descriptor = words.word()?;
+                Constant::InvokeDynamicRef(ty::constant::InvokeDynamicRef {
+                    bootstrap,
+                    name_and_type: self.name_and_type(name, descriptor)?,
+                })
+            }
+            "MethodHandle" => {
+                let kind = words.word()?;
+                let reference = self.constant(words)?;
+                let handle = match METHOD_HANDLE_KINDS.iter().position(|&k| k == kind) {
+                    Some(0) => ty::MethodHandle::GetField(reference),
+                    Some(1) => ty::MethodHandle::GetStatic(reference),
+                    Some(2) => ty::MethodHandle::PutField(reference),
+                    Some(3) => ty::MethodHandle::PutStatic(reference),
+                    Some(4) => ty::MethodHandle::InvokeVirtual(reference),
+                    Some(5) => ty::MethodHandle::InvokeStatic(reference),
+                    Some(6) => ty::MethodHandle::InvokeSpecial(reference),
+                    Some(7) => ty::MethodHandle::NewInvokeSpecial(reference),
+                    Some(_) => ty::MethodHandle::InvokeInterface(reference),
+                    None => fail!(line, "unknown method handle kind: {}", kind),
+                };
+                Constant::MethodHandleRef(handle)
+            }
+            number => match number_literal(number) {
+                Some(constant) => constant,
+                None => fail!(line, "expected a constant, got: {}", number),
+            },
+        };
+        self.intern(constant)
+    }
+
+    fn intern(&mut self, constant: Constant) -> Result<ConstantIndex> {
+        self.constant_pool.intern(constant)
+    }
+
+    fn utf8(&mut self, s: &str) -> Result<ConstantIndex> {
+        self.constant_pool.utf8(s)
+    }
+
+    fn class(&mut self, name: &str) -> Result<ConstantIndex> {
+        self.constant_pool.class(name)
+    }
+
+    fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<ConstantIndex> {
+        self.constant_pool.name_and_type(name, descriptor)
+    }
+
+    fn class_name(&self, index: ConstantIndex) -> &str {
+        self.constant_pool
+            .class_name(index)
+            .expect("interned as a ClassRef")
+    }
+}
+
+/// The error for `label`, which `line` refers to
+fn unresolved<T>(line: usize, label: &str, err: Unresolved) -> Result<T> {
+    match err {
+        Unresolved::Missing => fail!(line, "unknown label: {}", label),
+        Unresolved::TooFar => fail!(line, "{} is too far to branch to", label),
+        Unresolved::TooLong => fail!(line, "the code is too long"),
+    }
+}
+
+/// The directives of the attributes that `member_attribute` makes
+const MEMBER_ATTRIBUTES: [&str; 11] = [
+    ".signature",
+    ".deprecated",
+    ".synthetic",
+    ".enclosing",
+    ".debugextension",
+    ".annotationdefault",
+    ".parameter",
+    ".annotation",
+    ".paramannotation",
+    ".typeannotation",
+    ".attribute",
+];
+
+/// A step of the path of a type annotation, like `3:0`
+fn path_step(word: &str) -> Option<(u8, u8)> {
+    let (kind, index) = word.split_once(':')?;
+    Some((kind.parse().ok()?, index.parse().ok()?))
+}
+
+/// `10`, `10L`, `1.5f` or `1.5d`
+fn number_literal(word: &str) -> Option<Constant> {
+    if let Some(n) = word.strip_suffix('L') {
+        n.parse::<i64>().ok().map(|n| Constant::Long(n as u64))
+    } else if let Some(n) = word.strip_suffix('f') {
+        n.parse::<f32>().ok().map(Constant::Float)
+    } else if let Some(n) = word.strip_suffix('d') {
+        n.parse::<f64>().ok().map(Constant::Double)
+    } else {
+        word.parse::<i32>()
+            .ok()
+            .map(|n| Constant::Integer(n as u32))
+    }
+}
+
+/// The lines of a `tableswitch` or `lookupswitch`, up to its `default:`
+fn switch<'a, T>(
+    lines: &mut Lines<'a>,
+    start: usize,
+    mut case: impl FnMut(&mut Words<'a>) -> Result<(T, &'a str)>,
+) -> Result<(Vec<(T, &'a str)>, &'a str)> {
+    let mut cases = vec![];
+    for line in lines.by_ref() {
+        let mut words = Words::new(line);
+        if words.peek() == Some("default:") {
+            words.word()?;
+            let default = words.word()?;
+            words.end()?;
+            return Ok((cases, default));
+        }
+        cases.push(case(&mut words)?);
+        words.end()?;
+    }
+    fail!(start, "missing default: of the switch")
+}
+
+/// A verification type of a `.stack` frame, which can refer to a label
+enum Type {
+    Known(VerificationType),
+    Uninitialized(String),
+}
+
+enum Frame {
+    Same { extended: bool },
+    SameLocals { extended: bool, item: Type },
+    Chop(u8),
+    Append(Vec<Type>),
+    Full(Vec<Type>, Vec<Type>),
+}
+
+/// The target of a type annotation, which can refer to labels
+enum Target {
+    Known(attr::TypeAnnotationTarget),
+    LocalVariable(Vec<([String; 2], u16)>),
+    Offset(String),
+    TypeArgument(String, u8),
+}
+
+struct TypeAnnotation {
+    line: usize,
+    target_type: u8,
+    target: Target,
+    target_path: Vec<(u8, u8)>,
+    annotation: attr::Annotation,
+}
+
+impl TypeAnnotation {
+    /// The type annotation with the labels of its target in `code` resolved
+    fn resolve(self, code: &Code) -> Result<attr::TypeAnnotation> {
+        use attr::TypeAnnotationTarget::*;
+        let line = self.line;
+        let target = match self.target {
+            Target::Known(target) => target,
+            Target::LocalVariable(ranges) => {
+                let mut table = vec![];
+                for ([start, end], index) in ranges {
+                    let start_pc = code.pc_u16(line, &start)?;
+                    let length = match code.pc_u16(line, &end)?.checked_sub(start_pc) {
+                        Some(length) => length,
+                        None => fail!(line, "{} is before {}", end, start),
+                    };
+                    table.push((start_pc, length, index));
+                }
+                LocalVariable(table)
+            }
+            Target::Offset(label) => Offset(code.pc_u16(line, &label)?),
+            Target::TypeArgument(label, type_argument) => TypeArgument {
+                offset: code.pc_u16(line, &label)?,
+                type_argument,
+            },
+        };
+        Ok(attr::TypeAnnotation {
+            target_type: self.target_type,
+            target,
+            target_path: self.target_path,
+            annotation: self.annotation,
+        })
+    }
+}
+
+struct Variable {
+    line: usize,
+    index: u16,
+    name: ConstantIndex,
+    descriptor: ConstantIndex,
+    range: [String; 2],
+    /// Whether this is a `.vartype`, with a signature instead of a descriptor
+    generic: bool,
+}
+
+/// The code of a method as it is assembled, with what refers to labels
+#[derive(Default)]
+struct Code {
+    bytes: Vec<u8>,
+    max_stack: Option<u16>,
+    max_locals: Option<u16>,
+    /// With the line of each branch
+    labels: Labels<String, usize>,
+    catches: Vec<(usize, ConstantIndex, [String; 3])>,
+    lines: Vec<(usize, u16)>,
+    variables: Vec<Variable>,
+    frames: Vec<(usize, usize, Frame)>,
+    /// The `.typeannotation`s on the code, and whether each is visible
+    type_annotations: Vec<(bool, TypeAnnotation)>,
+    /// The `.codeattribute`s, which go after the others
+    attributes: Vec<Attribute>,
+}
+
+impl Code {
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty() && self.max_stack.is_none() && self.max_locals.is_none()
+    }
+
+    fn label(&mut self, label: &str, line: usize) -> Result<()> {
+        if label.is_empty() || label.starts_with('.') {
+            fail!(line, "invalid label: {}:", label);
+        }
+        if !self.labels.place(label.to_string(), self.bytes.len()) {
+            fail!(line, "label defined twice: {}", label);
+        }
+        Ok(())
+    }
+
+    fn pc_u16(&self, line: usize, label: &str) -> Result<u16> {
+        match self.labels.pc_u16(label) {
+            Ok(pc) => Ok(pc),
+            Err(err) => unresolved(line, label, err),
+        }
+    }
+
+    fn finish(mut self, asm: &mut Assembler, params: u16) -> Result<attr::Code> {
+        if let Err((line, label, err)) = self.labels.resolve(&mut self.bytes) {
+            return unresolved(line, &label, err);
+        }
+
+        let mut exception_table = vec![];
+        for (line, catch_type, [start, end, handler]) in &self.catches {
+            exception_table.push(attr::ExceptionTableRow {
+                start_pc: self.pc_u16(*line, start)?,
+                end_pc: self.pc_u16(*line, end)?,
+                handler_pc: self.pc_u16(*line, handler)?,
+                catch_type: *catch_type,
+            });
+        }
+
+        let mut attributes = vec![];
+        if !self.lines.is_empty() {
+            attributes.push(Attribute::LineNumberTable(attr::LineNumberTable {
+                attribute_name: asm.utf8("LineNumberTable")?,
+                table: self
+                    .lines
+                    .iter()
+                    .map(|&(pc, line)| (pc as u16, line))
+                    .collect(),
+            }));
+        }
+
+        let (mut variables, mut types) = (vec![], vec![]);
+        for var in &self.variables {
+            let start_pc = self.pc_u16(var.line, &var.range[0])?;
+            let length = match self.pc_u16(var.line, &var.range[1])?.checked_sub(start_pc) {
+                Some(length) => length,
+                None => fail!(var.line, "{} is before {}", var.range[1], var.range[0]),
+            };
+            if var.generic {
+                types.push(attr::LocalVariableType {
+                    start_pc,
+                    length,
+                    name: var.name,
+                    signature: var.descriptor,
+                    index: var.index,
+                });
+            } else {
+                variables.push(attr::LocalVariable {
+                    start_pc,
+                    length,
+                    name: var.name,
+                    descriptor: var.descriptor,
+                    index: var.index,
+                });
+            }
+        }
+        if !variables.is_empty() {
+            attributes.push(Attribute::LocalVariableTable(attr::LocalVariableTable {
+                attribute_name: asm.utf8("LocalVariableTable")?,
+                variables,
+            }));
+        }
+        if !types.is_empty() {
+            attributes.push(Attribute::LocalVariableTypeTable(
+                attr::LocalVariableTypeTable {
+                    attribute_name: asm.utf8("LocalVariableTypeTable")?,
+                    variables_types: types,
+                },
+            ));
+        }
+
+        if !self.frames.is_empty() {
+            let entries = self.stack_map()?;
+            attributes.push(Attribute::StackMapTable(attr::StackMapTable {
+                attribute_name: asm.utf8("StackMapTable")?,
+                entries,
+            }));
+        }
+
+        for (visible, annotation) in std::mem::take(&mut self.type_annotations) {
+            let annotation = annotation.resolve(&self)?;
+            asm.add_type_annotation(&mut attributes, visible, annotation)?;
+        }
+        attributes.append(&mut self.attributes);
+
+        if u32::try_from(self.bytes.len()).is_err() {
+            generic_error!("the code is too long");
+        }
+        Ok(attr::Code {
+            attribute_name: asm.utf8("Code")?,
+            max_stack: self.max_stack.unwrap_or(0),
+            max_locals: self.max_locals.unwrap_or(params),
+            code: self.bytes,
+            exception_table,
+            attributes,
+        })
+    }
+
+    /// The `.stack` frames, with the offset of each from the one before it
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.4
+    fn stack_map(&self) -> Result<Vec<StackMapFrame>> {
+        let resolve = |line: usize, ty: &Type| match ty {
+            Type::Known(ty) => Ok(ty.clone()),
+            Type::Uninitialized(label) => self
+                .pc_u16(line, label)
+                .map(VerificationType::Uninitialized),
+        };
+        let resolve_all = |line: usize, types: &[Type]| {
+            types
+                .iter()
+                .map(|ty| resolve(line, ty))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        let mut entries = vec![];
+        let mut previous: Option<usize> = None;
+        for (line, pc, frame) in &self.frames {
+            let line = *line;
+            let delta = match previous {
+                None => *pc,
+                Some(previous) if *pc > previous => pc - previous - 1,
+                Some(..) => fail!(line, "there is already a stack frame here"),
+            };
+            let offset = match u16::try_from(delta) {
+                Ok(offset) => offset,
+                Err(..) => fail!(line, "the code is too long"),
+            };
+            previous = Some(*pc);
+
+            // frames that can't reach that far are made extended
+            entries.push(match frame {
+                Frame::Same { extended } if *extended || offset > 63 => {
+                    StackMapFrame::SameFrameExtended(attr::SameFrameExtended { offset })
+                }
+                Frame::Same { .. } => StackMapFrame::SameFrame(attr::SameFrame {
+                    offset: offset as u8,
+                }),
+                Frame::SameLocals { extended, item } if *extended || offset > 63 => {
+                    StackMapFrame::SameLocalsOneStackItemFrameExtended(
+                        attr::SameLocalsOneStackItemFrameExtended {
+                            offset,
+                            stack_item: resolve(line, item)?,
+                        },
+                    )
+                }
+                Frame::SameLocals { item, .. } => {
+                    StackMapFrame::SameLocalsOneStackItemFrame(attr::SameLocalsOneStackItemFrame {
+                        offset: offset as u8,
+                        stack_item: resolve(line, item)?,
+                    })
+                }
+                Frame::Chop(absent_locals) => StackMapFrame::ChopFrame(attr::ChopFrame {
+                    offset,
+                    absent_locals: *absent_locals,
+                }),
+                Frame::Append(locals) => StackMapFrame::AppendFrame(attr::AppendFrame {
+                    offset,
+                    new_locals: resolve_all(line, locals)?,
+                }),
+                Frame::Full(locals, stack) => StackMapFrame::FullFrame(attr::FullFrame {
+                    offset,
+                    locals: resolve_all(line, locals)?,
+                    stack_items: resolve_all(line, stack)?,
+                }),
+            });
+        }
+        Ok(entries)
+    }
+}
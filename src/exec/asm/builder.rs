@@ -0,0 +1,824 @@
+//! Making class files from Rust, for classes that are generated instead of written
+use super::*;
+
+use attr::Attribute;
+use std::convert::TryFrom;
+use ty::{Constant, ConstantIndex};
+use verify::Hierarchy;
+
+/// A constant that `ldc` can load, or that a static field can start out as
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Literal<'a> {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(&'a str),
+    /// The internal name of a class, for a `java/lang/Class`
+    Class(&'a str),
+    MethodType(&'a str),
+}
+
+impl Literal<'_> {
+    fn intern(self, pool: &mut ConstantPool) -> Result<ConstantIndex> {
+        match self {
+            Literal::Int(n) => pool.intern(Constant::Integer(n as u32)),
+            Literal::Long(n) => pool.intern(Constant::Long(n as u64)),
+            Literal::Float(n) => pool.intern(Constant::Float(n)),
+            Literal::Double(n) => pool.intern(Constant::Double(n)),
+            Literal::String(s) => pool.string(s),
+            Literal::Class(name) => pool.class(name),
+            Literal::MethodType(descriptor) => {
+                let descriptor = pool.utf8(descriptor)?;
+                pool.intern(Constant::MethodType(descriptor))
+            }
+        }
+    }
+}
+
+impl From<i32> for Literal<'_> {
+    fn from(n: i32) -> Self {
+        Literal::Int(n)
+    }
+}
+
+impl From<i64> for Literal<'_> {
+    fn from(n: i64) -> Self {
+        Literal::Long(n)
+    }
+}
+
+impl From<f32> for Literal<'_> {
+    fn from(n: f32) -> Self {
+        Literal::Float(n)
+    }
+}
+
+impl From<f64> for Literal<'_> {
+    fn from(n: f64) -> Self {
+        Literal::Double(n)
+    }
+}
+
+impl<'a> From<&'a str> for Literal<'a> {
+    fn from(s: &'a str) -> Self {
+        Literal::String(s)
+    }
+}
+
+/// Builds a class file. The constants are interned as the class is built, so no
+/// `ConstantIndex` has to be worked out by hand
+///
+/// ```ignore
+/// let mut class = ClassBuilder::new("Adapter");
+/// let mut method = class.method(MethodFlags::PUBLIC | MethodFlags::STATIC, "twice", "(I)I");
+/// method.op("iload_0")?.int(2)?.op("imul")?.op("ireturn")?;
+/// method.finish()?;
+/// let file = class.build(&mut interpreter)?;
+/// ```
+#[derive(Debug)]
+pub struct ClassBuilder {
+    constant_pool: ConstantPool,
+    version: (u16, u16),
+    flags: ty::ClassFlags,
+    name: String,
+    super_class: Option<String>,
+    interfaces: Vec<ConstantIndex>,
+    fields: Vec<ty::Field>,
+    methods: Vec<ty::Method>,
+    attributes: Vec<Attribute>,
+}
+
+impl ClassBuilder {
+    /// A public class called `name`, which extends `java/lang/Object` and is made for
+    /// version 52 (Java 8)
+    pub fn new(name: &str) -> Self {
+        Self {
+            constant_pool: ConstantPool::default(),
+            version: (52, 0),
+            flags: ty::ClassFlags::PUBLIC | ty::ClassFlags::SUPER,
+            name: name.to_string(),
+            super_class: match name {
+                "java/lang/Object" => None,
+                _ => Some("java/lang/Object".to_string()),
+            },
+            interfaces: vec![],
+            fields: vec![],
+            methods: vec![],
+            attributes: vec![],
+        }
+    }
+
+    pub fn version(&mut self, major: u16, minor: u16) -> &mut Self {
+        self.version = (major, minor);
+        self
+    }
+
+    pub fn flags(&mut self, flags: ty::ClassFlags) -> &mut Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn super_class(&mut self, name: &str) -> &mut Self {
+        self.super_class = Some(name.to_string());
+        self
+    }
+
+    pub fn interface(&mut self, name: &str) -> Result<&mut Self> {
+        let interface = self.constant_pool.class(name)?;
+        if !self.interfaces.contains(&interface) {
+            self.interfaces.push(interface);
+        }
+        Ok(self)
+    }
+
+    pub fn source_file(&mut self, name: &str) -> Result<&mut Self> {
+        let attribute_name = self.constant_pool.utf8("SourceFile")?;
+        let source_file = self.constant_pool.utf8(name)?;
+        self.attributes
+            .retain(|attribute| !matches!(attribute, Attribute::SourceFile(..)));
+        self.attributes
+            .push(Attribute::SourceFile(attr::SourceFile {
+                attribute_name,
+                source_file,
+            }));
+        Ok(self)
+    }
+
+    pub fn field(
+        &mut self,
+        flags: ty::FieldFlags,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<&mut Self> {
+        self.add_field(flags, name, descriptor, vec![])
+    }
+
+    /// A static final field, which starts out as `value` without a `<clinit>` setting it
+    pub fn constant_field<'a>(
+        &mut self,
+        flags: ty::FieldFlags,
+        name: &str,
+        descriptor: &str,
+        value: impl Into<Literal<'a>>,
+    ) -> Result<&mut Self> {
+        let value = value.into();
+        let fits = matches!(
+            (descriptor, value),
+            ("I" | "S" | "C" | "B" | "Z", Literal::Int(..))
+                | ("J", Literal::Long(..))
+                | ("F", Literal::Float(..))
+                | ("D", Literal::Double(..))
+                | ("Ljava/lang/String;", Literal::String(..))
+        );
+        if !fits {
+            generic_error!("{} can't start out as {:?}", descriptor, value);
+        }
+        let attribute = Attribute::ConstantValue(attr::ConstantValue {
+            attribute_name: self.constant_pool.utf8("ConstantValue")?,
+            constant_value: value.intern(&mut self.constant_pool)?,
+        });
+        let flags = flags | ty::FieldFlags::STATIC | ty::FieldFlags::FINAL;
+        self.add_field(flags, name, descriptor, vec![attribute])
+    }
+
+    fn add_field(
+        &mut self,
+        flags: ty::FieldFlags,
+        name: &str,
+        descriptor: &str,
+        attributes: Vec<Attribute>,
+    ) -> Result<&mut Self> {
+        if ty::FieldType::parse(descriptor).is_err() {
+            generic_error!("invalid field descriptor: {}", descriptor);
+        }
+        let field = ty::Field {
+            flags,
+            name: self.constant_pool.utf8(name)?,
+            descriptor: self.constant_pool.utf8(descriptor)?,
+            attributes,
+        };
+        self.fields.push(field);
+        Ok(self)
+    }
+
+    /// Starts a method, which is added to the class by `MethodBuilder::finish`
+    pub fn method(
+        &mut self,
+        flags: ty::MethodFlags,
+        name: &str,
+        descriptor: &str,
+    ) -> MethodBuilder<'_> {
+        MethodBuilder {
+            class: self,
+            flags,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            code: vec![],
+            label_count: 0,
+            labels: Labels::default(),
+            catches: vec![],
+            lines: vec![],
+            throws: vec![],
+        }
+    }
+
+    /// The constant pool, for the constants that the builder has no method for
+    pub fn constant_pool(&mut self) -> &mut ConstantPool {
+        &mut self.constant_pool
+    }
+
+    /// Makes the class file. Its stack map frames, `max_stack` and `max_locals` are
+    /// computed by verifying each method, so a class that doesn't verify is an error.
+    /// `hierarchy` is asked about every class other than this one
+    pub fn build(mut self, hierarchy: &mut dyn Hierarchy) -> Result<ty::ClassFile> {
+        let this_class = self.constant_pool.class(&self.name)?;
+        let super_class = match &self.super_class {
+            Some(name) => self.constant_pool.class(name)?,
+            None => ConstantIndex(0),
+        };
+        let (major_version, minor_version) = self.version;
+        let mut file = ty::ClassFile {
+            minor_version,
+            major_version,
+            constant_pool: self.constant_pool.into_vec(),
+            flags: self.flags,
+            this_class,
+            super_class,
+            interfaces: self.interfaces,
+            fields: self.fields,
+            methods: self.methods,
+            attributes: self.attributes,
+        };
+
+        let mut hierarchy = Building {
+            name: self.name,
+            super_class: self.super_class,
+            is_interface: self.flags.contains(ty::ClassFlags::INTERFACE),
+            hierarchy,
+        };
+        verify::compute_frames(&mut file, &mut hierarchy)?;
+        Ok(file)
+    }
+}
+
+/// The hierarchy with the class being built in it, which hasn't been loaded anywhere
+struct Building<'a> {
+    name: String,
+    super_class: Option<String>,
+    is_interface: bool,
+    hierarchy: &'a mut dyn Hierarchy,
+}
+
+impl Hierarchy for Building<'_> {
+    fn super_class(&mut self, class: &str) -> Result<Option<String>> {
+        match class == self.name {
+            true => Ok(self.super_class.clone()),
+            false => self.hierarchy.super_class(class),
+        }
+    }
+
+    fn is_interface(&mut self, class: &str) -> Result<bool> {
+        match class == self.name {
+            true => Ok(self.is_interface),
+            false => self.hierarchy.is_interface(class),
+        }
+    }
+}
+
+/// Where a branch can go. A label is made by `MethodBuilder::label`, and can be used
+/// before `MethodBuilder::place` puts it in front of the next instruction
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+/// Builds the code of a method, from instructions named by their mnemonics in lower case
+#[derive(Debug)]
+pub struct MethodBuilder<'a> {
+    class: &'a mut ClassBuilder,
+    flags: ty::MethodFlags,
+    name: String,
+    descriptor: String,
+    code: Vec<u8>,
+    label_count: usize,
+    labels: Labels<Label, ()>,
+    catches: Vec<([Label; 3], ConstantIndex)>,
+    lines: Vec<(usize, u16)>,
+    throws: Vec<ConstantIndex>,
+}
+
+impl<'a> MethodBuilder<'a> {
+    pub fn label(&mut self) -> Label {
+        self.label_count += 1;
+        Label(self.label_count - 1)
+    }
+
+    /// Places `label` at the next instruction
+    pub fn place(&mut self, label: Label) -> Result<&mut Self> {
+        if !self.labels.place(label, self.code.len()) {
+            generic_error!("label {} is placed twice", label.0);
+        }
+        Ok(self)
+    }
+
+    /// The next instructions come from `line` of the source
+    pub fn line(&mut self, line: u16) -> &mut Self {
+        let pc = self.code.len();
+        match self.lines.last_mut() {
+            Some(last) if last.0 == pc => last.1 = line,
+            _ => self.lines.push((pc, line)),
+        }
+        self
+    }
+
+    /// Handles the exceptions of `class`, or all of them for `None`, thrown from `start`
+    /// up to `end` by jumping to `handler`
+    pub fn catch(
+        &mut self,
+        start: Label,
+        end: Label,
+        handler: Label,
+        class: Option<&str>,
+    ) -> Result<&mut Self> {
+        let catch_type = match class {
+            Some(class) => self.class.constant_pool.class(class)?,
+            None => ConstantIndex(0),
+        };
+        self.catches.push(([start, end, handler], catch_type));
+        Ok(self)
+    }
+
+    /// Declares that the method throws `class`
+    pub fn throws(&mut self, class: &str) -> Result<&mut Self> {
+        let class = self.class.constant_pool.class(class)?;
+        self.throws.push(class);
+        Ok(self)
+    }
+
+    /// The opcode of `mnemonic`, when it has the shape of operands that is given
+    fn opcode(&self, mnemonic: &str, shapes: &[Shape]) -> Result<u8> {
+        match opcode(mnemonic) {
+            Some(opcode) if shapes.contains(&Shape::of(opcode)) => Ok(opcode),
+            Some(..) => generic_error!("{} can't be used like this", mnemonic),
+            None => generic_error!("unknown instruction: {}", mnemonic),
+        }
+    }
+
+    /// An instruction without operands
+    pub fn op(&mut self, mnemonic: &str) -> Result<&mut Self> {
+        let opcode = self.opcode(mnemonic, &[Shape::None])?;
+        self.code.push(opcode);
+        Ok(self)
+    }
+
+    /// Pushes `n` with the shortest instruction that can
+    pub fn int(&mut self, n: i32) -> Result<&mut Self> {
+        match n {
+            -1..=5 => self.code.push((0x03 + n) as u8), // iconst_<n>
+            _ if i8::try_from(n).is_ok() => self.code.extend(&[0x10, n as u8]),
+            _ if i16::try_from(n).is_ok() => {
+                self.code.push(0x11);
+                self.code.extend(&(n as i16).to_be_bytes());
+            }
+            _ => return self.ldc(n),
+        }
+        Ok(self)
+    }
+
+    /// Loads a constant with `ldc`, `ldc_w` or `ldc2_w`, whichever it needs
+    pub fn ldc<'l>(&mut self, value: impl Into<Literal<'l>>) -> Result<&mut Self> {
+        let value = value.into();
+        let index = value.intern(&mut self.class.constant_pool)?;
+        match value {
+            Literal::Long(..) | Literal::Double(..) => self.code.push(0x14),
+            _ if index.0 <= 0xFF => {
+                self.code.extend(&[0x12, index.0 as u8]);
+                return Ok(self);
+            }
+            _ => self.code.push(0x13),
+        }
+        self.code.extend(&index.0.to_be_bytes());
+        Ok(self)
+    }
+
+    /// A load, a store or `ret`, which is made `wide` when `index` needs it
+    pub fn local(&mut self, mnemonic: &str, index: u16) -> Result<&mut Self> {
+        let opcode = self.opcode(mnemonic, &[Shape::Local])?;
+        match u8::try_from(index) {
+            Ok(index) => self.code.extend(&[opcode, index]),
+            Err(..) => {
+                self.code.extend(&[0xC4, opcode]);
+                self.code.extend(&index.to_be_bytes());
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn iinc(&mut self, index: u16, delta: i16) -> Result<&mut Self> {
+        match (u8::try_from(index), i8::try_from(delta)) {
+            (Ok(index), Ok(delta)) => self.code.extend(&[0x84, index, delta as u8]),
+            _ => {
+                self.code.extend(&[0xC4, 0x84]);
+                self.code.extend(&index.to_be_bytes());
+                self.code.extend(&delta.to_be_bytes());
+            }
+        }
+        Ok(self)
+    }
+
+    /// A branch to `target`
+    pub fn jump(&mut self, mnemonic: &str, target: Label) -> Result<&mut Self> {
+        let opcode = self.opcode(mnemonic, &[Shape::Branch, Shape::BranchWide])?;
+        let pc = self.code.len();
+        self.code.push(opcode);
+        let wide = Shape::of(opcode) == Shape::BranchWide;
+        self.labels.branch(&mut self.code, (), pc, target, wide);
+        Ok(self)
+    }
+
+    /// `getstatic`, `putstatic`, `getfield` or `putfield`
+    pub fn field(
+        &mut self,
+        mnemonic: &str,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<&mut Self> {
+        let opcode = self.opcode(mnemonic, &[Shape::Member])?;
+        if !(0xB2..=0xB5).contains(&opcode) {
+            generic_error!("{} doesn't access a field", mnemonic);
+        }
+        let index = self.class.constant_pool.field(class, name, descriptor)?;
+        self.code.push(opcode);
+        self.code.extend(&index.0.to_be_bytes());
+        Ok(self)
+    }
+
+    /// `invokevirtual`, `invokespecial`, `invokestatic` or `invokeinterface`, where only
+    /// `invokeinterface` refers to an interface method
+    pub fn invoke(
+        &mut self,
+        mnemonic: &str,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<&mut Self> {
+        let opcode = self.opcode(mnemonic, &[Shape::Member, Shape::Interface])?;
+        if !(0xB6..=0xB9).contains(&opcode) {
+            generic_error!("{} doesn't invoke a method", mnemonic);
+        }
+        self.invoke_member(opcode, class, name, descriptor, opcode == 0xB9)
+    }
+
+    /// `invokestatic` or `invokespecial` of a method that an interface declares, which is
+    /// referred to by an interface method reference
+    pub fn invoke_interface_member(
+        &mut self,
+        mnemonic: &str,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<&mut Self> {
+        let opcode = self.opcode(mnemonic, &[Shape::Member])?;
+        if !(0xB7..=0xB8).contains(&opcode) {
+            generic_error!("{} can't invoke an interface's own method", mnemonic);
+        }
+        self.invoke_member(opcode, class, name, descriptor, true)
+    }
+
+    fn invoke_member(
+        &mut self,
+        opcode: u8,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        interface: bool,
+    ) -> Result<&mut Self> {
+        let pool = &mut self.class.constant_pool;
+        let index = if interface {
+            pool.interface_method(class, name, descriptor)?
+        } else {
+            pool.method(class, name, descriptor)?
+        };
+        self.code.push(opcode);
+        self.code.extend(&index.0.to_be_bytes());
+        if opcode == 0xB9 {
+            let count = match ty::MethodDescriptor::parse(descriptor) {
+                Ok(descriptor) => descriptor.param_slots() + 1,
+                Err(..) => generic_error!("invalid method descriptor: {}", descriptor),
+            };
+            self.code.extend(&[count as u8, 0]);
+        }
+        Ok(self)
+    }
+
+    /// `new`, `anewarray`, `checkcast` or `instanceof`
+    pub fn class(&mut self, mnemonic: &str, class: &str) -> Result<&mut Self> {
+        let opcode = self.opcode(mnemonic, &[Shape::Class])?;
+        let index = self.class.constant_pool.class(class)?;
+        self.code.push(opcode);
+        self.code.extend(&index.0.to_be_bytes());
+        Ok(self)
+    }
+
+    /// `newarray` of a primitive type, like `int`
+    pub fn new_array(&mut self, element: &str) -> Result<&mut Self> {
+        match ARRAY_TYPES.iter().find(|(ty, _)| *ty == element) {
+            Some(&(_, ty)) => self.code.extend(&[0xBC, ty]),
+            None => generic_error!("unknown array type: {}", element),
+        }
+        Ok(self)
+    }
+
+    /// `multianewarray` of the array type `descriptor`
+    pub fn multi_array(&mut self, descriptor: &str, dimensions: u8) -> Result<&mut Self> {
+        let index = self.class.constant_pool.class(descriptor)?;
+        self.code.push(0xC5);
+        self.code.extend(&index.0.to_be_bytes());
+        self.code.push(dimensions);
+        Ok(self)
+    }
+
+    /// Jumps to `targets[key - low]`, or to `default` when the key is out of range
+    pub fn table_switch(
+        &mut self,
+        low: i32,
+        targets: &[Label],
+        default: Label,
+    ) -> Result<&mut Self> {
+        let high = match i32::try_from(targets.len()) {
+            Ok(len) if len > 0 => low.checked_add(len - 1),
+            _ => None,
+        };
+        let high = match high {
+            Some(high) => high,
+            None => generic_error!("tableswitch needs between 1 and 2^31 targets"),
+        };
+        let pc = self.code.len();
+        self.code.push(0xAA);
+        pad(&mut self.code);
+        self.labels.branch(&mut self.code, (), pc, default, true);
+        self.code.extend(&low.to_be_bytes());
+        self.code.extend(&high.to_be_bytes());
+        for &target in targets {
+            self.labels.branch(&mut self.code, (), pc, target, true);
+        }
+        Ok(self)
+    }
+
+    /// Jumps to the label of the key, or to `default` when there isn't one
+    pub fn lookup_switch(&mut self, pairs: &[(i32, Label)], default: Label) -> Result<&mut Self> {
+        let mut pairs = pairs.to_vec();
+        pairs.sort_by_key(|&(key, _)| key);
+        if pairs.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            generic_error!("lookupswitch has the same key twice");
+        }
+        let pc = self.code.len();
+        self.code.push(0xAB);
+        pad(&mut self.code);
+        self.labels.branch(&mut self.code, (), pc, default, true);
+        self.code.extend(&(pairs.len() as i32).to_be_bytes());
+        for (key, target) in pairs {
+            self.code.extend(&key.to_be_bytes());
+            self.labels.branch(&mut self.code, (), pc, target, true);
+        }
+        Ok(self)
+    }
+
+    fn pc_u16(&self, label: Label) -> Result<u16> {
+        match self.labels.pc_u16(&label) {
+            Ok(pc) => Ok(pc),
+            Err(err) => self.unresolved(label, err),
+        }
+    }
+
+    /// The error for `label`
+    fn unresolved<T>(&self, label: Label, err: Unresolved) -> Result<T> {
+        match err {
+            Unresolved::Missing => {
+                generic_error!("label {} of {} is never placed", label.0, self.name)
+            }
+            Unresolved::TooFar => generic_error!("a branch in {} is too far", self.name),
+            Unresolved::TooLong => generic_error!("the code of {} is too long", self.name),
+        }
+    }
+
+    /// Adds the method to its class
+    pub fn finish(mut self) -> Result<()> {
+        let is_static = self.flags.contains(ty::MethodFlags::STATIC);
+        let params = match ty::MethodDescriptor::parse(&self.descriptor) {
+            Ok(descriptor) => descriptor.param_slots() + if is_static { 0 } else { 1 },
+            Err(..) => generic_error!("invalid method descriptor: {}", self.descriptor),
+        };
+        let has_code = !self
+            .flags
+            .intersects(ty::MethodFlags::ABSTRACT | ty::MethodFlags::NATIVE);
+        match (has_code, self.code.is_empty()) {
+            (true, true) => generic_error!("{} has no code", self.name),
+            (false, false) => generic_error!("{} can't have code", self.name),
+            _ => {}
+        }
+
+        let mut attributes = vec![];
+        if has_code {
+            let code = self.code_attribute(params)?;
+            attributes.push(Attribute::Code(code));
+        }
+        let pool = &mut self.class.constant_pool;
+        if !self.throws.is_empty() {
+            attributes.push(Attribute::Exceptions(attr::Exceptions {
+                attribute_name: pool.utf8("Exceptions")?,
+                index_table: std::mem::take(&mut self.throws),
+            }));
+        }
+        let name = pool.utf8(&self.name)?;
+        let descriptor = pool.utf8(&self.descriptor)?;
+        let method = ty::Method::new(self.flags, name, descriptor, attributes, pool.as_slice())?;
+        self.class.methods.push(method);
+        Ok(())
+    }
+
+    /// The `Code` attribute, with room for just the parameters until the frames are
+    /// computed by `ClassBuilder::build`
+    fn code_attribute(&mut self, params: usize) -> Result<attr::Code> {
+        if let Err(((), label, err)) = self.labels.resolve(&mut self.code) {
+            return self.unresolved(label, err);
+        }
+
+        let mut exception_table = vec![];
+        for ([start, end, handler], catch_type) in &self.catches {
+            exception_table.push(attr::ExceptionTableRow {
+                start_pc: self.pc_u16(*start)?,
+                end_pc: self.pc_u16(*end)?,
+                handler_pc: self.pc_u16(*handler)?,
+                catch_type: *catch_type,
+            });
+        }
+
+        if u16::try_from(self.code.len()).is_err() {
+            generic_error!("the code of {} is too long", self.name);
+        }
+        let len = self.code.len();
+        let pool = &mut self.class.constant_pool;
+        let mut attributes = vec![];
+        if !self.lines.is_empty() {
+            attributes.push(Attribute::LineNumberTable(attr::LineNumberTable {
+                attribute_name: pool.utf8("LineNumberTable")?,
+                table: self
+                    .lines
+                    .iter()
+                    .filter(|&&(pc, _)| pc < len)
+                    .map(|&(pc, line)| (pc as u16, line))
+                    .collect(),
+            }));
+        }
+        Ok(attr::Code {
+            attribute_name: pool.utf8("Code")?,
+            max_stack: 0,
+            max_locals: params as u16,
+            code: std::mem::take(&mut self.code),
+            exception_table,
+            attributes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exec::interpreter::Interpreter;
+    use std::rc::Rc;
+    use ty::{FieldFlags, MethodFlags};
+
+    fn load(file: ty::ClassFile, interpreter: &mut Interpreter) {
+        let mut data = vec![];
+        file.write(&mut data).unwrap();
+        let file = ty::ClassFile::read(&mut data.as_slice()).unwrap();
+        verify::verify_class(&file, interpreter).unwrap();
+        interpreter.load_class(Rc::new(file));
+    }
+
+    #[test]
+    fn adapter() {
+        let mut class = ClassBuilder::new("Adapter");
+        class
+            .constant_field(FieldFlags::PUBLIC, "BASE", "J", 1_000_000_000_000i64)
+            .unwrap();
+        class
+            .field(FieldFlags::PRIVATE | FieldFlags::STATIC, "calls", "I")
+            .unwrap();
+
+        // sums 1..=n with a loop, and adds the long constant twice
+        let public_static = MethodFlags::PUBLIC | MethodFlags::STATIC;
+        let mut method = class.method(public_static, "sum", "(I)J");
+        let (top, done) = (method.label(), method.label());
+        method.line(3).op("lconst_0").unwrap();
+        method.local("lstore", 1).unwrap();
+        method.place(top).unwrap().local("iload", 0).unwrap();
+        method.jump("ifle", done).unwrap();
+        method.local("lload", 1).unwrap().local("iload", 0).unwrap();
+        method.op("i2l").unwrap().op("ladd").unwrap();
+        method.local("lstore", 1).unwrap().iinc(0, -1).unwrap();
+        method.jump("goto", top).unwrap();
+        method.place(done).unwrap().local("lload", 1).unwrap();
+        method
+            .ldc(1_000_000_000_000i64)
+            .unwrap()
+            .op("ladd")
+            .unwrap();
+        method.field("getstatic", "Adapter", "BASE", "J").unwrap();
+        method.op("ladd").unwrap().op("lreturn").unwrap();
+        method.finish().unwrap();
+
+        let mut method = class.method(public_static, "name", "(I)Ljava/lang/String;");
+        let labels = [method.label(), method.label(), method.label()];
+        method.local("iload", 0).unwrap();
+        method
+            .lookup_switch(&[(7, labels[1]), (-3, labels[0])], labels[2])
+            .unwrap();
+        for (label, name) in labels.iter().zip(&["minus three", "seven", "other"]) {
+            method
+                .place(*label)
+                .unwrap()
+                .ldc(*name)
+                .unwrap()
+                .op("areturn")
+                .unwrap();
+        }
+        method.finish().unwrap();
+
+        let mut interpreter = Interpreter::default();
+        let file = class.build(&mut interpreter).unwrap();
+
+        // the strings, class and name-and-type of Adapter.BASE are only in there once
+        let pool = &file.constant_pool;
+        let count = |constant: &Constant| pool.iter().filter(|c| *c == constant).count();
+        assert_eq!(count(&Constant::Utf8("Adapter".to_string())), 1);
+        assert_eq!(count(&Constant::Utf8("BASE".to_string())), 1);
+        assert_eq!(count(&Constant::Long(1_000_000_000_000)), 1);
+        let long = pool
+            .iter()
+            .position(|c| *c == Constant::Long(1_000_000_000_000));
+        assert_eq!(pool[long.unwrap() + 1], Constant::Padding);
+        assert!(file.methods[0].get_code().unwrap().max_stack >= 4);
+
+        load(file, &mut interpreter);
+        let sum: i64 = interpreter.invoke("Adapter", "sum", (4,)).unwrap();
+        assert_eq!(sum, 2_000_000_000_010);
+    }
+
+    #[test]
+    fn catches() {
+        let mut class = ClassBuilder::new("Catcher");
+        let mut method = class.method(MethodFlags::STATIC, "divide", "(II)I");
+        let (start, end, handler) = (method.label(), method.label(), method.label());
+        method.place(start).unwrap().local("iload", 0).unwrap();
+        method.local("iload", 1).unwrap().op("idiv").unwrap();
+        method.place(end).unwrap().op("ireturn").unwrap();
+        method.place(handler).unwrap().op("pop").unwrap();
+        method.int(-100_000).unwrap().op("ireturn").unwrap();
+        method
+            .catch(start, end, handler, Some("java/lang/ArithmeticException"))
+            .unwrap();
+        method.finish().unwrap();
+
+        let mut interpreter = Interpreter::default();
+        let file = class.build(&mut interpreter).unwrap();
+        load(file, &mut interpreter);
+        let quotient: i32 = interpreter.invoke("Catcher", "divide", (9, 2)).unwrap();
+        assert_eq!(quotient, 4);
+        let quotient: i32 = interpreter.invoke("Catcher", "divide", (9, 0)).unwrap();
+        assert_eq!(quotient, -100_000);
+    }
+
+    #[test]
+    fn errors() {
+        let mut class = ClassBuilder::new("Broken");
+        let error = |result: Result<&mut MethodBuilder<'_>>| result.err().unwrap().to_string();
+
+        let mut method = class.method(MethodFlags::STATIC, "m", "()V");
+        assert_eq!(
+            error(method.op("frobnicate")),
+            "unknown instruction: frobnicate"
+        );
+        assert_eq!(error(method.op("iload")), "iload can't be used like this");
+        assert_eq!(
+            error(method.field("invokestatic", "A", "b", "I")),
+            "invokestatic doesn't access a field"
+        );
+        let nowhere = method.label();
+        method.jump("goto", nowhere).unwrap();
+        let error = method.finish().err().unwrap().to_string();
+        assert_eq!(error, "label 0 of m is never placed");
+
+        let method = class.method(MethodFlags::STATIC, "empty", "()V");
+        let error = method.finish().err().unwrap().to_string();
+        assert_eq!(error, "empty has no code");
+
+        // the stack underflows
+        let mut method = class.method(MethodFlags::STATIC, "bad", "()V");
+        method.op("pop").unwrap().op("return").unwrap();
+        method.finish().unwrap();
+        assert!(class.build(&mut Interpreter::default()).is_err());
+    }
+}
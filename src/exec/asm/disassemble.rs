@@ -0,0 +1,765 @@
+//! Writing a class file out as the text that `assemble` reads
+use super::*;
+
+use attr::{Attribute, ElementValue, StackMapFrame, TypeAnnotationTarget, VerificationType};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use ty::{Constant, ConstantIndex};
+
+/// Disassembles the class into text, which assembles back into the same class
+pub fn disassemble(file: &ty::ClassFile) -> Result<String> {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        ".version {} {}",
+        file.major_version, file.minor_version
+    );
+    let _ = writeln!(
+        out,
+        ".class {}{}",
+        flags(file.flags.bits(), &CLASS_FLAGS),
+        as_name(file.get_class_name(), &CLASS_FLAGS)
+    );
+    if let Some(super_class) = file.super_class_name()? {
+        let _ = writeln!(out, ".super {}", super_class);
+    }
+    for &interface in &file.interfaces {
+        let _ = writeln!(out, ".implements {}", file.class_name(interface)?);
+    }
+
+    let (mut inner_classes, mut bootstrap_methods) = (vec![], vec![]);
+    for attribute in &file.attributes {
+        match attribute {
+            Attribute::SourceFile(source) => {
+                let _ = writeln!(out, ".source {}", quote(file.utf8(source.source_file)?));
+            }
+            Attribute::InnerClasses(classes) => inner_classes.extend(&classes.classes),
+            Attribute::BootstrapMethods(methods) => bootstrap_methods.extend(&methods.methods),
+            attribute => member_attribute(file, &mut out, "", attribute)?,
+        }
+    }
+    // the assembler puts these last
+    for class in inner_classes {
+        let class_name = |index| match index {
+            ConstantIndex(0) => Ok("none"),
+            index => file.class_name(index),
+        };
+        let name = match class.inner_class_name {
+            ConstantIndex(0) => "none",
+            index => file.utf8(index)?,
+        };
+        let _ = writeln!(
+            out,
+            ".innerclass {}{} {} {}",
+            flags(class.flags.bits(), &INNER_CLASS_FLAGS),
+            as_name(class_name(class.inner_class)?, &INNER_CLASS_FLAGS),
+            class_name(class.outer_class)?,
+            name,
+        );
+    }
+    for (index, method) in bootstrap_methods.into_iter().enumerate() {
+        let _ = write!(
+            out,
+            ".bootstrap {} {}",
+            index,
+            constant(file, method.method_ref)?
+        );
+        for &argument in &method.arguments {
+            let _ = write!(out, " {}", constant(file, argument)?);
+        }
+        out.push('\n');
+    }
+
+    for field in &file.fields {
+        let _ = write!(
+            out,
+            "\n.field {}{} {}",
+            flags(field.flags.bits(), &FIELD_FLAGS),
+            as_name(file.utf8(field.name)?, &FIELD_FLAGS),
+            file.utf8(field.descriptor)?
+        );
+        let mut block = String::new();
+        for attribute in &field.attributes {
+            match attribute {
+                Attribute::ConstantValue(value) => {
+                    let _ = write!(out, " = {}", constant(file, value.constant_value)?);
+                }
+                attribute => member_attribute(file, &mut block, "    ", attribute)?,
+            }
+        }
+        out.push('\n');
+        if !block.is_empty() {
+            let _ = writeln!(out, "{}.end field", block);
+        }
+    }
+
+    for method in &file.methods {
+        let _ = writeln!(
+            out,
+            "\n.method {}{} {}",
+            flags(method.flags.bits(), &METHOD_FLAGS),
+            as_name(method.name(), &METHOD_FLAGS),
+            file.utf8(method.descriptor)?
+        );
+        // the assembler puts the exceptions after the other attributes
+        for attribute in &method.attributes {
+            if let Attribute::Exceptions(exceptions) = attribute {
+                for &class in &exceptions.index_table {
+                    let _ = writeln!(out, "    .throws {}", file.class_name(class)?);
+                }
+            }
+        }
+        for attribute in &method.attributes {
+            match attribute {
+                Attribute::Code(code) => disassemble_code(file, &mut out, code)?,
+                Attribute::Exceptions(..) => {}
+                attribute => member_attribute(file, &mut out, "    ", attribute)?,
+            }
+        }
+        let _ = writeln!(out, ".end method");
+    }
+    Ok(out)
+}
+
+/// The flags that are set, each followed by a space
+fn flags(bits: u16, table: &[(&str, u16)]) -> String {
+    table
+        .iter()
+        .filter(|(_, flag)| bits & flag != 0)
+        .map(|(name, _)| format!("{} ", name))
+        .collect()
+}
+
+/// `name`, which is quoted when it is one of the flags that go before it
+fn as_name(name: &str, table: &[(&str, u16)]) -> String {
+    if table.iter().any(|(flag, _)| *flag == name) {
+        quote(name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// The attributes that classes, fields and methods can have, other than their own
+fn member_attribute(
+    file: &ty::ClassFile,
+    out: &mut String,
+    indent: &str,
+    attribute: &Attribute,
+) -> Result<()> {
+    match attribute {
+        Attribute::Signature(signature) => {
+            let signature = quote(file.utf8(signature.signature)?);
+            let _ = writeln!(out, "{}.signature {}", indent, signature);
+        }
+        Attribute::Deprecated(..) => {
+            let _ = writeln!(out, "{}.deprecated", indent);
+        }
+        Attribute::Synthetic(..) => {
+            let _ = writeln!(out, "{}.synthetic", indent);
+        }
+        Attribute::EnclosingMethod(enclosing) => {
+            let class = file.class_name(enclosing.class)?;
+            let _ = match enclosing.method {
+                ConstantIndex(0) => writeln!(out, "{}.enclosing {}", indent, class),
+                method => {
+                    let (name, descriptor) = file.name_and_type(method)?;
+                    writeln!(
+                        out,
+                        "{}.enclosing {} {} {}",
+                        indent, class, name, descriptor
+                    )
+                }
+            };
+        }
+        Attribute::SourceDebugExtension(extension) => {
+            match std::str::from_utf8(&extension.debug_extension) {
+                Ok(text) => {
+                    let _ = writeln!(out, "{}.debugextension {}", indent, quote(text));
+                }
+                Err(..) => {
+                    generic_error!("can't disassemble a SourceDebugExtension that isn't UTF-8")
+                }
+            }
+        }
+        Attribute::AnnotationDefault(default) => {
+            let value = element_value(file, &default.value)?;
+            let _ = writeln!(out, "{}.annotationdefault {}", indent, value);
+        }
+        Attribute::MethodParameters(parameters) => {
+            for parameter in &parameters.parameters {
+                let name = match parameter.name {
+                    ConstantIndex(0) => "none".to_string(),
+                    index => as_name(file.utf8(index)?, &PARAMETER_FLAGS),
+                };
+                let flags = flags(parameter.flags.bits(), &PARAMETER_FLAGS);
+                let _ = writeln!(out, "{}.parameter {}{}", indent, flags, name);
+            }
+        }
+        Attribute::RuntimeVisibleAnnotations(attr::RuntimeVisibleAnnotations {
+            annotations,
+            ..
+        })
+        | Attribute::RuntimeInvisibleAnnotations(attr::RuntimeInvisibleAnnotations {
+            annotations,
+            ..
+        }) => {
+            let visibility = visibility(attribute);
+            for annotation in annotations {
+                let annotation = self::annotation(file, annotation)?;
+                let _ = writeln!(out, "{}.annotation {} {}", indent, visibility, annotation);
+            }
+        }
+        Attribute::RuntimeVisibleParameterAnnotations(
+            attr::RuntimeVisibleParameterAnnotations {
+                annotations_by_param_index,
+                ..
+            },
+        )
+        | Attribute::RuntimeInvisibleParameterAnnotations(
+            attr::RuntimeInvisibleParameterAnnotations {
+                annotations_by_param_index,
+                ..
+            },
+        ) => {
+            let visibility = visibility(attribute);
+            for (index, annotations) in annotations_by_param_index.iter().enumerate() {
+                let _ = write!(out, "{}.paramannotation {} {}", indent, visibility, index);
+                for annotation in &annotations.0 {
+                    let _ = write!(out, " {}", self::annotation(file, annotation)?);
+                }
+                out.push('\n');
+            }
+        }
+        Attribute::RuntimeVisibleTypeAnnotations(attr::RuntimeVisibleTypeAnnotations {
+            annotations,
+            ..
+        })
+        | Attribute::RuntimeInvisibleTypeAnnotations(attr::RuntimeInvisibleTypeAnnotations {
+            annotations,
+            ..
+        }) => {
+            for annotation in annotations {
+                let annotation = type_annotation(file, visibility(attribute), annotation)?;
+                let _ = writeln!(out, "{}.typeannotation {}", indent, annotation);
+            }
+        }
+        Attribute::Unknown(unknown) => {
+            let _ = writeln!(
+                out,
+                "{}.attribute {}",
+                indent,
+                self::unknown(file, unknown)?
+            );
+        }
+        attribute => generic_error!("can't disassemble the {} attribute", attribute.name()),
+    }
+    Ok(())
+}
+
+/// An attribute that isn't read, as `<name> <bytes in hex>`
+fn unknown(file: &ty::ClassFile, unknown: &attr::Unknown) -> Result<String> {
+    let name = file.utf8(unknown.attribute_name)?;
+    let plain = !name.is_empty() && !name.starts_with(';') && !name.contains(is_separator);
+    let mut out = if plain { name.to_string() } else { quote(name) };
+    if !unknown.info.is_empty() {
+        out.push(' ');
+    }
+    for byte in &unknown.info {
+        let _ = write!(out, "{:02x}", byte);
+    }
+    Ok(out)
+}
+
+/// Whether `c` ends a word
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == '"'
+}
+
+/// Whether annotations are `visible` at run time or `invisible`
+fn visibility(attribute: &Attribute) -> &'static str {
+    match attribute {
+        Attribute::RuntimeInvisibleAnnotations(..)
+        | Attribute::RuntimeInvisibleParameterAnnotations(..)
+        | Attribute::RuntimeInvisibleTypeAnnotations(..) => "invisible",
+        _ => "visible",
+    }
+}
+
+/// An annotation as `<type> ( <name> = <value> ... )`
+fn annotation(file: &ty::ClassFile, annotation: &attr::Annotation) -> Result<String> {
+    let mut out = format!("{} (", file.utf8(annotation.type_index)?);
+    for (name, value) in &annotation.indices_with_values {
+        let _ = write!(
+            out,
+            " {} = {}",
+            file.utf8(*name)?,
+            element_value(file, value)?
+        );
+    }
+    out.push_str(" )");
+    Ok(out)
+}
+
+/// The value of an annotation element, after the tag of its kind
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.16.1
+fn element_value(file: &ty::ClassFile, value: &ElementValue) -> Result<String> {
+    Ok(match value {
+        ElementValue::Byte(index) => format!("B {}", constant(file, *index)?),
+        ElementValue::Char(index) => format!("C {}", constant(file, *index)?),
+        ElementValue::Double(index) => format!("D {}", constant(file, *index)?),
+        ElementValue::Float(index) => format!("F {}", constant(file, *index)?),
+        ElementValue::Integer(index) => format!("I {}", constant(file, *index)?),
+        ElementValue::Long(index) => format!("J {}", constant(file, *index)?),
+        ElementValue::Short(index) => format!("S {}", constant(file, *index)?),
+        ElementValue::Boolean(index) => format!("Z {}", constant(file, *index)?),
+        ElementValue::String(index) => format!("s {}", quote(file.utf8(*index)?)),
+        ElementValue::Enum { ty, val } => format!("e {} {}", file.utf8(*ty)?, file.utf8(*val)?),
+        ElementValue::Class(index) => format!("c {}", file.utf8(*index)?),
+        ElementValue::Anotation(annotation) => format!("@ {}", self::annotation(file, annotation)?),
+        ElementValue::Array(values) => {
+            let mut out = "[".to_string();
+            for value in values {
+                let _ = write!(out, " {}", element_value(file, value)?);
+            }
+            out.push_str(" ]");
+            out
+        }
+    })
+}
+
+/// A type annotation as `<visibility> <target type> <target> [path <kind>:<index> ...]
+/// <annotation>`, where the pcs of the target are labels
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.20
+fn type_annotation(
+    file: &ty::ClassFile,
+    visibility: &str,
+    annotation: &attr::TypeAnnotation,
+) -> Result<String> {
+    use TypeAnnotationTarget::*;
+    let mut out = format!("{} {:#04x}", visibility, annotation.target_type);
+    let _ = match &annotation.target {
+        TypeParameter(index) | FormalParameter(index) => write!(out, " {}", index),
+        Supertype(index) | Throws(index) | Catch(index) => write!(out, " {}", index),
+        TypeParameterBound {
+            type_parameter,
+            bound,
+        } => write!(out, " {} {}", type_parameter, bound),
+        Empty => Ok(()),
+        LocalVariable(ranges) => {
+            let _ = write!(out, " {}", ranges.len());
+            for &(start, length, index) in ranges {
+                let end = usize::from(start) + usize::from(length);
+                let _ = write!(out, " L{} L{} {}", start, end, index);
+            }
+            Ok(())
+        }
+        Offset(offset) => write!(out, " L{}", offset),
+        TypeArgument {
+            offset,
+            type_argument,
+        } => write!(out, " L{} {}", offset, type_argument),
+    };
+    if !annotation.target_path.is_empty() {
+        out.push_str(" path");
+        for (kind, index) in &annotation.target_path {
+            let _ = write!(out, " {}:{}", kind, index);
+        }
+    }
+    let _ = write!(out, " {}", self::annotation(file, &annotation.annotation)?);
+    Ok(out)
+}
+
+/// The pcs the target of a type annotation on code refers to
+fn type_annotation_pcs(annotation: &attr::TypeAnnotation) -> Vec<usize> {
+    match &annotation.target {
+        TypeAnnotationTarget::LocalVariable(ranges) => ranges
+            .iter()
+            .flat_map(|&(start, length, _)| {
+                let start = usize::from(start);
+                vec![start, start + usize::from(length)]
+            })
+            .collect(),
+        TypeAnnotationTarget::Offset(offset)
+        | TypeAnnotationTarget::TypeArgument { offset, .. } => vec![usize::from(*offset)],
+        _ => vec![],
+    }
+}
+
+/// A constant as a literal
+fn constant(file: &ty::ClassFile, index: ConstantIndex) -> Result<String> {
+    Ok(match index.lookup(&file.constant_pool)? {
+        Constant::Integer(n) => format!("{}", *n as i32),
+        Constant::Float(n) if n.is_nan() => "NaNf".to_string(),
+        Constant::Float(n) => format!("{:?}f", n),
+        Constant::Long(n) => format!("{}L", *n as i64),
+        Constant::Double(n) if n.is_nan() => "NaNd".to_string(),
+        Constant::Double(n) => format!("{:?}d", n),
+        Constant::ClassRef(..) => format!("Class {}", file.class_name(index)?),
+        Constant::StringRef(s) => quote(file.utf8(*s)?),
+        Constant::FieldRef(..) | Constant::MethodRef(..) | Constant::InterfaceMethodRef(..) => {
+            let member = file.member_ref(index)?;
+            let kind = match index.lookup(&file.constant_pool)? {
+                Constant::FieldRef(..) => "Field",
+                Constant::MethodRef(..) => "Method",
+                _ => "InterfaceMethod",
+            };
+            format!(
+                "{} {} {} {}",
+                kind, member.class, member.name, member.descriptor
+            )
+        }
+        Constant::InvokeDynamicRef(indy) => {
+            let (name, descriptor) = file.name_and_type(indy.name_and_type)?;
+            format!("InvokeDynamic {} {} {}", indy.bootstrap.0, name, descriptor)
+        }
+        Constant::MethodHandleRef(handle) => format!(
+            "MethodHandle {} {}",
+            METHOD_HANDLE_KINDS[usize::from(handle.kind()) - 1],
+            constant(file, handle.reference())?
+        ),
+        Constant::MethodType(descriptor) => format!("MethodType {}", file.utf8(*descriptor)?),
+        constant => generic_error!("can't load constant: {:?}", constant),
+    })
+}
+
+fn disassemble_code(file: &ty::ClassFile, out: &mut String, code: &attr::Code) -> Result<()> {
+    let _ = writeln!(out, "    .limit stack {}", code.max_stack);
+    let _ = writeln!(out, "    .limit locals {}", code.max_locals);
+
+    let bytes = &code.code;
+    let mut instructions = vec![];
+    let mut pc = 0;
+    while pc < bytes.len() {
+        let size = match Instruction::decode(bytes, pc) {
+            Some(instruction) => instruction.size(),
+            None => generic_error!("invalid instruction at {}: {:#04X}", pc, bytes[pc]),
+        };
+        instructions.push(pc);
+        pc += size;
+    }
+
+    // what refers to a pc needs a label there
+    let mut labels = BTreeSet::new();
+    for &pc in &instructions {
+        labels.extend(targets(bytes, pc));
+    }
+    for row in &code.exception_table {
+        labels.extend(&[row.start_pc, row.end_pc, row.handler_pc].map(usize::from));
+    }
+
+    let mut lines = BTreeMap::<usize, Vec<u16>>::new();
+    let mut frames = BTreeMap::new();
+    let (mut variables, mut annotations, mut unknowns) = (vec![], vec![], vec![]);
+    for attribute in &code.attributes {
+        match attribute {
+            Attribute::LineNumberTable(table) => {
+                for &(pc, line) in &table.table {
+                    lines.entry(usize::from(pc)).or_default().push(line);
+                }
+            }
+            Attribute::StackMapTable(table) => {
+                let mut pc = None;
+                for frame in &table.entries {
+                    let next = match pc {
+                        None => offset(frame),
+                        Some(pc) => pc + offset(frame) + 1,
+                    };
+                    pc = Some(next);
+                    frames.insert(next, frame);
+                    labels.extend(frame_types(frame).filter_map(|ty| match ty {
+                        VerificationType::Uninitialized(pc) => Some(usize::from(*pc)),
+                        _ => None,
+                    }));
+                }
+            }
+            Attribute::LocalVariableTable(table) => {
+                for var in &table.variables {
+                    let (start, end) = (var.start_pc, var.start_pc + var.length);
+                    labels.extend(&[usize::from(start), usize::from(end)]);
+                    variables.push(format!(
+                        "    .var {} is {} {} from L{} to L{}",
+                        var.index,
+                        file.utf8(var.name)?,
+                        file.utf8(var.descriptor)?,
+                        start,
+                        end
+                    ));
+                }
+            }
+            Attribute::LocalVariableTypeTable(table) => {
+                for var in &table.variables_types {
+                    let (start, end) = (var.start_pc, var.start_pc + var.length);
+                    labels.extend(&[usize::from(start), usize::from(end)]);
+                    variables.push(format!(
+                        "    .vartype {} is {} {} from L{} to L{}",
+                        var.index,
+                        file.utf8(var.name)?,
+                        file.utf8(var.signature)?,
+                        start,
+                        end
+                    ));
+                }
+            }
+            Attribute::RuntimeVisibleTypeAnnotations(attr::RuntimeVisibleTypeAnnotations {
+                annotations: table,
+                ..
+            })
+            | Attribute::RuntimeInvisibleTypeAnnotations(attr::RuntimeInvisibleTypeAnnotations {
+                annotations: table,
+                ..
+            }) => {
+                for annotation in table {
+                    labels.extend(type_annotation_pcs(annotation));
+                    annotations.push(type_annotation(file, visibility(attribute), annotation)?);
+                }
+            }
+            Attribute::Unknown(unknown) => unknowns.push(self::unknown(file, unknown)?),
+            attribute => generic_error!("can't disassemble the {} attribute", attribute.name()),
+        }
+    }
+
+    for row in &code.exception_table {
+        let class = match row.catch_type {
+            ConstantIndex(0) => "all",
+            class => file.class_name(class)?,
+        };
+        let _ = writeln!(
+            out,
+            "    .catch {} from L{} to L{} using L{}",
+            class, row.start_pc, row.end_pc, row.handler_pc
+        );
+    }
+
+    let is_instruction = |pc: &usize| instructions.binary_search(pc).is_ok() || *pc == bytes.len();
+    if let Some(pc) = labels.iter().find(|pc| !is_instruction(pc)) {
+        generic_error!("there is no instruction at {}", pc);
+    }
+    if let Some(pc) = frames
+        .keys()
+        .find(|pc| instructions.binary_search(pc).is_err())
+    {
+        generic_error!("there is no instruction at {} for its stack frame", pc);
+    }
+
+    for &pc in &instructions {
+        if labels.contains(&pc) {
+            let _ = writeln!(out, "L{}:", pc);
+        }
+        for line in lines.get(&pc).into_iter().flatten() {
+            let _ = writeln!(out, "    .line {}", line);
+        }
+        if let Some(frame) = frames.get(&pc) {
+            let _ = writeln!(out, "    .stack {}", stack_frame(file, frame)?);
+        }
+        instruction(file, out, bytes, pc)?;
+    }
+    if labels.contains(&bytes.len()) {
+        let _ = writeln!(out, "L{}:", bytes.len());
+    }
+
+    for var in variables {
+        let _ = writeln!(out, "{}", var);
+    }
+    for annotation in annotations {
+        let _ = writeln!(out, "    .typeannotation {}", annotation);
+    }
+    for unknown in unknowns {
+        let _ = writeln!(out, "    .codeattribute {}", unknown);
+    }
+    Ok(())
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    wide_index(bytes[at], bytes[at + 1])
+}
+
+fn i32_at(bytes: &[u8], at: usize) -> i32 {
+    branch_offset_wide(bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3])
+}
+
+/// Where the switch's operands start, after its padding
+fn switch_start(pc: usize) -> usize {
+    pc + 1 + (4 - (pc + 1) % 4) % 4
+}
+
+/// The pcs the instruction at `pc` can branch to
+fn targets(bytes: &[u8], pc: usize) -> Vec<usize> {
+    let target = |offset: i32| (pc as i64 + i64::from(offset)) as usize;
+    match Shape::of(bytes[pc]) {
+        Shape::Branch => vec![target(branch_offset(bytes[pc + 1], bytes[pc + 2]))],
+        Shape::BranchWide => vec![target(i32_at(bytes, pc + 1))],
+        Shape::TableSwitch => {
+            let start = switch_start(pc);
+            let (low, high) = (i32_at(bytes, start + 4), i32_at(bytes, start + 8));
+            let count = (i64::from(high) - i64::from(low) + 1) as usize;
+            std::iter::once(start)
+                .chain((0..count).map(|i| start + 12 + i * 4))
+                .map(|at| target(i32_at(bytes, at)))
+                .collect()
+        }
+        Shape::LookupSwitch => {
+            let start = switch_start(pc);
+            let count = i32_at(bytes, start + 4) as usize;
+            std::iter::once(start)
+                .chain((0..count).map(|i| start + 12 + i * 8))
+                .map(|at| target(i32_at(bytes, at)))
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+fn instruction(file: &ty::ClassFile, out: &mut String, bytes: &[u8], pc: usize) -> Result<()> {
+    let opcode = bytes[pc];
+    let name = mnemonic(opcode).unwrap_or_default();
+    let label = |offset: i32| format!("L{}", pc as i64 + i64::from(offset));
+    let operands = match Shape::of(opcode) {
+        Shape::None => String::new(),
+        Shape::Byte => format!("{}", bytes[pc + 1] as i8),
+        Shape::Short => format!("{}", u16_at(bytes, pc + 1) as i16),
+        Shape::Constant => constant(file, ConstantIndex(u16::from(bytes[pc + 1])))?,
+        Shape::WideConstant => constant(file, ConstantIndex(u16_at(bytes, pc + 1)))?,
+        Shape::Local => format!("{}", bytes[pc + 1]),
+        Shape::Iinc => format!("{} {}", bytes[pc + 1], bytes[pc + 2] as i8),
+        Shape::Wide => {
+            let inner = mnemonic(bytes[pc + 1]).unwrap_or_default();
+            let index = u16_at(bytes, pc + 2);
+            match bytes[pc + 1] {
+                0x84 => format!("{} {} {}", inner, index, u16_at(bytes, pc + 4) as i16),
+                _ => format!("{} {}", inner, index),
+            }
+        }
+        Shape::Branch => label(branch_offset(bytes[pc + 1], bytes[pc + 2])),
+        Shape::BranchWide => label(i32_at(bytes, pc + 1)),
+        Shape::Member | Shape::Dynamic => constant(file, ConstantIndex(u16_at(bytes, pc + 1)))?,
+        Shape::Interface => {
+            let index = ConstantIndex(u16_at(bytes, pc + 1));
+            let descriptor = ty::MethodDescriptor::parse(file.member_ref(index)?.descriptor)?;
+            // the count is left out when it is what the assembler would work out
+            match bytes[pc + 3] {
+                count if usize::from(count) == descriptor.param_slots() + 1 => {
+                    constant(file, index)?
+                }
+                count => format!("{} {}", constant(file, index)?, count),
+            }
+        }
+        Shape::Class => file
+            .class_name(ConstantIndex(u16_at(bytes, pc + 1)))?
+            .to_string(),
+        Shape::MultiArray => format!(
+            "{} {}",
+            file.class_name(ConstantIndex(u16_at(bytes, pc + 1)))?,
+            bytes[pc + 3]
+        ),
+        Shape::NewArray => match ARRAY_TYPES.iter().find(|(_, ty)| *ty == bytes[pc + 1]) {
+            Some((name, _)) => name.to_string(),
+            None => generic_error!("unknown array type at {}: {}", pc, bytes[pc + 1]),
+        },
+        Shape::TableSwitch => {
+            let start = switch_start(pc);
+            let mut operands = format!("{}\n", i32_at(bytes, start + 4));
+            for target in &targets(bytes, pc)[1..] {
+                let _ = writeln!(operands, "        L{}", target);
+            }
+            let _ = write!(operands, "        default: {}", label(i32_at(bytes, start)));
+            operands
+        }
+        Shape::LookupSwitch => {
+            let start = switch_start(pc);
+            let mut operands = String::new();
+            for (i, target) in targets(bytes, pc)[1..].iter().enumerate() {
+                let key = i32_at(bytes, start + 8 + i * 8);
+                let _ = write!(operands, "\n        {}: L{}", key, target);
+            }
+            let _ = write!(
+                operands,
+                "\n        default: {}",
+                label(i32_at(bytes, start))
+            );
+            operands
+        }
+    };
+    if operands.is_empty() || Shape::of(opcode) == Shape::LookupSwitch {
+        let _ = writeln!(out, "    {}{}", name, operands);
+    } else {
+        let _ = writeln!(out, "    {} {}", name, operands);
+    }
+    Ok(())
+}
+
+/// The instruction at `pc` of `code` on one line, e.g. `iinc 1 -1` or `goto L12`
+pub fn disassemble_instruction(file: &ty::ClassFile, code: &[u8], pc: usize) -> Result<String> {
+    let mut out = String::new();
+    instruction(file, &mut out, code, pc)?;
+    Ok(out.trim().replace("\n        ", " "))
+}
+
+fn offset(frame: &StackMapFrame) -> usize {
+    usize::from(match frame {
+        StackMapFrame::SameFrame(frame) => u16::from(frame.offset),
+        StackMapFrame::SameLocalsOneStackItemFrame(frame) => u16::from(frame.offset),
+        StackMapFrame::SameFrameExtended(frame) => frame.offset,
+        StackMapFrame::SameLocalsOneStackItemFrameExtended(frame) => frame.offset,
+        StackMapFrame::ChopFrame(frame) => frame.offset,
+        StackMapFrame::AppendFrame(frame) => frame.offset,
+        StackMapFrame::FullFrame(frame) => frame.offset,
+    })
+}
+
+fn frame_types(frame: &StackMapFrame) -> impl Iterator<Item = &VerificationType> {
+    let (locals, stack): (&[_], &[_]) = match frame {
+        StackMapFrame::SameLocalsOneStackItemFrame(frame) => {
+            (&[], std::slice::from_ref(&frame.stack_item))
+        }
+        StackMapFrame::SameLocalsOneStackItemFrameExtended(frame) => {
+            (&[], std::slice::from_ref(&frame.stack_item))
+        }
+        StackMapFrame::AppendFrame(frame) => (&frame.new_locals, &[]),
+        StackMapFrame::FullFrame(frame) => (&frame.locals, &frame.stack_items),
+        _ => (&[], &[]),
+    };
+    locals.iter().chain(stack)
+}
+
+fn stack_frame(file: &ty::ClassFile, frame: &StackMapFrame) -> Result<String> {
+    let types = |types: &[VerificationType]| {
+        types
+            .iter()
+            .map(|ty| verification_type(file, ty).map(|ty| format!(" {}", ty)))
+            .collect::<Result<String>>()
+    };
+    Ok(match frame {
+        StackMapFrame::SameFrame(..) => "same".to_string(),
+        StackMapFrame::SameFrameExtended(..) => "same_extended".to_string(),
+        StackMapFrame::SameLocalsOneStackItemFrame(frame) => format!(
+            "same_locals_1_stack_item {}",
+            verification_type(file, &frame.stack_item)?
+        ),
+        StackMapFrame::SameLocalsOneStackItemFrameExtended(frame) => format!(
+            "same_locals_1_stack_item_extended {}",
+            verification_type(file, &frame.stack_item)?
+        ),
+        StackMapFrame::ChopFrame(frame) => format!("chop {}", frame.absent_locals),
+        StackMapFrame::AppendFrame(frame) => format!("append{}", types(&frame.new_locals)?),
+        StackMapFrame::FullFrame(frame) => format!(
+            "full locals{} stack{}",
+            types(&frame.locals)?,
+            types(&frame.stack_items)?
+        ),
+    })
+}
+
+fn verification_type(file: &ty::ClassFile, ty: &VerificationType) -> Result<String> {
+    use VerificationType::*;
+    Ok(match ty {
+        Top => "Top".to_string(),
+        Integer => "Integer".to_string(),
+        Float => "Float".to_string(),
+        Long => "Long".to_string(),
+        Double => "Double".to_string(),
+        Null => "Null".to_string(),
+        UninitializedThis => "UninitializedThis".to_string(),
+        Object(class) => format!("Object {}", file.class_name(*class)?),
+        Uninitialized(pc) => format!("Uninitialized L{}", pc),
+    })
+}
@@ -0,0 +1,115 @@
+//! Splitting the source into lines of words and string literals
+use super::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Word(String),
+    Str(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word),
+            Token::Str(s) => write!(f, "{}", quote(s)),
+        }
+    }
+}
+
+/// A line of the source that has something other than a comment on it
+#[derive(Debug, Clone)]
+pub struct Line {
+    /// Counting from 1
+    pub number: usize,
+    pub tokens: Vec<Token>,
+}
+
+/// Splits `source` into lines. A `;` at the start of a word starts a comment, so that
+/// descriptors like `Ljava/lang/Object;` can still be written without quotes
+pub fn lex(source: &str) -> Result<Vec<Line>> {
+    let mut lines = vec![];
+    for (index, text) in source.lines().enumerate() {
+        let number = index + 1;
+        let mut tokens = vec![];
+        let mut chars = text.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            match c {
+                c if c.is_whitespace() => {
+                    chars.next();
+                }
+                ';' => break,
+                '"' => {
+                    chars.next();
+                    tokens.push(Token::Str(string(&mut chars, number)?));
+                }
+                _ => {
+                    let mut word = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || c == '"' {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    tokens.push(Token::Word(word));
+                }
+            }
+        }
+        if !tokens.is_empty() {
+            lines.push(Line { number, tokens });
+        }
+    }
+    Ok(lines)
+}
+
+/// The rest of a string literal, after its opening quote
+fn string(chars: &mut impl Iterator<Item = char>, line: usize) -> Result<String> {
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => s.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('0') => '\0',
+                Some(c @ '\\') | Some(c @ '"') | Some(c @ '\'') => c,
+                Some('u') => {
+                    let hex = chars.by_ref().take(4).collect::<String>();
+                    match u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(std::char::from_u32)
+                    {
+                        Some(c) if hex.len() == 4 => c,
+                        _ => generic_error!("line {}: invalid escape: \\u{}", line, hex),
+                    }
+                }
+                Some(c) => generic_error!("line {}: invalid escape: \\{}", line, c),
+                None => generic_error!("line {}: unterminated string", line),
+            }),
+            Some(c) => s.push(c),
+            None => generic_error!("line {}: unterminated string", line),
+        }
+    }
+}
+
+/// Quotes `s` so that `lex` reads it back as it is
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
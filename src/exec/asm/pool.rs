@@ -0,0 +1,238 @@
+//! A constant pool that is built up one constant at a time
+use super::*;
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use ty::{Constant, ConstantIndex};
+
+/// The constants of a class file being made, where each constant is only added once
+#[derive(Debug, Default, Clone)]
+pub struct ConstantPool {
+    constants: Vec<Constant>,
+    /// The index of each constant, so that interning doesn't have to look through them all
+    indices: HashMap<Key, ConstantIndex>,
+}
+
+impl ConstantPool {
+    /// The index of `constant`, which is added if it isn't in the pool yet. A `Long` or a
+    /// `Double` is followed by the `Padding` for the slot it takes up
+    pub fn intern(&mut self, constant: Constant) -> Result<ConstantIndex> {
+        let key = Key(constant);
+        if let Some(&index) = self.indices.get(&key) {
+            return Ok(index);
+        }
+        let wide = matches!(key.0, Constant::Long(..) | Constant::Double(..));
+        if self.constants.len() + 1 + wide as usize >= usize::from(u16::MAX) {
+            generic_error!("the constant pool is full");
+        }
+        self.constants.push(key.0.clone());
+        let index = ConstantIndex(self.constants.len() as u16);
+        self.indices.insert(key, index);
+        if wide {
+            self.constants.push(Constant::Padding);
+        }
+        Ok(index)
+    }
+
+    pub fn utf8(&mut self, s: &str) -> Result<ConstantIndex> {
+        self.intern(Constant::Utf8(s.to_string()))
+    }
+
+    pub fn class(&mut self, name: &str) -> Result<ConstantIndex> {
+        let name = self.utf8(name)?;
+        self.intern(Constant::ClassRef(name))
+    }
+
+    pub fn string(&mut self, s: &str) -> Result<ConstantIndex> {
+        let s = self.utf8(s)?;
+        self.intern(Constant::StringRef(s))
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> Result<ConstantIndex> {
+        let name = self.utf8(name)?;
+        let descriptor = self.utf8(descriptor)?;
+        self.intern(Constant::NameAndTypeRef(ty::constant::NameAndTypeRef {
+            name,
+            descriptor,
+        }))
+    }
+
+    pub fn field(&mut self, class: &str, name: &str, descriptor: &str) -> Result<ConstantIndex> {
+        let class = self.class(class)?;
+        let name_and_type = self.name_and_type(name, descriptor)?;
+        self.intern(Constant::FieldRef(ty::constant::FieldRef {
+            class,
+            name_and_type,
+        }))
+    }
+
+    pub fn method(&mut self, class: &str, name: &str, descriptor: &str) -> Result<ConstantIndex> {
+        let class = self.class(class)?;
+        let name_and_type = self.name_and_type(name, descriptor)?;
+        self.intern(Constant::MethodRef(ty::constant::MethodRef {
+            class,
+            name_and_type,
+        }))
+    }
+
+    pub fn interface_method(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<ConstantIndex> {
+        let class = self.class(class)?;
+        let name_and_type = self.name_and_type(name, descriptor)?;
+        self.intern(Constant::InterfaceMethodRef(
+            ty::constant::InterfaceMethodRef {
+                class,
+                name_and_type,
+            },
+        ))
+    }
+
+    /// Replaces the constant at `index`, which has to be in the pool and take up as many
+    /// slots as `constant`
+    pub fn set(&mut self, index: ConstantIndex, constant: Constant) {
+        let slot = &mut self.constants[usize::from(index.0) - 1];
+        let old = Key(std::mem::replace(slot, constant.clone()));
+        if self.indices.get(&old) == Some(&index) {
+            self.indices.remove(&old);
+        }
+        self.indices.entry(Key(constant)).or_insert(index);
+    }
+
+    /// The constant at `index`, which has to be in the pool
+    pub fn get(&self, index: ConstantIndex) -> &Constant {
+        &self.constants[usize::from(index.0) - 1]
+    }
+
+    /// The `Utf8` at `index`
+    pub fn get_utf8(&self, index: ConstantIndex) -> Option<&str> {
+        match self.constants.get(usize::from(index.0).checked_sub(1)?)? {
+            Constant::Utf8(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The name of the `ClassRef` at `index`
+    pub fn class_name(&self, index: ConstantIndex) -> Option<&str> {
+        match self.constants.get(usize::from(index.0).checked_sub(1)?)? {
+            Constant::ClassRef(name) => self.get_utf8(*name),
+            _ => None,
+        }
+    }
+
+    /// The descriptor of the field or method reference at `index`
+    pub fn descriptor(&self, index: ConstantIndex) -> Option<&str> {
+        let name_and_type = match self.constants.get(usize::from(index.0).checked_sub(1)?)? {
+            Constant::FieldRef(field) => field.name_and_type,
+            Constant::MethodRef(method) => method.name_and_type,
+            Constant::InterfaceMethodRef(method) => method.name_and_type,
+            Constant::InvokeDynamicRef(dynamic) => dynamic.name_and_type,
+            _ => return None,
+        };
+        match self.get(name_and_type) {
+            Constant::NameAndTypeRef(nat) => self.get_utf8(nat.descriptor),
+            _ => None,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[Constant] {
+        &self.constants
+    }
+
+    pub fn into_vec(self) -> Vec<Constant> {
+        self.constants
+    }
+}
+
+/// The constants of an existing class file, which new constants are added after
+impl From<Vec<Constant>> for ConstantPool {
+    fn from(constants: Vec<Constant>) -> Self {
+        let mut indices = HashMap::new();
+        for (i, constant) in constants.iter().enumerate() {
+            let index = ConstantIndex(i as u16 + 1);
+            indices.entry(Key(constant.clone())).or_insert(index);
+        }
+        Self { constants, indices }
+    }
+}
+
+/// A constant in `ConstantPool::indices`, which is the same as another one if `same`
+#[derive(Debug, Clone)]
+struct Key(Constant);
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        same(&self.0, &other.0)
+    }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(&self.0).hash(state);
+        match &self.0 {
+            Constant::Integer(n) => n.hash(state),
+            Constant::Float(n) => n.to_bits().hash(state),
+            Constant::Long(n) => n.hash(state),
+            Constant::Double(n) => n.to_bits().hash(state),
+            Constant::Utf8(s) => s.hash(state),
+            Constant::ClassRef(index)
+            | Constant::StringRef(index)
+            | Constant::MethodType(index) => index.0.hash(state),
+            Constant::FieldRef(ty::constant::FieldRef {
+                class,
+                name_and_type,
+            })
+            | Constant::MethodRef(ty::constant::MethodRef {
+                class,
+                name_and_type,
+            })
+            | Constant::InterfaceMethodRef(ty::constant::InterfaceMethodRef {
+                class,
+                name_and_type,
+            }) => (class.0, name_and_type.0).hash(state),
+            Constant::NameAndTypeRef(nat) => (nat.name.0, nat.descriptor.0).hash(state),
+            Constant::InvokeDynamicRef(indy) => {
+                (indy.bootstrap.0, indy.name_and_type.0).hash(state)
+            }
+            Constant::MethodHandleRef(handle) => (handle.kind(), handle.reference().0).hash(state),
+            Constant::Padding => {}
+        }
+    }
+}
+
+/// Constants are the same if they have the same bits, even if they are NaNs
+fn same(left: &Constant, right: &Constant) -> bool {
+    match (left, right) {
+        (Constant::Float(l), Constant::Float(r)) => l.to_bits() == r.to_bits(),
+        (Constant::Double(l), Constant::Double(r)) => l.to_bits() == r.to_bits(),
+        _ => left == right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interns_once() {
+        let mut pool = ConstantPool::default();
+        let nan = pool.intern(Constant::Double(f64::NAN)).unwrap();
+        let name = pool.utf8("name").unwrap();
+        assert_eq!(pool.intern(Constant::Double(f64::NAN)).unwrap(), nan);
+        assert_eq!(pool.utf8("name").unwrap(), name);
+        assert_eq!(name, ConstantIndex(3));
+
+        // what is set is found in its place, and what it replaced is added again
+        pool.set(name, Constant::Utf8("renamed".to_string()));
+        assert_eq!(pool.utf8("renamed").unwrap(), name);
+        assert_eq!(pool.utf8("name").unwrap(), ConstantIndex(4));
+
+        let mut pool = ConstantPool::from(pool.into_vec());
+        assert_eq!(pool.utf8("name").unwrap(), ConstantIndex(4));
+    }
+}
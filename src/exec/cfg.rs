@@ -0,0 +1,670 @@
+//! Control flow graphs of the basic blocks of a method
+use super::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// How control gets from one block to another
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeKind {
+    /// Continuing to the next instruction
+    Fallthrough,
+    /// A `goto`, or a conditional branch that's taken
+    Branch,
+    /// A case of a `tableswitch` or `lookupswitch`, `None` for the default
+    Switch(Option<i32>),
+    /// A `jsr` to a subroutine
+    Jsr,
+    /// A `ret` from a subroutine, to after the `jsr` that called it
+    Ret,
+    /// An exception caught by a handler, of the class, `None` for any (`finally`)
+    Exception(Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that's only entered at the first one, and only left after the
+/// last one (or by an exception)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    /// The pc of the first instruction
+    pub start: usize,
+    /// The pc after the last instruction
+    pub end: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+}
+
+/// A loop, made of the blocks that can reach a back edge to its header without leaving
+/// through the header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    pub header: usize,
+    pub blocks: BTreeSet<usize>,
+    /// The blocks that jump back to the header
+    pub latches: BTreeSet<usize>,
+}
+
+/// The control flow graph of a method, where block `0` is the entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub blocks: Vec<Block>,
+    pub edges: Vec<Edge>,
+}
+
+impl Cfg {
+    /// Builds the graph of the code of `method`
+    pub fn new(file: &ty::ClassFile, method: &ty::Method) -> Result<Self> {
+        match method.get_code() {
+            Some(code) => Self::from_code(file, code),
+            None => generic_error!("{} has no code", method.name()),
+        }
+    }
+
+    pub fn from_code(file: &ty::ClassFile, code: &attr::Code) -> Result<Self> {
+        let mut instructions = BTreeMap::new();
+        let mut pc = 0;
+        while pc < code.code.len() {
+            let instruction = match Instruction::decode(&code.code, pc) {
+                Some(instruction) => instruction,
+                None => generic_error!("invalid instruction at {}", pc),
+            };
+            let size = instruction.size();
+            instructions.insert(pc, instruction);
+            pc += size;
+        }
+
+        // the first instruction of every block
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for (&pc, instruction) in &instructions {
+            let next = pc + instruction.size();
+            let (targets, falls_through) = jumps(instruction, pc);
+            if !targets.is_empty() || !falls_through {
+                leaders.insert(next);
+            }
+            leaders.extend(targets.into_iter().map(|(target, _)| target));
+        }
+        for row in &code.exception_table {
+            leaders.insert(usize::from(row.start_pc));
+            leaders.insert(usize::from(row.end_pc));
+            leaders.insert(usize::from(row.handler_pc));
+        }
+        for &leader in &leaders {
+            if leader < code.code.len() && !instructions.contains_key(&leader) {
+                generic_error!("{} is not the start of an instruction", leader);
+            }
+        }
+
+        let starts = leaders
+            .iter()
+            .copied()
+            .filter(|&leader| leader < code.code.len())
+            .collect::<Vec<_>>();
+        let blocks = starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = starts.get(i + 1).copied().unwrap_or(code.code.len());
+                Block {
+                    start,
+                    end,
+                    instructions: (instructions.range(start..end))
+                        .map(|(&pc, instruction)| (pc, instruction.clone()))
+                        .collect(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut cfg = Cfg {
+            blocks,
+            edges: vec![],
+        };
+        for from in 0..cfg.blocks.len() {
+            let (pc, instruction) = match cfg.blocks[from].instructions.last() {
+                Some((pc, instruction)) => (*pc, instruction.clone()),
+                None => continue,
+            };
+            let (targets, falls_through) = jumps(&instruction, pc);
+            for (target, kind) in targets {
+                cfg.add_edge(from, target, kind)?;
+            }
+            if falls_through {
+                let next = cfg.blocks[from].end;
+                if next >= code.code.len() {
+                    generic_error!("falls off the end of the code at {}", pc);
+                }
+                cfg.add_edge(from, next, EdgeKind::Fallthrough)?;
+            }
+        }
+
+        for row in &code.exception_table {
+            let catch_type = match row.catch_type.0 {
+                0 => None,
+                _ => Some(file.class_name(row.catch_type)?.to_string()),
+            };
+            let (start, end) = (usize::from(row.start_pc), usize::from(row.end_pc));
+            for from in 0..cfg.blocks.len() {
+                let block = &cfg.blocks[from];
+                if block.start >= start && block.end <= end {
+                    let kind = EdgeKind::Exception(catch_type.clone());
+                    cfg.add_edge(from, usize::from(row.handler_pc), kind)?;
+                }
+            }
+        }
+
+        cfg.add_returns()?;
+        Ok(cfg)
+    }
+
+    fn add_edge(&mut self, from: usize, target: usize, kind: EdgeKind) -> Result<()> {
+        let to = self.block_at(target)?;
+        self.edges.push(Edge { from, to, kind });
+        Ok(())
+    }
+
+    /// The block that starts at `pc`
+    pub fn block_at(&self, pc: usize) -> Result<usize> {
+        match self.blocks.binary_search_by_key(&pc, |block| block.start) {
+            Ok(index) => Ok(index),
+            Err(..) => generic_error!("no block starts at {}", pc),
+        }
+    }
+
+    /// Adds the edges from each `ret` to after the `jsr`s of its subroutine, which is
+    /// made of the blocks reached from its start without returning
+    fn add_returns(&mut self) -> Result<()> {
+        let calls = (self.edges.iter())
+            .filter(|edge| edge.kind == EdgeKind::Jsr)
+            .map(|edge| (edge.from, edge.to))
+            .collect::<Vec<_>>();
+        let subroutines = calls.iter().map(|&(_, to)| to).collect::<BTreeSet<_>>();
+
+        for subroutine in subroutines {
+            let mut seen = BTreeSet::new();
+            let mut work = vec![subroutine];
+            let mut rets = BTreeSet::new();
+            while let Some(block) = work.pop() {
+                if !seen.insert(block) {
+                    continue;
+                }
+                let is_ret = matches!(
+                    self.blocks[block].instructions.last(),
+                    Some((_, Instruction::RET(..))) | Some((_, Instruction::WIDE(WIDE(0xA9, ..))))
+                );
+                if is_ret {
+                    rets.insert(block);
+                }
+                for edge in self.edges.iter().filter(|edge| edge.from == block) {
+                    match edge.kind {
+                        // a nested subroutine returns to after its jsr
+                        EdgeKind::Jsr if edge.to != subroutine => {
+                            if block + 1 < self.blocks.len() {
+                                work.push(block + 1);
+                            }
+                        }
+                        EdgeKind::Jsr | EdgeKind::Ret => {}
+                        _ => work.push(edge.to),
+                    }
+                }
+            }
+
+            for &(call, _) in calls.iter().filter(|&&(_, to)| to == subroutine) {
+                let next = self.blocks[call].end;
+                for &ret in &rets {
+                    self.add_edge(ret, next, EdgeKind::Ret)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+
+    /// The blocks reachable from the entry, in reverse postorder
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut order = vec![];
+        let mut seen = vec![false; self.blocks.len()];
+        // blocks with the index of the next successor to visit
+        let mut stack = vec![(0, 0)];
+        seen[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            let successor = self.successors(block).nth(next).map(|edge| edge.to);
+            match successor {
+                Some(successor) => {
+                    stack.push((block, next + 1));
+                    if !seen[successor] {
+                        seen[successor] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => order.push(block),
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// The immediate dominator of each block, `None` for the entry and unreachable blocks
+    // https://www.cs.rice.edu/~keith/EMBED/dom.pdf
+    pub fn dominators(&self) -> Vec<Option<usize>> {
+        let order = self.reverse_postorder();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (i, &block) in order.iter().enumerate() {
+            position[block] = i;
+        }
+
+        let mut idom = vec![None; self.blocks.len()];
+        idom[0] = Some(0);
+        let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while position[a] > position[b] {
+                    a = idom[a].unwrap_or(0);
+                }
+                while position[b] > position[a] {
+                    b = idom[b].unwrap_or(0);
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut new = None;
+                for edge in self.predecessors(block) {
+                    if idom[edge.from].is_none() {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => edge.from,
+                        Some(new) => intersect(&idom, edge.from, new),
+                    });
+                }
+                if new.is_some() && idom[block] != new {
+                    idom[block] = new;
+                    changed = true;
+                }
+            }
+        }
+        idom[0] = None;
+        idom
+    }
+
+    /// The immediate post-dominator of each block, following everything but exception
+    /// edges, `None` for blocks that are only followed by leaving the method (or that
+    /// never leave it)
+    pub fn post_dominators(&self) -> Vec<Option<usize>> {
+        // the blocks are numbered from 0, and `exit` stands in for leaving the method
+        let exit = self.blocks.len();
+        let normal = |edge: &&Edge| !matches!(edge.kind, EdgeKind::Exception(..));
+        let mut successors = vec![vec![]; exit + 1];
+        let mut predecessors = vec![vec![]; exit + 1];
+        for edge in self.edges.iter().filter(normal) {
+            successors[edge.from].push(edge.to);
+            predecessors[edge.to].push(edge.from);
+        }
+        for (block, successors) in successors.iter_mut().enumerate().take(exit) {
+            if successors.is_empty() {
+                successors.push(exit);
+                predecessors[exit].push(block);
+            }
+        }
+
+        // the reverse postorder of the reversed graph, starting from the exit
+        let mut order = vec![];
+        let mut seen = vec![false; exit + 1];
+        let mut stack = vec![(exit, 0)];
+        seen[exit] = true;
+        while let Some((block, next)) = stack.pop() {
+            match predecessors[block].get(next) {
+                Some(&predecessor) => {
+                    stack.push((block, next + 1));
+                    if !seen[predecessor] {
+                        seen[predecessor] = true;
+                        stack.push((predecessor, 0));
+                    }
+                }
+                None => order.push(block),
+            }
+        }
+        order.reverse();
+        let mut position = vec![usize::MAX; exit + 1];
+        for (i, &block) in order.iter().enumerate() {
+            position[block] = i;
+        }
+
+        let mut ipdom = vec![None; exit + 1];
+        ipdom[exit] = Some(exit);
+        let intersect = |ipdom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while position[a] > position[b] {
+                    a = ipdom[a].unwrap_or(exit);
+                }
+                while position[b] > position[a] {
+                    b = ipdom[b].unwrap_or(exit);
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut new = None;
+                for &successor in &successors[block] {
+                    if ipdom[successor].is_none() {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => successor,
+                        Some(new) => intersect(&ipdom, successor, new),
+                    });
+                }
+                if new.is_some() && ipdom[block] != new {
+                    ipdom[block] = new;
+                    changed = true;
+                }
+            }
+        }
+        ipdom.pop();
+        ipdom
+            .into_iter()
+            .map(|block| block.filter(|&block| block != exit))
+            .collect()
+    }
+
+    /// Whether every path from the entry to `b` goes through `a`
+    pub fn dominates(&self, dominators: &[Option<usize>], a: usize, mut b: usize) -> bool {
+        loop {
+            if a == b {
+                return true;
+            }
+            match dominators[b] {
+                Some(dominator) => b = dominator,
+                None => return false,
+            }
+        }
+    }
+
+    /// The natural loops, one for each header, ordered by header
+    ///
+    /// Exception edges aren't back edges: a handler that covers itself, like the one that
+    /// exits the monitor of a `synchronized` block, isn't a loop.
+    pub fn loops(&self) -> Vec<Loop> {
+        let dominators = self.dominators();
+        let mut loops = BTreeMap::<usize, Loop>::new();
+        for edge in &self.edges {
+            let exception = matches!(edge.kind, EdgeKind::Exception(..));
+            if exception || !self.dominates(&dominators, edge.to, edge.from) {
+                continue;
+            }
+            let header = edge.to;
+            let found = loops.entry(header).or_insert_with(|| Loop {
+                header,
+                blocks: std::iter::once(header).collect(),
+                latches: BTreeSet::new(),
+            });
+            found.latches.insert(edge.from);
+
+            let mut work = vec![edge.from];
+            while let Some(block) = work.pop() {
+                // unreachable blocks that jump into the loop aren't a part of it
+                if self.dominates(&dominators, header, block) && found.blocks.insert(block) {
+                    work.extend(self.predecessors(block).map(|edge| edge.from));
+                }
+            }
+        }
+        loops.into_values().collect()
+    }
+
+    /// Formats the graph in the Graphviz DOT language
+    pub fn to_dot(&self, name: &str) -> String {
+        let mut dot = String::new();
+        let _ = writeln!(dot, "digraph \"{}\" {{", escape(name));
+        let _ = writeln!(dot, "    node [shape=box, fontname=monospace];");
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for (pc, instruction) in &block.instructions {
+                let mnemonic = instruction.to_string().to_lowercase();
+                let _ = write!(label, "{}: {}\\l", pc, mnemonic);
+            }
+            let _ = writeln!(dot, "    b{} [label=\"{}\"];", i, label);
+        }
+        for edge in &self.edges {
+            let attributes = match &edge.kind {
+                EdgeKind::Fallthrough => String::new(),
+                EdgeKind::Branch => " [color=blue]".to_string(),
+                EdgeKind::Switch(Some(key)) => format!(" [label=\"{}\"]", key),
+                EdgeKind::Switch(None) => " [label=\"default\"]".to_string(),
+                EdgeKind::Jsr => " [style=dotted, label=\"jsr\"]".to_string(),
+                EdgeKind::Ret => " [style=dotted, label=\"ret\"]".to_string(),
+                EdgeKind::Exception(catch_type) => format!(
+                    " [style=dashed, color=red, label=\"{}\"]",
+                    escape(catch_type.as_deref().unwrap_or("any"))
+                ),
+            };
+            let _ = writeln!(dot, "    b{} -> b{}{};", edge.from, edge.to, attributes);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Where an instruction can jump to, and whether it can continue to the next one
+fn jumps(instruction: &Instruction, pc: usize) -> (Vec<(usize, EdgeKind)>, bool) {
+    let at = |offset: i32| (pc as i64 + i64::from(offset)) as usize;
+    let branch = |a: u8, b: u8| vec![(at(branch_offset(a, b)), EdgeKind::Branch)];
+    match instruction {
+        Instruction::IFEQ(IFEQ(a, b))
+        | Instruction::IFNE(IFNE(a, b))
+        | Instruction::IFLT(IFLT(a, b))
+        | Instruction::IFGE(IFGE(a, b))
+        | Instruction::IFGT(IFGT(a, b))
+        | Instruction::IFLE(IFLE(a, b))
+        | Instruction::IF_ICMPEQ(IF_ICMPEQ(a, b))
+        | Instruction::IF_ICMPNE(IF_ICMPNE(a, b))
+        | Instruction::IF_ICMPLT(IF_ICMPLT(a, b))
+        | Instruction::IF_ICMPGE(IF_ICMPGE(a, b))
+        | Instruction::IF_ICMPGT(IF_ICMPGT(a, b))
+        | Instruction::IF_ICMPLE(IF_ICMPLE(a, b))
+        | Instruction::IF_ACMPEQ(IF_ACMPEQ(a, b))
+        | Instruction::IF_ACMPNE(IF_ACMPNE(a, b))
+        | Instruction::IFNULL(IFNULL(a, b))
+        | Instruction::IFNONNULL(IFNONNULL(a, b)) => (branch(*a, *b), true),
+        Instruction::GOTO(GOTO(a, b)) => (branch(*a, *b), false),
+        Instruction::GOTO_W(GOTO_W(a, b, c, d)) => {
+            let target = at(branch_offset_wide(*a, *b, *c, *d));
+            (vec![(target, EdgeKind::Branch)], false)
+        }
+        Instruction::JSR(JSR(a, b)) => (vec![(at(branch_offset(*a, *b)), EdgeKind::Jsr)], false),
+        Instruction::JSR_W(JSR_W(a, b, c, d)) => {
+            let target = at(branch_offset_wide(*a, *b, *c, *d));
+            (vec![(target, EdgeKind::Jsr)], false)
+        }
+        Instruction::TABLESWITCH(switch) => {
+            let (default, low, offsets) = switch.table();
+            let cases = (offsets.into_iter().enumerate())
+                .map(|(i, offset)| (at(offset), EdgeKind::Switch(Some(low + i as i32))));
+            let default = (at(default), EdgeKind::Switch(None));
+            (cases.chain(std::iter::once(default)).collect(), false)
+        }
+        Instruction::LOOKUPSWITCH(switch) => {
+            let (default, pairs) = switch.pairs();
+            let cases =
+                (pairs.into_iter()).map(|(key, offset)| (at(offset), EdgeKind::Switch(Some(key))));
+            let default = (at(default), EdgeKind::Switch(None));
+            (cases.chain(std::iter::once(default)).collect(), false)
+        }
+        Instruction::IRETURN(..)
+        | Instruction::LRETURN(..)
+        | Instruction::FRETURN(..)
+        | Instruction::DRETURN(..)
+        | Instruction::ARETURN(..)
+        | Instruction::RETURN(..)
+        | Instruction::ATHROW(..)
+        | Instruction::RET(..)
+        | Instruction::WIDE(WIDE(0xA9, ..)) => (vec![], false),
+        _ => (vec![], true),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(name: &str, method: &str) -> Cfg {
+        let data = std::fs::read(format!("./etc/{}.class", name)).unwrap();
+        let file = ty::ClassFile::read(&mut data.as_slice()).unwrap();
+        let method = file.methods.iter().find(|m| m.name() == method).unwrap();
+        Cfg::new(&file, method).unwrap()
+    }
+
+    fn edges(cfg: &Cfg) -> Vec<(usize, usize, EdgeKind)> {
+        (cfg.edges.iter())
+            .map(|edge| {
+                (
+                    cfg.blocks[edge.from].start,
+                    cfg.blocks[edge.to].start,
+                    edge.kind.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn switches() {
+        let cfg = build("cfg", "classify");
+        let starts = cfg
+            .blocks
+            .iter()
+            .map(|block| block.start)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![0, 28, 31, 34]);
+        assert_eq!(
+            edges(&cfg),
+            vec![
+                (0, 28, EdgeKind::Switch(Some(1))),
+                (0, 31, EdgeKind::Switch(Some(2))),
+                (0, 31, EdgeKind::Switch(Some(3))),
+                (0, 34, EdgeKind::Switch(None)),
+            ]
+        );
+
+        let cfg = build("cfg", "sparse");
+        assert_eq!(
+            edges(&cfg),
+            vec![
+                (0, 28, EdgeKind::Switch(Some(1))),
+                (0, 30, EdgeKind::Switch(Some(1000))),
+                (0, 32, EdgeKind::Switch(None)),
+            ]
+        );
+        assert_eq!(cfg.dominators(), vec![None, Some(0), Some(0), Some(0)]);
+        assert!(cfg.loops().is_empty());
+    }
+
+    #[test]
+    fn nested_loops() {
+        let cfg = build("cfg", "nested");
+        let starts = cfg
+            .blocks
+            .iter()
+            .map(|block| block.start)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![0, 4, 9, 11, 16, 26, 32]);
+        assert_eq!(
+            cfg.dominators(),
+            vec![None, Some(0), Some(1), Some(2), Some(3), Some(3), Some(1)]
+        );
+
+        let loops = cfg.loops();
+        assert_eq!(loops.len(), 2);
+        assert_eq!(loops[0].header, 1);
+        assert_eq!(loops[0].blocks, vec![1, 2, 3, 4, 5].into_iter().collect());
+        assert_eq!(loops[0].latches, std::iter::once(5).collect());
+        assert_eq!(loops[1].header, 3);
+        assert_eq!(loops[1].blocks, vec![3, 4].into_iter().collect());
+
+        assert_eq!(
+            cfg.post_dominators(),
+            vec![Some(1), Some(6), Some(3), Some(5), Some(3), Some(1), None]
+        );
+    }
+
+    #[test]
+    fn exception_edges() {
+        let cfg = build("cfg", "guarded");
+        assert_eq!(
+            edges(&cfg),
+            vec![
+                (0, 3, EdgeKind::Fallthrough),
+                (
+                    0,
+                    4,
+                    EdgeKind::Exception(Some("java/lang/ArrayIndexOutOfBoundsException".into()))
+                ),
+                (
+                    0,
+                    7,
+                    EdgeKind::Exception(Some("java/lang/NullPointerException".into()))
+                ),
+            ]
+        );
+        assert_eq!(cfg.dominators()[3], Some(0));
+
+        let dot = cfg.to_dot("cfg.guarded([I)I");
+        assert!(dot.starts_with("digraph \"cfg.guarded([I)I\" {\n"));
+        assert!(dot.contains("    b0 [label=\"0: aload_0\\l1: iconst_0\\l2: iaload\\l\"];\n"));
+        assert!(dot.contains(
+            "    b0 -> b3 [style=dashed, color=red, label=\"java/lang/NullPointerException\"];\n"
+        ));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn subroutines() {
+        let data = std::fs::read("./etc/verify.class").unwrap();
+        let file = ty::ClassFile::read(&mut data.as_slice()).unwrap();
+        let mut code = file.methods[0].get_code().unwrap().clone();
+        code.exception_table.clear();
+        code.code = vec![
+            0xA8, 0x00, 0x08, // 0: jsr 8
+            0xA8, 0x00, 0x05, // 3: jsr 8
+            0xB1, // 6: return
+            0x00, // 7: nop
+            0x4C, // 8: astore_1
+            0xA9, 0x01, // 9: ret 1
+        ];
+        let cfg = Cfg::from_code(&file, &code).unwrap();
+        assert_eq!(
+            edges(&cfg),
+            vec![
+                (0, 8, EdgeKind::Jsr),
+                (3, 8, EdgeKind::Jsr),
+                (7, 8, EdgeKind::Fallthrough),
+                (8, 3, EdgeKind::Ret),
+                (8, 6, EdgeKind::Ret),
+            ]
+        );
+        // the nop can't be reached
+        assert_eq!(cfg.reverse_postorder(), vec![0, 4, 2, 1]);
+        assert_eq!(cfg.dominators()[3], None);
+    }
+}
@@ -0,0 +1,539 @@
+use super::ffi::HostFn;
+use super::native::NativeFn;
+use super::value::Value;
+use super::*;
+
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// Selected methods, keyed by the name and descriptor of the resolved method
+type DispatchTable = RefCell<HashMap<(String, String), Option<Rc<Method>>>>;
+
+#[derive(Clone)]
+pub enum MethodBody {
+    Code(Rc<attr::Code>),
+    Native(NativeFn),
+    /// A native method bound to a closure from the `NativeRegistry`
+    Bound(Rc<HostFn>),
+    /// Abstract methods, and native methods that haven't been bound
+    None,
+}
+
+impl std::fmt::Debug for MethodBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MethodBody::Code(code) => write!(f, "Code({} bytes)", code.code.len()),
+            MethodBody::Native(..) => write!(f, "Native"),
+            MethodBody::Bound(..) => write!(f, "Bound"),
+            MethodBody::None => write!(f, "None"),
+        }
+    }
+}
+
+/// A linked method
+#[derive(Debug)]
+pub struct Method {
+    pub name: String,
+    pub descriptor: String,
+    pub signature: ty::MethodDescriptor,
+    pub flags: ty::MethodFlags,
+    pub body: MethodBody,
+    /// The code as the interpreter runs it, decoded the first time it is
+    pub(crate) decoded: OnceCell<Rc<interpreter::DecodedCode>>,
+    #[cfg(feature = "jit")]
+    pub(crate) hotness: super::jit::Hotness,
+    class: Weak<Class>,
+}
+
+impl Method {
+    pub fn new(
+        name: impl Into<String>,
+        descriptor: impl Into<String>,
+        flags: ty::MethodFlags,
+        body: MethodBody,
+    ) -> Result<Self> {
+        let descriptor = descriptor.into();
+        Ok(Self {
+            name: name.into(),
+            signature: ty::MethodDescriptor::parse(&descriptor)?,
+            descriptor,
+            flags,
+            body,
+            decoded: OnceCell::new(),
+            #[cfg(feature = "jit")]
+            hotness: Default::default(),
+            class: Weak::new(),
+        })
+    }
+
+    /// The class that declared this method
+    pub fn class(&self) -> Rc<Class> {
+        self.class.upgrade().expect("classes are never unloaded")
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.flags.contains(ty::MethodFlags::STATIC)
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.flags.contains(ty::MethodFlags::PRIVATE)
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.flags.contains(ty::MethodFlags::ABSTRACT)
+    }
+
+    pub fn code(&self) -> Option<&Rc<attr::Code>> {
+        match &self.body {
+            MethodBody::Code(code) => Some(code),
+            _ => None,
+        }
+    }
+
+    /// How many values the caller pops off of its stack, including `this`
+    pub fn arg_count(&self) -> usize {
+        self.signature.params.len() + if self.is_static() { 0 } else { 1 }
+    }
+}
+
+/// A linked field
+#[derive(Debug)]
+pub struct Field {
+    pub name: String,
+    pub descriptor: String,
+    pub ty: ty::FieldType,
+    pub flags: ty::FieldFlags,
+    /// Index into the instance's fields, or the class' statics
+    pub slot: usize,
+    /// The `ConstantValue` a static field is set to when its class is initialized
+    pub constant_value: Option<ty::ConstantIndex>,
+    class: Weak<Class>,
+}
+
+impl Field {
+    pub fn new(
+        name: impl Into<String>,
+        descriptor: impl Into<String>,
+        flags: ty::FieldFlags,
+    ) -> Result<Self> {
+        let descriptor = descriptor.into();
+        Ok(Self {
+            name: name.into(),
+            ty: ty::FieldType::parse(&descriptor)?,
+            descriptor,
+            flags,
+            slot: 0,
+            constant_value: None,
+            class: Weak::new(),
+        })
+    }
+
+    /// The class that declared this field
+    pub fn class(&self) -> Rc<Class> {
+        self.class.upgrade().expect("classes are never unloaded")
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.flags.contains(ty::FieldFlags::STATIC)
+    }
+}
+
+/// Everything needed to link a class, once its super class and interfaces are linked
+#[derive(Debug)]
+pub struct ClassDef {
+    pub name: String,
+    pub flags: ty::ClassFlags,
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+    pub file: Option<Rc<ty::ClassFile>>,
+    pub methods: Vec<Method>,
+    pub fields: Vec<Field>,
+}
+
+impl ClassDef {
+    pub fn from_class_file(file: Rc<ty::ClassFile>) -> Result<Self> {
+        let methods = file
+            .methods
+            .iter()
+            .map(|method| {
+                let body = match method.get_code() {
+                    Some(code) => MethodBody::Code(Rc::new(code.clone())),
+                    None => MethodBody::None,
+                };
+                Method::new(
+                    method.name(),
+                    file.utf8(method.descriptor)?,
+                    method.flags,
+                    body,
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let fields = file
+            .fields
+            .iter()
+            .map(|field| {
+                let mut linked = Field::new(
+                    file.utf8(field.name)?,
+                    file.utf8(field.descriptor)?,
+                    field.flags,
+                )?;
+                linked.constant_value =
+                    field
+                        .attributes
+                        .iter()
+                        .find_map(|attribute| match attribute {
+                            attr::Attribute::ConstantValue(value) => Some(value.constant_value),
+                            _ => None,
+                        });
+                Ok(linked)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let interfaces = file
+            .interfaces
+            .iter()
+            .map(|&index| file.class_name(index).map(ToString::to_string))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            name: file.get_class_name().to_string(),
+            flags: file.flags,
+            super_class: file.super_class_name()?.map(ToString::to_string),
+            interfaces,
+            methods,
+            fields,
+            file: Some(file),
+        })
+    }
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-5.html#jvms-5.5
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InitState {
+    Uninitialized,
+    /// `<clinit>` is running
+    InProgress,
+    Initialized,
+    /// Initialization failed, and cannot be retried
+    Erroneous,
+}
+
+/// A linked class
+pub struct Class {
+    pub name: String,
+    pub flags: ty::ClassFlags,
+    pub super_class: Option<Rc<Class>>,
+    pub interfaces: Vec<Rc<Class>>,
+    /// `None` for classes provided by the interpreter
+    pub file: Option<Rc<ty::ClassFile>>,
+    pub methods: Vec<Rc<Method>>,
+    pub fields: Vec<Rc<Field>>,
+    pub statics: RefCell<Vec<Value>>,
+    pub init_state: Cell<InitState>,
+    /// The types of every instance field, including inherited ones
+    instance_fields: Vec<ty::FieldType>,
+    vtable: DispatchTable,
+    itable: DispatchTable,
+}
+
+impl std::fmt::Debug for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Class")
+            .field("name", &self.name)
+            .field("flags", &self.flags)
+            .field(
+                "super_class",
+                &self.super_class.as_ref().map(|class| &class.name),
+            )
+            .field(
+                "interfaces",
+                &self.interfaces.iter().map(|i| &i.name).collect::<Vec<_>>(),
+            )
+            .field("methods", &self.methods)
+            .field("fields", &self.fields)
+            .field("init_state", &self.init_state.get())
+            .finish()
+    }
+}
+
+impl Class {
+    pub fn new(
+        def: ClassDef,
+        super_class: Option<Rc<Class>>,
+        interfaces: Vec<Rc<Class>>,
+    ) -> Rc<Self> {
+        let mut instance_fields = super_class
+            .as_ref()
+            .map(|class| class.instance_fields.clone())
+            .unwrap_or_default();
+
+        let ClassDef {
+            name,
+            flags,
+            file,
+            methods,
+            mut fields,
+            ..
+        } = def;
+
+        let mut statics = vec![];
+        for field in &mut fields {
+            if field.is_static() {
+                field.slot = statics.len();
+                statics.push(Value::default_for(&field.ty));
+            } else {
+                field.slot = instance_fields.len();
+                instance_fields.push(field.ty.clone());
+            }
+        }
+
+        Rc::new_cyclic(|this| Self {
+            name,
+            flags,
+            super_class,
+            interfaces,
+            file,
+            methods: methods
+                .into_iter()
+                .map(|mut method| {
+                    method.class = Weak::clone(this);
+                    Rc::new(method)
+                })
+                .collect(),
+            fields: fields
+                .into_iter()
+                .map(|mut field| {
+                    field.class = Weak::clone(this);
+                    Rc::new(field)
+                })
+                .collect(),
+            statics: RefCell::new(statics),
+            init_state: Cell::new(InitState::Uninitialized),
+            instance_fields,
+            vtable: RefCell::default(),
+            itable: RefCell::default(),
+        })
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.flags.contains(ty::ClassFlags::INTERFACE)
+    }
+
+    /// The zero values for a new instance of this class
+    pub fn instance_field_defaults(&self) -> Vec<Value> {
+        self.instance_fields
+            .iter()
+            .map(Value::default_for)
+            .collect()
+    }
+
+    /// The class file that the constant pool indices in this class' code refer to
+    pub fn class_file(&self) -> Result<&ty::ClassFile> {
+        match &self.file {
+            Some(file) => Ok(file),
+            None => generic_error!("{} has no constant pool", self.name),
+        }
+    }
+
+    /// Finds a method declared by this class
+    pub fn find_method(&self, name: &str, descriptor: &str) -> Option<Rc<Method>> {
+        self.methods
+            .iter()
+            .find(|method| method.name == name && method.descriptor == descriptor)
+            .map(Rc::clone)
+    }
+
+    /// Finds a field declared by this class
+    pub fn find_field(&self, name: &str, descriptor: &str) -> Option<Rc<Field>> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name && field.descriptor == descriptor)
+            .map(Rc::clone)
+    }
+
+    /// The super classes of this class, starting with this class
+    pub fn ancestors(self: &Rc<Self>) -> impl Iterator<Item = Rc<Class>> {
+        std::iter::successors(Some(Rc::clone(self)), |class| class.super_class.clone())
+    }
+
+    /// Every interface this class implements, directly or indirectly
+    pub fn all_interfaces(self: &Rc<Self>) -> Vec<Rc<Class>> {
+        fn collect(class: &Class, out: &mut Vec<Rc<Class>>) {
+            for interface in &class.interfaces {
+                if !out.iter().any(|c| c.name == interface.name) {
+                    out.push(Rc::clone(interface));
+                    collect(interface, out);
+                }
+            }
+        }
+
+        let mut out = vec![];
+        for class in self.ancestors() {
+            collect(&class, &mut out);
+        }
+        out
+    }
+
+    /// Whether this class is `name`, or a subclass or implementation of it
+    pub fn is_subtype_of(self: &Rc<Self>, name: &str) -> bool {
+        self.ancestors().any(|class| class.name == name)
+            || self.all_interfaces().iter().any(|class| class.name == name)
+    }
+
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-5.html#jvms-5.4.3.2
+    pub fn resolve_field(self: &Rc<Self>, name: &str, descriptor: &str) -> Option<Rc<Field>> {
+        if let Some(field) = self.find_field(name, descriptor) {
+            return Some(field);
+        }
+        for interface in &self.interfaces {
+            if let Some(field) = interface.resolve_field(name, descriptor) {
+                return Some(field);
+            }
+        }
+        self.super_class
+            .as_ref()
+            .and_then(|class| class.resolve_field(name, descriptor))
+    }
+
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-5.html#jvms-5.4.3.3
+    pub fn resolve_method(self: &Rc<Self>, name: &str, descriptor: &str) -> Result<Rc<Method>> {
+        if self.is_interface() {
+            return Err(Error::IncompatibleClassChange(format!(
+                "{} is an interface",
+                self.name
+            )));
+        }
+
+        if let Some(method) = self
+            .ancestors()
+            .find_map(|class| class.find_method(name, descriptor))
+        {
+            return Ok(method);
+        }
+
+        self.superinterface_method(name, descriptor)
+            .ok_or_else(|| self.no_such_method(name, descriptor))
+    }
+
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-5.html#jvms-5.4.3.4
+    pub fn resolve_interface_method(
+        self: &Rc<Self>,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<Rc<Method>> {
+        if !self.is_interface() {
+            return Err(Error::IncompatibleClassChange(format!(
+                "{} is not an interface",
+                self.name
+            )));
+        }
+
+        if let Some(method) = self.find_method(name, descriptor) {
+            return Ok(method);
+        }
+
+        // an interface's super class is always java/lang/Object
+        let object = self
+            .ancestors()
+            .last()
+            .filter(|class| class.name != self.name);
+        if let Some(method) = object
+            .and_then(|object| object.find_method(name, descriptor))
+            .filter(|method| method.flags.contains(ty::MethodFlags::PUBLIC) && !method.is_static())
+        {
+            return Ok(method);
+        }
+
+        self.superinterface_method(name, descriptor)
+            .ok_or_else(|| self.no_such_method(name, descriptor))
+    }
+
+    /// Selects the method that `resolved` dispatches to when this is the class of the receiver
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-5.html#jvms-5.4.6
+    pub fn select_method(self: &Rc<Self>, resolved: &Rc<Method>) -> Result<Rc<Method>> {
+        if resolved.is_private() {
+            return Ok(Rc::clone(resolved));
+        }
+        self.dispatch(&self.vtable, resolved)
+    }
+
+    /// Like `select_method`, for `invokeinterface` which has its own table
+    pub fn select_interface_method(self: &Rc<Self>, resolved: &Rc<Method>) -> Result<Rc<Method>> {
+        if resolved.is_private() {
+            return Ok(Rc::clone(resolved));
+        }
+        self.dispatch(&self.itable, resolved)
+    }
+
+    fn dispatch(
+        self: &Rc<Self>,
+        table: &DispatchTable,
+        resolved: &Rc<Method>,
+    ) -> Result<Rc<Method>> {
+        let key = (resolved.name.clone(), resolved.descriptor.clone());
+        let selected = table
+            .borrow_mut()
+            .entry(key)
+            .or_insert_with(|| self.find_override(&resolved.name, &resolved.descriptor))
+            .clone();
+
+        match selected {
+            Some(method) if method.is_abstract() => Err(Error::AbstractMethod(format!(
+                "{}.{}{}",
+                method.class().name,
+                method.name,
+                method.descriptor
+            ))),
+            Some(method) => Ok(method),
+            None => Err(Error::AbstractMethod(format!(
+                "{}.{}{}",
+                self.name, resolved.name, resolved.descriptor
+            ))),
+        }
+    }
+
+    fn find_override(self: &Rc<Self>, name: &str, descriptor: &str) -> Option<Rc<Method>> {
+        self.ancestors()
+            .find_map(|class| {
+                class
+                    .find_method(name, descriptor)
+                    .filter(|method| !method.is_static() && !method.is_private())
+            })
+            .or_else(|| self.superinterface_method(name, descriptor))
+    }
+
+    /// Finds the maximally-specific superinterface method, preferring one that isn't abstract
+    fn superinterface_method(self: &Rc<Self>, name: &str, descriptor: &str) -> Option<Rc<Method>> {
+        let candidates = self
+            .all_interfaces()
+            .into_iter()
+            .filter_map(|interface| interface.find_method(name, descriptor))
+            .filter(|method| !method.is_static() && !method.is_private())
+            .collect::<Vec<_>>();
+
+        let maximally_specific = candidates
+            .iter()
+            .filter(|method| {
+                let class = method.class();
+                !candidates.iter().any(|other| {
+                    let other = other.class();
+                    other.name != class.name && other.is_subtype_of(&class.name)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut concrete = maximally_specific
+            .iter()
+            .filter(|method| !method.is_abstract());
+        match (concrete.next(), concrete.next()) {
+            (Some(method), None) => Some(Rc::clone(method)),
+            _ => maximally_specific.first().map(|method| Rc::clone(method)),
+        }
+    }
+
+    fn no_such_method(&self, name: &str, descriptor: &str) -> Error {
+        Error::NoSuchMethod(format!("{}.{}{}", self.name, name, descriptor))
+    }
+}
@@ -0,0 +1,303 @@
+//! Conversions between Rust values and Java values, for calling Java from Rust
+use super::interpreter::Interpreter;
+use super::value::Value;
+use super::*;
+
+use ty::FieldType;
+
+/// A Rust value that can be passed to Java
+pub trait IntoJava {
+    /// The field descriptor of the Java type
+    fn descriptor() -> String;
+
+    fn into_java(self, vm: &mut Interpreter) -> Result<Value>;
+}
+
+/// A Rust value that can be made from a Java value
+pub trait FromJava: Sized {
+    /// The field descriptor of the Java type, `V` for `()`
+    fn descriptor() -> String;
+
+    fn from_java(vm: &Interpreter, value: Value) -> Result<Self>;
+}
+
+/// The arguments of a method, as a tuple of values that can be passed to Java
+pub trait JavaArgs {
+    /// The parameter descriptors, without the parentheses
+    fn descriptor() -> String;
+
+    fn into_java(self, vm: &mut Interpreter) -> Result<Vec<Value>>;
+}
+
+macro_rules! primitive {
+    ($($ty:ty => $descriptor:expr, $as:ident, |$value:ident| $from:expr);* $(;)?) => {
+        $(
+            impl IntoJava for $ty {
+                fn descriptor() -> String {
+                    $descriptor.to_string()
+                }
+
+                fn into_java(self, _: &mut Interpreter) -> Result<Value> {
+                    Ok(Value::from(self))
+                }
+            }
+
+            impl FromJava for $ty {
+                fn descriptor() -> String {
+                    $descriptor.to_string()
+                }
+
+                fn from_java(_: &Interpreter, value: Value) -> Result<Self> {
+                    let $value = value.$as()?;
+                    Ok($from)
+                }
+            }
+        )*
+    };
+}
+
+primitive! {
+    bool => "Z", as_int, |d| d != 0;
+    i8 => "B", as_int, |d| d as i8;
+    u16 => "C", as_int, |d| d as u16;
+    i16 => "S", as_int, |d| d as i16;
+    i32 => "I", as_int, |d| d;
+    i64 => "J", as_long, |d| d;
+    f32 => "F", as_float, |d| d;
+    f64 => "D", as_double, |d| d;
+}
+
+impl FromJava for () {
+    fn descriptor() -> String {
+        "V".to_string()
+    }
+
+    fn from_java(_: &Interpreter, _: Value) -> Result<Self> {
+        Ok(())
+    }
+}
+
+impl IntoJava for &str {
+    fn descriptor() -> String {
+        "Ljava/lang/String;".to_string()
+    }
+
+    fn into_java(self, vm: &mut Interpreter) -> Result<Value> {
+        vm.new_string(self).map(Value::from)
+    }
+}
+
+impl IntoJava for String {
+    fn descriptor() -> String {
+        <&str>::descriptor()
+    }
+
+    fn into_java(self, vm: &mut Interpreter) -> Result<Value> {
+        self.as_str().into_java(vm)
+    }
+}
+
+impl FromJava for String {
+    fn descriptor() -> String {
+        <&str>::descriptor()
+    }
+
+    fn from_java(vm: &Interpreter, value: Value) -> Result<Self> {
+        match value.as_reference()? {
+            Some(string) => vm.read_string(string),
+            None => generic_error!("unexpected null String, expected Option<String>"),
+        }
+    }
+}
+
+/// `None` is `null`
+impl<T: IntoJava> IntoJava for Option<T> {
+    fn descriptor() -> String {
+        T::descriptor()
+    }
+
+    fn into_java(self, vm: &mut Interpreter) -> Result<Value> {
+        match self {
+            Some(val) => val.into_java(vm),
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+/// `null` is `None`
+impl<T: FromJava> FromJava for Option<T> {
+    fn descriptor() -> String {
+        T::descriptor()
+    }
+
+    fn from_java(vm: &Interpreter, value: Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_java(vm, value).map(Some),
+        }
+    }
+}
+
+/// An array, which is copied into the heap
+impl<T: IntoJava> IntoJava for Vec<T> {
+    fn descriptor() -> String {
+        format!("[{}", T::descriptor())
+    }
+
+    fn into_java(self, vm: &mut Interpreter) -> Result<Value> {
+        let elements = self
+            .into_iter()
+            .map(|element| element.into_java(vm))
+            .collect::<Result<Vec<_>>>()?;
+        let component = FieldType::parse(&T::descriptor())?;
+        let array = vm.heap_mut().new_array(component, elements.len());
+        vm.heap_mut().array_mut(array)?.elements = elements;
+        Ok(array.into())
+    }
+}
+
+impl<T: IntoJava + Clone> IntoJava for &[T] {
+    fn descriptor() -> String {
+        Vec::<T>::descriptor()
+    }
+
+    fn into_java(self, vm: &mut Interpreter) -> Result<Value> {
+        self.to_vec().into_java(vm)
+    }
+}
+
+/// An array, which is copied out of the heap
+impl<T: FromJava> FromJava for Vec<T> {
+    fn descriptor() -> String {
+        format!("[{}", T::descriptor())
+    }
+
+    fn from_java(vm: &Interpreter, value: Value) -> Result<Self> {
+        let array = match value.as_reference()? {
+            Some(array) => array,
+            None => generic_error!("unexpected null array, expected Option<Vec<_>>"),
+        };
+        vm.heap()
+            .array(array)?
+            .elements
+            .iter()
+            .map(|&element| T::from_java(vm, element))
+            .collect()
+    }
+}
+
+macro_rules! args {
+    ($($arg:ident),*) => {
+        impl<$($arg: IntoJava),*> JavaArgs for ($($arg,)*) {
+            fn descriptor() -> String {
+                let descriptors: &[String] = &[$($arg::descriptor()),*];
+                descriptors.concat()
+            }
+
+            #[allow(non_snake_case, unused_variables)]
+            fn into_java(self, vm: &mut Interpreter) -> Result<Vec<Value>> {
+                let ($($arg,)*) = self;
+                Ok(vec![$($arg.into_java(vm)?),*])
+            }
+        }
+    };
+}
+
+args!();
+args!(A);
+args!(A, B);
+args!(A, B, C);
+args!(A, B, C, D);
+args!(A, B, C, D, E);
+args!(A, B, C, D, E, F);
+args!(A, B, C, D, E, F, G);
+args!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::load_classes;
+
+    #[test]
+    fn invoke_with_values() {
+        let mut vm = load_classes(&["embed"]);
+        let sum = vm.invoke_static("embed", "add", "(II)I", &[Value::Int(1), Value::Int(2)]);
+        assert_eq!(sum.unwrap(), Some(Value::Int(3)));
+
+        match vm.invoke_static("embed", "add", "(II)I", &[Value::Int(1)]) {
+            Err(Error::IllegalArgument(msg)) => {
+                assert_eq!(msg, "add(II)I takes 2 arguments, got 1")
+            }
+            e => panic!("{:?}", e),
+        }
+        match vm.invoke_static("embed", "add", "(II)I", &[Value::Int(1), Value::Long(2)]) {
+            Err(Error::IllegalArgument(msg)) => {
+                assert_eq!(msg, "argument 1 of add(II)I is not a I: Long(2)")
+            }
+            e => panic!("{:?}", e),
+        }
+        let array = vm.invoke_static("embed", "range", "(I)[I", &[Value::Int(2)]);
+        let array = array.unwrap().unwrap();
+        match vm.invoke_static(
+            "embed",
+            "join",
+            "([Ljava/lang/String;)Ljava/lang/String;",
+            &[array],
+        ) {
+            Err(Error::IllegalArgument(msg)) => assert!(msg.starts_with("argument 0 of join")),
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn invoke_typed() {
+        let mut vm = load_classes(&["embed"]);
+        let sum: i32 = vm.invoke("embed", "add", (1, 2)).unwrap();
+        assert_eq!(sum, 3);
+        let mean: f64 = vm.invoke("embed", "mean", (1i64, 2.0f32)).unwrap();
+        assert_eq!(mean, 1.5);
+        let upper: bool = vm.invoke("embed", "isUpper", ('Q' as u16,)).unwrap();
+        assert!(upper);
+
+        let greeting: String = vm.invoke("embed", "greet", ("plugin",)).unwrap();
+        assert_eq!(greeting, "hello, plugin");
+        let nothing: Option<String> = vm.invoke("embed", "greet", (None::<String>,)).unwrap();
+        assert_eq!(nothing, None);
+
+        let range: Vec<i32> = vm.invoke("embed", "range", (4,)).unwrap();
+        assert_eq!(range, vec![0, 1, 2, 3]);
+        let words = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let joined: String = vm.invoke("embed", "join", (words,)).unwrap();
+        assert_eq!(joined, "a-b-c");
+        let split: Vec<String> = vm.invoke("embed", "split", ("x,y",)).unwrap();
+        assert_eq!(split, vec!["x", "y"]);
+
+        let () = vm.invoke("embed", "count", ()).unwrap();
+        let count: i32 = vm.invoke("embed", "counter", ()).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn java_exceptions() {
+        let mut vm = load_classes(&["embed"]);
+        match vm.invoke::<_, i32>("embed", "divide", (1, 0)) {
+            Err(Error::Exception(exception)) => {
+                assert_eq!(exception.class, "java/lang/ArithmeticException");
+                assert_eq!(exception.message.as_deref(), Some("/ by zero"));
+                assert_eq!(exception.stack_trace[0].method, "divide");
+                assert_eq!(
+                    Error::Exception(exception).to_string().lines().next(),
+                    Some("java.lang.ArithmeticException: / by zero")
+                );
+            }
+            e => panic!("{:?}", e),
+        }
+        // the interpreter can still be used afterwards
+        assert_eq!(vm.invoke::<_, i32>("embed", "divide", (6, 3)).unwrap(), 2);
+
+        match vm.invoke::<_, i32>("embed", "add", (1i64, 2i64)) {
+            Err(Error::NoSuchMethod(name)) => assert_eq!(name, "embed.add(JJ)I"),
+            e => panic!("{:?}", e),
+        }
+    }
+}
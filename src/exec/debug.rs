@@ -0,0 +1,242 @@
+//! Stopping the interpreter at breakpoints and stepping through bytecode. A `Debugger`
+//! holds the breakpoints, and a handler that is called whenever execution stops. The
+//! handler looks at the program through the interpreter (`call_stack`, `locals`,
+//! `operand_stack`, `object_fields`) and says how to go on
+use super::heap::Reference;
+use super::interpreter::Interpreter;
+use super::value::Value;
+use super::*;
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// Called when execution stops, with why it stopped
+pub type DebugHandler = dyn FnMut(&mut Interpreter, &Stop) -> Result<Resume>;
+
+/// Where execution stops
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    /// At `pc` of a method, or its first instruction. The method can include the descriptor
+    /// to pick an overload (`add(II)I`)
+    Method {
+        class: String,
+        method: String,
+        pc: Option<usize>,
+    },
+    /// At the first instruction of a source line, from the `LineNumberTable`
+    Line { class: String, line: u16 },
+    /// When a field is written, or also read if `reads` is set
+    Field {
+        class: String,
+        field: String,
+        reads: bool,
+    },
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Method { class, method, pc } => {
+                write!(f, "{}.{}", class.replace('/', "."), method)?;
+                match pc {
+                    Some(pc) => write!(f, " at pc {}", pc),
+                    None => Ok(()),
+                }
+            }
+            Breakpoint::Line { class, line } => write!(f, "{}:{}", class.replace('/', "."), line),
+            Breakpoint::Field {
+                class,
+                field,
+                reads,
+            } => {
+                let access = if *reads { "access" } else { "write" };
+                write!(f, "{} of {}.{}", access, class.replace('/', "."), field)
+            }
+        }
+    }
+}
+
+/// How far a step goes
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Unit {
+    Instruction,
+    Line,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Step {
+    /// To the next instruction or line, including into the methods that are called
+    Into(Unit),
+    /// To the next instruction or line of this method, or of its caller once it returns
+    Over(Unit),
+    /// Until the method returns
+    Out,
+}
+
+/// How the handler wants execution to go on
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Resume {
+    Continue,
+    Step(Step),
+    /// Stops running the program
+    Quit,
+}
+
+/// Why execution stopped. It stops before the instruction at the top of the call stack
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop {
+    /// A step finished, or the program is about to start
+    Step,
+    Breakpoint(usize),
+    /// The pause flag was set. If the handler continues, the step that was running goes on
+    Pause,
+    /// The field of breakpoint `id` is about to be accessed. `object` is `None` for static
+    /// fields, and `value` is what is written
+    Field {
+        id: usize,
+        object: Option<Reference>,
+        value: Option<Value>,
+    },
+}
+
+/// A frame of the call stack
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameInfo {
+    pub thread: String,
+    pub class: String,
+    pub method: String,
+    pub descriptor: String,
+    pub pc: usize,
+    pub file: Option<String>,
+    pub line: Option<u16>,
+    /// The instruction at `pc`, as the disassembler writes it
+    pub instruction: String,
+}
+
+impl std::fmt::Display for FrameInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}(", self.class.replace('/', "."), self.method)?;
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{})", file, line)?,
+            (Some(file), None) => write!(f, "{})", file)?,
+            (None, _) => write!(f, "Unknown Source)")?,
+        }
+        write!(f, " pc {}: {}", self.pc, self.instruction)
+    }
+}
+
+/// What a thread is doing
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ThreadStatus {
+    Running,
+    Sleeping,
+    /// Waiting to enter a monitor
+    Monitor,
+    /// In `Object.wait` or `Thread.join`
+    Waiting,
+}
+
+/// A thread that has been started and hasn't ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadInfo {
+    /// The index of the thread in the interpreter, `0` for the main thread
+    pub id: usize,
+    pub name: String,
+    /// The `java/lang/Thread`, which the main thread only has once it asked for it
+    pub object: Option<Reference>,
+    pub status: ThreadStatus,
+}
+
+/// A local variable slot of a frame, with its name and type if the `LocalVariableTable` has
+/// them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+    pub index: usize,
+    pub name: Option<String>,
+    pub descriptor: Option<String>,
+    pub value: Value,
+}
+
+/// Where a step started from
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Stepping {
+    pub step: Step,
+    /// The thread and number of frames when it started, `None` to stop anywhere
+    pub origin: Option<(usize, usize)>,
+    pub line: Option<u16>,
+}
+
+/// The breakpoints, the step that is running and the handler to call when execution stops
+pub struct Debugger {
+    breakpoints: Vec<(usize, Breakpoint)>,
+    next_id: usize,
+    pub(crate) stepping: Option<Stepping>,
+    pub(crate) handler: Option<Box<DebugHandler>>,
+    pub(crate) pause: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for Debugger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Debugger")
+            .field("breakpoints", &self.breakpoints)
+            .field("stepping", &self.stepping)
+            .finish()
+    }
+}
+
+impl Debugger {
+    /// A debugger that stops before the first instruction that is run, so that breakpoints
+    /// can be set up
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: FnMut(&mut Interpreter, &Stop) -> Result<Resume> + 'static,
+    {
+        Self {
+            breakpoints: vec![],
+            next_id: 1,
+            stepping: Some(Stepping {
+                step: Step::Into(Unit::Instruction),
+                origin: None,
+                line: None,
+            }),
+            handler: Some(Box::new(handler)),
+            pause: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// A flag that stops execution before the next instruction with `Stop::Pause` when it is
+    /// set, so that another thread can get the handler called while the program runs
+    pub fn pause_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.pause)
+    }
+
+    /// Adds a breakpoint, and returns its id. Class names can be internal or binary names
+    pub fn add_breakpoint(&mut self, mut breakpoint: Breakpoint) -> usize {
+        match &mut breakpoint {
+            Breakpoint::Method { class, .. }
+            | Breakpoint::Line { class, .. }
+            | Breakpoint::Field { class, .. } => *class = class.replace('.', "/"),
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push((id, breakpoint));
+        id
+    }
+
+    /// Removes the breakpoint `id`, if there is one
+    pub fn remove_breakpoint(&mut self, id: usize) -> Option<Breakpoint> {
+        let index = self.breakpoints.iter().position(|(i, _)| *i == id)?;
+        Some(self.breakpoints.remove(index).1)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.breakpoints
+            .iter()
+            .map(|(id, breakpoint)| (*id, breakpoint))
+    }
+}
+
+/// Whether `pattern` names the method, by name or by name and descriptor
+pub(crate) fn matches_method(pattern: &str, name: &str, descriptor: &str) -> bool {
+    pattern == name || (pattern.starts_with(name) && &pattern[name.len()..] == descriptor)
+}
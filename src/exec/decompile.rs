@@ -0,0 +1,644 @@
+//! Decompiling class files back to Java-like source
+//!
+//! Each basic block is lifted into statements by keeping the expression that computed
+//! each value on the operand stack, then the blocks are structured into conditionals,
+//! loops, switches and try/catch by following the control flow graph. The copies javac
+//! makes of `finally` blocks are folded back into one, and the ones that exit a monitor
+//! into `synchronized`. Whatever can't be structured is left as labels and `goto`s.
+use super::*;
+
+mod expr;
+mod lift;
+mod signature;
+mod simplify;
+mod structure;
+
+pub use expr::{Catch, Expr, Op, Stmt};
+
+use cfg::Cfg;
+use lift::{Lifter, Locals, Value};
+use ty::{Constant, ConstantIndex, FieldType, MethodDescriptor};
+
+use std::fmt::Write;
+
+/// Decompiles the class, with all of its fields and methods
+pub fn decompile_class(file: &ty::ClassFile) -> Result<String> {
+    let class = file.get_class_name();
+    let package = package(class);
+    let names = |name: &str| class_name(package, name);
+
+    let mut out = String::new();
+    if !package.is_empty() {
+        let _ = writeln!(out, "package {};\n", package.replace('/', "."));
+    }
+
+    let flags = file.flags;
+    let is_interface = flags.contains(ty::ClassFlags::INTERFACE);
+    let signature = signature_of(file, &file.attributes)
+        .and_then(|signature| signature::class(signature, &names));
+
+    let mut header = vec![];
+    if flags.contains(ty::ClassFlags::PUBLIC) {
+        header.push("public");
+    }
+    if flags.contains(ty::ClassFlags::ABSTRACT) && !is_interface {
+        header.push("abstract");
+    }
+    if flags.contains(ty::ClassFlags::FINAL) && !flags.contains(ty::ClassFlags::ENUM) {
+        header.push("final");
+    }
+    header.push(if flags.contains(ty::ClassFlags::ANNOTATION) {
+        "@interface"
+    } else if is_interface {
+        "interface"
+    } else if flags.contains(ty::ClassFlags::ENUM) {
+        "enum"
+    } else {
+        "class"
+    });
+    let _ = write!(out, "{} {}", header.join(" "), simple_name(class));
+
+    let (params, super_class, interfaces) = match signature {
+        Some(signature) => (
+            signature.params,
+            Some(signature.super_class),
+            signature.interfaces,
+        ),
+        None => (
+            String::new(),
+            file.super_class_name()?.map(names),
+            (file.interfaces.iter())
+                .map(|&index| file.class_name(index).map(names))
+                .collect::<std::result::Result<_, _>>()?,
+        ),
+    };
+    out.push_str(&params);
+    match super_class {
+        Some(super_class) if !is_interface && super_class != "Object" && super_class != "Enum" => {
+            let _ = write!(out, " extends {}", super_class);
+        }
+        _ => {}
+    }
+    if !interfaces.is_empty() {
+        let keyword = if is_interface {
+            "extends"
+        } else {
+            "implements"
+        };
+        let _ = write!(out, " {} {}", keyword, interfaces.join(", "));
+    }
+    out.push_str(" {\n");
+
+    let mut members = vec![];
+    for field in &file.fields {
+        let mut member = String::new();
+        write_field(&mut member, file, field)?;
+        members.push(member);
+    }
+    for method in &file.methods {
+        if method.flags.contains(ty::MethodFlags::BRIDGE) {
+            continue;
+        }
+        members.push(decompile_method(file, method)?);
+    }
+    let indented = members.iter().map(|member| {
+        (member.lines())
+            .map(|line| match line {
+                "" => "\n".to_string(),
+                line => format!("    {}\n", line),
+            })
+            .collect::<String>()
+    });
+    // fields are grouped together, and methods are separated by a blank line
+    let fields = file.fields.len();
+    for (i, member) in indented.enumerate() {
+        if i > 0 && i >= fields {
+            out.push('\n');
+        }
+        out.push_str(&member);
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// Decompiles the method, with its declaration
+pub fn decompile_method(file: &ty::ClassFile, method: &ty::Method) -> Result<String> {
+    let ctx = Context::new(file, method)?;
+    let names = |name: &str| ctx.class_name(name);
+
+    let flags = method.flags;
+    let is_interface = file.flags.contains(ty::ClassFlags::INTERFACE);
+    let mut out = String::new();
+    let mut header = vec![];
+    for (flag, keyword) in &[
+        (ty::MethodFlags::PUBLIC, "public"),
+        (ty::MethodFlags::PROTECTED, "protected"),
+        (ty::MethodFlags::PRIVATE, "private"),
+        (ty::MethodFlags::ABSTRACT, "abstract"),
+        (ty::MethodFlags::STATIC, "static"),
+        (ty::MethodFlags::FINAL, "final"),
+        (ty::MethodFlags::SYNCHRONIZED, "synchronized"),
+        (ty::MethodFlags::NATIVE, "native"),
+        (ty::MethodFlags::STRICT, "strictfp"),
+    ] {
+        let implied = is_interface
+            && (*flag == ty::MethodFlags::PUBLIC || *flag == ty::MethodFlags::ABSTRACT);
+        if flags.contains(*flag) && !implied {
+            header.push(*keyword);
+        }
+    }
+    if is_interface && ctx.code.is_some() && !ctx.is_static {
+        header.push("default");
+    }
+    let name = method.name();
+    if name == "<clinit>" {
+        out.push_str("static");
+    } else {
+        for keyword in header {
+            let _ = write!(out, "{} ", keyword);
+        }
+
+        let signature = signature_of(file, &method.attributes)
+            .and_then(|signature| signature::method(signature, &names))
+            // inner class constructors have parameters that aren't in the signature
+            .filter(|signature| signature.args.len() == ctx.descriptor.params.len());
+
+        if let Some(signature) = &signature {
+            if !signature.params.is_empty() {
+                let _ = write!(out, "{} ", signature.params);
+            }
+        }
+        if name == "<init>" {
+            out.push_str(simple_name(ctx.class));
+        } else {
+            let ret = match (&signature, &ctx.descriptor.ret) {
+                (Some(signature), _) => signature.ret.clone(),
+                (None, Some(ret)) => ctx.type_name(ret),
+                (None, None) => "void".to_string(),
+            };
+            let _ = write!(out, "{} {}", ret, name);
+        }
+
+        let locals = Locals::new(&ctx)?;
+        let params = locals.params(&ctx);
+        let last = params.len().wrapping_sub(1);
+        out.push('(');
+        for (i, (ty, name)) in params.iter().enumerate() {
+            let ty = match &signature {
+                Some(signature) => &signature.args[i],
+                None => ty,
+            };
+            if i > 0 {
+                out.push_str(", ");
+            }
+            match ty.strip_suffix("[]") {
+                Some(element) if i == last && flags.contains(ty::MethodFlags::VARARGS) => {
+                    let _ = write!(out, "{}... {}", element, name);
+                }
+                _ => {
+                    let _ = write!(out, "{} {}", ty, name);
+                }
+            }
+        }
+        out.push(')');
+
+        let throws = match &signature {
+            Some(signature) if !signature.throws.is_empty() => signature.throws.clone(),
+            _ => exceptions(file, method)?
+                .into_iter()
+                .map(|class| ctx.class_name(class))
+                .collect(),
+        };
+        if !throws.is_empty() {
+            let _ = write!(out, " throws {}", throws.join(", "));
+        }
+    }
+
+    if ctx.code.is_none() {
+        out.push_str(";\n");
+        return Ok(out);
+    }
+    out.push_str(" {\n");
+    match body(&ctx) {
+        Ok(body) => expr::write_block(&mut out, &body, 1),
+        Err(err) => {
+            let _ = writeln!(out, "    // could not decompile: {}", err);
+        }
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// Decompiles the statements of the method's code
+fn body(ctx: &Context<'_>) -> Result<Vec<Stmt>> {
+    let code = match ctx.code {
+        Some(code) => code,
+        None => generic_error!("{} has no code", ctx.method.name()),
+    };
+    let cfg = Cfg::from_code(ctx.file, code)?;
+    let mut lifter = Lifter::new(ctx, Locals::new(ctx)?);
+    let body = structure::structure(ctx, &cfg, &mut lifter)?;
+    Ok(simplify::simplify(ctx, body, &lifter.locals))
+}
+
+fn write_field(out: &mut String, file: &ty::ClassFile, field: &ty::Field) -> Result<()> {
+    let package = package(file.get_class_name());
+    let names = |name: &str| class_name(package, name);
+    for (flag, keyword) in &[
+        (ty::FieldFlags::PUBLIC, "public"),
+        (ty::FieldFlags::PROTECTED, "protected"),
+        (ty::FieldFlags::PRIVATE, "private"),
+        (ty::FieldFlags::STATIC, "static"),
+        (ty::FieldFlags::FINAL, "final"),
+        (ty::FieldFlags::VOLATILE, "volatile"),
+        (ty::FieldFlags::TRANSIENT, "transient"),
+    ] {
+        if field.flags.contains(*flag) {
+            let _ = write!(out, "{} ", keyword);
+        }
+    }
+
+    let descriptor = FieldType::parse(file.utf8(field.descriptor)?)?;
+    let ty = signature_of(file, &field.attributes)
+        .and_then(|signature| signature::field(signature, &names))
+        .unwrap_or_else(|| type_name(package, &descriptor));
+    let _ = write!(out, "{} {}", ty, file.utf8(field.name)?);
+
+    let value = field
+        .attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            attr::Attribute::ConstantValue(value) => Some(value.constant_value),
+            _ => None,
+        });
+    if let Some(index) = value {
+        let value = constant(file, package, index)?;
+        let _ = write!(out, " = {}", lift::coerce(value.expr, &descriptor));
+    }
+    out.push_str(";\n");
+    Ok(())
+}
+
+/// What decompiling a method needs to know about it
+pub(super) struct Context<'a> {
+    pub file: &'a ty::ClassFile,
+    pub method: &'a ty::Method,
+    pub code: Option<&'a attr::Code>,
+    pub class: &'a str,
+    pub package: &'a str,
+    pub descriptor: MethodDescriptor,
+    pub is_static: bool,
+}
+
+impl<'a> Context<'a> {
+    fn new(file: &'a ty::ClassFile, method: &'a ty::Method) -> Result<Self> {
+        let class = file.get_class_name();
+        Ok(Self {
+            file,
+            method,
+            code: method.get_code(),
+            class,
+            package: package(class),
+            descriptor: MethodDescriptor::parse(file.utf8(method.descriptor)?)?,
+            is_static: method.flags.contains(ty::MethodFlags::STATIC),
+        })
+    }
+
+    pub fn is_constructor(&self) -> bool {
+        self.method.name() == "<init>"
+    }
+
+    pub fn class_name(&self, name: &str) -> String {
+        class_name(self.package, name)
+    }
+
+    pub fn type_name(&self, ty: &FieldType) -> String {
+        type_name(self.package, ty)
+    }
+}
+
+fn package(class: &str) -> &str {
+    class.rfind('/').map_or("", |i| &class[..i])
+}
+
+fn simple_name(class: &str) -> &str {
+    class.rsplit('/').next().unwrap_or(class)
+}
+
+/// How the class is named in the source of a class in `package`
+fn class_name(package: &str, name: &str) -> String {
+    if name.starts_with('[') {
+        if let Ok(ty) = FieldType::parse(name) {
+            return type_name(package, &ty);
+        }
+    }
+    let (class_package, simple) = match name.rfind('/') {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => ("", name),
+    };
+    if class_package == package || class_package == "java/lang" {
+        simple.to_string()
+    } else {
+        name.replace('/', ".")
+    }
+}
+
+fn type_name(package: &str, ty: &FieldType) -> String {
+    match ty {
+        FieldType::Object(name) => class_name(package, name),
+        FieldType::Array(element) => format!("{}[]", type_name(package, element)),
+        primitive => primitive.keyword().unwrap_or_default().to_string(),
+    }
+}
+
+fn signature_of<'a>(file: &'a ty::ClassFile, attributes: &[attr::Attribute]) -> Option<&'a str> {
+    attributes.iter().find_map(|attribute| match attribute {
+        attr::Attribute::Signature(signature) => file.utf8(signature.signature).ok(),
+        _ => None,
+    })
+}
+
+/// The classes in the `Exceptions` attribute of the method
+fn exceptions<'a>(file: &'a ty::ClassFile, method: &ty::Method) -> Result<Vec<&'a str>> {
+    let mut classes = vec![];
+    for attribute in &method.attributes {
+        if let attr::Attribute::Exceptions(exceptions) = attribute {
+            for &index in &exceptions.index_table {
+                classes.push(file.class_name(index)?);
+            }
+        }
+    }
+    Ok(classes)
+}
+
+/// A loadable constant, as a literal
+fn constant(file: &ty::ClassFile, package: &str, index: ConstantIndex) -> Result<Value> {
+    let (expr, ty) = match index.lookup(&file.constant_pool)? {
+        Constant::Integer(i) => (Expr::Int(*i as i32), FieldType::Int),
+        Constant::Float(f) => (Expr::Literal(float(*f)), FieldType::Float),
+        Constant::Long(l) => (Expr::Literal(format!("{}L", *l as i64)), FieldType::Long),
+        Constant::Double(d) => (Expr::Literal(double(*d)), FieldType::Double),
+        Constant::StringRef(s) => (
+            Expr::Literal(expr::quote(file.utf8(*s)?)),
+            FieldType::Object("java/lang/String".into()),
+        ),
+        Constant::ClassRef(name) => (
+            Expr::Literal(format!("{}.class", class_name(package, file.utf8(*name)?))),
+            FieldType::Object("java/lang/Class".into()),
+        ),
+        Constant::MethodType(descriptor) => (
+            Expr::Literal(format!("/* MethodType {} */", file.utf8(*descriptor)?)),
+            FieldType::Object("java/lang/invoke/MethodType".into()),
+        ),
+        Constant::MethodHandleRef(handle) => {
+            let member = file.member_ref(handle.reference())?;
+            (
+                Expr::Literal(format!(
+                    "/* MethodHandle {}.{}{} */",
+                    member.class, member.name, member.descriptor
+                )),
+                FieldType::Object("java/lang/invoke/MethodHandle".into()),
+            )
+        }
+        constant => generic_error!("constant {:?} can not be loaded", constant),
+    };
+    Ok(Value { expr, ty: Some(ty) })
+}
+
+pub(super) fn float(f: f32) -> String {
+    match f {
+        f if f.is_nan() => "Float.NaN".to_string(),
+        f if f.is_infinite() && f > 0.0 => "Float.POSITIVE_INFINITY".to_string(),
+        f if f.is_infinite() => "Float.NEGATIVE_INFINITY".to_string(),
+        f => format!("{:?}f", f),
+    }
+}
+
+pub(super) fn double(d: f64) -> String {
+    match d {
+        d if d.is_nan() => "Double.NaN".to_string(),
+        d if d.is_infinite() && d > 0.0 => "Double.POSITIVE_INFINITY".to_string(),
+        d if d.is_infinite() => "Double.NEGATIVE_INFINITY".to_string(),
+        d => format!("{:?}", d),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(name: &str) -> ty::ClassFile {
+        let data = std::fs::read(format!("./etc/{}.class", name)).unwrap();
+        ty::ClassFile::read(&mut data.as_slice()).unwrap()
+    }
+
+    fn method(file: &ty::ClassFile, name: &str) -> String {
+        let method = file.methods.iter().find(|m| m.name() == name).unwrap();
+        decompile_method(file, method).unwrap()
+    }
+
+    #[test]
+    fn loops() {
+        let file = read("decompile");
+        assert_eq!(
+            method(&file, "sum"),
+            "static int sum(int[] values) {
+    int total = 0;
+    int i = 0;
+    while (i < values.length) {
+        total += values[i];
+        i++;
+    }
+    return total;
+}
+"
+        );
+        assert_eq!(
+            method(&file, "countdown"),
+            "static int countdown(int n) {
+    int steps = 0;
+    do {
+        n /= 2;
+        steps++;
+    } while (n > 0);
+    return steps;
+}
+"
+        );
+        assert_eq!(
+            method(&file, "max"),
+            "static <T extends Comparable<T>> T max(T[] items) {
+    T best = null;
+    Comparable[] o2 = items;
+    int i3 = o2.length;
+    int i4 = 0;
+    while (i4 < i3) {
+        T item = o2[i4];
+        if (best == null || item.compareTo(best) > 0) {
+            best = item;
+        }
+        i4++;
+    }
+    return best;
+}
+"
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        let file = read("decompile");
+        assert_eq!(
+            method(&file, "sign"),
+            r#"static String sign(int x, boolean strict) {
+    if (x > 0 && strict) {
+        return "positive";
+    }
+    if (x >= 0 && strict) {
+        return "zero";
+    }
+    return "negative";
+}
+"#
+        );
+        assert_eq!(
+            method(&file, "pick"),
+            "static int pick(boolean flag, int a, int b) {
+    return flag ? a : b;
+}
+"
+        );
+        assert_eq!(
+            method(&file, "name"),
+            r#"static String name(int day) {
+    String result;
+    switch (day) {
+        case 0:
+            result = "sunday";
+            break;
+        case 6:
+            result = "saturday";
+            break;
+        default:
+            result = "weekday";
+    }
+    return result;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn exceptions() {
+        let file = read("decompile");
+        assert_eq!(
+            method(&file, "parse"),
+            "static int parse(String s) {
+    try {
+        return Integer.parseInt(s);
+    } catch (NumberFormatException e) {
+        return -1;
+    }
+}
+"
+        );
+
+        assert_eq!(
+            method(&file, "attempt"),
+            r#"static int attempt(String s) {
+    int result;
+    try {
+        result = Integer.parseInt(s);
+    } catch (NumberFormatException e) {
+        result = -1;
+    } finally {
+        System.out.println("done");
+    }
+    return result;
+}
+"#
+        );
+
+        // without debug info, and with a finally block
+        let file = read("exceptions");
+        assert_eq!(
+            method(&file, "finallyBlock"),
+            "static int finallyBlock() {
+    exceptions.counter = 0;
+    try {
+        try {
+            thrower(1);
+        } finally {
+            exceptions.counter += 10;
+        }
+    } catch (Oops o0) {
+        exceptions.counter += o0.code;
+    }
+    return exceptions.counter;
+}
+"
+        );
+    }
+
+    #[test]
+    fn monitors() {
+        let file = read("decompile");
+        assert_eq!(
+            method(&file, "locked"),
+            "int locked(String name) {
+    synchronized (this) {
+        this.last = name;
+        return this.count;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        let file = read("decompile");
+        assert_eq!(
+            method(&file, "greet"),
+            r#"static String greet(String name, int times) {
+    return "hello " + name + " x" + times;
+}
+"#
+        );
+        assert_eq!(
+            method(&file, "primes"),
+            "static int[] primes() {
+    return new int[]{2, 3, 5, 7};
+}
+"
+        );
+    }
+
+    #[test]
+    fn class() {
+        let out = decompile_class(&read("decompile")).unwrap();
+        assert!(out.starts_with(
+            "public class decompile {
+    private Comparable<String> last;
+    private int count;
+    static final int LIMIT = 10;
+
+    public decompile() {
+        this.last = \"\";
+    }
+
+    static int sum(int[] values) {
+"
+        ));
+        assert!(out.contains(
+            "    void add(String name) {
+        if (this.count < 10) {
+            this.last = name;
+            this.count++;
+        }
+    }
+}
+"
+        ));
+    }
+}
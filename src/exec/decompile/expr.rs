@@ -0,0 +1,759 @@
+//! The expressions and statements of decompiled code, and how they're printed
+use std::fmt::{self, Write};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    Ushr,
+    And,
+    Or,
+    Xor,
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+    AndAnd,
+    OrOr,
+    Neg,
+    Not,
+}
+
+impl Op {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Op::Add => "+",
+            Op::Sub | Op::Neg => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Rem => "%",
+            Op::Shl => "<<",
+            Op::Shr => ">>",
+            Op::Ushr => ">>>",
+            Op::And => "&",
+            Op::Or => "|",
+            Op::Xor => "^",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Ge => ">=",
+            Op::Gt => ">",
+            Op::Le => "<=",
+            Op::AndAnd => "&&",
+            Op::OrOr => "||",
+            Op::Not => "!",
+        }
+    }
+
+    // https://docs.oracle.com/javase/specs/jls/se8/html/jls-15.html
+    fn precedence(self) -> u8 {
+        match self {
+            Op::OrOr => 3,
+            Op::AndAnd => 4,
+            Op::Or => 5,
+            Op::Xor => 6,
+            Op::And => 7,
+            Op::Eq | Op::Ne => 8,
+            Op::Lt | Op::Ge | Op::Gt | Op::Le => 9,
+            Op::Shl | Op::Shr | Op::Ushr => 10,
+            Op::Add | Op::Sub => 11,
+            Op::Mul | Op::Div | Op::Rem => 12,
+            Op::Neg | Op::Not => 13,
+        }
+    }
+
+    /// The comparison that's true when this one is false
+    fn inverse(self) -> Option<Op> {
+        let op = match self {
+            Op::Eq => Op::Ne,
+            Op::Ne => Op::Eq,
+            Op::Lt => Op::Ge,
+            Op::Ge => Op::Lt,
+            Op::Gt => Op::Le,
+            Op::Le => Op::Gt,
+            _ => return None,
+        };
+        Some(op)
+    }
+}
+
+const CONDITIONAL: u8 = 2;
+const UNARY: u8 = 13;
+const PRIMARY: u8 = 14;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A literal, or anything else that's printed as is
+    Literal(String),
+    /// An int constant, which might turn out to be a `boolean` or a `char`
+    Int(i32),
+    /// A local variable (or `this`)
+    Local(String),
+    /// A type name, like the class of a static field
+    Name(String),
+    /// The exception caught by a handler, before it's stored
+    Caught,
+    Field(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Unary(Op, Box<Expr>),
+    Cast(String, Box<Expr>),
+    InstanceOf(Box<Expr>, String),
+    /// A method call on an object or a class, or `this(..)` and `super(..)` without one
+    Call(Option<Box<Expr>>, String, Vec<Expr>),
+    New(String, Vec<Expr>),
+    /// An array of the element type with the lengths, and the dimensions left unsized
+    NewArray(String, Vec<Expr>, usize),
+    ArrayInit(String, Vec<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// `lcmp`, `fcmpl` and friends, of the boxed type, which are usually part of a condition
+    Compare(&'static str, Box<Expr>, Box<Expr>),
+    /// An object created by `new`, tagged with its pc, before its constructor is called
+    Uninit(usize, String),
+    /// A `StringBuilder`, and the values appended to it, whether they're strings
+    Concat(Option<Box<Expr>>, Vec<(Expr, bool)>),
+}
+
+impl Expr {
+    pub fn local(name: impl Into<String>) -> Self {
+        Expr::Local(name.into())
+    }
+
+    pub fn binary(op: Op, left: Expr, right: Expr) -> Self {
+        Expr::Binary(op, Box::new(left), Box::new(right))
+    }
+
+    /// The condition that's true when this one is false
+    pub fn negate(self) -> Self {
+        match self {
+            Expr::Unary(Op::Not, expr) => *expr,
+            Expr::Binary(op, left, right) => match (op, op.inverse()) {
+                (_, Some(inverse)) => Expr::Binary(inverse, left, right),
+                (Op::AndAnd, _) => Expr::binary(Op::OrOr, left.negate(), right.negate()),
+                (Op::OrOr, _) => Expr::binary(Op::AndAnd, left.negate(), right.negate()),
+                _ => Expr::Unary(Op::Not, Box::new(Expr::Binary(op, left, right))),
+            },
+            Expr::Literal(s) if s == "true" => Expr::Literal("false".into()),
+            Expr::Literal(s) if s == "false" => Expr::Literal("true".into()),
+            expr => Expr::Unary(Op::Not, Box::new(expr)),
+        }
+    }
+
+    /// Whether this can be evaluated again (or not at all) without changing anything
+    pub fn is_simple(&self) -> bool {
+        match self {
+            Expr::Literal(..)
+            | Expr::Int(..)
+            | Expr::Local(..)
+            | Expr::Name(..)
+            | Expr::Caught
+            | Expr::Uninit(..) => true,
+            Expr::Field(object, _) => object.is_simple(),
+            _ => false,
+        }
+    }
+
+    /// Whether evaluating this could have side effects, other than throwing
+    pub fn has_effects(&self) -> bool {
+        let mut effects = false;
+        self.visit(&mut |expr| {
+            if let Expr::Call(..) | Expr::New(..) | Expr::Concat(..) = expr {
+                effects = true
+            }
+        });
+        effects
+    }
+
+    /// How many times the local variable `name` is used
+    pub fn uses(&self, name: &str) -> usize {
+        let mut count = 0;
+        self.visit(&mut |expr| {
+            if matches!(expr, Expr::Local(local) if local == name) {
+                count += 1
+            }
+        });
+        count
+    }
+
+    /// Replaces the uses of the local variable `name` with `with`
+    pub fn replace(&mut self, name: &str, with: &Expr) {
+        self.visit_mut(&mut |expr| {
+            if matches!(expr, Expr::Local(local) if local == name) {
+                *expr = with.clone();
+            }
+        });
+    }
+
+    pub fn visit(&self, f: &mut dyn FnMut(&Expr)) {
+        f(self);
+        match self {
+            Expr::Field(expr, _)
+            | Expr::Unary(_, expr)
+            | Expr::Cast(_, expr)
+            | Expr::InstanceOf(expr, _) => expr.visit(f),
+            Expr::Index(a, b) | Expr::Binary(_, a, b) | Expr::Compare(_, a, b) => {
+                a.visit(f);
+                b.visit(f);
+            }
+            Expr::Call(object, _, args) => {
+                if let Some(object) = object {
+                    object.visit(f);
+                }
+                args.iter().for_each(|arg| arg.visit(f));
+            }
+            Expr::New(_, args) | Expr::NewArray(_, args, _) | Expr::ArrayInit(_, args) => {
+                args.iter().for_each(|arg| arg.visit(f))
+            }
+            Expr::Conditional(a, b, c) => {
+                a.visit(f);
+                b.visit(f);
+                c.visit(f);
+            }
+            Expr::Concat(init, parts) => {
+                if let Some(init) = init {
+                    init.visit(f);
+                }
+                parts.iter().for_each(|(part, _)| part.visit(f));
+            }
+            Expr::Literal(..)
+            | Expr::Int(..)
+            | Expr::Local(..)
+            | Expr::Name(..)
+            | Expr::Caught
+            | Expr::Uninit(..) => {}
+        }
+    }
+
+    pub fn visit_mut(&mut self, f: &mut dyn FnMut(&mut Expr)) {
+        f(self);
+        match self {
+            Expr::Field(expr, _)
+            | Expr::Unary(_, expr)
+            | Expr::Cast(_, expr)
+            | Expr::InstanceOf(expr, _) => expr.visit_mut(f),
+            Expr::Index(a, b) | Expr::Binary(_, a, b) | Expr::Compare(_, a, b) => {
+                a.visit_mut(f);
+                b.visit_mut(f);
+            }
+            Expr::Call(object, _, args) => {
+                if let Some(object) = object {
+                    object.visit_mut(f);
+                }
+                args.iter_mut().for_each(|arg| arg.visit_mut(f));
+            }
+            Expr::New(_, args) | Expr::NewArray(_, args, _) | Expr::ArrayInit(_, args) => {
+                args.iter_mut().for_each(|arg| arg.visit_mut(f))
+            }
+            Expr::Conditional(a, b, c) => {
+                a.visit_mut(f);
+                b.visit_mut(f);
+                c.visit_mut(f);
+            }
+            Expr::Concat(init, parts) => {
+                if let Some(init) = init {
+                    init.visit_mut(f);
+                }
+                parts.iter_mut().for_each(|(part, _)| part.visit_mut(f));
+            }
+            Expr::Literal(..)
+            | Expr::Int(..)
+            | Expr::Local(..)
+            | Expr::Name(..)
+            | Expr::Caught
+            | Expr::Uninit(..) => {}
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(op, ..) | Expr::Unary(op, _) => op.precedence(),
+            Expr::Cast(..) => UNARY,
+            Expr::InstanceOf(..) => Op::Lt.precedence(),
+            Expr::Conditional(..) => CONDITIONAL,
+            Expr::Literal(s) if s.starts_with('-') => UNARY,
+            Expr::Int(i) if *i < 0 => UNARY,
+            _ => PRIMARY,
+        }
+    }
+
+    /// Writes the expression, in parentheses if it binds looser than `precedence`
+    fn write(&self, f: &mut fmt::Formatter<'_>, precedence: u8) -> fmt::Result {
+        if self.precedence() < precedence {
+            f.write_char('(')?;
+            self.write(f, 0)?;
+            return f.write_char(')');
+        }
+        match self {
+            Expr::Literal(s) | Expr::Local(s) | Expr::Name(s) => f.write_str(s),
+            Expr::Int(i) => write!(f, "{}", i),
+            Expr::Caught => f.write_str("e"),
+            Expr::Field(object, name) => {
+                object.write(f, PRIMARY)?;
+                write!(f, ".{}", name)
+            }
+            Expr::Index(array, index) => {
+                array.write(f, PRIMARY)?;
+                f.write_char('[')?;
+                index.write(f, 0)?;
+                f.write_char(']')
+            }
+            Expr::Binary(op, left, right) => {
+                let precedence = op.precedence();
+                left.write(f, precedence)?;
+                write!(f, " {} ", op.symbol())?;
+                right.write(f, precedence + 1)
+            }
+            Expr::Unary(op, expr) => {
+                f.write_str(op.symbol())?;
+                // `- -x` isn't `--x`
+                match &**expr {
+                    Expr::Unary(Op::Neg, _) | Expr::Int(..) | Expr::Literal(..)
+                        if *op == Op::Neg && expr.precedence() == UNARY =>
+                    {
+                        f.write_char('(')?;
+                        expr.write(f, 0)?;
+                        f.write_char(')')
+                    }
+                    _ => expr.write(f, UNARY),
+                }
+            }
+            Expr::Cast(ty, expr) => {
+                write!(f, "({}) ", ty)?;
+                expr.write(f, UNARY)
+            }
+            Expr::InstanceOf(expr, ty) => {
+                expr.write(f, Op::Lt.precedence())?;
+                write!(f, " instanceof {}", ty)
+            }
+            Expr::Call(object, name, args) => {
+                if let Some(object) = object {
+                    object.write(f, PRIMARY)?;
+                    f.write_char('.')?;
+                }
+                write!(f, "{}(", name)?;
+                write_list(f, args)?;
+                f.write_char(')')
+            }
+            Expr::New(class, args) => {
+                write!(f, "new {}(", class)?;
+                write_list(f, args)?;
+                f.write_char(')')
+            }
+            Expr::NewArray(element, lengths, unsized_dimensions) => {
+                write!(f, "new {}", element)?;
+                for length in lengths {
+                    f.write_char('[')?;
+                    length.write(f, 0)?;
+                    f.write_char(']')?;
+                }
+                (0..*unsized_dimensions).try_for_each(|_| f.write_str("[]"))
+            }
+            Expr::ArrayInit(ty, values) => {
+                write!(f, "new {}{{", ty)?;
+                write_list(f, values)?;
+                f.write_char('}')
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                condition.write(f, CONDITIONAL + 1)?;
+                f.write_str(" ? ")?;
+                then.write(f, CONDITIONAL + 1)?;
+                f.write_str(" : ")?;
+                otherwise.write(f, CONDITIONAL)
+            }
+            Expr::Compare(ty, left, right) => {
+                write!(f, "{}.compare(", ty)?;
+                write_list(f, &[(**left).clone(), (**right).clone()])?;
+                f.write_char(')')
+            }
+            Expr::Uninit(_, class) => write!(f, "new {}", class),
+            Expr::Concat(init, parts) => {
+                f.write_str("new StringBuilder(")?;
+                if let Some(init) = init {
+                    init.write(f, 0)?;
+                }
+                f.write_char(')')?;
+                for (part, _) in parts {
+                    f.write_str(".append(")?;
+                    part.write(f, 0)?;
+                    f.write_char(')')?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, exprs: &[Expr]) -> fmt::Result {
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        expr.write(f, CONDITIONAL)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catch {
+    pub class: String,
+    pub name: String,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Expr(Expr),
+    Declare(String, String, Option<Expr>),
+    Assign(Expr, Expr),
+    /// `a op= b`
+    Compound(Expr, Op, Expr),
+    /// `iinc` of the local variable
+    Increment(String, i32),
+    Return(Option<Expr>),
+    Throw(Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Option<String>, Expr, Vec<Stmt>),
+    DoWhile(Option<String>, Vec<Stmt>, Expr),
+    /// The cases are the keys that lead to them, `None` for the default
+    Switch(Expr, Vec<(Vec<Option<i32>>, Vec<Stmt>)>),
+    /// The try block, its catches, and the `finally` block
+    Try(Vec<Stmt>, Vec<Catch>, Vec<Stmt>),
+    Synchronized(Expr, Vec<Stmt>),
+    /// The `monitorenter` and `monitorexit` that aren't part of a `synchronized` block
+    MonitorEnter(Expr),
+    MonitorExit(Expr),
+    Break(Option<String>),
+    Continue(Option<String>),
+    Label(String),
+    Goto(String),
+    /// Something without a Java equivalent, like `jsr`
+    Raw(String),
+}
+
+impl Stmt {
+    /// Whether control never continues after this
+    pub fn is_jump(&self) -> bool {
+        match self {
+            Stmt::Return(..)
+            | Stmt::Throw(..)
+            | Stmt::Break(..)
+            | Stmt::Continue(..)
+            | Stmt::Goto(..) => true,
+            Stmt::If(_, then, otherwise) => ends_in_jump(then) && ends_in_jump(otherwise),
+            _ => false,
+        }
+    }
+
+    /// Calls `f` with each expression of this statement, and the statements in it
+    pub fn visit(&self, f: &mut dyn FnMut(&Expr)) {
+        match self {
+            Stmt::Expr(expr)
+            | Stmt::Throw(expr)
+            | Stmt::Return(Some(expr))
+            | Stmt::MonitorEnter(expr)
+            | Stmt::MonitorExit(expr) => f(expr),
+            Stmt::Declare(_, _, value) => value.iter().for_each(f),
+            Stmt::Assign(target, value) | Stmt::Compound(target, _, value) => {
+                f(target);
+                f(value);
+            }
+            Stmt::Increment(name, _) => f(&Expr::Local(name.clone())),
+            Stmt::If(condition, then, otherwise) => {
+                f(condition);
+                then.iter().for_each(|stmt| stmt.visit(f));
+                otherwise.iter().for_each(|stmt| stmt.visit(f));
+            }
+            Stmt::While(_, condition, body) | Stmt::DoWhile(_, body, condition) => {
+                f(condition);
+                body.iter().for_each(|stmt| stmt.visit(f));
+            }
+            Stmt::Switch(value, cases) => {
+                f(value);
+                for (_, body) in cases {
+                    body.iter().for_each(|stmt| stmt.visit(f));
+                }
+            }
+            Stmt::Try(body, catches, finally) => {
+                body.iter().for_each(|stmt| stmt.visit(f));
+                for catch in catches {
+                    catch.body.iter().for_each(|stmt| stmt.visit(f));
+                }
+                finally.iter().for_each(|stmt| stmt.visit(f));
+            }
+            Stmt::Synchronized(lock, body) => {
+                f(lock);
+                body.iter().for_each(|stmt| stmt.visit(f));
+            }
+            Stmt::Return(None)
+            | Stmt::Break(..)
+            | Stmt::Continue(..)
+            | Stmt::Label(..)
+            | Stmt::Goto(..)
+            | Stmt::Raw(..) => {}
+        }
+    }
+
+    /// How many times the local variable `name` is used in this statement
+    pub fn uses(&self, name: &str) -> usize {
+        let mut count = 0;
+        self.visit(&mut |expr| count += expr.uses(name));
+        count
+    }
+
+    pub fn write(&self, out: &mut String, indent: usize) {
+        let pad = "    ".repeat(indent);
+        match self {
+            Stmt::Expr(expr) => {
+                let _ = writeln!(out, "{}{};", pad, expr);
+            }
+            Stmt::Declare(ty, name, None) => {
+                let _ = writeln!(out, "{}{} {};", pad, ty, name);
+            }
+            Stmt::Declare(ty, name, Some(value)) => {
+                let _ = writeln!(out, "{}{} {} = {};", pad, ty, name, value);
+            }
+            Stmt::Assign(target, value) => {
+                let _ = writeln!(out, "{}{} = {};", pad, target, value);
+            }
+            Stmt::Compound(target, Op::Add, Expr::Int(1)) => {
+                let _ = writeln!(out, "{}{}++;", pad, target);
+            }
+            Stmt::Compound(target, Op::Sub, Expr::Int(1)) => {
+                let _ = writeln!(out, "{}{}--;", pad, target);
+            }
+            Stmt::Compound(target, op, value) => {
+                let _ = writeln!(out, "{}{} {}= {};", pad, target, op.symbol(), value);
+            }
+            Stmt::Increment(name, 1) => {
+                let _ = writeln!(out, "{}{}++;", pad, name);
+            }
+            Stmt::Increment(name, -1) => {
+                let _ = writeln!(out, "{}{}--;", pad, name);
+            }
+            Stmt::Increment(name, by) if *by < 0 => {
+                let _ = writeln!(out, "{}{} -= {};", pad, name, -i64::from(*by));
+            }
+            Stmt::Increment(name, by) => {
+                let _ = writeln!(out, "{}{} += {};", pad, name, by);
+            }
+            Stmt::Return(None) => {
+                let _ = writeln!(out, "{}return;", pad);
+            }
+            Stmt::Return(Some(value)) => {
+                let _ = writeln!(out, "{}return {};", pad, value);
+            }
+            Stmt::Throw(value) => {
+                let _ = writeln!(out, "{}throw {};", pad, value);
+            }
+            Stmt::If(..) => {
+                out.push_str(&pad);
+                self.write_if(out, indent);
+            }
+            Stmt::While(label, condition, body) => {
+                if let Some(label) = label {
+                    let _ = writeln!(out, "{}{}:", pad, label);
+                }
+                let _ = writeln!(out, "{}while ({}) {{", pad, condition);
+                write_block(out, body, indent + 1);
+                let _ = writeln!(out, "{}}}", pad);
+            }
+            Stmt::DoWhile(label, body, condition) => {
+                if let Some(label) = label {
+                    let _ = writeln!(out, "{}{}:", pad, label);
+                }
+                let _ = writeln!(out, "{}do {{", pad);
+                write_block(out, body, indent + 1);
+                let _ = writeln!(out, "{}}} while ({});", pad, condition);
+            }
+            Stmt::Switch(value, cases) => {
+                let _ = writeln!(out, "{}switch ({}) {{", pad, value);
+                for (keys, body) in cases {
+                    for key in keys {
+                        match key {
+                            Some(key) => {
+                                let _ = writeln!(out, "{}    case {}:", pad, key);
+                            }
+                            None => {
+                                let _ = writeln!(out, "{}    default:", pad);
+                            }
+                        }
+                    }
+                    write_block(out, body, indent + 2);
+                }
+                let _ = writeln!(out, "{}}}", pad);
+            }
+            Stmt::Try(body, catches, finally) => {
+                let _ = writeln!(out, "{}try {{", pad);
+                write_block(out, body, indent + 1);
+                for catch in catches {
+                    let _ = writeln!(out, "{}}} catch ({} {}) {{", pad, catch.class, catch.name);
+                    write_block(out, &catch.body, indent + 1);
+                }
+                if !finally.is_empty() {
+                    let _ = writeln!(out, "{}}} finally {{", pad);
+                    write_block(out, finally, indent + 1);
+                }
+                let _ = writeln!(out, "{}}}", pad);
+            }
+            Stmt::Synchronized(lock, body) => {
+                let _ = writeln!(out, "{}synchronized ({}) {{", pad, lock);
+                write_block(out, body, indent + 1);
+                let _ = writeln!(out, "{}}}", pad);
+            }
+            Stmt::MonitorEnter(lock) => {
+                let _ = writeln!(out, "{}monitorenter({});", pad, lock);
+            }
+            Stmt::MonitorExit(lock) => {
+                let _ = writeln!(out, "{}monitorexit({});", pad, lock);
+            }
+            Stmt::Break(None) => {
+                let _ = writeln!(out, "{}break;", pad);
+            }
+            Stmt::Break(Some(label)) => {
+                let _ = writeln!(out, "{}break {};", pad, label);
+            }
+            Stmt::Continue(None) => {
+                let _ = writeln!(out, "{}continue;", pad);
+            }
+            Stmt::Continue(Some(label)) => {
+                let _ = writeln!(out, "{}continue {};", pad, label);
+            }
+            Stmt::Label(label) => {
+                let _ = writeln!(out, "{}{}:", "    ".repeat(indent.saturating_sub(1)), label);
+            }
+            Stmt::Goto(label) => {
+                let _ = writeln!(out, "{}goto {};", pad, label);
+            }
+            Stmt::Raw(text) => {
+                let _ = writeln!(out, "{}{};", pad, text);
+            }
+        }
+    }
+
+    /// Writes an `if`, after the indentation, with `else if` for nested ones
+    fn write_if(&self, out: &mut String, indent: usize) {
+        let pad = "    ".repeat(indent);
+        if let Stmt::If(condition, then, otherwise) = self {
+            let _ = writeln!(out, "if ({}) {{", condition);
+            write_block(out, then, indent + 1);
+            match otherwise.as_slice() {
+                [] => {
+                    let _ = writeln!(out, "{}}}", pad);
+                }
+                [nested @ Stmt::If(..)] => {
+                    let _ = write!(out, "{}}} else ", pad);
+                    nested.write_if(out, indent);
+                }
+                _ => {
+                    let _ = writeln!(out, "{}}} else {{", pad);
+                    write_block(out, otherwise, indent + 1);
+                    let _ = writeln!(out, "{}}}", pad);
+                }
+            }
+        }
+    }
+}
+
+/// Whether control never gets to the end of the statements
+pub fn ends_in_jump(stmts: &[Stmt]) -> bool {
+    stmts.last().is_some_and(Stmt::is_jump)
+}
+
+pub fn write_block(out: &mut String, stmts: &[Stmt], indent: usize) {
+    for stmt in stmts {
+        stmt.write(out, indent);
+    }
+}
+
+/// Quotes a string constant
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        escape(&mut quoted, c, '"');
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes a char constant
+pub fn quote_char(c: u16) -> String {
+    let mut quoted = String::from("'");
+    match std::char::from_u32(u32::from(c)) {
+        Some(c) => escape(&mut quoted, c, '\''),
+        None => {
+            let _ = write!(quoted, "\\u{:04x}", c);
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+fn escape(out: &mut String, c: char, quote: char) {
+    match c {
+        '\n' => out.push_str("\\n"),
+        '\t' => out.push_str("\\t"),
+        '\r' => out.push_str("\\r"),
+        '\\' => out.push_str("\\\\"),
+        c if c == quote => {
+            out.push('\\');
+            out.push(c);
+        }
+        c if c.is_control() => {
+            let _ = write!(out, "\\u{:04x}", c as u32);
+        }
+        c => out.push(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        let local = |name: &str| Expr::local(name);
+        let sum = Expr::binary(Op::Add, local("a"), local("b"));
+        let product = Expr::binary(Op::Mul, sum.clone(), local("c"));
+        assert_eq!(product.to_string(), "(a + b) * c");
+
+        let right = Expr::binary(Op::Sub, local("a"), sum);
+        assert_eq!(right.to_string(), "a - (a + b)");
+
+        let cast = Expr::Cast("long".into(), Box::new(product));
+        assert_eq!(cast.to_string(), "(long) ((a + b) * c)");
+
+        let negated = Expr::Unary(Op::Neg, Box::new(Expr::Int(-1)));
+        assert_eq!(negated.to_string(), "-(-1)");
+    }
+
+    #[test]
+    fn negate() {
+        let less = Expr::binary(Op::Lt, Expr::local("i"), Expr::local("n"));
+        assert_eq!(less.clone().negate().to_string(), "i >= n");
+
+        let both = Expr::binary(Op::AndAnd, less, Expr::local("ok"));
+        assert_eq!(both.clone().negate().to_string(), "i >= n || !ok");
+        assert_eq!(both.clone().negate().negate(), both);
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(quote("a\"b\n"), r#""a\"b\n""#);
+        assert_eq!(quote_char(u16::from(b'\'')), r"'\''");
+        assert_eq!(quote_char(0), r"'\u0000'");
+    }
+}
@@ -0,0 +1,1131 @@
+//! Lifting the instructions of a block into statements, by keeping the expression that
+//! computed each value on the operand stack
+use super::*;
+
+use std::collections::HashMap;
+
+/// An expression on the operand stack, and its type if it's known (which it isn't for
+/// `null`)
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Value {
+    pub expr: Expr,
+    pub ty: Option<FieldType>,
+}
+
+impl Value {
+    pub fn new(expr: Expr, ty: FieldType) -> Self {
+        Self { expr, ty: Some(ty) }
+    }
+
+    fn size(&self) -> usize {
+        self.ty.as_ref().map_or(1, FieldType::slots)
+    }
+}
+
+/// How control leaves a block
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Exit {
+    /// Continuing to the next block
+    Next,
+    Goto(usize),
+    /// Jumping to the pc when the condition holds, and continuing to the next block otherwise
+    If(Expr, usize),
+    /// The pc for each case, `None` for the default
+    Switch(Expr, Vec<(Option<i32>, usize)>),
+    Return(Option<Expr>),
+    Throw(Expr),
+    Jsr(usize),
+    /// Returning from a subroutine, to the address in the local
+    Ret(String),
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Lifted {
+    pub stmts: Vec<Stmt>,
+    /// What's left on the stack for the next block
+    pub stack: Vec<Value>,
+    pub exit: Exit,
+}
+
+/// What the `load` and `store` instructions treat a local as
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Kind {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+}
+
+impl Kind {
+    fn of(ty: &FieldType) -> Self {
+        match ty {
+            FieldType::Long => Kind::Long,
+            FieldType::Float => Kind::Float,
+            FieldType::Double => Kind::Double,
+            FieldType::Object(..) | FieldType::Array(..) => Kind::Reference,
+            _ => Kind::Int,
+        }
+    }
+
+    /// From the opcode of an `iload` or `istore` style instruction
+    fn from_opcode(opcode: u8) -> Self {
+        match opcode {
+            0x15 | 0x36 => Kind::Int,
+            0x16 | 0x37 => Kind::Long,
+            0x17 | 0x38 => Kind::Float,
+            0x18 | 0x39 => Kind::Double,
+            _ => Kind::Reference,
+        }
+    }
+
+    fn default_type(self) -> FieldType {
+        match self {
+            Kind::Int => FieldType::Int,
+            Kind::Long => FieldType::Long,
+            Kind::Float => FieldType::Float,
+            Kind::Double => FieldType::Double,
+            Kind::Reference => FieldType::Object("java/lang/Object".into()),
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            Kind::Int => "i",
+            Kind::Long => "l",
+            Kind::Float => "f",
+            Kind::Double => "d",
+            Kind::Reference => "o",
+        }
+    }
+}
+
+/// A variable from the `LocalVariableTable`
+#[derive(Debug)]
+struct Variable {
+    start: usize,
+    end: usize,
+    index: usize,
+    name: String,
+    ty: FieldType,
+    /// The generic type from the `LocalVariableTypeTable`, or the plain one
+    source: String,
+}
+
+/// The names and types of the local variables
+#[derive(Debug)]
+pub(super) struct Locals {
+    variables: Vec<Variable>,
+    /// The slot, name and type of each parameter
+    params: Vec<(usize, String, FieldType)>,
+    /// Names made up for locals without debug info
+    generated: HashMap<(usize, Kind), String>,
+    /// The type of each local by name, and how it's declared
+    pub types: HashMap<String, (FieldType, String)>,
+}
+
+impl Locals {
+    pub fn new(ctx: &Context<'_>) -> Result<Self> {
+        let mut variables = vec![];
+        let mut generics = HashMap::new();
+        for attribute in ctx.code.iter().flat_map(|code| &code.attributes) {
+            match attribute {
+                attr::Attribute::LocalVariableTable(table) => {
+                    for variable in &table.variables {
+                        let start = usize::from(variable.start_pc);
+                        let ty = FieldType::parse(ctx.file.utf8(variable.descriptor)?)?;
+                        variables.push(Variable {
+                            start,
+                            end: start + usize::from(variable.length),
+                            index: usize::from(variable.index),
+                            name: ctx.file.utf8(variable.name)?.to_string(),
+                            source: ctx.type_name(&ty),
+                            ty,
+                        });
+                    }
+                }
+                attr::Attribute::LocalVariableTypeTable(table) => {
+                    for variable in &table.variables_types {
+                        let key = (variable.start_pc, variable.index);
+                        generics.insert(key, ctx.file.utf8(variable.signature)?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        let names = |name: &str| ctx.class_name(name);
+        for variable in &mut variables {
+            let key = (variable.start as u16, variable.index as u16);
+            if let Some(source) = generics
+                .get(&key)
+                .and_then(|signature| signature::field(signature, &names))
+            {
+                variable.source = source;
+            }
+        }
+
+        let mut locals = Self {
+            variables,
+            params: vec![],
+            generated: HashMap::new(),
+            types: HashMap::new(),
+        };
+        let mut slot = 0;
+        if !ctx.is_static {
+            let this = FieldType::Object(ctx.class.to_string());
+            locals.params.push((0, "this".to_string(), this));
+            slot += 1;
+        }
+        for (i, param) in ctx.descriptor.params.iter().enumerate() {
+            let name = match locals.variable(slot, 0) {
+                Some(variable) => variable.name.clone(),
+                None => format!("arg{}", i),
+            };
+            locals.params.push((slot, name, param.clone()));
+            slot += param.slots();
+        }
+        for (_, name, ty) in locals.params.clone() {
+            locals.types.insert(name, (ty.clone(), ctx.type_name(&ty)));
+        }
+        Ok(locals)
+    }
+
+    fn variable(&self, index: usize, pc: usize) -> Option<&Variable> {
+        (self.variables.iter()).find(|variable| {
+            variable.index == index && (variable.start..variable.end).contains(&pc)
+        })
+    }
+
+    /// The type and name of each parameter, not counting `this`
+    pub fn params(&self, ctx: &Context<'_>) -> Vec<(String, String)> {
+        (self.params.iter())
+            .skip(usize::from(!ctx.is_static))
+            .map(|(_, name, _)| (self.types[name].1.clone(), name.clone()))
+            .collect()
+    }
+
+    /// The names that are declared before the code starts
+    pub fn declared(&self) -> impl Iterator<Item = &str> {
+        self.params.iter().map(|(_, name, _)| name.as_str())
+    }
+
+    /// The local at `index`, seen at `pcs`, used as a `kind`, with the type of what's
+    /// stored in it if this is a store
+    fn name(
+        &mut self,
+        ctx: &Context<'_>,
+        index: usize,
+        pcs: &[usize],
+        kind: Kind,
+        stored: Option<&FieldType>,
+    ) -> String {
+        let variable = pcs.iter().find_map(|&pc| self.variable(index, pc));
+        if let Some(variable) = variable {
+            let name = variable.name.clone();
+            let ty = (variable.ty.clone(), variable.source.clone());
+            self.types.entry(name.clone()).or_insert(ty);
+            return name;
+        }
+
+        let param =
+            (self.params.iter()).find(|(slot, _, ty)| *slot == index && Kind::of(ty) == kind);
+        if let Some((_, name, _)) = param {
+            return name.clone();
+        }
+
+        let name = (self.generated.entry((index, kind)))
+            .or_insert_with(|| format!("{}{}", kind.prefix(), index))
+            .clone();
+        let ty = match stored {
+            Some(ty) if Kind::of(ty) == kind => ty.clone(),
+            _ => kind.default_type(),
+        };
+        let source = ctx.type_name(&ty);
+        self.types.entry(name.clone()).or_insert((ty, source));
+        name
+    }
+
+    /// Records the type of a made up variable
+    pub fn add(&mut self, ctx: &Context<'_>, name: &str, ty: Option<&FieldType>) {
+        let ty = ty
+            .cloned()
+            .unwrap_or_else(|| Kind::Reference.default_type());
+        let source = ctx.type_name(&ty);
+        self.types.entry(name.to_string()).or_insert((ty, source));
+    }
+}
+
+/// The expression as a value of type `ty`, which turns int constants into `boolean` or
+/// `char` ones
+pub(super) fn coerce(expr: Expr, ty: &FieldType) -> Expr {
+    match (expr, ty) {
+        (Expr::Int(0), FieldType::Boolean) => Expr::Literal("false".into()),
+        (Expr::Int(1), FieldType::Boolean) => Expr::Literal("true".into()),
+        (Expr::Int(c), FieldType::Char) if (0..=0xFFFF).contains(&c) => {
+            Expr::Literal(expr::quote_char(c as u16))
+        }
+        (Expr::Conditional(condition, then, otherwise), ty) => Expr::Conditional(
+            condition,
+            Box::new(coerce(*then, ty)),
+            Box::new(coerce(*otherwise, ty)),
+        ),
+        (expr, _) => expr,
+    }
+}
+
+fn string() -> FieldType {
+    FieldType::Object("java/lang/String".into())
+}
+
+pub(super) struct Lifter<'a> {
+    ctx: &'a Context<'a>,
+    pub locals: Locals,
+    temps: usize,
+    stack: Vec<Value>,
+    stmts: Vec<Stmt>,
+}
+
+impl<'a> Lifter<'a> {
+    pub fn new(ctx: &'a Context<'a>, locals: Locals) -> Self {
+        Self {
+            ctx,
+            locals,
+            temps: 0,
+            stack: vec![],
+            stmts: vec![],
+        }
+    }
+
+    /// Lifts the instructions of the block, starting with `stack`
+    pub fn lift(&mut self, block: &cfg::Block, stack: Vec<Value>) -> Result<Lifted> {
+        self.stack = stack;
+        self.stmts = vec![];
+        let mut exit = Exit::Next;
+        for (pc, instruction) in &block.instructions {
+            if let Some(jump) = self.instruction(*pc, instruction)? {
+                exit = jump;
+            }
+        }
+        Ok(Lifted {
+            stmts: std::mem::take(&mut self.stmts),
+            stack: std::mem::take(&mut self.stack),
+            exit,
+        })
+    }
+
+    fn pop(&mut self) -> Result<Value> {
+        match self.stack.pop() {
+            Some(value) => Ok(value),
+            None => generic_error!("the operand stack is empty"),
+        }
+    }
+
+    /// Pops values that take up `slots` slots, the top one last
+    fn pop_slots(&mut self, slots: usize) -> Result<Vec<Value>> {
+        let mut values = vec![];
+        let mut size = 0;
+        while size < slots {
+            let value = self.pop()?;
+            size += value.size();
+            values.insert(0, value);
+        }
+        Ok(values)
+    }
+
+    fn push(&mut self, expr: Expr, ty: FieldType) {
+        self.stack.push(Value::new(expr, ty));
+    }
+
+    /// Adds a statement, after saving the values on the stack that it could change
+    fn emit(&mut self, stmt: Stmt) {
+        for i in 0..self.stack.len() {
+            if self.stack[i].expr.has_effects() {
+                self.spill(i);
+            }
+        }
+        self.stmts.push(stmt);
+    }
+
+    /// Stores the value at `index` on the stack in a new variable
+    fn spill(&mut self, index: usize) {
+        let name = format!("$t{}", self.temps);
+        self.temps += 1;
+        self.locals
+            .add(self.ctx, &name, self.stack[index].ty.as_ref());
+        let value = std::mem::replace(&mut self.stack[index].expr, Expr::local(&name));
+        self.stmts.push(Stmt::Assign(Expr::local(&name), value));
+    }
+
+    /// Saves the values on the stack that use a local that's about to change
+    fn spill_uses(&mut self, name: &str) {
+        for i in 0..self.stack.len() {
+            if self.stack[i].expr.uses(name) > 0 {
+                self.spill(i);
+            }
+        }
+    }
+
+    /// Makes the values that take up the top `slots` slots safe to copy
+    fn spill_top(&mut self, slots: usize) {
+        let mut size = 0;
+        for i in (0..self.stack.len()).rev() {
+            if size >= slots {
+                break;
+            }
+            size += self.stack[i].size();
+            if !self.stack[i].expr.is_simple() {
+                self.spill(i);
+            }
+        }
+    }
+
+    fn load(&mut self, pc: usize, opcode: u8, index: usize) {
+        let kind = Kind::from_opcode(opcode);
+        let name = self.locals.name(self.ctx, index, &[pc], kind, None);
+        let ty = self.locals.types[&name].0.clone();
+        self.push(Expr::local(name), ty);
+    }
+
+    fn store(&mut self, pc: usize, next: usize, opcode: u8, index: usize) -> Result<()> {
+        let value = self.pop()?;
+        let kind = Kind::from_opcode(opcode);
+        let name = (self.locals).name(self.ctx, index, &[next, pc], kind, value.ty.as_ref());
+        self.spill_uses(&name);
+        let ty = self.locals.types[&name].0.clone();
+        let value = match value.expr {
+            Expr::Caught => Expr::Caught,
+            expr => coerce(expr, &ty),
+        };
+        self.emit(Stmt::Assign(Expr::local(name), value));
+        Ok(())
+    }
+
+    fn iinc(&mut self, pc: usize, index: usize, by: i32) {
+        let name = self.locals.name(self.ctx, index, &[pc], Kind::Int, None);
+        self.spill_uses(&name);
+        self.emit(Stmt::Increment(name, by));
+    }
+
+    fn binary(&mut self, op: Op, ty: FieldType) -> Result<()> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        let boolean = Some(FieldType::Boolean);
+        let ty = match op {
+            Op::And | Op::Or | Op::Xor if left.ty == boolean && right.ty == boolean => {
+                FieldType::Boolean
+            }
+            _ => ty,
+        };
+        let (left, right) = match ty {
+            FieldType::Boolean => (
+                coerce(left.expr, &FieldType::Boolean),
+                coerce(right.expr, &FieldType::Boolean),
+            ),
+            _ => (left.expr, right.expr),
+        };
+        self.push(Expr::binary(op, left, right), ty);
+        Ok(())
+    }
+
+    fn cast(&mut self, ty: FieldType) -> Result<()> {
+        let value = self.pop()?;
+        let expr = Expr::Cast(self.ctx.type_name(&ty), Box::new(value.expr));
+        self.push(expr, ty);
+        Ok(())
+    }
+
+    fn compare(&mut self, ty: &'static str) -> Result<()> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        let expr = Expr::Compare(ty, Box::new(left.expr), Box::new(right.expr));
+        self.push(expr, FieldType::Int);
+        Ok(())
+    }
+
+    /// The condition of an `if` that compares the top of the stack to zero
+    fn if_zero(&mut self, op: Op) -> Result<Expr> {
+        let value = self.pop()?;
+        let condition = match (value.expr, &value.ty) {
+            (Expr::Compare(_, left, right), _) => Expr::Binary(op, left, right),
+            (expr, Some(FieldType::Boolean)) if op == Op::Ne => expr,
+            (expr, Some(FieldType::Boolean)) if op == Op::Eq => expr.negate(),
+            (expr, _) => Expr::binary(op, expr, Expr::Int(0)),
+        };
+        Ok(condition)
+    }
+
+    /// The condition of an `if` that compares the top two values on the stack
+    fn if_compare(&mut self, op: Op) -> Result<Expr> {
+        let right = self.pop()?;
+        let left = self.pop()?;
+        let (left, right) = match (&left.ty, &right.ty) {
+            (Some(ty), _) if *ty == FieldType::Char || *ty == FieldType::Boolean => {
+                (left.expr, coerce(right.expr, ty))
+            }
+            (_, Some(ty)) if *ty == FieldType::Char || *ty == FieldType::Boolean => {
+                (coerce(left.expr, ty), right.expr)
+            }
+            _ => (left.expr, right.expr),
+        };
+        Ok(Expr::binary(op, left, right))
+    }
+
+    fn if_null(&mut self, op: Op) -> Result<Expr> {
+        let value = self.pop()?;
+        Ok(Expr::binary(op, value.expr, Expr::Literal("null".into())))
+    }
+
+    fn array_load(&mut self, ty: Option<FieldType>) -> Result<()> {
+        let index = self.pop()?;
+        let array = self.pop()?;
+        let ty = match (ty, &array.ty) {
+            (Some(ty), _) => ty,
+            (None, Some(FieldType::Array(element))) => (**element).clone(),
+            (None, _) => Kind::Reference.default_type(),
+        };
+        let expr = Expr::Index(Box::new(array.expr), Box::new(index.expr));
+        self.push(expr, ty);
+        Ok(())
+    }
+
+    fn array_store(&mut self) -> Result<()> {
+        let value = self.pop()?;
+        let index = self.pop()?;
+        let array = self.pop()?;
+        let value = match &array.ty {
+            Some(FieldType::Array(element)) => coerce(value.expr, element),
+            _ => value.expr,
+        };
+        let target = Expr::Index(Box::new(array.expr), Box::new(index.expr));
+        self.emit(Stmt::Assign(target, value));
+        Ok(())
+    }
+
+    /// The `dup` instructions, which copy the top `slots` slots, and insert them under the
+    /// `under` slots below them
+    fn dup(&mut self, slots: usize, under: usize) -> Result<()> {
+        self.spill_top(slots);
+        let top = self.pop_slots(slots)?;
+        let below = self.pop_slots(under)?;
+        self.stack.extend(top.iter().cloned());
+        self.stack.extend(below);
+        self.stack.extend(top);
+        Ok(())
+    }
+
+    fn pop_op(&mut self, slots: usize) -> Result<()> {
+        for value in self.pop_slots(slots)? {
+            match value.expr {
+                Expr::Caught => {}
+                expr if expr.has_effects() => self.emit(Stmt::Expr(expr)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn field(&mut self, index: u16, is_static: bool, is_get: bool) -> Result<()> {
+        let field = self.ctx.file.member_ref(ConstantIndex(index))?;
+        let ty = FieldType::parse(field.descriptor)?;
+        let value = match is_get {
+            true => None,
+            false => Some(self.pop()?),
+        };
+        let object = match is_static {
+            true => Expr::Name(self.ctx.class_name(field.class)),
+            false => self.pop()?.expr,
+        };
+        let target = Expr::Field(Box::new(object), field.name.to_string());
+        match value {
+            None => self.push(target, ty),
+            Some(value) => self.emit(Stmt::Assign(target, coerce(value.expr, &ty))),
+        }
+        Ok(())
+    }
+
+    fn invoke(&mut self, index: u16, opcode: u8) -> Result<()> {
+        let ctx = self.ctx;
+        let (class, name, descriptor) = match opcode {
+            // invokedynamic
+            0xBA => match ConstantIndex(index).lookup(&ctx.file.constant_pool)? {
+                Constant::InvokeDynamicRef(call_site) => {
+                    let (name, descriptor) = ctx.file.name_and_type(call_site.name_and_type)?;
+                    (None, name, descriptor)
+                }
+                constant => generic_error!("invokedynamic of {:?}", constant),
+            },
+            _ => {
+                let method = ctx.file.member_ref(ConstantIndex(index))?;
+                (Some(method.class), method.name, method.descriptor)
+            }
+        };
+        let descriptor = MethodDescriptor::parse(descriptor)?;
+
+        let mut args = vec![];
+        for param in descriptor.params.iter().rev() {
+            let value = self.pop()?;
+            args.push((coerce(value.expr, param), param == &string()));
+        }
+        args.reverse();
+        let (args, strings): (Vec<_>, Vec<_>) = args.into_iter().unzip();
+
+        let class = match class {
+            Some(class) => class,
+            None => {
+                let name = format!("/* invokedynamic */ {}", name);
+                return self.result(Expr::Call(None, name, args), descriptor.ret);
+            }
+        };
+
+        let expr = match opcode {
+            // invokestatic
+            0xB8 if class == ctx.class => Expr::Call(None, name.to_string(), args),
+            0xB8 => {
+                let class = Expr::Name(ctx.class_name(class));
+                Expr::Call(Some(Box::new(class)), name.to_string(), args)
+            }
+            // invokespecial
+            0xB7 if name == "<init>" => return self.construct(class, args, strings),
+            0xB7 => {
+                let object = match self.pop()?.expr {
+                    Expr::Local(this) if this == "this" && class != ctx.class => {
+                        Expr::Name("super".into())
+                    }
+                    object => object,
+                };
+                Expr::Call(Some(Box::new(object)), name.to_string(), args)
+            }
+            _ => {
+                let object = self.pop()?;
+                match object.expr {
+                    Expr::Concat(init, mut parts) if class == "java/lang/StringBuilder" => {
+                        match (name, args.len()) {
+                            ("append", 1) => {
+                                parts.extend(args.into_iter().zip(strings));
+                                let expr = Expr::Concat(init, parts);
+                                self.push(expr, FieldType::Object(class.into()));
+                                return Ok(());
+                            }
+                            ("toString", 0) => {
+                                self.push(concat(init, parts), string());
+                                return Ok(());
+                            }
+                            _ => {
+                                let object = Box::new(Expr::Concat(init, parts));
+                                Expr::Call(Some(object), name.to_string(), args)
+                            }
+                        }
+                    }
+                    object => Expr::Call(Some(Box::new(object)), name.to_string(), args),
+                }
+            }
+        };
+        self.result(expr, descriptor.ret)
+    }
+
+    /// Pushes the result of a call, or adds it as a statement if there isn't one
+    fn result(&mut self, expr: Expr, ret: Option<FieldType>) -> Result<()> {
+        match ret {
+            Some(ty) => self.push(expr, ty),
+            None => self.emit(Stmt::Expr(expr)),
+        }
+        Ok(())
+    }
+
+    /// Calls a constructor, either on an object from `new`, or on `this` from a constructor
+    fn construct(&mut self, class: &str, args: Vec<Expr>, strings: Vec<bool>) -> Result<()> {
+        let ctx = self.ctx;
+        let object = self.pop()?;
+        let created = match object.expr {
+            Expr::Uninit(pc, _) => pc,
+            Expr::Local(this) if this == "this" && ctx.is_constructor() => {
+                let name = if class == ctx.class { "this" } else { "super" };
+                self.emit(Stmt::Expr(Expr::Call(None, name.into(), args)));
+                return Ok(());
+            }
+            object => {
+                let expr = Expr::Call(Some(Box::new(object)), "<init>".into(), args);
+                self.emit(Stmt::Expr(expr));
+                return Ok(());
+            }
+        };
+
+        let expr = match (class, args.len()) {
+            ("java/lang/StringBuilder", 0) => Expr::Concat(None, vec![]),
+            ("java/lang/StringBuilder", 1) if strings[0] => {
+                Expr::Concat(None, args.into_iter().zip(strings).collect())
+            }
+            _ => Expr::New(ctx.class_name(class), args),
+        };
+        let mut replaced = false;
+        for value in &mut self.stack {
+            if matches!(value.expr, Expr::Uninit(pc, _) if pc == created) {
+                value.expr = expr.clone();
+                replaced = true;
+            }
+        }
+        if !replaced {
+            self.emit(Stmt::Expr(expr));
+        }
+        Ok(())
+    }
+
+    fn new_array(&mut self, element: FieldType) -> Result<()> {
+        let length = self.pop()?;
+        let (name, dimensions) = base_type(self.ctx, &element);
+        let expr = Expr::NewArray(name, vec![length.expr], dimensions);
+        self.push(expr, FieldType::Array(Box::new(element)));
+        Ok(())
+    }
+
+    /// Lifts one instruction, returning how control leaves the block if it's a jump
+    fn instruction(&mut self, pc: usize, instruction: &Instruction) -> Result<Option<Exit>> {
+        use FieldType::{Double as D, Float as F, Int as I, Long as J};
+        let next = pc + instruction.size();
+        let at = |offset: i32| (pc as i64 + i64::from(offset)) as usize;
+        let ctx = self.ctx;
+
+        match instruction {
+            Instruction::NOP(..) => {}
+            Instruction::ACONST_NULL(..) => {
+                self.stack.push(Value {
+                    expr: Expr::Literal("null".into()),
+                    ty: None,
+                });
+            }
+            Instruction::ICONST_M1(..) => self.push(Expr::Int(-1), I),
+            Instruction::ICONST_0(..) => self.push(Expr::Int(0), I),
+            Instruction::ICONST_1(..) => self.push(Expr::Int(1), I),
+            Instruction::ICONST_2(..) => self.push(Expr::Int(2), I),
+            Instruction::ICONST_3(..) => self.push(Expr::Int(3), I),
+            Instruction::ICONST_4(..) => self.push(Expr::Int(4), I),
+            Instruction::ICONST_5(..) => self.push(Expr::Int(5), I),
+            Instruction::LCONST_0(..) => self.push(Expr::Literal("0L".into()), J),
+            Instruction::LCONST_1(..) => self.push(Expr::Literal("1L".into()), J),
+            Instruction::FCONST_0(..) => self.push(Expr::Literal("0.0f".into()), F),
+            Instruction::FCONST_1(..) => self.push(Expr::Literal("1.0f".into()), F),
+            Instruction::FCONST_2(..) => self.push(Expr::Literal("2.0f".into()), F),
+            Instruction::DCONST_0(..) => self.push(Expr::Literal("0.0".into()), D),
+            Instruction::DCONST_1(..) => self.push(Expr::Literal("1.0".into()), D),
+            Instruction::BIPUSH(BIPUSH(byte)) => self.push(Expr::Int(i32::from(*byte as i8)), I),
+            Instruction::SIPUSH(SIPUSH(a, b)) => {
+                self.push(Expr::Int(i32::from(wide_index(*a, *b) as i16)), I)
+            }
+            Instruction::LDC(LDC(index)) => {
+                let value = constant(ctx.file, ctx.package, ConstantIndex(u16::from(*index)))?;
+                self.stack.push(value);
+            }
+            Instruction::LDC_W(LDC_W(a, b)) | Instruction::LDC2_W(LDC2_W(a, b)) => {
+                let value = constant(ctx.file, ctx.package, ConstantIndex(wide_index(*a, *b)))?;
+                self.stack.push(value);
+            }
+            //
+            Instruction::ILOAD(ILOAD(index)) => self.load(pc, 0x15, usize::from(*index)),
+            Instruction::LLOAD(LLOAD(index)) => self.load(pc, 0x16, usize::from(*index)),
+            Instruction::FLOAD(FLOAD(index)) => self.load(pc, 0x17, usize::from(*index)),
+            Instruction::DLOAD(DLOAD(index)) => self.load(pc, 0x18, usize::from(*index)),
+            Instruction::ALOAD(ALOAD(index)) => self.load(pc, 0x19, usize::from(*index)),
+            Instruction::ILOAD_0(..) => self.load(pc, 0x15, 0),
+            Instruction::ILOAD_1(..) => self.load(pc, 0x15, 1),
+            Instruction::ILOAD_2(..) => self.load(pc, 0x15, 2),
+            Instruction::ILOAD_3(..) => self.load(pc, 0x15, 3),
+            Instruction::LLOAD_0(..) => self.load(pc, 0x16, 0),
+            Instruction::LLOAD_1(..) => self.load(pc, 0x16, 1),
+            Instruction::LLOAD_2(..) => self.load(pc, 0x16, 2),
+            Instruction::LLOAD_3(..) => self.load(pc, 0x16, 3),
+            Instruction::FLOAD_0(..) => self.load(pc, 0x17, 0),
+            Instruction::FLOAD_1(..) => self.load(pc, 0x17, 1),
+            Instruction::FLOAD_2(..) => self.load(pc, 0x17, 2),
+            Instruction::FLOAD_3(..) => self.load(pc, 0x17, 3),
+            Instruction::DLOAD_0(..) => self.load(pc, 0x18, 0),
+            Instruction::DLOAD_1(..) => self.load(pc, 0x18, 1),
+            Instruction::DLOAD_2(..) => self.load(pc, 0x18, 2),
+            Instruction::DLOAD_3(..) => self.load(pc, 0x18, 3),
+            Instruction::ALOAD_0(..) => self.load(pc, 0x19, 0),
+            Instruction::ALOAD_1(..) => self.load(pc, 0x19, 1),
+            Instruction::ALOAD_2(..) => self.load(pc, 0x19, 2),
+            Instruction::ALOAD_3(..) => self.load(pc, 0x19, 3),
+            //
+            Instruction::IALOAD(..) => self.array_load(Some(I))?,
+            Instruction::LALOAD(..) => self.array_load(Some(J))?,
+            Instruction::FALOAD(..) => self.array_load(Some(F))?,
+            Instruction::DALOAD(..) => self.array_load(Some(D))?,
+            Instruction::BALOAD(..) | Instruction::CALOAD(..) | Instruction::SALOAD(..) => {
+                self.array_load(None)?
+            }
+            Instruction::AALOAD(..) => self.array_load(None)?,
+            //
+            Instruction::ISTORE(ISTORE(index)) => {
+                self.store(pc, next, 0x36, usize::from(*index))?
+            }
+            Instruction::LSTORE(LSTORE(index)) => {
+                self.store(pc, next, 0x37, usize::from(*index))?
+            }
+            Instruction::FSTORE(FSTORE(index)) => {
+                self.store(pc, next, 0x38, usize::from(*index))?
+            }
+            Instruction::DSTORE(DSTORE(index)) => {
+                self.store(pc, next, 0x39, usize::from(*index))?
+            }
+            Instruction::ASTORE(ASTORE(index)) => {
+                self.store(pc, next, 0x3A, usize::from(*index))?
+            }
+            Instruction::ISTORE_0(..) => self.store(pc, next, 0x36, 0)?,
+            Instruction::ISTORE_1(..) => self.store(pc, next, 0x36, 1)?,
+            Instruction::ISTORE_2(..) => self.store(pc, next, 0x36, 2)?,
+            Instruction::ISTORE_3(..) => self.store(pc, next, 0x36, 3)?,
+            Instruction::LSTORE_0(..) => self.store(pc, next, 0x37, 0)?,
+            Instruction::LSTORE_1(..) => self.store(pc, next, 0x37, 1)?,
+            Instruction::LSTORE_2(..) => self.store(pc, next, 0x37, 2)?,
+            Instruction::LSTORE_3(..) => self.store(pc, next, 0x37, 3)?,
+            Instruction::FSTORE_0(..) => self.store(pc, next, 0x38, 0)?,
+            Instruction::FSTORE_1(..) => self.store(pc, next, 0x38, 1)?,
+            Instruction::FSTORE_2(..) => self.store(pc, next, 0x38, 2)?,
+            Instruction::FSTORE_3(..) => self.store(pc, next, 0x38, 3)?,
+            Instruction::DSTORE_0(..) => self.store(pc, next, 0x39, 0)?,
+            Instruction::DSTORE_1(..) => self.store(pc, next, 0x39, 1)?,
+            Instruction::DSTORE_2(..) => self.store(pc, next, 0x39, 2)?,
+            Instruction::DSTORE_3(..) => self.store(pc, next, 0x39, 3)?,
+            Instruction::ASTORE_0(..) => self.store(pc, next, 0x3A, 0)?,
+            Instruction::ASTORE_1(..) => self.store(pc, next, 0x3A, 1)?,
+            Instruction::ASTORE_2(..) => self.store(pc, next, 0x3A, 2)?,
+            Instruction::ASTORE_3(..) => self.store(pc, next, 0x3A, 3)?,
+            //
+            Instruction::IASTORE(..)
+            | Instruction::LASTORE(..)
+            | Instruction::FASTORE(..)
+            | Instruction::DASTORE(..)
+            | Instruction::AASTORE(..)
+            | Instruction::BASTORE(..)
+            | Instruction::CASTORE(..)
+            | Instruction::SASTORE(..) => self.array_store()?,
+            //
+            Instruction::POP(..) => self.pop_op(1)?,
+            Instruction::POP2(..) => self.pop_op(2)?,
+            Instruction::DUP(..) => self.dup(1, 0)?,
+            Instruction::DUP_X1(..) => self.dup(1, 1)?,
+            Instruction::DUP_X2(..) => self.dup(1, 2)?,
+            Instruction::DUP2(..) => self.dup(2, 0)?,
+            Instruction::DUP2_X1(..) => self.dup(2, 1)?,
+            Instruction::DUP2_X2(..) => self.dup(2, 2)?,
+            Instruction::SWAP(..) => {
+                self.spill_top(2);
+                let top = self.pop()?;
+                let below = self.pop()?;
+                self.stack.push(top);
+                self.stack.push(below);
+            }
+            //
+            Instruction::IADD(..) => self.binary(Op::Add, I)?,
+            Instruction::LADD(..) => self.binary(Op::Add, J)?,
+            Instruction::FADD(..) => self.binary(Op::Add, F)?,
+            Instruction::DADD(..) => self.binary(Op::Add, D)?,
+            Instruction::ISUB(..) => self.binary(Op::Sub, I)?,
+            Instruction::LSUB(..) => self.binary(Op::Sub, J)?,
+            Instruction::FSUB(..) => self.binary(Op::Sub, F)?,
+            Instruction::DSUB(..) => self.binary(Op::Sub, D)?,
+            Instruction::IMUL(..) => self.binary(Op::Mul, I)?,
+            Instruction::LMUL(..) => self.binary(Op::Mul, J)?,
+            Instruction::FMUL(..) => self.binary(Op::Mul, F)?,
+            Instruction::DMUL(..) => self.binary(Op::Mul, D)?,
+            Instruction::IDIV(..) => self.binary(Op::Div, I)?,
+            Instruction::LDIV(..) => self.binary(Op::Div, J)?,
+            Instruction::FDIV(..) => self.binary(Op::Div, F)?,
+            Instruction::DDIV(..) => self.binary(Op::Div, D)?,
+            Instruction::IREM(..) => self.binary(Op::Rem, I)?,
+            Instruction::LREM(..) => self.binary(Op::Rem, J)?,
+            Instruction::FREM(..) => self.binary(Op::Rem, F)?,
+            Instruction::DREM(..) => self.binary(Op::Rem, D)?,
+            Instruction::ISHL(..) => self.binary(Op::Shl, I)?,
+            Instruction::LSHL(..) => self.binary(Op::Shl, J)?,
+            Instruction::ISHR(..) => self.binary(Op::Shr, I)?,
+            Instruction::LSHR(..) => self.binary(Op::Shr, J)?,
+            Instruction::IUSHR(..) => self.binary(Op::Ushr, I)?,
+            Instruction::LUSHR(..) => self.binary(Op::Ushr, J)?,
+            Instruction::IAND(..) => self.binary(Op::And, I)?,
+            Instruction::LAND(..) => self.binary(Op::And, J)?,
+            Instruction::IOR(..) => self.binary(Op::Or, I)?,
+            Instruction::LOR(..) => self.binary(Op::Or, J)?,
+            Instruction::IXOR(..) => self.binary(Op::Xor, I)?,
+            Instruction::LXOR(..) => self.binary(Op::Xor, J)?,
+            Instruction::INEG(..)
+            | Instruction::LNEG(..)
+            | Instruction::FNEG(..)
+            | Instruction::DNEG(..) => {
+                let value = self.pop()?;
+                let ty = value.ty.unwrap_or(I);
+                self.push(Expr::Unary(Op::Neg, Box::new(value.expr)), ty);
+            }
+            Instruction::IINC(IINC(index, by)) => {
+                self.iinc(pc, usize::from(*index), i32::from(*by as i8))
+            }
+            //
+            Instruction::I2L(..) | Instruction::F2L(..) | Instruction::D2L(..) => self.cast(J)?,
+            Instruction::I2F(..) | Instruction::L2F(..) | Instruction::D2F(..) => self.cast(F)?,
+            Instruction::I2D(..) | Instruction::L2D(..) | Instruction::F2D(..) => self.cast(D)?,
+            Instruction::L2I(..) | Instruction::F2I(..) | Instruction::D2I(..) => self.cast(I)?,
+            Instruction::I2B(..) => self.cast(FieldType::Byte)?,
+            Instruction::I2C(..) => self.cast(FieldType::Char)?,
+            Instruction::I2S(..) => self.cast(FieldType::Short)?,
+            //
+            Instruction::LCMP(..) => self.compare("Long")?,
+            Instruction::FCMPL(..) | Instruction::FCMPG(..) => self.compare("Float")?,
+            Instruction::DCMPL(..) | Instruction::DCMPG(..) => self.compare("Double")?,
+            //
+            Instruction::IFEQ(IFEQ(a, b)) => {
+                let condition = self.if_zero(Op::Eq)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IFNE(IFNE(a, b)) => {
+                let condition = self.if_zero(Op::Ne)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IFLT(IFLT(a, b)) => {
+                let condition = self.if_zero(Op::Lt)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IFGE(IFGE(a, b)) => {
+                let condition = self.if_zero(Op::Ge)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IFGT(IFGT(a, b)) => {
+                let condition = self.if_zero(Op::Gt)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IFLE(IFLE(a, b)) => {
+                let condition = self.if_zero(Op::Le)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IF_ICMPEQ(IF_ICMPEQ(a, b)) | Instruction::IF_ACMPEQ(IF_ACMPEQ(a, b)) => {
+                let condition = self.if_compare(Op::Eq)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IF_ICMPNE(IF_ICMPNE(a, b)) | Instruction::IF_ACMPNE(IF_ACMPNE(a, b)) => {
+                let condition = self.if_compare(Op::Ne)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IF_ICMPLT(IF_ICMPLT(a, b)) => {
+                let condition = self.if_compare(Op::Lt)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IF_ICMPGE(IF_ICMPGE(a, b)) => {
+                let condition = self.if_compare(Op::Ge)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IF_ICMPGT(IF_ICMPGT(a, b)) => {
+                let condition = self.if_compare(Op::Gt)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IF_ICMPLE(IF_ICMPLE(a, b)) => {
+                let condition = self.if_compare(Op::Le)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IFNULL(IFNULL(a, b)) => {
+                let condition = self.if_null(Op::Eq)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::IFNONNULL(IFNONNULL(a, b)) => {
+                let condition = self.if_null(Op::Ne)?;
+                return Ok(Some(Exit::If(condition, at(branch_offset(*a, *b)))));
+            }
+            Instruction::GOTO(GOTO(a, b)) => {
+                return Ok(Some(Exit::Goto(at(branch_offset(*a, *b)))));
+            }
+            Instruction::GOTO_W(GOTO_W(a, b, c, d)) => {
+                return Ok(Some(Exit::Goto(at(branch_offset_wide(*a, *b, *c, *d)))));
+            }
+            Instruction::JSR(JSR(a, b)) => {
+                return Ok(Some(Exit::Jsr(at(branch_offset(*a, *b)))));
+            }
+            Instruction::JSR_W(JSR_W(a, b, c, d)) => {
+                return Ok(Some(Exit::Jsr(at(branch_offset_wide(*a, *b, *c, *d)))));
+            }
+            Instruction::RET(RET(index)) => {
+                let kind = Kind::Reference;
+                let name = self
+                    .locals
+                    .name(ctx, usize::from(*index), &[pc], kind, None);
+                return Ok(Some(Exit::Ret(name)));
+            }
+            Instruction::TABLESWITCH(switch) => {
+                let value = self.pop()?;
+                let (default, low, offsets) = switch.table();
+                let cases = (offsets.into_iter().enumerate())
+                    .map(|(i, offset)| (Some(low + i as i32), at(offset)))
+                    .chain(std::iter::once((None, at(default))))
+                    .collect();
+                return Ok(Some(Exit::Switch(value.expr, cases)));
+            }
+            Instruction::LOOKUPSWITCH(switch) => {
+                let value = self.pop()?;
+                let (default, pairs) = switch.pairs();
+                let cases = (pairs.into_iter())
+                    .map(|(key, offset)| (Some(key), at(offset)))
+                    .chain(std::iter::once((None, at(default))))
+                    .collect();
+                return Ok(Some(Exit::Switch(value.expr, cases)));
+            }
+            //
+            Instruction::IRETURN(..)
+            | Instruction::LRETURN(..)
+            | Instruction::FRETURN(..)
+            | Instruction::DRETURN(..)
+            | Instruction::ARETURN(..) => {
+                let value = self.pop()?;
+                let value = match &ctx.descriptor.ret {
+                    Some(ty) => coerce(value.expr, ty),
+                    None => value.expr,
+                };
+                return Ok(Some(Exit::Return(Some(value))));
+            }
+            Instruction::RETURN(..) => return Ok(Some(Exit::Return(None))),
+            //
+            Instruction::GETSTATIC(GETSTATIC(a, b)) => {
+                self.field(wide_index(*a, *b), true, true)?
+            }
+            Instruction::PUTSTATIC(PUTSTATIC(a, b)) => {
+                self.field(wide_index(*a, *b), true, false)?
+            }
+            Instruction::GETFIELD(GETFIELD(a, b)) => self.field(wide_index(*a, *b), false, true)?,
+            Instruction::PUTFIELD(PUTFIELD(a, b)) => {
+                self.field(wide_index(*a, *b), false, false)?
+            }
+            Instruction::INVOKEVIRTUAL(INVOKEVIRTUAL(a, b)) => {
+                self.invoke(wide_index(*a, *b), 0xB6)?
+            }
+            Instruction::INVOKESPECIAL(INVOKESPECIAL(a, b)) => {
+                self.invoke(wide_index(*a, *b), 0xB7)?
+            }
+            Instruction::INVOKESTATIC(INVOKESTATIC(a, b)) => {
+                self.invoke(wide_index(*a, *b), 0xB8)?
+            }
+            Instruction::INVOKEINTERFACE(INVOKEINTERFACE(a, b, ..)) => {
+                self.invoke(wide_index(*a, *b), 0xB9)?
+            }
+            Instruction::INVOKEDYNAMIC(INVOKEDYNAMIC(a, b, ..)) => {
+                self.invoke(wide_index(*a, *b), 0xBA)?
+            }
+            //
+            Instruction::NEW(NEW(a, b)) => {
+                let class = ctx.file.class_name(ConstantIndex(wide_index(*a, *b)))?;
+                let expr = Expr::Uninit(pc, ctx.class_name(class));
+                self.push(expr, FieldType::Object(class.to_string()));
+            }
+            Instruction::NEWARRAY(NEWARRAY(atype)) => {
+                let element = match atype {
+                    4 => FieldType::Boolean,
+                    5 => FieldType::Char,
+                    6 => F,
+                    7 => D,
+                    8 => FieldType::Byte,
+                    9 => FieldType::Short,
+                    10 => I,
+                    11 => J,
+                    _ => generic_error!("invalid newarray type {}", atype),
+                };
+                self.new_array(element)?
+            }
+            Instruction::ANEWARRAY(ANEWARRAY(a, b)) => {
+                let class = ctx.file.class_name(ConstantIndex(wide_index(*a, *b)))?;
+                let element = match class.starts_with('[') {
+                    true => FieldType::parse(class)?,
+                    false => FieldType::Object(class.to_string()),
+                };
+                self.new_array(element)?
+            }
+            Instruction::MULTIANEWARRAY(MULTIANEWARRAY(a, b, dimensions)) => {
+                let class = ctx.file.class_name(ConstantIndex(wide_index(*a, *b)))?;
+                let ty = FieldType::parse(class)?;
+                let mut lengths = vec![];
+                for _ in 0..*dimensions {
+                    lengths.insert(0, self.pop()?.expr);
+                }
+                let (name, depth) = base_type(ctx, &ty);
+                let unsized_dimensions = depth.saturating_sub(usize::from(*dimensions));
+                self.push(Expr::NewArray(name, lengths, unsized_dimensions), ty);
+            }
+            Instruction::ARRAYLENGTH(..) => {
+                let array = self.pop()?;
+                self.push(Expr::Field(Box::new(array.expr), "length".into()), I);
+            }
+            Instruction::ATHROW(..) => {
+                let value = self.pop()?;
+                return Ok(Some(Exit::Throw(value.expr)));
+            }
+            Instruction::CHECKCAST(CHECKCAST(a, b)) => {
+                let class = ctx.file.class_name(ConstantIndex(wide_index(*a, *b)))?;
+                let ty = match class.starts_with('[') {
+                    true => FieldType::parse(class)?,
+                    false => FieldType::Object(class.to_string()),
+                };
+                self.cast(ty)?
+            }
+            Instruction::INSTANCEOF(INSTANCEOF(a, b)) => {
+                let class = ctx.file.class_name(ConstantIndex(wide_index(*a, *b)))?;
+                let value = self.pop()?;
+                let expr = Expr::InstanceOf(Box::new(value.expr), ctx.class_name(class));
+                self.push(expr, FieldType::Boolean);
+            }
+            Instruction::MONITORENTER(..) | Instruction::MONITOREXIT(..) => {
+                let value = self.pop()?;
+                // the values on the stack are read on this side of the monitor
+                for i in 0..self.stack.len() {
+                    if !self.stack[i].expr.is_simple() {
+                        self.spill(i);
+                    }
+                }
+                self.emit(match instruction {
+                    Instruction::MONITORENTER(..) => Stmt::MonitorEnter(value.expr),
+                    _ => Stmt::MonitorExit(value.expr),
+                });
+            }
+            Instruction::WIDE(WIDE(opcode, a, b, c, d)) => {
+                let index = usize::from(wide_index(*a, *b));
+                match opcode {
+                    0x15..=0x19 => self.load(pc, *opcode, index),
+                    0x36..=0x3A => self.store(pc, next, *opcode, index)?,
+                    0x84 => self.iinc(pc, index, i32::from(wide_index(*c, *d) as i16)),
+                    0xA9 => {
+                        let kind = Kind::Reference;
+                        let name = self.locals.name(ctx, index, &[pc], kind, None);
+                        return Ok(Some(Exit::Ret(name)));
+                    }
+                    _ => generic_error!("invalid wide instruction at {}", pc),
+                }
+            }
+            Instruction::BREAKPOINT(..) | Instruction::IMPDEP1(..) | Instruction::IMPDEP2(..) => {
+                generic_error!("reserved instruction at {}", pc)
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The name of the innermost element type of an array, and how many dimensions it has
+fn base_type(ctx: &Context<'_>, ty: &FieldType) -> (String, usize) {
+    match ty {
+        FieldType::Array(element) => {
+            let (name, depth) = base_type(ctx, element);
+            (name, depth + 1)
+        }
+        ty => (ctx.type_name(ty), 0),
+    }
+}
+
+/// String concatenation from the values appended to a `StringBuilder`
+fn concat(init: Option<Box<Expr>>, parts: Vec<(Expr, bool)>) -> Expr {
+    let mut parts = init.map(|init| (*init, true)).into_iter().chain(parts);
+    let empty = || Expr::Literal("\"\"".into());
+    let (first, first_string) = parts.next().unwrap_or_else(|| (empty(), true));
+    let mut rest = parts.peekable();
+    // `1 + 2 + "s"` adds before it concatenates
+    let second_string = rest.peek().is_some_and(|(_, string)| *string);
+    let mut expr = match first_string || second_string {
+        true => first,
+        false => Expr::binary(Op::Add, empty(), first),
+    };
+    for (part, _) in rest {
+        expr = Expr::binary(Op::Add, expr, part);
+    }
+    expr
+}
@@ -0,0 +1,237 @@
+//! Generic signatures, printed as Java types
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.9.1
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The generic type parameters, super class and interfaces of a class
+#[derive(Debug, PartialEq)]
+pub(super) struct ClassSignature {
+    /// Like `<T extends Comparable<T>>`, or empty
+    pub params: String,
+    pub super_class: String,
+    pub interfaces: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct MethodSignature {
+    pub params: String,
+    pub args: Vec<String>,
+    pub ret: String,
+    pub throws: Vec<String>,
+}
+
+pub(super) fn class(signature: &str, names: &dyn Fn(&str) -> String) -> Option<ClassSignature> {
+    let mut parser = Parser::new(signature, names);
+    let params = parser.type_params()?;
+    let super_class = parser.reference()?;
+    let mut interfaces = vec![];
+    while parser.chars.peek().is_some() {
+        interfaces.push(parser.reference()?);
+    }
+    Some(ClassSignature {
+        params,
+        super_class,
+        interfaces,
+    })
+}
+
+pub(super) fn method(signature: &str, names: &dyn Fn(&str) -> String) -> Option<MethodSignature> {
+    let mut parser = Parser::new(signature, names);
+    let params = parser.type_params()?;
+    parser.expect('(')?;
+    let mut args = vec![];
+    while parser.chars.peek() != Some(&')') {
+        args.push(parser.java_type()?);
+    }
+    parser.expect(')')?;
+    let ret = match parser.chars.peek() {
+        Some('V') => {
+            parser.chars.next();
+            "void".to_string()
+        }
+        _ => parser.java_type()?,
+    };
+    let mut throws = vec![];
+    while parser.chars.next_if_eq(&'^').is_some() {
+        throws.push(parser.reference()?);
+    }
+    parser.end()?;
+    Some(MethodSignature {
+        params,
+        args,
+        ret,
+        throws,
+    })
+}
+
+pub(super) fn field(signature: &str, names: &dyn Fn(&str) -> String) -> Option<String> {
+    let mut parser = Parser::new(signature, names);
+    let ty = parser.java_type()?;
+    parser.end()?;
+    Some(ty)
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    names: &'a dyn Fn(&str) -> String,
+}
+
+impl<'a> Parser<'a> {
+    fn new(signature: &'a str, names: &'a dyn Fn(&str) -> String) -> Self {
+        Self {
+            chars: signature.chars().peekable(),
+            names,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.chars.next_if_eq(&c).map(drop)
+    }
+
+    fn end(&mut self) -> Option<()> {
+        match self.chars.peek() {
+            Some(..) => None,
+            None => Some(()),
+        }
+    }
+
+    fn identifier(&mut self, terminators: &[char]) -> Option<String> {
+        let mut identifier = String::new();
+        while let Some(c) = self.chars.next_if(|c| !terminators.contains(c)) {
+            identifier.push(c);
+        }
+        Some(identifier).filter(|identifier| !identifier.is_empty())
+    }
+
+    fn type_params(&mut self) -> Option<String> {
+        if self.expect('<').is_none() {
+            return Some(String::new());
+        }
+        let mut params = vec![];
+        while self.expect('>').is_none() {
+            let name = self.identifier(&[':'])?;
+            let mut bounds = vec![];
+            // the class bound can be empty when there are interface bounds
+            self.expect(':')?;
+            if self.chars.peek() != Some(&':') {
+                bounds.push(self.reference()?);
+            }
+            while self.expect(':').is_some() {
+                bounds.push(self.reference()?);
+            }
+            bounds.retain(|bound| bound != "Object");
+            match bounds.is_empty() {
+                true => params.push(name),
+                false => params.push(format!("{} extends {}", name, bounds.join(" & "))),
+            }
+        }
+        Some(format!("<{}>", params.join(", ")))
+    }
+
+    fn java_type(&mut self) -> Option<String> {
+        let keyword = match self.chars.peek()? {
+            'B' => "byte",
+            'C' => "char",
+            'D' => "double",
+            'F' => "float",
+            'I' => "int",
+            'J' => "long",
+            'S' => "short",
+            'Z' => "boolean",
+            _ => return self.reference(),
+        };
+        self.chars.next();
+        Some(keyword.to_string())
+    }
+
+    fn reference(&mut self) -> Option<String> {
+        match self.chars.next()? {
+            'L' => self.class_type(),
+            'T' => {
+                let name = self.identifier(&[';'])?;
+                self.expect(';')?;
+                Some(name)
+            }
+            '[' => Some(format!("{}[]", self.java_type()?)),
+            _ => None,
+        }
+    }
+
+    fn class_type(&mut self) -> Option<String> {
+        let mut name = self.identifier(&['<', '.', ';'])?;
+        let mut ty = (self.names)(&name);
+        loop {
+            if self.expect('<').is_some() {
+                let mut args = vec![];
+                while self.expect('>').is_none() {
+                    args.push(self.type_arg()?);
+                }
+                ty = format!("{}<{}>", ty, args.join(", "));
+            }
+            match self.chars.next()? {
+                ';' => return Some(ty),
+                '.' => {
+                    let inner = self.identifier(&['<', '.', ';'])?;
+                    name = format!("{}${}", name, inner);
+                    ty = format!("{}.{}", ty, inner);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn type_arg(&mut self) -> Option<String> {
+        match self.chars.peek()? {
+            '*' => {
+                self.chars.next();
+                Some("?".to_string())
+            }
+            '+' => {
+                self.chars.next();
+                Some(format!("? extends {}", self.reference()?))
+            }
+            '-' => {
+                self.chars.next();
+                Some(format!("? super {}", self.reference()?))
+            }
+            _ => self.reference(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simple(name: &str) -> String {
+        name.rsplit('/').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn signatures() {
+        let class = class(
+            "<K::Ljava/lang/Comparable<TK;>;V:Ljava/lang/Object;>Ljava/util/AbstractMap<TK;TV;>;Ljava/io/Serializable;",
+            &simple,
+        )
+        .unwrap();
+        assert_eq!(class.params, "<K extends Comparable<K>, V>");
+        assert_eq!(class.super_class, "AbstractMap<K, V>");
+        assert_eq!(class.interfaces, vec!["Serializable"]);
+
+        let method = method(
+            "<T:Ljava/lang/Object;>(Ljava/util/List<+TT;>;[I)Ljava/util/Map<TT;*>;^TE;",
+            &simple,
+        )
+        .unwrap();
+        assert_eq!(method.params, "<T>");
+        assert_eq!(method.args, vec!["List<? extends T>", "int[]"]);
+        assert_eq!(method.ret, "Map<T, ?>");
+        assert_eq!(method.throws, vec!["E"]);
+
+        assert_eq!(
+            field("Lfoo/Outer<Ljava/lang/String;>.Inner;", &simple).unwrap(),
+            "Outer<String>.Inner"
+        );
+        assert_eq!(field("Ljava/util/List", &simple), None);
+    }
+}
@@ -0,0 +1,783 @@
+//! Cleaning up structured code: `finally` and `synchronized` blocks, loop conditions,
+//! ternaries, array initializers, inlined temporaries and declarations
+use super::expr::ends_in_jump;
+use super::*;
+
+use std::collections::{HashMap, HashSet};
+
+/// How many times each local is assigned, and used
+type Counts = HashMap<String, (usize, usize)>;
+
+pub(super) fn simplify(ctx: &Context<'_>, body: Vec<Stmt>, locals: &Locals) -> Vec<Stmt> {
+    let mut body = body;
+    remove_labels(&mut body);
+    // the outer ones first, since their copies come after the inner ones
+    for_each_block_first(&mut body, &mut finally_blocks);
+    for_each_block(&mut body, &mut synchronized);
+    let mut body = structured(body);
+    for_each_block(&mut body, &mut collapse_arrays);
+    return_in_try(&mut body);
+    inline(&mut body);
+    booleans(ctx, &mut body, locals);
+
+    // the implied `return;` and `super();`
+    if ctx.descriptor.ret.is_none() && body.last() == Some(&Stmt::Return(None)) {
+        body.pop();
+    }
+    if ctx.is_constructor() {
+        if let Some(Stmt::Expr(Expr::Call(None, name, args))) = body.first() {
+            if name == "super" && args.is_empty() {
+                body.remove(0);
+            }
+        }
+    }
+    declare(body, locals)
+}
+
+/// Calls `f` with every statement, before the statements in it
+fn walk(stmts: &[Stmt], f: &mut dyn FnMut(&Stmt)) {
+    for stmt in stmts {
+        f(stmt);
+        match stmt {
+            Stmt::If(_, then, otherwise) => {
+                walk(then, f);
+                walk(otherwise, f);
+            }
+            Stmt::While(_, _, body) | Stmt::DoWhile(_, body, _) => walk(body, f),
+            Stmt::Switch(_, cases) => cases.iter().for_each(|(_, body)| walk(body, f)),
+            Stmt::Try(body, catches, finally) => {
+                walk(body, f);
+                catches.iter().for_each(|catch| walk(&catch.body, f));
+                walk(finally, f);
+            }
+            Stmt::Synchronized(_, body) => walk(body, f),
+            _ => {}
+        }
+    }
+}
+
+/// The lists of statements directly in a statement
+fn blocks(stmt: &mut Stmt) -> Vec<&mut Vec<Stmt>> {
+    match stmt {
+        Stmt::If(_, then, otherwise) => vec![then, otherwise],
+        Stmt::While(_, _, body) | Stmt::DoWhile(_, body, _) | Stmt::Synchronized(_, body) => {
+            vec![body]
+        }
+        Stmt::Switch(_, cases) => cases.iter_mut().map(|(_, body)| body).collect(),
+        Stmt::Try(body, catches, finally) => std::iter::once(body)
+            .chain(catches.iter_mut().map(|catch| &mut catch.body))
+            .chain(std::iter::once(finally))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Calls `f` with every list of statements, after the ones nested in it
+fn for_each_block(stmts: &mut Vec<Stmt>, f: &mut dyn FnMut(&mut Vec<Stmt>)) {
+    for stmt in stmts.iter_mut() {
+        for block in blocks(stmt) {
+            for_each_block(block, f);
+        }
+    }
+    f(stmts);
+}
+
+/// Calls `f` with every list of statements, before the ones nested in it
+fn for_each_block_first(stmts: &mut Vec<Stmt>, f: &mut dyn FnMut(&mut Vec<Stmt>)) {
+    f(stmts);
+    for stmt in stmts.iter_mut() {
+        for block in blocks(stmt) {
+            for_each_block_first(block, f);
+        }
+    }
+}
+
+/// The expressions a statement evaluates itself, not counting the statements in it
+fn heads(stmt: &Stmt) -> Vec<&Expr> {
+    match stmt {
+        Stmt::Expr(expr)
+        | Stmt::Throw(expr)
+        | Stmt::Return(Some(expr))
+        | Stmt::MonitorEnter(expr)
+        | Stmt::MonitorExit(expr) => vec![expr],
+        Stmt::Declare(_, _, Some(value)) => vec![value],
+        Stmt::Assign(target, value) | Stmt::Compound(target, _, value) => vec![target, value],
+        Stmt::If(condition, ..) | Stmt::While(_, condition, _) | Stmt::DoWhile(_, _, condition) => {
+            vec![condition]
+        }
+        Stmt::Switch(value, _) | Stmt::Synchronized(value, _) => vec![value],
+        _ => vec![],
+    }
+}
+
+/// The expressions a value can be moved into, which are evaluated once, right away
+fn heads_mut(stmt: &mut Stmt) -> Vec<&mut Expr> {
+    match stmt {
+        Stmt::Expr(expr)
+        | Stmt::Throw(expr)
+        | Stmt::Return(Some(expr))
+        | Stmt::MonitorEnter(expr)
+        | Stmt::MonitorExit(expr) => vec![expr],
+        Stmt::Declare(_, _, Some(value)) => vec![value],
+        Stmt::Assign(target, value) | Stmt::Compound(target, _, value) => vec![target, value],
+        Stmt::If(condition, ..) | Stmt::Switch(condition, _) | Stmt::Synchronized(condition, _) => {
+            vec![condition]
+        }
+        _ => vec![],
+    }
+}
+
+fn counts(stmts: &[Stmt]) -> Counts {
+    let mut counts = Counts::new();
+    walk(stmts, &mut |stmt| {
+        let mut heads = heads(stmt);
+        match stmt {
+            Stmt::Assign(Expr::Local(name), value) => {
+                counts.entry(name.clone()).or_default().0 += 1;
+                heads = vec![value];
+            }
+            Stmt::Compound(Expr::Local(name), ..) | Stmt::Declare(_, name, _) => {
+                counts.entry(name.clone()).or_default().0 += 1;
+            }
+            Stmt::Increment(name, _) => {
+                let count = counts.entry(name.clone()).or_default();
+                count.0 += 1;
+                count.1 += 1;
+            }
+            _ => {}
+        }
+        for head in heads {
+            head.visit(&mut |expr| {
+                if let Expr::Local(name) = expr {
+                    counts.entry(name.clone()).or_default().1 += 1;
+                }
+            });
+        }
+    });
+    counts
+}
+
+fn remove_labels(body: &mut Vec<Stmt>) {
+    let mut used = HashSet::new();
+    walk(body, &mut |stmt| match stmt {
+        Stmt::Goto(label) | Stmt::Break(Some(label)) | Stmt::Continue(Some(label)) => {
+            used.insert(label.clone());
+        }
+        Stmt::Raw(text) => {
+            if let Some(label) = text.strip_prefix("jsr ") {
+                used.insert(label.to_string());
+            }
+        }
+        _ => {}
+    });
+    for_each_block(body, &mut |stmts| {
+        stmts.retain(|stmt| !matches!(stmt, Stmt::Label(label) if !used.contains(label)));
+        for stmt in stmts.iter_mut() {
+            if let Stmt::While(label, ..) | Stmt::DoWhile(label, ..) = stmt {
+                if !label.as_ref().is_some_and(|label| used.contains(label)) {
+                    *label = None;
+                }
+            }
+        }
+    });
+}
+
+/// Removes the copies of each `finally` block where the try block and the catches complete,
+/// and before the statements that leave them
+fn finally_blocks(stmts: &mut Vec<Stmt>) {
+    let mut i = 0;
+    while i < stmts.len() {
+        i += 1;
+        let (body, catches, finally) = match &mut stmts[i - 1] {
+            Stmt::Try(body, catches, finally) if !finally.is_empty() => (body, catches, finally),
+            _ => continue,
+        };
+        let blocks = std::iter::once(&mut *body).chain(catches.iter_mut().map(|c| &mut c.body));
+        for block in blocks {
+            for_each_block(block, &mut |stmts| remove_copies(stmts, finally));
+        }
+        let completes = !ends_in_jump(body);
+        let copied = remove_tail(body, finally);
+        for catch in catches.iter_mut() {
+            remove_tail(&mut catch.body, finally);
+        }
+
+        // `try { try { .. } catch (..) { .. } } finally { .. }` is one try statement
+        let nested = matches!(body.as_slice(), [Stmt::Try(_, _, inner)] if inner.is_empty());
+        if nested && catches.is_empty() {
+            if let Some(Stmt::Try(inner_body, inner_catches, _)) = body.pop() {
+                *body = inner_body;
+                *catches = inner_catches;
+            }
+        }
+
+        // the copy after the try statement, where the try block ended before it
+        let finally = finally.clone();
+        if completes && !copied && stmts[i..].starts_with(&finally) {
+            stmts.drain(i..i + finally.len());
+        }
+    }
+}
+
+/// Removes `finally` before each `return`, `break` and `continue` (and the temporaries that
+/// pass the value returned), and at the end of both sides of an `if` that go on to leave
+fn remove_copies(stmts: &mut Vec<Stmt>, finally: &[Stmt]) {
+    let n = finally.len();
+    for stmt in stmts.iter_mut() {
+        if let Stmt::If(_, then, otherwise) = stmt {
+            if then.ends_with(finally) && otherwise.ends_with(finally) {
+                then.truncate(then.len() - n);
+                otherwise.truncate(otherwise.len() - n);
+            }
+        }
+    }
+    let mut i = stmts.len();
+    while i > 0 {
+        i -= 1;
+        if !matches!(
+            stmts[i],
+            Stmt::Return(..) | Stmt::Break(..) | Stmt::Continue(..)
+        ) {
+            continue;
+        }
+        let mut end = i;
+        while end > 0 {
+            match &stmts[end - 1] {
+                Stmt::Assign(Expr::Local(name), _) if name.starts_with('$') => end -= 1,
+                _ => break,
+            }
+        }
+        i = end;
+        if end >= n && stmts[end - n..end] == *finally {
+            stmts.drain(end - n..end);
+            i -= n;
+        }
+    }
+}
+
+/// Removes `finally` from where `stmts` complete, returning whether it was there
+fn remove_tail(stmts: &mut Vec<Stmt>, finally: &[Stmt]) -> bool {
+    if stmts.ends_with(finally) {
+        stmts.truncate(stmts.len() - finally.len());
+        return true;
+    }
+    match stmts.last_mut() {
+        Some(Stmt::Try(body, catches, _)) => {
+            let mut found = remove_tail(body, finally);
+            for catch in catches {
+                found |= remove_tail(&mut catch.body, finally);
+            }
+            found
+        }
+        Some(Stmt::If(_, then, otherwise)) => {
+            remove_tail(then, finally) | remove_tail(otherwise, finally)
+        }
+        _ => false,
+    }
+}
+
+/// `l = x; monitorenter(x); try { .. } finally { monitorexit(l); }` is
+/// `synchronized (x) { .. }`
+fn synchronized(stmts: &mut Vec<Stmt>) {
+    let mut i = 0;
+    while i + 2 < stmts.len() {
+        let lock = match &stmts[i..i + 3] {
+            [Stmt::Assign(Expr::Local(name), lock), Stmt::MonitorEnter(entered), Stmt::Try(_, catches, finally)]
+                if entered == lock
+                    && catches.is_empty()
+                    && *finally == [Stmt::MonitorExit(Expr::local(name))]
+                    && stmts.iter().map(|stmt| stmt.uses(name)).sum::<usize>() == 2 =>
+            {
+                lock.clone()
+            }
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        stmts.drain(i..i + 2);
+        if let Stmt::Try(body, ..) = stmts.remove(i) {
+            stmts.insert(i, Stmt::Synchronized(lock, body));
+        }
+        i += 1;
+    }
+}
+
+/// Rewrites conditionals and loops into the shapes they likely had in the source
+fn structured(stmts: Vec<Stmt>) -> Vec<Stmt> {
+    let mut out = vec![];
+    for stmt in stmts {
+        match stmt {
+            Stmt::If(condition, then, otherwise) => {
+                if_stmt(condition, structured(then), structured(otherwise), &mut out)
+            }
+            Stmt::While(label, condition, body) => {
+                out.push(while_stmt(label, condition, structured(body)))
+            }
+            Stmt::DoWhile(label, body, condition) => {
+                out.push(Stmt::DoWhile(label, structured(body), condition))
+            }
+            Stmt::Switch(value, cases) => {
+                let mut cases = (cases.into_iter())
+                    .map(|(keys, body)| (keys, structured(body)))
+                    .collect::<Vec<_>>();
+                if let Some((_, last)) = cases.last_mut() {
+                    if last.last() == Some(&Stmt::Break(None)) {
+                        last.pop();
+                    }
+                }
+                out.push(Stmt::Switch(value, cases));
+            }
+            Stmt::Try(body, catches, finally) => {
+                let catches = (catches.into_iter())
+                    .map(|catch| Catch {
+                        body: structured(catch.body),
+                        ..catch
+                    })
+                    .collect();
+                out.push(Stmt::Try(structured(body), catches, structured(finally)));
+            }
+            Stmt::Synchronized(lock, body) => out.push(Stmt::Synchronized(lock, structured(body))),
+            Stmt::Assign(target, Expr::Binary(op, left, right))
+                if *left == target && target.is_simple() && compound(op) =>
+            {
+                out.push(Stmt::Compound(target, op, *right))
+            }
+            stmt => out.push(stmt),
+        }
+    }
+    out
+}
+
+fn compound(op: Op) -> bool {
+    matches!(
+        op,
+        Op::Add
+            | Op::Sub
+            | Op::Mul
+            | Op::Div
+            | Op::Rem
+            | Op::Shl
+            | Op::Shr
+            | Op::Ushr
+            | Op::And
+            | Op::Or
+            | Op::Xor
+    )
+}
+
+fn if_stmt(condition: Expr, then: Vec<Stmt>, otherwise: Vec<Stmt>, out: &mut Vec<Stmt>) {
+    if then.is_empty() && !otherwise.is_empty() {
+        return if_stmt(condition.negate(), otherwise, vec![], out);
+    }
+    if then.is_empty() && !condition.has_effects() {
+        return;
+    }
+
+    // `x = c ? a : b` puts what's left on the stack in the same variable on both sides
+    match (then.as_slice(), otherwise.as_slice()) {
+        (
+            [Stmt::Assign(Expr::Local(left), then)],
+            [Stmt::Assign(Expr::Local(right), otherwise)],
+        ) if left == right && left.starts_with('$') => {
+            let value = Expr::Conditional(
+                Box::new(condition),
+                Box::new(then.clone()),
+                Box::new(otherwise.clone()),
+            );
+            out.push(Stmt::Assign(Expr::local(left), value));
+            return;
+        }
+        _ => {}
+    }
+
+    if !otherwise.is_empty() && ends_in_jump(&then) {
+        out.push(Stmt::If(condition, then, vec![]));
+        out.extend(otherwise);
+        return;
+    }
+    if !otherwise.is_empty() && ends_in_jump(&otherwise) {
+        out.push(Stmt::If(condition.negate(), otherwise, vec![]));
+        out.extend(then);
+        return;
+    }
+
+    match (then.as_slice(), otherwise.is_empty()) {
+        ([Stmt::If(nested, inner, inner_otherwise)], true) if inner_otherwise.is_empty() => {
+            let condition = Expr::binary(Op::AndAnd, condition, nested.clone());
+            out.push(Stmt::If(condition, inner.clone(), vec![]));
+        }
+        _ => out.push(Stmt::If(condition, then, otherwise)),
+    }
+}
+
+/// Turns a `while (true)` loop that tests a condition first or last into a `while` or a
+/// `do`/`while`
+fn while_stmt(label: Option<String>, condition: Expr, mut body: Vec<Stmt>) -> Stmt {
+    let own = |target: &Option<String>| target.is_none() || *target == label;
+    while matches!(body.last(), Some(Stmt::Continue(target)) if own(target)) {
+        body.pop();
+    }
+    if condition != Expr::Literal("true".into()) {
+        return Stmt::While(label, condition, body);
+    }
+
+    if let Some(Stmt::If(test, then, otherwise)) = body.first() {
+        if otherwise.is_empty() && matches!(then.as_slice(), [Stmt::Break(target)] if own(target)) {
+            let condition = test.clone().negate();
+            body.remove(0);
+            return Stmt::While(label, condition, body);
+        }
+    }
+
+    let n = body.len();
+    if n == 2 && matches!(&body[1], Stmt::Break(target) if own(target)) {
+        if let Stmt::If(condition, then, otherwise) = &body[0] {
+            if otherwise.is_empty() {
+                let (condition, mut then) = (condition.clone(), then.clone());
+                while matches!(then.last(), Some(Stmt::Continue(target)) if own(target)) {
+                    then.pop();
+                }
+                return Stmt::While(label, condition, then);
+            }
+        }
+    }
+
+    if n >= 2 && matches!(&body[n - 1], Stmt::Break(target) if own(target)) {
+        if let Stmt::If(condition, then, otherwise) = &body[n - 2] {
+            let latch = otherwise.is_empty()
+                && matches!(then.as_slice(), [Stmt::Continue(target)] if own(target));
+            if latch && !continues(&body[..n - 2], &label, false) {
+                let condition = condition.clone();
+                body.truncate(n - 2);
+                return Stmt::DoWhile(label, body, condition);
+            }
+        }
+    }
+    Stmt::While(label, condition, body)
+}
+
+/// Whether the statements continue the loop labeled `label` that they're directly in
+fn continues(stmts: &[Stmt], label: &Option<String>, nested: bool) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Continue(None) => !nested,
+        Stmt::Continue(target) => target == label,
+        Stmt::If(_, then, otherwise) => {
+            continues(then, label, nested) || continues(otherwise, label, nested)
+        }
+        Stmt::While(_, _, body) | Stmt::DoWhile(_, body, _) => continues(body, label, true),
+        Stmt::Switch(_, cases) => cases.iter().any(|(_, body)| continues(body, label, nested)),
+        Stmt::Try(body, catches, finally) => {
+            continues(body, label, nested)
+                || (catches.iter()).any(|catch| continues(&catch.body, label, nested))
+                || continues(finally, label, nested)
+        }
+        Stmt::Synchronized(_, body) => continues(body, label, nested),
+        _ => false,
+    })
+}
+
+/// `$t = new T[n]; $t[0] = a; ..` is `$t = new T[]{a, ..}`
+fn collapse_arrays(stmts: &mut Vec<Stmt>) {
+    let mut i = 0;
+    while i < stmts.len() {
+        i += 1;
+        let (name, ty, length) = match &stmts[i - 1] {
+            Stmt::Assign(
+                Expr::Local(name),
+                Expr::NewArray(element, lengths, unsized_dimensions),
+            ) => match lengths.as_slice() {
+                [Expr::Int(length)] if *length > 0 => {
+                    let ty = format!("{}{}", element, "[]".repeat(1 + unsized_dimensions));
+                    (name.clone(), ty, *length as usize)
+                }
+                _ => continue,
+            },
+            _ => continue,
+        };
+        if i + length > stmts.len() {
+            continue;
+        }
+        let mut values = vec![];
+        for (k, stmt) in stmts[i..i + length].iter().enumerate() {
+            match stmt {
+                Stmt::Assign(Expr::Index(array, index), value)
+                    if **array == Expr::local(&name)
+                        && **index == Expr::Int(k as i32)
+                        && value.uses(&name) == 0 =>
+                {
+                    values.push(value.clone())
+                }
+                _ => break,
+            }
+        }
+        if values.len() == length {
+            stmts[i - 1] = Stmt::Assign(Expr::local(&name), Expr::ArrayInit(ty, values));
+            stmts.drain(i..i + length);
+        }
+    }
+}
+
+/// `try { $s = f(); } catch (..) { return ..; } return $s;` returns from inside of the try
+/// block, where the `return` was outside of the range the handlers cover
+fn return_in_try(body: &mut Vec<Stmt>) {
+    let counts = counts(body);
+    for_each_block(body, &mut |stmts| {
+        for i in (1..stmts.len()).rev() {
+            let name = match &stmts[i] {
+                Stmt::Return(Some(Expr::Local(name)))
+                    if name.starts_with('$') && counts.get(name) == Some(&(1, 1)) =>
+                {
+                    name.clone()
+                }
+                _ => continue,
+            };
+            let assigned = match &mut stmts[i - 1] {
+                Stmt::Try(body, catches, _)
+                    if catches.iter().all(|catch| ends_in_jump(&catch.body)) =>
+                {
+                    match body.last() {
+                        Some(Stmt::Assign(Expr::Local(local), _)) if *local == name => body,
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            if let Some(Stmt::Assign(_, value)) = assigned.pop() {
+                assigned.push(Stmt::Return(Some(value)));
+            }
+            stmts.remove(i);
+        }
+    });
+}
+
+/// Whether evaluating `expr` has side effects before it reads the local `name`, and
+/// whether it reads it at all
+fn effects_before(expr: &Expr, name: &str, effects: &mut bool) -> bool {
+    if matches!(expr, Expr::Local(local) if local == name) {
+        return true;
+    }
+    let mut found = false;
+    let mut children = vec![];
+    match expr {
+        Expr::Field(expr, _)
+        | Expr::Unary(_, expr)
+        | Expr::Cast(_, expr)
+        | Expr::InstanceOf(expr, _) => children.push(&**expr),
+        Expr::Index(a, b) | Expr::Binary(_, a, b) | Expr::Compare(_, a, b) => {
+            children.extend(&[&**a, &**b])
+        }
+        Expr::Call(object, _, args) => {
+            children.extend(object.as_deref());
+            children.extend(args);
+        }
+        Expr::New(_, args) | Expr::NewArray(_, args, _) | Expr::ArrayInit(_, args) => {
+            children.extend(args)
+        }
+        Expr::Conditional(a, b, c) => children.extend(&[&**a, &**b, &**c]),
+        Expr::Concat(init, parts) => {
+            children.extend(init.as_deref());
+            children.extend(parts.iter().map(|(part, _)| part));
+        }
+        _ => {}
+    }
+    for child in children {
+        if effects_before(child, name, effects) {
+            found = true;
+            break;
+        }
+    }
+    if !found && matches!(expr, Expr::Call(..) | Expr::New(..) | Expr::Concat(..)) {
+        *effects = true;
+    }
+    found
+}
+
+/// Moves the values of temporaries that are only used once into the statement after them
+fn inline(body: &mut Vec<Stmt>) {
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let counts = counts(body);
+        for_each_block(body, &mut |stmts| {
+            let mut i = 0;
+            while i + 1 < stmts.len() {
+                let (name, value) = match &stmts[i] {
+                    Stmt::Assign(Expr::Local(name), value)
+                        if name.starts_with('$') && counts.get(name) == Some(&(1, 1)) =>
+                    {
+                        (name.clone(), value.clone())
+                    }
+                    _ => {
+                        i += 1;
+                        continue;
+                    }
+                };
+
+                let mut heads = heads_mut(&mut stmts[i + 1]);
+                let uses = heads.iter().map(|head| head.uses(&name)).sum::<usize>();
+                let mut effects = false;
+                for head in &heads {
+                    if effects_before(head, &name, &mut effects) {
+                        break;
+                    }
+                }
+                if uses != 1 || (effects && value.has_effects()) {
+                    i += 1;
+                    continue;
+                }
+                for head in &mut heads {
+                    head.replace(&name, &value);
+                }
+                stmts.remove(i);
+                changed = true;
+            }
+        });
+    }
+}
+
+/// `c ? 1 : 0` for a `boolean` is `c`
+fn boolean(expr: Expr) -> Expr {
+    match expr {
+        Expr::Int(0) => Expr::Literal("false".into()),
+        Expr::Int(1) => Expr::Literal("true".into()),
+        Expr::Conditional(condition, then, otherwise) => {
+            let (then, otherwise) = (boolean(*then), boolean(*otherwise));
+            let (t, f) = (Expr::Literal("true".into()), Expr::Literal("false".into()));
+            match (then == t, otherwise == f, then == f, otherwise == t) {
+                (true, true, ..) => *condition,
+                (_, _, true, true) => condition.negate(),
+                _ => Expr::Conditional(condition, Box::new(then), Box::new(otherwise)),
+            }
+        }
+        expr => expr,
+    }
+}
+
+/// Fixes up the `boolean` values that came from a condition
+fn booleans(ctx: &Context<'_>, body: &mut Vec<Stmt>, locals: &Locals) {
+    let returns_boolean = ctx.descriptor.ret == Some(FieldType::Boolean);
+    for_each_block(body, &mut |stmts| {
+        for stmt in stmts.iter_mut() {
+            match stmt {
+                Stmt::Return(Some(value)) if returns_boolean => {
+                    *value = boolean(value.clone());
+                }
+                Stmt::Assign(Expr::Local(name), value)
+                    if locals.types.get(name).map(|(ty, _)| ty) == Some(&FieldType::Boolean) =>
+                {
+                    *value = boolean(value.clone());
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+struct Declarations<'a> {
+    locals: &'a Locals,
+    counts: Counts,
+    scopes: Vec<HashSet<String>>,
+    /// Names that are declared at the start of the method, because they're used across
+    /// blocks
+    hoisted: Vec<String>,
+    /// Every name that's been declared somewhere
+    declared: HashSet<String>,
+}
+
+/// Declares each local where it's first assigned, or at the start of the method if it's
+/// used before or outside of that block
+fn declare(mut body: Vec<Stmt>, locals: &Locals) -> Vec<Stmt> {
+    let params = locals
+        .declared()
+        .map(str::to_string)
+        .collect::<HashSet<_>>();
+    let mut declarations = Declarations {
+        locals,
+        counts: counts(&body),
+        scopes: vec![params.clone()],
+        hoisted: vec![],
+        declared: params,
+    };
+    declarations.block(&mut body, true);
+
+    // and the ones that are never assigned in a way that could declare them
+    let mut names = declarations.counts.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        if !declarations.declared.contains(&name) && !declarations.hoisted.contains(&name) {
+            declarations.hoisted.push(name);
+        }
+    }
+
+    let mut out = (declarations.hoisted.iter())
+        .map(|name| Stmt::Declare(declarations.type_of(name), name.clone(), None))
+        .collect::<Vec<_>>();
+    out.extend(body);
+    out
+}
+
+impl Declarations<'_> {
+    fn type_of(&self, name: &str) -> String {
+        (self.locals.types.get(name)).map_or_else(|| "Object".to_string(), |(_, ty)| ty.clone())
+    }
+
+    fn is_declared(&self, name: &str) -> bool {
+        self.hoisted.iter().any(|hoisted| hoisted == name)
+            || self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    /// Declares the locals in a block, which is its own scope if `scoped`
+    fn block(&mut self, stmts: &mut [Stmt], scoped: bool) {
+        if scoped {
+            self.scopes.push(HashSet::new());
+        }
+        for i in 0..stmts.len() {
+            if let Stmt::Assign(Expr::Local(name), value) = &stmts[i] {
+                if !self.is_declared(name) {
+                    let name = name.clone();
+                    let here = counts(&stmts[i..]).get(&name).copied();
+                    if here == self.counts.get(&name).copied() {
+                        let ty = self.type_of(&name);
+                        stmts[i] = Stmt::Declare(ty, name.clone(), Some(value.clone()));
+                        self.declared.insert(name.clone());
+                        self.scopes.last_mut().unwrap().insert(name);
+                    } else {
+                        self.hoisted.push(name);
+                    }
+                }
+            }
+            match &mut stmts[i] {
+                Stmt::If(_, then, otherwise) => {
+                    self.block(then, true);
+                    self.block(otherwise, true);
+                }
+                Stmt::While(_, _, body) | Stmt::DoWhile(_, body, _) => self.block(body, true),
+                // the cases of a switch share a scope
+                Stmt::Switch(_, cases) => {
+                    self.scopes.push(HashSet::new());
+                    for (_, body) in cases {
+                        self.block(body, false);
+                    }
+                    self.scopes.pop();
+                }
+                Stmt::Try(body, catches, finally) => {
+                    self.block(body, true);
+                    for catch in catches {
+                        self.declared.insert(catch.name.clone());
+                        self.scopes
+                            .push(std::iter::once(catch.name.clone()).collect());
+                        self.block(&mut catch.body, false);
+                        self.scopes.pop();
+                    }
+                    self.block(finally, true);
+                }
+                Stmt::Synchronized(_, body) => self.block(body, true),
+                _ => {}
+            }
+        }
+        if scoped {
+            self.scopes.pop();
+        }
+    }
+}
@@ -0,0 +1,657 @@
+//! Structuring the lifted blocks into conditionals, loops, switches and try/catch/finally
+use super::lift::{Exit, Lifted};
+use super::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How control leaves a block, by the index of the block it goes to
+#[derive(Debug, Clone)]
+enum Flow {
+    Jump(usize),
+    /// The block when the condition holds, and the one when it doesn't
+    If(Expr, usize, usize),
+    Switch(Expr, Vec<(Option<i32>, usize)>),
+    Return(Option<Expr>),
+    Throw(Expr),
+    /// The subroutine, and the block it returns to
+    Jsr(usize, usize),
+    Ret(String),
+}
+
+impl Flow {
+    fn targets(&self) -> Vec<usize> {
+        match self {
+            Flow::Jump(to) => vec![*to],
+            Flow::If(_, then, otherwise) => vec![*then, *otherwise],
+            Flow::Switch(_, cases) => cases.iter().map(|&(_, to)| to).collect(),
+            Flow::Jsr(to, after) => vec![*to, *after],
+            Flow::Return(..) | Flow::Throw(..) | Flow::Ret(..) => vec![],
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    stmts: Vec<Stmt>,
+    flow: Flow,
+}
+
+/// A statement that can be left with `break` (or `continue`)
+enum Scope {
+    Loop {
+        header: usize,
+        exit: Option<usize>,
+        blocks: BTreeSet<usize>,
+        label: String,
+    },
+    Switch {
+        merge: Option<usize>,
+        label: String,
+    },
+}
+
+/// The rows of the exception table that cover the same range, or that go to the same
+/// handler of any exception, which covers the whole `try` statement of a `finally` block
+struct Region {
+    start: usize,
+    end: usize,
+    /// The handler block, and the class it catches
+    handlers: Vec<(usize, Option<String>)>,
+}
+
+struct Structurer<'a> {
+    ctx: &'a Context<'a>,
+    cfg: &'a Cfg,
+    /// `None` for blocks that are unreachable, or merged into the condition of another
+    nodes: Vec<Option<Node>>,
+    post_dominators: Vec<Option<usize>>,
+    loops: BTreeMap<usize, cfg::Loop>,
+    regions: Vec<Region>,
+    opened: Vec<bool>,
+    emitted: Vec<bool>,
+    scopes: Vec<Scope>,
+    /// The blocks after the enclosing try statements, with how many scopes were open there
+    try_exits: Vec<(usize, usize)>,
+}
+
+/// Lifts and structures the blocks of the graph
+pub(super) fn structure(
+    ctx: &Context<'_>,
+    cfg: &Cfg,
+    lifter: &mut Lifter<'_>,
+) -> Result<Vec<Stmt>> {
+    let code = match ctx.code {
+        Some(code) => code,
+        None => generic_error!("{} has no code", ctx.method.name()),
+    };
+
+    let mut regions: Vec<Region> = vec![];
+    for row in &code.exception_table {
+        let (start, end) = (usize::from(row.start_pc), usize::from(row.end_pc));
+        let class = match row.catch_type.0 {
+            0 => None,
+            _ => Some(ctx.file.class_name(row.catch_type)?.to_string()),
+        };
+        let handler = (cfg.block_at(usize::from(row.handler_pc))?, class);
+        let any = handler.1.is_none();
+        match regions.iter_mut().find(|region| match any {
+            true => region.handlers == [handler.clone()],
+            false => region.start == start && region.end == end && region.handlers[0].1.is_some(),
+        }) {
+            Some(region) if any => {
+                region.start = region.start.min(start);
+                region.end = region.end.max(end);
+            }
+            Some(region) => region.handlers.push(handler),
+            None => regions.push(Region {
+                start,
+                end,
+                handlers: vec![handler],
+            }),
+        }
+    }
+
+    let nodes = lift(ctx, cfg, lifter, &regions)?;
+    let mut structurer = Structurer {
+        ctx,
+        cfg,
+        nodes,
+        post_dominators: cfg.post_dominators(),
+        loops: (cfg.loops().into_iter())
+            .map(|found| (found.header, found))
+            .collect(),
+        opened: vec![false; regions.len()],
+        regions,
+        emitted: vec![false; cfg.blocks.len()],
+        scopes: vec![],
+        try_exits: vec![],
+    };
+    structurer.merge_conditions();
+
+    let (mut body, _) = structurer.sequence(Some(0), &[], false);
+    // whatever is left is only reached by a goto, or a jsr
+    for block in 0..cfg.blocks.len() {
+        if structurer.nodes[block].is_some() && !structurer.emitted[block] {
+            let (rest, _) = structurer.sequence(Some(block), &[], false);
+            body.extend(rest);
+        }
+    }
+    Ok(body)
+}
+
+/// Lifts each reachable block, passing values left on the stack to the next blocks in
+/// variables
+fn lift(
+    ctx: &Context<'_>,
+    cfg: &Cfg,
+    lifter: &mut Lifter<'_>,
+    regions: &[Region],
+) -> Result<Vec<Option<Node>>> {
+    let mut entries = vec![None; cfg.blocks.len()];
+    entries[0] = Some(vec![]);
+    for region in regions {
+        for (handler, class) in &region.handlers {
+            let class = class.as_deref().unwrap_or("java/lang/Throwable");
+            let caught = Value::new(Expr::Caught, FieldType::Object(class.to_string()));
+            entries[*handler] = Some(vec![caught]);
+        }
+    }
+
+    let mut nodes = (0..cfg.blocks.len()).map(|_| None).collect::<Vec<_>>();
+    for block in cfg.reverse_postorder() {
+        let stack = entries[block].clone().unwrap_or_default();
+        let Lifted {
+            mut stmts,
+            stack,
+            exit,
+        } = lifter.lift(&cfg.blocks[block], stack)?;
+        let next = block + 1;
+        let flow = match exit {
+            Exit::Next => Flow::Jump(next),
+            Exit::Goto(pc) => Flow::Jump(cfg.block_at(pc)?),
+            Exit::If(condition, pc) => Flow::If(condition, cfg.block_at(pc)?, next),
+            Exit::Switch(value, cases) => Flow::Switch(
+                value,
+                (cases.into_iter())
+                    .map(|(key, pc)| Ok((key, cfg.block_at(pc)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            Exit::Return(value) => Flow::Return(value),
+            Exit::Throw(value) => Flow::Throw(value),
+            Exit::Jsr(pc) => {
+                let subroutine = cfg.block_at(pc)?;
+                let address = Value {
+                    expr: Expr::Literal("returnAddress".into()),
+                    ty: None,
+                };
+                entries[subroutine].get_or_insert_with(|| vec![address]);
+                Flow::Jsr(subroutine, next)
+            }
+            Exit::Ret(name) => Flow::Ret(name),
+        };
+
+        if !stack.is_empty() {
+            let targets = match &flow {
+                Flow::Jsr(..) => vec![],
+                flow => flow.targets(),
+            };
+            for target in targets {
+                let start = cfg.blocks[target].start;
+                let mut values = vec![];
+                for (i, value) in stack.iter().enumerate() {
+                    let name = match i {
+                        0 => format!("$s{}", start),
+                        i => format!("$s{}_{}", start, i),
+                    };
+                    lifter.locals.add(ctx, &name, value.ty.as_ref());
+                    stmts.push(Stmt::Assign(Expr::local(&name), value.expr.clone()));
+                    values.push(Value {
+                        expr: Expr::local(name),
+                        ty: value.ty.clone(),
+                    });
+                }
+                entries[target] = Some(values);
+            }
+        }
+        nodes[block] = Some(Node { stmts, flow });
+    }
+    Ok(nodes)
+}
+
+impl Structurer<'_> {
+    fn label(&self, block: usize) -> String {
+        format!("L{}", self.cfg.blocks[block].start)
+    }
+
+    /// Turns the blocks that only test a condition for `&&` and `||` into one condition
+    fn merge_conditions(&mut self) {
+        let mut handlers = BTreeSet::new();
+        let mut boundaries = BTreeSet::new();
+        for region in &self.regions {
+            handlers.extend(region.handlers.iter().map(|&(handler, _)| handler));
+            boundaries.insert(region.start);
+            boundaries.insert(region.end);
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in 0..self.nodes.len() {
+                let (first, then, next) = match &self.nodes[block] {
+                    Some(Node {
+                        flow: Flow::If(condition, then, otherwise),
+                        ..
+                    }) => (condition.clone(), *then, *otherwise),
+                    _ => continue,
+                };
+                if next == block
+                    || handlers.contains(&next)
+                    || boundaries.contains(&self.cfg.blocks[next].start)
+                    || self.loops.contains_key(&next)
+                    || self.predecessors(next) != 1
+                {
+                    continue;
+                }
+                let (second, then2, otherwise2) = match &self.nodes[next] {
+                    Some(Node {
+                        stmts,
+                        flow: Flow::If(condition, then, otherwise),
+                    }) if stmts.is_empty() => (condition.clone(), *then, *otherwise),
+                    _ => continue,
+                };
+
+                let flow = if then2 == then {
+                    Flow::If(Expr::binary(Op::OrOr, first, second), then, otherwise2)
+                } else if otherwise2 == then {
+                    let second = second.negate();
+                    Flow::If(Expr::binary(Op::OrOr, first, second), then, then2)
+                } else {
+                    continue;
+                };
+                if let Some(node) = &mut self.nodes[block] {
+                    node.flow = flow;
+                }
+                self.nodes[next] = None;
+                changed = true;
+            }
+        }
+    }
+
+    fn predecessors(&self, block: usize) -> usize {
+        (self.nodes.iter().flatten())
+            .flat_map(|node| node.flow.targets())
+            .filter(|&target| target == block)
+            .count()
+    }
+
+    /// Structures the blocks starting at `next` until one of the `stops`, returning the
+    /// stop that was reached
+    ///
+    /// `entering` is for the header of a loop, which is otherwise a `continue`.
+    fn sequence(
+        &mut self,
+        mut next: Option<usize>,
+        stops: &[usize],
+        mut entering: bool,
+    ) -> (Vec<Stmt>, Option<usize>) {
+        let mut out = vec![];
+        while let Some(block) = next {
+            let depth = self.scopes.len();
+            if stops.contains(&block) || self.try_exits.contains(&(depth, block)) {
+                return (out, Some(block));
+            }
+            if !entering {
+                if let Some(jump) = self.jump(block) {
+                    out.push(jump);
+                    break;
+                }
+            }
+            if self.emitted[block] || self.nodes[block].is_none() {
+                out.push(Stmt::Goto(self.label(block)));
+                break;
+            }
+            if let Some(region) = self.region_at(block, entering) {
+                let (stmts, after) = self.try_stmt(region, block, stops, entering);
+                out.extend(stmts);
+                next = after;
+                entering = false;
+                continue;
+            }
+            if !entering && self.loops.contains_key(&block) {
+                let (stmt, after) = self.loop_stmt(block);
+                out.push(stmt);
+                next = after;
+                continue;
+            }
+            entering = false;
+            next = self.block(block, &mut out);
+        }
+        (out, None)
+    }
+
+    /// The `break` or `continue` that gets to `block`, if it's the exit or the header of
+    /// an enclosing statement
+    fn jump(&self, block: usize) -> Option<Stmt> {
+        let mut nested = false;
+        for scope in self.scopes.iter().rev() {
+            match scope {
+                Scope::Loop {
+                    header,
+                    exit,
+                    label,
+                    ..
+                } => {
+                    let label = Some(label.clone()).filter(|_| nested);
+                    if block == *header {
+                        return Some(Stmt::Continue(label));
+                    }
+                    if Some(block) == *exit {
+                        return Some(Stmt::Break(label));
+                    }
+                }
+                Scope::Switch { merge, label } => {
+                    if Some(block) == *merge {
+                        return Some(Stmt::Break(Some(label.clone()).filter(|_| nested)));
+                    }
+                }
+            }
+            nested = true;
+        }
+        None
+    }
+
+    /// Where the paths from `block` meet again, inside of the innermost loop
+    fn merge(&self, block: usize) -> Option<usize> {
+        let merge = self.post_dominators[block]?;
+        let innermost = self.scopes.iter().rev().find_map(|scope| match scope {
+            Scope::Loop { blocks, .. } => Some(blocks),
+            _ => None,
+        });
+        match innermost {
+            Some(blocks) if !blocks.contains(&merge) => None,
+            _ => Some(merge),
+        }
+    }
+
+    /// The blocks that post-dominate `block`, nearest first, inside of the innermost loop
+    fn chain(&self, block: usize) -> Vec<usize> {
+        let mut chain = vec![];
+        let mut next = self.merge(block);
+        while let Some(block) = next {
+            chain.push(block);
+            next = self.merge(block);
+        }
+        chain
+    }
+
+    /// Emits the statements of the block, returning the block that comes after it
+    fn block(&mut self, block: usize, out: &mut Vec<Stmt>) -> Option<usize> {
+        self.emitted[block] = true;
+        let node = self.nodes[block].take()?;
+        out.push(Stmt::Label(self.label(block)));
+        out.extend(node.stmts);
+        // put back what later blocks need to know about
+        let flow = node.flow.clone();
+        self.nodes[block] = Some(Node {
+            stmts: vec![],
+            flow: node.flow,
+        });
+
+        match flow {
+            Flow::Jump(to) => Some(to),
+            Flow::Return(value) => {
+                out.push(Stmt::Return(value));
+                None
+            }
+            Flow::Throw(value) => {
+                out.push(Stmt::Throw(value));
+                None
+            }
+            Flow::Jsr(subroutine, after) => {
+                out.push(Stmt::Raw(format!("jsr {}", self.label(subroutine))));
+                Some(after)
+            }
+            Flow::Ret(name) => {
+                out.push(Stmt::Raw(format!("ret {}", name)));
+                None
+            }
+            Flow::If(condition, then, otherwise) => {
+                self.conditional(block, condition, then, otherwise, out)
+            }
+            Flow::Switch(value, cases) => self.switch(block, value, cases, out),
+        }
+    }
+
+    fn conditional(
+        &mut self,
+        block: usize,
+        condition: Expr,
+        then: usize,
+        otherwise: usize,
+        out: &mut Vec<Stmt>,
+    ) -> Option<usize> {
+        let merge = self.merge(block);
+
+        // a branch that leaves a loop or a switch is a plain `if`
+        for &(target, rest, negate) in &[(then, otherwise, false), (otherwise, then, true)] {
+            let jump = match self.jump(target) {
+                Some(jump) => jump,
+                None => continue,
+            };
+            if Some(target) != merge || matches!(jump, Stmt::Break(..)) {
+                let condition = match negate {
+                    true => condition.negate(),
+                    false => condition,
+                };
+                out.push(Stmt::If(condition, vec![jump], vec![]));
+                return Some(rest);
+            }
+        }
+
+        let stops = merge.into_iter().collect::<Vec<_>>();
+        // javac jumps over the `then` part when the condition doesn't hold
+        let (then_part, _) = self.sequence(Some(otherwise), &stops, false);
+        let (else_part, _) = self.sequence(Some(then), &stops, false);
+        out.push(Stmt::If(condition.negate(), then_part, else_part));
+        merge
+    }
+
+    fn switch(
+        &mut self,
+        block: usize,
+        value: Expr,
+        cases: Vec<(Option<i32>, usize)>,
+        out: &mut Vec<Stmt>,
+    ) -> Option<usize> {
+        let merge = self.merge(block);
+        let mut groups = BTreeMap::<usize, Vec<Option<i32>>>::new();
+        for (key, target) in cases {
+            if key.is_none() && Some(target) == merge {
+                continue;
+            }
+            groups.entry(target).or_default().push(key);
+        }
+        let groups = groups.into_iter().collect::<Vec<_>>();
+
+        let label = format!("switch{}", self.cfg.blocks[block].start);
+        self.scopes.push(Scope::Switch {
+            merge,
+            label: label.clone(),
+        });
+        let mut bodies = vec![];
+        for (i, (target, mut keys)) in groups.iter().cloned().enumerate() {
+            // a case falls through to the next one
+            let stops = groups.get(i + 1).map(|&(next, _)| next);
+            let (body, _) = self.sequence(Some(target), stops.as_slice(), false);
+            keys.sort_by_key(|key| key.map_or((1, 0), |key| (0, key)));
+            bodies.push((keys, body));
+        }
+        self.scopes.pop();
+
+        out.push(Stmt::Label(label));
+        out.push(Stmt::Switch(value, bodies));
+        merge
+    }
+
+    fn loop_stmt(&mut self, header: usize) -> (Stmt, Option<usize>) {
+        let blocks = self.loops[&header].blocks.clone();
+        let outside = |target: &usize| !blocks.contains(target);
+        // the exit of the loop's condition, or the first block it can leave to
+        let exit = match &self.nodes[header] {
+            Some(Node {
+                flow: Flow::If(_, then, otherwise),
+                ..
+            }) if outside(then) || outside(otherwise) => {
+                Some(if outside(then) { *then } else { *otherwise })
+            }
+            _ => (blocks.iter())
+                .filter_map(|&block| self.nodes[block].as_ref())
+                .flat_map(|node| node.flow.targets())
+                .filter(outside)
+                .min(),
+        };
+
+        let label = format!("loop{}", self.cfg.blocks[header].start);
+        self.scopes.push(Scope::Loop {
+            header,
+            exit,
+            blocks,
+            label: label.clone(),
+        });
+        let (body, _) = self.sequence(Some(header), &[], true);
+        self.scopes.pop();
+
+        let condition = Expr::Literal("true".into());
+        (Stmt::While(Some(label), condition, body), exit)
+    }
+
+    /// The outermost try block that starts at `block` which hasn't been structured yet, the
+    /// one with a `finally` block if there are several
+    fn region_at(&self, block: usize, entering: bool) -> Option<usize> {
+        let start = self.cfg.blocks[block].start;
+        let region = (0..self.regions.len())
+            .filter(|&region| !self.opened[region] && self.regions[region].start == start)
+            .filter(|&region| {
+                let handlers = &self.regions[region].handlers;
+                handlers.iter().any(|&(handler, _)| !self.emitted[handler])
+            })
+            .max_by_key(|&region| {
+                let region = &self.regions[region];
+                (region.end, region.handlers[0].1.is_none())
+            })?;
+
+        // a loop that starts with a try block is outside of it, unless it's all inside
+        match self.loops.get(&block) {
+            Some(found) if !entering => {
+                let end = self.regions[region].end;
+                let inside = |&block: &usize| self.cfg.blocks[block].end <= end;
+                Some(region).filter(|_| found.blocks.iter().all(inside))
+            }
+            _ => Some(region),
+        }
+    }
+
+    /// The `try` statement of a region, or just its try block if all of its handlers have
+    /// already been structured
+    fn try_stmt(
+        &mut self,
+        region: usize,
+        block: usize,
+        stops: &[usize],
+        entering: bool,
+    ) -> (Vec<Stmt>, Option<usize>) {
+        self.opened[region] = true;
+        let (start, end) = (self.regions[region].start, self.regions[region].end);
+        let handlers = self.regions[region].handlers.clone();
+
+        // the first block after the try block where it meets the handlers that don't leave
+        let outside = |block: &usize| !(start..end).contains(&self.cfg.blocks[*block].start);
+        let candidates = (self.chain(block).into_iter())
+            .filter(outside)
+            .collect::<Vec<_>>();
+        let chains = (handlers.iter())
+            .map(|&(handler, _)| {
+                let mut chain = self.chain(handler);
+                chain.insert(0, handler);
+                chain
+            })
+            .filter(|chain| chain.iter().any(|block| candidates.contains(block)))
+            .collect::<Vec<_>>();
+        // or, for a `finally` block around a try block that returns on some paths, the
+        // first block after it that the try block goes on to
+        let mut after = None;
+        if handlers.iter().all(|(_, class)| class.is_none()) {
+            let (mut seen, mut work) = (BTreeSet::new(), vec![block]);
+            while let Some(next) = work.pop() {
+                for target in self.nodes[next].iter().flat_map(|node| node.flow.targets()) {
+                    if !outside(&target) {
+                        if seen.insert(target) {
+                            work.push(target);
+                        }
+                    } else if self.cfg.blocks[target].start >= end {
+                        after = Some(after.map_or(target, |after: usize| after.min(target)));
+                    }
+                }
+            }
+        }
+        let merge = (candidates.iter())
+            .find(|candidate| chains.iter().all(|chain| chain.contains(candidate)))
+            .or_else(|| candidates.first())
+            .copied()
+            .or(after);
+
+        let mut inner = stops.to_vec();
+        inner.extend(merge);
+        let exits = self.try_exits.len();
+        // a branch that returns doesn't make the other one go on past the try statement
+        self.try_exits
+            .extend(merge.map(|merge| (self.scopes.len(), merge)));
+        let (body, _) = self.sequence(Some(block), &inner, entering);
+
+        let (mut catches, mut finally) = (vec![], vec![]);
+        for (handler, class) in handlers {
+            if self.emitted[handler] {
+                continue;
+            }
+            let (mut body, _) = self.sequence(Some(handler), &inner, false);
+            let first = body
+                .iter()
+                .position(|stmt| !matches!(stmt, Stmt::Label(..)));
+            let name = match first.map(|i| (i, &body[i])) {
+                Some((i, Stmt::Assign(Expr::Local(name), Expr::Caught))) => {
+                    let name = name.clone();
+                    body.remove(i);
+                    name
+                }
+                _ => "e".to_string(),
+            };
+
+            // a handler of any exception that rethrows it is a `finally` block
+            let last = body
+                .iter()
+                .rposition(|stmt| !matches!(stmt, Stmt::Label(..)));
+            if let (None, Some(last)) = (&class, last) {
+                let uses = body[..last]
+                    .iter()
+                    .map(|stmt| stmt.uses(&name))
+                    .sum::<usize>();
+                if body[last] == Stmt::Throw(Expr::local(&name)) && uses == 0 {
+                    body.truncate(last);
+                    finally = body;
+                    continue;
+                }
+            }
+            let class = class.as_deref().unwrap_or("java/lang/Throwable");
+            catches.push(Catch {
+                class: self.ctx.class_name(class),
+                name,
+                body,
+            });
+        }
+        self.try_exits.truncate(exits);
+        match catches.is_empty() && finally.is_empty() {
+            true => (body, merge),
+            false => (vec![Stmt::Try(body, catches, finally)], merge),
+        }
+    }
+}
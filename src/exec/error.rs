@@ -1,14 +1,32 @@
+use super::exception::JavaException;
+use super::verify::VerifyError;
+
 pub(super) type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
     Parse(crate::parse::Error),
+    Io(std::io::Error),
     MissingMainClass,
     MissingEntryPoint,
     EmptyStack,
     StackType(&'static str),
     VariableType(&'static str, usize),
     VariableOutOfScope,
+    ClassNotFound(String),
+    NoSuchMethod(String),
+    NoSuchField(String),
+    AbstractMethod(String),
+    IncompatibleClassChange(String),
+    UnsatisfiedLink(String),
+    /// A method of a class that failed verification
+    Verify(Box<VerifyError>),
+    /// Arguments passed in from Rust that don't fit the method's descriptor
+    IllegalArgument(String),
+    /// A Java exception thrown out of the method called from Rust
+    Exception(Box<JavaException>),
+    /// Every thread is waiting for another, with a dump of their stacks
+    Deadlock(String),
     GenericError(String),
 }
 
@@ -18,10 +36,19 @@ impl From<crate::parse::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::Parse(err) => Some(err),
+            Error::Io(err) => Some(err),
+            Error::Verify(err) => Some(err.as_ref()),
+            Error::Exception(exception) => Some(exception.as_ref()),
             _ => None,
         }
     }
@@ -31,6 +58,7 @@ impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Parse(err) => write!(f, "{}", err),
+            Error::Io(err) => write!(f, "{}", err),
             Error::MissingMainClass => write!(f, "main class is missing"),
             Error::MissingEntryPoint => write!(f, "entry point is missing"),
             Error::EmptyStack => write!(f, "empty stack"),
@@ -39,6 +67,16 @@ impl std::fmt::Display for Error {
                 write!(f, "expected {} at offset {}", expected, offset)
             }
             Error::VariableOutOfScope => write!(f, "variable is out of scope"),
+            Error::ClassNotFound(name) => write!(f, "class not found: {}", name),
+            Error::NoSuchMethod(name) => write!(f, "no such method: {}", name),
+            Error::NoSuchField(name) => write!(f, "no such field: {}", name),
+            Error::AbstractMethod(name) => write!(f, "abstract method: {}", name),
+            Error::IncompatibleClassChange(msg) => write!(f, "incompatible class change: {}", msg),
+            Error::UnsatisfiedLink(name) => write!(f, "unsatisfied link: {}", name),
+            Error::Verify(err) => write!(f, "verify error: {}", err),
+            Error::IllegalArgument(msg) => write!(f, "illegal argument: {}", msg),
+            Error::Exception(exception) => write!(f, "{}", exception),
+            Error::Deadlock(dump) => write!(f, "deadlock, every thread is waiting:\n{}", dump),
             Error::GenericError(msg) => write!(f, "{}", msg),
         }
     }
@@ -46,10 +84,10 @@ impl std::fmt::Display for Error {
 
 #[macro_export]
 macro_rules! generic_error {
-    ($f:expr, $($args:tt),* $(,)?) => {
+    ($f:expr, $($args:expr),* $(,)?) => {
         generic_error!(format_args!($f, $($args),*))
     };
     ($msg:expr) => {
-        return Err(Error::GenericError(format!("{}", $msg)))
+        return Err(Error::GenericError(format!("{}", $msg)).into())
     };
 }
@@ -0,0 +1,93 @@
+use super::heap::Reference;
+use super::*;
+
+/// Why a method or instruction completed abruptly
+#[derive(Debug)]
+pub(crate) enum Abrupt {
+    /// An internal fault, which stops the interpreter
+    Fault(Error),
+    /// An exception raised by the VM, given as the class and its message. It is created
+    /// when it is thrown
+    Raise(&'static str, Option<String>),
+    /// A Java exception that is being thrown
+    Throw(Reference),
+}
+
+pub(crate) type Completion<T> = std::result::Result<T, Abrupt>;
+
+impl From<Error> for Abrupt {
+    /// Linkage errors are thrown as their Java counterparts, everything else is a fault
+    fn from(err: Error) -> Self {
+        match err {
+            Error::ClassNotFound(msg) => Abrupt::Raise("java/lang/NoClassDefFoundError", Some(msg)),
+            Error::NoSuchMethod(msg) => Abrupt::Raise("java/lang/NoSuchMethodError", Some(msg)),
+            Error::NoSuchField(msg) => Abrupt::Raise("java/lang/NoSuchFieldError", Some(msg)),
+            Error::AbstractMethod(msg) => Abrupt::Raise("java/lang/AbstractMethodError", Some(msg)),
+            Error::IncompatibleClassChange(msg) => {
+                Abrupt::Raise("java/lang/IncompatibleClassChangeError", Some(msg))
+            }
+            Error::UnsatisfiedLink(msg) => {
+                Abrupt::Raise("java/lang/UnsatisfiedLinkError", Some(msg))
+            }
+            Error::Verify(err) => Abrupt::Raise("java/lang/VerifyError", Some(err.to_string())),
+            err => Abrupt::Fault(err),
+        }
+    }
+}
+
+impl From<crate::parse::Error> for Abrupt {
+    fn from(err: crate::parse::Error) -> Self {
+        Abrupt::Fault(err.into())
+    }
+}
+
+/// A frame of a Java stack trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackTraceElement {
+    /// The internal name of the class
+    pub class: String,
+    pub method: String,
+    pub file: Option<String>,
+    pub line: Option<u16>,
+}
+
+impl std::fmt::Display for StackTraceElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}(", self.class.replace('/', "."), self.method)?;
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{})", file, line),
+            (Some(file), None) => write!(f, "{})", file),
+            (None, _) => write!(f, "Unknown Source)"),
+        }
+    }
+}
+
+/// A Java exception that was not caught
+#[derive(Debug, Clone)]
+pub struct JavaException {
+    pub exception: Reference,
+    /// The internal name of the exception's class
+    pub class: String,
+    pub message: Option<String>,
+    pub stack_trace: Vec<StackTraceElement>,
+    pub cause: Option<Box<JavaException>>,
+}
+
+impl std::fmt::Display for JavaException {
+    /// Formats the exception like `Throwable.printStackTrace`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.class.replace('/', "."))?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        for element in &self.stack_trace {
+            write!(f, "\n\tat {}", element)?;
+        }
+        if let Some(cause) = &self.cause {
+            write!(f, "\nCaused by: {}", cause)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JavaException {}
@@ -0,0 +1,368 @@
+//! Binding Rust closures to `native` methods, like JNI's `RegisterNatives`
+use super::heap::{Object, Reference};
+use super::interpreter::Interpreter;
+use super::value::Value;
+use super::*;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use ty::FieldType;
+
+/// A Rust closure implementing a `native` method. The arguments include `this` for
+/// instance methods
+pub type HostFn = dyn Fn(&mut Env<'_>, &[Value]) -> Result<Option<Value>>;
+
+/// Rust closures for `native` methods, keyed by class, name and descriptor
+///
+/// Methods are bound when their class is linked, so they have to be registered before the
+/// class is first used.
+#[derive(Default, Clone)]
+pub struct NativeRegistry {
+    methods: HashMap<(String, String, String), Rc<HostFn>>,
+}
+
+impl NativeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds `func` to the `native` method `name` with the `descriptor` of `class`, which
+    /// is an internal name (`com/acme/Calc`). A previous binding is replaced
+    pub fn register<F>(&mut self, class: &str, name: &str, descriptor: &str, func: F) -> &mut Self
+    where
+        F: Fn(&mut Env<'_>, &[Value]) -> Result<Option<Value>> + 'static,
+    {
+        let key = (class.to_string(), name.to_string(), descriptor.to_string());
+        self.methods.insert(key, Rc::new(func));
+        self
+    }
+
+    pub fn lookup(&self, class: &str, name: &str, descriptor: &str) -> Option<Rc<HostFn>> {
+        let key = (class.to_string(), name.to_string(), descriptor.to_string());
+        self.methods.get(&key).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.methods.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.methods.is_empty()
+    }
+}
+
+impl std::fmt::Debug for NativeRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set()
+            .entries(
+                self.methods
+                    .keys()
+                    .map(|(class, name, descriptor)| format!("{}.{}{}", class, name, descriptor)),
+            )
+            .finish()
+    }
+}
+
+/// What a native method can do with the interpreter, like JNI's `JNIEnv`
+///
+/// Exceptions work like they do in JNI: `throw` makes the exception pending, and it is
+/// thrown once the native method returns. Calls back into Java that throw return `None`
+/// and leave the exception pending, which can be checked with `exception_check`.
+pub struct Env<'a> {
+    vm: &'a mut Interpreter,
+}
+
+impl<'a> Env<'a> {
+    pub fn new(vm: &'a mut Interpreter) -> Self {
+        Self { vm }
+    }
+
+    /// The interpreter itself, for anything this doesn't cover
+    pub fn interpreter(&mut self) -> &mut Interpreter {
+        self.vm
+    }
+
+    /// Reads a `java/lang/String`, `None` for `null`
+    pub fn get_string(&self, string: Value) -> Result<Option<String>> {
+        match string.as_reference()? {
+            Some(string) => self.vm.read_string(string).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Creates a `java/lang/String`
+    pub fn new_string(&mut self, s: &str) -> Result<Value> {
+        self.vm.new_string(s).map(Value::from)
+    }
+
+    /// Creates an array with the component type `component`, a descriptor like `I` or
+    /// `Ljava/lang/String;`
+    pub fn new_array(&mut self, component: &str, elements: Vec<Value>) -> Result<Value> {
+        let component = FieldType::parse(component)?;
+        let array = self.vm.heap_mut().new_array(component, elements.len());
+        self.vm.heap_mut().array_mut(array)?.elements = elements;
+        Ok(array.into())
+    }
+
+    pub fn array_length(&self, array: Value) -> Result<usize> {
+        Ok(self.array(array)?.len())
+    }
+
+    /// The elements of an array, copied out of the heap
+    pub fn array_elements(&self, array: Value) -> Result<Vec<Value>> {
+        Ok(self.array(array)?.to_vec())
+    }
+
+    pub fn set_array_element(&mut self, array: Value, index: usize, value: Value) -> Result<()> {
+        let array = self.reference(array)?;
+        let elements = &mut self.vm.heap_mut().array_mut(array)?.elements;
+        match elements.get_mut(index) {
+            Some(element) => {
+                *element = value;
+                Ok(())
+            }
+            None => generic_error!("array index {} out of bounds", index),
+        }
+    }
+
+    fn array(&self, array: Value) -> Result<&[Value]> {
+        let array = self.reference(array)?;
+        Ok(&self.vm.heap().array(array)?.elements)
+    }
+
+    fn reference(&self, value: Value) -> Result<Reference> {
+        match value.as_reference()? {
+            Some(reference) => Ok(reference),
+            None => generic_error!("unexpected null reference"),
+        }
+    }
+
+    /// The internal name of an object's class, `None` for `null`
+    pub fn class_name(&self, object: Value) -> Result<Option<String>> {
+        Ok(object
+            .as_reference()?
+            .map(|object| self.vm.heap().get(object).class_name()))
+    }
+
+    /// Whether `object` is an instance of `class`, `false` for `null`
+    pub fn is_instance_of(&mut self, object: Value, class: &str) -> Result<bool> {
+        match object.as_reference()? {
+            Some(object) => {
+                let name = self.vm.heap().get(object).class_name();
+                self.vm.is_assignable(&name, class)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn get_field(&self, object: Value, name: &str, descriptor: &str) -> Result<Value> {
+        self.vm.get_field(self.reference(object)?, name, descriptor)
+    }
+
+    pub fn set_field(
+        &mut self,
+        object: Value,
+        name: &str,
+        descriptor: &str,
+        value: Value,
+    ) -> Result<()> {
+        let object = self.reference(object)?;
+        self.vm.set_field(object, name, descriptor, value)
+    }
+
+    /// Creates an instance of `class` with the constructor that has the `descriptor`.
+    /// `None` if the class' initializer or the constructor threw
+    pub fn new_object(
+        &mut self,
+        class: &str,
+        descriptor: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        Ok(self
+            .vm
+            .new_object(class, descriptor, args)?
+            .map(Value::from))
+    }
+
+    /// Calls a static method, `None` if it threw
+    pub fn call_static(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        self.vm.call_static(class, name, descriptor, args)
+    }
+
+    /// Calls an instance method, selected by the class of `object`. `None` if it threw
+    pub fn call_method(
+        &mut self,
+        object: Value,
+        name: &str,
+        descriptor: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        match object.as_reference()? {
+            Some(object) => self.vm.call_method(object, name, descriptor, args),
+            None => {
+                self.vm.throw_new("java/lang/NullPointerException", None)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Throws a `Throwable` once the native method returns
+    pub fn throw(&mut self, exception: Value) -> Result<()> {
+        let exception = self.reference(exception)?;
+        if !self.is_instance_of(exception.into(), "java/lang/Throwable")? {
+            generic_error!(
+                "{} is not a java/lang/Throwable",
+                self.vm.heap().get(exception).class_name()
+            );
+        }
+        self.vm.throw(exception);
+        Ok(())
+    }
+
+    /// Creates an exception of `class` and throws it once the native method returns
+    pub fn throw_new(&mut self, class: &str, message: Option<&str>) -> Result<()> {
+        self.vm.throw_new(class, message)
+    }
+
+    /// Whether an exception is pending
+    pub fn exception_check(&self) -> bool {
+        self.vm.exception_pending()
+    }
+
+    /// Takes the pending exception, so that it isn't thrown
+    pub fn exception_clear(&mut self) -> Option<Value> {
+        self.vm.clear_exception().map(Value::from)
+    }
+
+    /// Keeps an object alive across garbage collections, for a native library that holds on
+    /// to it after returning, until `delete_global_ref` is called
+    pub fn new_global_ref(&mut self, object: Value) -> Result<()> {
+        let object = self.reference(object)?;
+        self.vm.pin(object);
+        Ok(())
+    }
+
+    pub fn delete_global_ref(&mut self, object: Value) -> Result<()> {
+        let object = self.reference(object)?;
+        self.vm.unpin(object);
+        Ok(())
+    }
+
+    /// Whether the `Object` is an array
+    pub fn is_array(&self, object: Value) -> Result<bool> {
+        match object.as_reference()? {
+            Some(object) => Ok(matches!(self.vm.heap().get(object), Object::Array(..))),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::stream::Capture;
+    use crate::test_utils::load_classes;
+
+    fn load(natives: NativeRegistry) -> Interpreter {
+        let mut interpreter = load_classes(&["host"]);
+        interpreter.set_natives(natives);
+        interpreter
+    }
+
+    fn natives() -> NativeRegistry {
+        let mut natives = NativeRegistry::new();
+        natives
+            .register("host", "add", "(II)I", |_, args| {
+                Ok(Some(Value::Int(args[0].as_int()? + args[1].as_int()?)))
+            })
+            .register(
+                "host",
+                "greet",
+                "(Ljava/lang/String;)Ljava/lang/String;",
+                |env, args| {
+                    let name = env.get_string(args[0])?.unwrap_or_default();
+                    env.new_string(&format!("hello, {}", name)).map(Some)
+                },
+            )
+            .register("host", "range", "(I)[I", |env, args| {
+                let elements = (0..args[0].as_int()?).map(Value::Int).collect();
+                env.new_array("I", elements).map(Some)
+            })
+            .register("host", "fail", "(Ljava/lang/String;)V", |env, args| {
+                let message = env.get_string(args[0])?;
+                env.throw_new("java/lang/IllegalStateException", message.as_deref())?;
+                Ok(None)
+            })
+            .register("host", "twice", "(I)I", |env, args| {
+                // calls back into java
+                let square = env.call_static("host", "square", "(I)I", vec![args[0]])?;
+                match square {
+                    Some(square) => Ok(Some(Value::Int(square.as_int()? * 2))),
+                    None => Ok(None),
+                }
+            })
+            .register(
+                "host",
+                "describe",
+                "(Ljava/lang/Object;)Ljava/lang/String;",
+                |env, args| {
+                    let s = env.call_method(args[0], "toString", "()Ljava/lang/String;", vec![])?;
+                    match s {
+                        Some(s) => {
+                            let s = env.get_string(s)?.unwrap_or_default();
+                            env.new_string(&format!("<{}>", s)).map(Some)
+                        }
+                        None => Ok(None),
+                    }
+                },
+            )
+            .register("host", "sum", "()I", |env, args| {
+                // an instance method, `this` comes first
+                let left = env.get_field(args[0], "left", "I")?.as_int()?;
+                let right = env.get_field(args[0], "right", "I")?.as_int()?;
+                Ok(Some(Value::Int(left + right)))
+            });
+        natives
+    }
+
+    #[test]
+    fn bind_native_methods() {
+        let mut interpreter = load(natives());
+        let stdout = Capture::new();
+        interpreter.set_stdout(stdout.clone());
+
+        let mut call = |name| interpreter.invoke_static("host", name, "()I", &[]).unwrap();
+
+        assert_eq!(call("arithmetic"), Some(Value::Int(5 + 8 + 2 * 49)));
+        assert_eq!(call("strings"), Some(Value::Int(0)));
+        assert_eq!(call("arrays"), Some(Value::Int(45)));
+        assert_eq!(call("exceptions"), Some(Value::Int(-1)));
+        assert_eq!(call("instance"), Some(Value::Int(7)));
+        // no binding for `missing`
+        assert_eq!(call("unbound"), Some(Value::Int(-2)));
+
+        assert_eq!(
+            stdout.contents(),
+            "hello, world\n<host(3, 4)>\nbad state\njava.lang.UnsatisfiedLinkError: host.missing()V\n"
+        );
+    }
+
+    #[test]
+    fn uncaught_from_native() {
+        let mut interpreter = load(natives());
+        let args = vec![Value::Null];
+        let exception =
+            match interpreter.invoke_static("host", "fail", "(Ljava/lang/String;)V", &args) {
+                Err(Error::Exception(exception)) => *exception,
+                e => panic!("{:?}", e),
+            };
+        assert_eq!(exception.class, "java/lang/IllegalStateException");
+        assert_eq!(exception.message, None);
+    }
+}
@@ -0,0 +1,83 @@
+use super::class::{Field, Method};
+use super::*;
+
+use std::rc::Rc;
+
+// The reference kinds, numbered like the `reference_kind` of a `MethodHandle` constant
+pub const REF_GET_FIELD: u8 = 1;
+pub const REF_GET_STATIC: u8 = 2;
+pub const REF_PUT_FIELD: u8 = 3;
+pub const REF_PUT_STATIC: u8 = 4;
+pub const REF_INVOKE_VIRTUAL: u8 = 5;
+pub const REF_INVOKE_STATIC: u8 = 6;
+pub const REF_INVOKE_SPECIAL: u8 = 7;
+pub const REF_NEW_INVOKE_SPECIAL: u8 = 8;
+pub const REF_INVOKE_INTERFACE: u8 = 9;
+
+/// The member a `java/lang/invoke/MethodHandle` refers to, by reference kind
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-5.html#jvms-5.4.3.5
+#[derive(Debug, Clone)]
+pub enum HandleTarget {
+    GetField(Rc<Field>),
+    GetStatic(Rc<Field>),
+    PutField(Rc<Field>),
+    PutStatic(Rc<Field>),
+    InvokeVirtual(Rc<Method>),
+    InvokeStatic(Rc<Method>),
+    InvokeSpecial(Rc<Method>),
+    NewInvokeSpecial(Rc<Method>),
+    InvokeInterface(Rc<Method>),
+}
+
+impl HandleTarget {
+    /// The descriptor of the handle's `MethodType`
+    pub fn method_descriptor(&self) -> String {
+        fn object(name: &str) -> String {
+            format!("L{};", name)
+        }
+
+        fn method(receiver: Option<String>, method: &Method, ret: Option<String>) -> String {
+            let params = method.signature.params.iter().map(ToString::to_string);
+            let params = receiver.into_iter().chain(params).collect::<String>();
+            let ret = ret.unwrap_or_else(|| match &method.signature.ret {
+                Some(ret) => ret.to_string(),
+                None => "V".to_string(),
+            });
+            format!("({}){}", params, ret)
+        }
+
+        match self {
+            HandleTarget::GetField(field) => {
+                format!("({}){}", object(&field.class().name), field.descriptor)
+            }
+            HandleTarget::GetStatic(field) => format!("(){}", field.descriptor),
+            HandleTarget::PutField(field) => {
+                format!("({}{})V", object(&field.class().name), field.descriptor)
+            }
+            HandleTarget::PutStatic(field) => format!("({})V", field.descriptor),
+            HandleTarget::InvokeVirtual(m)
+            | HandleTarget::InvokeSpecial(m)
+            | HandleTarget::InvokeInterface(m) => method(Some(object(&m.class().name)), m, None),
+            HandleTarget::InvokeStatic(m) => method(None, m, None),
+            HandleTarget::NewInvokeSpecial(m) => method(None, m, Some(object(&m.class().name))),
+        }
+    }
+}
+
+/// A method type the way `MethodType.toString` shows it, e.g. `(int,String)void`
+pub fn method_type_string(descriptor: &ty::MethodDescriptor) -> String {
+    let name = |ty: Option<&ty::FieldType>| match ty {
+        None => "void".to_string(),
+        Some(ty::FieldType::Object(name)) => name.rsplit('/').next().unwrap_or(name).to_string(),
+        Some(ty) => ty
+            .keyword()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| ty.to_string()),
+    };
+    let params = descriptor
+        .params
+        .iter()
+        .map(|param| name(Some(param)))
+        .collect::<Vec<_>>();
+    format!("({}){}", params.join(","), name(descriptor.ret.as_ref()))
+}
@@ -0,0 +1,295 @@
+use super::class::Class;
+use super::value::Value;
+use super::*;
+
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Roughly what an object takes up besides its fields or elements
+const HEADER: usize = 16;
+
+/// How much is allocated before the first collection
+const INITIAL_THRESHOLD: usize = 1 << 20;
+
+/// The default maximum heap size, like `-Xmx256m`
+const DEFAULT_MAX: usize = 256 << 20;
+
+/// Roughly how many bytes an object with `values` fields or elements takes up
+pub fn object_size(values: usize) -> usize {
+    values
+        .saturating_mul(std::mem::size_of::<Value>())
+        .saturating_add(HEADER)
+}
+
+/// A handle to an object on the interpreter heap
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Reference(pub(crate) u32);
+
+impl Reference {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Object {
+    Instance(Instance),
+    Array(Array),
+}
+
+impl Object {
+    /// The internal name of this object's class (`[I`, `java/lang/String`, etc)
+    pub fn class_name(&self) -> String {
+        match self {
+            Object::Instance(instance) => instance.class.name.clone(),
+            Object::Array(array) => format!("[{}", array.component),
+        }
+    }
+
+    fn values(&self) -> &[Value] {
+        match self {
+            Object::Instance(instance) => &instance.fields,
+            Object::Array(array) => &array.elements,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        object_size(self.values().len())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: Vec<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Array {
+    pub component: ty::FieldType,
+    pub elements: Vec<Value>,
+}
+
+/// What the garbage collector has done so far
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct GcStats {
+    pub collections: usize,
+    pub objects_freed: usize,
+    pub bytes_freed: usize,
+    /// How long all the collections took together
+    pub pause: Duration,
+}
+
+/// The objects, which are reclaimed by a mark-sweep collector. References are indices that
+/// don't move, so the interpreter's tables keyed by them stay valid across collections
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Option<Object>>,
+    free: Vec<u32>,
+    /// The bytes taken up by the objects, including garbage that hasn't been collected
+    used: usize,
+    max: Option<usize>,
+    /// How big `used` gets before the next collection
+    threshold: usize,
+    stats: GcStats,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            objects: vec![],
+            free: vec![],
+            used: 0,
+            max: Some(DEFAULT_MAX),
+            threshold: INITIAL_THRESHOLD,
+            stats: GcStats::default(),
+        }
+    }
+}
+
+impl Heap {
+    pub fn allocate(&mut self, object: Object) -> Reference {
+        self.used += object.size();
+        if let Some(index) = self.free.pop() {
+            self.objects[index as usize] = Some(object);
+            return Reference(index);
+        }
+        self.objects.push(Some(object));
+        Reference(self.objects.len() as u32 - 1)
+    }
+
+    pub fn new_instance(&mut self, class: Rc<Class>) -> Reference {
+        let fields = class.instance_field_defaults();
+        self.allocate(Object::Instance(Instance { class, fields }))
+    }
+
+    pub fn new_array(&mut self, component: ty::FieldType, len: usize) -> Reference {
+        let elements = vec![Value::default_for(&component); len];
+        self.allocate(Object::Array(Array {
+            component,
+            elements,
+        }))
+    }
+
+    pub fn get(&self, reference: Reference) -> &Object {
+        self.objects[reference.index()]
+            .as_ref()
+            .expect("reference to a live object")
+    }
+
+    pub fn get_mut(&mut self, reference: Reference) -> &mut Object {
+        self.objects[reference.index()]
+            .as_mut()
+            .expect("reference to a live object")
+    }
+
+    pub fn instance(&self, reference: Reference) -> Result<&Instance> {
+        match self.get(reference) {
+            Object::Instance(instance) => Ok(instance),
+            Object::Array(..) => Err(Error::StackType("object")),
+        }
+    }
+
+    pub fn instance_mut(&mut self, reference: Reference) -> Result<&mut Instance> {
+        match self.get_mut(reference) {
+            Object::Instance(instance) => Ok(instance),
+            Object::Array(..) => Err(Error::StackType("object")),
+        }
+    }
+
+    pub fn array(&self, reference: Reference) -> Result<&Array> {
+        match self.get(reference) {
+            Object::Array(array) => Ok(array),
+            Object::Instance(..) => Err(Error::StackType("array")),
+        }
+    }
+
+    pub fn array_mut(&mut self, reference: Reference) -> Result<&mut Array> {
+        match self.get_mut(reference) {
+            Object::Array(array) => Ok(array),
+            Object::Instance(..) => Err(Error::StackType("array")),
+        }
+    }
+
+    /// The reference to the object at `index`, if it is alive
+    pub fn reference(&self, index: usize) -> Option<Reference> {
+        match self.objects.get(index) {
+            Some(Some(..)) => Some(Reference(index as u32)),
+            _ => None,
+        }
+    }
+
+    /// How many objects are currently alive
+    pub fn len(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Roughly how many bytes the objects take up
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// How big the heap can get before allocating throws `OutOfMemoryError`, `None` for no
+    /// limit
+    pub fn max_size(&self) -> Option<usize> {
+        self.max
+    }
+
+    pub fn set_max_size(&mut self, max: Option<usize>) {
+        self.max = max;
+    }
+
+    /// Whether allocating `bytes` more would take the heap past its maximum size
+    pub fn would_exceed(&self, bytes: usize) -> bool {
+        self.max
+            .is_some_and(|max| self.used.saturating_add(bytes) > max)
+    }
+
+    /// Whether enough has been allocated since the last collection to collect again
+    pub fn should_collect(&self) -> bool {
+        self.used >= self.threshold
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    /// Frees every object that can't be reached from `roots`, and returns them
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = Reference>) -> Vec<Reference> {
+        let start = Instant::now();
+
+        let mut marked = vec![false; self.objects.len()];
+        let mut pending = roots.into_iter().collect::<Vec<_>>();
+        while let Some(reference) = pending.pop() {
+            if std::mem::replace(&mut marked[reference.index()], true) {
+                continue;
+            }
+            let values = self.get(reference).values();
+            pending.extend(values.iter().filter_map(|value| match value {
+                Value::Reference(reference) => Some(*reference),
+                _ => None,
+            }));
+        }
+
+        let mut freed = vec![];
+        for (index, object) in self.objects.iter_mut().enumerate() {
+            if marked[index] {
+                continue;
+            }
+            if let Some(object) = object.take() {
+                let size = object.size();
+                self.used -= size;
+                self.stats.bytes_freed += size;
+                self.free.push(index as u32);
+                freed.push(Reference(index as u32));
+            }
+        }
+
+        self.threshold = self.used.saturating_mul(2).max(INITIAL_THRESHOLD);
+        self.stats.collections += 1;
+        self.stats.objects_freed += freed.len();
+        self.stats.pause += start.elapsed();
+        freed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_cycles() {
+        let mut heap = Heap::default();
+        let object = ty::FieldType::Object("java/lang/Object".into());
+        let root = heap.new_array(object.clone(), 1);
+        let child = heap.new_array(object.clone(), 1);
+        heap.array_mut(root).unwrap().elements[0] = child.into();
+
+        // two arrays that only refer to each other
+        let a = heap.new_array(object.clone(), 1);
+        let b = heap.new_array(object, 1);
+        heap.array_mut(a).unwrap().elements[0] = b.into();
+        heap.array_mut(b).unwrap().elements[0] = a.into();
+
+        let used = heap.used();
+        let mut freed = heap.collect(vec![root]);
+        freed.sort();
+        assert_eq!(freed, vec![a, b]);
+        assert_eq!(heap.len(), 2);
+        assert_eq!(heap.used(), used - 2 * object_size(1));
+
+        let stats = heap.stats();
+        assert_eq!(stats.collections, 1);
+        assert_eq!(stats.objects_freed, 2);
+        assert_eq!(stats.bytes_freed, 2 * object_size(1));
+
+        // freed slots are reused
+        let reused = heap.new_array(ty::FieldType::Int, 4);
+        assert!(reused == a || reused == b);
+        assert!(heap.would_exceed(DEFAULT_MAX));
+    }
+}
@@ -19,6 +19,22 @@ macro_rules! instruction {
         }
     };
 
+    (@read $inst:ident, |$this:ident| $size:expr, |$code:ident, $pc:ident| $body:expr) => {
+        #[allow(unused_variables)]
+        impl $inst {
+            /// Reads the operands for this instruction, `pc` is the position of the opcode
+            fn read($code: &[u8], $pc: usize) -> Option<Self> {
+                $body
+            }
+
+            /// The size of this instruction, in bytes, including the opcode
+            pub fn size(&self) -> usize {
+                let $this = self;
+                $size
+            }
+        }
+    };
+
     (@create $opcode:expr, $inst:ident, (), $doc:expr) => {
         instruction!(@empty $opcode, $inst, $doc);
     };
@@ -29,6 +45,9 @@ macro_rules! instruction {
         #[doc = $doc]
         pub struct $inst (pub Vec<$a>);
         instruction!(@impl_ $inst, $opcode, $doc);
+        instruction!(@read $inst, |this| 1 + this.0.len(), |code, pc| {
+            switch_operands($opcode, code, pc).map($inst)
+        });
     };
 
     (@create $opcode:expr, $inst:ident, ($a:tt), $doc:expr) => {
@@ -37,6 +56,7 @@ macro_rules! instruction {
         #[doc = $doc]
         pub struct $inst (pub $a);
         instruction!(@impl_ $inst, $opcode, $doc);
+        instruction!(@read $inst, |this| 2, |code, pc| Some($inst(*code.get(pc + 1)?)));
     };
 
     (@create $opcode:expr, $inst:ident, ($a:tt, $b:tt), $doc:expr) => {
@@ -45,6 +65,12 @@ macro_rules! instruction {
         #[doc = $doc]
         pub struct $inst (pub $a, pub $b);
         instruction!(@impl_ $inst, $opcode, $doc);
+        instruction!(@read $inst, |this| 3, |code, pc| {
+            match code.get(pc + 1..pc + 3)? {
+                &[a, b] => Some($inst(a, b)),
+                _ => None,
+            }
+        });
     };
 
     (@create $opcode:expr, $inst:ident, ($a:tt, $b:tt, $c:tt), $doc:expr) => {
@@ -53,6 +79,12 @@ macro_rules! instruction {
         #[doc = $doc]
         pub struct $inst (pub $a, pub $b, pub $c);
         instruction!(@impl_ $inst, $opcode, $doc);
+        instruction!(@read $inst, |this| 4, |code, pc| {
+            match code.get(pc + 1..pc + 4)? {
+                &[a, b, c] => Some($inst(a, b, c)),
+                _ => None,
+            }
+        });
     };
 
     (@create $opcode:expr, $inst:ident, ($a:tt, $b:tt, $c:tt, $d:tt), $doc:expr) => {
@@ -61,6 +93,12 @@ macro_rules! instruction {
         #[doc = $doc]
         pub struct $inst (pub $a, pub $b, pub $c, pub $d);
         instruction!(@impl_ $inst, $opcode, $doc);
+        instruction!(@read $inst, |this| 5, |code, pc| {
+            match code.get(pc + 1..pc + 5)? {
+                &[a, b, c, d] => Some($inst(a, b, c, d)),
+                _ => None,
+            }
+        });
     };
 
     (@create $opcode:expr, $inst:ident, ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt), $doc:expr) => {
@@ -69,6 +107,19 @@ macro_rules! instruction {
         #[doc = $doc]
         pub struct $inst (pub $a, pub $b, pub $c, pub $d, pub $e);
         instruction!(@impl_ $inst, $opcode, $doc);
+        // only WIDE has 5 operands, and it only uses the last two for `iinc`
+        instruction!(@read $inst, |this| if this.0 == 0x84 { 6 } else { 4 }, |code, pc| {
+            match *code.get(pc + 1)? {
+                0x84 => match code.get(pc + 1..pc + 6)? {
+                    &[a, b, c, d, e] => Some($inst(a, b, c, d, e)),
+                    _ => None,
+                },
+                _ => match code.get(pc + 1..pc + 4)? {
+                    &[a, b, c] => Some($inst(a, b, c, 0, 0)),
+                    _ => None,
+                },
+            }
+        });
     };
 
     (@empty $opcode:expr, $inst:ident, $doc:expr) => {
@@ -77,6 +128,7 @@ macro_rules! instruction {
         #[doc = $doc]
         pub struct $inst;
         instruction!(@impl_ $inst, $opcode, $doc);
+        instruction!(@read $inst, |this| 1, |code, pc| Some($inst));
     };
 
     ($($opcode:expr => $inst:ident >> $ty:tt => $doc:expr);* $(;)?) => {
@@ -97,6 +149,27 @@ macro_rules! instruction {
                 }
             }
 
+            /// Decode the instruction (and its operands) at `pc`
+            pub fn decode(code: &[u8], pc: usize) -> Option<Self> {
+                match *code.get(pc)? {
+                    $($opcode => $inst::read(code, pc).map(Instruction::$inst),)*
+                    _ => None
+                }
+            }
+
+            /// The size of this instruction, in bytes, including the opcode
+            pub fn size(&self) -> usize {
+                match self {
+                    $(Instruction::$inst(d) => d.size(),)*
+                }
+            }
+
+            pub fn opcode(&self) -> u8 {
+                match self {
+                    $(Instruction::$inst(d) => d.opcode(),)*
+                }
+            }
+
             /// Is this instruction a variable-length isntruction?
             pub fn is_varargs(&self) -> bool {
                 match self {
@@ -131,6 +204,68 @@ macro_rules! instruction {
     };
 }
 
+/// Reads the (padded) operands of a `tableswitch` or `lookupswitch`
+fn switch_operands(opcode: u8, code: &[u8], pc: usize) -> Option<Vec<u8>> {
+    let pad = (4 - (pc + 1) % 4) % 4;
+    let start = pc + 1 + pad;
+    let int = |offset: usize| {
+        code.get(start + offset..start + offset + 4)
+            .map(|d| i32::from_be_bytes([d[0], d[1], d[2], d[3]]))
+    };
+    let len = match opcode {
+        0xAA => 12 + (i64::from(int(8)?) - i64::from(int(4)?) + 1).max(0) as usize * 4,
+        _ => 8 + int(4)?.max(0) as usize * 8,
+    };
+    code.get(pc + 1..start + len).map(<[u8]>::to_vec)
+}
+
+/// A 16-bit constant pool index from two operand bytes
+#[inline]
+pub fn wide_index(hi: u8, lo: u8) -> u16 {
+    u16::from_be_bytes([hi, lo])
+}
+
+/// A signed 16-bit branch offset from two operand bytes
+#[inline]
+pub fn branch_offset(hi: u8, lo: u8) -> i32 {
+    i32::from(i16::from_be_bytes([hi, lo]))
+}
+
+/// A signed 32-bit branch offset from four operand bytes
+#[inline]
+pub fn branch_offset_wide(a: u8, b: u8, c: u8, d: u8) -> i32 {
+    i32::from_be_bytes([a, b, c, d])
+}
+
+fn switch_ints(operands: &[u8]) -> impl Iterator<Item = i32> + '_ {
+    // the padding makes the operands a multiple of 4
+    operands[operands.len() % 4..]
+        .chunks(4)
+        .map(|d| i32::from_be_bytes([d[0], d[1], d[2], d[3]]))
+}
+
+impl TABLESWITCH {
+    /// Returns the default offset, the low key and the jump offsets
+    pub fn table(&self) -> (i32, i32, Vec<i32>) {
+        let mut ints = switch_ints(&self.0);
+        let default = ints.next().unwrap_or_default();
+        let low = ints.next().unwrap_or_default();
+        let _high = ints.next();
+        (default, low, ints.collect())
+    }
+}
+
+impl LOOKUPSWITCH {
+    /// Returns the default offset and the (match, offset) pairs
+    pub fn pairs(&self) -> (i32, Vec<(i32, i32)>) {
+        let mut ints = switch_ints(&self.0);
+        let default = ints.next().unwrap_or_default();
+        let _npairs = ints.next();
+        let ints = ints.collect::<Vec<_>>();
+        (default, ints.chunks(2).map(|d| (d[0], d[1])).collect())
+    }
+}
+
 // TODO make this documentation look less like garbage
 instruction! {
     0x00 => NOP            >> ()               => "perform no operation";
@@ -158,7 +293,7 @@ instruction! {
     0x16 => LLOAD          >> (u8)             => "load a long value from a local variable #index";
     0x17 => FLOAD          >> (u8)             => "load a float value from a local variable #index";
     0x18 => DLOAD          >> (u8)             => "load a double value from a local variable #index";
-    0x19 => ALOAD          >> ()               => "load a reference onto the stack from a local variable #index";
+    0x19 => ALOAD          >> (u8)             => "load a reference onto the stack from a local variable #index";
     0x1A => ILOAD_0        >> ()               => "load an int value from local variable 0";
     0x1B => ILOAD_1        >> ()               => "load an int value from local variable 1";
     0x1C => ILOAD_2        >> ()               => "load an int value from local variable 2";
@@ -175,7 +310,7 @@ instruction! {
     0x27 => DLOAD_1        >> ()               => "load a double from local variable 1";
     0x28 => DLOAD_2        >> ()               => "load a double from local variable 2";
     0x29 => DLOAD_3        >> ()               => "load a double from local variable 3";
-    0x2A => ALOAD_0        >> (u8)             => "load a reference onto the stack from local variable 0";
+    0x2A => ALOAD_0        >> ()               => "load a reference onto the stack from local variable 0";
     0x2B => ALOAD_1        >> ()               => "load a reference onto the stack from local variable 1";
     0x2C => ALOAD_2        >> ()               => "load a reference onto the stack from local variable 2";
     0x2D => ALOAD_3        >> ()               => "load a reference onto the stack from local variable 3";
@@ -1,155 +1,310 @@
-#![allow(dead_code, unused_variables, unused_mut)]
-
 use std::collections::HashMap;
 use std::rc::Rc;
 
+use super::class::{Class, ClassDef, InitState, Method, MethodBody};
+use super::convert::{FromJava, JavaArgs};
+use super::debug::Debugger;
+use super::exception::{Abrupt, Completion, JavaException, StackTraceElement};
+use super::ffi::{Env, NativeRegistry};
+use super::handle::HandleTarget;
+use super::heap::{Heap, Object};
+use super::native;
+use super::profile::Profiler;
+use super::stream::Output;
+use super::trace::{TraceRecord, Tracer};
+use super::value::Value;
 use super::*;
 
-#[derive(Debug, Copy, Clone)]
-enum LocalVariable {
-    None,
-    Null,
-    Integer(i64),
-}
+use decode::Code;
+use ty::{ConstantIndex, FieldType};
 
-#[derive(Debug, Copy, Clone)]
-enum StackValue {
-    None,
-    Null,
-    Integer(i64),
-}
+pub(crate) use decode::Code as DecodedCode;
 
-macro_rules! stack_value_integer {
-    ($($ty:ty)*) => {
-        $(
-            impl From<$ty> for StackValue {
-                fn from(d: $ty) -> Self {
-                    StackValue::Integer(d as i64)
-                }
-            }
-        )*
+/// How many frames a thread's call stack holds by default
+const MAX_FRAMES: usize = 4_096;
+
+/// Throws an exception of the given class from the running method
+macro_rules! raise {
+    ($class:expr) => {
+        return Err(Abrupt::Raise($class, None))
     };
+    ($class:expr, $($args:expr),+ $(,)?) => {
+        return Err(Abrupt::Raise($class, Some(format!($($args),+))))
+    };
+}
+
+mod debug;
+mod decode;
+mod handles;
+mod indy;
+#[cfg(feature = "jit")]
+mod jit;
+mod profile;
+mod threads;
+
+/// The kinds of values the typed load, store and return instructions operate on
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Kind {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
 }
 
-stack_value_integer! {
-    u8 u16 u32 u64 usize
-    i8 i16 i32 i64 isize
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Int => "int",
+            Kind::Long => "long",
+            Kind::Float => "float",
+            Kind::Double => "double",
+            Kind::Reference => "reference",
+        }
+    }
+
+    fn matches(self, value: Value) -> bool {
+        matches!(
+            (self, value),
+            (Kind::Int, Value::Int(..))
+                | (Kind::Long, Value::Long(..))
+                | (Kind::Float, Value::Float(..))
+                | (Kind::Double, Value::Double(..))
+                | (Kind::Reference, Value::Null)
+                | (Kind::Reference, Value::Reference(..))
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
 struct StackFrame {
-    local_variables: Vec<LocalVariable>,
-    stack: Vec<StackValue>,
+    class: Rc<Class>,
+    method: Rc<Method>,
+    code: Rc<attr::Code>,
+    decoded: Rc<Code>,
+    local_variables: Vec<Value>,
+    stack: Vec<Value>,
+    pc: usize,
+    /// The object a `synchronized` method entered the monitor of
+    monitor: Option<heap::Reference>,
 }
 
 impl StackFrame {
-    fn create(vars: impl Into<usize>, size: impl Into<usize>) -> Self {
-        StackFrame {
-            local_variables: vec![LocalVariable::None; vars.into()],
-            stack: Vec::with_capacity(size.into()),
+    fn for_method(method: &Rc<Method>, args: Vec<Value>) -> Result<Self> {
+        let code = match method.code() {
+            Some(code) => Rc::clone(code),
+            None => generic_error!("{}.{} has no code", method.class().name, method.name),
+        };
+
+        let mut local_variables = vec![Value::Top; usize::from(code.max_locals)];
+        let mut index = 0;
+        for arg in args {
+            let wide = arg.is_wide();
+            match local_variables.get_mut(index) {
+                Some(local) => *local = arg,
+                None => return Err(Error::VariableOutOfScope),
+            }
+            index += if wide { 2 } else { 1 };
         }
+
+        Ok(StackFrame {
+            class: method.class(),
+            method: Rc::clone(method),
+            decoded: Code::of(method)?,
+            local_variables,
+            stack: Vec::with_capacity(usize::from(code.max_stack)),
+            code,
+            pc: 0,
+            monitor: None,
+        })
     }
 
-    fn for_method(method: &ty::Method, mut args: Vec<LocalVariable>) -> Self {
-        let code = method.get_code().unwrap();
-        let mut stackframe = Self::create(code.max_locals, code.max_stack);
-        for (i, arg) in args.drain(..).enumerate() {
-            stackframe.local_variables[i] = arg
+    fn trace_element(&self) -> StackTraceElement {
+        StackTraceElement {
+            class: self.class.name.clone(),
+            method: self.method.name.clone(),
+            file: self
+                .class
+                .file
+                .as_ref()
+                .and_then(|file| file.source_file())
+                .map(ToString::to_string),
+            line: self.code.line_number(self.pc),
         }
-        stackframe
     }
 
-    // fn trace() {}
-
-    fn get_variable(&self, index: usize) -> Option<&LocalVariable> {
-        self.local_variables.get(index)
+    fn get_variable(&self, index: usize) -> Result<Value> {
+        self.local_variables
+            .get(index)
+            .cloned()
+            .ok_or(Error::VariableOutOfScope)
     }
 
-    fn get_variable_mut(&mut self, index: usize) -> Option<&mut LocalVariable> {
-        self.local_variables.get_mut(index)
-    }
+    fn set_variable(&mut self, index: usize, var: Value) -> Result<()> {
+        let wide = var.is_wide();
+        if index + wide as usize >= self.local_variables.len() {
+            return Err(Error::VariableOutOfScope);
+        }
 
-    fn set_variable(&mut self, index: usize, var: impl Into<LocalVariable>) {
-        if let Some(local_variables) = self.get_variable_mut(index) {
-            *local_variables = var.into();
-            return;
+        // storing over the second half of a long or double invalidates it
+        if index > 0 && self.local_variables[index - 1].is_wide() {
+            self.local_variables[index - 1] = Value::Top;
+        }
+        self.local_variables[index] = var;
+        if wide {
+            self.local_variables[index + 1] = Value::Top;
         }
-        // TODO make a linked list for this
-        self.local_variables.insert(index, var.into());
+        Ok(())
     }
 
-    fn pop(&mut self) -> Option<StackValue> {
-        self.stack.pop()
+    fn pop(&mut self) -> Result<Value> {
+        self.stack.pop().ok_or(Error::EmptyStack)
     }
 
-    fn push(&mut self, value: impl Into<StackValue>) {
+    fn push(&mut self, value: impl Into<Value>) {
         self.stack.push(value.into())
     }
-}
 
-#[derive(Debug, Clone)]
-struct Context {
-    return_value: Option<StackValue>,
-    class: Rc<ty::ClassFile>,
+    /// Pops `count` values, in the order they were pushed
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Value>> {
+        if self.stack.len() < count {
+            return Err(Error::EmptyStack);
+        }
+        let len = self.stack.len();
+        Ok(self.stack.split_off(len - count))
+    }
 }
 
-struct InstructionIter<'a> {
+pub struct InstructionIter<'a> {
     code: Option<&'a [u8]>,
     pos: usize,
 }
 
 impl ty::Method {
-    fn instructions(&self) -> InstructionIter<'_> {
+    pub fn instructions(&self) -> InstructionIter<'_> {
         InstructionIter::from_method(self)
     }
 }
 
 impl<'a> Iterator for InstructionIter<'a> {
-    type Item = Instruction;
+    type Item = (usize, Instruction);
     fn next(&mut self) -> Option<Self::Item> {
-        let op = self.code?.get(self.pos).cloned()?;
-        self.pos += 1;
-        Instruction::lookup(op)
+        let pc = self.pos;
+        let instruction = Instruction::decode(self.code?, pc)?;
+        self.pos += instruction.size();
+        Some((pc, instruction))
     }
 }
 
 impl<'a> InstructionIter<'a> {
-    fn peek(&self, offset: usize) -> Option<&'a [u8]> {
-        self.code?.get(self.pos..self.pos + offset)
-    }
-
-    fn advance(&mut self, offset: usize) {
-        self.pos = offset;
-    }
-
-    fn goto(&mut self, abs: usize) {
-        self.pos = abs;
+    pub fn new(code: &'a [u8]) -> Self {
+        Self {
+            code: Some(code),
+            pos: 0,
+        }
     }
 
     fn from_method(method: &'a ty::Method) -> Self {
         match method.get_code() {
-            Some(s) => Self {
-                code: Some(s.code.as_slice()),
-                pos: 0,
-            },
+            Some(s) => Self::new(&s.code),
             None => Self { code: None, pos: 0 },
         }
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Interpreter {
     main_class: String,
-    class_index_map: HashMap<String, HashMap<usize, String>>,
     classes: HashMap<String, Rc<ty::ClassFile>>,
+    linked: HashMap<String, Rc<Class>>,
+    heap: Heap,
+    frames: Vec<StackFrame>,
+    stack_traces: HashMap<heap::Reference, Vec<StackTraceElement>>,
+    /// The exception thrown by the running native method
+    pending: Option<heap::Reference>,
+    interned: HashMap<String, heap::Reference>,
+    mirrors: HashMap<String, heap::Reference>,
+    mirror_names: HashMap<heap::Reference, String>,
+    method_types: HashMap<String, heap::Reference>,
+    method_type_descriptors: HashMap<heap::Reference, String>,
+    method_handles: HashMap<heap::Reference, HandleTarget>,
+    /// Resolved `MethodHandle` constants, by class and constant pool index
+    handle_constants: HashMap<(String, u16), heap::Reference>,
+    /// Linked `invokedynamic` call sites, by class and constant pool index
+    call_sites: HashMap<(String, u16), Rc<indy::CallSite>>,
+    /// References the embedder holds on to, which are garbage collection roots. They are
+    /// counted, so a reference pinned twice has to be unpinned twice
+    pinned: HashMap<heap::Reference, usize>,
+    /// How many `run_frames` loops and native methods are running. Garbage is only collected
+    /// between the instructions of the outermost loop, where every live reference is on a
+    /// frame or a root rather than in the locals of Rust code
+    nesting: usize,
+    /// Whether `System.gc` asked for a collection at the next chance
+    gc_requested: bool,
+    /// The green threads, starting with the main thread. The running one's frames are in
+    /// `frames`
+    threads: Vec<threads::Thread>,
+    current: threads::ThreadId,
+    /// The monitors that are entered, by object
+    monitors: HashMap<heap::Reference, threads::Monitor>,
+    scheduler: threads::Scheduler,
+    stdout: Output,
+    stderr: Output,
+    tracer: Option<Tracer>,
+    debugger: Option<Debugger>,
+    profiler: Option<Profiler>,
+    #[cfg(feature = "jit")]
+    jit: Option<super::jit::Jit>,
+    natives: NativeRegistry,
+    /// Whether class files are verified when they are linked
+    verify: bool,
+    /// How many frames a thread's call stack can hold before `StackOverflowError` is thrown
+    max_frames: usize,
     // class_path
 }
 
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self {
+            main_class: String::new(),
+            classes: HashMap::new(),
+            linked: HashMap::new(),
+            heap: Heap::default(),
+            frames: vec![],
+            stack_traces: HashMap::new(),
+            pending: None,
+            interned: HashMap::new(),
+            mirrors: HashMap::new(),
+            mirror_names: HashMap::new(),
+            method_types: HashMap::new(),
+            method_type_descriptors: HashMap::new(),
+            method_handles: HashMap::new(),
+            handle_constants: HashMap::new(),
+            call_sites: HashMap::new(),
+            pinned: HashMap::new(),
+            nesting: 0,
+            gc_requested: false,
+            threads: vec![threads::Thread::main()],
+            current: 0,
+            monitors: HashMap::new(),
+            scheduler: threads::Scheduler::default(),
+            stdout: Output::stdout(),
+            stderr: Output::stderr(),
+            tracer: None,
+            debugger: None,
+            profiler: None,
+            #[cfg(feature = "jit")]
+            jit: None,
+            natives: NativeRegistry::default(),
+            verify: true,
+            max_frames: MAX_FRAMES,
+        }
+    }
+}
+
 impl Interpreter {
     pub fn load_class(&mut self, class: Rc<ty::ClassFile>) {
-        let map = Self::build_class_map(&class);
         let name = class.get_class_name();
-        self.class_index_map.insert(name.to_string(), map);
         self.classes.insert(name.to_string(), Rc::clone(&class));
     }
 
@@ -164,241 +319,2629 @@ impl Interpreter {
             .map(|class| self.load_class(class))
     }
 
-    pub fn run(mut self) -> Result<()> {
+    /// Runs the main class. An exception thrown out of `main` is returned as the inner error
+    pub fn run(mut self) -> Result<std::result::Result<(), JavaException>> {
         // TODO make this work properly
         self.main_class = "hello".into();
+        let class = self.main_class.clone();
+        self.run_main(&class)
+    }
 
-        let class = &self
-            .classes
-            .get(&self.main_class)
-            .ok_or_else(|| Error::MissingMainClass)
-            .map(Rc::clone)?;
-
-        let method = class
-            .methods
-            .iter()
-            .find(|method| method.name() == "main") // TODO check arity (and args (and type))
-            .ok_or_else(|| Error::MissingEntryPoint)?;
+    /// Runs `main` of `class`, and then the threads it started. An exception thrown out of
+    /// `main` is returned as the inner error
+    pub fn run_main(&mut self, class: &str) -> Result<std::result::Result<(), JavaException>> {
+        if !self.classes.contains_key(class) {
+            return Err(Error::MissingMainClass);
+        }
 
-        if let Some(val) = self.run_method(method, Rc::clone(&class), vec![])? {
-            eprintln!(">> {:?}", val)
+        let args = self
+            .heap
+            .new_array(FieldType::Object("java/lang/String".into()), 0);
+        let result = self.invoke_static(class, "main", "([Ljava/lang/String;)V", &[args.into()]);
+        // the threads main started run on once it is done, unless the VM itself failed
+        if matches!(result, Ok(..) | Err(Error::Exception(..))) {
+            self.run_threads()?;
+        }
+        match result {
+            Err(Error::NoSuchMethod(..)) => Err(Error::MissingEntryPoint),
+            Err(Error::Exception(exception)) => Ok(Err(*exception)),
+            Err(err) => Err(err),
+            Ok(..) => Ok(Ok(())),
         }
-        Ok(())
     }
 
-    fn build_class_map(class: &ty::ClassFile) -> HashMap<usize, String> {
-        class
-            .constant_pool
-            .iter()
-            .filter_map(|k| match k {
-                ty::Constant::MethodRef(ty::constant::MethodRef { class, .. }) => Some(class),
-                _ => None,
-            })
-            .filter_map(|index| match index.lookup(&class.constant_pool) {
-                Ok(&ty::Constant::ClassRef(class)) => Some((index, class)),
-                _ => None,
-            })
-            .filter_map(|(index, name)| match name.lookup(&class.constant_pool) {
-                Ok(ty::Constant::Utf8(s)) => Some((index.0, s)),
-                _ => None,
-            })
-            .map(|(index, name)| (usize::from(index), name.to_string()))
-            .collect()
+    /// Sets where `System.out` is written to
+    pub fn set_stdout(&mut self, stdout: impl std::io::Write + 'static) {
+        self.stdout = Output::new(stdout);
     }
 
-    fn run_method(
-        &mut self,
-        method: &ty::Method,
-        class: Rc<ty::ClassFile>,
-        args: Vec<LocalVariable>,
-    ) -> Result<Option<StackValue>> {
-        let mut stack_frame = StackFrame::for_method(method, args);
-        let mut return_value: Option<StackValue> = None;
-        let mut pc = 0;
-        let mut instructions = method.instructions();
+    /// Sets where `System.err` (and uncaught stack traces) are written to
+    pub fn set_stderr(&mut self, stderr: impl std::io::Write + 'static) {
+        self.stderr = Output::new(stderr);
+    }
+
+    /// Sets where the instructions that are run are traced to, if anywhere
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    pub fn stdout(&mut self) -> &mut Output {
+        &mut self.stdout
+    }
+
+    pub fn stderr(&mut self) -> &mut Output {
+        &mut self.stderr
+    }
+
+    /// The Rust closures `native` methods are bound to when their class is linked
+    pub fn natives(&mut self) -> &mut NativeRegistry {
+        &mut self.natives
+    }
+
+    pub fn set_natives(&mut self, natives: NativeRegistry) {
+        self.natives = natives;
+    }
+
+    /// Sets whether class files are verified before they are run, which is on by default
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
 
-        let mut context = Context {
-            return_value: None,
-            class: Rc::clone(&class),
+    /// Sets how many frames a thread's call stack can hold before invoking another method
+    /// throws `StackOverflowError`
+    pub fn set_max_frames(&mut self, frames: usize) {
+        self.max_frames = frames;
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    pub fn heap_mut(&mut self) -> &mut Heap {
+        &mut self.heap
+    }
+
+    /// Links the class named `name`, and its super classes and interfaces
+    pub fn resolve_class(&mut self, name: &str) -> Result<Rc<Class>> {
+        if let Some(class) = self.linked.get(name) {
+            return Ok(Rc::clone(class));
+        }
+
+        let mut def = match self.classes.get(name) {
+            Some(file) => ClassDef::from_class_file(Rc::clone(file))?,
+            None => match native::bootstrap_class(name) {
+                Some(def) => def?,
+                None => return Err(Error::ClassNotFound(name.to_string())),
+            },
         };
 
-        while let Some(instruction) = instructions.next() {
-            match self.execute(&instruction, &mut stack_frame, &mut context)? {
-                State::Continue => {}
-                State::GotoAbsolute(offset) => {}
-                State::GotoRelative(offset) => {}
-                State::Return(val) => {}
+        let unbound = def.methods.iter_mut().filter(|method| {
+            method.flags.contains(ty::MethodFlags::NATIVE)
+                && matches!(method.body, MethodBody::None)
+        });
+        for method in unbound {
+            if let Some(func) = self
+                .natives
+                .lookup(&def.name, &method.name, &method.descriptor)
+            {
+                method.body = MethodBody::Bound(func);
             }
         }
 
-        unimplemented!()
+        let super_class = match &def.super_class {
+            Some(super_class) => Some(self.resolve_class(super_class)?),
+            None => None,
+        };
+
+        let interfaces = def
+            .interfaces
+            .iter()
+            .map(|name| self.resolve_class(name))
+            .collect::<Result<Vec<_>>>()?;
+
+        let class = Class::new(def, super_class, interfaces);
+        self.linked.insert(name.to_string(), Rc::clone(&class));
+
+        // linked first, so that the verifier can look up the class while checking it
+        if let Some(file) = self.classes.get(name).filter(|_| self.verify).cloned() {
+            if let Err(err) = verify::verify_class(&file, self) {
+                self.linked.remove(name);
+                return Err(err);
+            }
+        }
+        Ok(class)
     }
 
-    fn execute(
-        &mut self,
-        instruction: &Instruction,
-        mut stack_frame: &mut StackFrame,
-        context: &mut Context,
-    ) -> Result<State> {
-        match instruction {
-            Instruction::NOP(..) => return Ok(State::Continue),
-            Instruction::ACONST_NULL(..) => stack_frame.push(StackValue::Null),
-            //
-            Instruction::ICONST_M1(..) => stack_frame.push(-1),
-            Instruction::ICONST_0(..) => stack_frame.push(0),
-            Instruction::ICONST_1(..) => stack_frame.push(1),
-            Instruction::ICONST_2(..) => stack_frame.push(2),
-            Instruction::ICONST_3(..) => stack_frame.push(3),
-            Instruction::ICONST_4(..) => stack_frame.push(4),
-            Instruction::ICONST_5(..) => stack_frame.push(5),
-            //
-            Instruction::BIPUSH(BIPUSH(d)) => stack_frame.push(*d),
-            Instruction::SIPUSH(SIPUSH(d, ..)) => stack_frame.push(*d),
-            //
-            Instruction::ILOAD(ILOAD(offset)) => {
-                Self::exec_iload(&mut stack_frame, usize::from(*offset))?
+    /// Initializes `class` if it hasn't been already, running its `<clinit>` after its
+    /// super class has been initialized
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-5.html#jvms-5.5
+    fn initialize(&mut self, class: &Rc<Class>) -> Completion<()> {
+        match class.init_state.get() {
+            // a recursive request from `<clinit>` sees the class as it is
+            InitState::Initialized | InitState::InProgress => return Ok(()),
+            InitState::Erroneous => raise!(
+                "java/lang/NoClassDefFoundError",
+                "Could not initialize class {}",
+                class.name.replace('/', ".")
+            ),
+            InitState::Uninitialized => class.init_state.set(InitState::InProgress),
+        }
+
+        match self.run_initializer(class) {
+            Ok(()) => {
+                class.init_state.set(InitState::Initialized);
+                Ok(())
             }
-            Instruction::ILOAD_0(..) => Self::exec_iload(&mut stack_frame, 0)?,
-            Instruction::ILOAD_1(..) => Self::exec_iload(&mut stack_frame, 1)?,
-            Instruction::ILOAD_2(..) => Self::exec_iload(&mut stack_frame, 2)?,
-            Instruction::ILOAD_3(..) => Self::exec_iload(&mut stack_frame, 3)?,
-            //
-            Instruction::ISTORE(ISTORE(offset)) => {
-                Self::exec_istore(&mut stack_frame, usize::from(*offset))?
+            Err(abrupt) => {
+                class.init_state.set(InitState::Erroneous);
+                Err(abrupt)
             }
-            Instruction::ISTORE_0(..) => Self::exec_istore(&mut stack_frame, 0)?,
-            Instruction::ISTORE_1(..) => Self::exec_istore(&mut stack_frame, 1)?,
-            Instruction::ISTORE_2(..) => Self::exec_istore(&mut stack_frame, 2)?,
-            Instruction::ISTORE_3(..) => Self::exec_istore(&mut stack_frame, 3)?,
-            //
-            Instruction::IADD(..) => match (stack_frame.pop(), stack_frame.pop()) {
-                (Some(StackValue::Integer(lhs)), Some(StackValue::Integer(rhs))) => {
-                    stack_frame.push(StackValue::Integer(lhs + rhs)) // TODO check for overflow
+        }
+    }
+
+    fn run_initializer(&mut self, class: &Rc<Class>) -> Completion<()> {
+        for field in class.fields.iter().filter(|field| field.is_static()) {
+            if let Some(index) = field.constant_value {
+                let val = self.resolve_constant(class, index)?;
+                class.statics.borrow_mut()[field.slot] = val;
+            }
+        }
+
+        if !class.is_interface() {
+            if let Some(super_class) = &class.super_class {
+                self.initialize(super_class)?;
+            }
+            // only interfaces with default methods are initialized along with their implementors
+            for interface in class.all_interfaces() {
+                if interface
+                    .methods
+                    .iter()
+                    .any(|method| !method.is_static() && !method.is_abstract())
+                {
+                    self.initialize(&interface)?;
                 }
-                (Some(..), Some(..)) => return Err(Error::StackType("integer")),
-                (None, None) | (Some(..), None) => return Err(Error::EmptyStack),
-                _ => generic_error!("invalid state for IADD"), // TODO this should be a panic
-            },
-            //
-            Instruction::IINC(IINC(offset, _value)) => {
-                let offset = usize::from(*offset);
-                match stack_frame.get_variable_mut(offset) {
-                    Some(LocalVariable::Integer(val)) => *val = *val + 1,
-                    Some(..) => return Err(Error::VariableType("integer", offset)),
-                    None => return Err(Error::VariableOutOfScope),
+            }
+        }
+
+        let clinit = match class.find_method("<clinit>", "()V") {
+            Some(clinit) => clinit,
+            None => return Ok(()),
+        };
+
+        let exception = match self.run_method(&clinit, vec![]) {
+            Ok(..) => return Ok(()),
+            Err(abrupt) => self.exception_for(abrupt)?,
+        };
+
+        if self.class_of(exception)?.is_subtype_of("java/lang/Error") {
+            return Err(Abrupt::Throw(exception));
+        }
+
+        let error = self.new_exception("java/lang/ExceptionInInitializerError", None)?;
+        self.set_field(error, "cause", "Ljava/lang/Throwable;", exception.into())?;
+        Err(Abrupt::Throw(error))
+    }
+
+    /// Resolves the loadable constant at `index` in the constant pool of `class`
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-6.html#jvms-6.5.ldc
+    pub fn resolve_constant(&mut self, class: &Rc<Class>, index: ConstantIndex) -> Result<Value> {
+        let file = class.class_file()?;
+        let val = match index.lookup(&file.constant_pool)? {
+            ty::Constant::Integer(d) => Value::Int(*d as i32),
+            ty::Constant::Float(d) => Value::Float(*d),
+            ty::Constant::Long(d) => Value::Long(*d as i64),
+            ty::Constant::Double(d) => Value::Double(*d),
+            ty::Constant::StringRef(s) => self.intern(file.utf8(*s)?)?.into(),
+            ty::Constant::ClassRef(name) => {
+                let name = file.utf8(*name)?;
+                // resolving an array class resolves its element class
+                match FieldType::parse(name) {
+                    Ok(FieldType::Array(ref component)) => {
+                        let mut element = &**component;
+                        while let FieldType::Array(component) = element {
+                            element = component;
+                        }
+                        if let FieldType::Object(element) = element {
+                            self.resolve_class(element)?;
+                        }
+                    }
+                    _ => {
+                        self.resolve_class(name)?;
+                    }
                 }
+                self.class_mirror(name)?.into()
             }
-            //
-            Instruction::GOTO(GOTO(offset, _)) => {
-                return Ok(State::GotoRelative(usize::from(*offset)))
+            ty::Constant::MethodType(descriptor) => {
+                self.method_type(file.utf8(*descriptor)?)?.into()
             }
-            Instruction::RETURN(..) => return Ok(State::Return(StackValue::None)),
-            Instruction::IRETURN(..) => {
-                return match stack_frame.pop() {
-                    Some(StackValue::Integer(ret)) => Ok(State::Return(StackValue::Integer(ret))),
-                    Some(..) => Err(Error::StackType("integer")),
-                    None => Err(Error::EmptyStack),
+            ty::Constant::MethodHandleRef(handle) => {
+                let key = (class.name.clone(), index.0);
+                match self.handle_constants.get(&key) {
+                    Some(&handle) => handle.into(),
+                    None => {
+                        let handle = self.resolve_method_handle(class, *handle)?;
+                        self.handle_constants.insert(key, handle);
+                        handle.into()
+                    }
                 }
             }
-            //
-            Instruction::GETSTATIC(GETSTATIC(a, b)) => {}
-            Instruction::INVOKEVIRTUAL(INVOKEVIRTUAL(a, b)) => {}
-            //
-            Instruction::LDC(LDC(offset)) => {}
-            e => eprintln!("unhandled instruction: {}", e),
-        }
+            e => generic_error!("{:?} is not a loadable constant", e),
+        };
+        Ok(val)
+    }
 
-        Ok(State::Continue)
+    fn resolve_method_handle(
+        &mut self,
+        class: &Rc<Class>,
+        handle: ty::MethodHandle,
+    ) -> Result<heap::Reference> {
+        let file = class.class_file()?;
+        let member = file.member_ref(handle.reference())?;
+        self.find_method_handle(
+            handle.kind(),
+            member.class,
+            member.name,
+            member.descriptor,
+            member.interface,
+        )
     }
 
-    fn exec_iload(stack_frame: &mut StackFrame, offset: usize) -> Result<()> {
-        let val = match stack_frame.get_variable(offset) {
-            Some(LocalVariable::Integer(val)) => *val,
-            Some(LocalVariable::None) => {
-                generic_error!("local variable at index {} is not defined", offset);
+    /// A `java/lang/invoke/MethodHandle` for a member of `class`, which is used the way the
+    /// reference `kind` (`handle::REF_GET_FIELD` etc) says. `interface` is whether methods
+    /// are looked up in an interface
+    pub fn find_method_handle(
+        &mut self,
+        kind: u8,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        interface: bool,
+    ) -> Result<heap::Reference> {
+        use handle::*;
+        let owner = self.resolve_class(class)?;
+
+        let field = |is_static: bool| match owner.resolve_field(name, descriptor) {
+            Some(field) if field.is_static() == is_static => Ok(field),
+            Some(..) => Err(Error::IncompatibleClassChange(format!(
+                "{}.{} is {}static",
+                class,
+                name,
+                if is_static { "not " } else { "" }
+            ))),
+            None => Err(Error::NoSuchField(format!(
+                "{}.{}:{}",
+                class, name, descriptor
+            ))),
+        };
+        let method = |is_static: bool| {
+            let method = if interface {
+                owner.resolve_interface_method(name, descriptor)?
+            } else {
+                owner.resolve_method(name, descriptor)?
+            };
+            if method.is_static() != is_static {
+                return Err(Error::IncompatibleClassChange(format!(
+                    "{}.{}{} is {}static",
+                    class,
+                    name,
+                    descriptor,
+                    if is_static { "not " } else { "" }
+                )));
+            }
+            Ok(method)
+        };
+
+        let target = match kind {
+            REF_GET_FIELD => HandleTarget::GetField(field(false)?),
+            REF_GET_STATIC => HandleTarget::GetStatic(field(true)?),
+            REF_PUT_FIELD => HandleTarget::PutField(field(false)?),
+            REF_PUT_STATIC => HandleTarget::PutStatic(field(true)?),
+            REF_INVOKE_VIRTUAL => HandleTarget::InvokeVirtual(method(false)?),
+            REF_INVOKE_STATIC => HandleTarget::InvokeStatic(method(true)?),
+            REF_INVOKE_SPECIAL => HandleTarget::InvokeSpecial(method(false)?),
+            REF_NEW_INVOKE_SPECIAL if name == "<init>" => {
+                HandleTarget::NewInvokeSpecial(method(false)?)
+            }
+            REF_NEW_INVOKE_SPECIAL => {
+                return Err(Error::IncompatibleClassChange(format!(
+                    "{}.{} is not a constructor",
+                    class, name
+                )))
             }
-            Some(..) => generic_error!("local variable at index {} is not an integer", offset),
-            None => generic_error!("local variable at index {} is out of range", offset),
+            REF_INVOKE_INTERFACE => {
+                HandleTarget::InvokeInterface(owner.resolve_interface_method(name, descriptor)?)
+            }
+            kind => generic_error!("invalid reference kind {}", kind),
         };
-        stack_frame.push(StackValue::Integer(val));
-        Ok(())
+
+        let method_type = self.method_type(&target.method_descriptor())?;
+        let class = self.resolve_class("java/lang/invoke/MethodHandle")?;
+        let handle = self.heap.new_instance(class);
+        let ty = "Ljava/lang/invoke/MethodType;";
+        self.set_field(handle, "type", ty, method_type.into())?;
+        self.method_handles.insert(handle, target);
+        Ok(handle)
     }
 
-    fn exec_istore(stack_frame: &mut StackFrame, offset: usize) -> Result<()> {
-        match stack_frame.pop() {
-            Some(StackValue::Integer(val)) => {
-                stack_frame.set_variable(offset, LocalVariable::Integer(val));
-                Ok(())
+    /// The interned `java/lang/String` with the contents `s`
+    pub fn intern(&mut self, s: &str) -> Result<heap::Reference> {
+        if let Some(&string) = self.interned.get(s) {
+            return Ok(string);
+        }
+        let string = self.new_string(s)?;
+        self.interned.insert(s.to_string(), string);
+        Ok(string)
+    }
+
+    /// The `java/lang/Class` object for a type. Classes are named by their internal name
+    /// (`java/lang/String`), arrays by their descriptor (`[I`) and primitives by their
+    /// keyword (`int`, `void`)
+    pub fn class_mirror(&mut self, name: &str) -> Result<heap::Reference> {
+        if let Some(&mirror) = self.mirrors.get(name) {
+            return Ok(mirror);
+        }
+        let class = self.resolve_class("java/lang/Class")?;
+        let mirror = self.heap.new_instance(class);
+        self.mirrors.insert(name.to_string(), mirror);
+        self.mirror_names.insert(mirror, name.to_string());
+        Ok(mirror)
+    }
+
+    /// The `java/lang/Class` object for a type from a descriptor, `None` being `void`
+    pub fn type_mirror(&mut self, ty: Option<&FieldType>) -> Result<heap::Reference> {
+        let name = match ty {
+            None => "void".to_string(),
+            Some(FieldType::Object(name)) => name.clone(),
+            Some(ty) => match ty.keyword() {
+                Some(keyword) => keyword.to_string(),
+                None => ty.to_string(),
+            },
+        };
+        self.class_mirror(&name)
+    }
+
+    /// The name of the type a `java/lang/Class` object stands for, as given to `class_mirror`
+    pub fn mirror_name(&self, mirror: heap::Reference) -> Option<&str> {
+        self.mirror_names.get(&mirror).map(String::as_str)
+    }
+
+    /// The `java/lang/invoke/MethodType` for a method descriptor
+    pub fn method_type(&mut self, descriptor: &str) -> Result<heap::Reference> {
+        if let Some(&method_type) = self.method_types.get(descriptor) {
+            return Ok(method_type);
+        }
+
+        let parsed = ty::MethodDescriptor::parse(descriptor)?;
+        let rtype = self.type_mirror(parsed.ret.as_ref())?;
+        let ptypes = parsed
+            .params
+            .iter()
+            .map(|param| self.type_mirror(Some(param)).map(Value::from))
+            .collect::<Result<Vec<_>>>()?;
+
+        let class_type = FieldType::Object("java/lang/Class".into());
+        let array = self.heap.new_array(class_type, ptypes.len());
+        self.heap.array_mut(array)?.elements = ptypes;
+
+        let class = self.resolve_class("java/lang/invoke/MethodType")?;
+        let method_type = self.heap.new_instance(class);
+        self.set_field(method_type, "rtype", "Ljava/lang/Class;", rtype.into())?;
+        self.set_field(method_type, "ptypes", "[Ljava/lang/Class;", array.into())?;
+
+        self.method_types
+            .insert(descriptor.to_string(), method_type);
+        self.method_type_descriptors
+            .insert(method_type, descriptor.to_string());
+        Ok(method_type)
+    }
+
+    /// The descriptor of a `java/lang/invoke/MethodType`
+    pub fn method_type_descriptor(&self, method_type: heap::Reference) -> Option<&str> {
+        self.method_type_descriptors
+            .get(&method_type)
+            .map(String::as_str)
+    }
+
+    /// What a `java/lang/invoke/MethodHandle` refers to
+    pub fn method_handle(&self, handle: heap::Reference) -> Option<&HandleTarget> {
+        self.method_handles.get(&handle)
+    }
+
+    /// The class of the object, arrays are treated as `java/lang/Object`
+    pub(crate) fn class_of(&mut self, reference: heap::Reference) -> Result<Rc<Class>> {
+        match self.heap.get(reference) {
+            Object::Instance(instance) => Ok(Rc::clone(&instance.class)),
+            Object::Array(..) => self.resolve_class("java/lang/Object"),
+        }
+    }
+
+    /// Whether a value of class `from` can be assigned to `to`. Both are internal names,
+    /// where array classes are given as descriptors (e.g. `[Ljava/lang/String;`)
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-6.html#jvms-6.5.checkcast
+    pub(crate) fn is_assignable(&mut self, from: &str, to: &str) -> Result<bool> {
+        if from == to {
+            return Ok(true);
+        }
+
+        match (from.starts_with('['), to.starts_with('[')) {
+            (false, _) => {
+                let class = self.resolve_class(from)?;
+                Ok(class.is_subtype_of(to))
+            }
+            (true, false) => Ok(matches!(
+                to,
+                "java/lang/Object" | "java/lang/Cloneable" | "java/io/Serializable"
+            )),
+            (true, true) => {
+                let from = FieldType::parse(&from[1..])?;
+                let to = FieldType::parse(&to[1..])?;
+                match (&from, &to) {
+                    (FieldType::Object(from), FieldType::Object(to)) => {
+                        self.is_assignable(from, to)
+                    }
+                    (FieldType::Object(..), FieldType::Array(..)) => Ok(false),
+                    (FieldType::Array(..), FieldType::Object(..))
+                    | (FieldType::Array(..), FieldType::Array(..)) => {
+                        self.is_assignable(&internal_name(&from), &internal_name(&to))
+                    }
+                    _ => Ok(from == to),
+                }
             }
-            _ => Err(Error::GenericError(format!(
-                "stack value at index {} is not an integer",
-                offset
-            ))),
         }
     }
-}
 
-#[derive(Copy, Clone, Debug)]
-enum State {
-    Continue,
-    GotoAbsolute(usize),
-    GotoRelative(usize),
-    Return(StackValue),
-}
+    /// Calls the static method `name` of `class`, initializing the class first. The
+    /// arguments have to match the `descriptor`, with one value per parameter.
+    /// An exception thrown out of the method is returned as `Error::Exception`
+    pub fn invoke_static(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        args: &[Value],
+    ) -> Result<Option<Value>> {
+        let class = self.resolve_class(class)?;
+        let method = class.resolve_method(name, descriptor)?;
+        if !method.is_static() {
+            return Err(Error::IncompatibleClassChange(format!(
+                "{}.{}{} is not static",
+                class.name, name, descriptor
+            )));
+        }
+        self.check_arguments(&method, args)?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn something() {
-        let fi = std::fs::read("./etc/hello.class").unwrap();
+        // the arguments aren't on a frame yet while the class is initialized
+        let pinned = args
+            .iter()
+            .filter_map(|arg| arg.as_reference().ok().flatten())
+            .collect::<Vec<_>>();
+        pinned.iter().for_each(|&arg| self.pin(arg));
+        let result = self
+            .initialize(&method.class())
+            .and_then(|()| self.run_method(&method, args.to_vec()));
+        pinned.into_iter().for_each(|arg| self.unpin(arg));
+        match result {
+            Ok(val) => Ok(val),
+            Err(abrupt) => match self.exception_for(abrupt) {
+                Ok(exception) => Err(Error::Exception(Box::new(self.java_exception(exception)?))),
+                Err(Abrupt::Fault(err)) => Err(err),
+                Err(..) => unreachable!("only faults are passed on"),
+            },
+        }
+    }
 
-        let mut interpreter = Interpreter::default();
-        interpreter
-            .load_class_from_reader(&mut fi.as_slice())
-            .unwrap();
+    /// Calls a static method with Rust values, converting them to and from Java. The
+    /// descriptor is made from the types of the arguments and of the result
+    ///
+    /// ```ignore
+    /// let sum: i32 = vm.invoke("com/acme/Calc", "add", (1, 2))?;
+    /// ```
+    pub fn invoke<A, R>(&mut self, class: &str, name: &str, args: A) -> Result<R>
+    where
+        A: JavaArgs,
+        R: FromJava,
+    {
+        let descriptor = format!("({}){}", A::descriptor(), R::descriptor());
+        let args = args.into_java(self)?;
+        let val = self.invoke_static(class, name, &descriptor, &args)?;
+        R::from_java(self, val.unwrap_or_default())
+    }
 
-        for method in &interpreter.classes["hello"].methods {
-            for d in &method.get_code().unwrap().code {
-                let inst = Instruction::lookup(*d).unwrap();
-                eprintln!("{:02X} -> {}", d, inst);
-                eprintln!("  {}", wrap_line(inst.description(), 30));
+    /// Makes sure the arguments passed in by an embedder fit the parameters of `method`,
+    /// which the verifier would otherwise have done
+    fn check_arguments(&mut self, method: &Method, args: &[Value]) -> Result<()> {
+        let params = &method.signature.params;
+        if args.len() != params.len() {
+            return Err(Error::IllegalArgument(format!(
+                "{}{} takes {} arguments, got {}",
+                method.name,
+                method.descriptor,
+                params.len(),
+                args.len()
+            )));
+        }
+
+        for (i, (&arg, param)) in args.iter().zip(params).enumerate() {
+            let fits = match (arg, param) {
+                (Value::Int(..), FieldType::Boolean)
+                | (Value::Int(..), FieldType::Byte)
+                | (Value::Int(..), FieldType::Char)
+                | (Value::Int(..), FieldType::Short)
+                | (Value::Int(..), FieldType::Int)
+                | (Value::Long(..), FieldType::Long)
+                | (Value::Float(..), FieldType::Float)
+                | (Value::Double(..), FieldType::Double)
+                | (Value::Null, FieldType::Object(..))
+                | (Value::Null, FieldType::Array(..)) => true,
+                (Value::Reference(arg), FieldType::Object(name)) => {
+                    let class = self.heap.get(arg).class_name();
+                    self.is_assignable(&class, name)?
+                }
+                (Value::Reference(arg), param @ FieldType::Array(..)) => {
+                    let class = self.heap.get(arg).class_name();
+                    self.is_assignable(&class, &param.to_string())?
+                }
+                _ => false,
+            };
+            if !fits {
+                return Err(Error::IllegalArgument(format!(
+                    "argument {} of {}{} is not a {}: {:?}",
+                    i, method.name, method.descriptor, param, arg
+                )));
             }
         }
+        Ok(())
+    }
 
-        // interpreter.run().unwrap();
+    /// Runs `method` to completion, on top of the current call stack
+    fn run_method(&mut self, method: &Rc<Method>, args: Vec<Value>) -> Completion<Option<Value>> {
+        self.profile_call(method);
+        match &method.body {
+            MethodBody::Native(..) | MethodBody::Bound(..) => self.call_native(method, args),
+            MethodBody::Code(..) => {
+                #[cfg(feature = "jit")]
+                if let Some(val) = self.run_compiled(method, &args) {
+                    return Ok(val);
+                }
+                self.check_depth()?;
+                let monitor = self.method_monitor(method, &args)?;
+                let mut frame = StackFrame::for_method(method, args)?;
+                if let Some(object) = monitor {
+                    self.enter_monitor_in_place(object)?;
+                    frame.monitor = Some(object);
+                }
+                let base = self.frames.len();
+                self.frames.push(frame);
+                self.nesting += 1;
+                let result = self.run_frames(base);
+                self.nesting -= 1;
+                while self.frames.len() > base {
+                    self.pop_frame();
+                }
+                result
+            }
+            MethodBody::None => Err(Self::missing_body(method).into()),
+        }
     }
 
-    enum Line<'a> {
-        Single(&'a str),
-        Many(Vec<&'a str>),
+    /// Throws `StackOverflowError` if the call stack has no room for another frame
+    fn check_depth(&self) -> Completion<()> {
+        if self.frames.len() >= self.max_frames {
+            raise!("java/lang/StackOverflowError");
+        }
+        Ok(())
     }
 
-    impl<'a> std::fmt::Display for Line<'a> {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            match self {
-                Line::Single(s) => write!(f, "{}", s),
-                Line::Many(list) => {
-                    for el in list {
-                        writeln!(f, "{}", el)?;
+    fn call_native(&mut self, method: &Rc<Method>, args: Vec<Value>) -> Completion<Option<Value>> {
+        self.profile_native(method);
+        self.nesting += 1;
+        let val = match &method.body {
+            MethodBody::Native(func) => func(self, args),
+            MethodBody::Bound(func) => func(&mut Env::new(self), &args),
+            _ => unreachable!("only native methods are called"),
+        };
+        self.nesting -= 1;
+        self.profile_native_return();
+        let val = val?;
+        match self.pending.take() {
+            Some(exception) => Err(Abrupt::Throw(exception)),
+            None => Ok(val),
+        }
+    }
+
+    fn missing_body(method: &Method) -> Error {
+        let name = format!(
+            "{}.{}{}",
+            method.class().name,
+            method.name,
+            method.descriptor
+        );
+        if method.flags.contains(ty::MethodFlags::NATIVE) {
+            Error::UnsatisfiedLink(name)
+        } else {
+            Error::AbstractMethod(name)
+        }
+    }
+
+    /// Executes instructions until the frame at `base` returns, or an exception is thrown
+    /// out of it. The outermost loop runs the other threads in between, and the threads it
+    /// switches to run until they finish
+    fn run_frames(&mut self, base: usize) -> Completion<Option<Value>> {
+        let driver = self.current;
+        let result = self.drive(base, driver);
+        self.switch_to(driver);
+        result
+    }
+
+    fn drive(&mut self, base: usize, driver: threads::ThreadId) -> Completion<Option<Value>> {
+        loop {
+            if self.nesting == 1 {
+                if self.gc_requested || self.heap.should_collect() {
+                    self.collect_garbage();
+                }
+                self.schedule()?;
+            } else {
+                self.block_in_place()?;
+            }
+
+            // the other threads' stacks are theirs from the bottom
+            let base = if self.current == driver { base } else { 0 };
+            match self.step(base) {
+                Ok(None) => {}
+                Ok(Some(val)) if self.current == driver => return Ok(val),
+                Ok(Some(..)) => self.finish_thread(None)?,
+                Err(abrupt) if self.current == driver => return Err(abrupt),
+                Err(abrupt) => {
+                    let exception = self.exception_for(abrupt)?;
+                    self.finish_thread(Some(exception))?
+                }
+            }
+        }
+    }
+
+    /// Executes an instruction, and the ones that follow it straight on in the same frame
+    /// while the thread's time slice lasts. The result is returned once the frame at `base`
+    /// returns
+    fn step(&mut self, base: usize) -> Completion<Option<Option<Value>>> {
+        let frame = self.frame();
+        let (code, mut pc) = (Rc::clone(&frame.decoded), frame.pc);
+        let hooked = self.profiler.is_some() || self.tracer.is_some() || self.debugger.is_some();
+        let mut budget = if hooked { 0 } else { self.straight_budget() };
+        loop {
+            let (ip, op) = match code.op_at(pc) {
+                Some(op) => op,
+                None => generic_error!("invalid instruction at {}", pc),
+            };
+            if hooked {
+                self.hooks(pc)?;
+            }
+
+            let state = match self.run_op(&code, op) {
+                Ok(state) => state,
+                Err(abrupt) => State::Throw(self.exception_for(abrupt)?),
+            };
+            pc = match state {
+                State::Continue => code.next_pc(ip),
+                State::GotoAbsolute(target) => {
+                    #[cfg(feature = "jit")]
+                    if target <= pc {
+                        self.count_back_edge();
                     }
-                    Ok(())
+                    target
                 }
+                State::GotoRelative(offset) => (pc as isize + offset as isize) as usize,
+                state => return self.transfer(state, base),
+            };
+            self.frame().pc = pc;
+
+            if budget == 0 || !op.is_straight() {
+                return Ok(None);
             }
+            budget -= 1;
+            self.charge_instruction();
         }
     }
 
-    fn wrap_line(mut s: &str, max: usize) -> Line<'_> {
-        if s.len() <= max {
-            return Line::Single(s);
+    /// Runs the profiler, tracer and debugger before the instruction at `pc`
+    fn hooks(&mut self, pc: usize) -> Result<()> {
+        let instruction = match Instruction::decode(&self.frame().code.code, pc) {
+            Some(instruction) => instruction,
+            None => generic_error!("invalid instruction at {}", pc),
+        };
+        if self.profiler.is_some() {
+            self.profile(&instruction);
         }
-        let mut parts = vec![];
+        if self.tracer.is_some() {
+            self.trace(&instruction)?;
+        }
+        if self.debugger.is_some() {
+            self.debug(&instruction)?;
+        }
+        Ok(())
+    }
 
-        let mut s = &mut s;
-        for i in 0..(s.len() % max) - 1 {
-            let t = &s[i * s.len()..];
-            parts.push(&t[..std::cmp::min(s.len(), max)]);
-            *s = t;
+    /// Leaves the current frame for another, by returning, invoking or throwing. The result
+    /// is returned once the frame at `base` returns
+    fn transfer(&mut self, state: State, base: usize) -> Completion<Option<Option<Value>>> {
+        match state {
+            State::Continue | State::GotoAbsolute(..) | State::GotoRelative(..) => {
+                unreachable!("the frame goes on")
+            }
+            State::Return(val) => {
+                self.pop_frame();
+                if self.frames.len() == base {
+                    return Ok(Some(val));
+                }
+                self.resume(val)?;
+            }
+            State::Invoke(method, args) => match &method.body {
+                MethodBody::Native(..) | MethodBody::Bound(..) => {
+                    self.profile_call(&method);
+                    match self.call_native(&method, args) {
+                        Ok(val) => self.resume(val)?,
+                        Err(abrupt) => {
+                            let exception = self.exception_for(abrupt)?;
+                            self.unwind(exception, base)?
+                        }
+                    }
+                }
+                MethodBody::Code(..) => {
+                    if let Err(abrupt) = self.check_depth() {
+                        let exception = self.exception_for(abrupt)?;
+                        self.unwind(exception, base)?;
+                        return Ok(None);
+                    }
+                    // synchronized methods aren't compiled, so compiled calls never hold a monitor
+                    let monitor = self.method_monitor(&method, &args)?;
+                    if let Some(object) = monitor {
+                        if !self.enter_monitor(object) {
+                            // the invoke is run again once the monitor is free
+                            self.frame().stack.extend(args);
+                            return Ok(None);
+                        }
+                    }
+                    self.profile_call(&method);
+                    #[cfg(feature = "jit")]
+                    if let Some(val) = self.run_compiled(&method, &args) {
+                        self.resume(val)?;
+                        return Ok(None);
+                    }
+                    let mut frame = StackFrame::for_method(&method, args)?;
+                    frame.monitor = monitor;
+                    self.frames.push(frame)
+                }
+                MethodBody::None => {
+                    let abrupt = Self::missing_body(&method).into();
+                    let exception = self.exception_for(abrupt)?;
+                    self.unwind(exception, base)?
+                }
+            },
+            State::Block => {}
+            State::Throw(exception) => self.unwind(exception, base)?,
+        }
+        Ok(None)
+    }
+
+    /// Traces `instruction` of the current frame, if the tracer traces its method
+    fn trace(&mut self, instruction: &Instruction) -> Result<()> {
+        let frame = self.frames.last().expect("a frame must be running");
+        let (class, method) = (&frame.class, &frame.method);
+        match &self.tracer {
+            Some(tracer) if tracer.traces(&class.name, &method.name, &method.descriptor) => {}
+            _ => return Ok(()),
+        }
+
+        let (code, pc) = (&frame.code.code, frame.pc);
+        let record = TraceRecord {
+            thread: self.thread_name().to_string(),
+            class: class.name.clone(),
+            method: method.name.clone(),
+            descriptor: method.descriptor.clone(),
+            pc,
+            bytes: code[pc..pc + instruction.size()].to_vec(),
+            instruction: asm::disassemble_instruction(class.class_file()?, code, pc)?,
+            stack: frame.stack.iter().map(|&val| self.describe(val)).collect(),
+            locals: frame
+                .local_variables
+                .iter()
+                .map(|&val| self.describe(val))
+                .collect(),
+        };
+        match &mut self.tracer {
+            Some(tracer) => tracer.trace(&record),
+            None => Ok(()),
+        }
+    }
+
+    /// A value the way it is traced: references show their class, and strings their text
+    pub fn describe(&self, val: Value) -> String {
+        match val {
+            Value::Int(d) => d.to_string(),
+            Value::Long(d) => format!("{}L", d),
+            Value::Float(d) => format!("{:?}f", d),
+            Value::Double(d) => format!("{:?}d", d),
+            Value::Null => "null".to_string(),
+            Value::ReturnAddress(pc) => format!("L{}", pc),
+            Value::Top => "top".to_string(),
+            Value::Reference(reference) => {
+                let class = self.heap.get(reference).class_name();
+                let object = format!("{}@{:x}", class, reference.index());
+                match self.read_string(reference) {
+                    Ok(s) if class == "java/lang/String" => format!("{} {:?}", object, s),
+                    _ => object,
+                }
+            }
+        }
+    }
+
+    /// Pops the current frame, exiting the monitor its method is synchronized on
+    fn pop_frame(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            if let Some(object) = frame.monitor {
+                // the monitor can only be unbalanced by `monitorexit`, which has thrown then
+                let _ = self.exit_monitor(object);
+            }
+        }
+    }
+
+    /// The exception to throw for `abrupt`. Faults are passed on
+    fn exception_for(&mut self, abrupt: Abrupt) -> Completion<heap::Reference> {
+        match abrupt {
+            Abrupt::Fault(err) => Err(Abrupt::Fault(err)),
+            Abrupt::Raise(class, message) => self
+                .new_exception(class, message.as_deref())
+                .map_err(Abrupt::Fault),
+            Abrupt::Throw(exception) => Ok(exception),
+        }
+    }
+
+    /// Pops frames until one has a handler for `exception`, and continues there.
+    /// The exception is thrown out of `run_frames` if the frame at `base` has no handler
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-6.html#jvms-6.5.athrow
+    fn unwind(&mut self, exception: heap::Reference, base: usize) -> Completion<()> {
+        let class = self.class_of(exception)?;
+        loop {
+            if let Some(handler) = self.find_handler(&class)? {
+                let frame = self.frame();
+                frame.stack.clear();
+                frame.push(exception);
+                frame.pc = handler;
+                return Ok(());
+            }
+
+            self.pop_frame();
+            if self.frames.len() == base {
+                return Err(Abrupt::Throw(exception));
+            }
+        }
+    }
+
+    /// Searches the exception table of the current frame for a handler of `class`
+    fn find_handler(&mut self, class: &Rc<Class>) -> Result<Option<usize>> {
+        let frame = self.frame();
+        let (pc, code, current) = (frame.pc, Rc::clone(&frame.code), Rc::clone(&frame.class));
+
+        for row in &code.exception_table {
+            if pc < usize::from(row.start_pc) || pc >= usize::from(row.end_pc) {
+                continue;
+            }
+            if row.catch_type == ConstantIndex(0)
+                || class.is_subtype_of(current.class_file()?.class_name(row.catch_type)?)
+            {
+                return Ok(Some(usize::from(row.handler_pc)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Continues the current frame after the invoke instruction it is stopped on
+    fn resume(&mut self, val: Option<Value>) -> Result<()> {
+        let frame = self.frame();
+        match frame.decoded.op_at(frame.pc) {
+            Some((ip, _)) => frame.pc = frame.decoded.next_pc(ip),
+            None => generic_error!("invalid instruction at {}", frame.pc),
+        }
+        if let Some(val) = val {
+            frame.push(val)
+        }
+        Ok(())
+    }
+
+    /// Calls `name` on `object` from a native method, selecting the method by the object's
+    /// class. If the method throws, the exception is left pending and `None` is returned
+    pub fn call_method(
+        &mut self,
+        object: heap::Reference,
+        name: &str,
+        descriptor: &str,
+        mut args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let class = self.class_of(object)?;
+        let resolved = class.resolve_method(name, descriptor)?;
+        if resolved.is_static() {
+            return Err(Error::IncompatibleClassChange(format!(
+                "{}.{}{} is static",
+                class.name, name, descriptor
+            )));
+        }
+        let method = class.select_method(&resolved)?;
+        args.insert(0, object.into());
+        self.call(&method, args)
+    }
+
+    /// Calls the static method `name` from a native method. If the method throws, the
+    /// exception is left pending and `None` is returned
+    pub fn call_static(
+        &mut self,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let class = self.resolve_class(class)?;
+        let method = class.resolve_method(name, descriptor)?;
+        if !method.is_static() {
+            return Err(Error::IncompatibleClassChange(format!(
+                "{}.{}{} is not static",
+                class.name, name, descriptor
+            )));
+        }
+        self.call(&method, args)
+    }
+
+    /// Creates an instance of `class` from a native method, running its constructor with the
+    /// `descriptor`. If the constructor throws, the exception is left pending and `None` is
+    /// returned
+    pub fn new_object(
+        &mut self,
+        class: &str,
+        descriptor: &str,
+        mut args: Vec<Value>,
+    ) -> Result<Option<heap::Reference>> {
+        let class = self.resolve_class(class)?;
+        let constructor = match class.find_method("<init>", descriptor) {
+            Some(constructor) => constructor,
+            None => {
+                return Err(Error::NoSuchMethod(format!(
+                    "{}.<init>{}",
+                    class.name, descriptor
+                )))
+            }
+        };
+        let result = self.instantiate(&class).and_then(|object| {
+            args.insert(0, object.into());
+            self.run_method(&constructor, args).map(|_| object)
+        });
+        self.pend(result)
+    }
+
+    fn call(&mut self, method: &Rc<Method>, args: Vec<Value>) -> Result<Option<Value>> {
+        let result = self
+            .initialize(&method.class())
+            .and_then(|()| self.run_method(method, args));
+        Ok(self.pend(result)?.flatten())
+    }
+
+    /// Makes the exception of an abrupt completion pending, for a native method calling
+    /// back into java
+    fn pend<T>(&mut self, result: Completion<T>) -> Result<Option<T>> {
+        match result {
+            Ok(val) => Ok(Some(val)),
+            Err(abrupt) => match self.exception_for(abrupt) {
+                Ok(exception) => {
+                    self.throw(exception);
+                    Ok(None)
+                }
+                Err(Abrupt::Fault(err)) => Err(err),
+                Err(..) => unreachable!("only faults are passed on"),
+            },
+        }
+    }
+
+    /// Creates an instance of `class`, initializing it first
+    fn instantiate(&mut self, class: &Rc<Class>) -> Completion<heap::Reference> {
+        if class.is_interface() || class.flags.contains(ty::ClassFlags::ABSTRACT) {
+            raise!(
+                "java/lang/InstantiationError",
+                "{}",
+                class.name.replace('/', ".")
+            );
+        }
+        self.initialize(class)?;
+        Ok(self.heap.new_instance(Rc::clone(class)))
+    }
+
+    /// Keeps `reference` alive across garbage collections until it is unpinned, for
+    /// references that are held outside of the interpreter
+    pub fn pin(&mut self, reference: heap::Reference) {
+        *self.pinned.entry(reference).or_insert(0) += 1;
+    }
+
+    pub fn unpin(&mut self, reference: heap::Reference) {
+        if let Some(count) = self.pinned.get_mut(&reference) {
+            *count -= 1;
+            if *count == 0 {
+                self.pinned.remove(&reference);
+            }
+        }
+    }
+
+    /// Collects garbage now if nothing is running, or else once it is safe to
+    pub fn gc(&mut self) {
+        if self.nesting == 0 {
+            self.collect_garbage();
+        } else {
+            self.gc_requested = true;
+        }
+    }
+
+    /// Frees the objects that can't be reached from the frames, static fields, interned
+    /// strings and constants, the pending exception or pinned references
+    fn collect_garbage(&mut self) {
+        let mut roots = vec![];
+        for frame in &self.frames {
+            roots.extend(frame.local_variables.iter().chain(&frame.stack).copied());
+        }
+        for class in self.linked.values() {
+            roots.extend(class.statics.borrow().iter().copied());
+        }
+        let mut roots = roots
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::Reference(reference) => Some(reference),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        roots.extend(self.interned.values());
+        roots.extend(self.mirrors.values());
+        roots.extend(self.method_types.values());
+        roots.extend(self.handle_constants.values());
+        roots.extend(self.pinned.keys());
+        roots.extend(self.pending);
+        roots.extend(self.thread_roots());
+
+        for reference in self.heap.collect(roots) {
+            self.method_handles.remove(&reference);
+            self.stack_traces.remove(&reference);
+        }
+        self.gc_requested = false;
+    }
+
+    /// Makes room for `bytes` more on the heap before allocating them. If that would take
+    /// the heap past its maximum size, garbage is collected when it is safe to, and
+    /// `OutOfMemoryError` is thrown if there still isn't room
+    fn reserve(&mut self, bytes: usize) -> Completion<()> {
+        if self.heap.would_exceed(bytes) && self.nesting == 1 {
+            self.collect_garbage();
+        }
+        if self.heap.would_exceed(bytes) {
+            raise!("java/lang/OutOfMemoryError", "Java heap space");
+        }
+        Ok(())
+    }
+
+    /// Whether the running native method has thrown an exception
+    pub fn exception_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Takes the exception thrown by the running native method, so that it isn't thrown
+    pub fn clear_exception(&mut self) -> Option<heap::Reference> {
+        self.pending.take()
+    }
+
+    /// Throws `exception` once the running native method returns
+    pub fn throw(&mut self, exception: heap::Reference) {
+        self.pending = Some(exception);
+    }
+
+    /// Creates an exception of `class` and throws it once the running native method returns
+    pub fn throw_new(&mut self, class: &str, message: Option<&str>) -> Result<()> {
+        let exception = self.new_exception(class, message)?;
+        self.throw(exception);
+        Ok(())
+    }
+
+    /// Creates an exception with the current stack trace, without running its constructor
+    pub fn new_exception(&mut self, class: &str, message: Option<&str>) -> Result<heap::Reference> {
+        let class = self.resolve_class(class)?;
+        let exception = self.heap.new_instance(class);
+        if let Some(message) = message {
+            let message = self.new_string(message)?;
+            self.set_field(
+                exception,
+                "detailMessage",
+                "Ljava/lang/String;",
+                message.into(),
+            )?;
+        }
+        self.fill_in_stack_trace(exception);
+        Ok(exception)
+    }
+
+    /// Records the current call stack as the stack trace of `exception`, leaving out the
+    /// constructors of the exception itself
+    pub fn fill_in_stack_trace(&mut self, exception: heap::Reference) {
+        let this = Value::Reference(exception);
+        let trace = self
+            .frames
+            .iter()
+            .rev()
+            .skip_while(|frame| {
+                frame.method.name == "<init>" && frame.local_variables.first() == Some(&this)
+            })
+            .map(StackFrame::trace_element)
+            .collect();
+        self.stack_traces.insert(exception, trace);
+    }
+
+    pub fn stack_trace(&self, exception: heap::Reference) -> &[StackTraceElement] {
+        self.stack_traces
+            .get(&exception)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Describes the `Throwable` `exception`, and its causes
+    pub fn java_exception(&self, exception: heap::Reference) -> Result<JavaException> {
+        let mut seen = vec![exception];
+        self.describe_exception(exception, &mut seen)
+    }
+
+    fn describe_exception(
+        &self,
+        exception: heap::Reference,
+        seen: &mut Vec<heap::Reference>,
+    ) -> Result<JavaException> {
+        let message = match self.get_field(exception, "detailMessage", "Ljava/lang/String;")? {
+            Value::Reference(message) => Some(self.read_string(message)?),
+            _ => None,
+        };
+
+        let cause = match self.get_field(exception, "cause", "Ljava/lang/Throwable;")? {
+            Value::Reference(cause) if !seen.contains(&cause) => {
+                seen.push(cause);
+                Some(Box::new(self.describe_exception(cause, seen)?))
+            }
+            _ => None,
+        };
+
+        Ok(JavaException {
+            exception,
+            class: self.heap.get(exception).class_name(),
+            message,
+            stack_trace: self.stack_trace(exception).to_vec(),
+            cause,
+        })
+    }
+
+    /// Creates a `java/lang/String`
+    pub fn new_string(&mut self, s: &str) -> Result<heap::Reference> {
+        let class = self.resolve_class("java/lang/String")?;
+        let chars = s.encode_utf16().map(Value::from).collect::<Vec<_>>();
+        let value = self.heap.new_array(FieldType::Char, chars.len());
+        self.heap.array_mut(value)?.elements = chars;
+
+        let string = self.heap.new_instance(class);
+        self.set_field(string, "value", "[C", value.into())?;
+        Ok(string)
+    }
+
+    /// Reads a `java/lang/String`
+    pub fn read_string(&self, string: heap::Reference) -> Result<String> {
+        let value = match self.get_field(string, "value", "[C")? {
+            Value::Reference(value) => value,
+            _ => return Err(Error::StackType("char[]")),
+        };
+        let chars = self
+            .heap
+            .array(value)?
+            .elements
+            .iter()
+            .map(|c| c.as_int().map(|c| c as u16))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(String::from_utf16_lossy(&chars))
+    }
+
+    /// Reads an instance field of `object`, looking it up by name
+    pub fn get_field(
+        &self,
+        object: heap::Reference,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<Value> {
+        let instance = self.heap.instance(object)?;
+        match instance.class.resolve_field(name, descriptor) {
+            Some(field) if !field.is_static() => Ok(instance.fields[field.slot]),
+            _ => Err(Error::NoSuchField(format!(
+                "{}.{}",
+                instance.class.name, name
+            ))),
+        }
+    }
+
+    /// Writes an instance field of `object`, looking it up by name
+    pub fn set_field(
+        &mut self,
+        object: heap::Reference,
+        name: &str,
+        descriptor: &str,
+        value: Value,
+    ) -> Result<()> {
+        let instance = self.heap.instance_mut(object)?;
+        match instance.class.resolve_field(name, descriptor) {
+            Some(field) if !field.is_static() => {
+                instance.fields[field.slot] = value;
+                Ok(())
+            }
+            _ => Err(Error::NoSuchField(format!(
+                "{}.{}",
+                instance.class.name, name
+            ))),
+        }
+    }
+
+    fn frame(&mut self) -> &mut StackFrame {
+        self.frames.last_mut().expect("a frame must be running")
+    }
+
+    fn pop(&mut self) -> Result<Value> {
+        self.frame().pop()
+    }
+
+    fn push(&mut self, value: impl Into<Value>) {
+        self.frame().push(value)
+    }
+
+    fn execute(&mut self, instruction: &Instruction) -> Completion<State> {
+        macro_rules! binary {
+            ($as:ident, |$lhs:ident, $rhs:ident| $body:expr) => {{
+                let $rhs = self.pop()?.$as()?;
+                let $lhs = self.pop()?.$as()?;
+                self.push($body)
+            }};
+        }
+
+        macro_rules! unary {
+            ($as:ident, |$val:ident| $body:expr) => {{
+                let $val = self.pop()?.$as()?;
+                self.push($body)
+            }};
+        }
+
+        macro_rules! shift {
+            ($as:ident, |$lhs:ident, $rhs:ident| $body:expr) => {{
+                let $rhs = self.pop()?.as_int()? as u32;
+                let $lhs = self.pop()?.$as()?;
+                self.push($body)
+            }};
+        }
+
+        macro_rules! divide {
+            ($as:ident, $op:ident) => {{
+                let rhs = self.pop()?.$as()?;
+                let lhs = self.pop()?.$as()?;
+                if rhs == 0 {
+                    raise!("java/lang/ArithmeticException", "/ by zero");
+                }
+                self.push(lhs.$op(rhs))
+            }};
+        }
+
+        macro_rules! branch {
+            (($a:expr, $b:expr), $cond:expr) => {{
+                if $cond {
+                    return Ok(State::GotoRelative(branch_offset(*$a, *$b)));
+                }
+            }};
+        }
+
+        macro_rules! if_zero {
+            ($a:expr, $b:expr, |$val:ident| $cond:expr) => {{
+                let $val = self.pop()?.as_int()?;
+                branch!(($a, $b), $cond)
+            }};
+        }
+
+        macro_rules! if_cmp {
+            ($a:expr, $b:expr, |$lhs:ident, $rhs:ident| $cond:expr) => {{
+                let $rhs = self.pop()?.as_int()?;
+                let $lhs = self.pop()?.as_int()?;
+                branch!(($a, $b), $cond)
+            }};
+        }
+
+        match instruction {
+            Instruction::NOP(..) => return Ok(State::Continue),
+            Instruction::ACONST_NULL(..) => self.push(Value::Null),
+            //
+            Instruction::ICONST_M1(..) => self.push(-1),
+            Instruction::ICONST_0(..) => self.push(0),
+            Instruction::ICONST_1(..) => self.push(1),
+            Instruction::ICONST_2(..) => self.push(2),
+            Instruction::ICONST_3(..) => self.push(3),
+            Instruction::ICONST_4(..) => self.push(4),
+            Instruction::ICONST_5(..) => self.push(5),
+            Instruction::LCONST_0(..) => self.push(0i64),
+            Instruction::LCONST_1(..) => self.push(1i64),
+            Instruction::FCONST_0(..) => self.push(0f32),
+            Instruction::FCONST_1(..) => self.push(1f32),
+            Instruction::FCONST_2(..) => self.push(2f32),
+            Instruction::DCONST_0(..) => self.push(0f64),
+            Instruction::DCONST_1(..) => self.push(1f64),
+            //
+            Instruction::BIPUSH(BIPUSH(d)) => self.push(*d as i8),
+            Instruction::SIPUSH(SIPUSH(a, b)) => self.push(i16::from_be_bytes([*a, *b])),
+            //
+            Instruction::ILOAD(ILOAD(offset)) => self.exec_load(usize::from(*offset), Kind::Int)?,
+            Instruction::LLOAD(LLOAD(offset)) => {
+                self.exec_load(usize::from(*offset), Kind::Long)?
+            }
+            Instruction::FLOAD(FLOAD(offset)) => {
+                self.exec_load(usize::from(*offset), Kind::Float)?
+            }
+            Instruction::DLOAD(DLOAD(offset)) => {
+                self.exec_load(usize::from(*offset), Kind::Double)?
+            }
+            Instruction::ALOAD(ALOAD(offset)) => {
+                self.exec_load(usize::from(*offset), Kind::Reference)?
+            }
+            Instruction::ILOAD_0(..) => self.exec_load(0, Kind::Int)?,
+            Instruction::ILOAD_1(..) => self.exec_load(1, Kind::Int)?,
+            Instruction::ILOAD_2(..) => self.exec_load(2, Kind::Int)?,
+            Instruction::ILOAD_3(..) => self.exec_load(3, Kind::Int)?,
+            Instruction::LLOAD_0(..) => self.exec_load(0, Kind::Long)?,
+            Instruction::LLOAD_1(..) => self.exec_load(1, Kind::Long)?,
+            Instruction::LLOAD_2(..) => self.exec_load(2, Kind::Long)?,
+            Instruction::LLOAD_3(..) => self.exec_load(3, Kind::Long)?,
+            Instruction::FLOAD_0(..) => self.exec_load(0, Kind::Float)?,
+            Instruction::FLOAD_1(..) => self.exec_load(1, Kind::Float)?,
+            Instruction::FLOAD_2(..) => self.exec_load(2, Kind::Float)?,
+            Instruction::FLOAD_3(..) => self.exec_load(3, Kind::Float)?,
+            Instruction::DLOAD_0(..) => self.exec_load(0, Kind::Double)?,
+            Instruction::DLOAD_1(..) => self.exec_load(1, Kind::Double)?,
+            Instruction::DLOAD_2(..) => self.exec_load(2, Kind::Double)?,
+            Instruction::DLOAD_3(..) => self.exec_load(3, Kind::Double)?,
+            Instruction::ALOAD_0(..) => self.exec_load(0, Kind::Reference)?,
+            Instruction::ALOAD_1(..) => self.exec_load(1, Kind::Reference)?,
+            Instruction::ALOAD_2(..) => self.exec_load(2, Kind::Reference)?,
+            Instruction::ALOAD_3(..) => self.exec_load(3, Kind::Reference)?,
+            //
+            Instruction::ISTORE(ISTORE(offset)) => {
+                self.exec_store(usize::from(*offset), Kind::Int)?
+            }
+            Instruction::LSTORE(LSTORE(offset)) => {
+                self.exec_store(usize::from(*offset), Kind::Long)?
+            }
+            Instruction::FSTORE(FSTORE(offset)) => {
+                self.exec_store(usize::from(*offset), Kind::Float)?
+            }
+            Instruction::DSTORE(DSTORE(offset)) => {
+                self.exec_store(usize::from(*offset), Kind::Double)?
+            }
+            Instruction::ASTORE(ASTORE(offset)) => {
+                self.exec_store(usize::from(*offset), Kind::Reference)?
+            }
+            Instruction::ISTORE_0(..) => self.exec_store(0, Kind::Int)?,
+            Instruction::ISTORE_1(..) => self.exec_store(1, Kind::Int)?,
+            Instruction::ISTORE_2(..) => self.exec_store(2, Kind::Int)?,
+            Instruction::ISTORE_3(..) => self.exec_store(3, Kind::Int)?,
+            Instruction::LSTORE_0(..) => self.exec_store(0, Kind::Long)?,
+            Instruction::LSTORE_1(..) => self.exec_store(1, Kind::Long)?,
+            Instruction::LSTORE_2(..) => self.exec_store(2, Kind::Long)?,
+            Instruction::LSTORE_3(..) => self.exec_store(3, Kind::Long)?,
+            Instruction::FSTORE_0(..) => self.exec_store(0, Kind::Float)?,
+            Instruction::FSTORE_1(..) => self.exec_store(1, Kind::Float)?,
+            Instruction::FSTORE_2(..) => self.exec_store(2, Kind::Float)?,
+            Instruction::FSTORE_3(..) => self.exec_store(3, Kind::Float)?,
+            Instruction::DSTORE_0(..) => self.exec_store(0, Kind::Double)?,
+            Instruction::DSTORE_1(..) => self.exec_store(1, Kind::Double)?,
+            Instruction::DSTORE_2(..) => self.exec_store(2, Kind::Double)?,
+            Instruction::DSTORE_3(..) => self.exec_store(3, Kind::Double)?,
+            Instruction::ASTORE_0(..) => self.exec_store(0, Kind::Reference)?,
+            Instruction::ASTORE_1(..) => self.exec_store(1, Kind::Reference)?,
+            Instruction::ASTORE_2(..) => self.exec_store(2, Kind::Reference)?,
+            Instruction::ASTORE_3(..) => self.exec_store(3, Kind::Reference)?,
+            //
+            Instruction::IALOAD(..)
+            | Instruction::LALOAD(..)
+            | Instruction::FALOAD(..)
+            | Instruction::DALOAD(..)
+            | Instruction::AALOAD(..)
+            | Instruction::BALOAD(..)
+            | Instruction::CALOAD(..)
+            | Instruction::SALOAD(..) => self.exec_array_load()?,
+            Instruction::IASTORE(..)
+            | Instruction::LASTORE(..)
+            | Instruction::FASTORE(..)
+            | Instruction::DASTORE(..)
+            | Instruction::AASTORE(..)
+            | Instruction::BASTORE(..)
+            | Instruction::CASTORE(..)
+            | Instruction::SASTORE(..) => self.exec_array_store()?,
+            //
+            Instruction::POP(..) => {
+                self.pop()?;
+            }
+            Instruction::POP2(..) => {
+                if !self.pop()?.is_wide() {
+                    self.pop()?;
+                }
+            }
+            Instruction::DUP(..) => {
+                let val = self.pop()?;
+                self.push(val);
+                self.push(val);
+            }
+            Instruction::DUP_X1(..) => {
+                let val1 = self.pop()?;
+                let val2 = self.pop()?;
+                self.frame().stack.extend_from_slice(&[val1, val2, val1]);
+            }
+            Instruction::DUP_X2(..) => {
+                let val1 = self.pop()?;
+                let val2 = self.pop()?;
+                if val2.is_wide() {
+                    self.frame().stack.extend_from_slice(&[val1, val2, val1]);
+                } else {
+                    let val3 = self.pop()?;
+                    self.frame()
+                        .stack
+                        .extend_from_slice(&[val1, val3, val2, val1]);
+                }
+            }
+            Instruction::DUP2(..) => {
+                let val1 = self.pop()?;
+                if val1.is_wide() {
+                    self.frame().stack.extend_from_slice(&[val1, val1]);
+                } else {
+                    let val2 = self.pop()?;
+                    self.frame()
+                        .stack
+                        .extend_from_slice(&[val2, val1, val2, val1]);
+                }
+            }
+            Instruction::DUP2_X1(..) => {
+                let val1 = self.pop()?;
+                if val1.is_wide() {
+                    let val2 = self.pop()?;
+                    self.frame().stack.extend_from_slice(&[val1, val2, val1]);
+                } else {
+                    let val2 = self.pop()?;
+                    let val3 = self.pop()?;
+                    self.frame()
+                        .stack
+                        .extend_from_slice(&[val2, val1, val3, val2, val1]);
+                }
+            }
+            Instruction::DUP2_X2(..) => {
+                let val1 = self.pop()?;
+                let mut top = vec![val1];
+                if !val1.is_wide() {
+                    top.insert(0, self.pop()?);
+                }
+                let val = self.pop()?;
+                let mut under = vec![val];
+                if !val.is_wide() {
+                    under.insert(0, self.pop()?);
+                }
+                let stack = &mut self.frame().stack;
+                stack.extend_from_slice(&top);
+                stack.extend_from_slice(&under);
+                stack.extend_from_slice(&top);
+            }
+            Instruction::SWAP(..) => {
+                let val1 = self.pop()?;
+                let val2 = self.pop()?;
+                self.frame().stack.extend_from_slice(&[val1, val2]);
+            }
+            //
+            Instruction::IADD(..) => binary!(as_int, |lhs, rhs| lhs.wrapping_add(rhs)),
+            Instruction::LADD(..) => binary!(as_long, |lhs, rhs| lhs.wrapping_add(rhs)),
+            Instruction::FADD(..) => binary!(as_float, |lhs, rhs| lhs + rhs),
+            Instruction::DADD(..) => binary!(as_double, |lhs, rhs| lhs + rhs),
+            Instruction::ISUB(..) => binary!(as_int, |lhs, rhs| lhs.wrapping_sub(rhs)),
+            Instruction::LSUB(..) => binary!(as_long, |lhs, rhs| lhs.wrapping_sub(rhs)),
+            Instruction::FSUB(..) => binary!(as_float, |lhs, rhs| lhs - rhs),
+            Instruction::DSUB(..) => binary!(as_double, |lhs, rhs| lhs - rhs),
+            Instruction::IMUL(..) => binary!(as_int, |lhs, rhs| lhs.wrapping_mul(rhs)),
+            Instruction::LMUL(..) => binary!(as_long, |lhs, rhs| lhs.wrapping_mul(rhs)),
+            Instruction::FMUL(..) => binary!(as_float, |lhs, rhs| lhs * rhs),
+            Instruction::DMUL(..) => binary!(as_double, |lhs, rhs| lhs * rhs),
+            Instruction::IDIV(..) => divide!(as_int, wrapping_div),
+            Instruction::LDIV(..) => divide!(as_long, wrapping_div),
+            Instruction::FDIV(..) => binary!(as_float, |lhs, rhs| lhs / rhs),
+            Instruction::DDIV(..) => binary!(as_double, |lhs, rhs| lhs / rhs),
+            Instruction::IREM(..) => divide!(as_int, wrapping_rem),
+            Instruction::LREM(..) => divide!(as_long, wrapping_rem),
+            Instruction::FREM(..) => binary!(as_float, |lhs, rhs| lhs % rhs),
+            Instruction::DREM(..) => binary!(as_double, |lhs, rhs| lhs % rhs),
+            Instruction::INEG(..) => unary!(as_int, |val| val.wrapping_neg()),
+            Instruction::LNEG(..) => unary!(as_long, |val| val.wrapping_neg()),
+            Instruction::FNEG(..) => unary!(as_float, |val| -val),
+            Instruction::DNEG(..) => unary!(as_double, |val| -val),
+            Instruction::ISHL(..) => shift!(as_int, |lhs, rhs| lhs.wrapping_shl(rhs)),
+            Instruction::LSHL(..) => shift!(as_long, |lhs, rhs| lhs.wrapping_shl(rhs)),
+            Instruction::ISHR(..) => shift!(as_int, |lhs, rhs| lhs.wrapping_shr(rhs)),
+            Instruction::LSHR(..) => shift!(as_long, |lhs, rhs| lhs.wrapping_shr(rhs)),
+            Instruction::IUSHR(..) => {
+                shift!(as_int, |lhs, rhs| (lhs as u32).wrapping_shr(rhs) as i32)
+            }
+            Instruction::LUSHR(..) => {
+                shift!(as_long, |lhs, rhs| (lhs as u64).wrapping_shr(rhs) as i64)
+            }
+            Instruction::IAND(..) => binary!(as_int, |lhs, rhs| lhs & rhs),
+            Instruction::LAND(..) => binary!(as_long, |lhs, rhs| lhs & rhs),
+            Instruction::IOR(..) => binary!(as_int, |lhs, rhs| lhs | rhs),
+            Instruction::LOR(..) => binary!(as_long, |lhs, rhs| lhs | rhs),
+            Instruction::IXOR(..) => binary!(as_int, |lhs, rhs| lhs ^ rhs),
+            Instruction::LXOR(..) => binary!(as_long, |lhs, rhs| lhs ^ rhs),
+            //
+            Instruction::IINC(IINC(offset, value)) => {
+                self.exec_iinc(usize::from(*offset), i32::from(*value as i8))?
+            }
+            //
+            Instruction::I2L(..) => unary!(as_int, |val| i64::from(val)),
+            Instruction::I2F(..) => unary!(as_int, |val| val as f32),
+            Instruction::I2D(..) => unary!(as_int, |val| f64::from(val)),
+            Instruction::L2I(..) => unary!(as_long, |val| val as i32),
+            Instruction::L2F(..) => unary!(as_long, |val| val as f32),
+            Instruction::L2D(..) => unary!(as_long, |val| val as f64),
+            Instruction::F2I(..) => unary!(as_float, |val| val as i32),
+            Instruction::F2L(..) => unary!(as_float, |val| val as i64),
+            Instruction::F2D(..) => unary!(as_float, |val| f64::from(val)),
+            Instruction::D2I(..) => unary!(as_double, |val| val as i32),
+            Instruction::D2L(..) => unary!(as_double, |val| val as i64),
+            Instruction::D2F(..) => unary!(as_double, |val| val as f32),
+            Instruction::I2B(..) => unary!(as_int, |val| val as i8),
+            Instruction::I2C(..) => unary!(as_int, |val| val as u16),
+            Instruction::I2S(..) => unary!(as_int, |val| val as i16),
+            //
+            Instruction::LCMP(..) => binary!(as_long, |lhs, rhs| compare(lhs, rhs, 0)),
+            Instruction::FCMPL(..) => binary!(as_float, |lhs, rhs| compare(lhs, rhs, -1)),
+            Instruction::FCMPG(..) => binary!(as_float, |lhs, rhs| compare(lhs, rhs, 1)),
+            Instruction::DCMPL(..) => binary!(as_double, |lhs, rhs| compare(lhs, rhs, -1)),
+            Instruction::DCMPG(..) => binary!(as_double, |lhs, rhs| compare(lhs, rhs, 1)),
+            //
+            Instruction::IFEQ(IFEQ(a, b)) => if_zero!(a, b, |val| val == 0),
+            Instruction::IFNE(IFNE(a, b)) => if_zero!(a, b, |val| val != 0),
+            Instruction::IFLT(IFLT(a, b)) => if_zero!(a, b, |val| val < 0),
+            Instruction::IFGE(IFGE(a, b)) => if_zero!(a, b, |val| val >= 0),
+            Instruction::IFGT(IFGT(a, b)) => if_zero!(a, b, |val| val > 0),
+            Instruction::IFLE(IFLE(a, b)) => if_zero!(a, b, |val| val <= 0),
+            Instruction::IF_ICMPEQ(IF_ICMPEQ(a, b)) => if_cmp!(a, b, |lhs, rhs| lhs == rhs),
+            Instruction::IF_ICMPNE(IF_ICMPNE(a, b)) => if_cmp!(a, b, |lhs, rhs| lhs != rhs),
+            Instruction::IF_ICMPLT(IF_ICMPLT(a, b)) => if_cmp!(a, b, |lhs, rhs| lhs < rhs),
+            Instruction::IF_ICMPGE(IF_ICMPGE(a, b)) => if_cmp!(a, b, |lhs, rhs| lhs >= rhs),
+            Instruction::IF_ICMPGT(IF_ICMPGT(a, b)) => if_cmp!(a, b, |lhs, rhs| lhs > rhs),
+            Instruction::IF_ICMPLE(IF_ICMPLE(a, b)) => if_cmp!(a, b, |lhs, rhs| lhs <= rhs),
+            Instruction::IF_ACMPEQ(IF_ACMPEQ(a, b)) => {
+                let rhs = self.pop()?.as_reference()?;
+                let lhs = self.pop()?.as_reference()?;
+                branch!((a, b), lhs == rhs)
+            }
+            Instruction::IF_ACMPNE(IF_ACMPNE(a, b)) => {
+                let rhs = self.pop()?.as_reference()?;
+                let lhs = self.pop()?.as_reference()?;
+                branch!((a, b), lhs != rhs)
+            }
+            Instruction::IFNULL(IFNULL(a, b)) => {
+                let val = self.pop()?.as_reference()?;
+                branch!((a, b), val.is_none())
+            }
+            Instruction::IFNONNULL(IFNONNULL(a, b)) => {
+                let val = self.pop()?.as_reference()?;
+                branch!((a, b), val.is_some())
+            }
+            //
+            Instruction::GOTO(GOTO(a, b)) => {
+                return Ok(State::GotoRelative(branch_offset(*a, *b)));
+            }
+            Instruction::GOTO_W(GOTO_W(a, b, c, d)) => {
+                return Ok(State::GotoRelative(branch_offset_wide(*a, *b, *c, *d)));
+            }
+            Instruction::JSR(JSR(a, b)) => {
+                let frame = self.frame();
+                frame.push(Value::ReturnAddress(frame.pc + instruction.size()));
+                return Ok(State::GotoRelative(branch_offset(*a, *b)));
+            }
+            Instruction::JSR_W(JSR_W(a, b, c, d)) => {
+                let frame = self.frame();
+                frame.push(Value::ReturnAddress(frame.pc + instruction.size()));
+                return Ok(State::GotoRelative(branch_offset_wide(*a, *b, *c, *d)));
+            }
+            Instruction::RET(RET(offset)) => {
+                let address = self.frame().get_variable(usize::from(*offset))?;
+                return Ok(State::GotoAbsolute(address.as_return_address()?));
+            }
+            Instruction::TABLESWITCH(table) => {
+                let index = self.pop()?.as_int()?;
+                let (default, low, offsets) = table.table();
+                let offset = i64::from(index) - i64::from(low);
+                let offset = if offset < 0 {
+                    default
+                } else {
+                    offsets.get(offset as usize).cloned().unwrap_or(default)
+                };
+                return Ok(State::GotoRelative(offset));
+            }
+            Instruction::LOOKUPSWITCH(table) => {
+                let key = self.pop()?.as_int()?;
+                let (default, pairs) = table.pairs();
+                let offset = pairs
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, offset)| *offset)
+                    .unwrap_or(default);
+                return Ok(State::GotoRelative(offset));
+            }
+            //
+            Instruction::IRETURN(..) => return self.exec_return(Kind::Int),
+            Instruction::LRETURN(..) => return self.exec_return(Kind::Long),
+            Instruction::FRETURN(..) => return self.exec_return(Kind::Float),
+            Instruction::DRETURN(..) => return self.exec_return(Kind::Double),
+            Instruction::ARETURN(..) => return self.exec_return(Kind::Reference),
+            Instruction::RETURN(..) => return Ok(State::Return(None)),
+            //
+            Instruction::GETSTATIC(GETSTATIC(a, b)) => {
+                let field = self.resolve_field(wide_index(*a, *b), true)?;
+                self.initialize(&field.class())?;
+                let val = field.class().statics.borrow()[field.slot];
+                self.push(val)
+            }
+            Instruction::PUTSTATIC(PUTSTATIC(a, b)) => {
+                let field = self.resolve_field(wide_index(*a, *b), true)?;
+                self.initialize(&field.class())?;
+                let val = self.pop()?;
+                field.class().statics.borrow_mut()[field.slot] = val;
+            }
+            Instruction::GETFIELD(GETFIELD(a, b)) => {
+                let field = self.resolve_field(wide_index(*a, *b), false)?;
+                let this = self.pop_non_null()?;
+                let val = self.heap.instance(this)?.fields[field.slot];
+                self.push(val)
+            }
+            Instruction::PUTFIELD(PUTFIELD(a, b)) => {
+                let field = self.resolve_field(wide_index(*a, *b), false)?;
+                let val = self.pop()?;
+                let this = self.pop_non_null()?;
+                self.heap.instance_mut(this)?.fields[field.slot] = val;
+            }
+            //
+            Instruction::INVOKEVIRTUAL(INVOKEVIRTUAL(a, b)) => {
+                return self.exec_invoke(wide_index(*a, *b), Invoke::Virtual)
+            }
+            Instruction::INVOKESPECIAL(INVOKESPECIAL(a, b)) => {
+                return self.exec_invoke(wide_index(*a, *b), Invoke::Special)
+            }
+            Instruction::INVOKESTATIC(INVOKESTATIC(a, b)) => {
+                return self.exec_invoke(wide_index(*a, *b), Invoke::Static)
+            }
+            Instruction::INVOKEINTERFACE(INVOKEINTERFACE(a, b, ..)) => {
+                return self.exec_invoke(wide_index(*a, *b), Invoke::Interface)
+            }
+            Instruction::INVOKEDYNAMIC(INVOKEDYNAMIC(a, b, ..)) => {
+                return self.exec_invoke_dynamic(wide_index(*a, *b))
+            }
+            //
+            Instruction::NEW(NEW(a, b)) => {
+                let class = self.resolve_class_ref(wide_index(*a, *b))?;
+                self.reserve(heap::object_size(class.instance_field_defaults().len()))?;
+                let object = self.instantiate(&class)?;
+                self.push(object)
+            }
+            Instruction::NEWARRAY(NEWARRAY(atype)) => {
+                let component = match atype {
+                    4 => FieldType::Boolean,
+                    5 => FieldType::Char,
+                    6 => FieldType::Float,
+                    7 => FieldType::Double,
+                    8 => FieldType::Byte,
+                    9 => FieldType::Short,
+                    10 => FieldType::Int,
+                    11 => FieldType::Long,
+                    e => generic_error!("invalid array type: {}", e),
+                };
+                let len = self.pop_array_length()?;
+                self.reserve(heap::object_size(len))?;
+                let array = self.heap.new_array(component, len);
+                self.push(array)
+            }
+            Instruction::ANEWARRAY(ANEWARRAY(a, b)) => {
+                let component = self.class_ref_type(wide_index(*a, *b))?;
+                let len = self.pop_array_length()?;
+                self.reserve(heap::object_size(len))?;
+                let array = self.heap.new_array(component, len);
+                self.push(array)
+            }
+            Instruction::MULTIANEWARRAY(MULTIANEWARRAY(a, b, dimensions)) => {
+                let ty = self.class_ref_type(wide_index(*a, *b))?;
+                let mut counts = vec![];
+                for _ in 0..*dimensions {
+                    counts.push(self.pop_array_length()?);
+                }
+                counts.reverse();
+                // the arrays of each dimension, times how many of them there are
+                let (mut size, mut arrays) = (0usize, 1usize);
+                for &count in &counts {
+                    size = size.saturating_add(arrays.saturating_mul(heap::object_size(count)));
+                    arrays = arrays.saturating_mul(count);
+                }
+                self.reserve(size)?;
+                let array = self.new_multi_array(&ty, &counts)?;
+                self.push(array)
+            }
+            Instruction::ARRAYLENGTH(..) => {
+                let array = self.pop_non_null()?;
+                let len = self.heap.array(array)?.elements.len();
+                self.push(len as i32)
+            }
+            Instruction::CHECKCAST(CHECKCAST(a, b)) => {
+                let val = self.pop()?;
+                if let Some(object) = val.as_reference()? {
+                    let target = self.class_ref_name(wide_index(*a, *b))?;
+                    let class = self.heap.get(object).class_name();
+                    if !self.is_assignable(&class, &target)? {
+                        raise!(
+                            "java/lang/ClassCastException",
+                            "{} cannot be cast to {}",
+                            class.replace('/', "."),
+                            target.replace('/', "."),
+                        );
+                    }
+                }
+                self.push(val)
+            }
+            Instruction::INSTANCEOF(INSTANCEOF(a, b)) => {
+                let val = match self.pop()?.as_reference()? {
+                    Some(object) => {
+                        let target = self.class_ref_name(wide_index(*a, *b))?;
+                        let class = self.heap.get(object).class_name();
+                        self.is_assignable(&class, &target)?
+                    }
+                    None => false,
+                };
+                self.push(val)
+            }
+            //
+            Instruction::WIDE(WIDE(opcode, a, b, c, d)) => {
+                let index = usize::from(wide_index(*a, *b));
+                match *opcode {
+                    0x15 => self.exec_load(index, Kind::Int)?,
+                    0x16 => self.exec_load(index, Kind::Long)?,
+                    0x17 => self.exec_load(index, Kind::Float)?,
+                    0x18 => self.exec_load(index, Kind::Double)?,
+                    0x19 => self.exec_load(index, Kind::Reference)?,
+                    0x36 => self.exec_store(index, Kind::Int)?,
+                    0x37 => self.exec_store(index, Kind::Long)?,
+                    0x38 => self.exec_store(index, Kind::Float)?,
+                    0x39 => self.exec_store(index, Kind::Double)?,
+                    0x3A => self.exec_store(index, Kind::Reference)?,
+                    0x84 => self.exec_iinc(index, i32::from(i16::from_be_bytes([*c, *d])))?,
+                    0xA9 => {
+                        let address = self.frame().get_variable(index)?;
+                        return Ok(State::GotoAbsolute(address.as_return_address()?));
+                    }
+                    e => generic_error!("invalid wide opcode: 0x{:02X}", e),
+                }
+            }
+            //
+            Instruction::ATHROW(..) => {
+                let exception = self.pop_non_null()?;
+                return Ok(State::Throw(exception));
+            }
+            //
+            Instruction::LDC(LDC(index)) => self.load_constant(u16::from(*index))?,
+            Instruction::LDC_W(LDC_W(a, b)) => self.load_constant(wide_index(*a, *b))?,
+            Instruction::LDC2_W(LDC2_W(a, b)) => self.load_constant(wide_index(*a, *b))?,
+            Instruction::MONITORENTER(..) => {
+                let object = self.pop_non_null()?;
+                if !self.enter_monitor(object) {
+                    self.push(object);
+                    return Ok(State::Block);
+                }
+            }
+            Instruction::MONITOREXIT(..) => {
+                let object = self.pop_non_null()?;
+                self.exit_monitor(object)?;
+            }
+            instruction => generic_error!("unhandled instruction: {}", instruction),
+        }
+
+        Ok(State::Continue)
+    }
+
+    fn load_constant(&mut self, index: u16) -> Result<()> {
+        let class = Rc::clone(&self.frame().class);
+        let val = self.resolve_constant(&class, ConstantIndex(index))?;
+        self.push(val);
+        Ok(())
+    }
+
+    fn exec_load(&mut self, offset: usize, kind: Kind) -> Result<()> {
+        let frame = self.frame();
+        let val = match frame.get_variable(offset) {
+            Ok(Value::Top) => generic_error!("local variable at index {} is not defined", offset),
+            Ok(val) if kind.matches(val) => val,
+            Ok(..) => return Err(Error::VariableType(kind.name(), offset)),
+            Err(..) => generic_error!("local variable at index {} is out of range", offset),
+        };
+        frame.push(val);
+        Ok(())
+    }
+
+    fn exec_store(&mut self, offset: usize, kind: Kind) -> Result<()> {
+        let frame = self.frame();
+        match frame.pop()? {
+            // astore is also used for the return address of jsr
+            val @ Value::ReturnAddress(..) if kind == Kind::Reference => {
+                frame.set_variable(offset, val)
+            }
+            val if kind.matches(val) => frame.set_variable(offset, val),
+            _ => Err(Error::StackType(kind.name())),
+        }
+    }
+
+    fn exec_iinc(&mut self, offset: usize, value: i32) -> Result<()> {
+        match self.frame().local_variables.get_mut(offset) {
+            Some(Value::Int(val)) => *val = val.wrapping_add(value),
+            Some(..) => return Err(Error::VariableType("int", offset)),
+            None => return Err(Error::VariableOutOfScope),
+        }
+        Ok(())
+    }
+
+    fn exec_return(&mut self, kind: Kind) -> Completion<State> {
+        match self.pop()? {
+            val if kind.matches(val) => Ok(State::Return(Some(val))),
+            _ => Err(Error::StackType(kind.name()).into()),
+        }
+    }
+
+    fn exec_array_load(&mut self) -> Completion<()> {
+        let index = self.pop()?.as_int()?;
+        let array = self.pop_non_null()?;
+        let elements = &self.heap.array(array)?.elements;
+        let len = elements.len();
+        match elements.get(index as usize) {
+            Some(&val) if index >= 0 => self.push(val),
+            _ => raise!(
+                "java/lang/ArrayIndexOutOfBoundsException",
+                "Index {} out of bounds for length {}",
+                index,
+                len,
+            ),
+        }
+        Ok(())
+    }
+
+    fn exec_array_store(&mut self) -> Completion<()> {
+        let val = self.pop()?;
+        let index = self.pop()?.as_int()?;
+        let array = self.pop_non_null()?;
+
+        let component = self.heap.array(array)?.component.clone();
+        let val = match (&component, val) {
+            (FieldType::Boolean, Value::Int(d)) => Value::Int(d & 1),
+            (FieldType::Byte, Value::Int(d)) => Value::Int(i32::from(d as i8)),
+            (FieldType::Char, Value::Int(d)) => Value::Int(i32::from(d as u16)),
+            (FieldType::Short, Value::Int(d)) => Value::Int(i32::from(d as i16)),
+            (FieldType::Object(..), Value::Reference(object))
+            | (FieldType::Array(..), Value::Reference(object)) => {
+                let class = self.heap.get(object).class_name();
+                if !self.is_assignable(&class, &internal_name(&component))? {
+                    raise!(
+                        "java/lang/ArrayStoreException",
+                        "{}",
+                        class.replace('/', ".")
+                    );
+                }
+                val
+            }
+            _ => val,
+        };
+
+        let elements = &mut self.heap.array_mut(array)?.elements;
+        let len = elements.len();
+        match elements.get_mut(index as usize) {
+            Some(element) if index >= 0 => *element = val,
+            _ => raise!(
+                "java/lang/ArrayIndexOutOfBoundsException",
+                "Index {} out of bounds for length {}",
+                index,
+                len,
+            ),
+        }
+        Ok(())
+    }
+
+    fn exec_invoke(&mut self, index: u16, kind: Invoke) -> Completion<State> {
+        let current = Rc::clone(&self.frame().class);
+        let file = current.class_file()?;
+        let member = file.member_ref(ConstantIndex(index))?;
+        if kind == Invoke::Virtual && handles::is_signature_polymorphic(member.class, member.name) {
+            let exact = member.name == "invokeExact";
+            return self.exec_invoke_handle(member.descriptor, exact);
+        }
+
+        let method = self.resolve_invoke(index, kind)?;
+        self.invoke_resolved(method, kind, None)
+    }
+
+    /// The method an invoke instruction of the current class calls, before it is selected
+    /// by the class of the receiver
+    fn resolve_invoke(&mut self, index: u16, kind: Invoke) -> Result<Rc<Method>> {
+        let current = Rc::clone(&self.frame().class);
+        let file = current.class_file()?;
+        let member = file.member_ref(ConstantIndex(index))?;
+        let class = self.resolve_class(member.class)?;
+        let resolved = if member.interface {
+            class.resolve_interface_method(member.name, member.descriptor)?
+        } else {
+            class.resolve_method(member.name, member.descriptor)?
+        };
+
+        if resolved.is_static() != (kind == Invoke::Static) {
+            return Err(Error::IncompatibleClassChange(format!(
+                "{}.{}{} is {}static",
+                class.name,
+                member.name,
+                member.descriptor,
+                if resolved.is_static() { "" } else { "not " },
+            )));
+        }
+
+        // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-6.html#jvms-6.5.invokespecial
+        if kind == Invoke::Special
+            && resolved.name != "<init>"
+            && !class.is_interface()
+            && current.flags.contains(ty::ClassFlags::SUPER)
+            && current.name != class.name
+            && current.is_subtype_of(&class.name)
+        {
+            let super_class = current.super_class.as_ref().expect("a super class");
+            return super_class.resolve_method(&resolved.name, &resolved.descriptor);
+        }
+        Ok(resolved)
+    }
+
+    /// Pops the arguments of `method` and invokes it, selecting the method by the class of
+    /// the receiver for `invokevirtual` and `invokeinterface`. The method last selected is
+    /// kept in `selected`, if there is one
+    fn invoke_resolved(
+        &mut self,
+        method: Rc<Method>,
+        kind: Invoke,
+        selected: Option<&decode::Selected>,
+    ) -> Completion<State> {
+        if kind == Invoke::Static {
+            self.initialize(&method.class())?;
+        }
+
+        let args = self.frame().pop_many(method.arg_count())?;
+        if kind == Invoke::Static {
+            return Ok(State::Invoke(method, args));
+        }
+
+        let this = match args[0].as_reference()? {
+            Some(this) => this,
+            None => raise!("java/lang/NullPointerException"),
+        };
+        if kind == Invoke::Special {
+            return Ok(State::Invoke(method, args));
+        }
+
+        let class = self.class_of(this)?;
+        if let Some((last, method)) = selected.and_then(|selected| selected.borrow().clone()) {
+            if Rc::ptr_eq(&last, &class) {
+                return Ok(State::Invoke(method, args));
+            }
+        }
+        let method = match kind {
+            Invoke::Interface => class.select_interface_method(&method)?,
+            _ => class.select_method(&method)?,
+        };
+        if let Some(selected) = selected {
+            *selected.borrow_mut() = Some((class, Rc::clone(&method)));
+        }
+        Ok(State::Invoke(method, args))
+    }
+
+    fn resolve_field(&mut self, index: u16, is_static: bool) -> Result<Rc<class::Field>> {
+        let current = Rc::clone(&self.frame().class);
+        let member = current.class_file()?.member_ref(ConstantIndex(index))?;
+        let class = self.resolve_class(member.class)?;
+        let field = class
+            .resolve_field(member.name, member.descriptor)
+            .ok_or_else(|| {
+                Error::NoSuchField(format!(
+                    "{}.{}:{}",
+                    member.class, member.name, member.descriptor
+                ))
+            })?;
+
+        if field.is_static() != is_static {
+            return Err(Error::IncompatibleClassChange(format!(
+                "{}.{} is {}static",
+                member.class,
+                member.name,
+                if field.is_static() { "" } else { "not " },
+            )));
+        }
+        Ok(field)
+    }
+
+    /// The name of the `ClassRef` at `index` in the current class
+    fn class_ref_name(&mut self, index: u16) -> Result<String> {
+        let current = Rc::clone(&self.frame().class);
+        let name = current.class_file()?.class_name(ConstantIndex(index))?;
+        Ok(name.to_string())
+    }
+
+    fn resolve_class_ref(&mut self, index: u16) -> Result<Rc<Class>> {
+        let name = self.class_ref_name(index)?;
+        self.resolve_class(&name)
+    }
+
+    /// The `ClassRef` at `index` as a type, array classes are named by their descriptor
+    fn class_ref_type(&mut self, index: u16) -> Result<FieldType> {
+        let name = self.class_ref_name(index)?;
+        if name.starts_with('[') {
+            return FieldType::parse(&name).map_err(Into::into);
+        }
+        Ok(FieldType::Object(name))
+    }
+
+    fn new_multi_array(&mut self, ty: &FieldType, counts: &[usize]) -> Result<heap::Reference> {
+        let component = match ty {
+            FieldType::Array(component) => component,
+            _ => generic_error!("{} is not an array type", ty),
+        };
+
+        let array = self.heap.new_array((**component).clone(), counts[0]);
+        if counts.len() > 1 {
+            for i in 0..counts[0] {
+                let inner = self.new_multi_array(component, &counts[1..])?;
+                self.heap.array_mut(array)?.elements[i] = inner.into();
+            }
+        }
+        Ok(array)
+    }
+
+    fn pop_non_null(&mut self) -> Completion<heap::Reference> {
+        match self.pop()?.as_reference()? {
+            Some(reference) => Ok(reference),
+            None => raise!("java/lang/NullPointerException"),
+        }
+    }
+
+    fn pop_array_length(&mut self) -> Completion<usize> {
+        match self.pop()?.as_int()? {
+            len if len < 0 => raise!("java/lang/NegativeArraySizeException", "{}", len),
+            len => Ok(len as usize),
+        }
+    }
+}
+
+/// The internal name of a type, as used by `checkcast` and friends
+fn internal_name(ty: &FieldType) -> String {
+    match ty {
+        FieldType::Object(name) => name.clone(),
+        ty => ty.to_string(),
+    }
+}
+
+/// -1, 0 or 1. `nan` is used when either value is NaN
+fn compare<T: PartialOrd>(lhs: T, rhs: T, nan: i32) -> i32 {
+    match lhs.partial_cmp(&rhs) {
+        Some(std::cmp::Ordering::Less) => -1,
+        Some(std::cmp::Ordering::Equal) => 0,
+        Some(std::cmp::Ordering::Greater) => 1,
+        None => nan,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Invoke {
+    Virtual,
+    Special,
+    Static,
+    Interface,
+}
+
+#[derive(Clone, Debug)]
+enum State {
+    Continue,
+    GotoAbsolute(usize),
+    GotoRelative(i32),
+    Return(Option<Value>),
+    Invoke(Rc<Method>, Vec<Value>),
+    /// The thread has to wait, and runs the instruction again when it can go on
+    Block,
+    Throw(heap::Reference),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::stream::Capture;
+    use crate::test_utils::load_classes;
+
+    #[test]
+    fn something() {
+        let fi = std::fs::read("./etc/hello.class").unwrap();
+
+        let mut interpreter = Interpreter::default();
+        interpreter
+            .load_class_from_reader(&mut fi.as_slice())
+            .unwrap();
+
+        for method in &interpreter.classes["hello"].methods {
+            for (pc, inst) in method.instructions() {
+                eprintln!("{:04} {:02X} -> {}", pc, inst.opcode(), inst);
+                eprintln!("  {}", wrap_line(inst.description(), 30));
+            }
+        }
+
+        // interpreter.run().unwrap();
+    }
+
+    #[test]
+    fn invoke_static() {
+        let mut interpreter = load_classes(&[
+            "invoke", "Shape", "Base", "Square", "Triangle", "Circle", "Cube",
+        ]);
+        let fib = interpreter.invoke_static("invoke", "fib", "(I)I", &[Value::Int(15)]);
+        assert_eq!(fib.unwrap(), Some(Value::Int(610)));
+
+        let args = vec![Value::Long(1 << 40), Value::Int(-1), Value::Long(5)];
+        let sum = interpreter.invoke_static("invoke", "addLong", "(JIJ)J", &args);
+        assert_eq!(sum.unwrap(), Some(Value::Long((1 << 40) + 4)));
+
+        let args = vec![
+            Value::Int(1),
+            Value::Double(2.5),
+            Value::Float(0.5),
+            Value::Long(10),
+        ];
+        let mix = interpreter.invoke_static("invoke", "mix", "(IDFJ)D", &args);
+        assert_eq!(mix.unwrap(), Some(Value::Double(14.0)));
+        assert!(interpreter.frames.is_empty());
+    }
+
+    #[test]
+    fn invoke_virtual_and_interface() {
+        let mut interpreter = load_classes(&[
+            "invoke", "Shape", "Base", "Square", "Triangle", "Circle", "Cube",
+        ]);
+
+        // invokeinterface, including a default method
+        let total = interpreter.invoke_static("invoke", "shapes", "()I", &[]);
+        assert_eq!(total.unwrap(), Some(Value::Int(66)));
+
+        // invokevirtual of an abstract method, and invokespecial of super and private methods
+        let describe = interpreter.invoke_static("invoke", "describe", "()I", &[]);
+        assert_eq!(describe.unwrap(), Some(Value::Int(224 + 7)));
+
+        let cube = interpreter.resolve_class("Cube").unwrap();
+        let area = cube.resolve_method("area", "()I").unwrap();
+        assert_eq!(cube.select_method(&area).unwrap().class().name, "Cube");
+        let sides = cube.resolve_method("sides", "()I").unwrap();
+        assert_eq!(cube.select_method(&sides).unwrap().class().name, "Base");
+
+        let circle = interpreter.resolve_class("Circle").unwrap();
+        let shape = interpreter.resolve_class("Shape").unwrap();
+        let sides = shape.resolve_interface_method("sides", "()I").unwrap();
+        let selected = circle.select_interface_method(&sides).unwrap();
+        assert_eq!(selected.class().name, "Shape");
+        assert!(!selected.is_abstract());
+    }
+
+    #[test]
+    fn missing_method() {
+        let mut interpreter = load_classes(&[
+            "invoke", "Shape", "Base", "Square", "Triangle", "Circle", "Cube",
+        ]);
+        match interpreter.invoke_static("invoke", "nope", "()V", &[]) {
+            Err(Error::NoSuchMethod(name)) => assert_eq!(name, "invoke.nope()V"),
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn catch_exceptions() {
+        let mut interpreter = load_classes(&["exceptions", "Oops"]);
+        let mut call = |name| {
+            interpreter
+                .invoke_static("exceptions", name, "()I", &[])
+                .unwrap()
+        };
+
+        assert_eq!(call("caught"), Some(Value::Int(42)));
+        assert_eq!(call("subtype"), Some(Value::Int(2)));
+        assert_eq!(call("nullPointer"), Some(Value::Int(-3)));
+        assert_eq!(call("classCast"), Some(Value::Int(-4)));
+        assert_eq!(call("index"), Some(Value::Int(-5)));
+        assert_eq!(call("finallyBlock"), Some(Value::Int(52)));
+
+        let arithmetic = interpreter.invoke_static("exceptions", "arithmetic", "(I)I", &[0.into()]);
+        assert_eq!(arithmetic.unwrap(), Some(Value::Int(-1)));
+        assert!(interpreter.frames.is_empty());
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let mut interpreter = load_classes(&["exceptions", "Oops"]);
+        let overflow = |interpreter: &mut Interpreter| {
+            interpreter
+                .invoke_static("exceptions", "overflow", "()I", &[])
+                .unwrap()
+        };
+
+        let depth = MAX_FRAMES as i32 - 1;
+        assert_eq!(overflow(&mut interpreter), Some(Value::Int(depth)));
+        interpreter.set_max_frames(100);
+        assert_eq!(overflow(&mut interpreter), Some(Value::Int(99)));
+        assert!(interpreter.frames.is_empty());
+    }
+
+    #[test]
+    fn uncaught_exception() {
+        let mut interpreter = load_classes(&["exceptions", "Oops"]);
+        let exception = match interpreter.invoke_static("exceptions", "uncaught", "()V", &[]) {
+            Err(Error::Exception(exception)) => *exception,
+            e => panic!("{:?}", e),
+        };
+        assert!(interpreter.frames.is_empty());
+
+        let trace = |exception: &JavaException| {
+            exception
+                .stack_trace
+                .iter()
+                .map(|element| element.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(exception.class, "java/lang/IllegalStateException");
+        assert_eq!(
+            exception.message.as_deref(),
+            Some("java.lang.ArithmeticException: / by zero")
+        );
+        assert_eq!(
+            trace(&exception),
+            vec![
+                "exceptions.wrap(exceptions.java:94)",
+                "exceptions.uncaught(exceptions.java:99)",
+            ]
+        );
+
+        let cause = exception.cause.as_ref().unwrap();
+        assert_eq!(cause.class, "java/lang/ArithmeticException");
+        assert_eq!(
+            trace(cause),
+            vec![
+                "exceptions.divide(exceptions.java:87)",
+                "exceptions.wrap(exceptions.java:92)",
+                "exceptions.uncaught(exceptions.java:99)",
+            ]
+        );
+
+        assert!(exception.to_string().starts_with(
+            "java.lang.IllegalStateException: java.lang.ArithmeticException: / by zero\n\t\
+             at exceptions.wrap(exceptions.java:94)\n"
+        ));
+    }
+
+    #[test]
+    fn class_initialization() {
+        let classes = [
+            "clinit",
+            "Log",
+            "Parent",
+            "Child",
+            "Created",
+            "Constants",
+            "First",
+            "Second",
+            "Broken",
+        ];
+        let mut interpreter = load_classes(&classes);
+        let mut call = |name| {
+            interpreter
+                .invoke_static("clinit", name, "()I", &[])
+                .unwrap()
+        };
+
+        // super classes first, and only once
+        assert_eq!(call("order"), Some(Value::Int(123)));
+        // the recursive request for First sees its default value
+        assert_eq!(call("recursive"), Some(Value::Int(11 * 100 + 10)));
+        // wrapped the first time, and erroneous after that
+        assert_eq!(call("broken"), Some(Value::Int(11)));
+
+        let broken = interpreter.resolve_class("Broken").unwrap();
+        assert_eq!(broken.init_state.get(), InitState::Erroneous);
+    }
+
+    #[test]
+    fn garbage_collection() {
+        let mut interpreter = load_classes(&["gc", "gc$Node"]);
+        interpreter.heap_mut().set_max_size(Some(4 << 20));
+
+        // hundreds of megabytes of cycles, which only fit if they are collected
+        let sum: i32 = interpreter.invoke("gc", "garbage", (20_000,)).unwrap();
+        assert_eq!(sum, 20_000_000);
+        let stats = interpreter.heap().stats();
+        assert!(stats.collections > 1);
+        assert!(stats.objects_freed > 30_000);
+        assert!(interpreter.heap().used() < 1 << 20);
+
+        let count: i32 = interpreter.invoke("gc", "exhaust", ()).unwrap();
+        assert!((200..300).contains(&count), "{}", count);
+        assert!(!interpreter.invoke::<_, bool>("gc", "huge", ()).unwrap());
+        // and the heap is usable again
+        let sum: i32 = interpreter.invoke("gc", "garbage", (10,)).unwrap();
+        assert_eq!(sum, 10_000);
+
+        let s = interpreter.new_string("pinned").unwrap();
+        interpreter.pin(s);
+        interpreter.gc();
+        assert_eq!(interpreter.read_string(s).unwrap(), "pinned");
+        interpreter.unpin(s);
+        let len = interpreter.heap().len();
+        interpreter.gc();
+        assert!(interpreter.heap().len() < len);
+    }
+
+    #[test]
+    fn tracing() {
+        use crate::exec::trace::TraceFormat;
+
+        let mut interpreter = load_classes(&["embed"]);
+        let out = Capture::new();
+        interpreter.set_tracer(Some(Tracer::new(out.clone())));
+        let sum: i32 = interpreter.invoke("embed", "add", (1, 2)).unwrap();
+        assert_eq!(sum, 3);
+        let lines = out.contents();
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("[main] embed.add(II)I 0000 1a"));
+        assert!(lines[2].contains(" iadd "));
+        assert!(lines[2].ends_with("stack=[1, 2] locals=[1, 2]"));
+        assert!(lines[3].contains("ireturn"));
+
+        let out = Capture::new();
+        let mut tracer = Tracer::new(out.clone());
+        tracer.format(TraceFormat::JsonLines).method("greet");
+        interpreter.set_tracer(Some(tracer));
+        let s: String = interpreter.invoke("embed", "greet", ("you",)).unwrap();
+        assert_eq!(s, "hello, you");
+        interpreter
+            .invoke::<_, i32>("embed", "add", (1, 2))
+            .unwrap();
+        let lines = out.contents();
+        assert!(lines
+            .lines()
+            .all(|line| line.contains(r#""method":"greet""#)));
+        assert!(lines.starts_with(
+            r#"{"thread":"main","class":"embed","method":"greet","descriptor":"(Ljava/lang/String;)Ljava/lang/String;","pc":0,"bytes":"2a","instruction":"aload_0","stack":[],"locals":["java/lang/String@"#
+        ));
+        assert!(lines.contains(r#"\"you\""]}"#));
+    }
+
+    #[test]
+    fn constant_values() {
+        let mut interpreter = load_classes(&["Constants"]);
+        let constants = interpreter.resolve_class("Constants").unwrap();
+        assert_eq!(constants.init_state.get(), InitState::Uninitialized);
+
+        let square = interpreter.invoke_static("Constants", "square", "(I)I", &[7.into()]);
+        assert_eq!(square.unwrap(), Some(Value::Int(49)));
+        assert_eq!(constants.init_state.get(), InitState::Initialized);
+
+        let get = |name, descriptor| {
+            let field = constants.find_field(name, descriptor).unwrap();
+            constants.statics.borrow()[field.slot]
+        };
+        assert_eq!(get("ANSWER", "I"), Value::Int(42));
+        assert_eq!(get("BIG", "J"), Value::Long(1 << 40));
+        assert_eq!(get("HALF", "D"), Value::Double(0.5));
+        match get("NAME", "Ljava/lang/String;") {
+            Value::Reference(name) => {
+                assert_eq!(interpreter.read_string(name).unwrap(), "constants")
+            }
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn load_constants() {
+        let mut interpreter = load_classes(&["ldc"]);
+        let mut call = |name, descriptor| {
+            interpreter
+                .invoke_static("ldc", name, descriptor, &[])
+                .unwrap()
+                .unwrap()
+        };
+
+        assert_eq!(call("integer", "()I"), Value::Int(100_000));
+        assert_eq!(call("half", "()F"), Value::Float(0.5));
+        assert_eq!(call("big", "()J"), Value::Long(1 << 40));
+        assert_eq!(call("pi", "()D"), Value::Double(std::f64::consts::PI));
+        assert_eq!(call("interned", "()Z"), Value::from(true));
+        assert_eq!(call("isArray", "()Z"), Value::from(true));
+
+        let greeting = call("greeting", "()Ljava/lang/String;");
+        let wide = call("wide", "()Ljava/lang/String;");
+        let string = call("string", "()Ljava/lang/Class;");
+        let ints = call("ints", "()Ljava/lang/Class;");
+        let array_name = call("arrayName", "()Ljava/lang/String;");
+        let class_name = call("className", "()Ljava/lang/String;");
+
+        let read = |interpreter: &Interpreter, value: Value| {
+            interpreter.read_string(value.as_reference().unwrap().unwrap())
+        };
+        let mirror = |interpreter: &Interpreter, value: Value| {
+            let mirror = value.as_reference().unwrap().unwrap();
+            interpreter.mirror_name(mirror).map(ToString::to_string)
+        };
+
+        assert_eq!(read(&interpreter, greeting).unwrap(), "hello");
+        assert_eq!(Value::from(interpreter.intern("hello").unwrap()), greeting);
+        assert_eq!(read(&interpreter, wide).unwrap(), "s299");
+        assert_eq!(mirror(&interpreter, string).unwrap(), "java/lang/String");
+        assert_eq!(mirror(&interpreter, ints).unwrap(), "[I");
+        assert_eq!(read(&interpreter, array_name).unwrap(), "[I");
+        assert_eq!(read(&interpreter, class_name).unwrap(), "ldc");
+        assert_eq!(
+            Value::from(interpreter.class_mirror("java/lang/String").unwrap()),
+            string
+        );
+    }
+
+    #[test]
+    fn method_handle_constants() {
+        let data = std::fs::read("./etc/ldc.class").unwrap();
+        let mut file = ty::ClassFile::read(&mut data.as_slice()).unwrap();
+
+        let greeting = (1..=file.constant_pool.len() as u16)
+            .map(ConstantIndex)
+            .find(|&index| match file.member_ref(index) {
+                Ok(member) => member.name == "greeting",
+                Err(..) => false,
+            })
+            .unwrap();
+
+        let pool = &mut file.constant_pool;
+        pool.push(ty::Constant::Utf8("(ILjava/lang/String;)[J".into()));
+        let descriptor = ConstantIndex(pool.len() as u16);
+        pool.push(ty::Constant::MethodType(descriptor));
+        let method_type = ConstantIndex(pool.len() as u16);
+        pool.push(ty::Constant::MethodHandleRef(
+            ty::MethodHandle::InvokeStatic(greeting),
+        ));
+        let method_handle = ConstantIndex(pool.len() as u16);
+
+        let mut interpreter = Interpreter::default();
+        interpreter.load_class(Rc::new(file));
+        let class = interpreter.resolve_class("ldc").unwrap();
+
+        let resolved = interpreter.resolve_constant(&class, method_type).unwrap();
+        let resolved = resolved.as_reference().unwrap().unwrap();
+        assert_eq!(
+            interpreter.method_type_descriptor(resolved),
+            Some("(ILjava/lang/String;)[J")
+        );
+        let again = interpreter.resolve_constant(&class, method_type).unwrap();
+        assert_eq!(again, Value::from(resolved));
+
+        let handle = interpreter.resolve_constant(&class, method_handle).unwrap();
+        let handle = handle.as_reference().unwrap().unwrap();
+        match interpreter.method_handle(handle) {
+            Some(HandleTarget::InvokeStatic(method)) => assert_eq!(method.name, "greeting"),
+            e => panic!("{:?}", e),
+        }
+        let ty = interpreter
+            .get_field(handle, "type", "Ljava/lang/invoke/MethodType;")
+            .unwrap();
+        let ty = ty.as_reference().unwrap().unwrap();
+        assert_eq!(
+            interpreter.method_type_descriptor(ty),
+            Some("()Ljava/lang/String;")
+        );
+    }
+
+    #[test]
+    fn native_library() {
+        let mut interpreter = load_classes(&["natives", "Pair"]);
+        let (stdout, stderr) = (Capture::new(), Capture::new());
+        interpreter.set_stdout(stdout.clone());
+        interpreter.set_stderr(stderr.clone());
+
+        let mut call = |name, descriptor| {
+            interpreter
+                .invoke_static("natives", name, descriptor, &[])
+                .unwrap()
+        };
+
+        call("strings", "()V");
+        assert_eq!(
+            stdout.contents(),
+            "x=42,1.5truenull\n12 o 7 WORLD\ntrue -1 96354 trim me|\ncba!\n"
+        );
+        stdout.clear();
+
+        call("math", "()V");
+        assert_eq!(
+            stdout.contents(),
+            "7 2.5 4.0 3 -2 2 -0.0 0.3333333333333333 1.0E10 100.0\n"
+        );
+        stdout.clear();
+
+        call("boxing", "()V");
+        assert_eq!(
+            stdout.contents(),
+            "true false true -123 ffffffff\n0.1 1099511627776 true q 1005 2500.0\n"
+        );
+        stdout.clear();
+
+        assert_eq!(call("badNumber", "()I"), Some(Value::Int(-1)));
+        assert_eq!(stderr.contents(), "For input string: \"12x\"\n");
+
+        call("arrays", "()V");
+        assert_eq!(
+            stdout.contents(),
+            "2131420304\nstored a null\nout of bounds\n"
+        );
+        stdout.clear();
+
+        call("objects", "()V");
+        assert_eq!(stdout.contents(), "true (1, one)\n");
+    }
+
+    #[test]
+    fn hello_world() {
+        let mut interpreter = load_classes(&["hello"]);
+        let stdout = Capture::new();
+        interpreter.set_stdout(stdout.clone());
+        interpreter.run().unwrap().unwrap();
+        assert_eq!(stdout.contents(), "hello world!\n");
+    }
+
+    enum Line<'a> {
+        Single(&'a str),
+        Many(Vec<&'a str>),
+    }
+
+    impl<'a> std::fmt::Display for Line<'a> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Line::Single(s) => write!(f, "{}", s),
+                Line::Many(list) => {
+                    for el in list {
+                        writeln!(f, "{}", el)?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    fn wrap_line(s: &str, max: usize) -> Line<'_> {
+        if s.len() <= max {
+            return Line::Single(s);
+        }
+
+        let mut parts = vec![];
+        let mut rest = s;
+        while !rest.is_empty() {
+            let mut end = std::cmp::min(rest.len(), max);
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            let (head, tail) = rest.split_at(end);
+            parts.push(head);
+            rest = tail;
         }
 
         Line::Many(parts)
@@ -0,0 +1,465 @@
+//! The interpreter's side of `exec::debug`: stopping before instructions, and looking into
+//! the frames of the running thread
+use super::*;
+
+use crate::exec::debug::{
+    matches_method, Breakpoint, Debugger, FrameInfo, Local, Resume, Step, Stepping, Stop, Unit,
+};
+
+use std::sync::atomic::Ordering;
+
+impl Interpreter {
+    /// Sets the debugger that execution stops for, if any
+    pub fn set_debugger(&mut self, debugger: Option<Debugger>) {
+        self.debugger = debugger;
+    }
+
+    pub fn debugger(&mut self) -> Option<&mut Debugger> {
+        self.debugger.as_mut()
+    }
+
+    /// Stops before `instruction` if a step ends there or it hits a breakpoint, and goes on
+    /// the way the handler says
+    pub(super) fn debug(&mut self, instruction: &Instruction) -> Result<()> {
+        let stop = match self.debug_stop(instruction)? {
+            Some(stop) => stop,
+            None => return Ok(()),
+        };
+        let mut handler = match self.debugger.as_mut().and_then(|d| d.handler.take()) {
+            Some(handler) => handler,
+            None => return Ok(()),
+        };
+        let resume = handler(self, &stop);
+        // unless the handler took the debugger away
+        if let Some(debugger) = &mut self.debugger {
+            debugger.handler = Some(handler);
+        }
+
+        let stepping = match resume? {
+            Resume::Continue if stop == Stop::Pause => self
+                .debugger
+                .as_ref()
+                .and_then(|debugger| debugger.stepping),
+            Resume::Continue => None,
+            Resume::Step(step) => {
+                let frame = self.frames.last().expect("a frame must be running");
+                Some(Stepping {
+                    step,
+                    origin: Some((self.current, self.frames.len())),
+                    line: frame.code.line_number(frame.pc),
+                })
+            }
+            Resume::Quit => generic_error!("stopped by the debugger"),
+        };
+        if let Some(debugger) = &mut self.debugger {
+            debugger.stepping = stepping;
+        }
+        Ok(())
+    }
+
+    /// Why execution stops before `instruction`, if it does
+    fn debug_stop(&self, instruction: &Instruction) -> Result<Option<Stop>> {
+        let debugger = match &self.debugger {
+            Some(debugger) => debugger,
+            None => return Ok(None),
+        };
+        // any stop gets the handler called, so the pause is only a stop of its own if there
+        // is no other
+        let paused = debugger.pause.swap(false, Ordering::SeqCst);
+        let frame = self.frames.last().expect("a frame must be running");
+        let (depth, line) = (self.frames.len(), frame.code.line_number(frame.pc));
+        let starts_line = starts_line(&frame.code, frame.pc);
+
+        if let Some(stepping) = &debugger.stepping {
+            let done = match stepping.origin {
+                None => true,
+                Some((thread, _)) if thread != self.current => false,
+                Some((_, origin)) => {
+                    // without line numbers, lines are single instructions
+                    let next_line =
+                        stepping.line.is_none() || (starts_line && line != stepping.line);
+                    match stepping.step {
+                        Step::Into(Unit::Instruction) => true,
+                        Step::Into(Unit::Line) => depth != origin || next_line,
+                        Step::Over(Unit::Instruction) => depth <= origin,
+                        Step::Over(Unit::Line) => depth < origin || (depth == origin && next_line),
+                        Step::Out => depth < origin,
+                    }
+                }
+            };
+            if done {
+                return Ok(Some(Stop::Step));
+            }
+        }
+
+        let (class, method) = (&frame.class.name, &frame.method);
+        for (id, breakpoint) in debugger.breakpoints() {
+            let stop = match breakpoint {
+                Breakpoint::Method {
+                    class: c,
+                    method: m,
+                    pc,
+                } => {
+                    let hit = c == class
+                        && matches_method(m, &method.name, &method.descriptor)
+                        && frame.pc == pc.unwrap_or(0);
+                    Some(Stop::Breakpoint(id)).filter(|_| hit)
+                }
+                Breakpoint::Line { class: c, line: l } => {
+                    let hit = c == class && line == Some(*l) && starts_line;
+                    Some(Stop::Breakpoint(id)).filter(|_| hit)
+                }
+                Breakpoint::Field {
+                    class: c,
+                    field,
+                    reads,
+                } => self.field_access(id, instruction, c, field, *reads)?,
+            };
+            if stop.is_some() {
+                return Ok(stop);
+            }
+        }
+        Ok(Some(Stop::Pause).filter(|_| paused))
+    }
+
+    /// Whether `instruction` accesses the watched `field` of `class`, and how
+    fn field_access(
+        &self,
+        id: usize,
+        instruction: &Instruction,
+        class: &str,
+        field: &str,
+        reads: bool,
+    ) -> Result<Option<Stop>> {
+        let (index, write, instance) = match instruction {
+            Instruction::GETSTATIC(GETSTATIC(a, b)) => (wide_index(*a, *b), false, false),
+            Instruction::PUTSTATIC(PUTSTATIC(a, b)) => (wide_index(*a, *b), true, false),
+            Instruction::GETFIELD(GETFIELD(a, b)) => (wide_index(*a, *b), false, true),
+            Instruction::PUTFIELD(PUTFIELD(a, b)) => (wide_index(*a, *b), true, true),
+            _ => return Ok(None),
+        };
+        if !write && !reads {
+            return Ok(None);
+        }
+
+        let frame = self.frames.last().expect("a frame must be running");
+        let member = frame.class.class_file()?.member_ref(ConstantIndex(index))?;
+        // the field can be named through a subclass
+        let declared = member.class == class
+            || self
+                .linked
+                .get(member.class)
+                .is_some_and(|linked| linked.is_subtype_of(class));
+        if member.name != field || !declared {
+            return Ok(None);
+        }
+
+        let stack = &frame.stack;
+        let top = |n: usize| stack.len().checked_sub(n + 1).map(|i| stack[i]);
+        let (object, value) = match (instance, write) {
+            (false, false) => (None, None),
+            (false, true) => (None, top(0)),
+            (true, false) => (top(0), None),
+            (true, true) => (top(1), top(0)),
+        };
+        let object = object.and_then(|object| object.as_reference().ok().flatten());
+        Ok(Some(Stop::Field { id, object, value }))
+    }
+
+    /// The classes that have been loaded or linked, by internal name
+    pub fn class_names(&self) -> Vec<String> {
+        let names = self.classes.keys().chain(self.linked.keys());
+        let mut names = names.cloned().collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn frame_at(&self, thread: usize, depth: usize) -> Option<&StackFrame> {
+        self.thread_frames(thread)?.iter().rev().nth(depth)
+    }
+
+    /// The frames of a thread, innermost first. The running thread is `running_thread`
+    pub fn call_stack(&self, thread: usize) -> Vec<FrameInfo> {
+        let frames = self.thread_frames(thread).unwrap_or_default();
+        let name = self
+            .thread_list()
+            .into_iter()
+            .find(|info| info.id == thread);
+        let name = name.map(|info| info.name).unwrap_or_default();
+        frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let element = frame.trace_element();
+                let instruction = frame
+                    .class
+                    .class_file()
+                    .and_then(|file| asm::disassemble_instruction(file, &frame.code.code, frame.pc))
+                    .unwrap_or_else(|err| format!("<{}>", err));
+                FrameInfo {
+                    thread: name.clone(),
+                    class: element.class,
+                    method: element.method,
+                    descriptor: frame.method.descriptor.clone(),
+                    pc: frame.pc,
+                    file: element.file,
+                    line: element.line,
+                    instruction,
+                }
+            })
+            .collect()
+    }
+
+    /// The local variables of the frame `depth` frames down the call stack of a thread,
+    /// named where the `LocalVariableTable` says what is in them
+    pub fn locals(&self, thread: usize, depth: usize) -> Option<Vec<Local>> {
+        let frame = self.frame_at(thread, depth)?;
+        let file = frame.class.class_file().ok()?;
+        let variables = frame
+            .code
+            .attributes
+            .iter()
+            .filter_map(|attribute| match attribute {
+                attr::Attribute::LocalVariableTable(table) => Some(&table.variables),
+                _ => None,
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+
+        let locals = frame
+            .local_variables
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| {
+                let variable = variables.iter().find(|variable| {
+                    let start = usize::from(variable.start_pc);
+                    usize::from(variable.index) == index
+                        && (start..start + usize::from(variable.length)).contains(&frame.pc)
+                });
+                let utf8 = |index| file.utf8(index).ok().map(ToString::to_string);
+                Local {
+                    index,
+                    name: variable.and_then(|variable| utf8(variable.name)),
+                    descriptor: variable.and_then(|variable| utf8(variable.descriptor)),
+                    value,
+                }
+            });
+        Some(locals.collect())
+    }
+
+    /// The operand stack of the frame `depth` frames down the call stack of a thread, from
+    /// the bottom
+    pub fn operand_stack(&self, thread: usize, depth: usize) -> Option<&[Value]> {
+        self.frame_at(thread, depth)
+            .map(|frame| frame.stack.as_slice())
+    }
+
+    /// The fields of an instance and their values, starting with the inherited ones, or
+    /// the elements of an array
+    pub fn object_fields(&self, reference: heap::Reference) -> Result<Vec<(String, Value)>> {
+        let instance = match self.heap.get(reference) {
+            Object::Array(array) => {
+                let elements = array.elements.iter().enumerate();
+                return Ok(elements
+                    .map(|(i, &val)| (format!("[{}]", i), val))
+                    .collect());
+            }
+            Object::Instance(instance) => instance,
+        };
+        let mut classes = vec![];
+        let mut class = Some(&instance.class);
+        while let Some(c) = class {
+            classes.push(c);
+            class = c.super_class.as_ref();
+        }
+        let fields = classes
+            .into_iter()
+            .rev()
+            .flat_map(|class| &class.fields)
+            .filter(|field| !field.is_static())
+            .map(|field| (field.name.clone(), instance.fields[field.slot]));
+        Ok(fields.collect())
+    }
+}
+
+/// Whether a source line starts at `pc`
+fn starts_line(code: &attr::Code, pc: usize) -> bool {
+    code.attributes
+        .iter()
+        .filter_map(|attribute| match attribute {
+            attr::Attribute::LineNumberTable(table) => Some(&table.table),
+            _ => None,
+        })
+        .flatten()
+        .any(|(start_pc, _)| usize::from(*start_pc) == pc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::load_classes;
+    use std::cell::RefCell;
+
+    fn load() -> Interpreter {
+        load_classes(&["debug", "debug$Point"])
+    }
+
+    /// Runs `debug.run` with the debugger stopping, and the handler going on with the
+    /// next of `resumes` each time. Returns where it stopped
+    fn stops(
+        breakpoints: Vec<Breakpoint>,
+        resumes: Vec<Resume>,
+    ) -> Vec<(Stop, Vec<FrameInfo>, Vec<Local>)> {
+        let seen = Rc::new(RefCell::new(vec![]));
+        let mut resumes = resumes.into_iter();
+        let mut debugger = Debugger::new({
+            let seen = Rc::clone(&seen);
+            move |vm, stop| {
+                let thread = vm.running_thread();
+                let locals = vm.locals(thread, 0).unwrap();
+                seen.borrow_mut()
+                    .push((stop.clone(), vm.call_stack(thread), locals));
+                Ok(resumes.next().unwrap_or(Resume::Continue))
+            }
+        });
+        for breakpoint in breakpoints {
+            debugger.add_breakpoint(breakpoint);
+        }
+
+        let mut vm = load();
+        vm.set_debugger(Some(debugger));
+        assert_eq!(vm.invoke::<_, i32>("debug", "run", ()).unwrap(), 7);
+        let seen = seen.borrow().clone();
+        seen
+    }
+
+    #[test]
+    fn breakpoints() {
+        let seen = stops(
+            vec![
+                Breakpoint::Method {
+                    class: "debug$Point".into(),
+                    method: "sum".into(),
+                    pc: None,
+                },
+                Breakpoint::Line {
+                    class: "debug".into(),
+                    line: 20,
+                },
+            ],
+            vec![Resume::Continue, Resume::Continue],
+        );
+        // the entry, then the breakpoints in the order they are hit
+        let stops = seen
+            .iter()
+            .map(|(stop, ..)| stop.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stops,
+            [Stop::Step, Stop::Breakpoint(1), Stop::Breakpoint(2)]
+        );
+
+        let (_, stack, locals) = &seen[1];
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack[0].method, "sum");
+        assert_eq!(stack[0].pc, 0);
+        assert_eq!(stack[1].line, Some(18));
+        assert_eq!(locals[0].name.as_deref(), Some("this"));
+
+        let (_, stack, locals) = &seen[2];
+        assert_eq!(stack[0].line, Some(20));
+        let sum = locals.iter().find(|l| l.name.as_deref() == Some("sum"));
+        assert_eq!(sum.map(|l| l.value), Some(Value::Int(7)));
+    }
+
+    #[test]
+    fn stepping() {
+        let into = Resume::Step(Step::Into(Unit::Line));
+        let seen = stops(vec![], vec![into; 5]);
+        let lines = seen
+            .iter()
+            .map(|(_, stack, _)| (stack[0].method.as_str(), stack[0].line.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                ("run", 17),
+                ("<init>", 6),
+                ("<init>", 7),
+                ("<init>", 8),
+                ("<init>", 9),
+                ("run", 17)
+            ]
+        );
+
+        let over = Resume::Step(Step::Over(Unit::Line));
+        let seen = stops(vec![], vec![over; 3]);
+        let lines = seen.iter().map(|(_, stack, _)| stack[0].line.unwrap());
+        assert_eq!(lines.collect::<Vec<_>>(), [17, 18, 19, 20]);
+
+        let seen = stops(
+            vec![Breakpoint::Method {
+                class: "debug$Point".into(),
+                method: "sum()I".into(),
+                pc: None,
+            }],
+            vec![Resume::Continue, Resume::Step(Step::Out)],
+        );
+        let (_, stack, _) = &seen[2];
+        assert_eq!((stack[0].method.as_str(), stack[0].line), ("run", Some(18)));
+        assert_eq!(stack.len(), 1);
+
+        let instruction = Resume::Step(Step::Over(Unit::Instruction));
+        let seen = stops(vec![], vec![instruction; 2]);
+        let pcs = seen.iter().map(|(_, stack, _)| stack[0].pc);
+        assert_eq!(pcs.collect::<Vec<_>>(), [0, 3, 4]);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let seen = stops(
+            vec![Breakpoint::Field {
+                class: "debug$Point".into(),
+                field: "x".into(),
+                reads: false,
+            }],
+            vec![],
+        );
+        let fields = seen
+            .iter()
+            .filter_map(|(stop, stack, _)| match stop {
+                Stop::Field { id, value, .. } => Some((*id, *value, stack[0].line)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(fields, [(1, Some(Value::Int(3)), Some(7))]);
+
+        let seen = stops(
+            vec![Breakpoint::Field {
+                class: "debug$Point".into(),
+                field: "x".into(),
+                reads: true,
+            }],
+            vec![],
+        );
+        assert_eq!(seen.len(), 3);
+    }
+
+    #[test]
+    fn objects() {
+        let mut vm = load();
+        let object = vm
+            .new_object("debug$Point", "(II)V", vec![Value::Int(1), Value::Int(2)])
+            .unwrap()
+            .unwrap();
+        let fields = vm.object_fields(object).unwrap();
+        assert_eq!(
+            fields,
+            [
+                ("x".to_string(), Value::Int(1)),
+                ("y".to_string(), Value::Int(2))
+            ]
+        );
+    }
+}
@@ -0,0 +1,630 @@
+//! Methods decoded once into the ops the interpreter runs. Constants, local indices and
+//! branch targets are worked out up front, and the fields and methods that instructions
+//! refer to are resolved the first time they run and kept for the next time
+use super::*;
+
+use std::cell::{OnceCell, RefCell};
+use std::convert::TryFrom;
+
+/// How a conditional branch compares two ints
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(super) enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+}
+
+impl Cmp {
+    pub(super) fn holds(self, lhs: i32, rhs: i32) -> bool {
+        match self {
+            Cmp::Eq => lhs == rhs,
+            Cmp::Ne => lhs != rhs,
+            Cmp::Lt => lhs < rhs,
+            Cmp::Ge => lhs >= rhs,
+            Cmp::Gt => lhs > rhs,
+            Cmp::Le => lhs <= rhs,
+        }
+    }
+}
+
+/// An instruction as it is run. Branches hold the pc they go to, and field and method
+/// instructions the index of their site in the `Code`
+#[derive(Debug, Clone)]
+pub(super) enum Op {
+    Const(Value),
+    Load(Kind, u16),
+    Store(Kind, u16),
+    Iinc(u16, i32),
+    Pop,
+    Dup,
+    IAdd,
+    ISub,
+    IMul,
+    IAnd,
+    IOr,
+    IXor,
+    IShl,
+    IShr,
+    IUshr,
+    LAdd,
+    LSub,
+    LMul,
+    DAdd,
+    DSub,
+    DMul,
+    DDiv,
+    I2L,
+    I2D,
+    /// Compares an int with zero
+    If(Cmp, usize),
+    IfICmp(Cmp, usize),
+    IfNull(usize),
+    IfNonNull(usize),
+    Goto(usize),
+    TableSwitch {
+        low: i32,
+        targets: Vec<usize>,
+        default: usize,
+    },
+    LookupSwitch {
+        pairs: Vec<(i32, usize)>,
+        default: usize,
+    },
+    ArrayLoad,
+    ArrayStore,
+    ArrayLength,
+    GetStatic(usize),
+    PutStatic(usize),
+    GetField(usize),
+    PutField(usize),
+    Invoke(usize),
+    Return(Kind),
+    ReturnVoid,
+    /// Any other instruction, which is run the way it was decoded
+    Other(Instruction),
+}
+
+impl Op {
+    /// Whether the op goes on to another op of the same frame without calling, returning,
+    /// allocating or blocking, so that the next op can be run straight after it
+    pub(super) fn is_straight(&self) -> bool {
+        !matches!(
+            self,
+            Op::Invoke(..) | Op::Return(..) | Op::ReturnVoid | Op::Other(..)
+        )
+    }
+}
+
+/// A field instruction, and the field once it is resolved
+#[derive(Debug)]
+pub(super) struct FieldSite {
+    pub(super) index: u16,
+    pub(super) field: OnceCell<Rc<class::Field>>,
+}
+
+/// An invoke instruction, the method once it is resolved, and the method last selected
+/// for the class of the receiver
+#[derive(Debug)]
+pub(super) struct InvokeSite {
+    pub(super) index: u16,
+    pub(super) kind: Invoke,
+    pub(super) method: OnceCell<Rc<Method>>,
+    pub(super) selected: Selected,
+}
+
+/// The class of the receiver an invoke last saw, and the method selected for it
+pub(super) type Selected = RefCell<Option<(Rc<Class>, Rc<Method>)>>;
+
+/// The decoded code of a method
+#[derive(Debug)]
+pub struct Code {
+    pub(super) ops: Vec<Op>,
+    /// The pc of each op, and then the length of the code
+    pcs: Vec<usize>,
+    /// The op at each pc, `NO_OP` in between
+    ips: Vec<u32>,
+    pub(super) fields: Vec<FieldSite>,
+    pub(super) invokes: Vec<InvokeSite>,
+}
+
+const NO_OP: u32 = u32::MAX;
+
+impl Code {
+    /// The code of `method`, which is decoded the first time
+    pub(super) fn of(method: &Method) -> Result<Rc<Code>> {
+        if let Some(code) = method.decoded.get() {
+            return Ok(Rc::clone(code));
+        }
+        let code = Rc::new(Self::decode(method)?);
+        Ok(Rc::clone(method.decoded.get_or_init(|| code)))
+    }
+
+    fn decode(method: &Method) -> Result<Self> {
+        let code = match method.code() {
+            Some(code) => &code.code,
+            None => generic_error!("{}.{} has no code", method.class().name, method.name),
+        };
+        let class = method.class();
+        let file = class.class_file()?;
+
+        let mut decoded = Code {
+            ops: vec![],
+            pcs: vec![],
+            ips: vec![NO_OP; code.len()],
+            fields: vec![],
+            invokes: vec![],
+        };
+        let mut pc = 0;
+        while pc < code.len() {
+            let instruction = match Instruction::decode(code, pc) {
+                Some(instruction) => instruction,
+                None => generic_error!("invalid instruction at {}", pc),
+            };
+            let size = instruction.size();
+            let op = decoded.op(file, pc, instruction)?;
+            decoded.ips[pc] = decoded.ops.len() as u32;
+            decoded.ops.push(op);
+            decoded.pcs.push(pc);
+            pc += size;
+        }
+        decoded.pcs.push(code.len());
+        Ok(decoded)
+    }
+
+    fn op(&mut self, file: &ty::ClassFile, pc: usize, instruction: Instruction) -> Result<Op> {
+        use Instruction as I;
+
+        let target = |offset: i32| (pc as isize + offset as isize) as usize;
+        let branch = |a: &u8, b: &u8| target(branch_offset(*a, *b));
+
+        let op = match &instruction {
+            I::ACONST_NULL(..) => Op::Const(Value::Null),
+            I::ICONST_M1(..) => Op::Const(Value::Int(-1)),
+            I::ICONST_0(..) => Op::Const(Value::Int(0)),
+            I::ICONST_1(..) => Op::Const(Value::Int(1)),
+            I::ICONST_2(..) => Op::Const(Value::Int(2)),
+            I::ICONST_3(..) => Op::Const(Value::Int(3)),
+            I::ICONST_4(..) => Op::Const(Value::Int(4)),
+            I::ICONST_5(..) => Op::Const(Value::Int(5)),
+            I::LCONST_0(..) => Op::Const(Value::Long(0)),
+            I::LCONST_1(..) => Op::Const(Value::Long(1)),
+            I::FCONST_0(..) => Op::Const(Value::Float(0.0)),
+            I::FCONST_1(..) => Op::Const(Value::Float(1.0)),
+            I::FCONST_2(..) => Op::Const(Value::Float(2.0)),
+            I::DCONST_0(..) => Op::Const(Value::Double(0.0)),
+            I::DCONST_1(..) => Op::Const(Value::Double(1.0)),
+            I::BIPUSH(BIPUSH(d)) => Op::Const(Value::Int(i32::from(*d as i8))),
+            I::SIPUSH(SIPUSH(a, b)) => {
+                Op::Const(Value::Int(i32::from(i16::from_be_bytes([*a, *b]))))
+            }
+            I::LDC(LDC(index)) => Self::constant(file, u16::from(*index), instruction.clone())?,
+            I::LDC_W(LDC_W(a, b)) | I::LDC2_W(LDC2_W(a, b)) => {
+                Self::constant(file, wide_index(*a, *b), instruction.clone())?
+            }
+            //
+            I::ILOAD(ILOAD(index)) => Op::Load(Kind::Int, u16::from(*index)),
+            I::LLOAD(LLOAD(index)) => Op::Load(Kind::Long, u16::from(*index)),
+            I::FLOAD(FLOAD(index)) => Op::Load(Kind::Float, u16::from(*index)),
+            I::DLOAD(DLOAD(index)) => Op::Load(Kind::Double, u16::from(*index)),
+            I::ALOAD(ALOAD(index)) => Op::Load(Kind::Reference, u16::from(*index)),
+            I::ILOAD_0(..) => Op::Load(Kind::Int, 0),
+            I::ILOAD_1(..) => Op::Load(Kind::Int, 1),
+            I::ILOAD_2(..) => Op::Load(Kind::Int, 2),
+            I::ILOAD_3(..) => Op::Load(Kind::Int, 3),
+            I::LLOAD_0(..) => Op::Load(Kind::Long, 0),
+            I::LLOAD_1(..) => Op::Load(Kind::Long, 1),
+            I::LLOAD_2(..) => Op::Load(Kind::Long, 2),
+            I::LLOAD_3(..) => Op::Load(Kind::Long, 3),
+            I::FLOAD_0(..) => Op::Load(Kind::Float, 0),
+            I::FLOAD_1(..) => Op::Load(Kind::Float, 1),
+            I::FLOAD_2(..) => Op::Load(Kind::Float, 2),
+            I::FLOAD_3(..) => Op::Load(Kind::Float, 3),
+            I::DLOAD_0(..) => Op::Load(Kind::Double, 0),
+            I::DLOAD_1(..) => Op::Load(Kind::Double, 1),
+            I::DLOAD_2(..) => Op::Load(Kind::Double, 2),
+            I::DLOAD_3(..) => Op::Load(Kind::Double, 3),
+            I::ALOAD_0(..) => Op::Load(Kind::Reference, 0),
+            I::ALOAD_1(..) => Op::Load(Kind::Reference, 1),
+            I::ALOAD_2(..) => Op::Load(Kind::Reference, 2),
+            I::ALOAD_3(..) => Op::Load(Kind::Reference, 3),
+            //
+            I::ISTORE(ISTORE(index)) => Op::Store(Kind::Int, u16::from(*index)),
+            I::LSTORE(LSTORE(index)) => Op::Store(Kind::Long, u16::from(*index)),
+            I::FSTORE(FSTORE(index)) => Op::Store(Kind::Float, u16::from(*index)),
+            I::DSTORE(DSTORE(index)) => Op::Store(Kind::Double, u16::from(*index)),
+            I::ASTORE(ASTORE(index)) => Op::Store(Kind::Reference, u16::from(*index)),
+            I::ISTORE_0(..) => Op::Store(Kind::Int, 0),
+            I::ISTORE_1(..) => Op::Store(Kind::Int, 1),
+            I::ISTORE_2(..) => Op::Store(Kind::Int, 2),
+            I::ISTORE_3(..) => Op::Store(Kind::Int, 3),
+            I::LSTORE_0(..) => Op::Store(Kind::Long, 0),
+            I::LSTORE_1(..) => Op::Store(Kind::Long, 1),
+            I::LSTORE_2(..) => Op::Store(Kind::Long, 2),
+            I::LSTORE_3(..) => Op::Store(Kind::Long, 3),
+            I::FSTORE_0(..) => Op::Store(Kind::Float, 0),
+            I::FSTORE_1(..) => Op::Store(Kind::Float, 1),
+            I::FSTORE_2(..) => Op::Store(Kind::Float, 2),
+            I::FSTORE_3(..) => Op::Store(Kind::Float, 3),
+            I::DSTORE_0(..) => Op::Store(Kind::Double, 0),
+            I::DSTORE_1(..) => Op::Store(Kind::Double, 1),
+            I::DSTORE_2(..) => Op::Store(Kind::Double, 2),
+            I::DSTORE_3(..) => Op::Store(Kind::Double, 3),
+            I::ASTORE_0(..) => Op::Store(Kind::Reference, 0),
+            I::ASTORE_1(..) => Op::Store(Kind::Reference, 1),
+            I::ASTORE_2(..) => Op::Store(Kind::Reference, 2),
+            I::ASTORE_3(..) => Op::Store(Kind::Reference, 3),
+            I::IINC(IINC(index, delta)) => Op::Iinc(u16::from(*index), i32::from(*delta as i8)),
+            //
+            I::POP(..) => Op::Pop,
+            I::DUP(..) => Op::Dup,
+            I::IADD(..) => Op::IAdd,
+            I::ISUB(..) => Op::ISub,
+            I::IMUL(..) => Op::IMul,
+            I::IAND(..) => Op::IAnd,
+            I::IOR(..) => Op::IOr,
+            I::IXOR(..) => Op::IXor,
+            I::ISHL(..) => Op::IShl,
+            I::ISHR(..) => Op::IShr,
+            I::IUSHR(..) => Op::IUshr,
+            I::LADD(..) => Op::LAdd,
+            I::LSUB(..) => Op::LSub,
+            I::LMUL(..) => Op::LMul,
+            I::DADD(..) => Op::DAdd,
+            I::DSUB(..) => Op::DSub,
+            I::DMUL(..) => Op::DMul,
+            I::DDIV(..) => Op::DDiv,
+            I::I2L(..) => Op::I2L,
+            I::I2D(..) => Op::I2D,
+            //
+            I::IFEQ(IFEQ(a, b)) => Op::If(Cmp::Eq, branch(a, b)),
+            I::IFNE(IFNE(a, b)) => Op::If(Cmp::Ne, branch(a, b)),
+            I::IFLT(IFLT(a, b)) => Op::If(Cmp::Lt, branch(a, b)),
+            I::IFGE(IFGE(a, b)) => Op::If(Cmp::Ge, branch(a, b)),
+            I::IFGT(IFGT(a, b)) => Op::If(Cmp::Gt, branch(a, b)),
+            I::IFLE(IFLE(a, b)) => Op::If(Cmp::Le, branch(a, b)),
+            I::IF_ICMPEQ(IF_ICMPEQ(a, b)) => Op::IfICmp(Cmp::Eq, branch(a, b)),
+            I::IF_ICMPNE(IF_ICMPNE(a, b)) => Op::IfICmp(Cmp::Ne, branch(a, b)),
+            I::IF_ICMPLT(IF_ICMPLT(a, b)) => Op::IfICmp(Cmp::Lt, branch(a, b)),
+            I::IF_ICMPGE(IF_ICMPGE(a, b)) => Op::IfICmp(Cmp::Ge, branch(a, b)),
+            I::IF_ICMPGT(IF_ICMPGT(a, b)) => Op::IfICmp(Cmp::Gt, branch(a, b)),
+            I::IF_ICMPLE(IF_ICMPLE(a, b)) => Op::IfICmp(Cmp::Le, branch(a, b)),
+            I::IFNULL(IFNULL(a, b)) => Op::IfNull(branch(a, b)),
+            I::IFNONNULL(IFNONNULL(a, b)) => Op::IfNonNull(branch(a, b)),
+            I::GOTO(GOTO(a, b)) => Op::Goto(branch(a, b)),
+            I::GOTO_W(GOTO_W(a, b, c, d)) => Op::Goto(target(branch_offset_wide(*a, *b, *c, *d))),
+            I::TABLESWITCH(table) => {
+                let (default, low, offsets) = table.table();
+                Op::TableSwitch {
+                    low,
+                    targets: offsets.into_iter().map(target).collect(),
+                    default: target(default),
+                }
+            }
+            I::LOOKUPSWITCH(table) => {
+                let (default, pairs) = table.pairs();
+                Op::LookupSwitch {
+                    pairs: pairs
+                        .into_iter()
+                        .map(|(key, offset)| (key, target(offset)))
+                        .collect(),
+                    default: target(default),
+                }
+            }
+            //
+            I::IALOAD(..)
+            | I::LALOAD(..)
+            | I::FALOAD(..)
+            | I::DALOAD(..)
+            | I::AALOAD(..)
+            | I::BALOAD(..)
+            | I::CALOAD(..)
+            | I::SALOAD(..) => Op::ArrayLoad,
+            I::IASTORE(..)
+            | I::LASTORE(..)
+            | I::FASTORE(..)
+            | I::DASTORE(..)
+            | I::AASTORE(..)
+            | I::BASTORE(..)
+            | I::CASTORE(..)
+            | I::SASTORE(..) => Op::ArrayStore,
+            I::ARRAYLENGTH(..) => Op::ArrayLength,
+            //
+            I::GETSTATIC(GETSTATIC(a, b)) => Op::GetStatic(self.field(wide_index(*a, *b))),
+            I::PUTSTATIC(PUTSTATIC(a, b)) => Op::PutStatic(self.field(wide_index(*a, *b))),
+            I::GETFIELD(GETFIELD(a, b)) => Op::GetField(self.field(wide_index(*a, *b))),
+            I::PUTFIELD(PUTFIELD(a, b)) => Op::PutField(self.field(wide_index(*a, *b))),
+            I::INVOKEVIRTUAL(INVOKEVIRTUAL(a, b)) => {
+                let index = wide_index(*a, *b);
+                let member = file.member_ref(ConstantIndex(index))?;
+                // method handles adapt to each call, so they are invoked on their own
+                if handles::is_signature_polymorphic(member.class, member.name) {
+                    return Ok(Op::Other(instruction));
+                }
+                Op::Invoke(self.invoke(index, Invoke::Virtual))
+            }
+            I::INVOKESPECIAL(INVOKESPECIAL(a, b)) => {
+                Op::Invoke(self.invoke(wide_index(*a, *b), Invoke::Special))
+            }
+            I::INVOKESTATIC(INVOKESTATIC(a, b)) => {
+                Op::Invoke(self.invoke(wide_index(*a, *b), Invoke::Static))
+            }
+            I::INVOKEINTERFACE(INVOKEINTERFACE(a, b, ..)) => {
+                Op::Invoke(self.invoke(wide_index(*a, *b), Invoke::Interface))
+            }
+            //
+            I::IRETURN(..) => Op::Return(Kind::Int),
+            I::LRETURN(..) => Op::Return(Kind::Long),
+            I::FRETURN(..) => Op::Return(Kind::Float),
+            I::DRETURN(..) => Op::Return(Kind::Double),
+            I::ARETURN(..) => Op::Return(Kind::Reference),
+            I::RETURN(..) => Op::ReturnVoid,
+            _ => Op::Other(instruction),
+        };
+        Ok(op)
+    }
+
+    /// Numbers are pushed as they are, other constants are resolved when they are loaded
+    fn constant(file: &ty::ClassFile, index: u16, instruction: Instruction) -> Result<Op> {
+        let val = match ConstantIndex(index).lookup(&file.constant_pool)? {
+            ty::Constant::Integer(d) => Value::Int(*d as i32),
+            ty::Constant::Float(d) => Value::Float(*d),
+            ty::Constant::Long(d) => Value::Long(*d as i64),
+            ty::Constant::Double(d) => Value::Double(*d),
+            _ => return Ok(Op::Other(instruction)),
+        };
+        Ok(Op::Const(val))
+    }
+
+    fn field(&mut self, index: u16) -> usize {
+        self.fields.push(FieldSite {
+            index,
+            field: OnceCell::new(),
+        });
+        self.fields.len() - 1
+    }
+
+    fn invoke(&mut self, index: u16, kind: Invoke) -> usize {
+        self.invokes.push(InvokeSite {
+            index,
+            kind,
+            method: OnceCell::new(),
+            selected: RefCell::new(None),
+        });
+        self.invokes.len() - 1
+    }
+
+    /// The op at `pc` and its index, if an instruction starts there
+    pub(super) fn op_at(&self, pc: usize) -> Option<(usize, &Op)> {
+        match self.ips.get(pc) {
+            Some(&ip) if ip != NO_OP => Some((ip as usize, &self.ops[ip as usize])),
+            _ => None,
+        }
+    }
+
+    /// The pc after the op at `ip`
+    pub(super) fn next_pc(&self, ip: usize) -> usize {
+        self.pcs[ip + 1]
+    }
+}
+
+impl Interpreter {
+    /// Runs `op` of `code` in the current frame
+    pub(super) fn run_op(&mut self, code: &Code, op: &Op) -> Completion<State> {
+        let frame = self.frame();
+        match op {
+            Op::Const(val) => frame.push(*val),
+            Op::Load(kind, index) => self.exec_load(usize::from(*index), *kind)?,
+            Op::Store(kind, index) => self.exec_store(usize::from(*index), *kind)?,
+            Op::Iinc(index, delta) => self.exec_iinc(usize::from(*index), *delta)?,
+            Op::Pop => {
+                frame.pop()?;
+            }
+            Op::Dup => {
+                let val = *frame.stack.last().ok_or(Error::EmptyStack)?;
+                frame.push(val)
+            }
+            Op::IAdd => ints(frame, |lhs, rhs| lhs.wrapping_add(rhs))?,
+            Op::ISub => ints(frame, |lhs, rhs| lhs.wrapping_sub(rhs))?,
+            Op::IMul => ints(frame, |lhs, rhs| lhs.wrapping_mul(rhs))?,
+            Op::IAnd => ints(frame, |lhs, rhs| lhs & rhs)?,
+            Op::IOr => ints(frame, |lhs, rhs| lhs | rhs)?,
+            Op::IXor => ints(frame, |lhs, rhs| lhs ^ rhs)?,
+            Op::IShl => ints(frame, |lhs, rhs| lhs.wrapping_shl(rhs as u32))?,
+            Op::IShr => ints(frame, |lhs, rhs| lhs.wrapping_shr(rhs as u32))?,
+            Op::IUshr => ints(frame, |lhs, rhs| {
+                (lhs as u32).wrapping_shr(rhs as u32) as i32
+            })?,
+            Op::LAdd => longs(frame, |lhs, rhs| lhs.wrapping_add(rhs))?,
+            Op::LSub => longs(frame, |lhs, rhs| lhs.wrapping_sub(rhs))?,
+            Op::LMul => longs(frame, |lhs, rhs| lhs.wrapping_mul(rhs))?,
+            Op::DAdd => doubles(frame, |lhs, rhs| lhs + rhs)?,
+            Op::DSub => doubles(frame, |lhs, rhs| lhs - rhs)?,
+            Op::DMul => doubles(frame, |lhs, rhs| lhs * rhs)?,
+            Op::DDiv => doubles(frame, |lhs, rhs| lhs / rhs)?,
+            Op::I2L => {
+                let val = frame.pop()?.as_int()?;
+                frame.push(i64::from(val))
+            }
+            Op::I2D => {
+                let val = frame.pop()?.as_int()?;
+                frame.push(f64::from(val))
+            }
+            //
+            Op::If(cmp, target) => {
+                if cmp.holds(frame.pop()?.as_int()?, 0) {
+                    return Ok(State::GotoAbsolute(*target));
+                }
+            }
+            Op::IfICmp(cmp, target) => {
+                let rhs = frame.pop()?.as_int()?;
+                let lhs = frame.pop()?.as_int()?;
+                if cmp.holds(lhs, rhs) {
+                    return Ok(State::GotoAbsolute(*target));
+                }
+            }
+            Op::IfNull(target) => {
+                if frame.pop()?.as_reference()?.is_none() {
+                    return Ok(State::GotoAbsolute(*target));
+                }
+            }
+            Op::IfNonNull(target) => {
+                if frame.pop()?.as_reference()?.is_some() {
+                    return Ok(State::GotoAbsolute(*target));
+                }
+            }
+            Op::Goto(target) => return Ok(State::GotoAbsolute(*target)),
+            Op::TableSwitch {
+                low,
+                targets,
+                default,
+            } => {
+                let index = i64::from(frame.pop()?.as_int()?) - i64::from(*low);
+                let target = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| targets.get(index))
+                    .unwrap_or(default);
+                return Ok(State::GotoAbsolute(*target));
+            }
+            Op::LookupSwitch { pairs, default } => {
+                let key = frame.pop()?.as_int()?;
+                let target = pairs
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map_or(default, |(_, target)| target);
+                return Ok(State::GotoAbsolute(*target));
+            }
+            //
+            Op::ArrayLoad => self.exec_array_load()?,
+            Op::ArrayStore => self.exec_array_store()?,
+            Op::ArrayLength => {
+                let array = self.pop_non_null()?;
+                let len = self.heap.array(array)?.elements.len();
+                self.push(len as i32)
+            }
+            Op::GetStatic(site) => {
+                let field = self.site_field(&code.fields[*site], true)?;
+                let class = field.class();
+                self.initialize(&class)?;
+                let val = class.statics.borrow()[field.slot];
+                self.push(val)
+            }
+            Op::PutStatic(site) => {
+                let field = self.site_field(&code.fields[*site], true)?;
+                let class = field.class();
+                self.initialize(&class)?;
+                let val = self.pop()?;
+                class.statics.borrow_mut()[field.slot] = val;
+            }
+            Op::GetField(site) => {
+                let field = self.site_field(&code.fields[*site], false)?;
+                let this = self.pop_non_null()?;
+                let val = self.heap.instance(this)?.fields[field.slot];
+                self.push(val)
+            }
+            Op::PutField(site) => {
+                let field = self.site_field(&code.fields[*site], false)?;
+                let val = self.pop()?;
+                let this = self.pop_non_null()?;
+                self.heap.instance_mut(this)?.fields[field.slot] = val;
+            }
+            Op::Invoke(site) => return self.invoke_site(&code.invokes[*site]),
+            Op::Return(kind) => return self.exec_return(*kind),
+            Op::ReturnVoid => return Ok(State::Return(None)),
+            Op::Other(instruction) => return self.execute(instruction),
+        }
+        Ok(State::Continue)
+    }
+
+    /// The field of `site`, which is resolved the first time
+    fn site_field<'a>(
+        &mut self,
+        site: &'a FieldSite,
+        is_static: bool,
+    ) -> Result<&'a Rc<class::Field>> {
+        if let Some(field) = site.field.get() {
+            return Ok(field);
+        }
+        let field = self.resolve_field(site.index, is_static)?;
+        Ok(site.field.get_or_init(|| field))
+    }
+
+    /// Invokes the method of `site`, which is resolved the first time
+    fn invoke_site(&mut self, site: &InvokeSite) -> Completion<State> {
+        let method = match site.method.get() {
+            Some(method) => Rc::clone(method),
+            None => {
+                let method = self.resolve_invoke(site.index, site.kind)?;
+                Rc::clone(site.method.get_or_init(|| method))
+            }
+        };
+        self.invoke_resolved(method, site.kind, Some(&site.selected))
+    }
+}
+
+fn ints(frame: &mut StackFrame, op: impl FnOnce(i32, i32) -> i32) -> Result<()> {
+    let rhs = frame.pop()?.as_int()?;
+    let lhs = frame.pop()?.as_int()?;
+    frame.push(op(lhs, rhs));
+    Ok(())
+}
+
+fn longs(frame: &mut StackFrame, op: impl FnOnce(i64, i64) -> i64) -> Result<()> {
+    let rhs = frame.pop()?.as_long()?;
+    let lhs = frame.pop()?.as_long()?;
+    frame.push(op(lhs, rhs));
+    Ok(())
+}
+
+fn doubles(frame: &mut StackFrame, op: impl FnOnce(f64, f64) -> f64) -> Result<()> {
+    let rhs = frame.pop()?.as_double()?;
+    let lhs = frame.pop()?.as_double()?;
+    frame.push(op(lhs, rhs));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::load_classes;
+
+    fn interpreter() -> Interpreter {
+        load_classes(&["bench"])
+    }
+
+    #[test]
+    fn runs() {
+        let mut vm = interpreter();
+        let mut run = |method, arg| vm.invoke::<_, i32>("bench", method, (arg,)).unwrap();
+        assert_eq!(run("loop", 100), (0..100).map(|i| i * i % 7).sum::<i32>());
+        assert_eq!(run("collatz", 30), 111);
+        assert_eq!(run("integrate", 1000), 3_141_592);
+        assert_eq!(run("sieve", 100), 25);
+        assert_eq!(run("fib", 10), 55);
+        // the field sites resolved the first time are used the second
+        assert_eq!(run("fields", 8), 12);
+        assert_eq!(run("fields", 8), 12);
+    }
+
+    #[test]
+    fn decodes() {
+        let mut vm = interpreter();
+        let class = vm.resolve_class("bench").unwrap();
+        let method = class.find_method("loop", "(I)I").unwrap();
+        let code = Code::of(&method).unwrap();
+        assert!(Rc::ptr_eq(&code, &Code::of(&method).unwrap()));
+        assert_eq!(code.ops.len(), code.pcs.len() - 1);
+        for (ip, &pc) in code.pcs[..code.ops.len()].iter().enumerate() {
+            assert_eq!(code.op_at(pc).unwrap().0, ip);
+            assert_eq!(code.next_pc(ip), code.pcs[ip + 1]);
+        }
+        // branches go to the pcs of ops
+        for op in &code.ops {
+            if let Op::If(_, target) | Op::IfICmp(_, target) | Op::Goto(target) = op {
+                assert!(code.op_at(*target).is_some());
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! Calling `java/lang/invoke/MethodHandle`s. `invokeExact` and `invoke` are signature
+//! polymorphic: the descriptor at the call site says what they take and return. The
+//! handle's type has to match it exactly for `invokeExact`, while `invoke` converts the
+//! arguments and the result like `MethodHandle.asType` does
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-2.html#jvms-2.9
+// https://docs.oracle.com/javase/8/docs/api/java/lang/invoke/MethodHandle.html#asType-java.lang.invoke.MethodType-
+use super::indy::{boxing, PRIMITIVES};
+use super::*;
+
+use crate::exec::handle::method_type_string;
+use ty::MethodDescriptor;
+
+/// Whether the method is `invokeExact` or `invoke` of `java/lang/invoke/MethodHandle`,
+/// which are linked by the descriptor of the call site rather than their own
+pub(super) fn is_signature_polymorphic(class: &str, name: &str) -> bool {
+    class == "java/lang/invoke/MethodHandle" && matches!(name, "invokeExact" | "invoke")
+}
+
+impl Interpreter {
+    /// `invokeExact` or `invoke` with the call site's `descriptor`, where the handle is on the
+    /// stack under the arguments
+    pub(super) fn exec_invoke_handle(
+        &mut self,
+        descriptor: &str,
+        exact: bool,
+    ) -> Completion<State> {
+        let signature = MethodDescriptor::parse(descriptor)?;
+        let mut args = self.frame().pop_many(signature.params.len() + 1)?;
+        let handle = match args.remove(0).as_reference()? {
+            Some(handle) => handle,
+            None => raise!("java/lang/NullPointerException"),
+        };
+        if let Some(val) = self.invoke_handle(handle, &signature, args, exact)? {
+            self.push(val)
+        }
+        Ok(State::Continue)
+    }
+
+    /// Calls a method handle from a native method, converting the arguments and result like
+    /// `invoke` does between `descriptor` and the handle's type. If the handle throws, the
+    /// exception is left pending and `None` is returned
+    pub fn invoke_method_handle(
+        &mut self,
+        handle: heap::Reference,
+        descriptor: &str,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>> {
+        let signature = MethodDescriptor::parse(descriptor)?;
+        let result = self.invoke_handle(handle, &signature, args, false);
+        Ok(self.pend(result)?.flatten())
+    }
+
+    /// Runs what `handle` refers to with `args`, which have the types of `signature`
+    fn invoke_handle(
+        &mut self,
+        handle: heap::Reference,
+        signature: &MethodDescriptor,
+        args: Vec<Value>,
+        exact: bool,
+    ) -> Completion<Option<Value>> {
+        let target = match self.method_handles.get(&handle) {
+            Some(target) => target.clone(),
+            None => generic_error!("not a method handle"),
+        };
+        let ty = MethodDescriptor::parse(&target.method_descriptor())?;
+        if (exact && ty != *signature) || ty.params.len() != signature.params.len() {
+            raise!(
+                "java/lang/invoke/WrongMethodTypeException",
+                "expected {} but found {}",
+                method_type_string(&ty),
+                method_type_string(signature),
+            );
+        }
+
+        let mut converted = Vec::with_capacity(args.len());
+        for ((arg, from), to) in args.into_iter().zip(&signature.params).zip(&ty.params) {
+            let arg = self.convert(Some(arg), Some(from), Some(to))?;
+            converted.push(arg.unwrap_or(Value::Null));
+        }
+        let mut args = converted;
+
+        let val = match &target {
+            HandleTarget::GetField(field) => {
+                let this = receiver(&args)?;
+                Some(self.heap.instance(this)?.fields[field.slot])
+            }
+            HandleTarget::PutField(field) => {
+                let this = receiver(&args)?;
+                self.heap.instance_mut(this)?.fields[field.slot] = args[1];
+                None
+            }
+            HandleTarget::GetStatic(field) => {
+                self.initialize(&field.class())?;
+                let val = field.class().statics.borrow()[field.slot];
+                Some(val)
+            }
+            HandleTarget::PutStatic(field) => {
+                self.initialize(&field.class())?;
+                field.class().statics.borrow_mut()[field.slot] = args[0];
+                None
+            }
+            HandleTarget::InvokeStatic(method) => {
+                self.initialize(&method.class())?;
+                self.run_method(method, args)?
+            }
+            HandleTarget::InvokeVirtual(method) => {
+                let method = self.class_of(receiver(&args)?)?.select_method(method)?;
+                self.run_method(&method, args)?
+            }
+            HandleTarget::InvokeInterface(method) => {
+                let class = self.class_of(receiver(&args)?)?;
+                let method = class.select_interface_method(method)?;
+                self.run_method(&method, args)?
+            }
+            HandleTarget::InvokeSpecial(method) => {
+                receiver(&args)?;
+                self.run_method(method, args)?
+            }
+            HandleTarget::NewInvokeSpecial(method) => {
+                let object = self.instantiate(&method.class())?;
+                args.insert(0, object.into());
+                self.run_method(method, args)?;
+                Some(object.into())
+            }
+        };
+        self.convert(val, ty.ret.as_ref(), signature.ret.as_ref())
+    }
+
+    /// Converts a value of type `from` to `to` by casting, widening, boxing or unboxing it.
+    /// `None` is `void`, which converts to zero or `null`, and everything converts to it
+    fn convert(
+        &mut self,
+        val: Option<Value>,
+        from: Option<&FieldType>,
+        to: Option<&FieldType>,
+    ) -> Completion<Option<Value>> {
+        let (val, from, to) = match (val, from, to) {
+            (_, _, None) => return Ok(None),
+            (_, None, Some(to)) | (None, _, Some(to)) => return Ok(Some(Value::default_for(to))),
+            (Some(val), Some(from), Some(to)) => (val, from, to),
+        };
+
+        let val = match (from.is_reference(), to.is_reference()) {
+            (false, false) => match widen(val, from, to) {
+                Some(val) => val,
+                None => raise!(
+                    "java/lang/invoke/WrongMethodTypeException",
+                    "cannot convert {} to {}",
+                    from.keyword().unwrap_or_default(),
+                    to.keyword().unwrap_or_default(),
+                ),
+            },
+            (false, true) => {
+                let (class, _) = boxing(from)?;
+                if !self.is_assignable(class, &internal_name(to))? {
+                    raise!(
+                        "java/lang/invoke/WrongMethodTypeException",
+                        "cannot convert {} to {}",
+                        from.keyword().unwrap_or_default(),
+                        internal_name(to).replace('/', "."),
+                    );
+                }
+                self.box_value(val, from)?
+            }
+            (true, false) => {
+                let boxed = match val.as_reference()? {
+                    Some(boxed) => boxed,
+                    None => raise!("java/lang/NullPointerException"),
+                };
+                let class = self.heap.get(boxed).class_name();
+                let unboxed = match PRIMITIVES.iter().find(|(_, (name, _))| *name == class) {
+                    Some((primitive, (_, unbox))) => {
+                        let val = self.unbox(boxed, primitive, unbox)?;
+                        widen(val, primitive, to)
+                    }
+                    None => None,
+                };
+                match unboxed {
+                    Some(val) => val,
+                    None => raise!(
+                        "java/lang/ClassCastException",
+                        "{} cannot be cast to {}",
+                        class.replace('/', "."),
+                        boxing(to)?.0.replace('/', "."),
+                    ),
+                }
+            }
+            (true, true) => {
+                if let Some(object) = val.as_reference()? {
+                    let class = self.heap.get(object).class_name();
+                    if !self.is_assignable(&class, &internal_name(to))? {
+                        raise!(
+                            "java/lang/ClassCastException",
+                            "{} cannot be cast to {}",
+                            class.replace('/', "."),
+                            internal_name(to).replace('/', "."),
+                        );
+                    }
+                }
+                val
+            }
+        };
+        Ok(Some(val))
+    }
+
+    /// Boxes a primitive with the `valueOf` of its box
+    fn box_value(&mut self, val: Value, primitive: &FieldType) -> Completion<Value> {
+        let (class, _) = boxing(primitive)?;
+        let class = self.resolve_class(class)?;
+        let descriptor = format!("({})L{};", primitive, class.name);
+        let value_of = class.resolve_method("valueOf", &descriptor)?;
+        self.initialize(&class)?;
+        Ok(self
+            .run_method(&value_of, vec![val])?
+            .unwrap_or(Value::Null))
+    }
+
+    /// The primitive in a box, from its `unbox` method (`intValue` etc)
+    fn unbox(
+        &mut self,
+        boxed: heap::Reference,
+        primitive: &FieldType,
+        unbox: &str,
+    ) -> Completion<Value> {
+        let class = self.class_of(boxed)?;
+        let method = class.resolve_method(unbox, &format!("(){}", primitive))?;
+        match self.run_method(&method, vec![boxed.into()])? {
+            Some(val) => Ok(val),
+            None => generic_error!("{}.{} returned nothing", class.name, unbox),
+        }
+    }
+}
+
+/// The `this` of a handle to an instance member, which can't be `null`
+fn receiver(args: &[Value]) -> Completion<heap::Reference> {
+    match args.first() {
+        Some(this) => match this.as_reference()? {
+            Some(this) => Ok(this),
+            None => raise!("java/lang/NullPointerException"),
+        },
+        None => Err(Error::StackType("reference").into()),
+    }
+}
+
+/// A widening primitive conversion, `None` if `from` doesn't widen to `to`
+// https://docs.oracle.com/javase/specs/jls/se8/html/jls-5.html#jls-5.1.2
+fn widen(val: Value, from: &FieldType, to: &FieldType) -> Option<Value> {
+    use FieldType::*;
+    let val = match (from, to, val) {
+        _ if from == to => val,
+        (Byte, Short | Int, _) | (Short | Char, Int, _) => val,
+        (Byte | Short | Char | Int, Long, Value::Int(d)) => Value::Long(i64::from(d)),
+        (Byte | Short | Char | Int, Float, Value::Int(d)) => Value::Float(d as f32),
+        (Byte | Short | Char | Int, Double, Value::Int(d)) => Value::Double(f64::from(d)),
+        (Long, Float, Value::Long(d)) => Value::Float(d as f32),
+        (Long, Double, Value::Long(d)) => Value::Double(d as f64),
+        (Float, Double, Value::Float(d)) => Value::Double(f64::from(d)),
+        _ => return None,
+    };
+    Some(val)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::load_classes;
+
+    fn load() -> Interpreter {
+        load_classes(&["handles/Handles"])
+    }
+
+    #[test]
+    fn invoke_handles() {
+        let mut vm = load();
+        assert_eq!(vm.invoke::<_, i32>("Handles", "exact", ()).unwrap(), 5);
+        assert_eq!(vm.invoke::<_, i64>("Handles", "adapted", ()).unwrap(), 42);
+        assert_eq!(vm.invoke::<_, i32>("Handles", "virtual", (7,)).unwrap(), 21);
+        assert_eq!(
+            vm.invoke::<_, i32>("Handles", "constructor", (9,)).unwrap(),
+            9
+        );
+        assert_eq!(vm.invoke::<_, i32>("Handles", "fields", (10,)).unwrap(), 11);
+        assert_eq!(
+            vm.invoke::<_, i32>("Handles", "withArguments", ()).unwrap(),
+            9
+        );
+        let s: String = vm.invoke("Handles", "type", ()).unwrap();
+        assert_eq!(s, "MethodHandle(int,int)int");
+    }
+
+    #[test]
+    fn handle_errors() {
+        let mut vm = load();
+        let s: String = vm.invoke("Handles", "wrongType", ()).unwrap();
+        assert_eq!(s, "java.lang.invoke.WrongMethodTypeException");
+        let s: String = vm.invoke("Handles", "missing", ()).unwrap();
+        assert_eq!(s, "java.lang.NoSuchMethodException");
+        let s: String = vm.invoke("Handles", "notStatic", ()).unwrap();
+        assert_eq!(s, "java.lang.IllegalAccessException");
+    }
+
+    #[test]
+    fn conversions() {
+        let int = FieldType::Int;
+        assert_eq!(
+            widen(Value::Int(-2), &FieldType::Byte, &FieldType::Double),
+            Some(Value::Double(-2.0))
+        );
+        assert_eq!(
+            widen(Value::Int(1), &FieldType::Char, &int),
+            Some(Value::Int(1))
+        );
+        assert_eq!(
+            widen(Value::Int(1), &FieldType::Byte, &FieldType::Char),
+            None
+        );
+        assert_eq!(widen(Value::Long(1), &FieldType::Long, &int), None);
+    }
+}
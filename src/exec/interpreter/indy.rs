@@ -0,0 +1,584 @@
+//! `invokedynamic`, for the bootstrap methods that javac emits. A lambda or method
+//! reference gets a class that is spun up for it, like `LambdaMetafactory` does, and string
+//! concatenation follows its recipe directly
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-6.html#jvms-6.5.invokedynamic
+use super::*;
+
+use crate::exec::asm::{ClassBuilder, MethodBuilder};
+use ty::{Constant, MethodDescriptor};
+
+/// A linked `invokedynamic` call site
+#[derive(Debug)]
+pub(super) struct CallSite {
+    /// The types of the arguments the call site takes off of the stack
+    params: Vec<FieldType>,
+    target: Target,
+}
+
+#[derive(Debug)]
+enum Target {
+    /// Instances of the class made for a lambda, which keeps the arguments in its fields
+    Lambda(Rc<Class>),
+    /// A string of the arguments and the text between them
+    Concat(Vec<Piece>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    Argument,
+}
+
+// https://docs.oracle.com/javase/8/docs/api/java/lang/invoke/LambdaMetafactory.html#altMetafactory-java.lang.invoke.MethodHandles.Lookup-java.lang.String-java.lang.invoke.MethodType-java.lang.Object...-
+const FLAG_MARKERS: u32 = 1 << 1;
+const FLAG_BRIDGES: u32 = 1 << 2;
+
+impl Interpreter {
+    /// Links the call site the first time it is run, and then calls it
+    pub(super) fn exec_invoke_dynamic(&mut self, index: u16) -> Completion<State> {
+        let current = Rc::clone(&self.frame().class);
+        let key = (current.name.clone(), index);
+        let site = match self.call_sites.get(&key) {
+            Some(site) => Rc::clone(site),
+            None => {
+                let site = Rc::new(self.link_call_site(&current, ConstantIndex(index))?);
+                self.call_sites.insert(key, Rc::clone(&site));
+                site
+            }
+        };
+
+        let args = self.frame().pop_many(site.params.len())?;
+        let val = match &site.target {
+            Target::Lambda(class) => {
+                let lambda = self.instantiate(class)?;
+                let fields = &mut self.heap.instance_mut(lambda)?.fields;
+                for (field, arg) in class.fields.iter().zip(args) {
+                    fields[field.slot] = arg;
+                }
+                lambda
+            }
+            Target::Concat(pieces) => self.concat(pieces, &site.params, args)?,
+        };
+        self.push(val);
+        Ok(State::Continue)
+    }
+
+    fn link_call_site(&mut self, class: &Rc<Class>, index: ConstantIndex) -> Completion<CallSite> {
+        let file = class.class_file()?;
+        let (bootstrap, name, descriptor) = match index.lookup(&file.constant_pool)? {
+            Constant::InvokeDynamicRef(call_site) => {
+                let (name, descriptor) = file.name_and_type(call_site.name_and_type)?;
+                (
+                    file.bootstrap_method(call_site.bootstrap)?,
+                    name,
+                    descriptor,
+                )
+            }
+            constant => generic_error!("invokedynamic of {:?}", constant),
+        };
+        let signature = MethodDescriptor::parse(descriptor)?;
+        let method = match bootstrap.method_ref.lookup(&file.constant_pool)? {
+            Constant::MethodHandleRef(handle) => file.member_ref(handle.reference())?,
+            constant => generic_error!("bootstrap method of {:?}", constant),
+        };
+
+        let arguments = &bootstrap.arguments;
+        let target = match (method.class, method.name) {
+            ("java/lang/invoke/LambdaMetafactory", "metafactory") => {
+                self.link_lambda(class, name, &signature, arguments, false)?
+            }
+            ("java/lang/invoke/LambdaMetafactory", "altMetafactory") => {
+                self.link_lambda(class, name, &signature, arguments, true)?
+            }
+            ("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants") => {
+                self.link_concat(class, &signature, arguments)?
+            }
+            ("java/lang/invoke/StringConcatFactory", "makeConcat") => {
+                let pieces = signature.params.iter().map(|_| Piece::Argument);
+                Target::Concat(pieces.collect())
+            }
+            _ => raise!(
+                "java/lang/BootstrapMethodError",
+                "bootstrap method {}.{}{} is not supported",
+                method.class,
+                method.name,
+                method.descriptor
+            ),
+        };
+
+        Ok(CallSite {
+            params: signature.params,
+            target,
+        })
+    }
+
+    /// Spins up a class for `LambdaMetafactory.metafactory`, or for `altMetafactory` which
+    /// can add marker interfaces and bridge methods
+    // https://docs.oracle.com/javase/8/docs/api/java/lang/invoke/LambdaMetafactory.html
+    fn link_lambda(
+        &mut self,
+        caller: &Rc<Class>,
+        name: &str,
+        signature: &MethodDescriptor,
+        arguments: &[ConstantIndex],
+        alt: bool,
+    ) -> Completion<Target> {
+        let file = caller.class_file()?;
+        let interface = match &signature.ret {
+            Some(FieldType::Object(interface)) => interface,
+            _ => raise!(
+                "java/lang/BootstrapMethodError",
+                "a lambda can't be a {}",
+                signature
+            ),
+        };
+        if arguments.len() < 3 + alt as usize {
+            raise!(
+                "java/lang/BootstrapMethodError",
+                "LambdaMetafactory takes at least {} arguments",
+                3 + alt as usize
+            );
+        }
+
+        let method_type = |index: ConstantIndex| -> Result<MethodDescriptor> {
+            match index.lookup(&file.constant_pool)? {
+                Constant::MethodType(descriptor) => {
+                    MethodDescriptor::parse(file.utf8(*descriptor)?).map_err(Into::into)
+                }
+                constant => generic_error!("{:?} is not a method type", constant),
+            }
+        };
+        let int = |index: ConstantIndex| -> Result<u32> {
+            match index.lookup(&file.constant_pool)? {
+                Constant::Integer(n) => Ok(*n),
+                constant => generic_error!("{:?} is not an int", constant),
+            }
+        };
+
+        let sam = method_type(arguments[0])?;
+        let implementation = match self.resolve_constant(caller, arguments[1])? {
+            Value::Reference(handle) => self.method_handle(handle).cloned(),
+            _ => None,
+        };
+        let implementation = match implementation {
+            Some(implementation) => implementation,
+            None => generic_error!("the implementation of a lambda is not a method handle"),
+        };
+        let instantiated = method_type(arguments[2])?;
+
+        let mut interfaces = vec![interface.as_str()];
+        let mut methods = vec![sam];
+        if alt {
+            let flags = int(arguments[3])?;
+            // each part is only there if its flag is set
+            let mut rest = arguments[4..].iter().copied();
+            let mut next = || -> Result<ConstantIndex> {
+                match rest.next() {
+                    Some(index) => Ok(index),
+                    None => generic_error!("too few arguments for altMetafactory"),
+                }
+            };
+            if flags & FLAG_MARKERS != 0 {
+                for _ in 0..int(next()?)? {
+                    interfaces.push(file.class_name(next()?)?);
+                }
+            }
+            if flags & FLAG_BRIDGES != 0 {
+                for _ in 0..int(next()?)? {
+                    methods.push(method_type(next()?)?);
+                }
+            }
+        }
+
+        // call sites are never dropped, so every lambda class gets its own name
+        let lambda = format!("{}$$Lambda${}", caller.name, self.call_sites.len() + 1);
+        let mut builder = ClassBuilder::new(&lambda);
+        builder.flags(ty::ClassFlags::FINAL | ty::ClassFlags::SUPER | ty::ClassFlags::SYNTHETIC);
+        for interface in interfaces {
+            builder.interface(interface)?;
+        }
+        for (i, param) in signature.params.iter().enumerate() {
+            let flags = ty::FieldFlags::PRIVATE | ty::FieldFlags::FINAL;
+            builder.field(flags, &format!("arg${}", i + 1), &param.to_string())?;
+        }
+        for descriptor in &methods {
+            let method = builder.method(ty::MethodFlags::PUBLIC, name, &descriptor.to_string());
+            let lambda = Lambda {
+                class: &lambda,
+                captured: &signature.params,
+                descriptor,
+                instantiated: &instantiated,
+                implementation: &implementation,
+            };
+            lambda.method(method)?;
+        }
+
+        let file = builder.build(self)?;
+        self.load_class(Rc::new(file));
+        Ok(Target::Lambda(self.resolve_class(&lambda)?))
+    }
+
+    /// Reads the recipe of `StringConcatFactory.makeConcatWithConstants`, where `\1` is an
+    /// argument and `\2` is the next constant
+    // https://docs.oracle.com/javase/9/docs/api/java/lang/invoke/StringConcatFactory.html
+    fn link_concat(
+        &mut self,
+        caller: &Rc<Class>,
+        signature: &MethodDescriptor,
+        arguments: &[ConstantIndex],
+    ) -> Completion<Target> {
+        let recipe = match arguments.first() {
+            Some(&recipe) => self.resolve_constant(caller, recipe)?,
+            None => raise!("java/lang/BootstrapMethodError", "missing concat recipe"),
+        };
+        let recipe = match recipe {
+            Value::Reference(recipe) => self.read_string(recipe)?,
+            _ => raise!(
+                "java/lang/BootstrapMethodError",
+                "the recipe is not a string"
+            ),
+        };
+
+        let mut constants = arguments[1..].iter();
+        let (mut pieces, mut text) = (vec![], String::new());
+        for c in recipe.chars() {
+            match c {
+                '\u{1}' => {
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Argument);
+                }
+                '\u{2}' => match constants.next() {
+                    Some(&constant) => match self.resolve_constant(caller, constant)? {
+                        Value::Int(n) => text.push_str(&n.to_string()),
+                        Value::Long(n) => text.push_str(&n.to_string()),
+                        val @ Value::Float(..) => {
+                            text.push_str(&native::format_primitive(val, &FieldType::Float)?)
+                        }
+                        val @ Value::Double(..) => {
+                            text.push_str(&native::format_primitive(val, &FieldType::Double)?)
+                        }
+                        Value::Reference(s)
+                            if self.heap.get(s).class_name() == "java/lang/String" =>
+                        {
+                            text.push_str(&self.read_string(s)?)
+                        }
+                        val => raise!(
+                            "java/lang/BootstrapMethodError",
+                            "unsupported concat constant: {:?}",
+                            val
+                        ),
+                    },
+                    None => raise!("java/lang/BootstrapMethodError", "missing concat constant"),
+                },
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+
+        let count = pieces
+            .iter()
+            .filter(|&piece| *piece == Piece::Argument)
+            .count();
+        if count != signature.params.len() {
+            raise!(
+                "java/lang/BootstrapMethodError",
+                "the recipe takes {} arguments, the call site has {}",
+                count,
+                signature.params.len()
+            );
+        }
+        Ok(Target::Concat(pieces))
+    }
+
+    /// Formats the arguments like `String.valueOf` does, calling `toString` on objects
+    fn concat(
+        &mut self,
+        pieces: &[Piece],
+        params: &[FieldType],
+        args: Vec<Value>,
+    ) -> Completion<heap::Reference> {
+        let mut args = params.iter().zip(args);
+        let mut s = String::new();
+        for piece in pieces {
+            let (ty, arg) = match piece {
+                Piece::Text(text) => {
+                    s.push_str(text);
+                    continue;
+                }
+                Piece::Argument => args.next().expect("one argument per piece"),
+            };
+            if !ty.is_reference() {
+                s.push_str(&native::format_primitive(arg, ty)?);
+                continue;
+            }
+            match native::stringify(self, arg)? {
+                Some(arg) => s.push_str(&arg),
+                None => match self.pending.take() {
+                    Some(exception) => return Err(Abrupt::Throw(exception)),
+                    None => generic_error!("toString threw nothing"),
+                },
+            }
+        }
+        Ok(self.new_string(&s)?)
+    }
+}
+
+/// A method of a lambda class, which passes the captured arguments and its own to the
+/// implementation
+struct Lambda<'a> {
+    class: &'a str,
+    captured: &'a [FieldType],
+    descriptor: &'a MethodDescriptor,
+    /// The method type with the type arguments of the call site, which says what the
+    /// arguments are unboxed from
+    instantiated: &'a MethodDescriptor,
+    implementation: &'a HandleTarget,
+}
+
+impl Lambda<'_> {
+    fn method(&self, mut builder: MethodBuilder<'_>) -> Result<()> {
+        let (method, mnemonic) = match self.implementation {
+            HandleTarget::InvokeStatic(method) => (method, "invokestatic"),
+            HandleTarget::InvokeVirtual(method) => (method, "invokevirtual"),
+            HandleTarget::InvokeInterface(method) => (method, "invokeinterface"),
+            // a private method of the caller, which only the caller can use invokespecial on
+            HandleTarget::InvokeSpecial(method) if method.class().is_interface() => {
+                (method, "invokeinterface")
+            }
+            HandleTarget::InvokeSpecial(method) => (method, "invokevirtual"),
+            HandleTarget::NewInvokeSpecial(method) => (method, "invokespecial"),
+            target => generic_error!("a lambda can't be implemented by {:?}", target),
+        };
+        let owner = method.class();
+
+        let mut params = vec![];
+        if matches!(mnemonic, "invokevirtual" | "invokeinterface") {
+            params.push(FieldType::Object(owner.name.clone()));
+        }
+        params.extend(method.signature.params.iter().cloned());
+        if params.len() != self.captured.len() + self.descriptor.params.len() {
+            generic_error!(
+                "{} can't implement {}.{}{}",
+                self.descriptor,
+                owner.name,
+                method.name,
+                method.descriptor
+            );
+        }
+
+        if mnemonic == "invokespecial" {
+            builder.class("new", &owner.name)?.op("dup")?;
+        }
+        for (i, captured) in self.captured.iter().enumerate() {
+            let name = format!("arg${}", i + 1);
+            builder.local("aload", 0)?;
+            builder.field("getfield", self.class, &name, &captured.to_string())?;
+            adapt(&mut builder, captured, None, &params[i])?;
+        }
+        let mut slot = 1;
+        let own = self.descriptor.params.iter().zip(&self.instantiated.params);
+        for ((param, instantiated), target) in own.zip(&params[self.captured.len()..]) {
+            builder.local(&format!("{}load", prefix(Some(param))), slot)?;
+            slot += param.slots() as u16;
+            adapt(&mut builder, param, Some(instantiated), target)?;
+        }
+
+        if mnemonic == "invokestatic" && owner.is_interface() {
+            builder.invoke_interface_member(
+                mnemonic,
+                &owner.name,
+                &method.name,
+                &method.descriptor,
+            )?;
+        } else {
+            builder.invoke(mnemonic, &owner.name, &method.name, &method.descriptor)?;
+        }
+
+        let ret = match mnemonic {
+            "invokespecial" => Some(FieldType::Object(owner.name.clone())),
+            _ => method.signature.ret.clone(),
+        };
+        match (&ret, &self.descriptor.ret) {
+            (None, None) => {}
+            (Some(ret), None) => {
+                builder.op(if ret.slots() == 2 { "pop2" } else { "pop" })?;
+            }
+            (None, Some(..)) => generic_error!("{} doesn't return anything", method.name),
+            (Some(ret), Some(to)) => adapt(&mut builder, ret, self.instantiated.ret.as_ref(), to)?,
+        }
+        builder.op(&format!("{}return", prefix(self.descriptor.ret.as_ref())))?;
+        builder.finish()
+    }
+}
+
+/// The prefix of the load and return instructions for a type, where `None` is `void`
+fn prefix(ty: Option<&FieldType>) -> &'static str {
+    match ty {
+        None => "",
+        Some(FieldType::Long) => "l",
+        Some(FieldType::Float) => "f",
+        Some(FieldType::Double) => "d",
+        Some(ty) if ty.is_reference() => "a",
+        Some(..) => "i",
+    }
+}
+
+/// Converts the value on top of the stack from `from` to `to` by casting, boxing, unboxing
+/// or widening it. `instantiated` is the type the value has at the call site, which is the
+/// box to unbox when `from` is erased to `java/lang/Object`
+fn adapt(
+    builder: &mut MethodBuilder<'_>,
+    from: &FieldType,
+    instantiated: Option<&FieldType>,
+    to: &FieldType,
+) -> Result<()> {
+    match (from.is_reference(), to.is_reference()) {
+        (false, false) => widen(builder, from, to),
+        (true, true) => {
+            if from != to && *to != FieldType::Object("java/lang/Object".into()) {
+                builder.class("checkcast", &internal_name(to))?;
+            }
+            Ok(())
+        }
+        (false, true) => {
+            let (class, _) = boxing(from)?;
+            let descriptor = format!("({})L{};", from, class);
+            builder.invoke("invokestatic", class, "valueOf", &descriptor)?;
+            Ok(())
+        }
+        (true, false) => {
+            let primitive = instantiated
+                .and_then(|ty| {
+                    PRIMITIVES
+                        .iter()
+                        .find(|(_, (class, _))| *ty == object(class))
+                })
+                .map_or(to, |(primitive, _)| primitive);
+            let (class, unbox) = boxing(primitive)?;
+            builder.class("checkcast", class)?;
+            builder.invoke("invokevirtual", class, unbox, &format!("(){}", primitive))?;
+            widen(builder, primitive, to)
+        }
+    }
+}
+
+/// The primitive types, with their box and the method that unboxes them
+pub(super) const PRIMITIVES: &[(FieldType, (&str, &str))] = &[
+    (FieldType::Boolean, ("java/lang/Boolean", "booleanValue")),
+    (FieldType::Byte, ("java/lang/Byte", "byteValue")),
+    (FieldType::Char, ("java/lang/Character", "charValue")),
+    (FieldType::Short, ("java/lang/Short", "shortValue")),
+    (FieldType::Int, ("java/lang/Integer", "intValue")),
+    (FieldType::Long, ("java/lang/Long", "longValue")),
+    (FieldType::Float, ("java/lang/Float", "floatValue")),
+    (FieldType::Double, ("java/lang/Double", "doubleValue")),
+];
+
+pub(super) fn boxing(primitive: &FieldType) -> Result<(&'static str, &'static str)> {
+    match PRIMITIVES.iter().find(|(ty, _)| ty == primitive) {
+        Some((_, boxing)) => Ok(*boxing),
+        None => generic_error!("{} is not a primitive", primitive),
+    }
+}
+
+fn object(class: &str) -> FieldType {
+    FieldType::Object(class.to_string())
+}
+
+fn widen(builder: &mut MethodBuilder<'_>, from: &FieldType, to: &FieldType) -> Result<()> {
+    use FieldType::*;
+    let op = match (from, to) {
+        _ if from == to => return Ok(()),
+        (Byte | Short | Char | Int, Byte | Short | Char | Int) => return Ok(()),
+        (Byte | Short | Char | Int, Long) => "i2l",
+        (Byte | Short | Char | Int, Float) => "i2f",
+        (Byte | Short | Char | Int, Double) => "i2d",
+        (Long, Float) => "l2f",
+        (Long, Double) => "l2d",
+        (Float, Double) => "f2d",
+        _ => generic_error!("{} can't be widened to {}", from, to),
+    };
+    builder.op(op)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::load_classes;
+
+    fn load() -> Interpreter {
+        load_classes(&[
+            "indy/Lambdas",
+            "indy/Op",
+            "indy/Fn",
+            "indy/BiFn",
+            "indy/Source",
+            "indy/Marker",
+        ])
+    }
+
+    #[test]
+    fn lambdas() {
+        let mut vm = load();
+        assert_eq!(
+            vm.invoke::<_, i32>("Lambdas", "nonCapturing", ()).unwrap(),
+            42
+        );
+        assert_eq!(
+            vm.invoke::<_, i32>("Lambdas", "capturing", (10,)).unwrap(),
+            13
+        );
+        assert_eq!(
+            vm.invoke::<_, i32>("Lambdas", "capturing", (20,)).unwrap(),
+            23
+        );
+        assert_eq!(
+            vm.invoke::<_, i32>("Lambdas", "staticReference", ())
+                .unwrap(),
+            5
+        );
+        assert_eq!(vm.invoke::<_, i32>("Lambdas", "instance", (4,)).unwrap(), 8);
+        assert_eq!(
+            vm.invoke::<_, i32>("Lambdas", "unbound", (4, 5)).unwrap(),
+            9
+        );
+        assert_eq!(
+            vm.invoke::<_, i32>("Lambdas", "constructor", (7,)).unwrap(),
+            7
+        );
+        assert_eq!(
+            vm.invoke::<_, i64>("Lambdas", "widened", (-3,)).unwrap(),
+            -3
+        );
+        assert_eq!(vm.invoke::<_, i32>("Lambdas", "marked", ()).unwrap(), 2);
+        let marked = vm.resolve_class("Lambdas$$Lambda$8").unwrap();
+        assert!(marked.is_subtype_of("Marker"));
+
+        // linked once, by the first call
+        assert_eq!(vm.call_sites.len(), 8);
+        let lambda = vm.resolve_class("Lambdas$$Lambda$2").unwrap();
+        assert!(lambda.is_subtype_of("Op"));
+        assert_eq!(lambda.fields.len(), 1);
+    }
+
+    #[test]
+    fn string_concat() {
+        let mut vm = load();
+        let s: String = vm
+            .invoke(
+                "Lambdas",
+                "concat",
+                ("x", 1, b'c' as u16, 0.5, true, 1i64 << 40),
+            )
+            .unwrap();
+        assert_eq!(s, "name=x, n=1c0.5true1099511627776 Lambdas(1)null");
+
+        let s: String = vm.invoke("Lambdas", "control", (-1,)).unwrap();
+        assert_eq!(s, "\u{1}-1\u{2}");
+    }
+}
@@ -0,0 +1,247 @@
+//! The interpreter's side of `exec::jit`: counting calls and back edges, and running the
+//! methods that have been compiled
+use super::*;
+use crate::exec::jit::{self, Jit, Tier};
+
+impl Interpreter {
+    /// Sets the compiler that hot methods are compiled with, if any
+    pub fn set_jit(&mut self, jit: Option<Jit>) {
+        self.jit = jit;
+    }
+
+    pub fn jit(&self) -> Option<&Jit> {
+        self.jit.as_ref()
+    }
+
+    /// Runs `method` compiled, compiling it first if it has become hot. `None` when it is
+    /// left to the interpreter, or deoptimized and has to be run by it
+    pub(super) fn run_compiled(
+        &mut self,
+        method: &Rc<Method>,
+        args: &[Value],
+    ) -> Option<Option<Value>> {
+        // the hooks see each instruction, which compiled methods don't show them
+        let hooked = self.profiler.is_some() || self.tracer.is_some() || self.debugger.is_some();
+        if !method.is_static() || self.jit.is_none() || hooked {
+            return None;
+        }
+
+        let hotness = &method.hotness;
+        if let Tier::Interpreted = hotness.tier.get() {
+            hotness.count_call();
+            let mut jit = self.jit.take()?;
+            if jit.is_hot(hotness) {
+                let linked = &self.linked;
+                let mut resolve =
+                    |method: &Rc<Method>, index| resolve_static(linked, method, index);
+                // methods that can't be compiled are interpreted
+                let _ = jit.compile(method, &mut resolve);
+            }
+            self.jit = Some(jit);
+        }
+        let entry = match hotness.tier.get() {
+            Tier::Compiled(entry) => entry,
+            Tier::Interpreted | Tier::Never => return None,
+        };
+
+        let mut slots = args.iter().map(|&arg| to_slot(arg)).collect::<Vec<_>>();
+        slots.push(0);
+        // the arguments are the ones the method was compiled for, and there is room for
+        // the result
+        let status = unsafe { entry(slots.as_mut_ptr(), jit::MAX_DEPTH) };
+        if status != 0 {
+            if let Some(jit) = &mut self.jit {
+                jit.deoptimize(method);
+            }
+            return None;
+        }
+        let ret = method.signature.ret.as_ref();
+        Some(ret.map(|ret| from_slot(slots[0], ret)))
+    }
+
+    /// Counts a branch back to an earlier instruction of the current method
+    pub(super) fn count_back_edge(&mut self) {
+        if self.jit.is_some() {
+            self.frame().method.hotness.count_back_edge();
+        }
+    }
+}
+
+/// The static method an `invokestatic` of `method` calls, if its class is linked and
+/// initialized, which compiled code can't do
+fn resolve_static(
+    linked: &HashMap<String, Rc<Class>>,
+    method: &Rc<Method>,
+    index: u16,
+) -> Option<Rc<Method>> {
+    let current = method.class();
+    let member = current
+        .class_file()
+        .ok()?
+        .member_ref(ConstantIndex(index))
+        .ok()?;
+    let class = linked.get(member.class)?;
+    let initialized = class.init_state.get() == InitState::Initialized;
+    if member.interface || !(initialized || Rc::ptr_eq(class, &current)) {
+        return None;
+    }
+    class.resolve_method(member.name, member.descriptor).ok()
+}
+
+/// A primitive value as compiled methods take it, in the low bytes of a slot
+fn to_slot(val: Value) -> u64 {
+    let mut slot = [0; 8];
+    match val {
+        Value::Int(d) => slot[..4].copy_from_slice(&d.to_ne_bytes()),
+        Value::Float(d) => slot[..4].copy_from_slice(&d.to_ne_bytes()),
+        Value::Long(d) => slot = d.to_ne_bytes(),
+        Value::Double(d) => slot = d.to_ne_bytes(),
+        _ => {}
+    }
+    u64::from_ne_bytes(slot)
+}
+
+fn from_slot(slot: u64, ty: &FieldType) -> Value {
+    let bytes = slot.to_ne_bytes();
+    let word = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    match ty {
+        FieldType::Long => Value::Long(i64::from_ne_bytes(bytes)),
+        FieldType::Float => Value::Float(f32::from_ne_bytes(word)),
+        FieldType::Double => Value::Double(f64::from_ne_bytes(bytes)),
+        _ => Value::Int(i32::from_ne_bytes(word)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::load_classes;
+
+    fn interpreter() -> Interpreter {
+        let mut vm = load_classes(&["jit", "bench"]);
+        let mut jit = Jit::new().unwrap();
+        jit.call_threshold(2).back_edge_threshold(100);
+        vm.set_jit(Some(jit));
+        vm
+    }
+
+    #[test]
+    fn compiles() {
+        let mut vm = interpreter();
+        for _ in 0..3 {
+            assert_eq!(vm.invoke::<_, i32>("bench", "fib", (15,)).unwrap(), 610);
+            assert_eq!(
+                vm.invoke::<_, i32>("bench", "integrate", (1000,)).unwrap(),
+                3_141_592
+            );
+        }
+        // the loop is hot before the method is called again
+        assert_eq!(
+            vm.invoke::<_, i32>("bench", "loop", (1000,)).unwrap(),
+            2_001
+        );
+        assert_eq!(
+            vm.invoke::<_, i32>("bench", "loop", (1000,)).unwrap(),
+            2_001
+        );
+        // fields aren't compiled
+        for _ in 0..3 {
+            assert_eq!(vm.invoke::<_, i32>("bench", "fields", (8,)).unwrap(), 12);
+        }
+
+        let compiled = vm.jit().unwrap().compiled();
+        for method in &["bench.fib(I)I", "bench.integrate(I)I", "bench.loop(I)I"] {
+            assert!(compiled.contains(&method.to_string()), "{}", method);
+        }
+        assert!(!compiled
+            .iter()
+            .any(|method| method.starts_with("bench.fields")));
+    }
+
+    /// Runs `method` with each of `args` compiled and interpreted, which agree
+    fn agree(method: &str, descriptor: &str, args: &[Vec<Value>]) {
+        let (mut vm, mut reference) = (interpreter(), interpreter());
+        reference.set_jit(None);
+        for _ in 0..3 {
+            for args in args {
+                let compiled = vm.invoke_static("jit", method, descriptor, args).unwrap();
+                let interpreted = reference.invoke_static("jit", method, descriptor, args);
+                let interpreted = interpreted.unwrap();
+                let (compiled, interpreted) =
+                    (format!("{:?}", compiled), format!("{:?}", interpreted));
+                assert_eq!(compiled, interpreted, "{}{:?}", method, args);
+            }
+        }
+        let name = format!("jit.{}{}", method, descriptor);
+        assert!(vm.jit().unwrap().compiled().contains(&name), "{}", name);
+        assert_eq!(vm.jit().unwrap().deoptimizations(), 0);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let ints = [0, 1, -1, 7, -7, 31, 33, i32::MIN, i32::MAX, 0x1234_5678];
+        let pairs = |values: &[Value]| {
+            let pairs = values
+                .iter()
+                .flat_map(|&a| values.iter().map(move |&b| vec![a, b]));
+            pairs.collect::<Vec<_>>()
+        };
+        let values = ints.iter().map(|&d| Value::Int(d)).collect::<Vec<_>>();
+        agree("ints", "(II)J", &pairs(&values));
+        let values = ints
+            .iter()
+            .map(|&d| Value::Long(i64::from(d).wrapping_mul(0x1_0000_0001)))
+            .collect::<Vec<_>>();
+        agree("longs", "(JJ)J", &pairs(&values));
+
+        let floats = [
+            0.0,
+            -0.0,
+            1.5,
+            -2.5,
+            1e30,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ];
+        let values = floats
+            .iter()
+            .map(|&d| Value::Float(d as f32))
+            .collect::<Vec<_>>();
+        agree("floats", "(FF)D", &pairs(&values));
+        let values = floats.iter().map(|&d| Value::Double(d)).collect::<Vec<_>>();
+        agree("doubles", "(DD)D", &pairs(&values));
+
+        let keys = [-5, -1, 0, 1, 2, 3, 4, 100];
+        let keys = keys
+            .iter()
+            .map(|&d| vec![Value::Int(d)])
+            .collect::<Vec<_>>();
+        agree("cases", "(I)I", &keys);
+    }
+
+    #[test]
+    fn deoptimizes() {
+        let mut vm = interpreter();
+        let mut divide = |a, b| vm.invoke::<_, i32>("jit", "divide", (a, b));
+        for _ in 0..3 {
+            assert_eq!(divide(i32::MIN, -1).unwrap(), 0);
+            assert_eq!(divide(7, 2).unwrap(), 7);
+        }
+        // dividing by zero is left to the interpreter to throw
+        assert!(divide(1, 0).is_err());
+        assert_eq!(divide(7, 2).unwrap(), 7);
+        assert_eq!(vm.jit().unwrap().deoptimizations(), 1);
+
+        // calls deeper than compiled code goes are left to the interpreter
+        for _ in 0..3 {
+            assert_eq!(vm.invoke::<_, i32>("jit", "depth", (10,)).unwrap(), 10);
+        }
+        let depth = jit::MAX_DEPTH as i32 * 2;
+        assert_eq!(
+            vm.invoke::<_, i32>("jit", "depth", (depth,)).unwrap(),
+            depth
+        );
+        assert_eq!(vm.jit().unwrap().deoptimizations(), 2);
+    }
+}
@@ -0,0 +1,142 @@
+//! The interpreter's side of `exec::profile`: counting calls, and recording each instruction
+//! with the call stack it runs in
+use super::*;
+
+impl Interpreter {
+    /// Sets the profiler that counts and times what is run, if any
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler;
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Records `instruction` of the current frame with the profiler
+    pub(super) fn profile(&mut self, instruction: &Instruction) {
+        let mut profiler = match self.profiler.take() {
+            Some(profiler) => profiler,
+            None => return,
+        };
+        let frame = self.frames.last().expect("a frame must be running");
+        profiler.record(
+            self.current,
+            self.thread_name(),
+            self.frames.iter().map(|frame| &frame.method),
+            frame.pc,
+            instruction.opcode(),
+        );
+        self.profiler = Some(profiler);
+    }
+
+    /// Starts timing the native `method`, which the current frame is calling
+    pub(super) fn profile_native(&mut self, method: &Rc<Method>) {
+        let mut profiler = match self.profiler.take() {
+            Some(profiler) => profiler,
+            None => return,
+        };
+        let stack = self.frames.iter().map(|frame| &frame.method);
+        profiler.enter_native(self.current, self.thread_name(), stack, method);
+        self.profiler = Some(profiler);
+    }
+
+    /// Stops timing the native method that `profile_native` started timing
+    pub(super) fn profile_native_return(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.leave_native();
+        }
+    }
+
+    /// Counts a call of `method` with the profiler
+    pub(super) fn profile_call(&mut self, method: &Rc<Method>) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.call(method);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::profile::Weight;
+    use crate::test_utils::load_classes;
+    use std::time::Duration;
+
+    fn profile(profiler: Profiler) -> Profiler {
+        let mut vm = load_classes(&["profile"]);
+        vm.set_profiler(Some(profiler));
+        assert_eq!(vm.invoke::<_, i32>("profile", "run", ()).unwrap(), 5005);
+        vm.profiler.take().unwrap()
+    }
+
+    #[test]
+    fn counts() {
+        let profiler = profile(Profiler::new());
+        let methods = profiler.methods();
+        let method = |name: &str| methods.iter().find(|m| m.name == name).unwrap();
+
+        let (fib, sum) = (method("fib"), method("sum"));
+        assert_eq!(fib.calls, 177);
+        assert_eq!(sum.calls, 1);
+        // the loop condition is checked once more than the body runs
+        assert_eq!(sum.pcs[&4], 101);
+        assert_eq!(sum.pcs[&11], 100);
+        assert_eq!(sum.instructions, 4 + 101 * 3 + 100 * 6 + 2);
+        assert!(method("run").inclusive >= fib.inclusive + sum.inclusive);
+        assert!(fib.inclusive >= fib.exclusive);
+
+        let iadd = profiler
+            .opcodes()
+            .into_iter()
+            .find(|op| op.mnemonic() == "iadd");
+        assert_eq!(iadd.unwrap().count, 100 + 88 + 1);
+
+        let collapsed = profiler.collapsed(Weight::Instructions);
+        let lines = collapsed.lines().collect::<Vec<_>>();
+        assert!(lines.contains(&"main;profile.run;profile.sum 909"));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("main;profile.run;profile.fib;profile.fib ")));
+        let total = lines
+            .iter()
+            .map(|line| line.rsplit(' ').next().unwrap().parse::<u64>().unwrap())
+            .sum::<u64>();
+        let instructions = methods.iter().map(|m| m.instructions).sum::<u64>();
+        assert_eq!(total, instructions);
+
+        let report = profiler.report();
+        assert!(report.contains("profile.fib(I)I"));
+    }
+
+    #[test]
+    fn natives() {
+        let mut vm = load_classes(&["profile"]);
+        vm.set_profiler(Some(Profiler::new()));
+        assert_eq!(vm.invoke::<_, i32>("profile", "copy", ()).unwrap(), 100000);
+        let profiler = vm.profiler.take().unwrap();
+        let methods = profiler.methods();
+        let method = |name: &str| methods.iter().find(|m| m.name == name).unwrap();
+
+        // the native method is timed on its own, and not as part of the method calling it
+        let (copy, arraycopy) = (method("copy"), method("arraycopy"));
+        assert_eq!(arraycopy.calls, 1);
+        assert!(arraycopy.exclusive > Duration::default());
+        assert_eq!(arraycopy.inclusive, arraycopy.exclusive);
+        assert!(copy.inclusive >= copy.exclusive + arraycopy.exclusive);
+        let collapsed = profiler.collapsed(Weight::Time);
+        assert!(collapsed.contains("main;profile.copy;java/lang/System.arraycopy "));
+    }
+
+    #[test]
+    fn samples() {
+        let mut profiler = Profiler::new();
+        profiler.sample_every(100);
+        let profiler = profile(profiler);
+        let collapsed = profiler.collapsed(Weight::Instructions);
+        // far fewer stacks are recorded, but the calls are all counted
+        assert!(collapsed.lines().count() < 10);
+        let methods = profiler.methods();
+        let fib = methods.iter().find(|m| m.name == "fib").unwrap();
+        assert_eq!(fib.calls, 177);
+    }
+}
@@ -0,0 +1,808 @@
+//! Green threads. Every `java/lang/Thread` runs on the interpreter's own loop: the outermost
+//! `run_frames` switches between them every few instructions, or when the running thread
+//! blocks. Time is virtual, so sleeping and timed waits are as deterministic as the rest.
+//! Switches only happen between instructions of the outermost loop, so a thread that blocks
+//! inside a nested call (e.g. from a native method) can't let the others run
+// https://docs.oracle.com/javase/specs/jls/se8/html/jls-17.html
+use super::*;
+use crate::exec::debug::{ThreadInfo, ThreadStatus};
+
+use std::collections::hash_map::Entry;
+use std::io::Write;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How many instructions a thread runs before the next one gets a turn
+const QUANTUM: u32 = 1000;
+
+/// The virtual time an instruction takes, in nanoseconds
+const INSTRUCTION_NANOS: u64 = 1000;
+
+/// An index into the interpreter's threads
+pub(super) type ThreadId = usize;
+
+#[derive(Debug)]
+pub(super) struct Thread {
+    name: String,
+    /// The `java/lang/Thread`, which the main thread only gets once it asks for it
+    object: Option<heap::Reference>,
+    /// The call stack, while another thread is running
+    frames: Vec<StackFrame>,
+    state: ThreadState,
+    daemon: bool,
+}
+
+impl Thread {
+    pub(super) fn main() -> Self {
+        Thread {
+            name: "main".to_string(),
+            object: None,
+            frames: vec![],
+            state: ThreadState::Runnable,
+            daemon: false,
+        }
+    }
+}
+
+/// What a thread is doing. Deadlines are in virtual nanoseconds
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ThreadState {
+    Runnable,
+    /// Waiting to enter the monitor of the object
+    Blocked(heap::Reference),
+    /// In `Object.wait`, having given up the monitor it entered `count` times
+    Waiting {
+        object: heap::Reference,
+        count: usize,
+        until: Option<u64>,
+    },
+    /// Notified, and waiting to enter the monitor again
+    Reentering {
+        object: heap::Reference,
+        count: usize,
+    },
+    Sleeping(u64),
+    Joining {
+        thread: ThreadId,
+        until: Option<u64>,
+    },
+    Terminated,
+}
+
+impl ThreadState {
+    /// When the thread wakes up by itself, if it does
+    fn deadline(self) -> Option<u64> {
+        match self {
+            ThreadState::Waiting { until, .. } | ThreadState::Joining { until, .. } => until,
+            ThreadState::Sleeping(until) => Some(until),
+            _ => None,
+        }
+    }
+}
+
+/// The owner of an object's monitor, and how many times it has entered it
+#[derive(Debug, Copy, Clone)]
+pub(super) struct Monitor {
+    owner: ThreadId,
+    count: usize,
+}
+
+#[derive(Debug)]
+pub(super) struct Scheduler {
+    /// The state of the random number generator that picks threads and time slices, or
+    /// `None` to take turns in order
+    rng: Option<u64>,
+    /// The instructions left in the running thread's time slice
+    slice: u32,
+    /// The virtual time in nanoseconds
+    clock: u64,
+    /// The wall clock and monotonic time when the virtual clock was at 0
+    started: (SystemTime, Instant),
+}
+
+/// The origin of the monotonic clock, shared by every interpreter in the process
+fn origin() -> Instant {
+    static ORIGIN: OnceLock<Instant> = OnceLock::new();
+    *ORIGIN.get_or_init(Instant::now)
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler {
+            rng: None,
+            slice: QUANTUM,
+            clock: 0,
+            // the origin has to come first, for the start to be after it
+            started: (SystemTime::now(), origin().max(Instant::now())),
+        }
+    }
+}
+
+impl Scheduler {
+    /// xorshift64*
+    fn random(&mut self) -> Option<u64> {
+        let state = self.rng.as_mut()?;
+        *state ^= *state >> 12;
+        *state ^= *state << 25;
+        *state ^= *state >> 27;
+        Some(state.wrapping_mul(0x2545_F491_4F6C_DD1D))
+    }
+
+    fn time_slice(&mut self) -> u32 {
+        match self.random() {
+            Some(random) => 1 + (random % u64::from(2 * QUANTUM)) as u32,
+            None => QUANTUM,
+        }
+    }
+}
+
+impl Interpreter {
+    /// Makes the scheduler switch threads after a random number of instructions, and pick
+    /// the next thread at random, from `seed`. The same seed gives the same interleaving.
+    /// `None` goes back to switching in turn every few instructions
+    pub fn set_scheduler_seed(&mut self, seed: Option<u64>) {
+        // xorshift can't start from 0
+        self.scheduler.rng = seed.map(|seed| seed | 1 << 63);
+        self.scheduler.slice = self.scheduler.time_slice();
+    }
+
+    pub(super) fn thread_name(&self) -> &str {
+        &self.threads[self.current].name
+    }
+
+    /// The id of the thread that is running
+    pub fn running_thread(&self) -> usize {
+        self.current
+    }
+
+    /// Every thread that has been started and hasn't ended
+    pub fn thread_list(&self) -> Vec<ThreadInfo> {
+        let threads = self.threads.iter().enumerate();
+        let threads = threads.filter_map(|(id, thread)| {
+            let status = match thread.state {
+                ThreadState::Runnable => ThreadStatus::Running,
+                ThreadState::Blocked(..) | ThreadState::Reentering { .. } => ThreadStatus::Monitor,
+                ThreadState::Waiting { .. } | ThreadState::Joining { .. } => ThreadStatus::Waiting,
+                ThreadState::Sleeping(..) => ThreadStatus::Sleeping,
+                ThreadState::Terminated => return None,
+            };
+            Some(ThreadInfo {
+                id,
+                name: thread.name.clone(),
+                object: thread.object,
+                status,
+            })
+        });
+        threads.collect()
+    }
+
+    /// The call stack of a thread, running or not
+    pub(super) fn thread_frames(&self, thread: ThreadId) -> Option<&[StackFrame]> {
+        match self.threads.get(thread) {
+            _ if thread == self.current => Some(&self.frames),
+            Some(thread) => Some(&thread.frames),
+            None => None,
+        }
+    }
+
+    /// Counts an instruction of the running thread, and switches to another thread when its
+    /// time slice is used up or it can't go on
+    pub(super) fn schedule(&mut self) -> Result<()> {
+        self.scheduler.clock += INSTRUCTION_NANOS;
+        if self.threads[self.current].state == ThreadState::Runnable && self.scheduler.slice > 0 {
+            self.scheduler.slice -= 1;
+            return Ok(());
+        }
+        let next = self.pick_thread()?;
+        self.switch_to(next);
+        self.scheduler.slice = self.scheduler.time_slice();
+        Ok(())
+    }
+
+    /// How many more instructions the running thread can run before the scheduler has to
+    /// be asked again. Nested calls run until they return
+    pub(super) fn straight_budget(&self) -> u32 {
+        match self.nesting {
+            1 => self.scheduler.slice,
+            _ => u32::MAX,
+        }
+    }
+
+    /// Counts an instruction run within the budget, like `schedule` does
+    pub(super) fn charge_instruction(&mut self) {
+        if self.nesting == 1 {
+            self.scheduler.clock += INSTRUCTION_NANOS;
+            self.scheduler.slice -= 1;
+        }
+    }
+
+    /// Waits for the running thread to be able to go on, where the other threads can't run.
+    /// Only time passing can wake it up, so this fails for anything but sleeping and timed
+    /// waits
+    pub(super) fn block_in_place(&mut self) -> Result<()> {
+        while self.threads[self.current].state != ThreadState::Runnable {
+            self.wake_threads();
+            let thread = &self.threads[self.current];
+            if thread.state == ThreadState::Runnable {
+                break;
+            }
+            match thread.state.deadline() {
+                Some(deadline) => self.scheduler.clock = self.scheduler.clock.max(deadline),
+                None => generic_error!(
+                    "thread \"{}\" blocked in a nested call, where other threads can't run",
+                    thread.name
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// The next thread to run, which can be the running one. When every thread is waiting
+    /// the clock skips ahead to the first deadline, and if there is none the threads are
+    /// deadlocked
+    fn pick_thread(&mut self) -> Result<ThreadId> {
+        loop {
+            self.wake_threads();
+            let count = self.threads.len();
+            let runnable = (1..=count)
+                .map(|i| (self.current + i) % count)
+                .filter(|&id| {
+                    let thread = &self.threads[id];
+                    thread.state == ThreadState::Runnable
+                        && (id == self.current || !thread.frames.is_empty())
+                })
+                .collect::<Vec<_>>();
+            if !runnable.is_empty() {
+                let index = match self.scheduler.random() {
+                    Some(random) => (random % runnable.len() as u64) as usize,
+                    None => 0,
+                };
+                return Ok(runnable[index]);
+            }
+
+            match self.threads.iter().filter_map(|t| t.state.deadline()).min() {
+                Some(deadline) => self.scheduler.clock = self.scheduler.clock.max(deadline),
+                None => return Err(Error::Deadlock(self.dump_threads())),
+            }
+        }
+    }
+
+    /// Makes the threads whose deadline has passed, or that can get what they are waiting
+    /// for, runnable
+    fn wake_threads(&mut self) {
+        let clock = self.scheduler.clock;
+        for id in 0..self.threads.len() {
+            let mut state = self.threads[id].state;
+            if let ThreadState::Waiting {
+                object,
+                count,
+                until: Some(until),
+            } = state
+            {
+                if clock >= until {
+                    state = ThreadState::Reentering { object, count };
+                    self.threads[id].state = state;
+                }
+            }
+
+            let runnable = match state {
+                ThreadState::Runnable | ThreadState::Terminated | ThreadState::Waiting { .. } => {
+                    continue
+                }
+                // entering the monitor is left to the instruction, which is run again
+                ThreadState::Blocked(object) => !self.monitors.contains_key(&object),
+                ThreadState::Reentering { object, count } => match self.monitors.entry(object) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(Monitor { owner: id, count });
+                        true
+                    }
+                    Entry::Occupied(..) => false,
+                },
+                ThreadState::Sleeping(until) => clock >= until,
+                ThreadState::Joining { thread, until } => {
+                    self.threads[thread].state == ThreadState::Terminated
+                        || until.is_some_and(|until| clock >= until)
+                }
+            };
+            if runnable {
+                self.threads[id].state = ThreadState::Runnable;
+            }
+        }
+    }
+
+    /// Puts the running thread's frames away and carries on with `thread`'s
+    pub(super) fn switch_to(&mut self, thread: ThreadId) {
+        if thread == self.current {
+            return;
+        }
+        let frames = std::mem::take(&mut self.frames);
+        self.threads[self.current].frames = frames;
+        self.frames = std::mem::take(&mut self.threads[thread].frames);
+        self.current = thread;
+    }
+
+    /// Ends the running thread, which isn't the one the loop was started on, and switches
+    /// to the next
+    pub(super) fn finish_thread(&mut self, exception: Option<heap::Reference>) -> Result<()> {
+        self.end_thread(exception)?;
+        let next = self.pick_thread()?;
+        self.switch_to(next);
+        self.scheduler.slice = self.scheduler.time_slice();
+        Ok(())
+    }
+
+    /// Marks the running thread as terminated and lets go of its monitors. An exception
+    /// thrown out of it is printed, like the default uncaught exception handler does
+    fn end_thread(&mut self, exception: Option<heap::Reference>) -> Result<()> {
+        if let Some(exception) = exception {
+            let exception = self.java_exception(exception)?;
+            let name = &self.threads[self.current].name;
+            writeln!(
+                self.stderr,
+                "Exception in thread \"{}\" {}",
+                name, exception
+            )?;
+        }
+
+        let current = self.current;
+        self.threads[current].state = ThreadState::Terminated;
+        self.frames.clear();
+        self.monitors.retain(|_, monitor| monitor.owner != current);
+        Ok(())
+    }
+
+    /// Runs the threads that are still alive until they have all finished, the way the JVM
+    /// waits for them before it exits. Daemon threads are left where they are once the
+    /// others are done
+    pub fn run_threads(&mut self) -> Result<()> {
+        let idle = self.current;
+        let state = std::mem::replace(&mut self.threads[idle].state, ThreadState::Terminated);
+        let result = self.drive_threads();
+        self.switch_to(idle);
+        self.threads[idle].state = state;
+        result
+    }
+
+    fn drive_threads(&mut self) -> Result<()> {
+        while self
+            .threads
+            .iter()
+            .any(|thread| thread.state != ThreadState::Terminated && !thread.daemon)
+        {
+            let next = self.pick_thread()?;
+            self.switch_to(next);
+            self.scheduler.slice = self.scheduler.time_slice();
+
+            // the thread runs until it finishes, when `run_frames` switches to the next one
+            // itself, so this only returns once the last thread it switched to is done
+            self.nesting += 1;
+            let result = self.run_frames(0);
+            self.nesting -= 1;
+            let exception = match result {
+                Ok(..) => None,
+                Err(Abrupt::Fault(err)) => return Err(err),
+                Err(abrupt) => match self.exception_for(abrupt) {
+                    Ok(exception) => Some(exception),
+                    Err(Abrupt::Fault(err)) => return Err(err),
+                    Err(..) => unreachable!("only faults are passed on"),
+                },
+            };
+            self.end_thread(exception)?;
+        }
+        Ok(())
+    }
+
+    /// Describes what every live thread is doing, and its stack, like a thread dump
+    fn dump_threads(&self) -> String {
+        let mut dump = String::new();
+        for (id, thread) in self.threads.iter().enumerate() {
+            let describe = |object: heap::Reference| {
+                let class = self.heap.get(object).class_name().replace('/', ".");
+                format!("{}@{:x}", class, object.index())
+            };
+            let state = match thread.state {
+                ThreadState::Terminated => continue,
+                ThreadState::Runnable => "runnable".to_string(),
+                ThreadState::Blocked(object) => format!("waiting to lock {}", describe(object)),
+                ThreadState::Waiting { object, .. } => format!("waiting on {}", describe(object)),
+                ThreadState::Reentering { object, .. } => {
+                    format!("waiting to re-lock {}", describe(object))
+                }
+                ThreadState::Sleeping(..) => "sleeping".to_string(),
+                ThreadState::Joining { thread, .. } => {
+                    format!("waiting for \"{}\" to finish", self.threads[thread].name)
+                }
+            };
+            dump.push_str(&format!("\"{}\" {}", thread.name, state));
+
+            let frames = if id == self.current {
+                &self.frames
+            } else {
+                &thread.frames
+            };
+            for frame in frames.iter().rev() {
+                dump.push_str(&format!("\n\tat {}", frame.trace_element()));
+            }
+            dump.push('\n');
+        }
+        dump
+    }
+
+    /// Enters the monitor of `object`, unless another thread owns it. Then the running thread
+    /// is blocked until it is free, and `false` is returned
+    pub(super) fn enter_monitor(&mut self, object: heap::Reference) -> bool {
+        let current = self.current;
+        let monitor = self.monitors.entry(object).or_insert(Monitor {
+            owner: current,
+            count: 0,
+        });
+        if monitor.owner == current {
+            monitor.count += 1;
+            true
+        } else {
+            self.threads[current].state = ThreadState::Blocked(object);
+            false
+        }
+    }
+
+    /// Enters the monitor of `object` from a nested call, where the thread can't wait for it
+    pub(super) fn enter_monitor_in_place(&mut self, object: heap::Reference) -> Result<()> {
+        while !self.enter_monitor(object) {
+            self.block_in_place()?;
+        }
+        Ok(())
+    }
+
+    /// Exits the monitor of `object`, which the running thread has to own
+    pub(super) fn exit_monitor(&mut self, object: heap::Reference) -> Completion<()> {
+        match self.monitors.get_mut(&object) {
+            Some(monitor) if monitor.owner == self.current => {
+                monitor.count -= 1;
+                if monitor.count == 0 {
+                    self.monitors.remove(&object);
+                }
+                Ok(())
+            }
+            _ => raise!(
+                "java/lang/IllegalMonitorStateException",
+                "current thread is not owner"
+            ),
+        }
+    }
+
+    /// The object a `synchronized` method locks: the receiver, or the class of a static
+    /// method
+    pub(super) fn method_monitor(
+        &mut self,
+        method: &Method,
+        args: &[Value],
+    ) -> Result<Option<heap::Reference>> {
+        if !method.flags.contains(ty::MethodFlags::SYNCHRONIZED) {
+            return Ok(None);
+        }
+        if method.is_static() {
+            return self.class_mirror(&method.class().name).map(Some);
+        }
+        match args.first() {
+            Some(Value::Reference(this)) => Ok(Some(*this)),
+            _ => Err(Error::StackType("reference")),
+        }
+    }
+
+    /// The id of the thread `object` was started as
+    fn thread_id(&self, object: heap::Reference) -> Option<ThreadId> {
+        self.threads
+            .iter()
+            .position(|thread| thread.object == Some(object))
+    }
+
+    /// Starts running the `java/lang/Thread` `object` alongside the others, from its `run`
+    /// method or that of its `Runnable`. A thread can only be started once
+    pub(crate) fn start_thread(&mut self, object: heap::Reference) -> Result<()> {
+        if self.thread_id(object).is_some() {
+            return self.throw_new("java/lang/IllegalThreadStateException", None);
+        }
+
+        let thread_class = self.resolve_class("java/lang/Thread")?;
+        let run = thread_class.resolve_method("run", "()V")?;
+        let run = self.class_of(object)?.select_method(&run)?;
+        // `Thread.run` calls the target, which is run directly so that it is on the thread's
+        // own stack
+        let (method, receiver) = if Rc::ptr_eq(&run.class(), &thread_class) {
+            match self.get_field(object, "target", "Ljava/lang/Runnable;")? {
+                Value::Reference(target) => {
+                    let runnable = self.resolve_class("java/lang/Runnable")?;
+                    let run = runnable.resolve_interface_method("run", "()V")?;
+                    let run = self.class_of(target)?.select_interface_method(&run)?;
+                    (Some(run), target)
+                }
+                _ => (None, object),
+            }
+        } else {
+            (Some(run), object)
+        };
+
+        let name = match self.get_field(object, "name", "Ljava/lang/String;")? {
+            Value::Reference(name) => self.read_string(name)?,
+            _ => String::new(),
+        };
+        let daemon = self.get_field(object, "daemon", "Z")?.as_int()? != 0;
+        let (frames, state) = match method {
+            Some(method) if method.code().is_some() => {
+                let frame = StackFrame::for_method(&method, vec![receiver.into()])?;
+                self.profile_call(&method);
+                (vec![frame], ThreadState::Runnable)
+            }
+            Some(method) => return Err(Self::missing_body(&method)),
+            // there is nothing to run
+            None => (vec![], ThreadState::Terminated),
+        };
+        self.threads.push(Thread {
+            name,
+            object: Some(object),
+            frames,
+            state,
+            daemon,
+        });
+        Ok(())
+    }
+
+    /// The `java/lang/Thread` of the running thread
+    pub(crate) fn current_thread(&mut self) -> Result<Option<heap::Reference>> {
+        if let Some(object) = self.threads[self.current].object {
+            return Ok(Some(object));
+        }
+        let name = self.threads[self.current].name.clone();
+        let name = self.new_string(&name)?;
+        let object = self.new_object(
+            "java/lang/Thread",
+            "(Ljava/lang/String;)V",
+            vec![name.into()],
+        )?;
+        self.threads[self.current].object = object;
+        Ok(object)
+    }
+
+    /// Whether the `java/lang/Thread` `object` has been started and hasn't finished
+    pub(crate) fn is_alive(&self, object: heap::Reference) -> bool {
+        match self.thread_id(object) {
+            Some(id) => self.threads[id].state != ThreadState::Terminated,
+            None => false,
+        }
+    }
+
+    /// Makes the running thread wait for the `java/lang/Thread` `object` to finish, or for
+    /// `millis` if that isn't 0
+    pub(crate) fn join_thread(&mut self, object: heap::Reference, millis: i64) -> Result<()> {
+        if millis < 0 {
+            return self.throw_new(
+                "java/lang/IllegalArgumentException",
+                Some("timeout value is negative"),
+            );
+        }
+        if let Some(thread) = self.thread_id(object) {
+            if thread == self.current || self.threads[thread].state == ThreadState::Terminated {
+                return Ok(());
+            }
+            let until = self.deadline_after(millis);
+            self.threads[self.current].state = ThreadState::Joining { thread, until };
+        }
+        Ok(())
+    }
+
+    /// Puts the running thread to sleep for `millis` of virtual time
+    pub(crate) fn sleep(&mut self, millis: i64) -> Result<()> {
+        if millis < 0 {
+            return self.throw_new(
+                "java/lang/IllegalArgumentException",
+                Some("timeout value is negative"),
+            );
+        }
+        let until = self.scheduler.clock + millis as u64 * 1_000_000;
+        self.threads[self.current].state = ThreadState::Sleeping(until);
+        Ok(())
+    }
+
+    /// The wall clock time, which moves with the virtual clock
+    pub(crate) fn wall_clock(&self) -> SystemTime {
+        self.scheduler.started.0 + Duration::from_nanos(self.scheduler.clock)
+    }
+
+    /// The monotonic time since a fixed origin, which moves with the virtual clock
+    pub(crate) fn monotonic_clock(&self) -> Duration {
+        let now = self.scheduler.started.1 + Duration::from_nanos(self.scheduler.clock);
+        now.duration_since(origin())
+    }
+
+    /// Gives up the rest of the running thread's time slice
+    pub(crate) fn yield_thread(&mut self) {
+        self.scheduler.slice = 0;
+    }
+
+    /// `Object.wait`: gives up the monitor of `object` until another thread notifies it, or
+    /// `millis` pass if that isn't 0
+    pub(crate) fn wait(&mut self, object: heap::Reference, millis: i64) -> Result<()> {
+        if millis < 0 {
+            return self.throw_new(
+                "java/lang/IllegalArgumentException",
+                Some("timeout value is negative"),
+            );
+        }
+        let count = match self.monitors.get(&object) {
+            Some(monitor) if monitor.owner == self.current => monitor.count,
+            _ => return self.not_owner(),
+        };
+        self.monitors.remove(&object);
+        let until = self.deadline_after(millis);
+        self.threads[self.current].state = ThreadState::Waiting {
+            object,
+            count,
+            until,
+        };
+        Ok(())
+    }
+
+    /// `Object.notify` and `notifyAll`: wakes up the threads waiting on `object`, which get
+    /// its monitor back once the running thread lets go of it
+    pub(crate) fn notify(&mut self, object: heap::Reference, all: bool) -> Result<()> {
+        match self.monitors.get(&object) {
+            Some(monitor) if monitor.owner == self.current => {}
+            _ => return self.not_owner(),
+        }
+        for thread in &mut self.threads {
+            if let ThreadState::Waiting {
+                object: o, count, ..
+            } = thread.state
+            {
+                if o == object {
+                    thread.state = ThreadState::Reentering { object, count };
+                    if !all {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn not_owner(&mut self) -> Result<()> {
+        self.throw_new(
+            "java/lang/IllegalMonitorStateException",
+            Some("current thread is not owner"),
+        )
+    }
+
+    /// The deadline `millis` from now, where 0 is never
+    fn deadline_after(&self, millis: i64) -> Option<u64> {
+        match millis {
+            0 => None,
+            millis => Some(self.scheduler.clock + millis as u64 * 1_000_000),
+        }
+    }
+
+    /// The references the other threads hold, for garbage collection
+    pub(super) fn thread_roots(&self) -> impl Iterator<Item = heap::Reference> + '_ {
+        let frames = self.threads.iter().flat_map(|thread| &thread.frames);
+        let values = frames
+            .flat_map(|frame| frame.local_variables.iter().chain(&frame.stack))
+            .filter_map(|value| match value {
+                Value::Reference(reference) => Some(*reference),
+                _ => None,
+            });
+        let waiting = self.threads.iter().filter_map(|thread| match thread.state {
+            ThreadState::Blocked(object)
+            | ThreadState::Waiting { object, .. }
+            | ThreadState::Reentering { object, .. } => Some(object),
+            _ => None,
+        });
+        values
+            .chain(self.threads.iter().filter_map(|thread| thread.object))
+            .chain(waiting)
+            .chain(self.monitors.keys().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::stream::Capture;
+    use crate::test_utils::load_classes;
+
+    fn load() -> Interpreter {
+        load_classes(&[
+            "threads/Threads",
+            "threads/Threads$1",
+            "threads/Threads$Counter",
+            "threads/Threads$Queue",
+            "threads/Threads$Locker",
+            "threads/Threads$Worker",
+        ])
+    }
+
+    #[test]
+    fn synchronized_counters() {
+        let mut vm = load();
+        assert_eq!(
+            vm.invoke::<_, i32>("Threads", "counters", ()).unwrap(),
+            40000
+        );
+        assert_eq!(vm.invoke::<_, i32>("Threads", "blocks", ()).unwrap(), 40000);
+    }
+
+    #[test]
+    fn wait_and_notify() {
+        let mut vm = load();
+        let sum = vm
+            .invoke::<_, i32>("Threads", "producerConsumer", ())
+            .unwrap();
+        assert_eq!(sum, (1..=100).sum::<i32>());
+        let s: String = vm.invoke("Threads", "notOwner", ()).unwrap();
+        assert_eq!(s, "java.lang.IllegalMonitorStateException");
+    }
+
+    #[test]
+    fn sleep_and_join() {
+        let mut vm = load();
+        let s: String = vm.invoke("Threads", "sleepers", ()).unwrap();
+        assert_eq!(s, "abc");
+        assert!(vm.invoke::<_, bool>("Threads", "joined", ()).unwrap());
+    }
+
+    #[test]
+    fn sleep_advances_clock() {
+        let mut vm = load();
+        assert!(vm
+            .invoke::<_, bool>("Threads", "sleepAdvancesClock", ())
+            .unwrap());
+    }
+
+    #[test]
+    fn deadlock() {
+        let mut vm = load();
+        match vm.invoke::<_, ()>("Threads", "deadlock", ()) {
+            Err(Error::Deadlock(dump)) => {
+                assert!(dump.contains("\"main\" waiting for \"Thread-0\" to finish"));
+                assert!(dump.contains("\"Thread-0\" waiting to lock"));
+                assert!(dump.contains("\"Thread-1\" waiting to lock"));
+                assert!(dump.contains("\tat Threads$Locker.run(Threads.java:"));
+            }
+            other => panic!("expected a deadlock, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deadlock_in_main() {
+        let mut vm = load();
+        match vm.run_main("Threads") {
+            Err(Error::Deadlock(dump)) => {
+                assert!(dump.contains("\"main\" waiting for \"Thread-0\" to finish"));
+            }
+            other => panic!("expected a deadlock, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn uncaught_exception() {
+        let mut vm = load();
+        let stderr = Capture::new();
+        vm.set_stderr(stderr.clone());
+        assert!(vm.invoke::<_, bool>("Threads", "uncaught", ()).unwrap());
+        assert!(stderr
+            .contents()
+            .starts_with("Exception in thread \"worker\" java.lang.IllegalStateException: boom"));
+    }
+
+    #[test]
+    fn reproducible_interleaving() {
+        let order = |seed| {
+            let mut vm = load();
+            vm.set_scheduler_seed(Some(seed));
+            vm.invoke::<_, String>("Threads", "interleaving", ())
+                .unwrap()
+        };
+        let first = order(7);
+        assert_eq!(first, order(7));
+        assert!((1..20).any(|seed| order(seed) != first));
+        assert_eq!(first.len(), 30);
+    }
+}
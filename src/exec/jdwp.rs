@@ -0,0 +1,1626 @@
+//! A debug server speaking enough of the Java Debug Wire Protocol for `jdb` and IDEs to
+//! attach over a socket: breakpoints, stepping, field watchpoints, and looking at threads,
+//! frames, locals and objects. The client drives a `Debugger`, so the program stops for it
+//! the way it stops for any other handler. Green threads all stop together, so suspending
+//! one thread suspends the whole VM
+//!
+//! ```ignore
+//! let listener = TcpListener::bind("127.0.0.1:5005")?;
+//! let session = Session::accept(&listener)?;
+//! session.attach(&mut vm)?;
+//! let result = vm.run_main("com/acme/Main");
+//! session.finish()?;
+//! ```
+// https://docs.oracle.com/javase/8/docs/platform/jpda/jdwp/jdwp-protocol.html
+mod packet;
+
+use packet::*;
+pub use packet::{Data, DataReader, ErrorCode, Packet, HANDSHAKE};
+
+use super::class::{Class, Field, Method};
+use super::debug::{Breakpoint, Debugger, FrameInfo, Resume, Step, Stop, ThreadStatus, Unit};
+use super::heap::{Object, Reference};
+use super::interpreter::Interpreter;
+use super::value::Value;
+use super::*;
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+
+// command sets
+const VIRTUAL_MACHINE: u8 = 1;
+const REFERENCE_TYPE: u8 = 2;
+const CLASS_TYPE: u8 = 3;
+const METHOD: u8 = 6;
+const OBJECT_REFERENCE: u8 = 9;
+const STRING_REFERENCE: u8 = 10;
+const THREAD_REFERENCE: u8 = 11;
+const THREAD_GROUP_REFERENCE: u8 = 12;
+const ARRAY_REFERENCE: u8 = 13;
+const EVENT_REQUEST: u8 = 15;
+const STACK_FRAME: u8 = 16;
+const EVENT: u8 = 64;
+
+// the commands that end a session, and the one event command
+const DISPOSE: u8 = 6;
+const EXIT: u8 = 10;
+const COMPOSITE: u8 = 100;
+
+// event kinds
+const SINGLE_STEP: u8 = 1;
+const BREAKPOINT: u8 = 2;
+const EXCEPTION: u8 = 4;
+const THREAD_START: u8 = 6;
+const THREAD_DEATH: u8 = 7;
+const CLASS_PREPARE: u8 = 8;
+const CLASS_UNLOAD: u8 = 9;
+const FIELD_ACCESS: u8 = 20;
+const FIELD_MODIFICATION: u8 = 21;
+const VM_START: u8 = 90;
+const VM_DEATH: u8 = 99;
+
+const SUSPEND_NONE: u8 = 0;
+const SUSPEND_ALL: u8 = 2;
+
+// type tags
+const CLASS: u8 = 1;
+const INTERFACE: u8 = 2;
+const ARRAY: u8 = 3;
+
+/// Verified, prepared and initialized: classes are linked by the time the client sees them
+const CLASS_STATUS: i32 = 7;
+
+/// Objects are identified by their heap index + 1, and threads and the one thread group
+/// apart from them, since the main thread may have no `java/lang/Thread`
+const THREAD_IDS: u64 = 1 << 48;
+const THREAD_GROUP: u64 = 1 << 49;
+
+/// What the client asked to hear about
+#[derive(Debug)]
+struct Request {
+    id: i32,
+    kind: u8,
+    suspend: u8,
+    /// Only reported the `count`th time it happens, after which the request expires
+    count: Option<i32>,
+    thread: Option<usize>,
+    /// Class name patterns like `java.*`, and whether they exclude rather than match
+    classes: Vec<(String, bool)>,
+    target: Target,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Target {
+    /// The debugger breakpoint that reports it
+    Breakpoint(usize),
+    /// The debugger breakpoint of a watched field, and the field's ids
+    Field {
+        breakpoint: usize,
+        class: u64,
+        field: u64,
+    },
+    Step(Step),
+    /// Events this VM never sends, like classes being loaded after it started
+    Never,
+}
+
+#[derive(Debug)]
+struct State {
+    writer: TcpStream,
+    /// The stream and channel for the thread that reads commands, until it is started
+    reader: Option<(TcpStream, Sender<Packet>)>,
+    packets: Receiver<Packet>,
+    /// Reference types by their id - 1, as internal names
+    types: Vec<String>,
+    requests: Vec<Request>,
+    /// The last request id, and packet id of an event
+    last_id: i32,
+    /// How many times the VM was suspended and not resumed yet
+    suspended: u32,
+    started: bool,
+    /// Whether the client has gone, or let go of the VM
+    detached: bool,
+}
+
+/// A client connected over JDWP
+#[derive(Debug)]
+pub struct Session {
+    state: Rc<RefCell<State>>,
+}
+
+impl Session {
+    /// Waits for a client to connect, and shakes hands with it
+    pub fn accept(listener: &TcpListener) -> Result<Self> {
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    /// Shakes hands with the client on the other end of `stream`
+    pub fn new(mut stream: TcpStream) -> Result<Self> {
+        let mut handshake = [0; HANDSHAKE.len()];
+        stream.read_exact(&mut handshake)?;
+        if handshake != HANDSHAKE {
+            generic_error!(format!(
+                "expected a JDWP handshake, got {:?}",
+                String::from_utf8_lossy(&handshake)
+            ));
+        }
+        stream.write_all(HANDSHAKE)?;
+        stream.set_nodelay(true)?;
+
+        let (sender, packets) = mpsc::channel();
+        Ok(Self {
+            state: Rc::new(RefCell::new(State {
+                writer: stream.try_clone()?,
+                reader: Some((stream, sender)),
+                packets,
+                types: vec![],
+                requests: vec![],
+                last_id: 0,
+                suspended: 0,
+                started: false,
+                detached: false,
+            })),
+        })
+    }
+
+    /// Sets the interpreter's debugger to one the client controls. The VM starts suspended,
+    /// so the program stops before its first instruction until the client resumes it
+    pub fn attach(&self, vm: &mut Interpreter) -> Result<()> {
+        let (mut reader, sender) = match self.state.borrow_mut().reader.take() {
+            Some(reader) => reader,
+            None => generic_error!("the session is already attached"),
+        };
+        let state = Rc::clone(&self.state);
+        let debugger = Debugger::new(move |vm, stop| state.borrow_mut().stopped(vm, stop));
+
+        // commands get to the interpreter at the next instruction, even while it runs
+        let pause = debugger.pause_flag();
+        std::thread::spawn(move || {
+            while let Ok(packet) = Packet::read(&mut reader) {
+                if sender.send(packet).is_err() {
+                    break;
+                }
+                pause.store(true, Ordering::SeqCst);
+            }
+            drop(sender);
+            pause.store(true, Ordering::SeqCst);
+        });
+        vm.set_debugger(Some(debugger));
+        Ok(())
+    }
+
+    /// Tells the client the program has ended, and hangs up
+    pub fn finish(&self) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        if state.detached {
+            return Ok(());
+        }
+        state.detached = true;
+        let mut event = Data::default();
+        event.byte(VM_DEATH).int(0);
+        let sent = state.send_events(SUSPEND_NONE, &[event]);
+        let _ = state.writer.shutdown(Shutdown::Both);
+        sent
+    }
+}
+
+impl State {
+    /// Tells the client why the VM stopped, and answers its commands until it resumes the
+    /// VM, or right away if nothing suspended it
+    fn stopped(&mut self, vm: &mut Interpreter, stop: &Stop) -> Result<Resume> {
+        let thread = vm.running_thread();
+        let mut events = vec![];
+        let mut policy = SUSPEND_NONE;
+        if !self.started {
+            // the debugger stops before the first instruction
+            self.started = true;
+            let mut event = Data::default();
+            event.byte(VM_START).int(0).id(thread_id(thread));
+            events.push(event);
+            policy = SUSPEND_ALL;
+        } else {
+            for index in self.hits(vm, stop) {
+                let request = &self.requests[index];
+                let (kind, id, suspend, target) =
+                    (request.kind, request.id, request.suspend, request.target);
+                let mut event = Data::default();
+                event.byte(kind).int(id).id(thread_id(thread));
+                if let Some(frame) = vm.call_stack(thread).first() {
+                    let location = self.location(vm, frame).map_err(jdwp_error)?;
+                    event.data(&location);
+                }
+                if let (Target::Field { class, field, .. }, Stop::Field { object, value, .. }) =
+                    (target, stop)
+                {
+                    let tag = self.type_tag(vm, class).map_err(jdwp_error)?;
+                    event.byte(tag).id(class).id(field);
+                    match object {
+                        Some(object) => event.byte(object_tag(vm, *object)),
+                        None => event.byte(b'L'),
+                    };
+                    event.id(object.map_or(0, |object| object_id(vm, object)));
+                    if let Some(value) = value {
+                        event.data(&tagged(vm, *value, value_tag(vm, *value)));
+                    }
+                }
+                policy = policy.max(suspend);
+                events.push(event);
+            }
+            self.expire(vm);
+        }
+
+        if !events.is_empty() {
+            if self.send_events(policy, &events).is_err() {
+                return Ok(self.detach(vm));
+            }
+            if policy != SUSPEND_NONE {
+                self.suspended += 1;
+            }
+        }
+        self.serve(vm, *stop == Stop::Pause)
+    }
+
+    /// The requests that report `stop`, counting down the ones that only report a later
+    /// time
+    fn hits(&mut self, vm: &Interpreter, stop: &Stop) -> Vec<usize> {
+        let thread = vm.running_thread();
+        let class = match vm.call_stack(thread).first() {
+            Some(frame) => frame.class.replace('/', "."),
+            None => return vec![],
+        };
+        let mut hits = vec![];
+        for (index, request) in self.requests.iter_mut().enumerate() {
+            let hit = match (&request.target, stop) {
+                (Target::Breakpoint(breakpoint), Stop::Breakpoint(id)) => breakpoint == id,
+                (Target::Field { breakpoint, .. }, Stop::Field { id, value, .. }) => {
+                    breakpoint == id && (request.kind == FIELD_MODIFICATION) == value.is_some()
+                }
+                (Target::Step(..), Stop::Step) => true,
+                _ => false,
+            };
+            let classes = request
+                .classes
+                .iter()
+                .all(|(pattern, exclude)| matches_class(pattern, &class) != *exclude);
+            if !hit || !classes || request.thread.is_some_and(|t| t != thread) {
+                continue;
+            }
+            match &mut request.count {
+                Some(count) if *count > 1 => *count -= 1,
+                Some(count) => {
+                    *count = 0;
+                    hits.push(index);
+                }
+                None => hits.push(index),
+            }
+        }
+        hits
+    }
+
+    /// Removes the requests that have counted down
+    fn expire(&mut self, vm: &mut Interpreter) {
+        while let Some(index) = self.requests.iter().position(|r| r.count == Some(0)) {
+            self.remove(vm, index);
+        }
+    }
+
+    fn remove(&mut self, vm: &mut Interpreter, index: usize) {
+        let request = self.requests.remove(index);
+        let breakpoint = match request.target {
+            Target::Breakpoint(breakpoint) | Target::Field { breakpoint, .. } => breakpoint,
+            _ => return,
+        };
+        if let Some(debugger) = vm.debugger() {
+            debugger.remove_breakpoint(breakpoint);
+        }
+    }
+
+    /// Lets go of the VM, which runs on without a debugger
+    fn detach(&mut self, vm: &mut Interpreter) -> Resume {
+        self.detached = true;
+        self.requests.clear();
+        self.suspended = 0;
+        vm.set_debugger(None);
+        Resume::Continue
+    }
+
+    fn send_events(&mut self, policy: u8, events: &[Data]) -> Result<()> {
+        let mut data = Data::default();
+        data.byte(policy).int(events.len() as i32);
+        for event in events {
+            data.data(event);
+        }
+        self.last_id += 1;
+        let packet = Packet::Command {
+            id: self.last_id as u32,
+            set: EVENT,
+            command: COMPOSITE,
+            data: data.0,
+        };
+        packet.write(&mut self.writer).map_err(Into::into)
+    }
+
+    /// Answers commands while the VM is suspended, and those already sent otherwise. A pause
+    /// goes on the way it was going unless the client suspended and resumed the VM
+    fn serve(&mut self, vm: &mut Interpreter, paused: bool) -> Result<Resume> {
+        let mut resumed = false;
+        loop {
+            let packet = if self.suspended > 0 {
+                self.packets.recv().ok()
+            } else {
+                match self.packets.try_recv() {
+                    Ok(packet) => Some(packet),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => None,
+                }
+            };
+            let (id, set, command, data) = match packet {
+                Some(Packet::Command {
+                    id,
+                    set,
+                    command,
+                    data,
+                }) => (id, set, command, data),
+                Some(Packet::Reply { .. }) => continue,
+                None => return Ok(self.detach(vm)),
+            };
+
+            let suspended = self.suspended > 0;
+            let (error, data) = match self.command(vm, set, command, &mut DataReader(&data)) {
+                Ok(data) => (0, data.0),
+                Err(error) => (error, vec![]),
+            };
+            let reply = Packet::Reply { id, error, data };
+            if reply.write(&mut self.writer).is_err() {
+                return Ok(self.detach(vm));
+            }
+            match (set, command) {
+                (VIRTUAL_MACHINE, DISPOSE) => return Ok(self.detach(vm)),
+                (VIRTUAL_MACHINE, EXIT) => return Ok(Resume::Quit),
+                _ => resumed |= suspended && self.suspended == 0,
+            }
+        }
+
+        let step = self
+            .requests
+            .iter()
+            .find_map(|request| match request.target {
+                Target::Step(step) => Some(step),
+                _ => None,
+            });
+        Ok(match step {
+            Some(step) if resumed || !paused => Resume::Step(step),
+            _ => Resume::Continue,
+        })
+    }
+
+    fn command(
+        &mut self,
+        vm: &mut Interpreter,
+        set: u8,
+        command: u8,
+        args: &mut DataReader,
+    ) -> std::result::Result<Data, ErrorCode> {
+        let mut data = Data::default();
+        match (set, command) {
+            // Version
+            (VIRTUAL_MACHINE, 1) => {
+                data.string("watertower bytecode interpreter")
+                    .int(1)
+                    .int(8)
+                    .string("1.8.0")
+                    .string("watertower");
+            }
+            // ClassesBySignature
+            (VIRTUAL_MACHINE, 2) => {
+                let signature = args.string()?;
+                let name = signature
+                    .strip_prefix('L')
+                    .and_then(|name| name.strip_suffix(';'));
+                let loaded = name.filter(|name| vm.class_names().iter().any(|n| n == name));
+                match loaded {
+                    Some(name) => {
+                        let id = self.type_id(name);
+                        data.int(1).byte(self.type_tag(vm, id)?).id(id);
+                        data.int(CLASS_STATUS);
+                    }
+                    None => {
+                        data.int(0);
+                    }
+                }
+            }
+            // AllClasses, AllClassesWithGeneric
+            (VIRTUAL_MACHINE, 3) | (VIRTUAL_MACHINE, 20) => {
+                let mut classes = vec![];
+                for name in vm.class_names() {
+                    let id = self.type_id(&name);
+                    // classes that fail to link aren't there as far as the client can see
+                    if let Ok(tag) = self.type_tag(vm, id) {
+                        classes.push((tag, id, signature(&name)));
+                    }
+                }
+                data.int(classes.len() as i32);
+                for (tag, id, signature) in classes {
+                    data.byte(tag).id(id).string(&signature);
+                    if command == 20 {
+                        data.string("");
+                    }
+                    data.int(CLASS_STATUS);
+                }
+            }
+            // AllThreads
+            (VIRTUAL_MACHINE, 4) => {
+                let threads = vm.thread_list();
+                data.int(threads.len() as i32);
+                for thread in threads {
+                    data.id(thread_id(thread.id));
+                }
+            }
+            // TopLevelThreadGroups
+            (VIRTUAL_MACHINE, 5) => {
+                data.int(1).id(THREAD_GROUP);
+            }
+            // Dispose
+            (VIRTUAL_MACHINE, DISPOSE) => {
+                while !self.requests.is_empty() {
+                    self.remove(vm, 0);
+                }
+            }
+            // IDSizes
+            (VIRTUAL_MACHINE, 7) => {
+                data.int(8).int(8).int(8).int(8).int(8);
+            }
+            // Suspend
+            (VIRTUAL_MACHINE, 8) => self.suspended += 1,
+            // Resume
+            (VIRTUAL_MACHINE, 9) => self.suspended = self.suspended.saturating_sub(1),
+            // Exit
+            (VIRTUAL_MACHINE, EXIT) => {
+                args.int()?;
+            }
+            // CreateString
+            (VIRTUAL_MACHINE, 11) => {
+                let string = vm.new_string(&args.string()?).map_err(internal)?;
+                // the client holds on to it
+                vm.pin(string);
+                data.id(object_id(vm, string));
+            }
+            // Capabilities, CapabilitiesNew: watching fields, getting bytecodes and hearing
+            // about the VM's death
+            (VIRTUAL_MACHINE, 12) | (VIRTUAL_MACHINE, 17) => {
+                let count = if command == 12 { 7 } else { 32 };
+                for capability in 0..count {
+                    data.boolean(matches!(capability, 0..=2 | 13));
+                }
+            }
+            // ClassPaths
+            (VIRTUAL_MACHINE, 13) => {
+                data.string("").int(0).int(0);
+            }
+            // DisposeObjects, HoldEvents, ReleaseEvents
+            (VIRTUAL_MACHINE, 14) | (VIRTUAL_MACHINE, 15) | (VIRTUAL_MACHINE, 16) => {}
+
+            (REFERENCE_TYPE, _) => {
+                let id = args.id()?;
+                self.reference_type(vm, command, id, args, &mut data)?;
+            }
+            // Superclass
+            (CLASS_TYPE, 1) => {
+                let class = self.class(vm, args.id()?)?;
+                match class.and_then(|class| class.super_class.clone()) {
+                    Some(super_class) => data.id(self.type_id(&super_class.name)),
+                    None => data.id(0),
+                };
+            }
+            // InvokeMethod
+            (CLASS_TYPE, 3) => {
+                args.id()?;
+                thread(vm, args.id()?)?;
+                let method = args.id()?;
+                self.invoke(vm, None, method, args, &mut data)?;
+            }
+            (METHOD, _) => {
+                args.id()?;
+                let (class, method) = self.method(vm, args.id()?)?;
+                self.method_command(command, &class, &method, &mut data)?;
+            }
+
+            (OBJECT_REFERENCE, _) => {
+                let id = args.id()?;
+                self.object_reference(vm, command, id, args, &mut data)?;
+            }
+            // Value
+            (STRING_REFERENCE, 1) => {
+                let string = object(vm, args.id()?)?.ok_or(INVALID_OBJECT)?;
+                data.string(&vm.read_string(string).map_err(|_| INVALID_STRING)?);
+            }
+            (THREAD_REFERENCE, _) => {
+                let thread = thread(vm, args.id()?)?;
+                self.thread_reference(vm, command, thread, args, &mut data)?;
+            }
+            (THREAD_GROUP_REFERENCE, _) => {
+                if args.id()? != THREAD_GROUP {
+                    return Err(INVALID_THREAD_GROUP);
+                }
+                match command {
+                    // Name
+                    1 => data.string("main"),
+                    // Parent
+                    2 => data.id(0),
+                    // Children
+                    3 => {
+                        let threads = vm.thread_list();
+                        data.int(threads.len() as i32);
+                        for thread in threads {
+                            data.id(thread_id(thread.id));
+                        }
+                        data.int(0)
+                    }
+                    _ => return Err(NOT_IMPLEMENTED),
+                };
+            }
+            (ARRAY_REFERENCE, _) => {
+                let array = object(vm, args.id()?)?.ok_or(INVALID_OBJECT)?;
+                let array = vm.heap().array(array).map_err(|_| INVALID_ARRAY)?;
+                match command {
+                    // Length
+                    1 => {
+                        data.int(array.elements.len() as i32);
+                    }
+                    // GetValues
+                    2 => {
+                        let (first, length) = (args.int()?, args.int()?);
+                        let start = usize::try_from(first).map_err(|_| INVALID_INDEX)?;
+                        let end = start + usize::try_from(length).map_err(|_| INVALID_INDEX)?;
+                        let elements = array.elements.get(start..end).ok_or(INVALID_INDEX)?;
+                        let tag = array.component.to_string().as_bytes()[0];
+                        data.byte(tag).int(length);
+                        for &element in elements {
+                            match tag {
+                                b'L' | b'[' => data.data(&tagged(vm, element, tag)),
+                                _ => {
+                                    untagged(&mut data, element, tag);
+                                    &mut data
+                                }
+                            };
+                        }
+                    }
+                    _ => return Err(NOT_IMPLEMENTED),
+                }
+            }
+            // Set
+            (EVENT_REQUEST, 1) => {
+                let request = self.request(vm, args)?;
+                data.int(request.id);
+                self.requests.push(request);
+            }
+            // Clear
+            (EVENT_REQUEST, 2) => {
+                let (kind, id) = (args.byte()?, args.int()?);
+                let index = self
+                    .requests
+                    .iter()
+                    .position(|r| r.kind == kind && r.id == id);
+                if let Some(index) = index {
+                    self.remove(vm, index);
+                }
+            }
+            // ClearAllBreakpoints
+            (EVENT_REQUEST, 3) => {
+                while let Some(index) = self.requests.iter().position(|r| r.kind == BREAKPOINT) {
+                    self.remove(vm, index);
+                }
+            }
+            (STACK_FRAME, _) => {
+                let thread = thread(vm, args.id()?)?;
+                let depth = frame(vm, thread, args.id()?)?;
+                self.stack_frame(vm, command, thread, depth, args, &mut data)?;
+            }
+            _ => return Err(NOT_IMPLEMENTED),
+        }
+        Ok(data)
+    }
+
+    fn reference_type(
+        &mut self,
+        vm: &mut Interpreter,
+        command: u8,
+        id: u64,
+        args: &mut DataReader,
+        data: &mut Data,
+    ) -> std::result::Result<(), ErrorCode> {
+        let name = self.type_name(id)?.to_string();
+        let class = self.class(vm, id)?;
+        let fields = class.as_ref().map_or(&[][..], |class| &class.fields[..]);
+        let methods = class.as_ref().map_or(&[][..], |class| &class.methods[..]);
+        match command {
+            // Signature, SignatureWithGeneric
+            1 | 13 => {
+                data.string(&signature(&name));
+                if command == 13 {
+                    data.string("");
+                }
+            }
+            // ClassLoader: the bootstrap loader
+            2 => {
+                data.id(0);
+            }
+            // Modifiers
+            3 => {
+                let flags = match &class {
+                    Some(class) => i32::from(class.flags.bits()),
+                    // public final abstract
+                    None => 0x0411,
+                };
+                data.int(flags);
+            }
+            // Fields, FieldsWithGeneric
+            4 | 14 => {
+                data.int(fields.len() as i32);
+                for (index, field) in fields.iter().enumerate() {
+                    data.id(member_id(id, index))
+                        .string(&field.name)
+                        .string(&field.descriptor);
+                    if command == 14 {
+                        data.string("");
+                    }
+                    data.int(i32::from(field.flags.bits()));
+                }
+            }
+            // Methods, MethodsWithGeneric
+            5 | 15 => {
+                data.int(methods.len() as i32);
+                for (index, method) in methods.iter().enumerate() {
+                    data.id(member_id(id, index))
+                        .string(&method.name)
+                        .string(&method.descriptor);
+                    if command == 15 {
+                        data.string("");
+                    }
+                    data.int(i32::from(method.flags.bits()));
+                }
+            }
+            // GetValues of static fields
+            6 => {
+                let count = args.int()?;
+                data.int(count);
+                for _ in 0..count {
+                    let (class, field) = self.field(vm, args.id()?)?;
+                    if !field.is_static() {
+                        return Err(INVALID_FIELDID);
+                    }
+                    let value = class.statics.borrow()[field.slot];
+                    data.data(&tagged(vm, value, field.descriptor.as_bytes()[0]));
+                }
+            }
+            // SourceFile
+            7 => {
+                let file = class.as_ref().and_then(|class| class.file.as_ref());
+                let source = file.and_then(|file| file.source_file());
+                data.string(source.ok_or(ABSENT_INFORMATION)?);
+            }
+            // Status
+            9 => {
+                data.int(CLASS_STATUS);
+            }
+            // Interfaces
+            10 => {
+                let interfaces = class
+                    .as_ref()
+                    .map_or(&[][..], |class| &class.interfaces[..]);
+                data.int(interfaces.len() as i32);
+                for interface in interfaces {
+                    data.id(self.type_id(&interface.name));
+                }
+            }
+            // ClassObject
+            11 => {
+                let mirror = vm.class_mirror(&name).map_err(internal)?;
+                data.id(object_id(vm, mirror));
+            }
+            // SourceDebugExtension
+            12 => return Err(ABSENT_INFORMATION),
+            _ => return Err(NOT_IMPLEMENTED),
+        }
+        Ok(())
+    }
+
+    fn method_command(
+        &mut self,
+        command: u8,
+        class: &Class,
+        method: &Method,
+        data: &mut Data,
+    ) -> std::result::Result<(), ErrorCode> {
+        let code = method.code();
+        let attributes = code.map_or(&[][..], |code| &code.attributes[..]);
+        match command {
+            // LineTable
+            1 => {
+                let code = match code {
+                    Some(code) => code,
+                    None => {
+                        data.long(-1).long(-1).int(0);
+                        return Ok(());
+                    }
+                };
+                let lines = attributes
+                    .iter()
+                    .filter_map(|attribute| match attribute {
+                        attr::Attribute::LineNumberTable(table) => Some(&table.table),
+                        _ => None,
+                    })
+                    .flatten()
+                    .collect::<Vec<_>>();
+                data.long(0).long(code.code.len() as i64 - 1);
+                data.int(lines.len() as i32);
+                for (pc, line) in lines {
+                    data.long(i64::from(*pc)).int(i32::from(*line));
+                }
+            }
+            // VariableTable, VariableTableWithGeneric
+            2 | 5 => {
+                let file = class.class_file().map_err(|_| ABSENT_INFORMATION)?;
+                let variables = attributes
+                    .iter()
+                    .filter_map(|attribute| match attribute {
+                        attr::Attribute::LocalVariableTable(table) => Some(&table.variables),
+                        _ => None,
+                    })
+                    .flatten()
+                    .collect::<Vec<_>>();
+                if variables.is_empty() {
+                    return Err(ABSENT_INFORMATION);
+                }
+                let params = method.signature.params.iter();
+                let wide =
+                    |ty: &&ty::FieldType| matches!(ty, ty::FieldType::Long | ty::FieldType::Double);
+                let slots = params.clone().count() + params.filter(wide).count();
+                data.int((slots + !method.is_static() as usize) as i32);
+                data.int(variables.len() as i32);
+                for variable in variables {
+                    let name = file.utf8(variable.name).map_err(internal)?;
+                    let descriptor = file.utf8(variable.descriptor).map_err(internal)?;
+                    data.long(i64::from(variable.start_pc))
+                        .string(name)
+                        .string(descriptor);
+                    if command == 5 {
+                        data.string("");
+                    }
+                    data.int(i32::from(variable.length))
+                        .int(i32::from(variable.index));
+                }
+            }
+            // Bytecodes
+            3 => {
+                let bytes = code.map_or(&[][..], |code| &code.code[..]);
+                data.int(bytes.len() as i32);
+                data.0.extend_from_slice(bytes);
+            }
+            // IsObsolete
+            4 => {
+                data.boolean(false);
+            }
+            _ => return Err(NOT_IMPLEMENTED),
+        }
+        Ok(())
+    }
+
+    fn object_reference(
+        &mut self,
+        vm: &mut Interpreter,
+        command: u8,
+        id: u64,
+        args: &mut DataReader,
+        data: &mut Data,
+    ) -> std::result::Result<(), ErrorCode> {
+        // the main thread only gets a `java/lang/Thread` once it asks for it
+        let reference = match object(vm, id) {
+            Err(INVALID_OBJECT) if thread(vm, id).is_ok() => None,
+            reference => Some(reference?.ok_or(INVALID_OBJECT)?),
+        };
+        match command {
+            // ReferenceType
+            1 => {
+                let name = match reference {
+                    Some(reference) => vm.heap().get(reference).class_name(),
+                    None => "java/lang/Thread".to_string(),
+                };
+                let id = self.type_id(&name);
+                data.byte(self.type_tag(vm, id)?).id(id);
+            }
+            // GetValues
+            2 => {
+                let count = args.int()?;
+                data.int(count);
+                for _ in 0..count {
+                    let (class, field) = self.field(vm, args.id()?)?;
+                    let value = if field.is_static() {
+                        class.statics.borrow()[field.slot]
+                    } else {
+                        let instance = reference.ok_or(INVALID_OBJECT)?;
+                        let instance = vm.heap().instance(instance).map_err(|_| INVALID_OBJECT)?;
+                        *instance.fields.get(field.slot).ok_or(INVALID_FIELDID)?
+                    };
+                    data.data(&tagged(vm, value, field.descriptor.as_bytes()[0]));
+                }
+            }
+            // InvokeMethod
+            6 => {
+                thread(vm, args.id()?)?;
+                args.id()?;
+                let method = args.id()?;
+                let object = reference.ok_or(INVALID_OBJECT)?;
+                self.invoke(vm, Some(object), method, args, data)?;
+            }
+            // DisableCollection, EnableCollection
+            7 => reference.into_iter().for_each(|r| vm.pin(r)),
+            8 => reference.into_iter().for_each(|r| vm.unpin(r)),
+            // IsCollected
+            9 => {
+                data.boolean(false);
+            }
+            _ => return Err(NOT_IMPLEMENTED),
+        }
+        Ok(())
+    }
+
+    fn thread_reference(
+        &mut self,
+        vm: &mut Interpreter,
+        command: u8,
+        thread: usize,
+        args: &mut DataReader,
+        data: &mut Data,
+    ) -> std::result::Result<(), ErrorCode> {
+        let info = vm.thread_list().into_iter().find(|info| info.id == thread);
+        let info = info.ok_or(INVALID_THREAD)?;
+        match command {
+            // Name
+            1 => {
+                data.string(&info.name);
+            }
+            // Suspend, Resume: threads only run together
+            2 => self.suspended += 1,
+            3 => self.suspended = self.suspended.saturating_sub(1),
+            // Status
+            4 => {
+                let status = match info.status {
+                    ThreadStatus::Running => 1,
+                    ThreadStatus::Sleeping => 2,
+                    ThreadStatus::Monitor => 3,
+                    ThreadStatus::Waiting => 4,
+                };
+                data.int(status).int((self.suspended > 0) as i32);
+            }
+            // ThreadGroup
+            5 => {
+                data.id(THREAD_GROUP);
+            }
+            // Frames
+            6 => {
+                let (start, length) = (args.int()?, args.int()?);
+                let frames = vm.call_stack(thread);
+                let start = usize::try_from(start).map_err(|_| INVALID_INDEX)?;
+                let end = match length {
+                    -1 => frames.len(),
+                    length => start + usize::try_from(length).map_err(|_| INVALID_INDEX)?,
+                };
+                let frames = frames.get(start..end).ok_or(INVALID_INDEX)?;
+                data.int(frames.len() as i32);
+                for (depth, frame) in (start..).zip(frames) {
+                    data.id(frame_id(thread, depth));
+                    data.data(&self.location(vm, frame)?);
+                }
+            }
+            // FrameCount
+            7 => {
+                data.int(vm.call_stack(thread).len() as i32);
+            }
+            // SuspendCount
+            12 => {
+                data.int(self.suspended as i32);
+            }
+            _ => return Err(NOT_IMPLEMENTED),
+        }
+        Ok(())
+    }
+
+    fn stack_frame(
+        &mut self,
+        vm: &mut Interpreter,
+        command: u8,
+        thread: usize,
+        depth: usize,
+        args: &mut DataReader,
+        data: &mut Data,
+    ) -> std::result::Result<(), ErrorCode> {
+        let locals = vm.locals(thread, depth).ok_or(INVALID_FRAMEID)?;
+        match command {
+            // GetValues
+            1 => {
+                let count = args.int()?;
+                data.int(count);
+                for _ in 0..count {
+                    let slot = usize::try_from(args.int()?).map_err(|_| INVALID_SLOT)?;
+                    let tag = args.byte()?;
+                    let local = locals.get(slot).ok_or(INVALID_SLOT)?;
+                    data.data(&tagged(vm, local.value, tag));
+                }
+            }
+            // ThisObject
+            3 => {
+                let frame = vm.call_stack(thread).swap_remove(depth);
+                let class = vm.resolve_class(&frame.class).map_err(internal)?;
+                let method = class
+                    .find_method(&frame.method, &frame.descriptor)
+                    .ok_or(INVALID_METHODID)?;
+                match locals.first() {
+                    Some(this) if !method.is_static() => data.data(&tagged(vm, this.value, b'L')),
+                    _ => data.byte(b'L').id(0),
+                };
+            }
+            _ => return Err(NOT_IMPLEMENTED),
+        }
+        Ok(())
+    }
+
+    /// Calls a method for the client, on the running thread, and writes what it returned
+    /// and what it threw
+    fn invoke(
+        &mut self,
+        vm: &mut Interpreter,
+        object: Option<Reference>,
+        method: u64,
+        args: &mut DataReader,
+        data: &mut Data,
+    ) -> std::result::Result<(), ErrorCode> {
+        let (class, method) = self.method(vm, method)?;
+        let mut values = vec![];
+        for _ in 0..args.int()? {
+            values.push(read_value(vm, args)?);
+        }
+        // the options only say which threads run, and they all do
+        args.int()?;
+
+        let (name, descriptor) = (&method.name, &method.descriptor);
+        let returned = match object {
+            Some(object) => vm.call_method(object, name, descriptor, values),
+            None => vm.call_static(&class.name, name, descriptor, values),
+        };
+        let tag = match &method.signature.ret {
+            Some(ty) => ty.to_string().as_bytes()[0],
+            None => b'V',
+        };
+        data.data(&tagged(
+            vm,
+            returned.map_err(internal)?.unwrap_or(Value::Top),
+            tag,
+        ));
+        match vm.clear_exception() {
+            Some(exception) => data
+                .byte(object_tag(vm, exception))
+                .id(object_id(vm, exception)),
+            None => data.byte(b'L').id(0),
+        };
+        Ok(())
+    }
+
+    /// Reads the request the client sets
+    fn request(
+        &mut self,
+        vm: &mut Interpreter,
+        args: &mut DataReader,
+    ) -> std::result::Result<Request, ErrorCode> {
+        let (kind, suspend) = (args.byte()?, args.byte()?);
+        let mut request = Request {
+            id: self.last_id + 1,
+            kind,
+            suspend,
+            count: None,
+            thread: None,
+            classes: vec![],
+            target: Target::Never,
+        };
+        let (mut location, mut field, mut step) = (None, None, None);
+        for _ in 0..args.int()? {
+            match args.byte()? {
+                // Count
+                1 => request.count = Some(args.int()?).filter(|count| *count > 0),
+                // ThreadOnly
+                3 => request.thread = Some(thread(vm, args.id()?)?),
+                // ClassOnly
+                4 => {
+                    let name = self.type_name(args.id()?)?.replace('/', ".");
+                    request.classes.push((name, false));
+                }
+                // ClassMatch, ClassExclude
+                5 => request.classes.push((args.string()?, false)),
+                6 => request.classes.push((args.string()?, true)),
+                // LocationOnly
+                7 => {
+                    args.byte()?;
+                    location = Some((args.id()?, args.id()?, args.long()?));
+                }
+                // ExceptionOnly
+                8 => {
+                    args.id()?;
+                    args.boolean()?;
+                    args.boolean()?;
+                }
+                // FieldOnly
+                9 => field = Some((args.id()?, args.id()?)),
+                // Step
+                10 => {
+                    thread(vm, args.id()?)?;
+                    step = Some((args.int()?, args.int()?));
+                }
+                // Conditional, InstanceOnly, SourceNameMatch
+                _ => return Err(NOT_IMPLEMENTED),
+            }
+        }
+
+        request.target = match kind {
+            BREAKPOINT => {
+                let (_, method, pc) = location.ok_or(ILLEGAL_ARGUMENT)?;
+                let (class, method) = self.method(vm, method)?;
+                let length = method.code().map_or(0, |code| code.code.len());
+                let pc = usize::try_from(pc).map_err(|_| INVALID_LOCATION)?;
+                if pc >= length {
+                    return Err(INVALID_LOCATION);
+                }
+                let breakpoint = Breakpoint::Method {
+                    class: class.name.clone(),
+                    method: format!("{}{}", method.name, method.descriptor),
+                    pc: Some(pc),
+                };
+                let debugger = vm.debugger().ok_or(INTERNAL)?;
+                Target::Breakpoint(debugger.add_breakpoint(breakpoint))
+            }
+            FIELD_ACCESS | FIELD_MODIFICATION => {
+                let (class, id) = field.ok_or(ILLEGAL_ARGUMENT)?;
+                let (declaring, field) = self.field(vm, id)?;
+                let breakpoint = Breakpoint::Field {
+                    class: declaring.name.clone(),
+                    field: field.name.clone(),
+                    reads: kind == FIELD_ACCESS,
+                };
+                let debugger = vm.debugger().ok_or(INTERNAL)?;
+                Target::Field {
+                    breakpoint: debugger.add_breakpoint(breakpoint),
+                    class,
+                    field: id,
+                }
+            }
+            SINGLE_STEP => {
+                let unit = match step.ok_or(ILLEGAL_ARGUMENT)? {
+                    (0, _) => Unit::Instruction,
+                    (1, _) => Unit::Line,
+                    _ => return Err(ILLEGAL_ARGUMENT),
+                };
+                Target::Step(match step.map(|(_, depth)| depth) {
+                    Some(0) => Step::Into(unit),
+                    Some(1) => Step::Over(unit),
+                    Some(2) => Step::Out,
+                    _ => return Err(ILLEGAL_ARGUMENT),
+                })
+            }
+            EXCEPTION | THREAD_START | THREAD_DEATH | CLASS_PREPARE | CLASS_UNLOAD | VM_DEATH => {
+                Target::Never
+            }
+            _ => return Err(INVALID_EVENT_TYPE),
+        };
+        self.last_id += 1;
+        Ok(request)
+    }
+
+    /// The id of a reference type, given one if it hasn't got one yet
+    fn type_id(&mut self, name: &str) -> u64 {
+        let index = match self.types.iter().position(|t| t == name) {
+            Some(index) => index,
+            None => {
+                self.types.push(name.to_string());
+                self.types.len() - 1
+            }
+        };
+        index as u64 + 1
+    }
+
+    fn type_name(&self, id: u64) -> std::result::Result<&str, ErrorCode> {
+        let index = usize::try_from(id).map_err(|_| INVALID_CLASS)?;
+        let name = index.checked_sub(1).and_then(|index| self.types.get(index));
+        name.map(String::as_str).ok_or(INVALID_CLASS)
+    }
+
+    /// The class of a reference type, or `None` for array types
+    fn class(
+        &self,
+        vm: &mut Interpreter,
+        id: u64,
+    ) -> std::result::Result<Option<Rc<Class>>, ErrorCode> {
+        match self.type_name(id)? {
+            name if name.starts_with('[') => Ok(None),
+            name => vm.resolve_class(name).map(Some).map_err(|_| INVALID_CLASS),
+        }
+    }
+
+    fn type_tag(&self, vm: &mut Interpreter, id: u64) -> std::result::Result<u8, ErrorCode> {
+        Ok(match self.class(vm, id)? {
+            Some(class) if class.is_interface() => INTERFACE,
+            Some(..) => CLASS,
+            None => ARRAY,
+        })
+    }
+
+    fn method(
+        &self,
+        vm: &mut Interpreter,
+        id: u64,
+    ) -> std::result::Result<(Rc<Class>, Rc<Method>), ErrorCode> {
+        let class = self.class(vm, id >> 32).map_err(|_| INVALID_METHODID)?;
+        let class = class.ok_or(INVALID_METHODID)?;
+        let method = class.methods.get((id & 0xffff_ffff) as usize);
+        let method = Rc::clone(method.ok_or(INVALID_METHODID)?);
+        Ok((class, method))
+    }
+
+    fn field(
+        &self,
+        vm: &mut Interpreter,
+        id: u64,
+    ) -> std::result::Result<(Rc<Class>, Rc<Field>), ErrorCode> {
+        let class = self.class(vm, id >> 32).map_err(|_| INVALID_FIELDID)?;
+        let class = class.ok_or(INVALID_FIELDID)?;
+        let field = class.fields.get((id & 0xffff_ffff) as usize);
+        let field = Rc::clone(field.ok_or(INVALID_FIELDID)?);
+        Ok((class, field))
+    }
+
+    fn location(
+        &mut self,
+        vm: &mut Interpreter,
+        frame: &FrameInfo,
+    ) -> std::result::Result<Data, ErrorCode> {
+        let id = self.type_id(&frame.class);
+        let class = self.class(vm, id)?.ok_or(INTERNAL)?;
+        let index = class
+            .methods
+            .iter()
+            .position(|m| m.name == frame.method && m.descriptor == frame.descriptor)
+            .ok_or(INTERNAL)?;
+        let mut data = Data::default();
+        data.byte(self.type_tag(vm, id)?)
+            .id(id)
+            .id(member_id(id, index))
+            .long(frame.pc as i64);
+        Ok(data)
+    }
+}
+
+/// Methods and fields are numbered within their class, but a field's id has to say which
+/// class it is in by itself
+fn member_id(class: u64, index: usize) -> u64 {
+    class << 32 | index as u64
+}
+
+fn thread_id(thread: usize) -> u64 {
+    THREAD_IDS + thread as u64
+}
+
+fn thread(vm: &Interpreter, id: u64) -> std::result::Result<usize, ErrorCode> {
+    let thread = id.checked_sub(THREAD_IDS).ok_or(INVALID_THREAD)? as usize;
+    let threads = vm.thread_list();
+    let info = threads.iter().find(|info| info.id == thread);
+    info.map(|info| info.id).ok_or(INVALID_THREAD)
+}
+
+/// Frames are only looked at while the VM is suspended, so they are just their depth in
+/// the thread's call stack
+fn frame_id(thread: usize, depth: usize) -> u64 {
+    (thread as u64) << 32 | depth as u64
+}
+
+fn frame(vm: &Interpreter, thread: usize, id: u64) -> std::result::Result<usize, ErrorCode> {
+    let depth = (id & 0xffff_ffff) as usize;
+    if id >> 32 != thread as u64 || depth >= vm.call_stack(thread).len() {
+        return Err(INVALID_FRAMEID);
+    }
+    Ok(depth)
+}
+
+/// Objects by their heap index + 1, and the threads that have a `java/lang/Thread` by
+/// their thread id
+fn object_id(vm: &Interpreter, reference: Reference) -> u64 {
+    let threads = vm.thread_list();
+    match threads.iter().find(|info| info.object == Some(reference)) {
+        Some(info) => thread_id(info.id),
+        None => reference.index() as u64 + 1,
+    }
+}
+
+fn object(vm: &Interpreter, id: u64) -> std::result::Result<Option<Reference>, ErrorCode> {
+    if id == 0 {
+        return Ok(None);
+    }
+    if let Ok(thread) = thread(vm, id) {
+        let threads = vm.thread_list();
+        let info = threads.iter().find(|info| info.id == thread);
+        return info
+            .and_then(|info| info.object)
+            .map(Some)
+            .ok_or(INVALID_OBJECT);
+    }
+    let index = usize::try_from(id - 1).map_err(|_| INVALID_OBJECT)?;
+    vm.heap().reference(index).map(Some).ok_or(INVALID_OBJECT)
+}
+
+fn object_tag(vm: &Interpreter, reference: Reference) -> u8 {
+    match vm.heap().get(reference) {
+        Object::Array(..) => b'[',
+        Object::Instance(instance) if instance.class.name == "java/lang/String" => b's',
+        Object::Instance(..) if object_id(vm, reference) >= THREAD_IDS => b't',
+        Object::Instance(..) => b'L',
+    }
+}
+
+/// The tag of a value whose type isn't known from elsewhere
+fn value_tag(vm: &Interpreter, value: Value) -> u8 {
+    match value {
+        Value::Int(..) => b'I',
+        Value::Long(..) => b'J',
+        Value::Float(..) => b'F',
+        Value::Double(..) => b'D',
+        Value::Reference(reference) => object_tag(vm, reference),
+        Value::Null | Value::ReturnAddress(..) | Value::Top => b'L',
+    }
+}
+
+/// A value with its tag. `tag` is the type the value has, from its descriptor, which
+/// references refine
+fn tagged(vm: &Interpreter, value: Value, tag: u8) -> Data {
+    let mut data = Data::default();
+    match value {
+        Value::Reference(reference) => {
+            data.byte(object_tag(vm, reference))
+                .id(object_id(vm, reference));
+        }
+        value => {
+            data.byte(tag);
+            untagged(&mut data, value, tag);
+        }
+    }
+    data
+}
+
+fn untagged(data: &mut Data, value: Value, tag: u8) {
+    match (tag, value) {
+        (b'Z', Value::Int(int)) | (b'B', Value::Int(int)) => data.byte(int as u8),
+        (b'C', Value::Int(int)) | (b'S', Value::Int(int)) => data.short(int as u16),
+        (b'I', Value::Int(int)) => data.int(int),
+        (b'F', Value::Float(float)) => data.int(float.to_bits() as i32),
+        (b'J', Value::Long(long)) => data.long(long),
+        (b'D', Value::Double(double)) => data.long(double.to_bits() as i64),
+        (b'V', _) => data,
+        // unset slots, and nulls: zero of the size the tag says
+        (b'Z', _) | (b'B', _) => data.byte(0),
+        (b'C', _) | (b'S', _) => data.short(0),
+        (b'I', _) | (b'F', _) => data.int(0),
+        (b'J', _) | (b'D', _) => data.long(0),
+        (_, Value::Reference(reference)) => data.id(reference.index() as u64 + 1),
+        _ => data.id(0),
+    };
+}
+
+fn read_value(vm: &Interpreter, args: &mut DataReader) -> std::result::Result<Value, ErrorCode> {
+    Ok(match args.byte()? {
+        b'Z' | b'B' => Value::Int(i32::from(args.byte()? as i8)),
+        b'C' => Value::Int(i32::from(args.short()?)),
+        b'S' => Value::Int(i32::from(args.short()? as i16)),
+        b'I' => Value::Int(args.int()?),
+        b'F' => Value::Float(f32::from_bits(args.int()? as u32)),
+        b'J' => Value::Long(args.long()?),
+        b'D' => Value::Double(f64::from_bits(args.long()? as u64)),
+        _ => match object(vm, args.id()?)? {
+            Some(reference) => Value::Reference(reference),
+            None => Value::Null,
+        },
+    })
+}
+
+/// The JNI signature of a class or array type from its internal name
+fn signature(name: &str) -> String {
+    if name.starts_with('[') {
+        name.to_string()
+    } else {
+        format!("L{};", name)
+    }
+}
+
+/// Whether a class, by binary name, matches a pattern like `java.*` or `*.Foo`
+fn matches_class(pattern: &str, class: &str) -> bool {
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        class.starts_with(prefix)
+    } else if let Some(suffix) = pattern.strip_prefix('*') {
+        class.ends_with(suffix)
+    } else {
+        class == pattern
+    }
+}
+
+fn internal(_: impl std::fmt::Debug) -> ErrorCode {
+    INTERNAL
+}
+
+/// An error answering the client that can't be sent back as a reply, since it comes from an
+/// event
+fn jdwp_error(code: ErrorCode) -> Error {
+    Error::GenericError(format!("JDWP error {}", code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::load_classes;
+    use std::net::SocketAddr;
+
+    /// Sends commands one at a time, and keeps the events that come in the meantime
+    struct Client {
+        stream: TcpStream,
+        last_id: u32,
+        events: Vec<Vec<u8>>,
+    }
+
+    impl Client {
+        fn connect(address: SocketAddr) -> Self {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(HANDSHAKE).unwrap();
+            let mut handshake = [0; HANDSHAKE.len()];
+            stream.read_exact(&mut handshake).unwrap();
+            assert_eq!(handshake, HANDSHAKE);
+            Client {
+                stream,
+                last_id: 0,
+                events: vec![],
+            }
+        }
+
+        fn command(&mut self, set: u8, command: u8, data: &mut Data) -> Vec<u8> {
+            self.last_id += 1;
+            let packet = Packet::Command {
+                id: self.last_id,
+                set,
+                command,
+                data: data.0.clone(),
+            };
+            packet.write(&mut self.stream).unwrap();
+            loop {
+                match Packet::read(&mut self.stream).unwrap() {
+                    Packet::Reply { id, error, data } if id == self.last_id => {
+                        assert_eq!(error, 0, "command {} of set {}", command, set);
+                        return data;
+                    }
+                    Packet::Command { data, .. } => self.events.push(data),
+                    packet => panic!("unexpected reply {:?}", packet),
+                }
+            }
+        }
+
+        /// The kind, request id and the rest of the next event
+        fn event(&mut self) -> (u8, i32, Vec<u8>) {
+            let data = if self.events.is_empty() {
+                match Packet::read(&mut self.stream).unwrap() {
+                    Packet::Command { data, .. } => data,
+                    packet => panic!("expected an event, got {:?}", packet),
+                }
+            } else {
+                self.events.remove(0)
+            };
+            let mut reader = DataReader(&data);
+            reader.byte().unwrap();
+            assert_eq!(reader.int(), Ok(1));
+            let (kind, request) = (reader.byte().unwrap(), reader.int().unwrap());
+            (kind, request, reader.0.to_vec())
+        }
+
+        fn class(&mut self, signature: &str) -> u64 {
+            let reply = self.command(VIRTUAL_MACHINE, 2, Data::default().string(signature));
+            let mut reply = DataReader(&reply);
+            assert_eq!(reply.int(), Ok(1));
+            assert_eq!(reply.byte(), Ok(CLASS));
+            reply.id().unwrap()
+        }
+
+        /// The id of a method or field by name
+        fn member(&mut self, class: u64, command: u8, name: &str) -> u64 {
+            let reply = self.command(REFERENCE_TYPE, command, Data::default().id(class));
+            let mut reply = DataReader(&reply);
+            for _ in 0..reply.int().unwrap() {
+                let id = reply.id().unwrap();
+                let found = reply.string().unwrap() == name;
+                reply.string().unwrap();
+                reply.int().unwrap();
+                if found {
+                    return id;
+                }
+            }
+            panic!("no member {}", name)
+        }
+
+        /// Where each line starts, by line
+        fn lines(&mut self, class: u64, method: u64) -> Vec<(u16, i64)> {
+            let reply = self.command(METHOD, 1, Data::default().id(class).id(method));
+            let mut reply = DataReader(&reply);
+            reply.long().unwrap();
+            reply.long().unwrap();
+            let lines = (0..reply.int().unwrap()).map(|_| {
+                let pc = reply.long().unwrap();
+                (reply.int().unwrap() as u16, pc)
+            });
+            lines.collect()
+        }
+
+        fn set(&mut self, kind: u8, modifiers: &Data, count: i32) -> i32 {
+            let mut data = Data::default();
+            data.byte(kind).byte(SUSPEND_ALL).int(count).data(modifiers);
+            let reply = self.command(EVENT_REQUEST, 1, &mut data);
+            DataReader(&reply).int().unwrap()
+        }
+
+        fn resume(&mut self) {
+            self.command(VIRTUAL_MACHINE, 9, &mut Data::default());
+        }
+    }
+
+    /// Skips the thread, and reads the location of an event
+    fn location(event: &[u8]) -> (u64, u64, i64) {
+        let mut event = DataReader(event);
+        event.id().unwrap();
+        event.byte().unwrap();
+        (
+            event.id().unwrap(),
+            event.id().unwrap(),
+            event.long().unwrap(),
+        )
+    }
+
+    #[test]
+    fn session() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let mut client = Client::connect(address);
+            let (kind, _, event) = client.event();
+            assert_eq!(kind, VM_START);
+            let thread = DataReader(&event).id().unwrap();
+
+            let sizes = client.command(VIRTUAL_MACHINE, 7, &mut Data::default());
+            assert_eq!(DataReader(&sizes).int(), Ok(8));
+
+            let debug = client.class("Ldebug;");
+            let run = client.member(debug, 5, "run");
+            let lines = client.lines(debug, run);
+            let line_19 = lines.iter().find(|(line, _)| *line == 19).unwrap().1;
+            let point = client.class("Ldebug$Point;");
+            let (x, y) = (client.member(point, 4, "x"), client.member(point, 4, "y"));
+
+            let watch = client.set(
+                FIELD_MODIFICATION,
+                Data::default().byte(9).id(point).id(x),
+                1,
+            );
+            let breakpoint = client.set(
+                BREAKPOINT,
+                Data::default()
+                    .byte(7)
+                    .byte(CLASS)
+                    .id(debug)
+                    .id(run)
+                    .long(line_19),
+                1,
+            );
+
+            // the constructor sets `x`
+            client.resume();
+            let (kind, request, event) = client.event();
+            assert_eq!((kind, request), (FIELD_MODIFICATION, watch));
+            assert_eq!(location(&event).0, point);
+            assert_eq!(&event[event.len() - 5..], &[b'I', 0, 0, 0, 3]);
+            client.command(
+                EVENT_REQUEST,
+                2,
+                Data::default().byte(FIELD_MODIFICATION).int(watch),
+            );
+
+            client.resume();
+            let (kind, request, event) = client.event();
+            assert_eq!((kind, request), (BREAKPOINT, breakpoint));
+            assert_eq!(location(&event), (debug, run, line_19));
+
+            let frames = client.command(
+                THREAD_REFERENCE,
+                6,
+                Data::default().id(thread).int(0).int(-1),
+            );
+            let mut frames = DataReader(&frames);
+            assert_eq!(frames.int(), Ok(2));
+            let frame = frames.id().unwrap();
+
+            let variables = client.command(METHOD, 2, Data::default().id(debug).id(run));
+            let mut variables = DataReader(&variables);
+            assert_eq!(variables.int(), Ok(0));
+            let mut slots = std::collections::HashMap::new();
+            for _ in 0..variables.int().unwrap() {
+                variables.long().unwrap();
+                let name = variables.string().unwrap();
+                variables.string().unwrap();
+                variables.int().unwrap();
+                slots.insert(name, variables.int().unwrap());
+            }
+
+            let values = client.command(
+                STACK_FRAME,
+                1,
+                Data::default()
+                    .id(thread)
+                    .id(frame)
+                    .int(2)
+                    .int(slots["sum"])
+                    .byte(b'I')
+                    .int(slots["point"])
+                    .byte(b'L'),
+            );
+            let mut values = DataReader(&values);
+            assert_eq!(values.int(), Ok(2));
+            assert_eq!((values.byte(), values.int()), (Ok(b'I'), Ok(7)));
+            assert_eq!(values.byte(), Ok(b'L'));
+            let object = values.id().unwrap();
+
+            let fields = client.command(
+                OBJECT_REFERENCE,
+                2,
+                Data::default().id(object).int(2).id(x).id(y),
+            );
+            let mut fields = DataReader(&fields);
+            assert_eq!(fields.int(), Ok(2));
+            assert_eq!((fields.byte(), fields.int()), (Ok(b'I'), Ok(3)));
+            assert_eq!((fields.byte(), fields.int()), (Ok(b'I'), Ok(4)));
+
+            // over the line, one line at a time
+            let step = client.set(
+                SINGLE_STEP,
+                Data::default()
+                    .byte(1)
+                    .int(1)
+                    .byte(10)
+                    .id(thread)
+                    .int(1)
+                    .int(1),
+                2,
+            );
+            client.resume();
+            let (kind, request, event) = client.event();
+            assert_eq!((kind, request), (SINGLE_STEP, step));
+            let (_, method, pc) = location(&event);
+            assert_eq!(method, run);
+            assert!(lines.contains(&(20, pc)));
+
+            client.resume();
+            let (kind, ..) = client.event();
+            assert_eq!(kind, VM_DEATH);
+        });
+
+        let mut vm = load_classes(&["debug", "debug$Point"]);
+        vm.set_stdout(std::io::sink());
+        let session = Session::accept(&listener).unwrap();
+        session.attach(&mut vm).unwrap();
+        assert!(vm.run_main("debug").unwrap().is_ok());
+        session.finish().unwrap();
+        client.join().unwrap();
+    }
+}
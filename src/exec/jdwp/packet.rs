@@ -0,0 +1,233 @@
+//! The JDWP wire format: command and reply packets, and the big-endian data in them
+// https://docs.oracle.com/javase/8/docs/technotes/guides/jpda/jdwp-spec.html
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+/// What both sides send first, before any packet
+pub const HANDSHAKE: &[u8] = b"JDWP-Handshake";
+
+/// The error code of a reply, `0` when there is no error
+// https://docs.oracle.com/javase/8/docs/platform/jpda/jdwp/jdwp-protocol.html#JDWP_Error
+pub type ErrorCode = u16;
+
+pub const INVALID_THREAD: ErrorCode = 10;
+pub const INVALID_THREAD_GROUP: ErrorCode = 11;
+pub const INVALID_OBJECT: ErrorCode = 20;
+pub const INVALID_CLASS: ErrorCode = 21;
+pub const INVALID_METHODID: ErrorCode = 23;
+pub const INVALID_LOCATION: ErrorCode = 24;
+pub const INVALID_FIELDID: ErrorCode = 25;
+pub const INVALID_FRAMEID: ErrorCode = 30;
+pub const INVALID_SLOT: ErrorCode = 35;
+pub const NOT_IMPLEMENTED: ErrorCode = 99;
+pub const ABSENT_INFORMATION: ErrorCode = 101;
+pub const INVALID_EVENT_TYPE: ErrorCode = 102;
+pub const ILLEGAL_ARGUMENT: ErrorCode = 103;
+pub const INTERNAL: ErrorCode = 113;
+pub const INVALID_STRING: ErrorCode = 506;
+pub const INVALID_ARRAY: ErrorCode = 508;
+pub const INVALID_INDEX: ErrorCode = 503;
+
+const HEADER: usize = 11;
+const REPLY: u8 = 0x80;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    Command {
+        id: u32,
+        set: u8,
+        command: u8,
+        data: Vec<u8>,
+    },
+    Reply {
+        id: u32,
+        error: ErrorCode,
+        data: Vec<u8>,
+    },
+}
+
+impl Packet {
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut header = [0; HEADER];
+        reader.read_exact(&mut header)?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        if length < HEADER {
+            let message = format!("packet length {} is shorter than its header", length);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        let mut data = vec![0; length - HEADER];
+        reader.read_exact(&mut data)?;
+
+        let id = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        Ok(if header[8] & REPLY != 0 {
+            Packet::Reply {
+                id,
+                error: u16::from_be_bytes([header[9], header[10]]),
+                data,
+            }
+        } else {
+            Packet::Command {
+                id,
+                set: header[9],
+                command: header[10],
+                data,
+            }
+        })
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let (id, flags, code, data) = match self {
+            Packet::Command {
+                id,
+                set,
+                command,
+                data,
+            } => (*id, 0, [*set, *command], data),
+            Packet::Reply { id, error, data } => (*id, REPLY, error.to_be_bytes(), data),
+        };
+        let mut packet = Vec::with_capacity(HEADER + data.len());
+        packet.extend_from_slice(&((HEADER + data.len()) as u32).to_be_bytes());
+        packet.extend_from_slice(&id.to_be_bytes());
+        packet.push(flags);
+        packet.extend_from_slice(&code);
+        packet.extend_from_slice(data);
+        writer.write_all(&packet)?;
+        writer.flush()
+    }
+}
+
+/// The data of a packet, as it is written. Every id is 8 bytes long
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Data(pub Vec<u8>);
+
+impl Data {
+    pub fn byte(&mut self, byte: u8) -> &mut Self {
+        self.0.push(byte);
+        self
+    }
+
+    pub fn boolean(&mut self, boolean: bool) -> &mut Self {
+        self.byte(boolean as u8)
+    }
+
+    pub fn short(&mut self, short: u16) -> &mut Self {
+        self.0.extend_from_slice(&short.to_be_bytes());
+        self
+    }
+
+    pub fn int(&mut self, int: i32) -> &mut Self {
+        self.0.extend_from_slice(&int.to_be_bytes());
+        self
+    }
+
+    pub fn long(&mut self, long: i64) -> &mut Self {
+        self.0.extend_from_slice(&long.to_be_bytes());
+        self
+    }
+
+    pub fn id(&mut self, id: u64) -> &mut Self {
+        self.0.extend_from_slice(&id.to_be_bytes());
+        self
+    }
+
+    /// A length, then the string in UTF-8
+    pub fn string(&mut self, s: &str) -> &mut Self {
+        self.int(s.len() as i32);
+        self.0.extend_from_slice(s.as_bytes());
+        self
+    }
+
+    pub fn data(&mut self, data: &Data) -> &mut Self {
+        self.0.extend_from_slice(&data.0);
+        self
+    }
+}
+
+/// Reads the data of a packet. Running out of data is an `ILLEGAL_ARGUMENT`
+#[derive(Debug, Clone)]
+pub struct DataReader<'a>(pub &'a [u8]);
+
+impl<'a> DataReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ErrorCode> {
+        if self.0.len() < n {
+            return Err(ILLEGAL_ARGUMENT);
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    pub fn byte(&mut self) -> Result<u8, ErrorCode> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn boolean(&mut self) -> Result<bool, ErrorCode> {
+        Ok(self.byte()? != 0)
+    }
+
+    pub fn short(&mut self) -> Result<u16, ErrorCode> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn int(&mut self) -> Result<i32, ErrorCode> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(i32::from_be_bytes(bytes))
+    }
+
+    pub fn long(&mut self) -> Result<i64, ErrorCode> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(i64::from_be_bytes(bytes))
+    }
+
+    pub fn id(&mut self) -> Result<u64, ErrorCode> {
+        Ok(self.long()? as u64)
+    }
+
+    pub fn string(&mut self) -> Result<String, ErrorCode> {
+        let length = self.int()?;
+        let bytes = self.take(usize::try_from(length).map_err(|_| ILLEGAL_ARGUMENT)?)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| INVALID_STRING)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets() {
+        let mut data = Data::default();
+        data.byte(1).int(-2).long(3).id(4).string("héllo");
+        let command = Packet::Command {
+            id: 7,
+            set: 1,
+            command: 2,
+            data: data.0.clone(),
+        };
+        let mut bytes = vec![];
+        command.write(&mut bytes).unwrap();
+        assert_eq!(&bytes[..11], &[0, 0, 0, 42, 0, 0, 0, 7, 0, 1, 2]);
+        assert_eq!(Packet::read(&mut bytes.as_slice()).unwrap(), command);
+
+        let mut reader = DataReader(&data.0);
+        assert_eq!(reader.byte(), Ok(1));
+        assert_eq!(reader.int(), Ok(-2));
+        assert_eq!(reader.long(), Ok(3));
+        assert_eq!(reader.id(), Ok(4));
+        assert_eq!(reader.string().as_deref(), Ok("héllo"));
+        assert_eq!(reader.byte(), Err(ILLEGAL_ARGUMENT));
+
+        let reply = Packet::Reply {
+            id: 7,
+            error: INVALID_THREAD,
+            data: vec![],
+        };
+        let mut bytes = vec![];
+        reply.write(&mut bytes).unwrap();
+        assert_eq!(bytes, [0, 0, 0, 11, 0, 0, 0, 7, 0x80, 0, 10]);
+        assert_eq!(Packet::read(&mut bytes.as_slice()).unwrap(), reply);
+    }
+}
@@ -0,0 +1,337 @@
+//! Compiling hot methods to native code with Cranelift. Only static methods that work on
+//! primitives alone are compiled: ones made of constants, locals, arithmetic, branches, and
+//! calls of other such methods. The rest are left to the interpreter.
+//!
+//! A compiled method that can't go on, because it divides by zero or calls too deep,
+//! deoptimizes: it returns to the interpreter, which runs the call again from the start. It
+//! can, since the method has no side effects, and from then on the method is interpreted
+// https://cranelift.dev/
+use super::class::Method;
+use super::*;
+
+mod translate;
+
+use cranelift_codegen::ir::{types, AbiParam, Signature};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Module};
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// How often a method is called before it is compiled
+const CALL_THRESHOLD: u32 = 1_000;
+/// How often a method branches back, in its loops, before it is compiled
+const BACK_EDGE_THRESHOLD: u32 = 10_000;
+/// How deep compiled methods can call each other before they deoptimize, so that deep
+/// recursion is left to the interpreter rather than overflowing the native stack
+pub(crate) const MAX_DEPTH: u32 = 1_024;
+/// Longer methods aren't compiled
+const MAX_CODE: usize = 8_000;
+
+/// A compiled method. The arguments are in `slots`, and the result is put in the first one.
+/// It returns `0` when it returns, or `1` when it deoptimizes
+pub(crate) type Entry = unsafe extern "C" fn(slots: *mut u64, depth: u32) -> u32;
+
+/// How a method is run
+#[derive(Debug, Copy, Clone, Default)]
+pub(crate) enum Tier {
+    #[default]
+    Interpreted,
+    Compiled(Entry),
+    /// It can't be compiled, or it deoptimized
+    Never,
+}
+
+/// How hot a method is, and whether it has been compiled
+#[derive(Debug, Default)]
+pub(crate) struct Hotness {
+    pub(crate) calls: Cell<u32>,
+    pub(crate) back_edges: Cell<u32>,
+    pub(crate) tier: Cell<Tier>,
+}
+
+impl Hotness {
+    pub(crate) fn count_call(&self) {
+        self.calls.set(self.calls.get().saturating_add(1));
+    }
+
+    pub(crate) fn count_back_edge(&self) {
+        self.back_edges.set(self.back_edges.get().saturating_add(1));
+    }
+}
+
+/// The static methods a method calls, by constant pool index
+type Callees = Vec<(u16, Rc<Method>)>;
+
+/// Finds the static method an `invokestatic` of a method calls, by its constant pool index,
+/// if it is linked and its class initialized
+pub(crate) type Resolve<'a> = dyn FnMut(&Rc<Method>, u16) -> Option<Rc<Method>> + 'a;
+
+/// Compiles methods once the interpreter has called them, or branched back in them, often
+/// enough
+///
+/// ```ignore
+/// vm.set_jit(Some(Jit::new()?));
+/// vm.run_main("com/acme/Main")?;
+/// ```
+pub struct Jit {
+    module: JITModule,
+    ctx: cranelift_codegen::Context,
+    builder: cranelift_frontend::FunctionBuilderContext,
+    call_threshold: u32,
+    back_edge_threshold: u32,
+    /// The compiled methods, which they are kept alive by
+    ids: HashMap<*const Method, FuncId>,
+    compiled: Vec<Rc<Method>>,
+    deoptimizations: u64,
+    /// Set when the code generated for a method is rejected, which is a bug here. Nothing
+    /// more is compiled then
+    broken: bool,
+}
+
+impl std::fmt::Debug for Jit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Jit")
+            .field("call_threshold", &self.call_threshold)
+            .field("back_edge_threshold", &self.back_edge_threshold)
+            .field("compiled", &self.compiled.len())
+            .field("deoptimizations", &self.deoptimizations)
+            .finish()
+    }
+}
+
+impl Jit {
+    /// A compiler for the machine this runs on
+    pub fn new() -> Result<Self> {
+        let mut flags = settings::builder();
+        let configured = flags
+            .set("opt_level", "speed")
+            .and_then(|_| flags.set("use_colocated_libcalls", "false"))
+            .and_then(|_| flags.set("is_pic", "false"));
+        if let Err(err) = configured {
+            generic_error!("cannot configure the JIT: {}", err);
+        }
+        let isa = match cranelift_native::builder() {
+            Ok(builder) => builder.finish(settings::Flags::new(flags)),
+            Err(err) => generic_error!("cannot compile for this machine: {}", err),
+        };
+        let isa = match isa {
+            Ok(isa) => isa,
+            Err(err) => generic_error!("cannot compile for this machine: {}", err),
+        };
+
+        let module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+        Ok(Self {
+            ctx: module.make_context(),
+            module,
+            builder: cranelift_frontend::FunctionBuilderContext::new(),
+            call_threshold: CALL_THRESHOLD,
+            back_edge_threshold: BACK_EDGE_THRESHOLD,
+            ids: HashMap::new(),
+            compiled: vec![],
+            deoptimizations: 0,
+            broken: false,
+        })
+    }
+
+    /// Compiles methods once they have been called `calls` times
+    pub fn call_threshold(&mut self, calls: u32) -> &mut Self {
+        self.call_threshold = calls;
+        self
+    }
+
+    /// Compiles methods once they have branched back `back_edges` times, for the next time
+    /// they are called
+    pub fn back_edge_threshold(&mut self, back_edges: u32) -> &mut Self {
+        self.back_edge_threshold = back_edges;
+        self
+    }
+
+    /// The methods that have been compiled, as `class.name(descriptor)`
+    pub fn compiled(&self) -> Vec<String> {
+        (self.compiled.iter())
+            .map(|method| {
+                let class = method.class();
+                format!("{}.{}{}", class.name, method.name, method.descriptor)
+            })
+            .collect()
+    }
+
+    /// How often compiled methods returned to the interpreter
+    pub fn deoptimizations(&self) -> u64 {
+        self.deoptimizations
+    }
+
+    /// Whether `hotness` has reached either threshold
+    pub(crate) fn is_hot(&self, hotness: &Hotness) -> bool {
+        hotness.calls.get() >= self.call_threshold
+            || hotness.back_edges.get() >= self.back_edge_threshold
+    }
+
+    /// Leaves `method` to the interpreter from now on
+    pub(crate) fn deoptimize(&mut self, method: &Method) {
+        method.hotness.tier.set(Tier::Never);
+        self.deoptimizations += 1;
+    }
+
+    /// Compiles `method`, along with the methods it calls that aren't compiled yet. It is
+    /// left to the interpreter from now on if it can't be
+    pub(crate) fn compile(&mut self, method: &Rc<Method>, resolve: &mut Resolve<'_>) -> Result<()> {
+        if self.broken {
+            generic_error!("the JIT is disabled");
+        }
+        let result = self.compile_unit(method, resolve);
+        if result.is_err() {
+            method.hotness.tier.set(Tier::Never);
+        }
+        result
+    }
+
+    fn compile_unit(&mut self, method: &Rc<Method>, resolve: &mut Resolve<'_>) -> Result<()> {
+        // the methods compiled together, with the methods each calls by constant pool index
+        let mut unit: Vec<(Rc<Method>, Callees)> = vec![];
+        let mut work = vec![Rc::clone(method)];
+        while let Some(next) = work.pop() {
+            let seen = unit.iter().any(|(method, _)| Rc::ptr_eq(method, &next));
+            if seen || self.ids.contains_key(&Rc::as_ptr(&next)) {
+                continue;
+            }
+            if let Tier::Never = next.hotness.tier.get() {
+                generic_error!("{}.{} can't be compiled", next.class().name, next.name);
+            }
+            let callees = match callees(&next, resolve) {
+                Ok(callees) => callees,
+                Err(err) => {
+                    next.hotness.tier.set(Tier::Never);
+                    return Err(err);
+                }
+            };
+            work.extend(callees.iter().map(|(_, callee)| Rc::clone(callee)));
+            unit.push((next, callees));
+        }
+
+        let signature = self.signature();
+        let mut ids = self.ids.clone();
+        let mut functions = vec![];
+        for (method, _) in &unit {
+            let id = match self.module.declare_anonymous_function(&signature) {
+                Ok(id) => id,
+                Err(err) => generic_error!("cannot declare {}: {}", method.name, err),
+            };
+            ids.insert(Rc::as_ptr(method), id);
+            functions.push(id);
+        }
+
+        // nothing is defined until everything is translated, so that a method that can't
+        // be doesn't leave the others calling nothing
+        let mut translated = vec![];
+        for (method, callees) in &unit {
+            let callees = (callees.iter())
+                .map(|(index, callee)| (*index, (Rc::clone(callee), ids[&Rc::as_ptr(callee)])))
+                .collect::<HashMap<_, _>>();
+            self.ctx.func.signature = signature.clone();
+            let translation = translate::translate(
+                &mut self.ctx.func,
+                &mut self.builder,
+                &mut self.module,
+                method,
+                &callees,
+            );
+            let func =
+                std::mem::replace(&mut self.ctx.func, cranelift_codegen::ir::Function::new());
+            if let Err(err) = translation {
+                method.hotness.tier.set(Tier::Never);
+                return Err(err);
+            }
+            translated.push(func);
+        }
+
+        for (&id, func) in functions.iter().zip(translated) {
+            self.ctx.func = func;
+            let defined = self.module.define_function(id, &mut self.ctx);
+            self.module.clear_context(&mut self.ctx);
+            if let Err(err) = defined {
+                self.broken = true;
+                generic_error!("cannot compile {}: {:?}", method.name, err);
+            }
+        }
+        if let Err(err) = self.module.finalize_definitions() {
+            self.broken = true;
+            generic_error!("cannot link {}: {}", method.name, err);
+        }
+
+        for ((method, _), &id) in unit.iter().zip(&functions) {
+            let code = self.module.get_finalized_function(id);
+            // the code was generated for this signature
+            let entry = unsafe { std::mem::transmute::<*const u8, Entry>(code) };
+            method.hotness.tier.set(Tier::Compiled(entry));
+            self.ids.insert(Rc::as_ptr(method), id);
+            self.compiled.push(Rc::clone(method));
+        }
+        Ok(())
+    }
+
+    /// The signature of `Entry`
+    fn signature(&self) -> Signature {
+        let mut signature = self.module.make_signature();
+        let pointer = self.module.target_config().pointer_type();
+        signature.params.push(AbiParam::new(pointer));
+        signature.params.push(AbiParam::new(types::I32));
+        signature.returns.push(AbiParam::new(types::I32));
+        signature
+    }
+}
+
+/// Whether values of `ty` can be passed to and from compiled methods
+fn is_primitive(ty: &ty::FieldType) -> bool {
+    !matches!(ty, ty::FieldType::Object(..) | ty::FieldType::Array(..))
+}
+
+/// The static methods `method` calls by constant pool index, if it can be compiled
+fn callees(method: &Rc<Method>, resolve: &mut Resolve<'_>) -> Result<Callees> {
+    let name = || {
+        format!(
+            "{}.{}{}",
+            method.class().name,
+            method.name,
+            method.descriptor
+        )
+    };
+    let code = match method.code() {
+        Some(code) => code,
+        None => generic_error!("{} has no code", name()),
+    };
+    if !method.is_static() || method.flags.contains(ty::MethodFlags::SYNCHRONIZED) {
+        generic_error!("{} isn't a static method that can be compiled", name());
+    }
+    let signature = &method.signature;
+    if !signature.params.iter().all(is_primitive) || !signature.ret.iter().all(is_primitive) {
+        generic_error!("{} takes or returns references", name());
+    }
+    if !code.exception_table.is_empty() || code.code.len() > MAX_CODE {
+        generic_error!("{} catches exceptions or is too long", name());
+    }
+
+    let mut callees = vec![];
+    let mut pc = 0;
+    while pc < code.code.len() {
+        let instruction = match Instruction::decode(&code.code, pc) {
+            Some(instruction) => instruction,
+            None => generic_error!("invalid instruction at {} of {}", pc, name()),
+        };
+        if let Instruction::INVOKESTATIC(INVOKESTATIC(a, b)) = instruction {
+            let index = wide_index(a, b);
+            let callee = match resolve(method, index) {
+                Some(callee) if callee.is_static() => callee,
+                _ => generic_error!("{} calls a method that isn't linked at {}", name(), pc),
+            };
+            callees.push((index, callee));
+        } else if !translate::is_supported(&instruction) {
+            generic_error!("{} runs {:?} at {}", name(), instruction, pc);
+        }
+        pc += instruction.size();
+    }
+    Ok(callees)
+}
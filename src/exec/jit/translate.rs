@@ -0,0 +1,912 @@
+//! Translating the bytecode of a method to Cranelift IR. The operand stack is kept while a
+//! block is translated, and handed to the next blocks in variables, like the locals are
+use super::*;
+use crate::exec::cfg::Cfg;
+use ty::ConstantIndex;
+
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::{
+    self, Function, InstBuilder, MemFlags, StackSlotData, StackSlotKind, Type,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Switch, Variable};
+
+use std::collections::HashSet;
+
+/// The types of the values compiled methods work on
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Ty {
+    Int,
+    Long,
+    Float,
+    Double,
+}
+
+impl Ty {
+    fn of(ty: &ty::FieldType) -> Self {
+        match ty {
+            ty::FieldType::Long => Ty::Long,
+            ty::FieldType::Float => Ty::Float,
+            ty::FieldType::Double => Ty::Double,
+            _ => Ty::Int,
+        }
+    }
+
+    fn ir(self) -> Type {
+        match self {
+            Ty::Int => ir::types::I32,
+            Ty::Long => ir::types::I64,
+            Ty::Float => ir::types::F32,
+            Ty::Double => ir::types::F64,
+        }
+    }
+
+    /// How many words it takes on the stack and in the locals
+    fn words(self) -> usize {
+        match self {
+            Ty::Long | Ty::Double => 2,
+            Ty::Int | Ty::Float => 1,
+        }
+    }
+}
+
+/// Whether instructions like `instruction` can be compiled. `invokestatic` and `ldc` are
+/// checked for what they refer to
+pub(super) fn is_supported(instruction: &Instruction) -> bool {
+    use Instruction as I;
+
+    matches!(
+        instruction,
+        I::NOP(..)
+            | I::ICONST_M1(..)
+            | I::ICONST_0(..)
+            | I::ICONST_1(..)
+            | I::ICONST_2(..)
+            | I::ICONST_3(..)
+            | I::ICONST_4(..)
+            | I::ICONST_5(..)
+            | I::LCONST_0(..)
+            | I::LCONST_1(..)
+            | I::FCONST_0(..)
+            | I::FCONST_1(..)
+            | I::FCONST_2(..)
+            | I::DCONST_0(..)
+            | I::DCONST_1(..)
+            | I::BIPUSH(..)
+            | I::SIPUSH(..)
+            | I::LDC(..)
+            | I::LDC_W(..)
+            | I::LDC2_W(..)
+            | I::ILOAD(..)
+            | I::LLOAD(..)
+            | I::FLOAD(..)
+            | I::DLOAD(..)
+            | I::ILOAD_0(..)
+            | I::ILOAD_1(..)
+            | I::ILOAD_2(..)
+            | I::ILOAD_3(..)
+            | I::LLOAD_0(..)
+            | I::LLOAD_1(..)
+            | I::LLOAD_2(..)
+            | I::LLOAD_3(..)
+            | I::FLOAD_0(..)
+            | I::FLOAD_1(..)
+            | I::FLOAD_2(..)
+            | I::FLOAD_3(..)
+            | I::DLOAD_0(..)
+            | I::DLOAD_1(..)
+            | I::DLOAD_2(..)
+            | I::DLOAD_3(..)
+            | I::ISTORE(..)
+            | I::LSTORE(..)
+            | I::FSTORE(..)
+            | I::DSTORE(..)
+            | I::ISTORE_0(..)
+            | I::ISTORE_1(..)
+            | I::ISTORE_2(..)
+            | I::ISTORE_3(..)
+            | I::LSTORE_0(..)
+            | I::LSTORE_1(..)
+            | I::LSTORE_2(..)
+            | I::LSTORE_3(..)
+            | I::FSTORE_0(..)
+            | I::FSTORE_1(..)
+            | I::FSTORE_2(..)
+            | I::FSTORE_3(..)
+            | I::DSTORE_0(..)
+            | I::DSTORE_1(..)
+            | I::DSTORE_2(..)
+            | I::DSTORE_3(..)
+            | I::POP(..)
+            | I::POP2(..)
+            | I::DUP(..)
+            | I::DUP_X1(..)
+            | I::DUP_X2(..)
+            | I::DUP2(..)
+            | I::DUP2_X1(..)
+            | I::DUP2_X2(..)
+            | I::SWAP(..)
+            | I::IADD(..)
+            | I::LADD(..)
+            | I::FADD(..)
+            | I::DADD(..)
+            | I::ISUB(..)
+            | I::LSUB(..)
+            | I::FSUB(..)
+            | I::DSUB(..)
+            | I::IMUL(..)
+            | I::LMUL(..)
+            | I::FMUL(..)
+            | I::DMUL(..)
+            | I::IDIV(..)
+            | I::LDIV(..)
+            | I::FDIV(..)
+            | I::DDIV(..)
+            | I::IREM(..)
+            | I::LREM(..)
+            | I::INEG(..)
+            | I::LNEG(..)
+            | I::FNEG(..)
+            | I::DNEG(..)
+            | I::ISHL(..)
+            | I::LSHL(..)
+            | I::ISHR(..)
+            | I::LSHR(..)
+            | I::IUSHR(..)
+            | I::LUSHR(..)
+            | I::IAND(..)
+            | I::LAND(..)
+            | I::IOR(..)
+            | I::LOR(..)
+            | I::IXOR(..)
+            | I::LXOR(..)
+            | I::IINC(..)
+            | I::I2L(..)
+            | I::I2F(..)
+            | I::I2D(..)
+            | I::L2I(..)
+            | I::L2F(..)
+            | I::L2D(..)
+            | I::F2I(..)
+            | I::F2L(..)
+            | I::F2D(..)
+            | I::D2I(..)
+            | I::D2L(..)
+            | I::D2F(..)
+            | I::I2B(..)
+            | I::I2C(..)
+            | I::I2S(..)
+            | I::LCMP(..)
+            | I::FCMPL(..)
+            | I::FCMPG(..)
+            | I::DCMPL(..)
+            | I::DCMPG(..)
+            | I::IFEQ(..)
+            | I::IFNE(..)
+            | I::IFLT(..)
+            | I::IFGE(..)
+            | I::IFGT(..)
+            | I::IFLE(..)
+            | I::IF_ICMPEQ(..)
+            | I::IF_ICMPNE(..)
+            | I::IF_ICMPLT(..)
+            | I::IF_ICMPGE(..)
+            | I::IF_ICMPGT(..)
+            | I::IF_ICMPLE(..)
+            | I::GOTO(..)
+            | I::GOTO_W(..)
+            | I::TABLESWITCH(..)
+            | I::LOOKUPSWITCH(..)
+            | I::IRETURN(..)
+            | I::LRETURN(..)
+            | I::FRETURN(..)
+            | I::DRETURN(..)
+            | I::RETURN(..)
+            | I::INVOKESTATIC(..)
+    )
+}
+
+/// Translates `method` into `func`, calling the functions of `callees` for its
+/// `invokestatic`s by constant pool index
+pub(super) fn translate(
+    func: &mut Function,
+    context: &mut FunctionBuilderContext,
+    module: &mut JITModule,
+    method: &Rc<Method>,
+    callees: &HashMap<u16, (Rc<Method>, FuncId)>,
+) -> Result<()> {
+    let code = match method.code() {
+        Some(code) => code,
+        None => generic_error!("{} has no code", method.name),
+    };
+    let class = method.class();
+    let file = class.class_file()?;
+    let cfg = Cfg::from_code(file, code)?;
+    let pointer = module.target_config().pointer_type();
+
+    let mut builder = FunctionBuilder::new(func, context);
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    let (slots, depth) = (
+        builder.block_params(entry)[0],
+        builder.block_params(entry)[1],
+    );
+
+    let mut calls = HashMap::new();
+    let mut scratch_words = 1;
+    for (&index, (callee, id)) in callees {
+        let func = module.declare_func_in_func(*id, builder.func);
+        scratch_words = scratch_words.max(callee.signature.params.len());
+        calls.insert(index, (func, Rc::clone(callee)));
+    }
+    let scratch = builder.create_sized_stack_slot(StackSlotData::new(
+        StackSlotKind::ExplicitSlot,
+        (scratch_words * 8) as u32,
+        3,
+    ));
+
+    let mut translator = Translator {
+        blocks: cfg.blocks.iter().map(|_| builder.create_block()).collect(),
+        deopt: builder.create_block(),
+        builder,
+        file,
+        cfg: &cfg,
+        pointer,
+        slots,
+        depth,
+        max_locals: usize::from(code.max_locals),
+        stack: vec![],
+        entries: vec![None; cfg.blocks.len()],
+        declared: HashSet::new(),
+        calls,
+        scratch,
+    };
+    translator.entry(method)?;
+    for block in cfg.reverse_postorder() {
+        translator.block(block)?;
+    }
+
+    let Translator {
+        mut builder, deopt, ..
+    } = translator;
+    builder.switch_to_block(deopt);
+    let status = builder.ins().iconst(ir::types::I32, 1);
+    builder.ins().return_(&[status]);
+    builder.seal_all_blocks();
+    builder.finalize();
+    Ok(())
+}
+
+struct Translator<'a, 'b> {
+    builder: FunctionBuilder<'a>,
+    file: &'b ty::ClassFile,
+    cfg: &'b Cfg,
+    pointer: Type,
+    slots: ir::Value,
+    depth: ir::Value,
+    max_locals: usize,
+    stack: Vec<(ir::Value, Ty)>,
+    /// The IR block of each block, and the types on the stack when it's entered
+    blocks: Vec<ir::Block>,
+    entries: Vec<Option<Vec<Ty>>>,
+    /// Where the method deoptimizes
+    deopt: ir::Block,
+    declared: HashSet<u32>,
+    calls: HashMap<u16, (ir::FuncRef, Rc<Method>)>,
+    /// Where the arguments of calls go, and their results come back
+    scratch: ir::StackSlot,
+}
+
+impl Translator<'_, '_> {
+    /// Deoptimizes when called too deep, and otherwise loads the arguments into the locals
+    fn entry(&mut self, method: &Method) -> Result<()> {
+        let too_deep = self.builder.ins().icmp_imm(IntCC::Equal, self.depth, 0);
+        let body = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(too_deep, self.deopt, &[], body, &[]);
+        self.builder.switch_to_block(body);
+
+        let mut index = 0;
+        for (i, param) in method.signature.params.iter().enumerate() {
+            let ty = Ty::of(param);
+            let flags = MemFlags::trusted();
+            let offset = (i * 8) as i32;
+            let val = self.builder.ins().load(ty.ir(), flags, self.slots, offset);
+            let var = self.local(index, ty);
+            self.builder.def_var(var, val);
+            index += ty.words();
+        }
+        self.entries[0] = Some(vec![]);
+        self.builder.ins().jump(self.blocks[0], &[]);
+        Ok(())
+    }
+
+    fn block(&mut self, index: usize) -> Result<()> {
+        let entry = match &self.entries[index] {
+            Some(entry) => entry.clone(),
+            None => generic_error!("block {} is entered before it's reached", index),
+        };
+        self.builder.switch_to_block(self.blocks[index]);
+        self.stack.clear();
+        for (depth, ty) in entry.into_iter().enumerate() {
+            let var = self.stack_var(depth, ty);
+            let val = self.builder.use_var(var);
+            self.stack.push((val, ty));
+        }
+
+        let cfg = self.cfg;
+        let block = &cfg.blocks[index];
+        for (pc, instruction) in &block.instructions {
+            if self.instruction(*pc, instruction)? {
+                return Ok(());
+            }
+        }
+        self.goto(block.end)?;
+        Ok(())
+    }
+
+    /// The variable of a local
+    fn local(&mut self, index: usize, ty: Ty) -> Variable {
+        self.var(index, ty)
+    }
+
+    /// The variable that hands the value at `depth` of the stack from block to block
+    fn stack_var(&mut self, depth: usize, ty: Ty) -> Variable {
+        self.var(self.max_locals + depth, ty)
+    }
+
+    fn var(&mut self, slot: usize, ty: Ty) -> Variable {
+        let index = slot as u32 * 4 + ty as u32;
+        let var = Variable::from_u32(index);
+        if self.declared.insert(index) {
+            self.builder.declare_var(var, ty.ir());
+        }
+        var
+    }
+
+    /// The block starting at `pc`, which is entered with the stack as it is. The stack has
+    /// to be handed over before jumping there
+    fn enter(&mut self, pc: usize) -> Result<ir::Block> {
+        let index = self.cfg.block_at(pc)?;
+        let types = self.stack.iter().map(|&(_, ty)| ty).collect::<Vec<_>>();
+        match &self.entries[index] {
+            Some(entry) if *entry != types => {
+                generic_error!("the stack at {} is {:?} and {:?}", pc, entry, types)
+            }
+            Some(..) => {}
+            None => self.entries[index] = Some(types),
+        }
+        Ok(self.blocks[index])
+    }
+
+    /// Puts the stack in its variables, for the next blocks
+    fn hand_over(&mut self) {
+        for depth in 0..self.stack.len() {
+            let (val, ty) = self.stack[depth];
+            let var = self.stack_var(depth, ty);
+            self.builder.def_var(var, val);
+        }
+    }
+
+    fn push(&mut self, val: ir::Value, ty: Ty) {
+        self.stack.push((val, ty));
+    }
+
+    fn pop(&mut self, ty: Ty) -> Result<ir::Value> {
+        match self.stack.pop() {
+            Some((val, popped)) if popped == ty => Ok(val),
+            Some((_, popped)) => generic_error!("expected {:?} but got {:?}", ty, popped),
+            None => generic_error!("the stack is empty"),
+        }
+    }
+
+    /// How many values at the top of the stack, under the top `skip`, make up `words`
+    fn values_in(&self, words: usize, skip: usize) -> Result<usize> {
+        let (mut count, mut total) = (0, 0);
+        for &(_, ty) in self.stack.iter().rev().skip(skip) {
+            if total == words {
+                break;
+            }
+            total += ty.words();
+            count += 1;
+        }
+        if total != words {
+            generic_error!("{} words aren't at the top of the stack", words);
+        }
+        Ok(count)
+    }
+
+    /// Pushes a copy of the top `words` of the stack, under the `under` words below them
+    fn dup(&mut self, words: usize, under: usize) -> Result<()> {
+        let top = self.values_in(words, 0)?;
+        let below = match under {
+            0 => 0,
+            _ => self.values_in(under, top)?,
+        };
+        let at = self.stack.len() - top;
+        let copy = self.stack[at..].to_vec();
+        let at = at - below;
+        self.stack.splice(at..at, copy);
+        Ok(())
+    }
+
+    fn load(&mut self, index: usize, ty: Ty) {
+        let var = self.local(index, ty);
+        let val = self.builder.use_var(var);
+        self.push(val, ty);
+    }
+
+    fn store(&mut self, index: usize, ty: Ty) -> Result<()> {
+        let val = self.pop(ty)?;
+        let var = self.local(index, ty);
+        self.builder.def_var(var, val);
+        Ok(())
+    }
+
+    fn constant(&mut self, index: u16) -> Result<()> {
+        let ins = self.builder.ins();
+        let (val, ty) = match ConstantIndex(index).lookup(&self.file.constant_pool)? {
+            ty::Constant::Integer(d) => (ins.iconst(ir::types::I32, i64::from(*d as i32)), Ty::Int),
+            ty::Constant::Float(d) => (ins.f32const(*d), Ty::Float),
+            ty::Constant::Long(d) => (ins.iconst(ir::types::I64, *d as i64), Ty::Long),
+            ty::Constant::Double(d) => (ins.f64const(*d), Ty::Double),
+            constant => generic_error!("{:?} can't be compiled", constant),
+        };
+        self.push(val, ty);
+        Ok(())
+    }
+
+    fn unary(
+        &mut self,
+        from: Ty,
+        to: Ty,
+        op: impl FnOnce(&mut FunctionBuilder<'_>, ir::Value) -> ir::Value,
+    ) -> Result<()> {
+        let val = self.pop(from)?;
+        let val = op(&mut self.builder, val);
+        self.push(val, to);
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        ty: Ty,
+        op: impl FnOnce(&mut FunctionBuilder<'_>, ir::Value, ir::Value) -> ir::Value,
+    ) -> Result<()> {
+        let rhs = self.pop(ty)?;
+        let lhs = self.pop(ty)?;
+        let val = op(&mut self.builder, lhs, rhs);
+        self.push(val, ty);
+        Ok(())
+    }
+
+    /// Shifts by the low 5 bits of the int on top of the stack for ints, or 6 for longs
+    fn shift(
+        &mut self,
+        ty: Ty,
+        op: impl FnOnce(&mut FunctionBuilder<'_>, ir::Value, ir::Value) -> ir::Value,
+    ) -> Result<()> {
+        let amount = self.pop(Ty::Int)?;
+        let val = self.pop(ty)?;
+        let ins = self.builder.ins();
+        let amount = match ty {
+            Ty::Long => {
+                let amount = ins.uextend(ir::types::I64, amount);
+                self.builder.ins().band_imm(amount, 63)
+            }
+            _ => ins.band_imm(amount, 31),
+        };
+        let val = op(&mut self.builder, val, amount);
+        self.push(val, ty);
+        Ok(())
+    }
+
+    /// Divides, or takes the remainder. Dividing by zero deoptimizes, for the interpreter to
+    /// throw the `ArithmeticException`, and dividing the least value by -1 overflows to it
+    /// rather than trapping
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-6.html#jvms-6.5.idiv
+    fn divide(&mut self, ty: Ty, remainder: bool) -> Result<()> {
+        let rhs = self.pop(ty)?;
+        let lhs = self.pop(ty)?;
+        let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, rhs, 0);
+        let divisible = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(is_zero, self.deopt, &[], divisible, &[]);
+        self.builder.switch_to_block(divisible);
+
+        let ins = self.builder.ins();
+        let is_minus_one = ins.icmp_imm(IntCC::Equal, rhs, -1);
+        let one = self.builder.ins().iconst(ty.ir(), 1);
+        let divisor = self.builder.ins().select(is_minus_one, one, rhs);
+        let val = if remainder {
+            let val = self.builder.ins().srem(lhs, divisor);
+            let zero = self.builder.ins().iconst(ty.ir(), 0);
+            self.builder.ins().select(is_minus_one, zero, val)
+        } else {
+            let val = self.builder.ins().sdiv(lhs, divisor);
+            let negated = self.builder.ins().ineg(lhs);
+            self.builder.ins().select(is_minus_one, negated, val)
+        };
+        self.push(val, ty);
+        Ok(())
+    }
+
+    /// Pushes 1, 0 or -1 as the top two values are greater, equal or less. Floating point
+    /// values that are NaN push `nan`
+    fn compare(&mut self, ty: Ty, nan: i64) -> Result<()> {
+        let rhs = self.pop(ty)?;
+        let lhs = self.pop(ty)?;
+        let ins = self.builder.ins();
+        let (greater, less) = match ty {
+            Ty::Long => (
+                ins.icmp(IntCC::SignedGreaterThan, lhs, rhs),
+                self.builder.ins().icmp(IntCC::SignedLessThan, lhs, rhs),
+            ),
+            _ => (
+                ins.fcmp(FloatCC::GreaterThan, lhs, rhs),
+                self.builder.ins().fcmp(FloatCC::LessThan, lhs, rhs),
+            ),
+        };
+        let greater = self.builder.ins().uextend(ir::types::I32, greater);
+        let less = self.builder.ins().uextend(ir::types::I32, less);
+        let mut val = self.builder.ins().isub(greater, less);
+        if ty != Ty::Long {
+            let unordered = self.builder.ins().fcmp(FloatCC::Unordered, lhs, rhs);
+            let nan = self.builder.ins().iconst(ir::types::I32, nan);
+            val = self.builder.ins().select(unordered, nan, val);
+        }
+        self.push(val, Ty::Int);
+        Ok(())
+    }
+
+    /// Branches to `target` if `cond` holds, and otherwise goes on to `next`
+    fn branch(&mut self, cond: ir::Value, target: usize, next: usize) -> Result<bool> {
+        self.hand_over();
+        let taken = self.enter(target)?;
+        let not_taken = self.enter(next)?;
+        self.builder.ins().brif(cond, taken, &[], not_taken, &[]);
+        Ok(true)
+    }
+
+    fn if_zero(&mut self, cc: IntCC, target: usize, next: usize) -> Result<bool> {
+        let val = self.pop(Ty::Int)?;
+        let cond = self.builder.ins().icmp_imm(cc, val, 0);
+        self.branch(cond, target, next)
+    }
+
+    fn if_compare(&mut self, cc: IntCC, target: usize, next: usize) -> Result<bool> {
+        let rhs = self.pop(Ty::Int)?;
+        let lhs = self.pop(Ty::Int)?;
+        let cond = self.builder.ins().icmp(cc, lhs, rhs);
+        self.branch(cond, target, next)
+    }
+
+    fn goto(&mut self, target: usize) -> Result<bool> {
+        self.hand_over();
+        let block = self.enter(target)?;
+        self.builder.ins().jump(block, &[]);
+        Ok(true)
+    }
+
+    fn switch(&mut self, pairs: Vec<(i32, usize)>, default: usize) -> Result<bool> {
+        let key = self.pop(Ty::Int)?;
+        self.hand_over();
+        let mut switch = Switch::new();
+        for (value, target) in pairs {
+            let block = self.enter(target)?;
+            switch.set_entry(u128::from(value as u32), block);
+        }
+        let default = self.enter(default)?;
+        switch.emit(&mut self.builder, key, default);
+        Ok(true)
+    }
+
+    /// Calls a compiled method, deoptimizing if it does
+    fn invoke(&mut self, index: u16) -> Result<()> {
+        let (func, callee) = match self.calls.get(&index) {
+            Some((func, callee)) => (*func, Rc::clone(callee)),
+            None => generic_error!("method {} isn't compiled", index),
+        };
+        let params = &callee.signature.params;
+        let mut args = vec![];
+        for param in params.iter().rev() {
+            args.push(self.pop(Ty::of(param))?);
+        }
+        for (i, arg) in args.into_iter().rev().enumerate() {
+            self.builder
+                .ins()
+                .stack_store(arg, self.scratch, (i * 8) as i32);
+        }
+
+        let slots = self.builder.ins().stack_addr(self.pointer, self.scratch, 0);
+        let depth = self.builder.ins().iadd_imm(self.depth, -1);
+        let call = self.builder.ins().call(func, &[slots, depth]);
+        let status = self.builder.inst_results(call)[0];
+        let returned = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(status, self.deopt, &[], returned, &[]);
+        self.builder.switch_to_block(returned);
+
+        if let Some(ret) = &callee.signature.ret {
+            let ty = Ty::of(ret);
+            let val = self.builder.ins().stack_load(ty.ir(), self.scratch, 0);
+            self.push(val, ty);
+        }
+        Ok(())
+    }
+
+    /// Puts the value on top of the stack, if any, in the first slot and returns
+    fn ret(&mut self, ty: Option<Ty>) -> Result<bool> {
+        if let Some(ty) = ty {
+            let val = self.pop(ty)?;
+            self.builder
+                .ins()
+                .store(MemFlags::trusted(), val, self.slots, 0);
+        }
+        let status = self.builder.ins().iconst(ir::types::I32, 0);
+        self.builder.ins().return_(&[status]);
+        Ok(true)
+    }
+
+    /// Translates `instruction`, and returns whether it ends the block
+    fn instruction(&mut self, pc: usize, instruction: &Instruction) -> Result<bool> {
+        use ir::types::{F32, F64, I16, I32, I64, I8};
+        use Instruction as I;
+
+        let target = |offset: i32| (pc as isize + offset as isize) as usize;
+        let branch = |a: &u8, b: &u8| target(branch_offset(*a, *b));
+        let next = pc + instruction.size();
+
+        match instruction {
+            I::NOP(..) => {}
+            I::ICONST_M1(..) => self.int(-1),
+            I::ICONST_0(..) => self.int(0),
+            I::ICONST_1(..) => self.int(1),
+            I::ICONST_2(..) => self.int(2),
+            I::ICONST_3(..) => self.int(3),
+            I::ICONST_4(..) => self.int(4),
+            I::ICONST_5(..) => self.int(5),
+            I::BIPUSH(BIPUSH(d)) => self.int(i32::from(*d as i8)),
+            I::SIPUSH(SIPUSH(a, b)) => self.int(i32::from(i16::from_be_bytes([*a, *b]))),
+            I::LCONST_0(..) | I::LCONST_1(..) => {
+                let d = if let I::LCONST_0(..) = instruction {
+                    0
+                } else {
+                    1
+                };
+                let val = self.builder.ins().iconst(I64, d);
+                self.push(val, Ty::Long);
+            }
+            I::FCONST_0(..) | I::FCONST_1(..) | I::FCONST_2(..) => {
+                let d = match instruction {
+                    I::FCONST_0(..) => 0.0,
+                    I::FCONST_1(..) => 1.0,
+                    _ => 2.0,
+                };
+                let val = self.builder.ins().f32const(d);
+                self.push(val, Ty::Float);
+            }
+            I::DCONST_0(..) | I::DCONST_1(..) => {
+                let d = if let I::DCONST_0(..) = instruction {
+                    0.0
+                } else {
+                    1.0
+                };
+                let val = self.builder.ins().f64const(d);
+                self.push(val, Ty::Double);
+            }
+            I::LDC(LDC(index)) => self.constant(u16::from(*index))?,
+            I::LDC_W(LDC_W(a, b)) | I::LDC2_W(LDC2_W(a, b)) => self.constant(wide_index(*a, *b))?,
+            //
+            I::ILOAD(ILOAD(index)) => self.load(usize::from(*index), Ty::Int),
+            I::LLOAD(LLOAD(index)) => self.load(usize::from(*index), Ty::Long),
+            I::FLOAD(FLOAD(index)) => self.load(usize::from(*index), Ty::Float),
+            I::DLOAD(DLOAD(index)) => self.load(usize::from(*index), Ty::Double),
+            I::ILOAD_0(..) => self.load(0, Ty::Int),
+            I::ILOAD_1(..) => self.load(1, Ty::Int),
+            I::ILOAD_2(..) => self.load(2, Ty::Int),
+            I::ILOAD_3(..) => self.load(3, Ty::Int),
+            I::LLOAD_0(..) => self.load(0, Ty::Long),
+            I::LLOAD_1(..) => self.load(1, Ty::Long),
+            I::LLOAD_2(..) => self.load(2, Ty::Long),
+            I::LLOAD_3(..) => self.load(3, Ty::Long),
+            I::FLOAD_0(..) => self.load(0, Ty::Float),
+            I::FLOAD_1(..) => self.load(1, Ty::Float),
+            I::FLOAD_2(..) => self.load(2, Ty::Float),
+            I::FLOAD_3(..) => self.load(3, Ty::Float),
+            I::DLOAD_0(..) => self.load(0, Ty::Double),
+            I::DLOAD_1(..) => self.load(1, Ty::Double),
+            I::DLOAD_2(..) => self.load(2, Ty::Double),
+            I::DLOAD_3(..) => self.load(3, Ty::Double),
+            //
+            I::ISTORE(ISTORE(index)) => self.store(usize::from(*index), Ty::Int)?,
+            I::LSTORE(LSTORE(index)) => self.store(usize::from(*index), Ty::Long)?,
+            I::FSTORE(FSTORE(index)) => self.store(usize::from(*index), Ty::Float)?,
+            I::DSTORE(DSTORE(index)) => self.store(usize::from(*index), Ty::Double)?,
+            I::ISTORE_0(..) => self.store(0, Ty::Int)?,
+            I::ISTORE_1(..) => self.store(1, Ty::Int)?,
+            I::ISTORE_2(..) => self.store(2, Ty::Int)?,
+            I::ISTORE_3(..) => self.store(3, Ty::Int)?,
+            I::LSTORE_0(..) => self.store(0, Ty::Long)?,
+            I::LSTORE_1(..) => self.store(1, Ty::Long)?,
+            I::LSTORE_2(..) => self.store(2, Ty::Long)?,
+            I::LSTORE_3(..) => self.store(3, Ty::Long)?,
+            I::FSTORE_0(..) => self.store(0, Ty::Float)?,
+            I::FSTORE_1(..) => self.store(1, Ty::Float)?,
+            I::FSTORE_2(..) => self.store(2, Ty::Float)?,
+            I::FSTORE_3(..) => self.store(3, Ty::Float)?,
+            I::DSTORE_0(..) => self.store(0, Ty::Double)?,
+            I::DSTORE_1(..) => self.store(1, Ty::Double)?,
+            I::DSTORE_2(..) => self.store(2, Ty::Double)?,
+            I::DSTORE_3(..) => self.store(3, Ty::Double)?,
+            I::IINC(IINC(index, delta)) => {
+                let var = self.local(usize::from(*index), Ty::Int);
+                let val = self.builder.use_var(var);
+                let val = self.builder.ins().iadd_imm(val, i64::from(*delta as i8));
+                self.builder.def_var(var, val);
+            }
+            //
+            I::POP(..) | I::POP2(..) => {
+                let words = if let I::POP(..) = instruction { 1 } else { 2 };
+                let count = self.values_in(words, 0)?;
+                self.stack.truncate(self.stack.len() - count);
+            }
+            I::DUP(..) => self.dup(1, 0)?,
+            I::DUP_X1(..) => self.dup(1, 1)?,
+            I::DUP_X2(..) => self.dup(1, 2)?,
+            I::DUP2(..) => self.dup(2, 0)?,
+            I::DUP2_X1(..) => self.dup(2, 1)?,
+            I::DUP2_X2(..) => self.dup(2, 2)?,
+            I::SWAP(..) => {
+                let (top, below) = (self.values_in(1, 0)?, self.values_in(1, 1)?);
+                if top + below != 2 {
+                    generic_error!("swap of {} values", top + below);
+                }
+                let len = self.stack.len();
+                self.stack.swap(len - 1, len - 2);
+            }
+            //
+            I::IADD(..) => self.binary(Ty::Int, |b, x, y| b.ins().iadd(x, y))?,
+            I::LADD(..) => self.binary(Ty::Long, |b, x, y| b.ins().iadd(x, y))?,
+            I::FADD(..) => self.binary(Ty::Float, |b, x, y| b.ins().fadd(x, y))?,
+            I::DADD(..) => self.binary(Ty::Double, |b, x, y| b.ins().fadd(x, y))?,
+            I::ISUB(..) => self.binary(Ty::Int, |b, x, y| b.ins().isub(x, y))?,
+            I::LSUB(..) => self.binary(Ty::Long, |b, x, y| b.ins().isub(x, y))?,
+            I::FSUB(..) => self.binary(Ty::Float, |b, x, y| b.ins().fsub(x, y))?,
+            I::DSUB(..) => self.binary(Ty::Double, |b, x, y| b.ins().fsub(x, y))?,
+            I::IMUL(..) => self.binary(Ty::Int, |b, x, y| b.ins().imul(x, y))?,
+            I::LMUL(..) => self.binary(Ty::Long, |b, x, y| b.ins().imul(x, y))?,
+            I::FMUL(..) => self.binary(Ty::Float, |b, x, y| b.ins().fmul(x, y))?,
+            I::DMUL(..) => self.binary(Ty::Double, |b, x, y| b.ins().fmul(x, y))?,
+            I::IDIV(..) => self.divide(Ty::Int, false)?,
+            I::LDIV(..) => self.divide(Ty::Long, false)?,
+            I::FDIV(..) => self.binary(Ty::Float, |b, x, y| b.ins().fdiv(x, y))?,
+            I::DDIV(..) => self.binary(Ty::Double, |b, x, y| b.ins().fdiv(x, y))?,
+            I::IREM(..) => self.divide(Ty::Int, true)?,
+            I::LREM(..) => self.divide(Ty::Long, true)?,
+            I::INEG(..) => self.unary(Ty::Int, Ty::Int, |b, x| b.ins().ineg(x))?,
+            I::LNEG(..) => self.unary(Ty::Long, Ty::Long, |b, x| b.ins().ineg(x))?,
+            I::FNEG(..) => self.unary(Ty::Float, Ty::Float, |b, x| b.ins().fneg(x))?,
+            I::DNEG(..) => self.unary(Ty::Double, Ty::Double, |b, x| b.ins().fneg(x))?,
+            I::ISHL(..) => self.shift(Ty::Int, |b, x, y| b.ins().ishl(x, y))?,
+            I::LSHL(..) => self.shift(Ty::Long, |b, x, y| b.ins().ishl(x, y))?,
+            I::ISHR(..) => self.shift(Ty::Int, |b, x, y| b.ins().sshr(x, y))?,
+            I::LSHR(..) => self.shift(Ty::Long, |b, x, y| b.ins().sshr(x, y))?,
+            I::IUSHR(..) => self.shift(Ty::Int, |b, x, y| b.ins().ushr(x, y))?,
+            I::LUSHR(..) => self.shift(Ty::Long, |b, x, y| b.ins().ushr(x, y))?,
+            I::IAND(..) => self.binary(Ty::Int, |b, x, y| b.ins().band(x, y))?,
+            I::LAND(..) => self.binary(Ty::Long, |b, x, y| b.ins().band(x, y))?,
+            I::IOR(..) => self.binary(Ty::Int, |b, x, y| b.ins().bor(x, y))?,
+            I::LOR(..) => self.binary(Ty::Long, |b, x, y| b.ins().bor(x, y))?,
+            I::IXOR(..) => self.binary(Ty::Int, |b, x, y| b.ins().bxor(x, y))?,
+            I::LXOR(..) => self.binary(Ty::Long, |b, x, y| b.ins().bxor(x, y))?,
+            // floating point values are converted to integers as Java does, saturating, and
+            // NaN to 0
+            I::I2L(..) => self.unary(Ty::Int, Ty::Long, |b, x| b.ins().sextend(I64, x))?,
+            I::I2F(..) => self.unary(Ty::Int, Ty::Float, |b, x| b.ins().fcvt_from_sint(F32, x))?,
+            I::I2D(..) => self.unary(Ty::Int, Ty::Double, |b, x| b.ins().fcvt_from_sint(F64, x))?,
+            I::L2I(..) => self.unary(Ty::Long, Ty::Int, |b, x| b.ins().ireduce(I32, x))?,
+            I::L2F(..) => self.unary(Ty::Long, Ty::Float, |b, x| b.ins().fcvt_from_sint(F32, x))?,
+            I::L2D(..) => {
+                self.unary(Ty::Long, Ty::Double, |b, x| b.ins().fcvt_from_sint(F64, x))?
+            }
+            I::F2I(..) => {
+                self.unary(Ty::Float, Ty::Int, |b, x| b.ins().fcvt_to_sint_sat(I32, x))?
+            }
+            I::F2L(..) => {
+                self.unary(Ty::Float, Ty::Long, |b, x| b.ins().fcvt_to_sint_sat(I64, x))?
+            }
+            I::F2D(..) => self.unary(Ty::Float, Ty::Double, |b, x| b.ins().fpromote(F64, x))?,
+            I::D2I(..) => {
+                self.unary(Ty::Double, Ty::Int, |b, x| b.ins().fcvt_to_sint_sat(I32, x))?
+            }
+            I::D2L(..) => self.unary(Ty::Double, Ty::Long, |b, x| {
+                b.ins().fcvt_to_sint_sat(I64, x)
+            })?,
+            I::D2F(..) => self.unary(Ty::Double, Ty::Float, |b, x| b.ins().fdemote(F32, x))?,
+            I::I2B(..) => self.unary(Ty::Int, Ty::Int, |b, x| {
+                let x = b.ins().ireduce(I8, x);
+                b.ins().sextend(I32, x)
+            })?,
+            I::I2C(..) => self.unary(Ty::Int, Ty::Int, |b, x| {
+                let x = b.ins().ireduce(I16, x);
+                b.ins().uextend(I32, x)
+            })?,
+            I::I2S(..) => self.unary(Ty::Int, Ty::Int, |b, x| {
+                let x = b.ins().ireduce(I16, x);
+                b.ins().sextend(I32, x)
+            })?,
+            I::LCMP(..) => self.compare(Ty::Long, 0)?,
+            I::FCMPL(..) => self.compare(Ty::Float, -1)?,
+            I::FCMPG(..) => self.compare(Ty::Float, 1)?,
+            I::DCMPL(..) => self.compare(Ty::Double, -1)?,
+            I::DCMPG(..) => self.compare(Ty::Double, 1)?,
+            //
+            I::IFEQ(IFEQ(a, b)) => return self.if_zero(IntCC::Equal, branch(a, b), next),
+            I::IFNE(IFNE(a, b)) => return self.if_zero(IntCC::NotEqual, branch(a, b), next),
+            I::IFLT(IFLT(a, b)) => return self.if_zero(IntCC::SignedLessThan, branch(a, b), next),
+            I::IFGE(IFGE(a, b)) => {
+                return self.if_zero(IntCC::SignedGreaterThanOrEqual, branch(a, b), next)
+            }
+            I::IFGT(IFGT(a, b)) => {
+                return self.if_zero(IntCC::SignedGreaterThan, branch(a, b), next)
+            }
+            I::IFLE(IFLE(a, b)) => {
+                return self.if_zero(IntCC::SignedLessThanOrEqual, branch(a, b), next)
+            }
+            I::IF_ICMPEQ(IF_ICMPEQ(a, b)) => {
+                return self.if_compare(IntCC::Equal, branch(a, b), next)
+            }
+            I::IF_ICMPNE(IF_ICMPNE(a, b)) => {
+                return self.if_compare(IntCC::NotEqual, branch(a, b), next)
+            }
+            I::IF_ICMPLT(IF_ICMPLT(a, b)) => {
+                return self.if_compare(IntCC::SignedLessThan, branch(a, b), next)
+            }
+            I::IF_ICMPGE(IF_ICMPGE(a, b)) => {
+                return self.if_compare(IntCC::SignedGreaterThanOrEqual, branch(a, b), next)
+            }
+            I::IF_ICMPGT(IF_ICMPGT(a, b)) => {
+                return self.if_compare(IntCC::SignedGreaterThan, branch(a, b), next)
+            }
+            I::IF_ICMPLE(IF_ICMPLE(a, b)) => {
+                return self.if_compare(IntCC::SignedLessThanOrEqual, branch(a, b), next)
+            }
+            I::GOTO(GOTO(a, b)) => return self.goto(branch(a, b)),
+            I::GOTO_W(GOTO_W(a, b, c, d)) => {
+                return self.goto(target(branch_offset_wide(*a, *b, *c, *d)))
+            }
+            I::TABLESWITCH(table) => {
+                let (default, low, offsets) = table.table();
+                let pairs = (offsets.into_iter().enumerate())
+                    .map(|(i, offset)| (low.wrapping_add(i as i32), target(offset)))
+                    .collect();
+                return self.switch(pairs, target(default));
+            }
+            I::LOOKUPSWITCH(table) => {
+                let (default, pairs) = table.pairs();
+                let pairs = (pairs.into_iter())
+                    .map(|(key, offset)| (key, target(offset)))
+                    .collect();
+                return self.switch(pairs, target(default));
+            }
+            //
+            I::INVOKESTATIC(INVOKESTATIC(a, b)) => self.invoke(wide_index(*a, *b))?,
+            I::IRETURN(..) => return self.ret(Some(Ty::Int)),
+            I::LRETURN(..) => return self.ret(Some(Ty::Long)),
+            I::FRETURN(..) => return self.ret(Some(Ty::Float)),
+            I::DRETURN(..) => return self.ret(Some(Ty::Double)),
+            I::RETURN(..) => return self.ret(None),
+            _ => generic_error!("{:?} at {} can't be compiled", instruction, pc),
+        }
+        Ok(false)
+    }
+
+    fn int(&mut self, d: i32) {
+        let val = self.builder.ins().iconst(ir::types::I32, i64::from(d));
+        self.push(val, Ty::Int);
+    }
+}
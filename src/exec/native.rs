@@ -0,0 +1,439 @@
+use super::class::{Class, ClassDef, Field, Method, MethodBody};
+use super::heap::Reference;
+use super::interpreter::Interpreter;
+use super::value::Value;
+use super::*;
+
+use std::io::Write;
+use std::rc::Rc;
+use ty::{ClassFlags, FieldFlags, FieldType, MethodFlags};
+
+mod boxing;
+mod invoke;
+mod math;
+mod string;
+mod system;
+mod thread;
+
+pub(crate) use string::{format_primitive, stringify};
+
+/// A method implemented in Rust. The arguments include `this` for instance methods
+pub type NativeFn = fn(&mut Interpreter, Vec<Value>) -> Result<Option<Value>>;
+
+/// The exceptions and errors that are provided by the interpreter, and their super class
+const THROWABLES: &[(&str, &str)] = &[
+    ("java/lang/Exception", "java/lang/Throwable"),
+    ("java/lang/Error", "java/lang/Throwable"),
+    ("java/lang/RuntimeException", "java/lang/Exception"),
+    (
+        "java/lang/ArithmeticException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/ArrayStoreException",
+        "java/lang/RuntimeException",
+    ),
+    ("java/lang/ClassCastException", "java/lang/RuntimeException"),
+    (
+        "java/lang/IllegalArgumentException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IllegalStateException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IllegalMonitorStateException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IllegalThreadStateException",
+        "java/lang/IllegalArgumentException",
+    ),
+    (
+        "java/lang/IndexOutOfBoundsException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/StringIndexOutOfBoundsException",
+        "java/lang/IndexOutOfBoundsException",
+    ),
+    (
+        "java/lang/NumberFormatException",
+        "java/lang/IllegalArgumentException",
+    ),
+    (
+        "java/lang/ArrayIndexOutOfBoundsException",
+        "java/lang/IndexOutOfBoundsException",
+    ),
+    (
+        "java/lang/NegativeArraySizeException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/NullPointerException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/UnsupportedOperationException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/invoke/WrongMethodTypeException",
+        "java/lang/RuntimeException",
+    ),
+    ("java/lang/InterruptedException", "java/lang/Exception"),
+    (
+        "java/lang/ReflectiveOperationException",
+        "java/lang/Exception",
+    ),
+    (
+        "java/lang/NoSuchMethodException",
+        "java/lang/ReflectiveOperationException",
+    ),
+    (
+        "java/lang/NoSuchFieldException",
+        "java/lang/ReflectiveOperationException",
+    ),
+    (
+        "java/lang/IllegalAccessException",
+        "java/lang/ReflectiveOperationException",
+    ),
+    ("java/lang/VirtualMachineError", "java/lang/Error"),
+    (
+        "java/lang/OutOfMemoryError",
+        "java/lang/VirtualMachineError",
+    ),
+    (
+        "java/lang/StackOverflowError",
+        "java/lang/VirtualMachineError",
+    ),
+    ("java/lang/LinkageError", "java/lang/Error"),
+    ("java/lang/NoClassDefFoundError", "java/lang/LinkageError"),
+    (
+        "java/lang/ExceptionInInitializerError",
+        "java/lang/LinkageError",
+    ),
+    ("java/lang/UnsatisfiedLinkError", "java/lang/LinkageError"),
+    ("java/lang/BootstrapMethodError", "java/lang/LinkageError"),
+    ("java/lang/VerifyError", "java/lang/LinkageError"),
+    (
+        "java/lang/IncompatibleClassChangeError",
+        "java/lang/LinkageError",
+    ),
+    (
+        "java/lang/AbstractMethodError",
+        "java/lang/IncompatibleClassChangeError",
+    ),
+    (
+        "java/lang/InstantiationError",
+        "java/lang/IncompatibleClassChangeError",
+    ),
+    (
+        "java/lang/NoSuchFieldError",
+        "java/lang/IncompatibleClassChangeError",
+    ),
+    (
+        "java/lang/NoSuchMethodError",
+        "java/lang/IncompatibleClassChangeError",
+    ),
+];
+
+/// Classes that are provided by the interpreter rather than a class file
+pub fn bootstrap_class(name: &str) -> Option<Result<ClassDef>> {
+    match name {
+        "java/lang/Object" => Some(object()),
+        "java/lang/String" => Some(string::string()),
+        "java/lang/StringBuilder" => Some(string::string_builder()),
+        "java/lang/CharSequence" => Some(string::char_sequence()),
+        "java/lang/Comparable" => Some(string::comparable()),
+        "java/lang/System" => Some(system::system()),
+        "java/io/PrintStream" => Some(system::print_stream()),
+        "java/lang/Math" => Some(math::math()),
+        "java/lang/Thread" => Some(thread::thread()),
+        "java/lang/Runnable" => Some(thread::runnable()),
+        "java/lang/Number" => Some(boxing::number()),
+        "java/lang/Integer" => Some(boxing::integer()),
+        "java/lang/Long" => Some(boxing::long()),
+        "java/lang/Short" => Some(boxing::short()),
+        "java/lang/Byte" => Some(boxing::byte()),
+        "java/lang/Float" => Some(boxing::float()),
+        "java/lang/Double" => Some(boxing::double()),
+        "java/lang/Boolean" => Some(boxing::boolean()),
+        "java/lang/Character" => Some(boxing::character()),
+        "java/lang/Throwable" => Some(throwable()),
+        "java/lang/Class" => Some(class_mirror()),
+        "java/lang/Void" => Some(boxing::void()),
+        "java/lang/invoke/MethodType" => Some(invoke::method_type()),
+        "java/lang/invoke/MethodHandle" => Some(invoke::method_handle()),
+        "java/lang/invoke/MethodHandles" => Some(invoke::method_handles()),
+        "java/lang/invoke/MethodHandles$Lookup" => Some(invoke::lookup()),
+        _ => THROWABLES
+            .iter()
+            .find(|(class, _)| *class == name)
+            .map(|(class, super_class)| Ok(self::class(class, Some(super_class), vec![]))),
+    }
+}
+
+fn native(name: &str, descriptor: &str, flags: MethodFlags, func: NativeFn) -> Result<Method> {
+    Method::new(
+        name,
+        descriptor,
+        flags | MethodFlags::NATIVE,
+        MethodBody::Native(func),
+    )
+}
+
+fn abstract_method(name: &str, descriptor: &str) -> Result<Method> {
+    let flags = MethodFlags::PUBLIC | MethodFlags::ABSTRACT;
+    Method::new(name, descriptor, flags, MethodBody::None)
+}
+
+fn class(name: &str, super_class: Option<&str>, methods: Vec<Method>) -> ClassDef {
+    ClassDef {
+        name: name.to_string(),
+        flags: ClassFlags::PUBLIC | ClassFlags::SUPER,
+        super_class: super_class.map(ToString::to_string),
+        interfaces: vec![],
+        file: None,
+        methods,
+        fields: vec![],
+    }
+}
+
+fn interface(name: &str, methods: Vec<Method>) -> ClassDef {
+    ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::INTERFACE | ClassFlags::ABSTRACT,
+        ..class(name, Some("java/lang/Object"), methods)
+    }
+}
+
+/// Sets a static field of a class, for use in its `<clinit>`
+fn set_static(class: &Rc<Class>, name: &str, descriptor: &str, value: Value) -> Result<()> {
+    match class.find_field(name, descriptor) {
+        Some(field) if field.is_static() => {
+            class.statics.borrow_mut()[field.slot] = value;
+            Ok(())
+        }
+        _ => Err(Error::NoSuchField(format!("{}.{}", class.name, name))),
+    }
+}
+
+fn this(args: &[Value]) -> Result<Reference> {
+    match args.first() {
+        Some(Value::Reference(this)) => Ok(*this),
+        _ => Err(Error::StackType("reference")),
+    }
+}
+
+fn object() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+    let final_ = MethodFlags::PUBLIC | MethodFlags::FINAL;
+    Ok(class(
+        "java/lang/Object",
+        None,
+        vec![
+            native("<init>", "()V", public, |_, _| Ok(None))?,
+            native("hashCode", "()I", public, |_, args| {
+                Ok(Some(Value::Int(this(&args)?.index() as i32)))
+            })?,
+            native("equals", "(Ljava/lang/Object;)Z", public, |_, args| {
+                Ok(Some(Value::from(args[0] == args[1])))
+            })?,
+            native("toString", "()Ljava/lang/String;", public, |vm, args| {
+                let this = this(&args)?;
+                let name = vm.heap().get(this).class_name().replace('/', ".");
+                let hash = match vm.call_method(this, "hashCode", "()I", vec![])? {
+                    Some(hash) => hash.as_int()?,
+                    None => return Ok(None),
+                };
+                string::string_value(vm, &format!("{}@{:x}", name, hash))
+            })?,
+            native("getClass", "()Ljava/lang/Class;", public, |vm, args| {
+                let name = vm.heap().get(this(&args)?).class_name();
+                vm.class_mirror(&name).map(|mirror| Some(mirror.into()))
+            })?,
+            native("wait", "()V", final_, |vm, args| {
+                vm.wait(this(&args)?, 0)?;
+                Ok(None)
+            })?,
+            native("wait", "(J)V", final_, |vm, args| {
+                vm.wait(this(&args)?, args[1].as_long()?)?;
+                Ok(None)
+            })?,
+            native("notify", "()V", final_, |vm, args| {
+                vm.notify(this(&args)?, false)?;
+                Ok(None)
+            })?,
+            native("notifyAll", "()V", final_, |vm, args| {
+                vm.notify(this(&args)?, true)?;
+                Ok(None)
+            })?,
+        ],
+    ))
+}
+
+/// The name a `java/lang/Class` object was created with
+fn mirror_name(vm: &Interpreter, args: &[Value]) -> Result<String> {
+    match vm.mirror_name(this(args)?) {
+        Some(name) => Ok(name.to_string()),
+        None => generic_error!("not a class mirror"),
+    }
+}
+
+fn is_primitive(name: &str) -> bool {
+    matches!(
+        name,
+        "boolean" | "byte" | "char" | "short" | "int" | "long" | "float" | "double" | "void"
+    )
+}
+
+fn class_mirror() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+
+    fn is_interface(vm: &mut Interpreter, name: &str) -> Result<bool> {
+        if is_primitive(name) || name.starts_with('[') {
+            return Ok(false);
+        }
+        Ok(vm.resolve_class(name)?.is_interface())
+    }
+
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::FINAL | ClassFlags::SUPER,
+        ..class(
+            "java/lang/Class",
+            Some("java/lang/Object"),
+            vec![
+                native("getName", "()Ljava/lang/String;", public, |vm, args| {
+                    let name = mirror_name(vm, &args)?.replace('/', ".");
+                    vm.intern(&name).map(|name| Some(name.into()))
+                })?,
+                native("isArray", "()Z", public, |vm, args| {
+                    Ok(Some(mirror_name(vm, &args)?.starts_with('[').into()))
+                })?,
+                native("isPrimitive", "()Z", public, |vm, args| {
+                    Ok(Some(is_primitive(&mirror_name(vm, &args)?).into()))
+                })?,
+                native("isInterface", "()Z", public, |vm, args| {
+                    let name = mirror_name(vm, &args)?;
+                    Ok(Some(is_interface(vm, &name)?.into()))
+                })?,
+                native("toString", "()Ljava/lang/String;", public, |vm, args| {
+                    let name = mirror_name(vm, &args)?;
+                    let s = match name.as_str() {
+                        name if is_primitive(name) => name.to_string(),
+                        name if is_interface(vm, name)? => {
+                            format!("interface {}", name.replace('/', "."))
+                        }
+                        name => format!("class {}", name.replace('/', ".")),
+                    };
+                    vm.new_string(&s).map(|s| Some(s.into()))
+                })?,
+            ],
+        )
+    })
+}
+
+const MESSAGE: &str = "Ljava/lang/String;";
+const CAUSE: &str = "Ljava/lang/Throwable;";
+
+fn throwable() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+
+    fn init(vm: &mut Interpreter, this: Reference, message: Value, cause: Value) -> Result<()> {
+        vm.set_field(this, "detailMessage", MESSAGE, message)?;
+        vm.set_field(this, "cause", CAUSE, cause)?;
+        vm.fill_in_stack_trace(this);
+        Ok(())
+    }
+
+    /// `Throwable.toString`, the class name and message
+    fn describe(vm: &mut Interpreter, this: Reference) -> Result<Reference> {
+        let exception = vm.java_exception(this)?;
+        let mut s = exception.class.replace('/', ".");
+        if let Some(message) = exception.message {
+            s = format!("{}: {}", s, message);
+        }
+        vm.new_string(&s)
+    }
+
+    Ok(ClassDef {
+        fields: vec![
+            Field::new("detailMessage", MESSAGE, FieldFlags::PRIVATE)?,
+            Field::new("cause", CAUSE, FieldFlags::PRIVATE)?,
+        ],
+        ..class(
+            "java/lang/Throwable",
+            Some("java/lang/Object"),
+            vec![
+                native("<init>", "()V", public, |vm, args| {
+                    init(vm, this(&args)?, Value::Null, Value::Null)?;
+                    Ok(None)
+                })?,
+                native("<init>", "(Ljava/lang/String;)V", public, |vm, args| {
+                    init(vm, this(&args)?, args[1], Value::Null)?;
+                    Ok(None)
+                })?,
+                native(
+                    "<init>",
+                    "(Ljava/lang/String;Ljava/lang/Throwable;)V",
+                    public,
+                    |vm, args| {
+                        init(vm, this(&args)?, args[1], args[2])?;
+                        Ok(None)
+                    },
+                )?,
+                native("<init>", "(Ljava/lang/Throwable;)V", public, |vm, args| {
+                    let message = match args[1] {
+                        Value::Reference(cause) => describe(vm, cause)?.into(),
+                        _ => Value::Null,
+                    };
+                    init(vm, this(&args)?, message, args[1])?;
+                    Ok(None)
+                })?,
+                native("getMessage", "()Ljava/lang/String;", public, |vm, args| {
+                    vm.get_field(this(&args)?, "detailMessage", MESSAGE)
+                        .map(Some)
+                })?,
+                native(
+                    "getLocalizedMessage",
+                    "()Ljava/lang/String;",
+                    public,
+                    |vm, args| {
+                        vm.get_field(this(&args)?, "detailMessage", MESSAGE)
+                            .map(Some)
+                    },
+                )?,
+                native("getCause", "()Ljava/lang/Throwable;", public, |vm, args| {
+                    vm.get_field(this(&args)?, "cause", CAUSE).map(Some)
+                })?,
+                native(
+                    "initCause",
+                    "(Ljava/lang/Throwable;)Ljava/lang/Throwable;",
+                    public,
+                    |vm, args| {
+                        vm.set_field(this(&args)?, "cause", CAUSE, args[1])?;
+                        Ok(Some(args[0]))
+                    },
+                )?,
+                native(
+                    "fillInStackTrace",
+                    "()Ljava/lang/Throwable;",
+                    public,
+                    |vm, args| {
+                        vm.fill_in_stack_trace(this(&args)?);
+                        Ok(Some(args[0]))
+                    },
+                )?,
+                native("toString", "()Ljava/lang/String;", public, |vm, args| {
+                    describe(vm, this(&args)?).map(|s| Some(s.into()))
+                })?,
+                native("printStackTrace", "()V", public, |vm, args| {
+                    let exception = vm.java_exception(this(&args)?)?;
+                    writeln!(vm.stderr(), "{}", exception)?;
+                    Ok(None)
+                })?,
+            ],
+        )
+    })
+}
@@ -0,0 +1,865 @@
+use super::string::{format_primitive, string_value};
+use super::*;
+
+use std::convert::TryFrom;
+
+/// Boxes in `-128..=127` are shared, like `Integer.valueOf` does
+const CACHE: std::ops::RangeInclusive<i64> = -128..=127;
+
+/// Converts a primitive like the `i2l`, `d2i`, etc instructions do. `descriptor` is the
+/// primitive to convert to
+fn convert(value: Value, descriptor: &str) -> Result<Value> {
+    let (int, long, float, double) = match value {
+        Value::Int(d) => (d, i64::from(d), d as f32, f64::from(d)),
+        Value::Long(d) => (d as i32, d, d as f32, d as f64),
+        Value::Float(d) => (d as i32, d as i64, d, f64::from(d)),
+        Value::Double(d) => (d as i32, d as i64, d as f32, d),
+        _ => return Err(Error::StackType("primitive")),
+    };
+    let val = match descriptor {
+        "B" => Value::Int(i32::from(int as i8)),
+        "S" => Value::Int(i32::from(int as i16)),
+        "C" => Value::Int(i32::from(int as u16)),
+        "J" => Value::Long(long),
+        "F" => Value::Float(float),
+        "D" => Value::Double(double),
+        _ => Value::Int(int),
+    };
+    Ok(val)
+}
+
+/// The bits `equals` and `hashCode` use, where all NaNs are the same
+fn bits(value: Value) -> i64 {
+    match value {
+        Value::Int(d) => i64::from(d),
+        Value::Long(d) => d,
+        Value::Float(d) if d.is_nan() => 0x7fc0_0000,
+        Value::Float(d) => i64::from(d.to_bits() as i32),
+        Value::Double(d) if d.is_nan() => 0x7ff8_0000_0000_0000,
+        Value::Double(d) => d.to_bits() as i64,
+        _ => 0,
+    }
+}
+
+// https://docs.oracle.com/javase/8/docs/api/java/lang/Long.html#hashCode--
+fn hash(value: Value) -> i32 {
+    match value {
+        Value::Long(..) | Value::Double(..) => {
+            let bits = bits(value);
+            (bits ^ (bits as u64 >> 32) as i64) as i32
+        }
+        value => bits(value) as i32,
+    }
+}
+
+/// `compare` of the boxing classes, where `-0.0` is less than `0.0` and NaN is greater
+/// than everything else
+fn compare(lhs: Value, rhs: Value) -> Result<i32> {
+    use std::cmp::Ordering::*;
+    let ordering = match (lhs, rhs) {
+        (Value::Int(a), Value::Int(b)) => a.cmp(&b),
+        (Value::Long(a), Value::Long(b)) => a.cmp(&b),
+        (Value::Float(a), Value::Float(b)) => a
+            .partial_cmp(&b)
+            .filter(|&ordering| ordering != Equal)
+            .unwrap_or_else(|| bits(lhs).cmp(&bits(rhs))),
+        (Value::Double(a), Value::Double(b)) => a
+            .partial_cmp(&b)
+            .filter(|&ordering| ordering != Equal)
+            .unwrap_or_else(|| bits(lhs).cmp(&bits(rhs))),
+        _ => return Err(Error::StackType("primitive")),
+    };
+    Ok(match ordering {
+        Less => -1,
+        Equal => 0,
+        Greater => 1,
+    })
+}
+
+/// Boxes `value` as an instance of `class`, whose `value` field has the type `descriptor`
+fn new_box(vm: &mut Interpreter, class: &str, descriptor: &str, value: Value) -> Result<Reference> {
+    let class = vm.resolve_class(class)?;
+    let boxed = vm.heap_mut().new_instance(class);
+    vm.set_field(boxed, "value", descriptor, value)?;
+    Ok(boxed)
+}
+
+/// `valueOf`, which shares the boxes of small values
+fn value_of(
+    vm: &mut Interpreter,
+    class: &str,
+    descriptor: &str,
+    value: Value,
+) -> Result<Reference> {
+    let small = match value {
+        Value::Int(d) if descriptor == "C" => d <= 127,
+        Value::Int(d) => CACHE.contains(&i64::from(d)),
+        Value::Long(d) => CACHE.contains(&d),
+        _ => false,
+    };
+    if !small {
+        return new_box(vm, class, descriptor, value);
+    }
+
+    let owner = vm.resolve_class(class)?;
+    let field = match owner.find_field("cache", &format!("[L{};", class)) {
+        Some(field) => field,
+        None => return new_box(vm, class, descriptor, value),
+    };
+    let cache = match owner.statics.borrow()[field.slot] {
+        Value::Reference(cache) => Some(cache),
+        _ => None,
+    };
+    let cache = match cache {
+        Some(cache) => cache,
+        None => {
+            let component = FieldType::Object(class.to_string());
+            let size = CACHE.count();
+            let cache = vm.heap_mut().new_array(component, size);
+            owner.statics.borrow_mut()[field.slot] = cache.into();
+            cache
+        }
+    };
+
+    let index = (bits(value) - CACHE.start()) as usize;
+    if let Value::Reference(boxed) = vm.heap().array(cache)?.elements[index] {
+        return Ok(boxed);
+    }
+    let boxed = new_box(vm, class, descriptor, value)?;
+    vm.heap_mut().array_mut(cache)?.elements[index] = boxed.into();
+    Ok(boxed)
+}
+
+/// Whether `other` is a box of the same class as `this`, holding the same value
+fn equals(vm: &Interpreter, this: Reference, other: Value, descriptor: &str) -> Result<bool> {
+    let other = match other.as_reference()? {
+        Some(other) => other,
+        None => return Ok(false),
+    };
+    if vm.heap().get(this).class_name() != vm.heap().get(other).class_name() {
+        return Ok(false);
+    }
+    let lhs = vm.get_field(this, "value", descriptor)?;
+    let rhs = vm.get_field(other, "value", descriptor)?;
+    Ok(bits(lhs) == bits(rhs))
+}
+
+/// The argument as a string, throwing a `NumberFormatException` for `null`
+fn number_arg(vm: &mut Interpreter, arg: Value) -> Result<Option<String>> {
+    match arg.as_reference()? {
+        Some(s) => vm.read_string(s).map(Some),
+        None => {
+            vm.throw_new("java/lang/NumberFormatException", Some("null"))?;
+            Ok(None)
+        }
+    }
+}
+
+fn number_format(vm: &mut Interpreter, s: &str) -> Result<Option<Value>> {
+    let message = format!("For input string: \"{}\"", s);
+    vm.throw_new("java/lang/NumberFormatException", Some(&message))?;
+    Ok(None)
+}
+
+/// `Integer.parseInt` and `Long.parseLong`, `descriptor` is the type to parse
+// https://docs.oracle.com/javase/8/docs/api/java/lang/Integer.html#parseInt-java.lang.String-int-
+fn parse_integer(
+    vm: &mut Interpreter,
+    arg: Value,
+    radix: i32,
+    descriptor: &str,
+) -> Result<Option<Value>> {
+    let s = match number_arg(vm, arg)? {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+    if !(2..=36).contains(&radix) {
+        let message = format!("radix {} out of range", radix);
+        vm.throw_new("java/lang/NumberFormatException", Some(&message))?;
+        return Ok(None);
+    }
+
+    let radix = radix as u32;
+    let parsed = i64::from_str_radix(&s, radix)
+        .ok()
+        .and_then(|d| match descriptor {
+            "B" => i8::try_from(d).ok().map(Value::from),
+            "S" => i16::try_from(d).ok().map(Value::from),
+            "I" => i32::try_from(d).ok().map(Value::from),
+            _ => Some(Value::Long(d)),
+        });
+    match parsed {
+        Some(val) => Ok(Some(val)),
+        None => number_format(vm, &s),
+    }
+}
+
+/// `Double.parseDouble` and `Float.parseFloat`
+fn parse_decimal(vm: &mut Interpreter, arg: Value, descriptor: &str) -> Result<Option<Value>> {
+    let s = match arg.as_reference()? {
+        Some(s) => vm.read_string(s)?,
+        None => {
+            vm.throw_new("java/lang/NullPointerException", None)?;
+            return Ok(None);
+        }
+    };
+
+    let trimmed = s.trim_matches(|c| c <= ' ');
+    let number = trimmed.trim_end_matches(&['d', 'D', 'f', 'F'][..]);
+    let (sign, unsigned) = match number.strip_prefix('-') {
+        Some(unsigned) => (-1.0, unsigned),
+        None => (1.0, number.strip_prefix('+').unwrap_or(number)),
+    };
+    let parsed = match trimmed {
+        "NaN" => Some(f64::NAN),
+        _ if unsigned == "Infinity" && number.len() == trimmed.len() => Some(sign * f64::INFINITY),
+        // rust also accepts `inf` and `nan`
+        _ if unsigned
+            .chars()
+            .any(|c| c.is_ascii_alphabetic() && c != 'e' && c != 'E') =>
+        {
+            None
+        }
+        _ => number.parse::<f64>().ok(),
+    };
+    match (parsed, descriptor) {
+        (Some(d), "F") => Ok(Some(Value::Float(d as f32))),
+        (Some(d), _) => Ok(Some(Value::Double(d))),
+        (None, _) if trimmed.is_empty() => {
+            vm.throw_new("java/lang/NumberFormatException", Some("empty String"))?;
+            Ok(None)
+        }
+        (None, _) => number_format(vm, &s),
+    }
+}
+
+/// Sets the `TYPE` of a boxing class to the `java/lang/Class` of its primitive, `None`
+/// being `void`
+fn init_type(vm: &mut Interpreter, class: &str, primitive: Option<&FieldType>) -> Result<()> {
+    let class = vm.resolve_class(class)?;
+    let mirror = vm.type_mirror(primitive)?;
+    set_static(&class, "TYPE", "Ljava/lang/Class;", mirror.into())
+}
+
+/// The methods every boxing class has. `$descriptor` is the type of its value
+macro_rules! boxed {
+    ($class:expr, $descriptor:expr) => {
+        vec![
+            native("<clinit>", "()V", MethodFlags::STATIC, |vm, _| {
+                let ty = FieldType::parse($descriptor)?;
+                init_type(vm, $class, Some(&ty))?;
+                Ok(None)
+            })?,
+            native(
+                "<init>",
+                concat!("(", $descriptor, ")V"),
+                MethodFlags::PUBLIC,
+                |vm, args| {
+                    vm.set_field(this(&args)?, "value", $descriptor, args[1])?;
+                    Ok(None)
+                },
+            )?,
+            native(
+                "valueOf",
+                concat!("(", $descriptor, ")L", $class, ";"),
+                MethodFlags::PUBLIC | MethodFlags::STATIC,
+                |vm, args| {
+                    let boxed = value_of(vm, $class, $descriptor, args[0])?;
+                    Ok(Some(boxed.into()))
+                },
+            )?,
+            native(
+                "toString",
+                "()Ljava/lang/String;",
+                MethodFlags::PUBLIC,
+                |vm, args| {
+                    let value = vm.get_field(this(&args)?, "value", $descriptor)?;
+                    let s = format_primitive(value, &FieldType::parse($descriptor)?)?;
+                    string_value(vm, &s)
+                },
+            )?,
+            native(
+                "toString",
+                concat!("(", $descriptor, ")Ljava/lang/String;"),
+                MethodFlags::PUBLIC | MethodFlags::STATIC,
+                |vm, args| {
+                    let s = format_primitive(args[0], &FieldType::parse($descriptor)?)?;
+                    string_value(vm, &s)
+                },
+            )?,
+            native("hashCode", "()I", MethodFlags::PUBLIC, |vm, args| {
+                let value = vm.get_field(this(&args)?, "value", $descriptor)?;
+                Ok(Some(Value::Int(match $descriptor {
+                    "Z" if value == Value::Int(1) => 1231,
+                    "Z" => 1237,
+                    _ => hash(value),
+                })))
+            })?,
+            native(
+                "equals",
+                "(Ljava/lang/Object;)Z",
+                MethodFlags::PUBLIC,
+                |vm, args| {
+                    let equal = equals(vm, this(&args)?, args[1], $descriptor)?;
+                    Ok(Some(equal.into()))
+                },
+            )?,
+            native(
+                "compare",
+                concat!("(", $descriptor, $descriptor, ")I"),
+                MethodFlags::PUBLIC | MethodFlags::STATIC,
+                |_, args| Ok(Some(Value::Int(compare(args[0], args[1])?))),
+            )?,
+            native(
+                "compareTo",
+                concat!("(L", $class, ";)I"),
+                MethodFlags::PUBLIC,
+                |vm, args| compare_to(vm, args, $descriptor),
+            )?,
+            native(
+                "compareTo",
+                "(Ljava/lang/Object;)I",
+                MethodFlags::PUBLIC,
+                |vm, args| compare_to(vm, args, $descriptor),
+            )?,
+        ]
+    };
+}
+
+/// `xValue` for each of the primitives a `Number` can be converted to
+macro_rules! number {
+    ($descriptor:expr) => {
+        vec![
+            native("byteValue", "()B", MethodFlags::PUBLIC, |vm, args| {
+                let value = vm.get_field(this(&args)?, "value", $descriptor)?;
+                convert(value, "B").map(Some)
+            })?,
+            native("shortValue", "()S", MethodFlags::PUBLIC, |vm, args| {
+                let value = vm.get_field(this(&args)?, "value", $descriptor)?;
+                convert(value, "S").map(Some)
+            })?,
+            native("intValue", "()I", MethodFlags::PUBLIC, |vm, args| {
+                let value = vm.get_field(this(&args)?, "value", $descriptor)?;
+                convert(value, "I").map(Some)
+            })?,
+            native("longValue", "()J", MethodFlags::PUBLIC, |vm, args| {
+                let value = vm.get_field(this(&args)?, "value", $descriptor)?;
+                convert(value, "J").map(Some)
+            })?,
+            native("floatValue", "()F", MethodFlags::PUBLIC, |vm, args| {
+                let value = vm.get_field(this(&args)?, "value", $descriptor)?;
+                convert(value, "F").map(Some)
+            })?,
+            native("doubleValue", "()D", MethodFlags::PUBLIC, |vm, args| {
+                let value = vm.get_field(this(&args)?, "value", $descriptor)?;
+                convert(value, "D").map(Some)
+            })?,
+        ]
+    };
+}
+
+fn compare_to(vm: &mut Interpreter, args: Vec<Value>, descriptor: &str) -> Result<Option<Value>> {
+    let other = match args[1].as_reference()? {
+        Some(other) => other,
+        None => {
+            vm.throw_new("java/lang/NullPointerException", None)?;
+            return Ok(None);
+        }
+    };
+    let lhs = vm.get_field(this(&args)?, "value", descriptor)?;
+    let rhs = match vm.get_field(other, "value", descriptor) {
+        Ok(rhs) => rhs,
+        Err(..) => {
+            let message = format!(
+                "{} cannot be cast to {}",
+                vm.heap().get(other).class_name().replace('/', "."),
+                vm.heap().get(this(&args)?).class_name().replace('/', ".")
+            );
+            vm.throw_new("java/lang/ClassCastException", Some(&message))?;
+            return Ok(None);
+        }
+    };
+    Ok(Some(Value::Int(compare(lhs, rhs)?)))
+}
+
+/// A boxing class with a `value` field of type `descriptor`, the cache used by `valueOf` and
+/// the `TYPE` of its primitive
+fn box_class(
+    name: &str,
+    super_class: &str,
+    descriptor: &str,
+    methods: Vec<Vec<Method>>,
+) -> Result<ClassDef> {
+    let flags = FieldFlags::PRIVATE | FieldFlags::FINAL;
+    let cache = FieldFlags::PRIVATE | FieldFlags::STATIC;
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::FINAL | ClassFlags::SUPER,
+        interfaces: vec!["java/lang/Comparable".to_string()],
+        fields: vec![
+            Field::new("value", descriptor, flags)?,
+            Field::new("cache", format!("[L{};", name), cache)?,
+            Field::new("TYPE", "Ljava/lang/Class;", type_flags())?,
+        ],
+        ..class(
+            name,
+            Some(super_class),
+            methods.into_iter().flatten().collect(),
+        )
+    })
+}
+
+fn type_flags() -> FieldFlags {
+    FieldFlags::PUBLIC | FieldFlags::STATIC | FieldFlags::FINAL
+}
+
+/// `java/lang/Void`, which only has the `TYPE` of `void`
+pub(super) fn void() -> Result<ClassDef> {
+    const CLASS: &str = "java/lang/Void";
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::FINAL | ClassFlags::SUPER,
+        fields: vec![Field::new("TYPE", "Ljava/lang/Class;", type_flags())?],
+        ..class(
+            CLASS,
+            Some("java/lang/Object"),
+            vec![native("<clinit>", "()V", MethodFlags::STATIC, |vm, _| {
+                init_type(vm, CLASS, None)?;
+                Ok(None)
+            })?],
+        )
+    })
+}
+
+pub(super) fn number() -> Result<ClassDef> {
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::ABSTRACT | ClassFlags::SUPER,
+        ..class(
+            "java/lang/Number",
+            Some("java/lang/Object"),
+            vec![
+                native("<init>", "()V", MethodFlags::PUBLIC, |_, _| Ok(None))?,
+                abstract_method("intValue", "()I")?,
+                abstract_method("longValue", "()J")?,
+                abstract_method("floatValue", "()F")?,
+                abstract_method("doubleValue", "()D")?,
+                native(
+                    "byteValue",
+                    "()B",
+                    MethodFlags::PUBLIC,
+                    |vm, args| match vm.call_method(this(&args)?, "intValue", "()I", vec![])? {
+                        Some(value) => convert(value, "B").map(Some),
+                        None => Ok(None),
+                    },
+                )?,
+                native(
+                    "shortValue",
+                    "()S",
+                    MethodFlags::PUBLIC,
+                    |vm, args| match vm.call_method(this(&args)?, "intValue", "()I", vec![])? {
+                        Some(value) => convert(value, "S").map(Some),
+                        None => Ok(None),
+                    },
+                )?,
+            ],
+        )
+    })
+}
+
+pub(super) fn integer() -> Result<ClassDef> {
+    const CLASS: &str = "java/lang/Integer";
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+
+    fn to_string_radix(vm: &mut Interpreter, d: i32, radix: u32) -> Result<Option<Value>> {
+        // these format the bits as an unsigned number
+        let s = match radix {
+            2 => format!("{:b}", d),
+            8 => format!("{:o}", d),
+            _ => format!("{:x}", d),
+        };
+        string_value(vm, &s)
+    }
+
+    box_class(
+        CLASS,
+        "java/lang/Number",
+        "I",
+        vec![
+            boxed!("java/lang/Integer", "I"),
+            number!("I"),
+            vec![
+                native("parseInt", "(Ljava/lang/String;)I", static_, |vm, args| {
+                    parse_integer(vm, args[0], 10, "I")
+                })?,
+                native("parseInt", "(Ljava/lang/String;I)I", static_, |vm, args| {
+                    parse_integer(vm, args[0], args[1].as_int()?, "I")
+                })?,
+                native(
+                    "valueOf",
+                    "(Ljava/lang/String;)Ljava/lang/Integer;",
+                    static_,
+                    |vm, args| match parse_integer(vm, args[0], 10, "I")? {
+                        Some(value) => value_of(vm, CLASS, "I", value).map(|b| Some(b.into())),
+                        None => Ok(None),
+                    },
+                )?,
+                native(
+                    "toHexString",
+                    "(I)Ljava/lang/String;",
+                    static_,
+                    |vm, args| to_string_radix(vm, args[0].as_int()?, 16),
+                )?,
+                native(
+                    "toOctalString",
+                    "(I)Ljava/lang/String;",
+                    static_,
+                    |vm, args| to_string_radix(vm, args[0].as_int()?, 8),
+                )?,
+                native(
+                    "toBinaryString",
+                    "(I)Ljava/lang/String;",
+                    static_,
+                    |vm, args| to_string_radix(vm, args[0].as_int()?, 2),
+                )?,
+                native("bitCount", "(I)I", static_, |_, args| {
+                    Ok(Some(Value::Int(args[0].as_int()?.count_ones() as i32)))
+                })?,
+                native("signum", "(I)I", static_, |_, args| {
+                    Ok(Some(Value::Int(args[0].as_int()?.signum())))
+                })?,
+            ],
+        ],
+    )
+}
+
+pub(super) fn long() -> Result<ClassDef> {
+    const CLASS: &str = "java/lang/Long";
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+    box_class(
+        CLASS,
+        "java/lang/Number",
+        "J",
+        vec![
+            boxed!("java/lang/Long", "J"),
+            number!("J"),
+            vec![
+                native("parseLong", "(Ljava/lang/String;)J", static_, |vm, args| {
+                    parse_integer(vm, args[0], 10, "J")
+                })?,
+                native(
+                    "parseLong",
+                    "(Ljava/lang/String;I)J",
+                    static_,
+                    |vm, args| parse_integer(vm, args[0], args[1].as_int()?, "J"),
+                )?,
+                native(
+                    "valueOf",
+                    "(Ljava/lang/String;)Ljava/lang/Long;",
+                    static_,
+                    |vm, args| match parse_integer(vm, args[0], 10, "J")? {
+                        Some(value) => value_of(vm, CLASS, "J", value).map(|b| Some(b.into())),
+                        None => Ok(None),
+                    },
+                )?,
+                native(
+                    "toHexString",
+                    "(J)Ljava/lang/String;",
+                    static_,
+                    |vm, args| string_value(vm, &format!("{:x}", args[0].as_long()?)),
+                )?,
+            ],
+        ],
+    )
+}
+
+pub(super) fn short() -> Result<ClassDef> {
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+    box_class(
+        "java/lang/Short",
+        "java/lang/Number",
+        "S",
+        vec![
+            boxed!("java/lang/Short", "S"),
+            number!("S"),
+            vec![native(
+                "parseShort",
+                "(Ljava/lang/String;)S",
+                static_,
+                |vm, args| parse_integer(vm, args[0], 10, "S"),
+            )?],
+        ],
+    )
+}
+
+pub(super) fn byte() -> Result<ClassDef> {
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+    box_class(
+        "java/lang/Byte",
+        "java/lang/Number",
+        "B",
+        vec![
+            boxed!("java/lang/Byte", "B"),
+            number!("B"),
+            vec![native(
+                "parseByte",
+                "(Ljava/lang/String;)B",
+                static_,
+                |vm, args| parse_integer(vm, args[0], 10, "B"),
+            )?],
+        ],
+    )
+}
+
+pub(super) fn float() -> Result<ClassDef> {
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+    box_class(
+        "java/lang/Float",
+        "java/lang/Number",
+        "F",
+        vec![
+            boxed!("java/lang/Float", "F"),
+            number!("F"),
+            vec![
+                native(
+                    "parseFloat",
+                    "(Ljava/lang/String;)F",
+                    static_,
+                    |vm, args| parse_decimal(vm, args[0], "F"),
+                )?,
+                native("isNaN", "(F)Z", static_, |_, args| {
+                    Ok(Some(args[0].as_float()?.is_nan().into()))
+                })?,
+                native("isInfinite", "(F)Z", static_, |_, args| {
+                    Ok(Some(args[0].as_float()?.is_infinite().into()))
+                })?,
+                native("floatToIntBits", "(F)I", static_, |_, args| {
+                    Ok(Some(Value::Int(bits(args[0]) as i32)))
+                })?,
+                native("floatToRawIntBits", "(F)I", static_, |_, args| {
+                    Ok(Some(Value::Int(args[0].as_float()?.to_bits() as i32)))
+                })?,
+                native("intBitsToFloat", "(I)F", static_, |_, args| {
+                    let bits = args[0].as_int()? as u32;
+                    Ok(Some(Value::Float(f32::from_bits(bits))))
+                })?,
+            ],
+        ],
+    )
+}
+
+pub(super) fn double() -> Result<ClassDef> {
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+    box_class(
+        "java/lang/Double",
+        "java/lang/Number",
+        "D",
+        vec![
+            boxed!("java/lang/Double", "D"),
+            number!("D"),
+            vec![
+                native(
+                    "parseDouble",
+                    "(Ljava/lang/String;)D",
+                    static_,
+                    |vm, args| parse_decimal(vm, args[0], "D"),
+                )?,
+                native("isNaN", "(D)Z", static_, |_, args| {
+                    Ok(Some(args[0].as_double()?.is_nan().into()))
+                })?,
+                native("isInfinite", "(D)Z", static_, |_, args| {
+                    Ok(Some(args[0].as_double()?.is_infinite().into()))
+                })?,
+                native("doubleToLongBits", "(D)J", static_, |_, args| {
+                    Ok(Some(Value::Long(bits(args[0]))))
+                })?,
+                native("doubleToRawLongBits", "(D)J", static_, |_, args| {
+                    Ok(Some(Value::Long(args[0].as_double()?.to_bits() as i64)))
+                })?,
+                native("longBitsToDouble", "(J)D", static_, |_, args| {
+                    let bits = args[0].as_long()? as u64;
+                    Ok(Some(Value::Double(f64::from_bits(bits))))
+                })?,
+            ],
+        ],
+    )
+}
+
+pub(super) fn boolean() -> Result<ClassDef> {
+    const CLASS: &str = "java/lang/Boolean";
+    const BOOLEAN: &str = "Ljava/lang/Boolean;";
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+
+    /// `Boolean.TRUE` or `Boolean.FALSE`
+    fn constant(vm: &mut Interpreter, value: bool) -> Result<Option<Value>> {
+        let class = vm.resolve_class(CLASS)?;
+        let field = if value { "TRUE" } else { "FALSE" };
+        match class.find_field(field, BOOLEAN) {
+            Some(field) => Ok(Some(class.statics.borrow()[field.slot])),
+            None => Err(Error::NoSuchField(format!("{}.{}", CLASS, field))),
+        }
+    }
+
+    fn parse(vm: &Interpreter, arg: Value) -> Result<bool> {
+        match arg.as_reference()? {
+            Some(s) => Ok(vm.read_string(s)?.eq_ignore_ascii_case("true")),
+            None => Ok(false),
+        }
+    }
+
+    let mut def = box_class(
+        CLASS,
+        "java/lang/Object",
+        "Z",
+        vec![
+            boxed!("java/lang/Boolean", "Z"),
+            vec![
+                native("booleanValue", "()Z", MethodFlags::PUBLIC, |vm, args| {
+                    vm.get_field(this(&args)?, "value", "Z").map(Some)
+                })?,
+                native(
+                    "parseBoolean",
+                    "(Ljava/lang/String;)Z",
+                    static_,
+                    |vm, args| Ok(Some(parse(vm, args[0])?.into())),
+                )?,
+                native(
+                    "valueOf",
+                    "(Ljava/lang/String;)Ljava/lang/Boolean;",
+                    static_,
+                    |vm, args| {
+                        let value = parse(vm, args[0])?;
+                        constant(vm, value)
+                    },
+                )?,
+            ],
+        ],
+    )?;
+
+    // `Boolean.valueOf(boolean)` returns one of the constants, which are made along with
+    // the `TYPE`
+    for method in &mut def.methods {
+        if method.name == "valueOf" && method.descriptor == "(Z)Ljava/lang/Boolean;" {
+            method.body = MethodBody::Native(|vm, args| {
+                let value = args[0].as_int()? != 0;
+                constant(vm, value)
+            });
+        }
+        if method.name == "<clinit>" {
+            method.body = MethodBody::Native(|vm, _| {
+                init_type(vm, CLASS, Some(&FieldType::Boolean))?;
+                let class = vm.resolve_class(CLASS)?;
+                let yes = new_box(vm, CLASS, "Z", true.into())?;
+                set_static(&class, "TRUE", BOOLEAN, yes.into())?;
+                let no = new_box(vm, CLASS, "Z", false.into())?;
+                set_static(&class, "FALSE", BOOLEAN, no.into())?;
+                Ok(None)
+            });
+        }
+    }
+
+    let constant = FieldFlags::PUBLIC | FieldFlags::STATIC | FieldFlags::FINAL;
+    def.fields.push(Field::new("TRUE", BOOLEAN, constant)?);
+    def.fields.push(Field::new("FALSE", BOOLEAN, constant)?);
+    Ok(def)
+}
+
+pub(super) fn character() -> Result<ClassDef> {
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+
+    /// A `Character` method testing a `char`
+    fn test(args: &[Value], test: fn(char) -> bool) -> Result<Option<Value>> {
+        let c = std::char::from_u32(args[0].as_int()? as u32).is_some_and(test);
+        Ok(Some(c.into()))
+    }
+
+    /// A `Character` method mapping a `char`, which is left alone if it doesn't map to a
+    /// single `char`
+    fn map(args: &[Value], map: fn(char) -> String) -> Result<Option<Value>> {
+        let c = args[0].as_int()?;
+        let mapped = std::char::from_u32(c as u32)
+            .map(map)
+            .and_then(|s| {
+                let mut utf16 = s.encode_utf16();
+                match (utf16.next(), utf16.next()) {
+                    (Some(c), None) => Some(i32::from(c)),
+                    _ => None,
+                }
+            })
+            .unwrap_or(c);
+        Ok(Some(Value::Int(mapped)))
+    }
+
+    box_class(
+        "java/lang/Character",
+        "java/lang/Object",
+        "C",
+        vec![
+            boxed!("java/lang/Character", "C"),
+            vec![
+                native("charValue", "()C", MethodFlags::PUBLIC, |vm, args| {
+                    vm.get_field(this(&args)?, "value", "C").map(Some)
+                })?,
+                native("isDigit", "(C)Z", static_, |_, args| {
+                    test(&args, char::is_numeric)
+                })?,
+                native("isLetter", "(C)Z", static_, |_, args| {
+                    test(&args, char::is_alphabetic)
+                })?,
+                native("isLetterOrDigit", "(C)Z", static_, |_, args| {
+                    test(&args, char::is_alphanumeric)
+                })?,
+                native("isWhitespace", "(C)Z", static_, |_, args| {
+                    test(&args, char::is_whitespace)
+                })?,
+                native("isUpperCase", "(C)Z", static_, |_, args| {
+                    test(&args, char::is_uppercase)
+                })?,
+                native("isLowerCase", "(C)Z", static_, |_, args| {
+                    test(&args, char::is_lowercase)
+                })?,
+                native("toUpperCase", "(C)C", static_, |_, args| {
+                    map(&args, |c| c.to_uppercase().collect())
+                })?,
+                native("toLowerCase", "(C)C", static_, |_, args| {
+                    map(&args, |c| c.to_lowercase().collect())
+                })?,
+                native("digit", "(CI)I", static_, |_, args| {
+                    let radix = args[1].as_int()? as u32;
+                    let digit = std::char::from_u32(args[0].as_int()? as u32)
+                        .filter(|_| (2..=36).contains(&radix))
+                        .and_then(|c| c.to_digit(radix))
+                        .map_or(-1, |d| d as i32);
+                    Ok(Some(Value::Int(digit)))
+                })?,
+            ],
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn box_semantics() {
+        assert_eq!(
+            convert(Value::Double(-1e20), "I").unwrap(),
+            Value::Int(i32::MIN)
+        );
+        assert_eq!(convert(Value::Int(300), "B").unwrap(), Value::Int(44));
+        assert_eq!(convert(Value::Int(-1), "C").unwrap(), Value::Int(0xffff));
+        assert_eq!(hash(Value::Long(1 << 32)), 1);
+        assert_eq!(hash(Value::Double(1.0)), 1_072_693_248);
+        assert_eq!(
+            compare(Value::Double(-0.0), Value::Double(0.0)).unwrap(),
+            -1
+        );
+        assert_eq!(
+            compare(Value::Double(f64::NAN), Value::Double(1.0)).unwrap(),
+            1
+        );
+        assert_eq!(
+            compare(Value::Float(f32::NAN), Value::Float(f32::NAN)).unwrap(),
+            0
+        );
+        assert_eq!(compare(Value::Int(-5), Value::Int(3)).unwrap(), -1);
+    }
+}
@@ -0,0 +1,393 @@
+//! `java.lang.invoke`: method types, method handles and the lookups that find them
+use super::*;
+use crate::exec::handle::*;
+
+const CLASS: &str = "Ljava/lang/Class;";
+const METHOD_TYPE: &str = "Ljava/lang/invoke/MethodType;";
+const LOOKUP: &str = "java/lang/invoke/MethodHandles$Lookup";
+
+/// The type a `java/lang/Class` argument stands for, `None` being `void`
+fn mirror_type(vm: &Interpreter, mirror: Value) -> Result<Option<FieldType>> {
+    let name = match mirror
+        .as_reference()?
+        .and_then(|mirror| vm.mirror_name(mirror))
+    {
+        Some(name) => name,
+        None => generic_error!("not a class mirror"),
+    };
+    let ty = match name {
+        "void" => return Ok(None),
+        "boolean" => FieldType::Boolean,
+        "byte" => FieldType::Byte,
+        "char" => FieldType::Char,
+        "short" => FieldType::Short,
+        "int" => FieldType::Int,
+        "long" => FieldType::Long,
+        "float" => FieldType::Float,
+        "double" => FieldType::Double,
+        name if name.starts_with('[') => FieldType::parse(name)?,
+        name => FieldType::Object(name.to_string()),
+    };
+    Ok(Some(ty))
+}
+
+/// The descriptor of a `java/lang/invoke/MethodType` argument
+fn type_descriptor(vm: &Interpreter, method_type: Value) -> Result<String> {
+    match method_type
+        .as_reference()?
+        .and_then(|method_type| vm.method_type_descriptor(method_type))
+    {
+        Some(descriptor) => Ok(descriptor.to_string()),
+        None => generic_error!("not a method type"),
+    }
+}
+
+/// `MethodType.methodType` with the return type `rtype`, and the parameter types in `ptypes`
+/// followed by the ones in the `java/lang/Class[]` array `rest`
+fn new_method_type(
+    vm: &mut Interpreter,
+    rtype: Value,
+    ptypes: &[Value],
+    rest: Option<Value>,
+) -> Result<Option<Value>> {
+    let mut params = ptypes.to_vec();
+    if let Some(rest) = rest {
+        match rest.as_reference()? {
+            Some(rest) => params.extend_from_slice(&vm.heap().array(rest)?.elements),
+            None => {
+                vm.throw_new("java/lang/NullPointerException", None)?;
+                return Ok(None);
+            }
+        }
+    }
+
+    let mut descriptor = "(".to_string();
+    for param in params {
+        match mirror_type(vm, param)? {
+            Some(param) => descriptor.push_str(&param.to_string()),
+            None => {
+                let message = "parameter type cannot be void";
+                vm.throw_new("java/lang/IllegalArgumentException", Some(message))?;
+                return Ok(None);
+            }
+        }
+    }
+    descriptor.push(')');
+    match mirror_type(vm, rtype)? {
+        Some(rtype) => descriptor.push_str(&rtype.to_string()),
+        None => descriptor.push('V'),
+    }
+    vm.method_type(&descriptor)
+        .map(|method_type| Some(method_type.into()))
+}
+
+pub(super) fn method_type() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+
+    fn descriptor(vm: &Interpreter, args: &[Value]) -> Result<ty::MethodDescriptor> {
+        match vm.method_type_descriptor(this(args)?) {
+            Some(descriptor) => ty::MethodDescriptor::parse(descriptor).map_err(Into::into),
+            None => generic_error!("not a method type"),
+        }
+    }
+
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::FINAL | ClassFlags::SUPER,
+        fields: vec![
+            Field::new("rtype", CLASS, FieldFlags::PRIVATE)?,
+            Field::new("ptypes", "[Ljava/lang/Class;", FieldFlags::PRIVATE)?,
+        ],
+        ..class(
+            "java/lang/invoke/MethodType",
+            Some("java/lang/Object"),
+            vec![
+                native(
+                    "methodType",
+                    "(Ljava/lang/Class;)Ljava/lang/invoke/MethodType;",
+                    static_,
+                    |vm, args| new_method_type(vm, args[0], &[], None),
+                )?,
+                native(
+                    "methodType",
+                    "(Ljava/lang/Class;Ljava/lang/Class;)Ljava/lang/invoke/MethodType;",
+                    static_,
+                    |vm, args| new_method_type(vm, args[0], &args[1..], None),
+                )?,
+                native(
+                    "methodType",
+                    "(Ljava/lang/Class;[Ljava/lang/Class;)Ljava/lang/invoke/MethodType;",
+                    static_,
+                    |vm, args| new_method_type(vm, args[0], &[], Some(args[1])),
+                )?,
+                native(
+                    "methodType",
+                    "(Ljava/lang/Class;Ljava/lang/Class;[Ljava/lang/Class;)Ljava/lang/invoke/MethodType;",
+                    static_,
+                    |vm, args| new_method_type(vm, args[0], &args[1..2], Some(args[2])),
+                )?,
+                native(
+                    "toMethodDescriptorString",
+                    "()Ljava/lang/String;",
+                    public,
+                    |vm, args| {
+                        let descriptor = descriptor(vm, &args)?.to_string();
+                        vm.new_string(&descriptor).map(|s| Some(s.into()))
+                    },
+                )?,
+                native("parameterCount", "()I", public, |vm, args| {
+                    Ok(Some(Value::Int(descriptor(vm, &args)?.params.len() as i32)))
+                })?,
+                native("returnType", "()Ljava/lang/Class;", public, |vm, args| {
+                    vm.get_field(this(&args)?, "rtype", CLASS).map(Some)
+                })?,
+                native(
+                    "parameterType",
+                    "(I)Ljava/lang/Class;",
+                    public,
+                    |vm, args| {
+                        let ptypes =
+                            match vm.get_field(this(&args)?, "ptypes", "[Ljava/lang/Class;")? {
+                                Value::Reference(ptypes) => ptypes,
+                                _ => generic_error!("method type without parameters"),
+                            };
+                        let index = args[1].as_int()?;
+                        match vm.heap().array(ptypes)?.elements.get(index as usize) {
+                            Some(ptype) if index >= 0 => Ok(Some(*ptype)),
+                            _ => {
+                                let message = index.to_string();
+                                vm.throw_new(
+                                    "java/lang/IndexOutOfBoundsException",
+                                    Some(&message),
+                                )?;
+                                Ok(None)
+                            }
+                        }
+                    },
+                )?,
+                native("toString", "()Ljava/lang/String;", public, |vm, args| {
+                    let s = method_type_string(&descriptor(vm, &args)?);
+                    vm.new_string(&s).map(|s| Some(s.into()))
+                })?,
+            ],
+        )
+    })
+}
+
+pub(super) fn method_handle() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::ABSTRACT | ClassFlags::SUPER,
+        fields: vec![Field::new(
+            "type",
+            METHOD_TYPE,
+            FieldFlags::PRIVATE | FieldFlags::FINAL,
+        )?],
+        ..class(
+            "java/lang/invoke/MethodHandle",
+            Some("java/lang/Object"),
+            vec![
+                native(
+                    "type",
+                    "()Ljava/lang/invoke/MethodType;",
+                    public,
+                    |vm, args| vm.get_field(this(&args)?, "type", METHOD_TYPE).map(Some),
+                )?,
+                native(
+                    "invokeWithArguments",
+                    "([Ljava/lang/Object;)Ljava/lang/Object;",
+                    public | MethodFlags::VARARGS,
+                    |vm, args| {
+                        let handle = this(&args)?;
+                        let array = match args[1].as_reference()? {
+                            Some(array) => array,
+                            None => {
+                                vm.throw_new("java/lang/NullPointerException", None)?;
+                                return Ok(None);
+                            }
+                        };
+                        let args = vm.heap().array(array)?.elements.clone();
+                        let descriptor = format!(
+                            "({})Ljava/lang/Object;",
+                            "Ljava/lang/Object;".repeat(args.len())
+                        );
+                        vm.invoke_method_handle(handle, &descriptor, args)
+                    },
+                )?,
+                native("toString", "()Ljava/lang/String;", public, |vm, args| {
+                    let method_type = vm.get_field(this(&args)?, "type", METHOD_TYPE)?;
+                    let descriptor = type_descriptor(vm, method_type)?;
+                    let descriptor = ty::MethodDescriptor::parse(&descriptor)?;
+                    let s = format!("MethodHandle{}", method_type_string(&descriptor));
+                    vm.new_string(&s).map(|s| Some(s.into()))
+                })?,
+            ],
+        )
+    })
+}
+
+pub(super) fn method_handles() -> Result<ClassDef> {
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+
+    fn lookup(vm: &mut Interpreter) -> Result<Option<Value>> {
+        let class = vm.resolve_class(LOOKUP)?;
+        Ok(Some(vm.heap_mut().new_instance(class).into()))
+    }
+
+    Ok(class(
+        "java/lang/invoke/MethodHandles",
+        Some("java/lang/Object"),
+        vec![
+            native(
+                "lookup",
+                "()Ljava/lang/invoke/MethodHandles$Lookup;",
+                static_,
+                |vm, _| lookup(vm),
+            )?,
+            native(
+                "publicLookup",
+                "()Ljava/lang/invoke/MethodHandles$Lookup;",
+                static_,
+                |vm, _| lookup(vm),
+            )?,
+        ],
+    ))
+}
+
+/// `MethodHandles.Lookup`, which finds members without checking access
+pub(super) fn lookup() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+
+    /// Finds the member `name` of the class in `class`, throwing the exception that
+    /// `Lookup` does if it isn't there or isn't what `kind` uses
+    fn find(
+        vm: &mut Interpreter,
+        kind: u8,
+        class: Value,
+        name: Value,
+        descriptor: &str,
+    ) -> Result<Option<Value>> {
+        let name = match name.as_reference()? {
+            Some(name) => vm.read_string(name)?,
+            None => {
+                vm.throw_new("java/lang/NullPointerException", None)?;
+                return Ok(None);
+            }
+        };
+        let class = match mirror_type(vm, class)? {
+            Some(FieldType::Object(class)) => class,
+            ty => {
+                let ty = ty.map_or("void".to_string(), |ty| ty.to_string());
+                let message = format!("no member {} in {}", name, ty);
+                vm.throw_new("java/lang/NoSuchMethodException", Some(&message))?;
+                return Ok(None);
+            }
+        };
+
+        let interface = vm.resolve_class(&class)?.is_interface();
+        let kind = match kind {
+            REF_INVOKE_VIRTUAL if interface => REF_INVOKE_INTERFACE,
+            kind => kind,
+        };
+        let (exception, message) =
+            match vm.find_method_handle(kind, &class, &name, descriptor, interface) {
+                Ok(handle) => return Ok(Some(handle.into())),
+                Err(Error::NoSuchMethod(message)) => ("java/lang/NoSuchMethodException", message),
+                Err(Error::NoSuchField(message)) => ("java/lang/NoSuchFieldException", message),
+                Err(Error::IncompatibleClassChange(message)) => {
+                    ("java/lang/IllegalAccessException", message)
+                }
+                Err(err) => return Err(err),
+            };
+        vm.throw_new(exception, Some(&message))?;
+        Ok(None)
+    }
+
+    /// The descriptor of a field of the type in `ty`
+    fn field_descriptor(vm: &Interpreter, ty: Value) -> Result<String> {
+        match mirror_type(vm, ty)? {
+            Some(ty) => Ok(ty.to_string()),
+            None => generic_error!("a field can't be void"),
+        }
+    }
+
+    Ok(class(
+        LOOKUP,
+        Some("java/lang/Object"),
+        vec![
+            native(
+                "findStatic",
+                "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+                public,
+                |vm, args| {
+                    let descriptor = type_descriptor(vm, args[3])?;
+                    find(vm, REF_INVOKE_STATIC, args[1], args[2], &descriptor)
+                },
+            )?,
+            native(
+                "findVirtual",
+                "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+                public,
+                |vm, args| {
+                    let descriptor = type_descriptor(vm, args[3])?;
+                    find(vm, REF_INVOKE_VIRTUAL, args[1], args[2], &descriptor)
+                },
+            )?,
+            native(
+                "findSpecial",
+                "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+                public,
+                |vm, args| {
+                    let descriptor = type_descriptor(vm, args[3])?;
+                    find(vm, REF_INVOKE_SPECIAL, args[1], args[2], &descriptor)
+                },
+            )?,
+            native(
+                "findConstructor",
+                "(Ljava/lang/Class;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+                public,
+                |vm, args| {
+                    let descriptor = type_descriptor(vm, args[2])?;
+                    let name = vm.intern("<init>")?.into();
+                    find(vm, REF_NEW_INVOKE_SPECIAL, args[1], name, &descriptor)
+                },
+            )?,
+            native(
+                "findGetter",
+                "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+                public,
+                |vm, args| {
+                    let descriptor = field_descriptor(vm, args[3])?;
+                    find(vm, REF_GET_FIELD, args[1], args[2], &descriptor)
+                },
+            )?,
+            native(
+                "findSetter",
+                "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+                public,
+                |vm, args| {
+                    let descriptor = field_descriptor(vm, args[3])?;
+                    find(vm, REF_PUT_FIELD, args[1], args[2], &descriptor)
+                },
+            )?,
+            native(
+                "findStaticGetter",
+                "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+                public,
+                |vm, args| {
+                    let descriptor = field_descriptor(vm, args[3])?;
+                    find(vm, REF_GET_STATIC, args[1], args[2], &descriptor)
+                },
+            )?,
+            native(
+                "findStaticSetter",
+                "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;",
+                public,
+                |vm, args| {
+                    let descriptor = field_descriptor(vm, args[3])?;
+                    find(vm, REF_PUT_STATIC, args[1], args[2], &descriptor)
+                },
+            )?,
+        ],
+    ))
+}
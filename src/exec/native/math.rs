@@ -0,0 +1,261 @@
+use super::*;
+
+use std::cell::Cell;
+
+/// A native of `Math` taking and returning values of one type
+macro_rules! math {
+    ($name:expr, $descriptor:expr, $as:ident, |$($arg:ident),*| $body:expr) => {
+        native(
+            $name,
+            $descriptor,
+            MethodFlags::PUBLIC | MethodFlags::STATIC,
+            |_, args| {
+                let mut args = args.into_iter();
+                $(let $arg = args.next().unwrap_or(Value::Top).$as()?;)*
+                Ok(Some(Value::from($body)))
+            },
+        )?
+    };
+}
+
+/// A native of `Math` that can throw an `ArithmeticException`, where `None` is an overflow
+/// or division by zero
+macro_rules! exact {
+    ($name:expr, $descriptor:expr, $as:ident, $message:expr, |$($arg:ident),*| $body:expr) => {
+        native(
+            $name,
+            $descriptor,
+            MethodFlags::PUBLIC | MethodFlags::STATIC,
+            |vm, args| {
+                let mut args = args.into_iter();
+                $(let $arg = args.next().unwrap_or(Value::Top).$as()?;)*
+                match $body {
+                    Some(val) => Ok(Some(Value::from(val))),
+                    None => {
+                        vm.throw_new("java/lang/ArithmeticException", Some($message))?;
+                        Ok(None)
+                    }
+                }
+            },
+        )?
+    };
+}
+
+/// `Math.max` for floating point, where NaN wins and 0.0 is greater than -0.0
+fn max<T: Into<f64> + Copy>(a: T, b: T) -> T {
+    let (x, y) = (a.into(), b.into());
+    match () {
+        _ if x.is_nan() => a,
+        _ if y.is_nan() => b,
+        _ if x == 0.0 && y == 0.0 && x.is_sign_negative() => b,
+        _ if x >= y => a,
+        _ => b,
+    }
+}
+
+/// `Math.min` for floating point, where NaN wins and -0.0 is less than 0.0
+fn min<T: Into<f64> + Copy>(a: T, b: T) -> T {
+    let (x, y) = (a.into(), b.into());
+    match () {
+        _ if x.is_nan() => a,
+        _ if y.is_nan() => b,
+        _ if x == 0.0 && y == 0.0 && y.is_sign_negative() => b,
+        _ if x <= y => a,
+        _ => b,
+    }
+}
+
+/// `Math.round`, which rounds halves up rather than away from zero
+fn round(d: f64) -> f64 {
+    let floor = d.floor();
+    if d - floor >= 0.5 {
+        floor + 1.0
+    } else {
+        floor
+    }
+}
+
+/// `Math.rint`, which rounds halves to even
+fn rint(d: f64) -> f64 {
+    let floor = d.floor();
+    let diff = d - floor;
+    if diff > 0.5 || (diff == 0.5 && floor % 2.0 != 0.0) {
+        floor + 1.0
+    } else {
+        floor
+    }
+}
+
+fn signum(d: f64) -> f64 {
+    if d == 0.0 || d.is_nan() {
+        d
+    } else {
+        d.signum()
+    }
+}
+
+thread_local! {
+    static SEED: Cell<u64> = Cell::new(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default()
+            | 1,
+    );
+}
+
+/// `Math.random`, a double in `0.0..1.0`
+fn random() -> f64 {
+    SEED.with(|seed| {
+        // xorshift64*
+        let mut x = seed.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        seed.set(x);
+        (x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+pub(super) fn math() -> Result<ClassDef> {
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::FINAL | ClassFlags::SUPER,
+        ..class(
+            "java/lang/Math",
+            Some("java/lang/Object"),
+            vec![
+                math!("abs", "(I)I", as_int, |a| a.wrapping_abs()),
+                math!("abs", "(J)J", as_long, |a| a.wrapping_abs()),
+                math!("abs", "(F)F", as_float, |a| a.abs()),
+                math!("abs", "(D)D", as_double, |a| a.abs()),
+                math!("max", "(II)I", as_int, |a, b| a.max(b)),
+                math!("max", "(JJ)J", as_long, |a, b| a.max(b)),
+                math!("max", "(FF)F", as_float, |a, b| max(a, b)),
+                math!("max", "(DD)D", as_double, |a, b| max(a, b)),
+                math!("min", "(II)I", as_int, |a, b| a.min(b)),
+                math!("min", "(JJ)J", as_long, |a, b| a.min(b)),
+                math!("min", "(FF)F", as_float, |a, b| min(a, b)),
+                math!("min", "(DD)D", as_double, |a, b| min(a, b)),
+                math!("sqrt", "(D)D", as_double, |a| a.sqrt()),
+                math!("cbrt", "(D)D", as_double, |a| a.cbrt()),
+                math!("pow", "(DD)D", as_double, |a, b| a.powf(b)),
+                math!("exp", "(D)D", as_double, |a| a.exp()),
+                math!("log", "(D)D", as_double, |a| a.ln()),
+                math!("log10", "(D)D", as_double, |a| a.log10()),
+                math!("sin", "(D)D", as_double, |a| a.sin()),
+                math!("cos", "(D)D", as_double, |a| a.cos()),
+                math!("tan", "(D)D", as_double, |a| a.tan()),
+                math!("asin", "(D)D", as_double, |a| a.asin()),
+                math!("acos", "(D)D", as_double, |a| a.acos()),
+                math!("atan", "(D)D", as_double, |a| a.atan()),
+                math!("atan2", "(DD)D", as_double, |y, x| y.atan2(x)),
+                math!("hypot", "(DD)D", as_double, |a, b| a.hypot(b)),
+                math!("toRadians", "(D)D", as_double, |a| a.to_radians()),
+                math!("toDegrees", "(D)D", as_double, |a| a.to_degrees()),
+                math!("floor", "(D)D", as_double, |a| a.floor()),
+                math!("ceil", "(D)D", as_double, |a| a.ceil()),
+                math!("rint", "(D)D", as_double, |a| rint(a)),
+                math!("signum", "(D)D", as_double, |a| signum(a)),
+                // `as` saturates and turns NaN into 0, like java does
+                math!("round", "(D)J", as_double, |a| round(a) as i64),
+                math!("round", "(F)I", as_float, |a| round(f64::from(a)) as i32),
+                native(
+                    "random",
+                    "()D",
+                    MethodFlags::PUBLIC | MethodFlags::STATIC,
+                    |_, _| Ok(Some(Value::Double(random()))),
+                )?,
+                exact!("addExact", "(II)I", as_int, "integer overflow", |a, b| {
+                    a.checked_add(b)
+                }),
+                exact!("addExact", "(JJ)J", as_long, "long overflow", |a, b| {
+                    a.checked_add(b)
+                }),
+                exact!(
+                    "subtractExact",
+                    "(II)I",
+                    as_int,
+                    "integer overflow",
+                    |a, b| { a.checked_sub(b) }
+                ),
+                exact!(
+                    "subtractExact",
+                    "(JJ)J",
+                    as_long,
+                    "long overflow",
+                    |a, b| { a.checked_sub(b) }
+                ),
+                exact!(
+                    "multiplyExact",
+                    "(II)I",
+                    as_int,
+                    "integer overflow",
+                    |a, b| { a.checked_mul(b) }
+                ),
+                exact!(
+                    "multiplyExact",
+                    "(JJ)J",
+                    as_long,
+                    "long overflow",
+                    |a, b| { a.checked_mul(b) }
+                ),
+                exact!("floorDiv", "(II)I", as_int, "/ by zero", |a, b| {
+                    (b != 0).then(|| floor_div(a.into(), b.into()) as i32)
+                }),
+                exact!("floorDiv", "(JJ)J", as_long, "/ by zero", |a, b| {
+                    (b != 0).then(|| floor_div(a, b))
+                }),
+                exact!("floorMod", "(II)I", as_int, "/ by zero", |a, b| {
+                    (b != 0).then(|| floor_mod(a.into(), b.into()) as i32)
+                }),
+                exact!("floorMod", "(JJ)J", as_long, "/ by zero", |a, b| {
+                    (b != 0).then(|| floor_mod(a, b))
+                }),
+            ],
+        )
+    })
+}
+
+/// Division rounding towards negative infinity
+fn floor_div(a: i64, b: i64) -> i64 {
+    let q = a.wrapping_div(b);
+    if (a.wrapping_rem(b) != 0) && ((a < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+/// The remainder of `floor_div`, which has the sign of the divisor
+fn floor_mod(a: i64, b: i64) -> i64 {
+    let m = a.wrapping_rem(b);
+    if m != 0 && ((m < 0) != (b < 0)) {
+        m + b
+    } else {
+        m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn java_semantics() {
+        assert_eq!(round(-2.5), -2.0);
+        assert_eq!(round(2.5), 3.0);
+        assert_eq!(round(0.499_999_999_999_999_94), 0.0);
+        assert_eq!(rint(2.5), 2.0);
+        assert_eq!(rint(3.5), 4.0);
+        assert!(max(f64::NAN, 1.0).is_nan());
+        assert!(max(-0.0f64, 0.0).is_sign_positive());
+        assert!(min(0.0f64, -0.0).is_sign_negative());
+        assert_eq!(floor_div(-7, 2), -4);
+        assert_eq!(floor_div(7, -2), -4);
+        assert_eq!(floor_div(i64::MIN, -1), i64::MIN);
+        assert_eq!(floor_mod(-7, 2), 1);
+        assert_eq!(floor_mod(7, -2), -1);
+        let r = random();
+        assert!((0.0..1.0).contains(&r));
+    }
+}
@@ -0,0 +1,751 @@
+use super::*;
+
+const VALUE: &str = "[C";
+
+/// The UTF-16 code units of a `java/lang/String` or `java/lang/StringBuilder`
+pub(super) fn chars(vm: &Interpreter, string: Reference) -> Result<Vec<u16>> {
+    let value = match vm.get_field(string, "value", VALUE)? {
+        Value::Reference(value) => value,
+        _ => return Err(Error::StackType("char[]")),
+    };
+    vm.heap()
+        .array(value)?
+        .elements
+        .iter()
+        .map(|c| c.as_int().map(|c| c as u16))
+        .collect()
+}
+
+/// The contents of a `char[]`, throwing a `NullPointerException` if it is `null`
+pub(super) fn array_chars(vm: &mut Interpreter, array: Value) -> Result<Option<Vec<u16>>> {
+    let array = match array.as_reference()? {
+        Some(array) => array,
+        None => {
+            vm.throw_new("java/lang/NullPointerException", None)?;
+            return Ok(None);
+        }
+    };
+    vm.heap()
+        .array(array)?
+        .elements
+        .iter()
+        .map(|c| c.as_int().map(|c| c as u16))
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+}
+
+fn char_array(vm: &mut Interpreter, chars: &[u16]) -> Result<Reference> {
+    let array = vm.heap_mut().new_array(FieldType::Char, chars.len());
+    vm.heap_mut().array_mut(array)?.elements = chars.iter().map(|&c| Value::from(c)).collect();
+    Ok(array)
+}
+
+fn new_string(vm: &mut Interpreter, chars: &[u16]) -> Result<Option<Value>> {
+    let class = vm.resolve_class("java/lang/String")?;
+    let value = char_array(vm, chars)?;
+    let string = vm.heap_mut().new_instance(class);
+    vm.set_field(string, "value", VALUE, value.into())?;
+    Ok(Some(string.into()))
+}
+
+pub(super) fn string_value(vm: &mut Interpreter, s: &str) -> Result<Option<Value>> {
+    vm.new_string(s).map(|s| Some(s.into()))
+}
+
+/// `String.valueOf(Object)`, calling `toString` on anything that isn't a string.
+/// `None` if `toString` threw
+pub(crate) fn stringify(vm: &mut Interpreter, value: Value) -> Result<Option<String>> {
+    let object = match value.as_reference()? {
+        Some(object) => object,
+        None => return Ok(Some("null".to_string())),
+    };
+    if vm.heap().get(object).class_name() == "java/lang/String" {
+        return vm.read_string(object).map(Some);
+    }
+    match vm.call_method(object, "toString", "()Ljava/lang/String;", vec![])? {
+        Some(Value::Reference(s)) => vm.read_string(s).map(Some),
+        Some(..) => Ok(Some("null".to_string())),
+        None => Ok(None),
+    }
+}
+
+/// A primitive as `String.valueOf` formats it
+pub(crate) fn format_primitive(value: Value, ty: &FieldType) -> Result<String> {
+    let s = match ty {
+        FieldType::Boolean => (value.as_int()? != 0).to_string(),
+        FieldType::Char => String::from_utf16_lossy(&[value.as_int()? as u16]),
+        FieldType::Long => value.as_long()?.to_string(),
+        FieldType::Float => format_float(value.as_float()?),
+        FieldType::Double => format_double(value.as_double()?),
+        _ => value.as_int()?.to_string(),
+    };
+    Ok(s)
+}
+
+/// Formats a double like `Double.toString`
+// https://docs.oracle.com/javase/8/docs/api/java/lang/Double.html#toString-double-
+pub(super) fn format_double(d: f64) -> String {
+    format_decimal(
+        d.is_nan(),
+        d.is_infinite(),
+        d == 0.0,
+        d.is_sign_negative(),
+        || {
+            if d.abs() >= 1e-3 && d.abs() < 1e7 {
+                format!("{:?}", d)
+            } else {
+                format!("{:e}", d)
+            }
+        },
+    )
+}
+
+/// Formats a float like `Float.toString`
+pub(super) fn format_float(d: f32) -> String {
+    format_decimal(
+        d.is_nan(),
+        d.is_infinite(),
+        d == 0.0,
+        d.is_sign_negative(),
+        || {
+            if d.abs() >= 1e-3 && d.abs() < 1e7 {
+                format!("{:?}", d)
+            } else {
+                format!("{:e}", d)
+            }
+        },
+    )
+}
+
+fn format_decimal(
+    nan: bool,
+    infinite: bool,
+    zero: bool,
+    negative: bool,
+    format: impl Fn() -> String,
+) -> String {
+    match (nan, infinite, zero, negative) {
+        (true, ..) => "NaN".to_string(),
+        (_, true, _, true) => "-Infinity".to_string(),
+        (_, true, ..) => "Infinity".to_string(),
+        (_, _, true, true) => "-0.0".to_string(),
+        (_, _, true, _) => "0.0".to_string(),
+        _ => {
+            let s = format();
+            // rust writes `1e20` and `1.5e-7`, java writes `1.0E20` and `1.5E-7`
+            match s.find('e') {
+                Some(e) if !s[..e].contains('.') => format!("{}.0E{}", &s[..e], &s[e + 1..]),
+                Some(e) => format!("{}E{}", &s[..e], &s[e + 1..]),
+                None => s,
+            }
+        }
+    }
+}
+
+fn string_arg(vm: &Interpreter, args: &[Value], index: usize) -> Result<Option<Vec<u16>>> {
+    match args[index].as_reference()? {
+        Some(string) => chars(vm, string).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Reads a string argument, throwing a `NullPointerException` if it is `null`
+fn non_null_string(vm: &mut Interpreter, args: &[Value], index: usize) -> Result<Option<Vec<u16>>> {
+    let string = string_arg(vm, args, index)?;
+    if string.is_none() {
+        vm.throw_new("java/lang/NullPointerException", None)?;
+    }
+    Ok(string)
+}
+
+/// Checks `index` against `len`, throwing a `StringIndexOutOfBoundsException` if it is
+/// outside of it
+fn check_index(vm: &mut Interpreter, index: i32, len: usize) -> Result<bool> {
+    if index < 0 || index as usize >= len {
+        let message = format!("String index out of range: {}", index);
+        vm.throw_new("java/lang/StringIndexOutOfBoundsException", Some(&message))?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Checks the range `begin..end` against `len`
+fn check_range(vm: &mut Interpreter, begin: i32, end: i32, len: usize) -> Result<bool> {
+    if begin < 0 || end < begin || end as usize > len {
+        let message = format!("begin {}, end {}, length {}", begin, end, len);
+        vm.throw_new("java/lang/StringIndexOutOfBoundsException", Some(&message))?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+fn find(haystack: &[u16], needle: &[u16], from: usize) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(from.min(haystack.len()));
+    }
+    (from..haystack.len())
+        .take_while(|&i| i + needle.len() <= haystack.len())
+        .find(|&i| haystack[i..].starts_with(needle))
+}
+
+fn map_chars(
+    vm: &mut Interpreter,
+    args: &[Value],
+    map: fn(char) -> String,
+) -> Result<Option<Value>> {
+    let s = String::from_utf16_lossy(&chars(vm, this(args)?)?);
+    let mapped = s.chars().map(map).collect::<String>();
+    string_value(vm, &mapped)
+}
+
+/// `String.valueOf` for a primitive argument
+fn value_of(vm: &mut Interpreter, value: Value, ty: FieldType) -> Result<Option<Value>> {
+    let s = format_primitive(value, &ty)?;
+    string_value(vm, &s)
+}
+
+pub(super) fn char_sequence() -> Result<ClassDef> {
+    Ok(interface(
+        "java/lang/CharSequence",
+        vec![
+            abstract_method("length", "()I")?,
+            abstract_method("charAt", "(I)C")?,
+            abstract_method("toString", "()Ljava/lang/String;")?,
+        ],
+    ))
+}
+
+pub(super) fn comparable() -> Result<ClassDef> {
+    Ok(interface(
+        "java/lang/Comparable",
+        vec![abstract_method("compareTo", "(Ljava/lang/Object;)I")?],
+    ))
+}
+
+pub(super) fn string() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+
+    fn compare(lhs: &[u16], rhs: &[u16]) -> i32 {
+        lhs.iter()
+            .zip(rhs)
+            .find(|(l, r)| l != r)
+            .map(|(&l, &r)| i32::from(l) - i32::from(r))
+            .unwrap_or(lhs.len() as i32 - rhs.len() as i32)
+    }
+
+    fn compare_to(vm: &mut Interpreter, args: Vec<Value>) -> Result<Option<Value>> {
+        let this = chars(vm, this(&args)?)?;
+        match non_null_string(vm, &args, 1)? {
+            Some(other) => Ok(Some(Value::Int(compare(&this, &other)))),
+            None => Ok(None),
+        }
+    }
+
+    fn lower(c: char) -> String {
+        c.to_lowercase().collect()
+    }
+
+    fn upper(c: char) -> String {
+        c.to_uppercase().collect()
+    }
+
+    fn init(vm: &mut Interpreter, this: Reference, chars: &[u16]) -> Result<Option<Value>> {
+        let value = char_array(vm, chars)?;
+        vm.set_field(this, "value", VALUE, value.into())?;
+        Ok(None)
+    }
+
+    let flags = FieldFlags::PRIVATE | FieldFlags::FINAL;
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::FINAL | ClassFlags::SUPER,
+        fields: vec![Field::new("value", VALUE, flags)?],
+        interfaces: vec![
+            "java/lang/CharSequence".to_string(),
+            "java/lang/Comparable".to_string(),
+        ],
+        ..class(
+            "java/lang/String",
+            Some("java/lang/Object"),
+            vec![
+                native("<init>", "()V", public, |vm, args| {
+                    init(vm, this(&args)?, &[])
+                })?,
+                native(
+                    "<init>",
+                    "(Ljava/lang/String;)V",
+                    public,
+                    |vm, args| match non_null_string(vm, &args, 1)? {
+                        Some(chars) => init(vm, this(&args)?, &chars),
+                        None => Ok(None),
+                    },
+                )?,
+                native("<init>", "([C)V", public, |vm, args| {
+                    match array_chars(vm, args[1])? {
+                        Some(chars) => init(vm, this(&args)?, &chars),
+                        None => Ok(None),
+                    }
+                })?,
+                native("<init>", "([CII)V", public, |vm, args| {
+                    let chars = match array_chars(vm, args[1])? {
+                        Some(chars) => chars,
+                        None => return Ok(None),
+                    };
+                    let (offset, count) = (args[2].as_int()?, args[3].as_int()?);
+                    if !check_range(vm, offset, offset.wrapping_add(count), chars.len())? {
+                        return Ok(None);
+                    }
+                    let range = offset as usize..(offset + count) as usize;
+                    init(vm, this(&args)?, &chars[range])
+                })?,
+                native(
+                    "<init>",
+                    "(Ljava/lang/StringBuilder;)V",
+                    public,
+                    |vm, args| match non_null_string(vm, &args, 1)? {
+                        Some(chars) => init(vm, this(&args)?, &chars),
+                        None => Ok(None),
+                    },
+                )?,
+                native("length", "()I", public, |vm, args| {
+                    Ok(Some(Value::Int(chars(vm, this(&args)?)?.len() as i32)))
+                })?,
+                native("isEmpty", "()Z", public, |vm, args| {
+                    Ok(Some(chars(vm, this(&args)?)?.is_empty().into()))
+                })?,
+                native("charAt", "(I)C", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let index = args[1].as_int()?;
+                    if !check_index(vm, index, chars.len())? {
+                        return Ok(None);
+                    }
+                    Ok(Some(chars[index as usize].into()))
+                })?,
+                native("equals", "(Ljava/lang/Object;)Z", public, |vm, args| {
+                    let other = match args[1].as_reference()? {
+                        Some(other) if vm.heap().get(other).class_name() == "java/lang/String" => {
+                            other
+                        }
+                        _ => return Ok(Some(false.into())),
+                    };
+                    let equal = chars(vm, this(&args)?)? == chars(vm, other)?;
+                    Ok(Some(equal.into()))
+                })?,
+                native(
+                    "equalsIgnoreCase",
+                    "(Ljava/lang/String;)Z",
+                    public,
+                    |vm, args| {
+                        let this = String::from_utf16_lossy(&chars(vm, this(&args)?)?);
+                        let equal = match string_arg(vm, &args, 1)? {
+                            Some(other) => {
+                                let other = String::from_utf16_lossy(&other);
+                                this.to_lowercase() == other.to_lowercase()
+                            }
+                            None => false,
+                        };
+                        Ok(Some(equal.into()))
+                    },
+                )?,
+                native("hashCode", "()I", public, |vm, args| {
+                    let hash = chars(vm, this(&args)?)?.iter().fold(0i32, |hash, &c| {
+                        hash.wrapping_mul(31).wrapping_add(i32::from(c))
+                    });
+                    Ok(Some(Value::Int(hash)))
+                })?,
+                native("compareTo", "(Ljava/lang/String;)I", public, compare_to)?,
+                native("compareTo", "(Ljava/lang/Object;)I", public, compare_to)?,
+                native("toString", "()Ljava/lang/String;", public, |_, args| {
+                    Ok(Some(args[0]))
+                })?,
+                native("intern", "()Ljava/lang/String;", public, |vm, args| {
+                    let s = vm.read_string(this(&args)?)?;
+                    vm.intern(&s).map(|s| Some(s.into()))
+                })?,
+                native(
+                    "concat",
+                    "(Ljava/lang/String;)Ljava/lang/String;",
+                    public,
+                    |vm, args| {
+                        let mut this = chars(vm, this(&args)?)?;
+                        match non_null_string(vm, &args, 1)? {
+                            Some(other) => {
+                                this.extend(other);
+                                new_string(vm, &this)
+                            }
+                            None => Ok(None),
+                        }
+                    },
+                )?,
+                native("substring", "(I)Ljava/lang/String;", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let begin = args[1].as_int()?;
+                    if !check_range(vm, begin, chars.len() as i32, chars.len())? {
+                        return Ok(None);
+                    }
+                    new_string(vm, &chars[begin as usize..])
+                })?,
+                native("substring", "(II)Ljava/lang/String;", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let (begin, end) = (args[1].as_int()?, args[2].as_int()?);
+                    if !check_range(vm, begin, end, chars.len())? {
+                        return Ok(None);
+                    }
+                    new_string(vm, &chars[begin as usize..end as usize])
+                })?,
+                native("indexOf", "(I)I", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let c = args[1].as_int()?;
+                    let index = chars.iter().position(|&ch| i32::from(ch) == c);
+                    Ok(Some(Value::Int(index.map(|i| i as i32).unwrap_or(-1))))
+                })?,
+                native("lastIndexOf", "(I)I", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let c = args[1].as_int()?;
+                    let index = chars.iter().rposition(|&ch| i32::from(ch) == c);
+                    Ok(Some(Value::Int(index.map(|i| i as i32).unwrap_or(-1))))
+                })?,
+                native("indexOf", "(Ljava/lang/String;)I", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let needle = match non_null_string(vm, &args, 1)? {
+                        Some(needle) => needle,
+                        None => return Ok(None),
+                    };
+                    let index = find(&chars, &needle, 0);
+                    Ok(Some(Value::Int(index.map(|i| i as i32).unwrap_or(-1))))
+                })?,
+                native("indexOf", "(Ljava/lang/String;I)I", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let needle = match non_null_string(vm, &args, 1)? {
+                        Some(needle) => needle,
+                        None => return Ok(None),
+                    };
+                    let from = args[2].as_int()?.max(0) as usize;
+                    let index = find(&chars, &needle, from);
+                    Ok(Some(Value::Int(index.map(|i| i as i32).unwrap_or(-1))))
+                })?,
+                native(
+                    "contains",
+                    "(Ljava/lang/CharSequence;)Z",
+                    public,
+                    |vm, args| {
+                        let chars = chars(vm, this(&args)?)?;
+                        let needle = match args[1].as_reference()? {
+                            Some(..) => match stringify(vm, args[1])? {
+                                Some(needle) => needle.encode_utf16().collect::<Vec<_>>(),
+                                None => return Ok(None),
+                            },
+                            None => {
+                                vm.throw_new("java/lang/NullPointerException", None)?;
+                                return Ok(None);
+                            }
+                        };
+                        Ok(Some(find(&chars, &needle, 0).is_some().into()))
+                    },
+                )?,
+                native("startsWith", "(Ljava/lang/String;)Z", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    match non_null_string(vm, &args, 1)? {
+                        Some(prefix) => Ok(Some(chars.starts_with(&prefix).into())),
+                        None => Ok(None),
+                    }
+                })?,
+                native("endsWith", "(Ljava/lang/String;)Z", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    match non_null_string(vm, &args, 1)? {
+                        Some(suffix) => Ok(Some(chars.ends_with(&suffix).into())),
+                        None => Ok(None),
+                    }
+                })?,
+                native("trim", "()Ljava/lang/String;", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let start = chars.iter().position(|&c| c > 0x20).unwrap_or(chars.len());
+                    let end = chars
+                        .iter()
+                        .rposition(|&c| c > 0x20)
+                        .map_or(start, |i| i + 1);
+                    if start == 0 && end == chars.len() {
+                        return Ok(Some(args[0]));
+                    }
+                    new_string(vm, &chars[start..end])
+                })?,
+                native("toLowerCase", "()Ljava/lang/String;", public, |vm, args| {
+                    map_chars(vm, &args, lower)
+                })?,
+                native("toUpperCase", "()Ljava/lang/String;", public, |vm, args| {
+                    map_chars(vm, &args, upper)
+                })?,
+                native("replace", "(CC)Ljava/lang/String;", public, |vm, args| {
+                    let (from, to) = (args[1].as_int()? as u16, args[2].as_int()? as u16);
+                    let chars = chars(vm, this(&args)?)?
+                        .into_iter()
+                        .map(|c| if c == from { to } else { c })
+                        .collect::<Vec<_>>();
+                    new_string(vm, &chars)
+                })?,
+                native("toCharArray", "()[C", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    char_array(vm, &chars).map(|array| Some(array.into()))
+                })?,
+                native(
+                    "valueOf",
+                    "(Ljava/lang/Object;)Ljava/lang/String;",
+                    static_,
+                    |vm, args| match stringify(vm, args[0])? {
+                        Some(s) => string_value(vm, &s),
+                        None => Ok(None),
+                    },
+                )?,
+                native(
+                    "valueOf",
+                    "([C)Ljava/lang/String;",
+                    static_,
+                    |vm, args| match array_chars(vm, args[0])? {
+                        Some(chars) => new_string(vm, &chars),
+                        None => Ok(None),
+                    },
+                )?,
+                native("valueOf", "(Z)Ljava/lang/String;", static_, |vm, args| {
+                    value_of(vm, args[0], FieldType::Boolean)
+                })?,
+                native("valueOf", "(C)Ljava/lang/String;", static_, |vm, args| {
+                    value_of(vm, args[0], FieldType::Char)
+                })?,
+                native("valueOf", "(I)Ljava/lang/String;", static_, |vm, args| {
+                    value_of(vm, args[0], FieldType::Int)
+                })?,
+                native("valueOf", "(J)Ljava/lang/String;", static_, |vm, args| {
+                    value_of(vm, args[0], FieldType::Long)
+                })?,
+                native("valueOf", "(F)Ljava/lang/String;", static_, |vm, args| {
+                    value_of(vm, args[0], FieldType::Float)
+                })?,
+                native("valueOf", "(D)Ljava/lang/String;", static_, |vm, args| {
+                    value_of(vm, args[0], FieldType::Double)
+                })?,
+            ],
+        )
+    })
+}
+
+/// Appends to the `StringBuilder` `this`, returning it
+fn append(vm: &mut Interpreter, this: Reference, s: &str) -> Result<Option<Value>> {
+    let value = match vm.get_field(this, "value", VALUE)? {
+        Value::Reference(value) => value,
+        _ => return Err(Error::StackType("char[]")),
+    };
+    let elements = &mut vm.heap_mut().array_mut(value)?.elements;
+    elements.extend(s.encode_utf16().map(Value::from));
+    Ok(Some(this.into()))
+}
+
+/// Replaces the contents of the `StringBuilder` `this`
+fn set_chars(vm: &mut Interpreter, this: Reference, chars: &[u16]) -> Result<()> {
+    let value = char_array(vm, chars)?;
+    vm.set_field(this, "value", VALUE, value.into())
+}
+
+fn append_primitive(vm: &mut Interpreter, args: &[Value], ty: FieldType) -> Result<Option<Value>> {
+    let s = format_primitive(args[1], &ty)?;
+    append(vm, this(args)?, &s)
+}
+
+fn append_object(vm: &mut Interpreter, args: Vec<Value>) -> Result<Option<Value>> {
+    match stringify(vm, args[1])? {
+        Some(s) => append(vm, this(&args)?, &s),
+        None => Ok(None),
+    }
+}
+
+pub(super) fn string_builder() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+    const BUILDER: &str = "Ljava/lang/StringBuilder;";
+
+    fn init(vm: &mut Interpreter, args: Vec<Value>) -> Result<Option<Value>> {
+        set_chars(vm, this(&args)?, &[])?;
+        Ok(None)
+    }
+
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::FINAL | ClassFlags::SUPER,
+        fields: vec![Field::new("value", VALUE, FieldFlags::PRIVATE)?],
+        interfaces: vec!["java/lang/CharSequence".to_string()],
+        ..class(
+            "java/lang/StringBuilder",
+            Some("java/lang/Object"),
+            vec![
+                native("<init>", "()V", public, init)?,
+                native("<init>", "(I)V", public, |vm, args| {
+                    if args[1].as_int()? < 0 {
+                        let message = args[1].as_int()?.to_string();
+                        vm.throw_new("java/lang/NegativeArraySizeException", Some(&message))?;
+                        return Ok(None);
+                    }
+                    init(vm, args)
+                })?,
+                native(
+                    "<init>",
+                    "(Ljava/lang/String;)V",
+                    public,
+                    |vm, args| match non_null_string(vm, &args, 1)? {
+                        Some(chars) => set_chars(vm, this(&args)?, &chars).map(|_| None),
+                        None => Ok(None),
+                    },
+                )?,
+                native(
+                    "append",
+                    &format!("(Ljava/lang/String;){}", BUILDER),
+                    public,
+                    append_object,
+                )?,
+                native(
+                    "append",
+                    &format!("(Ljava/lang/Object;){}", BUILDER),
+                    public,
+                    append_object,
+                )?,
+                native(
+                    "append",
+                    &format!("(Ljava/lang/CharSequence;){}", BUILDER),
+                    public,
+                    append_object,
+                )?,
+                native(
+                    "append",
+                    &format!("([C){}", BUILDER),
+                    public,
+                    |vm, args| match array_chars(vm, args[1])? {
+                        Some(chars) => append(vm, this(&args)?, &String::from_utf16_lossy(&chars)),
+                        None => Ok(None),
+                    },
+                )?,
+                native("append", &format!("(Z){}", BUILDER), public, |vm, args| {
+                    append_primitive(vm, &args, FieldType::Boolean)
+                })?,
+                native("append", &format!("(C){}", BUILDER), public, |vm, args| {
+                    append_primitive(vm, &args, FieldType::Char)
+                })?,
+                native("append", &format!("(I){}", BUILDER), public, |vm, args| {
+                    append_primitive(vm, &args, FieldType::Int)
+                })?,
+                native("append", &format!("(J){}", BUILDER), public, |vm, args| {
+                    append_primitive(vm, &args, FieldType::Long)
+                })?,
+                native("append", &format!("(F){}", BUILDER), public, |vm, args| {
+                    append_primitive(vm, &args, FieldType::Float)
+                })?,
+                native("append", &format!("(D){}", BUILDER), public, |vm, args| {
+                    append_primitive(vm, &args, FieldType::Double)
+                })?,
+                native(
+                    "insert",
+                    &format!("(ILjava/lang/String;){}", BUILDER),
+                    public,
+                    |vm, args| {
+                        let mut chars = chars(vm, this(&args)?)?;
+                        let offset = args[1].as_int()?;
+                        if !check_range(vm, offset, offset, chars.len())? {
+                            return Ok(None);
+                        }
+                        let s = match stringify(vm, args[2])? {
+                            Some(s) => s,
+                            None => return Ok(None),
+                        };
+                        let offset = offset as usize;
+                        chars.splice(offset..offset, s.encode_utf16());
+                        set_chars(vm, this(&args)?, &chars)?;
+                        Ok(Some(args[0]))
+                    },
+                )?,
+                native("reverse", &format!("(){}", BUILDER), public, |vm, args| {
+                    let s = String::from_utf16_lossy(&chars(vm, this(&args)?)?);
+                    let reversed = s.chars().rev().collect::<String>();
+                    let reversed = reversed.encode_utf16().collect::<Vec<_>>();
+                    set_chars(vm, this(&args)?, &reversed)?;
+                    Ok(Some(args[0]))
+                })?,
+                native(
+                    "deleteCharAt",
+                    &format!("(I){}", BUILDER),
+                    public,
+                    |vm, args| {
+                        let mut chars = chars(vm, this(&args)?)?;
+                        let index = args[1].as_int()?;
+                        if !check_index(vm, index, chars.len())? {
+                            return Ok(None);
+                        }
+                        chars.remove(index as usize);
+                        set_chars(vm, this(&args)?, &chars)?;
+                        Ok(Some(args[0]))
+                    },
+                )?,
+                native("setCharAt", "(IC)V", public, |vm, args| {
+                    let mut chars = chars(vm, this(&args)?)?;
+                    let index = args[1].as_int()?;
+                    if !check_index(vm, index, chars.len())? {
+                        return Ok(None);
+                    }
+                    chars[index as usize] = args[2].as_int()? as u16;
+                    set_chars(vm, this(&args)?, &chars)?;
+                    Ok(None)
+                })?,
+                native("setLength", "(I)V", public, |vm, args| {
+                    let mut chars = chars(vm, this(&args)?)?;
+                    let len = args[1].as_int()?;
+                    if len < 0 {
+                        let message = format!("String index out of range: {}", len);
+                        vm.throw_new("java/lang/StringIndexOutOfBoundsException", Some(&message))?;
+                        return Ok(None);
+                    }
+                    chars.resize(len as usize, 0);
+                    set_chars(vm, this(&args)?, &chars)?;
+                    Ok(None)
+                })?,
+                native("length", "()I", public, |vm, args| {
+                    Ok(Some(Value::Int(chars(vm, this(&args)?)?.len() as i32)))
+                })?,
+                native("charAt", "(I)C", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let index = args[1].as_int()?;
+                    if !check_index(vm, index, chars.len())? {
+                        return Ok(None);
+                    }
+                    Ok(Some(chars[index as usize].into()))
+                })?,
+                native("indexOf", "(Ljava/lang/String;)I", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    let needle = match non_null_string(vm, &args, 1)? {
+                        Some(needle) => needle,
+                        None => return Ok(None),
+                    };
+                    let index = find(&chars, &needle, 0);
+                    Ok(Some(Value::Int(index.map(|i| i as i32).unwrap_or(-1))))
+                })?,
+                native("toString", "()Ljava/lang/String;", public, |vm, args| {
+                    let chars = chars(vm, this(&args)?)?;
+                    new_string(vm, &chars)
+                })?,
+            ],
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn java_decimals() {
+        assert_eq!(format_double(1.0), "1.0");
+        assert_eq!(format_double(0.1), "0.1");
+        assert_eq!(format_double(-2.5), "-2.5");
+        assert_eq!(format_double(1e7), "1.0E7");
+        assert_eq!(format_double(1.5e-7), "1.5E-7");
+        assert_eq!(format_double(123_456.789), "123456.789");
+        assert_eq!(format_double(-0.0), "-0.0");
+        assert_eq!(format_double(f64::NAN), "NaN");
+        assert_eq!(format_double(f64::NEG_INFINITY), "-Infinity");
+        assert_eq!(format_float(0.1), "0.1");
+        assert_eq!(format_float(3.0e10), "3.0E10");
+    }
+}
@@ -0,0 +1,284 @@
+use super::string::{array_chars, format_primitive, stringify};
+use super::*;
+use crate::exec::stream::Output;
+
+use std::io::Write;
+use std::time::UNIX_EPOCH;
+
+const PRINT_STREAM: &str = "Ljava/io/PrintStream;";
+
+/// Which of the interpreter's outputs a `PrintStream` writes to
+const STDOUT: i32 = 1;
+const STDERR: i32 = 2;
+
+pub(super) fn system() -> Result<ClassDef> {
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+    let stream = FieldFlags::PUBLIC | FieldFlags::STATIC | FieldFlags::FINAL;
+
+    fn print_stream(vm: &mut Interpreter, fd: i32) -> Result<Value> {
+        let class = vm.resolve_class("java/io/PrintStream")?;
+        let stream = vm.heap_mut().new_instance(class);
+        vm.set_field(stream, "fd", "I", Value::Int(fd))?;
+        Ok(stream.into())
+    }
+
+    Ok(ClassDef {
+        flags: ClassFlags::PUBLIC | ClassFlags::FINAL | ClassFlags::SUPER,
+        fields: vec![
+            Field::new("out", PRINT_STREAM, stream)?,
+            Field::new("err", PRINT_STREAM, stream)?,
+        ],
+        ..class(
+            "java/lang/System",
+            Some("java/lang/Object"),
+            vec![
+                native("<clinit>", "()V", MethodFlags::STATIC, |vm, _| {
+                    let system = vm.resolve_class("java/lang/System")?;
+                    let out = print_stream(vm, STDOUT)?;
+                    set_static(&system, "out", PRINT_STREAM, out)?;
+                    let err = print_stream(vm, STDERR)?;
+                    set_static(&system, "err", PRINT_STREAM, err)?;
+                    Ok(None)
+                })?,
+                native(
+                    "arraycopy",
+                    "(Ljava/lang/Object;ILjava/lang/Object;II)V",
+                    static_,
+                    array_copy,
+                )?,
+                native("currentTimeMillis", "()J", static_, |vm, _| {
+                    let now = vm
+                        .wall_clock()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|elapsed| elapsed.as_millis() as i64)
+                        .unwrap_or_default();
+                    Ok(Some(Value::Long(now)))
+                })?,
+                native("nanoTime", "()J", static_, |vm, _| {
+                    let now = vm.monotonic_clock().as_nanos();
+                    Ok(Some(Value::Long(now as i64)))
+                })?,
+                native("gc", "()V", static_, |vm, _| {
+                    vm.gc();
+                    Ok(None)
+                })?,
+                native(
+                    "identityHashCode",
+                    "(Ljava/lang/Object;)I",
+                    static_,
+                    |_, args| {
+                        let hash = args[0].as_reference()?.map_or(0, |object| object.index());
+                        Ok(Some(Value::Int(hash as i32)))
+                    },
+                )?,
+                native("lineSeparator", "()Ljava/lang/String;", static_, |vm, _| {
+                    vm.intern("\n").map(|s| Some(s.into()))
+                })?,
+            ],
+        )
+    })
+}
+
+/// `System.arraycopy`
+// https://docs.oracle.com/javase/8/docs/api/java/lang/System.html#arraycopy-java.lang.Object-int-java.lang.Object-int-int-
+fn array_copy(vm: &mut Interpreter, args: Vec<Value>) -> Result<Option<Value>> {
+    let (src, dest) = match (args[0].as_reference()?, args[2].as_reference()?) {
+        (Some(src), Some(dest)) => (src, dest),
+        _ => {
+            vm.throw_new("java/lang/NullPointerException", None)?;
+            return Ok(None);
+        }
+    };
+    let (src_pos, dest_pos, len) = (args[1].as_int()?, args[3].as_int()?, args[4].as_int()?);
+
+    let component = |vm: &Interpreter, array| match vm.heap().get(array) {
+        heap::Object::Array(array) => Some(array.component.clone()),
+        heap::Object::Instance(..) => None,
+    };
+    let (src_type, dest_type) = match (component(vm, src), component(vm, dest)) {
+        (Some(src_type), Some(dest_type)) => (src_type, dest_type),
+        (src_type, _) => {
+            let (which, array) = if src_type.is_none() {
+                ("source", src)
+            } else {
+                ("destination", dest)
+            };
+            let message = format!(
+                "arraycopy: {} type {} is not an array",
+                which,
+                vm.heap().get(array).class_name().replace('/', ".")
+            );
+            vm.throw_new("java/lang/ArrayStoreException", Some(&message))?;
+            return Ok(None);
+        }
+    };
+
+    if src_type != dest_type && !(src_type.is_reference() && dest_type.is_reference()) {
+        let message = format!(
+            "arraycopy: type mismatch: can not copy {}[] into {}[]",
+            src_type, dest_type
+        );
+        vm.throw_new("java/lang/ArrayStoreException", Some(&message))?;
+        return Ok(None);
+    }
+
+    let src_len = vm.heap().array(src)?.elements.len() as i64;
+    let dest_len = vm.heap().array(dest)?.elements.len() as i64;
+    if src_pos < 0
+        || dest_pos < 0
+        || len < 0
+        || i64::from(src_pos) + i64::from(len) > src_len
+        || i64::from(dest_pos) + i64::from(len) > dest_len
+    {
+        let message = format!(
+            "arraycopy: range {}..{} into {}..{} is out of bounds",
+            src_pos,
+            i64::from(src_pos) + i64::from(len),
+            dest_pos,
+            i64::from(dest_pos) + i64::from(len)
+        );
+        vm.throw_new("java/lang/ArrayIndexOutOfBoundsException", Some(&message))?;
+        return Ok(None);
+    }
+
+    let (src_pos, dest_pos, len) = (src_pos as usize, dest_pos as usize, len as usize);
+    let elements = vm.heap().array(src)?.elements[src_pos..src_pos + len].to_vec();
+
+    // each element of a reference array has to be assignable to the destination's type,
+    // the elements before the first one that isn't are still copied
+    let name = |ty: &FieldType| match ty {
+        FieldType::Object(name) => name.clone(),
+        ty => ty.to_string(),
+    };
+    let mut count = elements.len();
+    if dest_type.is_reference() && !vm.is_assignable(&name(&src_type), &name(&dest_type))? {
+        for (i, element) in elements.iter().enumerate() {
+            if let Some(element) = element.as_reference()? {
+                let class = vm.heap().get(element).class_name();
+                if !vm.is_assignable(&class, &name(&dest_type))? {
+                    count = i;
+                    break;
+                }
+            }
+        }
+    }
+
+    let dest_elements = &mut vm.heap_mut().array_mut(dest)?.elements;
+    dest_elements[dest_pos..dest_pos + count].copy_from_slice(&elements[..count]);
+
+    if count < elements.len() {
+        let message = format!(
+            "arraycopy: element type mismatch: can not cast one of the elements of {}[] to \
+             the type of the destination array, {}",
+            name(&src_type).replace('/', "."),
+            name(&dest_type).replace('/', ".")
+        );
+        vm.throw_new("java/lang/ArrayStoreException", Some(&message))?;
+    }
+    Ok(None)
+}
+
+/// The output the `PrintStream` `this` writes to
+fn output(vm: &mut Interpreter, this: Reference) -> Result<&mut Output> {
+    match vm.get_field(this, "fd", "I")?.as_int()? {
+        STDERR => Ok(vm.stderr()),
+        _ => Ok(vm.stdout()),
+    }
+}
+
+fn write(vm: &mut Interpreter, this: Reference, s: &str) -> Result<Option<Value>> {
+    let output = output(vm, this)?;
+    output.write_all(s.as_bytes())?;
+    output.flush()?;
+    Ok(None)
+}
+
+fn print(
+    vm: &mut Interpreter,
+    args: &[Value],
+    ty: FieldType,
+    newline: bool,
+) -> Result<Option<Value>> {
+    let mut s = match ty {
+        FieldType::Object(..) | FieldType::Array(..) => match stringify(vm, args[1])? {
+            Some(s) => s,
+            None => return Ok(None),
+        },
+        ty => format_primitive(args[1], &ty)?,
+    };
+    if newline {
+        s.push('\n');
+    }
+    write(vm, this(args)?, &s)
+}
+
+fn print_chars(vm: &mut Interpreter, args: &[Value], newline: bool) -> Result<Option<Value>> {
+    let chars = match array_chars(vm, args[1])? {
+        Some(chars) => chars,
+        None => return Ok(None),
+    };
+    let mut s = String::from_utf16_lossy(&chars);
+    if newline {
+        s.push('\n');
+    }
+    write(vm, this(args)?, &s)
+}
+
+/// `print` and `println` for an argument of type `$ty`
+macro_rules! print_methods {
+    ($methods:expr, $public:expr, $($descriptor:expr => $ty:expr),* $(,)?) => {
+        $(
+            $methods.push(native("print", concat!("(", $descriptor, ")V"), $public, |vm, args| {
+                print(vm, &args, $ty, false)
+            })?);
+            $methods.push(native("println", concat!("(", $descriptor, ")V"), $public, |vm, args| {
+                print(vm, &args, $ty, true)
+            })?);
+        )*
+    };
+}
+
+pub(super) fn print_stream() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+
+    let mut methods = vec![
+        native("println", "()V", public, |vm, args| {
+            write(vm, this(&args)?, "\n")
+        })?,
+        native("print", "([C)V", public, |vm, args| {
+            print_chars(vm, &args, false)
+        })?,
+        native("println", "([C)V", public, |vm, args| {
+            print_chars(vm, &args, true)
+        })?,
+        native("write", "(I)V", public, |vm, args| {
+            let byte = args[1].as_int()? as u8;
+            output(vm, this(&args)?)?.write_all(&[byte])?;
+            Ok(None)
+        })?,
+        native("flush", "()V", public, |vm, args| {
+            output(vm, this(&args)?)?.flush()?;
+            Ok(None)
+        })?,
+    ];
+    print_methods! {
+        methods, public,
+        "Ljava/lang/String;" => FieldType::Object("java/lang/String".into()),
+        "Ljava/lang/Object;" => FieldType::Object("java/lang/Object".into()),
+        "Z" => FieldType::Boolean,
+        "C" => FieldType::Char,
+        "I" => FieldType::Int,
+        "J" => FieldType::Long,
+        "F" => FieldType::Float,
+        "D" => FieldType::Double,
+    }
+
+    Ok(ClassDef {
+        fields: vec![Field::new(
+            "fd",
+            "I",
+            FieldFlags::PRIVATE | FieldFlags::FINAL,
+        )?],
+        ..class("java/io/PrintStream", Some("java/lang/Object"), methods)
+    })
+}
@@ -0,0 +1,138 @@
+use super::*;
+
+const NAME: &str = "Ljava/lang/String;";
+const TARGET: &str = "Ljava/lang/Runnable;";
+
+pub(super) fn runnable() -> Result<ClassDef> {
+    Ok(interface(
+        "java/lang/Runnable",
+        vec![abstract_method("run", "()V")?],
+    ))
+}
+
+pub(super) fn thread() -> Result<ClassDef> {
+    let public = MethodFlags::PUBLIC;
+    let static_ = MethodFlags::PUBLIC | MethodFlags::STATIC;
+
+    /// Sets up a new thread. Threads without a name are numbered, like `Thread-0`
+    fn init(vm: &mut Interpreter, this: Reference, target: Value, name: Value) -> Result<()> {
+        let name = match name {
+            Value::Reference(..) => name,
+            _ => {
+                let class = vm.resolve_class("java/lang/Thread")?;
+                let number = match class.find_field("threadInitNumber", "I") {
+                    Some(field) => {
+                        let mut statics = class.statics.borrow_mut();
+                        let number = statics[field.slot].as_int()?;
+                        statics[field.slot] = Value::Int(number + 1);
+                        number
+                    }
+                    None => generic_error!("java/lang/Thread has no threadInitNumber"),
+                };
+                vm.new_string(&format!("Thread-{}", number))?.into()
+            }
+        };
+        vm.set_field(this, "target", TARGET, target)?;
+        vm.set_field(this, "name", NAME, name)
+    }
+
+    /// `millis`, which has to be the second argument
+    fn millis(args: &[Value]) -> Result<i64> {
+        match args.get(1) {
+            Some(millis) => millis.as_long(),
+            None => Err(Error::StackType("long")),
+        }
+    }
+
+    Ok(ClassDef {
+        interfaces: vec!["java/lang/Runnable".to_string()],
+        fields: vec![
+            Field::new("name", NAME, FieldFlags::PRIVATE)?,
+            Field::new("target", TARGET, FieldFlags::PRIVATE)?,
+            Field::new("daemon", "Z", FieldFlags::PRIVATE)?,
+            Field::new(
+                "threadInitNumber",
+                "I",
+                FieldFlags::PRIVATE | FieldFlags::STATIC,
+            )?,
+        ],
+        ..class(
+            "java/lang/Thread",
+            Some("java/lang/Object"),
+            vec![
+                native("<init>", "()V", public, |vm, args| {
+                    init(vm, this(&args)?, Value::Null, Value::Null)?;
+                    Ok(None)
+                })?,
+                native("<init>", "(Ljava/lang/Runnable;)V", public, |vm, args| {
+                    init(vm, this(&args)?, args[1], Value::Null)?;
+                    Ok(None)
+                })?,
+                native(
+                    "<init>",
+                    "(Ljava/lang/Runnable;Ljava/lang/String;)V",
+                    public,
+                    |vm, args| {
+                        init(vm, this(&args)?, args[1], args[2])?;
+                        Ok(None)
+                    },
+                )?,
+                native("<init>", "(Ljava/lang/String;)V", public, |vm, args| {
+                    init(vm, this(&args)?, Value::Null, args[1])?;
+                    Ok(None)
+                })?,
+                native("start", "()V", public, |vm, args| {
+                    vm.start_thread(this(&args)?)?;
+                    Ok(None)
+                })?,
+                native("run", "()V", public, |vm, args| {
+                    match vm.get_field(this(&args)?, "target", TARGET)? {
+                        Value::Reference(target) => vm.call_method(target, "run", "()V", vec![]),
+                        _ => Ok(None),
+                    }
+                })?,
+                native("join", "()V", public, |vm, args| {
+                    vm.join_thread(this(&args)?, 0)?;
+                    Ok(None)
+                })?,
+                native("join", "(J)V", public, |vm, args| {
+                    vm.join_thread(this(&args)?, millis(&args)?)?;
+                    Ok(None)
+                })?,
+                native("isAlive", "()Z", public, |vm, args| {
+                    Ok(Some(Value::from(vm.is_alive(this(&args)?))))
+                })?,
+                native("getName", "()Ljava/lang/String;", public, |vm, args| {
+                    vm.get_field(this(&args)?, "name", NAME).map(Some)
+                })?,
+                native("setName", "(Ljava/lang/String;)V", public, |vm, args| {
+                    vm.set_field(this(&args)?, "name", NAME, args[1])?;
+                    Ok(None)
+                })?,
+                native("isDaemon", "()Z", public, |vm, args| {
+                    vm.get_field(this(&args)?, "daemon", "Z").map(Some)
+                })?,
+                native("setDaemon", "(Z)V", public, |vm, args| {
+                    let this = this(&args)?;
+                    if vm.is_alive(this) {
+                        vm.throw_new("java/lang/IllegalThreadStateException", None)?;
+                        return Ok(None);
+                    }
+                    vm.set_field(this, "daemon", "Z", args[1])?;
+                    Ok(None)
+                })?,
+                native("currentThread", "()Ljava/lang/Thread;", static_, |vm, _| {
+                    Ok(vm.current_thread()?.map(Value::from))
+                })?,
+                native("sleep", "(J)V", static_, |vm, args| {
+                    vm.sleep(args[0].as_long()?)?;
+                    Ok(None)
+                })?,
+                native("yield", "()V", static_, |vm, _| {
+                    vm.yield_thread();
+                    Ok(None)
+                })?,
+            ],
+        )
+    })
+}
@@ -0,0 +1,427 @@
+//! Profiling what the interpreter runs: how often each method is called and each
+//! instruction is run, how long is spent in each method and call stack, and how long each
+//! opcode takes, to find the hot paths of a program and of the interpreter itself
+use super::class::Method;
+use super::*;
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How many methods and instructions the report lists
+const REPORT_LINES: usize = 20;
+
+/// What the call stacks of the collapsed output are weighed by
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Weight {
+    /// The instructions run in the stack
+    Instructions,
+    /// The nanoseconds spent in the stack
+    Time,
+}
+
+/// A method that has been run, and what was spent in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodProfile {
+    /// The internal name of the method's class
+    pub class: String,
+    pub name: String,
+    pub descriptor: String,
+    pub calls: u64,
+    pub instructions: u64,
+    /// The time spent in the method and the methods it called
+    pub inclusive: Duration,
+    /// The time spent in the method itself
+    pub exclusive: Duration,
+    /// How often the instruction at each pc was run
+    pub pcs: BTreeMap<usize, u64>,
+}
+
+/// How often an opcode was run, and how long it took altogether
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct OpcodeProfile {
+    pub opcode: u8,
+    pub count: u64,
+    pub time: Duration,
+}
+
+impl OpcodeProfile {
+    /// The opcode's mnemonic, like `iadd`
+    pub fn mnemonic(&self) -> String {
+        match Instruction::lookup(self.opcode) {
+            Some(instruction) => instruction.to_string().to_lowercase(),
+            None => format!("{:#04x}", self.opcode),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct Sample {
+    instructions: u64,
+    time: Duration,
+}
+
+/// The instruction or native method that is running, which the time until the next one is
+/// put down to
+#[derive(Debug, Copy, Clone)]
+struct Running {
+    since: Instant,
+    /// The opcode of the instruction, or `None` for a native method
+    opcode: Option<u8>,
+    /// The call stack it was sampled in, if it was
+    stack: Option<usize>,
+}
+
+/// Counts the calls and instructions the interpreter runs, and times them. Every call stack
+/// is recorded, unless the profiler samples it every so many instructions to be cheaper.
+/// Native methods are timed with the stacks they are called in, on top of them
+///
+/// ```ignore
+/// vm.set_profiler(Some(Profiler::new()));
+/// vm.run_main("com/acme/Main")?;
+/// eprint!("{}", vm.profiler().unwrap().report());
+/// ```
+#[derive(Debug)]
+pub struct Profiler {
+    interval: u64,
+    countdown: u64,
+    /// The methods by the address of their `Method`, which they are kept alive by so that
+    /// it isn't reused
+    ids: HashMap<*const Method, usize>,
+    methods: Vec<(Rc<Method>, MethodProfile)>,
+    threads: HashMap<usize, String>,
+    /// The call stacks as the thread, then the ids of the methods from the bottom
+    stack_ids: HashMap<Vec<usize>, usize>,
+    stacks: Vec<(Vec<usize>, Sample)>,
+    opcodes: Vec<OpcodeProfile>,
+    running: Option<Running>,
+    /// What was running when each native method that hasn't returned was called
+    natives: Vec<Option<Running>>,
+    scratch: Vec<usize>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            interval: 1,
+            countdown: 0,
+            ids: HashMap::new(),
+            methods: vec![],
+            threads: HashMap::new(),
+            stack_ids: HashMap::new(),
+            stacks: vec![],
+            opcodes: (0..=255u8)
+                .map(|opcode| OpcodeProfile {
+                    opcode,
+                    ..OpcodeProfile::default()
+                })
+                .collect(),
+            running: None,
+            natives: vec![],
+            scratch: vec![],
+        }
+    }
+
+    /// Records the call stack every `interval` instructions rather than for each one. The
+    /// time in between is put down to the stack last recorded. Calls and instructions are
+    /// still all counted
+    pub fn sample_every(&mut self, interval: u64) -> &mut Self {
+        self.interval = interval.max(1);
+        self
+    }
+
+    fn method_id(&mut self, method: &Rc<Method>) -> usize {
+        if let Some(&id) = self.ids.get(&Rc::as_ptr(method)) {
+            return id;
+        }
+        let profile = MethodProfile {
+            class: method.class().name.clone(),
+            name: method.name.clone(),
+            descriptor: method.descriptor.clone(),
+            calls: 0,
+            instructions: 0,
+            inclusive: Duration::default(),
+            exclusive: Duration::default(),
+            pcs: BTreeMap::new(),
+        };
+        let id = self.methods.len();
+        self.ids.insert(Rc::as_ptr(method), id);
+        self.methods.push((Rc::clone(method), profile));
+        id
+    }
+
+    /// Counts a call of `method`
+    pub(crate) fn call(&mut self, method: &Rc<Method>) {
+        let id = self.method_id(method);
+        self.methods[id].1.calls += 1;
+    }
+
+    /// Records that the instruction at `pc` of the top method of `stack` is about to run on
+    /// `thread`, and puts the time since the last one down to that
+    pub(crate) fn record<'a>(
+        &mut self,
+        thread: usize,
+        thread_name: &str,
+        stack: impl DoubleEndedIterator<Item = &'a Rc<Method>> + Clone,
+        pc: usize,
+        opcode: u8,
+    ) {
+        let now = Instant::now();
+        let mut sampled = self.running.and_then(|running| running.stack);
+        self.stop_running(now);
+
+        if let Some(top) = stack.clone().next_back() {
+            let id = self.method_id(top);
+            let method = &mut self.methods[id].1;
+            method.instructions += 1;
+            *method.pcs.entry(pc).or_default() += 1;
+        }
+        self.opcodes[usize::from(opcode)].count += 1;
+
+        if self.countdown == 0 {
+            self.countdown = self.interval;
+            sampled = Some(self.sample(thread, thread_name, stack));
+        }
+        self.countdown -= 1;
+        self.running = Some(Running {
+            since: now,
+            opcode: Some(opcode),
+            stack: sampled,
+        });
+    }
+
+    /// Records that the native `method` is called by the top method of `stack`, and puts the
+    /// time until it returns down to it, but for the methods it calls itself
+    pub(crate) fn enter_native<'a>(
+        &mut self,
+        thread: usize,
+        thread_name: &str,
+        stack: impl Iterator<Item = &'a Rc<Method>>,
+        method: &'a Rc<Method>,
+    ) {
+        let now = Instant::now();
+        self.stop_running(now);
+        let stack = self.stack_id(thread, thread_name, stack.chain(Some(method)));
+        self.natives.push(self.running.take());
+        self.running = Some(Running {
+            since: now,
+            opcode: None,
+            stack: Some(stack),
+        });
+    }
+
+    /// Records that the native method last called has returned, and goes back to timing
+    /// the instruction that called it
+    pub(crate) fn leave_native(&mut self) {
+        let now = Instant::now();
+        self.stop_running(now);
+        self.running = self.natives.pop().flatten().map(|caller| Running {
+            since: now,
+            ..caller
+        });
+    }
+
+    /// Puts the time since what is running started down to its opcode and stack
+    fn stop_running(&mut self, now: Instant) {
+        if let Some(running) = self.running {
+            let elapsed = now - running.since;
+            if let Some(opcode) = running.opcode {
+                self.opcodes[usize::from(opcode)].time += elapsed;
+            }
+            if let Some(stack) = running.stack {
+                self.stacks[stack].1.time += elapsed;
+            }
+        }
+    }
+
+    /// Counts the instructions until the next sample against `stack`, and returns its id
+    fn sample<'a>(
+        &mut self,
+        thread: usize,
+        thread_name: &str,
+        stack: impl Iterator<Item = &'a Rc<Method>>,
+    ) -> usize {
+        let id = self.stack_id(thread, thread_name, stack);
+        self.stacks[id].1.instructions += self.interval;
+        id
+    }
+
+    /// The id of `stack` on `thread`, which is recorded the first time
+    fn stack_id<'a>(
+        &mut self,
+        thread: usize,
+        thread_name: &str,
+        stack: impl Iterator<Item = &'a Rc<Method>>,
+    ) -> usize {
+        self.threads
+            .entry(thread)
+            .or_insert_with(|| thread_name.to_string());
+        let mut key = std::mem::take(&mut self.scratch);
+        key.clear();
+        key.push(thread);
+        for method in stack {
+            key.push(self.method_id(method));
+        }
+
+        match self.stack_ids.get(key.as_slice()) {
+            Some(&id) => {
+                self.scratch = key;
+                id
+            }
+            None => {
+                let id = self.stacks.len();
+                self.stack_ids.insert(key.clone(), id);
+                self.stacks.push((key, Sample::default()));
+                id
+            }
+        }
+    }
+
+    /// The methods that have been run, with the time spent in them, most exclusive time
+    /// first
+    pub fn methods(&self) -> Vec<MethodProfile> {
+        let mut methods = self
+            .methods
+            .iter()
+            .map(|(_, profile)| profile.clone())
+            .collect::<Vec<_>>();
+        let mut seen = vec![];
+        for (key, sample) in &self.stacks {
+            let stack = &key[1..];
+            if let Some(&top) = stack.last() {
+                methods[top].exclusive += sample.time;
+            }
+            // recursive methods are in the stack more than once, but only spend the time once
+            seen.clear();
+            for &id in stack {
+                if !seen.contains(&id) {
+                    seen.push(id);
+                    methods[id].inclusive += sample.time;
+                }
+            }
+        }
+        methods.sort_by(|a, b| {
+            (b.exclusive, b.instructions)
+                .cmp(&(a.exclusive, a.instructions))
+                .then_with(|| (&a.class, &a.name).cmp(&(&b.class, &b.name)))
+        });
+        methods
+    }
+
+    /// The opcodes that have been run, most often run first
+    pub fn opcodes(&self) -> Vec<OpcodeProfile> {
+        let mut opcodes = self
+            .opcodes
+            .iter()
+            .filter(|opcode| opcode.count > 0)
+            .copied()
+            .collect::<Vec<_>>();
+        opcodes.sort_by_key(|opcode| Reverse((opcode.count, opcode.time)));
+        opcodes
+    }
+
+    /// The call stacks in the collapsed format flame graph tools read: a line per stack,
+    /// from the thread down to the running method separated by `;`, and then its weight
+    // https://github.com/brendangregg/FlameGraph#2-fold-stacks
+    pub fn collapsed(&self, weight: Weight) -> String {
+        let mut lines = self
+            .stacks
+            .iter()
+            .filter_map(|(key, sample)| {
+                let weight = match weight {
+                    Weight::Instructions => sample.instructions,
+                    Weight::Time => sample.time.as_nanos() as u64,
+                };
+                if weight == 0 {
+                    return None;
+                }
+                let mut line = self.threads.get(&key[0]).cloned().unwrap_or_default();
+                for &id in &key[1..] {
+                    let method = &self.methods[id].1;
+                    let _ = write!(line, ";{}.{}", method.class, method.name);
+                }
+                Some((line, weight))
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+
+        let mut out = String::new();
+        for (line, weight) in lines {
+            let _ = writeln!(out, "{} {}", line, weight);
+        }
+        out
+    }
+
+    /// A report of the methods that took the most time, the instructions run most often,
+    /// and how often each opcode was run
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let methods = self.methods();
+
+        let _ = writeln!(
+            out,
+            "{:>12} {:>12} {:>10} {:>12}  method",
+            "exclusive", "inclusive", "calls", "instructions"
+        );
+        for method in methods.iter().take(REPORT_LINES) {
+            let _ = writeln!(
+                out,
+                "{:>12} {:>12} {:>10} {:>12}  {}.{}{}",
+                format!("{:.3?}", method.exclusive),
+                format!("{:.3?}", method.inclusive),
+                method.calls,
+                method.instructions,
+                method.class,
+                method.name,
+                method.descriptor
+            );
+        }
+
+        let mut instructions = self
+            .methods
+            .iter()
+            .flat_map(|(method, profile)| {
+                let pcs = profile.pcs.iter();
+                pcs.map(move |(&pc, &count)| (count, method, profile, pc))
+            })
+            .collect::<Vec<_>>();
+        instructions.sort_by_key(|&(count, ..)| Reverse(count));
+        let _ = writeln!(out, "\n{:>12} {:>6} {:<16} method", "count", "pc", "opcode");
+        for (count, method, profile, pc) in instructions.into_iter().take(REPORT_LINES) {
+            let opcode = method.code().and_then(|code| code.code.get(pc).copied());
+            let mnemonic = match opcode {
+                Some(opcode) => OpcodeProfile {
+                    opcode,
+                    ..OpcodeProfile::default()
+                }
+                .mnemonic(),
+                None => String::new(),
+            };
+            let _ = writeln!(
+                out,
+                "{:>12} {:>6} {:<16} {}.{}{}",
+                count, pc, mnemonic, profile.class, profile.name, profile.descriptor
+            );
+        }
+
+        let _ = writeln!(out, "\n{:>12} {:>12}  opcode", "count", "time");
+        for opcode in self.opcodes() {
+            let _ = writeln!(
+                out,
+                "{:>12} {:>12}  {}",
+                opcode.count,
+                format!("{:.3?}", opcode.time),
+                opcode.mnemonic()
+            );
+        }
+        out
+    }
+}
@@ -0,0 +1,722 @@
+//! Renaming classes, fields and methods across a set of class files, from mappings like
+//! a ProGuard `mapping.txt`
+//!
+//! Constants that are renamed get new entries in the constant pool instead of being
+//! changed in place, as a `Utf8` can be shared by names, descriptors and strings. So the
+//! indices used by the code stay the same, and the old names are left in the pool unused
+use super::asm::ConstantPool;
+use super::*;
+
+use attr::{Annotation, Attribute, ElementValue};
+use std::collections::HashMap;
+use ty::{Constant, ConstantIndex};
+
+mod mappings;
+mod signature;
+
+pub use mappings::Mappings;
+
+/// Renames what `mappings` renames in each of `files`. A member that isn't mapped in the
+/// class it is referred to through is looked up in the super classes and interfaces of
+/// that class which are in `files`, so an inherited field or method is renamed
+/// consistently
+pub fn remap(files: &mut [ty::ClassFile], mappings: &Mappings) -> Result<()> {
+    let mut supers = HashMap::new();
+    for file in files.iter() {
+        let mut parents = vec![];
+        if let Some(super_class) = file.super_class_name()? {
+            parents.push(super_class.to_string());
+        }
+        for &interface in &file.interfaces {
+            parents.push(file.class_name(interface)?.to_string());
+        }
+        supers.insert(file.get_class_name().to_string(), parents);
+    }
+
+    let remapper = Remapper { mappings, supers };
+    for file in files {
+        remapper.remap(file)?;
+    }
+    Ok(())
+}
+
+struct Remapper<'a> {
+    mappings: &'a Mappings,
+    /// The super class and interfaces of each class, by the names before remapping
+    supers: HashMap<String, Vec<String>>,
+}
+
+/// A class file as it is being remapped, with the constant pool from before
+struct Remapping<'a> {
+    old: &'a ty::ClassFile,
+    pool: ConstantPool,
+}
+
+impl Remapping<'_> {
+    fn utf8(&mut self, s: &str) -> Result<ConstantIndex> {
+        self.pool.utf8(s)
+    }
+
+    /// The index of `name` if it is different from the `Utf8` at `index`
+    fn renamed(&mut self, index: ConstantIndex, name: &str) -> Result<ConstantIndex> {
+        match self.old.utf8(index)? == name {
+            true => Ok(index),
+            false => self.pool.utf8(name),
+        }
+    }
+}
+
+impl Remapper<'_> {
+    fn class(&self, name: &str) -> String {
+        self.mappings.class(name)
+    }
+
+    fn signature(&self, signature: &str) -> String {
+        self.mappings.map_signature(signature)
+    }
+
+    /// Finds the first mapping of `owner` or one of its super types
+    fn inherited<'s>(
+        &'s self,
+        owner: &str,
+        find: impl Fn(&str) -> Option<&'s str>,
+    ) -> Option<&'s str> {
+        let mut queue = vec![owner.to_string()];
+        let mut seen = vec![];
+        while let Some(class) = queue.pop() {
+            if let Some(name) = find(&class) {
+                return Some(name);
+            }
+            if let Some(parents) = self.supers.get(&class) {
+                queue.extend(parents.iter().filter(|p| !seen.contains(*p)).cloned());
+            }
+            seen.push(class);
+        }
+        None
+    }
+
+    fn field<'s>(&'s self, owner: &str, name: &'s str) -> &'s str {
+        self.inherited(owner, |class| self.mappings.field(class, name))
+            .unwrap_or(name)
+    }
+
+    fn method<'s>(&'s self, owner: &str, name: &'s str, descriptor: &str) -> &'s str {
+        if name.starts_with('<') {
+            return name;
+        }
+        self.inherited(owner, |class| self.mappings.method(class, name, descriptor))
+            .unwrap_or(name)
+    }
+
+    fn remap(&self, file: &mut ty::ClassFile) -> Result<()> {
+        let old = file.clone();
+        let mut state = Remapping {
+            old: &old,
+            pool: ConstantPool::from(std::mem::take(&mut file.constant_pool)),
+        };
+        self.constants(&mut state)?;
+
+        let this = old.get_class_name();
+        for field in &mut file.fields {
+            let name = self.field(this, old.utf8(field.name)?);
+            field.name = state.renamed(field.name, name)?;
+            let descriptor = self.signature(old.utf8(field.descriptor)?);
+            field.descriptor = state.renamed(field.descriptor, &descriptor)?;
+            self.attributes(&mut state, &mut field.attributes)?;
+        }
+        for method in &mut file.methods {
+            let descriptor = old.utf8(method.descriptor)?;
+            let mut attributes = std::mem::take(&mut method.attributes);
+            self.parameters(&mut state, method, &mut attributes)?;
+            let name = self.method(this, old.utf8(method.name)?, descriptor);
+            let name = state.renamed(method.name, name)?;
+            let descriptor = state.renamed(method.descriptor, &self.signature(descriptor))?;
+            self.attributes(&mut state, &mut attributes)?;
+            *method = ty::Method::new(
+                method.flags,
+                name,
+                descriptor,
+                attributes,
+                state.pool.as_slice(),
+            )?;
+        }
+        self.attributes(&mut state, &mut file.attributes)?;
+
+        file.constant_pool = state.pool.into_vec();
+        Ok(())
+    }
+
+    /// Renames the constants that refer to classes and members. `this_class`, the
+    /// instructions and the `BootstrapMethods` arguments refer to these, so they follow
+    fn constants(&self, state: &mut Remapping<'_>) -> Result<()> {
+        let old = state.old;
+        for (at, constant) in old.constant_pool.iter().enumerate() {
+            let index = ConstantIndex(at as u16 + 1);
+            let renamed = match constant {
+                Constant::ClassRef(name) => {
+                    let renamed = state.renamed(*name, &self.class(old.utf8(*name)?))?;
+                    Constant::ClassRef(renamed)
+                }
+                Constant::MethodType(descriptor) => {
+                    let mapped = self.signature(old.utf8(*descriptor)?);
+                    Constant::MethodType(state.renamed(*descriptor, &mapped)?)
+                }
+                Constant::FieldRef(field) => {
+                    let member = old.member_ref(index)?;
+                    let name = self.field(member.class, member.name);
+                    let name_and_type = self.name_and_type(state, field.name_and_type, name)?;
+                    Constant::FieldRef(ty::constant::FieldRef {
+                        name_and_type,
+                        ..field.clone()
+                    })
+                }
+                Constant::MethodRef(method) => {
+                    let member = old.member_ref(index)?;
+                    let name = self.method(member.class, member.name, member.descriptor);
+                    let name_and_type = self.name_and_type(state, method.name_and_type, name)?;
+                    Constant::MethodRef(ty::constant::MethodRef {
+                        name_and_type,
+                        ..method.clone()
+                    })
+                }
+                Constant::InterfaceMethodRef(method) => {
+                    let member = old.member_ref(index)?;
+                    let name = self.method(member.class, member.name, member.descriptor);
+                    let name_and_type = self.name_and_type(state, method.name_and_type, name)?;
+                    Constant::InterfaceMethodRef(ty::constant::InterfaceMethodRef {
+                        name_and_type,
+                        ..method.clone()
+                    })
+                }
+                Constant::InvokeDynamicRef(dynamic) => {
+                    let name = self.lambda_name(old, dynamic)?;
+                    let name_and_type = self.name_and_type(state, dynamic.name_and_type, name)?;
+                    Constant::InvokeDynamicRef(ty::constant::InvokeDynamicRef {
+                        name_and_type,
+                        ..dynamic.clone()
+                    })
+                }
+                _ => continue,
+            };
+            if renamed != *constant {
+                state.pool.set(index, renamed);
+            }
+        }
+        Ok(())
+    }
+
+    /// The `NameAndTypeRef` at `index` with the new `name` and the descriptor remapped
+    fn name_and_type(
+        &self,
+        state: &mut Remapping<'_>,
+        index: ConstantIndex,
+        name: &str,
+    ) -> Result<ConstantIndex> {
+        let (old_name, descriptor) = state.old.name_and_type(index)?;
+        let descriptor = self.signature(descriptor);
+        if old_name == name && state.old.name_and_type(index)?.1 == descriptor {
+            return Ok(index);
+        }
+        state.pool.name_and_type(name, &descriptor)
+    }
+
+    /// The name of an `invokedynamic`. For a lambda, this is the name of the method it
+    /// implements, which is renamed like that method of the interface it returns
+    fn lambda_name<'s>(
+        &'s self,
+        old: &'s ty::ClassFile,
+        dynamic: &ty::constant::InvokeDynamicRef,
+    ) -> Result<&'s str> {
+        let (name, descriptor) = old.name_and_type(dynamic.name_and_type)?;
+        let bootstrap = old.attributes.iter().find_map(|attribute| match attribute {
+            Attribute::BootstrapMethods(methods) => {
+                methods.methods.get(usize::from(dynamic.bootstrap.0))
+            }
+            _ => None,
+        });
+        let bootstrap = match bootstrap {
+            Some(bootstrap) => bootstrap,
+            None => return Ok(name),
+        };
+        let factory = match bootstrap.method_ref.lookup(&old.constant_pool)? {
+            Constant::MethodHandleRef(handle) => old.member_ref(handle.reference())?,
+            _ => return Ok(name),
+        };
+        let interface = descriptor
+            .rsplit(')')
+            .next()
+            .and_then(|ret| ret.strip_prefix('L'))
+            .and_then(|ret| ret.strip_suffix(';'));
+        let method_type = bootstrap
+            .arguments
+            .first()
+            .map(|&argument| argument.lookup(&old.constant_pool));
+        match (factory.class, interface, method_type) {
+            (
+                "java/lang/invoke/LambdaMetafactory",
+                Some(interface),
+                Some(Ok(Constant::MethodType(implemented))),
+            ) => Ok(self.method(interface, name, old.utf8(*implemented)?)),
+            _ => Ok(name),
+        }
+    }
+
+    /// Renames the parameters in the `MethodParameters` of `method`, which are mapped by
+    /// their local variable index
+    fn parameters(
+        &self,
+        state: &mut Remapping<'_>,
+        method: &ty::Method,
+        attributes: &mut [Attribute],
+    ) -> Result<()> {
+        let old = state.old;
+        let (this, name) = (old.get_class_name(), old.utf8(method.name)?);
+        let descriptor = old.utf8(method.descriptor)?;
+        let mut slot = if method.flags.contains(ty::MethodFlags::STATIC) {
+            0
+        } else {
+            1
+        };
+        let mut slots = vec![];
+        for param in &ty::MethodDescriptor::parse(descriptor)?.params {
+            slots.push(slot);
+            slot += param.slots() as u16;
+        }
+
+        for attribute in attributes {
+            if let Attribute::MethodParameters(parameters) = attribute {
+                let named = parameters.parameters.iter_mut().zip(&slots);
+                for (parameter, &slot) in named {
+                    if let Some(to) = self.mappings.parameter(this, name, descriptor, slot) {
+                        parameter.name = match parameter.name {
+                            ConstantIndex(0) => state.utf8(to)?,
+                            index => state.renamed(index, to)?,
+                        };
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn attributes(&self, state: &mut Remapping<'_>, attributes: &mut [Attribute]) -> Result<()> {
+        let old = state.old;
+        for attribute in attributes {
+            match attribute {
+                Attribute::Code(code) => self.attributes(state, &mut code.attributes)?,
+                Attribute::Signature(signature) => {
+                    let mapped = self.signature(old.utf8(signature.signature)?);
+                    signature.signature = state.renamed(signature.signature, &mapped)?;
+                }
+                Attribute::InnerClasses(classes) => {
+                    for class in &mut classes.classes {
+                        if class.inner_class_name.0 == 0 || class.inner_class.0 == 0 {
+                            continue;
+                        }
+                        let inner = old.class_name(class.inner_class)?;
+                        let renamed = self.class(inner);
+                        if renamed == inner {
+                            continue;
+                        }
+                        let outer = match class.outer_class.0 {
+                            0 => None,
+                            _ => Some(self.class(old.class_name(class.outer_class)?)),
+                        };
+                        let simple = outer
+                            .and_then(|outer| {
+                                renamed
+                                    .strip_prefix(&format!("{}$", outer))
+                                    .map(String::from)
+                            })
+                            .unwrap_or_else(|| {
+                                renamed
+                                    .rsplit(['$', '/'])
+                                    .next()
+                                    .unwrap_or_default()
+                                    .to_string()
+                            });
+                        class.inner_class_name = state.renamed(class.inner_class_name, &simple)?;
+                    }
+                }
+                // a class in an initializer has no method
+                Attribute::EnclosingMethod(enclosing) if enclosing.method.0 != 0 => {
+                    let class = old.class_name(enclosing.class)?;
+                    let (name, descriptor) = old.name_and_type(enclosing.method)?;
+                    let name = self.method(class, name, descriptor);
+                    enclosing.method = self.name_and_type(state, enclosing.method, name)?;
+                }
+                Attribute::LocalVariableTable(table) => {
+                    for variable in &mut table.variables {
+                        let mapped = self.signature(old.utf8(variable.descriptor)?);
+                        variable.descriptor = state.renamed(variable.descriptor, &mapped)?;
+                    }
+                }
+                Attribute::LocalVariableTypeTable(table) => {
+                    for variable in &mut table.variables_types {
+                        let mapped = self.signature(old.utf8(variable.signature)?);
+                        variable.signature = state.renamed(variable.signature, &mapped)?;
+                    }
+                }
+                Attribute::RuntimeVisibleAnnotations(attr::RuntimeVisibleAnnotations {
+                    annotations,
+                    ..
+                })
+                | Attribute::RuntimeInvisibleAnnotations(attr::RuntimeInvisibleAnnotations {
+                    annotations,
+                    ..
+                }) => {
+                    for annotation in annotations {
+                        self.annotation(state, annotation)?;
+                    }
+                }
+                Attribute::RuntimeVisibleParameterAnnotations(
+                    attr::RuntimeVisibleParameterAnnotations {
+                        annotations_by_param_index,
+                        ..
+                    },
+                )
+                | Attribute::RuntimeInvisibleParameterAnnotations(
+                    attr::RuntimeInvisibleParameterAnnotations {
+                        annotations_by_param_index,
+                        ..
+                    },
+                ) => {
+                    for parameter in annotations_by_param_index {
+                        for annotation in &mut parameter.0 {
+                            self.annotation(state, annotation)?;
+                        }
+                    }
+                }
+                Attribute::RuntimeVisibleTypeAnnotations(attr::RuntimeVisibleTypeAnnotations {
+                    annotations,
+                    ..
+                })
+                | Attribute::RuntimeInvisibleTypeAnnotations(
+                    attr::RuntimeInvisibleTypeAnnotations { annotations, .. },
+                ) => {
+                    for annotation in annotations {
+                        self.annotation(state, &mut annotation.annotation)?;
+                    }
+                }
+                Attribute::AnnotationDefault(default) => {
+                    self.element_value(state, &mut default.value)?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn annotation(&self, state: &mut Remapping<'_>, annotation: &mut Annotation) -> Result<()> {
+        let old = state.old;
+        let descriptor = old.utf8(annotation.type_index)?;
+        let class = descriptor
+            .strip_prefix('L')
+            .and_then(|class| class.strip_suffix(';'))
+            .unwrap_or(descriptor);
+        for (name, value) in &mut annotation.indices_with_values {
+            let element = old.utf8(*name)?;
+            let renamed = self
+                .inherited(class, |class| self.mappings.element(class, element))
+                .unwrap_or(element);
+            *name = state.renamed(*name, renamed)?;
+            self.element_value(state, value)?;
+        }
+        let mapped = self.signature(descriptor);
+        annotation.type_index = state.renamed(annotation.type_index, &mapped)?;
+        Ok(())
+    }
+
+    fn element_value(&self, state: &mut Remapping<'_>, value: &mut ElementValue) -> Result<()> {
+        let old = state.old;
+        match value {
+            ElementValue::Enum { ty, val } => {
+                let descriptor = old.utf8(*ty)?;
+                if let Some(class) = descriptor
+                    .strip_prefix('L')
+                    .and_then(|c| c.strip_suffix(';'))
+                {
+                    let name = self.field(class, old.utf8(*val)?);
+                    *val = state.renamed(*val, name)?;
+                }
+                *ty = state.renamed(*ty, &self.signature(descriptor))?;
+            }
+            ElementValue::Class(index) => {
+                let mapped = self.signature(old.utf8(*index)?);
+                *index = state.renamed(*index, &mapped)?;
+            }
+            ElementValue::Anotation(annotation) => self.annotation(state, annotation)?,
+            ElementValue::Array(values) => {
+                for value in values {
+                    self.element_value(state, value)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exec::interpreter::Interpreter;
+    use std::rc::Rc;
+
+    fn read_all() -> Vec<ty::ClassFile> {
+        let mut files = vec![];
+        for name in &["Counter", "Counter$Step", "Counter$1", "Tag", "Level"] {
+            let data = std::fs::read(format!("./etc/remap/{}.class", name)).unwrap();
+            files.push(ty::ClassFile::read(&mut data.as_slice()).unwrap());
+        }
+        files
+    }
+
+    /// Writes the class file and reads it back
+    fn rewrite(file: &ty::ClassFile) -> ty::ClassFile {
+        let mut data = vec![];
+        file.write(&mut data).unwrap();
+        ty::ClassFile::read(&mut data.as_slice()).unwrap()
+    }
+
+    const MAPPING: &str = "\
+Counter -> a:
+    int count -> a
+    java.util.List children -> b
+    int increment(Counter$Step) -> c
+    java.util.function.IntSupplier supplier() -> d
+    java.lang.Object anonymous() -> e
+    int count(int,int) -> f
+Counter$Step -> a$a:
+    int size -> a
+Counter$1 -> a$b:
+Tag -> b:
+    java.lang.String value() -> a
+    Level level() -> b
+Level -> c:
+    Level HIGH -> a
+";
+
+    #[test]
+    fn proguard() {
+        let mut files = read_all();
+        let mappings = Mappings::parse(MAPPING).unwrap();
+        remap(&mut files, &mappings).unwrap();
+        let files = files.iter().map(rewrite).collect::<Vec<_>>();
+
+        let names = files
+            .iter()
+            .map(|file| file.get_class_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "a$a", "a$b", "b", "c"]);
+
+        let counter = &files[0];
+        let count = &counter.fields[0];
+        assert_eq!(counter.utf8(count.name).unwrap(), "a");
+        assert_eq!(counter.utf8(count.descriptor).unwrap(), "I");
+        let methods = counter.methods.iter().map(|m| m.name()).collect::<Vec<_>>();
+        assert_eq!(
+            methods,
+            [
+                "<init>",
+                "c",
+                "step",
+                "d",
+                "e",
+                "f",
+                "supplied",
+                "lambda$supplier$0",
+                "access$000"
+            ]
+        );
+        let signatures = counter
+            .constant_pool
+            .iter()
+            .filter_map(|constant| match constant {
+                Constant::Utf8(s) if s.contains('<') && s.contains(';') => Some(s.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            signatures.contains(&"<T:La<TT;>;>Ljava/lang/Object;"),
+            "{:?}",
+            signatures
+        );
+        assert!(signatures.contains(&"(La<TT;>.a;)I"), "{:?}", signatures);
+
+        // the annotation on the class, with its enum and class elements
+        let annotation = counter
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::RuntimeVisibleAnnotations(annotations) => {
+                    Some(&annotations.annotations[0])
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(counter.utf8(annotation.type_index).unwrap(), "Lb;");
+        let elements = annotation
+            .indices_with_values
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    ElementValue::Enum { ty, val } => {
+                        format!(
+                            "{}.{}",
+                            counter.utf8(*ty).unwrap(),
+                            counter.utf8(*val).unwrap()
+                        )
+                    }
+                    ElementValue::Class(class) => counter.utf8(*class).unwrap().to_string(),
+                    ElementValue::String(s) => counter.utf8(*s).unwrap().to_string(),
+                    value => panic!("{:?}", value),
+                };
+                (counter.utf8(*name).unwrap(), value)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            elements,
+            [
+                ("a", "counter".to_string()),
+                ("b", "Lc;.a".to_string()),
+                ("type", "La;".to_string())
+            ]
+        );
+
+        // the inner classes and the method the anonymous class is in
+        let anonymous = &files[2];
+        let enclosing = anonymous
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::EnclosingMethod(enclosing) => Some(enclosing),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(anonymous.class_name(enclosing.class).unwrap(), "a");
+        let (name, descriptor) = anonymous.name_and_type(enclosing.method).unwrap();
+        assert_eq!((name, descriptor), ("e", "()Ljava/lang/Object;"));
+        let step = &files[1];
+        let inner = step
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::InnerClasses(classes) => Some(&classes.classes[0]),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(step.class_name(inner.inner_class).unwrap(), "a$a");
+        assert_eq!(step.utf8(inner.inner_class_name).unwrap(), "a");
+
+        let mut interpreter = Interpreter::default();
+        for file in files {
+            interpreter.load_class(Rc::new(file));
+        }
+        let last: i32 = interpreter.invoke("a", "f", (3, 10)).unwrap();
+        assert_eq!(last, 16);
+    }
+
+    #[test]
+    fn kept_members() {
+        let mut files = read_all();
+        let data = std::fs::read("./etc/remap/Tally.class").unwrap();
+        files.push(ty::ClassFile::read(&mut data.as_slice()).unwrap());
+        let mapping = format!(
+            "{}Tally -> Tally:\n    int count -> count\n    int count(int,int) -> count\n",
+            MAPPING
+        );
+        remap(&mut files, &Mappings::parse(&mapping).unwrap()).unwrap();
+        let files = files.iter().map(rewrite).collect::<Vec<_>>();
+
+        // the super class's `count` members are renamed, but `Tally` keeps its own
+        let tally = &files[5];
+        assert_eq!(tally.utf8(tally.fields[0].name).unwrap(), "count");
+        let methods = tally.methods.iter().map(|m| m.name()).collect::<Vec<_>>();
+        assert_eq!(methods, ["<init>", "count"]);
+
+        let mut interpreter = Interpreter::default();
+        for file in files {
+            interpreter.load_class(Rc::new(file));
+        }
+        let count: i32 = interpreter.invoke("Tally", "count", (2, 10)).unwrap();
+        assert_eq!(count, 16);
+    }
+
+    #[test]
+    fn reversed() {
+        let mappings = Mappings::parse(MAPPING).unwrap();
+        let mut files = read_all();
+        remap(&mut files, &mappings).unwrap();
+        remap(&mut files, &mappings.reverse()).unwrap();
+
+        let originals = read_all();
+        for (file, original) in files.iter().map(rewrite).zip(&originals) {
+            assert_eq!(file.get_class_name(), original.get_class_name());
+            let methods = |file: &ty::ClassFile| {
+                file.methods
+                    .iter()
+                    .map(|m| {
+                        (
+                            m.name().to_string(),
+                            file.utf8(m.descriptor).unwrap().to_string(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(methods(&file), methods(original));
+            for (constant, original) in file.constant_pool.iter().zip(&original.constant_pool) {
+                if let Constant::ClassRef(..) = original {
+                    assert_eq!(constant, original);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parameters() {
+        let tiny = "\
+tiny\t2\t0\tnamed\tofficial
+c\tCounter\ta
+\tm\t(II)I\tcount\tf
+\t\tp\t0\ttimes\tn
+\t\tp\t1\tstart\tfrom
+c\tChecked\td
+";
+        let mut files = read_all();
+        remap(&mut files, &Mappings::parse(tiny).unwrap()).unwrap();
+        let counter = rewrite(&files[0]);
+
+        let count = counter.methods.iter().find(|m| m.name() == "f").unwrap();
+        let parameters = count
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::MethodParameters(parameters) => Some(&parameters.parameters),
+                _ => None,
+            });
+        let names = parameters
+            .unwrap()
+            .iter()
+            .map(|parameter| counter.utf8(parameter.name).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["n", "from"]);
+
+        // `List<@Checked Counter<T>> children`
+        let children = &counter.fields[1];
+        let annotated = children
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::RuntimeVisibleTypeAnnotations(annotations) => {
+                    Some(&annotations.annotations[0])
+                }
+                _ => None,
+            });
+        let annotated = annotated.unwrap();
+        assert_eq!(annotated.target, attr::TypeAnnotationTarget::Empty);
+        let descriptor = counter.utf8(annotated.annotation.type_index).unwrap();
+        assert_eq!(descriptor, "Ld;");
+    }
+}
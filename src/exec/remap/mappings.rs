@@ -0,0 +1,489 @@
+//! Reading mappings from ProGuard, Tiny and SRG files
+use super::signature::map_signature;
+use super::*;
+
+use std::collections::HashMap;
+
+/// What classes, fields and methods are renamed to. Classes use internal names, like
+/// `java/lang/Object`, and the descriptors of methods use the names from before renaming
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Mappings {
+    classes: HashMap<String, String>,
+    /// By owner and name
+    fields: HashMap<(String, String), String>,
+    /// By owner, name and descriptor
+    methods: HashMap<(String, String, String), String>,
+    /// By the owner, name and descriptor of the method, and the local variable index
+    parameters: HashMap<(String, String, String, u16), String>,
+    /// Package prefixes that are moved as a whole, like `com/google/` to `shaded/com/google/`
+    packages: Vec<(String, String)>,
+}
+
+impl Mappings {
+    /// Reads mappings in whichever format `text` is in. Tiny files map from their first
+    /// namespace to their second one
+    pub fn parse(text: &str) -> Result<Self> {
+        let first = text
+            .lines()
+            .find(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .unwrap_or_default();
+        if first.starts_with("v1\t") || first.starts_with("tiny\t") {
+            let namespaces = first
+                .split('\t')
+                .skip(if first.starts_with("v1") { 1 } else { 3 });
+            let namespaces = namespaces.take(2).collect::<Vec<_>>();
+            match &namespaces[..] {
+                [from, to] => Self::tiny(text, from, to),
+                _ => generic_error!("the tiny header needs two namespaces: {}", first),
+            }
+        } else if ["PK: ", "CL: ", "FD: ", "MD: "]
+            .iter()
+            .any(|prefix| first.starts_with(prefix))
+        {
+            Self::srg(text)
+        } else if first.contains(" -> ") {
+            Self::proguard(text)
+        } else {
+            generic_error!("unknown mapping format, starting with: {}", first)
+        }
+    }
+
+    /// Reads a ProGuard `mapping.txt`, which maps the original names to the obfuscated
+    /// ones. Use `reverse` to go from the obfuscated names back to the original ones
+    pub fn proguard(text: &str) -> Result<Self> {
+        let mut mappings = Self::default();
+        let mut class = None;
+        for (number, line) in text.lines().enumerate() {
+            let fail = || format!("line {}: invalid ProGuard mapping: {}", number + 1, line);
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let (from, to) = match line.trim().split_once(" -> ") {
+                Some(split) => split,
+                None => generic_error!(fail()),
+            };
+
+            if !line.starts_with(char::is_whitespace) {
+                let (from, to) = match to.strip_suffix(':') {
+                    Some(to) => (from.replace('.', "/"), to.replace('.', "/")),
+                    None => generic_error!(fail()),
+                };
+                mappings.add_class(&from, &to);
+                class = Some(from);
+                continue;
+            }
+            let owner = match &class {
+                Some(owner) => owner.clone(),
+                None => generic_error!(fail()),
+            };
+
+            // `1:3:int increment(Counter$Step):10:12 -> a`, where the line numbers are optional
+            let from = from.trim_start_matches(|c: char| c.is_ascii_digit() || c == ':');
+            let (ty, member) = match from.split_once(' ') {
+                Some(split) => split,
+                None => generic_error!(fail()),
+            };
+            match member.split_once('(') {
+                Some((name, rest)) => {
+                    // methods inlined from other classes are named with their class
+                    if name.contains('.') {
+                        continue;
+                    }
+                    let params = match rest.split_once(')') {
+                        Some((params, _)) => params,
+                        None => generic_error!(fail()),
+                    };
+                    let mut descriptor = String::from("(");
+                    for param in params.split(',').filter(|param| !param.is_empty()) {
+                        descriptor.push_str(&java_type(param));
+                    }
+                    descriptor.push(')');
+                    descriptor.push_str(&java_type(ty));
+                    mappings.add_method(&owner, name, &descriptor, to);
+                }
+                None => mappings.add_field(&owner, member, to),
+            }
+        }
+        Ok(mappings)
+    }
+
+    /// Reads a Tiny v1 or v2 file, mapping the names of namespace `from` to those of `to`
+    pub fn tiny(text: &str, from: &str, to: &str) -> Result<Self> {
+        let mut lines = text.lines().enumerate();
+        let header = match lines.next() {
+            Some((_, header)) => header.split('\t').collect::<Vec<_>>(),
+            None => generic_error!("missing tiny header"),
+        };
+        let (v2, namespaces) = match header.first() {
+            Some(&"v1") => (false, &header[1..]),
+            Some(&"tiny") if header.get(1) == Some(&"2") => (true, &header[3.min(header.len())..]),
+            _ => generic_error!("invalid tiny header: {}", header.join(" ")),
+        };
+        let column = |namespace: &str| -> Result<usize> {
+            match namespaces.iter().position(|&n| n == namespace) {
+                Some(column) => Ok(column),
+                None => generic_error!("the tiny file has no {} namespace", namespace),
+            }
+        };
+        let (from, to) = (column(from)?, column(to)?);
+
+        // the descriptors are written with the names of the first namespace
+        struct Member<'a> {
+            owner: &'a str,
+            descriptor: &'a str,
+            names: Vec<&'a str>,
+            method: bool,
+            /// The local variable index and names of each parameter of a method
+            parameters: Vec<(u16, Vec<&'a str>)>,
+        }
+        let mut classes = vec![];
+        let mut members = vec![];
+        let mut owner = None;
+        for (number, line) in lines {
+            let fail = || format!("line {}: invalid tiny mapping: {}", number + 1, line);
+            let parts = line.split('\t').collect::<Vec<_>>();
+            match (v2, &parts[..]) {
+                (_, [""]) | (_, []) => {}
+                (false, ["CLASS", names @ ..]) | (true, ["c", names @ ..]) => {
+                    owner = names.first().copied();
+                    classes.push(names.to_vec());
+                }
+                (false, [kind @ "FIELD", owner, descriptor, names @ ..])
+                | (false, [kind @ "METHOD", owner, descriptor, names @ ..]) => {
+                    members.push(Member {
+                        owner,
+                        descriptor,
+                        names: names.to_vec(),
+                        method: *kind == "METHOD",
+                        parameters: vec![],
+                    })
+                }
+                (true, ["", kind @ "f", descriptor, names @ ..])
+                | (true, ["", kind @ "m", descriptor, names @ ..]) => {
+                    let owner = match owner {
+                        Some(owner) => owner,
+                        None => generic_error!(fail()),
+                    };
+                    members.push(Member {
+                        owner,
+                        descriptor,
+                        names: names.to_vec(),
+                        method: *kind == "m",
+                        parameters: vec![],
+                    })
+                }
+                (true, ["", "", "p", index, names @ ..]) => {
+                    let index = match index.parse() {
+                        Ok(index) => index,
+                        Err(..) => generic_error!(fail()),
+                    };
+                    match members.last_mut() {
+                        Some(member) if member.method => {
+                            member.parameters.push((index, names.to_vec()))
+                        }
+                        _ => generic_error!(fail()),
+                    }
+                }
+                // comments and local variables
+                (true, ["", "c", ..]) | (true, ["", "", ..]) => {}
+                (false, ["#", ..]) => {}
+                _ => generic_error!(fail()),
+            }
+        }
+
+        // an empty name is the same as in the first namespace
+        let name = |names: &[&'_ str], column: usize| -> Option<String> {
+            match names.get(column).copied() {
+                Some("") | None => names.first().map(|name| name.to_string()),
+                Some(name) => Some(name.to_string()),
+            }
+        };
+        let (mut first_to_from, mut first_to_to) = (Self::default(), Self::default());
+        for names in &classes {
+            let first = names.first().copied().unwrap_or_default();
+            first_to_from.add_class(first, &name(names, from).unwrap_or_default());
+            first_to_to.add_class(first, &name(names, to).unwrap_or_default());
+        }
+
+        let mut mappings = Self::default();
+        for names in &classes {
+            let first = names.first().copied().unwrap_or_default();
+            mappings.add_class(&first_to_from.class(first), &first_to_to.class(first));
+        }
+        let column = to;
+        for member in &members {
+            let (from, to) = match (name(&member.names, from), name(&member.names, to)) {
+                (Some(from), Some(to)) => (from, to),
+                _ => continue,
+            };
+            let owner = first_to_from.class(member.owner);
+            if member.method {
+                let descriptor = first_to_from.map_signature(member.descriptor);
+                mappings.add_method(&owner, &from, &descriptor, &to);
+                for (index, names) in &member.parameters {
+                    if let Some(to) = names.get(column).filter(|name| !name.is_empty()) {
+                        mappings.add_parameter(&owner, &from, &descriptor, *index, to);
+                    }
+                }
+            } else {
+                mappings.add_field(&owner, &from, &to);
+            }
+        }
+        Ok(mappings)
+    }
+
+    /// Reads an SRG file, with `CL:`, `FD:` and `MD:` lines
+    pub fn srg(text: &str) -> Result<Self> {
+        let mut mappings = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let fail = || format!("line {}: invalid SRG mapping: {}", number + 1, line);
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            // members are written as `owner/name`
+            let member = |path: &str| path.contains('/');
+            match &parts[..] {
+                [] | ["PK:", ..] => {}
+                ["CL:", from, to] => mappings.add_class(from, to),
+                ["FD:", from, to] if member(from) && member(to) => {
+                    let (owner, name) = from.rsplit_once('/').unwrap_or_default();
+                    let (_, to) = to.rsplit_once('/').unwrap_or_default();
+                    mappings.add_field(owner, name, to);
+                }
+                ["MD:", from, descriptor, to, _] if member(from) && member(to) => {
+                    let (owner, name) = from.rsplit_once('/').unwrap_or_default();
+                    let (_, to) = to.rsplit_once('/').unwrap_or_default();
+                    mappings.add_method(owner, name, descriptor, to);
+                }
+                _ => generic_error!(fail()),
+            }
+        }
+        Ok(mappings)
+    }
+
+    pub fn add_class(&mut self, from: &str, to: &str) {
+        if from != to {
+            self.classes.insert(from.to_string(), to.to_string());
+        }
+    }
+
+    /// Members that keep their name are mapped too, so that they aren't renamed after a
+    /// member of a super type with the same name
+    pub fn add_field(&mut self, owner: &str, name: &str, to: &str) {
+        let key = (owner.to_string(), name.to_string());
+        self.fields.insert(key, to.to_string());
+    }
+
+    pub fn add_method(&mut self, owner: &str, name: &str, descriptor: &str, to: &str) {
+        let key = (owner.to_string(), name.to_string(), descriptor.to_string());
+        self.methods.insert(key, to.to_string());
+    }
+
+    /// Names the parameter of a method at local variable `index`
+    pub fn add_parameter(
+        &mut self,
+        owner: &str,
+        method: &str,
+        descriptor: &str,
+        index: u16,
+        to: &str,
+    ) {
+        let key = (
+            owner.to_string(),
+            method.to_string(),
+            descriptor.to_string(),
+            index,
+        );
+        self.parameters.insert(key, to.to_string());
+    }
+
+    /// Moves every class in the package `from`, and the packages in it, to `to`. This
+    /// is how dependencies are shaded, and is only used for classes that aren't mapped
+    pub fn relocate(&mut self, from: &str, to: &str) {
+        let package = |name: &str| format!("{}/", name.trim_end_matches('/'));
+        self.packages.push((package(from), package(to)));
+    }
+
+    /// What the class `name`, or an array of classes, is renamed to. An inner class that
+    /// isn't mapped moves along with its outer class
+    pub fn class(&self, name: &str) -> String {
+        if name.starts_with('[') {
+            return self.map_signature(name);
+        }
+        if let Some(to) = self.classes.get(name) {
+            return to.clone();
+        }
+        if let Some((outer, inner)) = name.rsplit_once('$') {
+            let outer_to = self.class(outer);
+            if outer_to != outer {
+                return format!("{}${}", outer_to, inner);
+            }
+        }
+        for (from, to) in &self.packages {
+            if let Some(rest) = name.strip_prefix(from.as_str()) {
+                return format!("{}{}", to, rest);
+            }
+        }
+        name.to_string()
+    }
+
+    pub fn field(&self, owner: &str, name: &str) -> Option<&str> {
+        let key = (owner.to_string(), name.to_string());
+        self.fields.get(&key).map(String::as_str)
+    }
+
+    pub fn method(&self, owner: &str, name: &str, descriptor: &str) -> Option<&str> {
+        let key = (owner.to_string(), name.to_string(), descriptor.to_string());
+        self.methods.get(&key).map(String::as_str)
+    }
+
+    /// The name of the parameter of a method at local variable `index`
+    pub fn parameter(
+        &self,
+        owner: &str,
+        method: &str,
+        descriptor: &str,
+        index: u16,
+    ) -> Option<&str> {
+        let key = (
+            owner.to_string(),
+            method.to_string(),
+            descriptor.to_string(),
+            index,
+        );
+        self.parameters.get(&key).map(String::as_str)
+    }
+
+    /// An element of the annotation `owner`, which is a method without parameters
+    pub(super) fn element(&self, owner: &str, name: &str) -> Option<&str> {
+        self.methods
+            .iter()
+            .find(|((o, n, descriptor), _)| o == owner && n == name && descriptor.starts_with("()"))
+            .map(|(_, to)| to.as_str())
+    }
+
+    /// Renames the classes in a descriptor or a generic signature
+    pub fn map_signature(&self, signature: &str) -> String {
+        map_signature(signature, &|name| self.class(name))
+    }
+
+    /// The mappings that undo these ones. Parameters aren't renamed back, as the names
+    /// they had aren't known
+    pub fn reverse(&self) -> Self {
+        let mut reversed = Self::default();
+        for (from, to) in &self.classes {
+            reversed.add_class(to, from);
+        }
+        for ((owner, name), to) in &self.fields {
+            reversed.add_field(&self.class(owner), to, name);
+        }
+        for ((owner, name, descriptor), to) in &self.methods {
+            let descriptor = self.map_signature(descriptor);
+            reversed.add_method(&self.class(owner), to, &descriptor, name);
+        }
+        for (from, to) in &self.packages {
+            reversed.packages.push((to.clone(), from.clone()));
+        }
+        reversed
+    }
+}
+
+/// The descriptor of a type as ProGuard writes it, like `int` or `java.lang.String[]`
+fn java_type(ty: &str) -> String {
+    let ty = ty.trim();
+    let (element, dimensions) = match ty.find('[') {
+        Some(at) => (&ty[..at], ty[at..].matches("[]").count()),
+        None => (ty, 0),
+    };
+    let element = match element {
+        "boolean" => "Z".to_string(),
+        "byte" => "B".to_string(),
+        "char" => "C".to_string(),
+        "short" => "S".to_string(),
+        "int" => "I".to_string(),
+        "long" => "J".to_string(),
+        "float" => "F".to_string(),
+        "double" => "D".to_string(),
+        "void" => "V".to_string(),
+        class => format!("L{};", class.replace('.', "/")),
+    };
+    "[".repeat(dimensions) + &element
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let proguard = "\
+# compiler: R8
+com.example.Counter -> a:
+    int count -> a
+    java.util.List children -> b
+    3:5:int increment(com.example.Counter$Step):31:33 -> a
+    void run(int[],java.lang.String) -> b
+    1:1:void com.example.Other.inlined():10:10 -> c
+com.example.Counter$Step -> a$a:
+";
+        let tiny_v1 = "\
+v1\tnamed\tofficial
+CLASS\tcom/example/Counter\ta
+CLASS\tcom/example/Counter$Step\ta$a
+FIELD\tcom/example/Counter\tI\tcount\ta
+FIELD\tcom/example/Counter\tLjava/util/List;\tchildren\tb
+METHOD\tcom/example/Counter\t(Lcom/example/Counter$Step;)I\tincrement\ta
+METHOD\tcom/example/Counter\t([ILjava/lang/String;)V\trun\tb
+";
+        let tiny_v2 = "\
+tiny\t2\t0\tofficial\tnamed
+c\ta\tcom/example/Counter
+\tf\tI\ta\tcount
+\tf\tLjava/util/List;\tb\tchildren
+\tm\t(La$a;)I\ta\tincrement
+\t\tp\t1\t\tstep
+\tm\t([ILjava/lang/String;)V\tb\trun
+\tc\ta comment
+c\ta$a\tcom/example/Counter$Step
+";
+        let srg = "\
+PK: com/example com/example
+CL: com/example/Counter a
+CL: com/example/Counter$Step a$a
+FD: com/example/Counter/count a/a
+FD: com/example/Counter/children a/b
+MD: com/example/Counter/increment (Lcom/example/Counter$Step;)I a/a (La$a;)I
+MD: com/example/Counter/run ([ILjava/lang/String;)V a/b ([ILjava/lang/String;)V
+";
+        let proguard = Mappings::parse(proguard).unwrap();
+        assert_eq!(Mappings::parse(tiny_v1).unwrap(), proguard);
+        assert_eq!(Mappings::parse(tiny_v2).unwrap().reverse(), proguard);
+        assert_eq!(Mappings::parse(srg).unwrap(), proguard);
+        let named = Mappings::parse(tiny_v2).unwrap();
+        assert_eq!(named.parameter("a", "a", "(La$a;)I", 1), Some("step"));
+
+        assert_eq!(proguard.class("com/example/Counter$Step"), "a$a");
+        assert_eq!(proguard.class("[Lcom/example/Counter;"), "[La;");
+        let increment = "(Lcom/example/Counter$Step;)I";
+        assert_eq!(
+            proguard.method("com/example/Counter", "increment", increment),
+            Some("a")
+        );
+
+        let reversed = proguard.reverse();
+        assert_eq!(reversed.class("a$a"), "com/example/Counter$Step");
+        assert_eq!(reversed.field("a", "b"), Some("children"));
+        assert_eq!(reversed.method("a", "a", "(La$a;)I"), Some("increment"));
+    }
+
+    #[test]
+    fn relocation() {
+        let mut mappings = Mappings::default();
+        mappings.relocate("com/google", "shaded/com/google");
+        mappings.add_class("com/google/Special", "Special");
+        assert_eq!(
+            mappings.class("com/google/common/List"),
+            "shaded/com/google/common/List"
+        );
+        assert_eq!(mappings.class("com/google/Special$Inner"), "Special$Inner");
+        assert_eq!(mappings.class("com/googlex/List"), "com/googlex/List");
+    }
+}
@@ -0,0 +1,154 @@
+//! Renaming the classes in descriptors and generic signatures
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.9.1
+
+/// Renames every class in `signature` with `class`. A descriptor is a signature without
+/// type parameters or type arguments, so this works for both. A signature that can't be
+/// parsed is left as it is
+pub fn map_signature(signature: &str, class: &dyn Fn(&str) -> String) -> String {
+    let mut mapper = Mapper {
+        rest: signature,
+        out: String::with_capacity(signature.len()),
+        class,
+    };
+    match mapper.signature() {
+        Some(()) => mapper.out,
+        None => signature.to_string(),
+    }
+}
+
+struct Mapper<'a> {
+    rest: &'a str,
+    out: String,
+    class: &'a dyn Fn(&str) -> String,
+}
+
+impl<'a> Mapper<'a> {
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    /// Copies the next character to the output
+    fn copy(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        self.out.push(c);
+        Some(c)
+    }
+
+    /// Takes the characters up to one of `ends`, without copying them
+    fn take_until(&mut self, ends: &[char]) -> Option<&'a str> {
+        let end = self.rest.find(|c| ends.contains(&c))?;
+        let (taken, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(taken)
+    }
+
+    /// A class, method or field signature
+    fn signature(&mut self) -> Option<()> {
+        if self.peek() == Some('<') {
+            self.type_parameters()?;
+        }
+        while let Some(c) = self.peek() {
+            match c {
+                '(' | ')' | '^' => {
+                    self.copy();
+                }
+                _ => self.ty()?,
+            }
+        }
+        Some(())
+    }
+
+    /// `<T:bound:interface...U::interface>`, where the class bound can be left out
+    fn type_parameters(&mut self) -> Option<()> {
+        self.copy();
+        while self.peek()? != '>' {
+            let name = self.take_until(&[':'])?;
+            self.out.push_str(name);
+            while self.peek()? == ':' {
+                self.copy();
+                if self.peek()? != ':' {
+                    self.ty()?;
+                }
+            }
+        }
+        self.copy();
+        Some(())
+    }
+
+    fn ty(&mut self) -> Option<()> {
+        match self.copy()? {
+            'B' | 'C' | 'D' | 'F' | 'I' | 'J' | 'S' | 'Z' | 'V' | '*' => Some(()),
+            '[' | '+' | '-' => self.ty(),
+            'T' => {
+                let name = self.take_until(&[';'])?;
+                self.out.push_str(name);
+                self.copy().map(drop)
+            }
+            'L' => self.class_type(),
+            _ => None,
+        }
+    }
+
+    /// The rest of `Lpkg/Outer<args>.Inner<args>;`, where `Inner` is the simple name of
+    /// `pkg/Outer$Inner`
+    fn class_type(&mut self) -> Option<()> {
+        let mut name = self.take_until(&['<', '.', ';'])?.to_string();
+        let mut mapped = (self.class)(&name);
+        self.out.push_str(&mapped);
+        loop {
+            if self.peek()? == '<' {
+                self.copy();
+                while self.peek()? != '>' {
+                    self.ty()?;
+                }
+                self.copy();
+            }
+            match self.copy()? {
+                ';' => return Some(()),
+                '.' => {
+                    let simple = self.take_until(&['<', '.', ';'])?;
+                    name = format!("{}${}", name, simple);
+                    let inner = (self.class)(&name);
+                    let prefix = format!("{}$", mapped);
+                    let simple = match inner.strip_prefix(&prefix) {
+                        Some(simple) => simple,
+                        None => inner.rsplit(['$', '/']).next()?,
+                    };
+                    self.out.push_str(simple);
+                    mapped = inner;
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures() {
+        let class = |name: &str| match name {
+            "Counter" => "a".to_string(),
+            "Counter$Step" => "a$b".to_string(),
+            "java/util/List" => "java/util/List".to_string(),
+            name => format!("x/{}", name),
+        };
+        let map = |signature: &str| map_signature(signature, &class);
+
+        assert_eq!(map("(ILCounter;[[LCounter$Step;)V"), "(ILa;[[La$b;)V");
+        assert_eq!(
+            map("<T:LCounter<TT;>;>Ljava/lang/Object;"),
+            "<T:La<TT;>;>Lx/java/lang/Object;"
+        );
+        assert_eq!(map("(LCounter<TT;>.Step;)I"), "(La<TT;>.b;)I");
+        assert_eq!(
+            map("Ljava/util/List<+LCounter<*>;>;"),
+            "Ljava/util/List<+La<*>;>;"
+        );
+        assert_eq!(map("<T::LCounter;U:TT;>()V^TU;"), "<T::La;U:TT;>()V^TU;");
+        assert_eq!(map("(LBroken"), "(LBroken");
+    }
+}
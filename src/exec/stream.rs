@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Where `System.out` or `System.err` is written to
+pub struct Output(Box<dyn Write>);
+
+impl Output {
+    pub fn new(write: impl Write + 'static) -> Self {
+        Output(Box::new(write))
+    }
+
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+
+    pub fn stderr() -> Self {
+        Self::new(std::io::stderr())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl std::fmt::Debug for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Output")
+    }
+}
+
+/// An in-memory `Output` that can be read back, e.g. to capture what a program printed
+#[derive(Debug, Default, Clone)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
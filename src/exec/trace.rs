@@ -0,0 +1,212 @@
+//! Tracing every instruction the interpreter runs, with the frame it runs in, to find where
+//! a program goes its own way
+use super::debug::matches_method;
+use super::stream::Output;
+use super::*;
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// How trace records are written out
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TraceFormat {
+    /// A line of text per instruction
+    Text,
+    /// A JSON object per line
+    JsonLines,
+}
+
+/// An instruction about to be run, and the frame it runs in
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    pub thread: String,
+    /// The internal name of the method's class
+    pub class: String,
+    pub method: String,
+    pub descriptor: String,
+    pub pc: usize,
+    pub bytes: Vec<u8>,
+    /// The instruction with its operands, as the disassembler writes it
+    pub instruction: String,
+    /// The operand stack, from the bottom
+    pub stack: Vec<String>,
+    pub locals: Vec<String>,
+}
+
+impl TraceRecord {
+    /// The instruction's bytes in hex, like `b2 00 02`
+    pub fn hex(&self) -> String {
+        let bytes = self.bytes.iter().map(|byte| format!("{:02x}", byte));
+        bytes.collect::<Vec<_>>().join(" ")
+    }
+
+    fn text(&self) -> String {
+        format!(
+            "[{}] {}.{}{} {:0>4X} {:<14} {:<40} stack=[{}] locals=[{}]",
+            self.thread,
+            self.class,
+            self.method,
+            self.descriptor,
+            self.pc,
+            self.hex(),
+            self.instruction,
+            self.stack.join(", "),
+            self.locals.join(", "),
+        )
+    }
+
+    fn json(&self) -> String {
+        let list = |values: &[String]| {
+            let values = values.iter().map(|value| json_string(value));
+            values.collect::<Vec<_>>().join(",")
+        };
+        format!(
+            r#"{{"thread":{},"class":{},"method":{},"descriptor":{},"pc":{},"bytes":{},"instruction":{},"stack":[{}],"locals":[{}]}}"#,
+            json_string(&self.thread),
+            json_string(&self.class),
+            json_string(&self.method),
+            json_string(&self.descriptor),
+            self.pc,
+            json_string(&self.hex()),
+            json_string(&self.instruction),
+            list(&self.stack),
+            list(&self.locals),
+        )
+    }
+}
+
+/// Writes a record for each instruction that is run, in the classes and methods it is
+/// narrowed to
+///
+/// ```ignore
+/// let mut tracer = Tracer::new(std::io::stderr());
+/// tracer.format(TraceFormat::JsonLines).class("com/acme/Calc").method("add");
+/// vm.set_tracer(Some(tracer));
+/// ```
+#[derive(Debug)]
+pub struct Tracer {
+    output: Output,
+    format: TraceFormat,
+    classes: Vec<String>,
+    methods: Vec<String>,
+}
+
+impl Tracer {
+    pub fn new(output: impl std::io::Write + 'static) -> Self {
+        Self {
+            output: Output::new(output),
+            format: TraceFormat::Text,
+            classes: vec![],
+            methods: vec![],
+        }
+    }
+
+    pub fn format(&mut self, format: TraceFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Only traces the methods of `class`, by internal or binary name. Every class that is
+    /// added is traced
+    pub fn class(&mut self, class: &str) -> &mut Self {
+        self.classes.push(class.replace('.', "/"));
+        self
+    }
+
+    /// Only traces the methods called `method`, which can include the descriptor to pick
+    /// an overload (`add(II)I`). Every method that is added is traced
+    pub fn method(&mut self, method: &str) -> &mut Self {
+        self.methods.push(method.to_string());
+        self
+    }
+
+    /// Whether instructions of the method are traced
+    pub fn traces(&self, class: &str, method: &str, descriptor: &str) -> bool {
+        let class = self.classes.is_empty() || self.classes.iter().any(|c| c == class);
+        let method = self.methods.is_empty()
+            || self
+                .methods
+                .iter()
+                .any(|m| matches_method(m, method, descriptor));
+        class && method
+    }
+
+    pub fn trace(&mut self, record: &TraceRecord) -> Result<()> {
+        let line = match self.format {
+            TraceFormat::Text => record.text(),
+            TraceFormat::JsonLines => record.json(),
+        };
+        writeln!(self.output, "{}", line.trim_end()).map_err(Into::into)
+    }
+}
+
+/// `s` as a JSON string literal
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> TraceRecord {
+        TraceRecord {
+            thread: "main".into(),
+            class: "hello".into(),
+            method: "main".into(),
+            descriptor: "([Ljava/lang/String;)V".into(),
+            pc: 3,
+            bytes: vec![0x12, 0x03],
+            instruction: r#"ldc "say \"hi\"""#.into(),
+            stack: vec!["1".into()],
+            locals: vec!["[Ljava/lang/String;@1".into(), "top".into()],
+        }
+    }
+
+    #[test]
+    fn formats() {
+        let record = record();
+        assert_eq!(
+            record.text(),
+            format!(
+                "[main] hello.main([Ljava/lang/String;)V 0003 12 03          {:<40} \
+                 stack=[1] locals=[[Ljava/lang/String;@1, top]",
+                r#"ldc "say \"hi\"""#
+            )
+        );
+        assert_eq!(
+            record.json(),
+            r#"{"thread":"main","class":"hello","method":"main","descriptor":"([Ljava/lang/String;)V","pc":3,"bytes":"12 03","instruction":"ldc \"say \\\"hi\\\"\"","stack":["1"],"locals":["[Ljava/lang/String;@1","top"]}"#
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let mut tracer = Tracer::new(std::io::sink());
+        assert!(tracer.traces("hello", "main", "()V"));
+        tracer
+            .class("java.lang.Math")
+            .method("max(II)I")
+            .method("abs");
+        assert!(tracer.traces("java/lang/Math", "max", "(II)I"));
+        assert!(tracer.traces("java/lang/Math", "abs", "(J)J"));
+        assert!(!tracer.traces("java/lang/Math", "max", "(JJ)J"));
+        assert!(!tracer.traces("hello", "abs", "(I)I"));
+    }
+}
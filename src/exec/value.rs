@@ -1,2 +1,107 @@
-#[derive(Debug, PartialEq)]
-pub enum Value {}
+use super::heap::Reference;
+use super::*;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Null,
+    Reference(Reference),
+    ReturnAddress(usize),
+    /// An unset local variable, or the second slot of a long or double
+    #[default]
+    Top,
+}
+
+macro_rules! value_as {
+    ($($name:ident => $kind:ident($ty:ty), $expected:expr);* $(;)?) => {
+        $(
+            #[inline]
+            pub fn $name(self) -> Result<$ty> {
+                match self {
+                    Value::$kind(d) => Ok(d),
+                    _ => Err(Error::StackType($expected)),
+                }
+            }
+        )*
+    };
+}
+
+impl Value {
+    /// The zero value for a field, array element or local of this type
+    pub fn default_for(ty: &ty::FieldType) -> Self {
+        use ty::FieldType::*;
+        match ty {
+            Byte | Char | Int | Short | Boolean => Value::Int(0),
+            Long => Value::Long(0),
+            Float => Value::Float(0.0),
+            Double => Value::Double(0.0),
+            Object(..) | Array(..) => Value::Null,
+        }
+    }
+
+    /// Long and double values take up two slots
+    pub fn is_wide(self) -> bool {
+        matches!(self, Value::Long(..) | Value::Double(..))
+    }
+
+    value_as! {
+        as_int => Int(i32), "int";
+        as_long => Long(i64), "long";
+        as_float => Float(f32), "float";
+        as_double => Double(f64), "double";
+        as_return_address => ReturnAddress(usize), "returnAddress";
+    }
+
+    /// `None` for `null`
+    pub fn as_reference(self) -> Result<Option<Reference>> {
+        match self {
+            Value::Reference(d) => Ok(Some(d)),
+            Value::Null => Ok(None),
+            _ => Err(Error::StackType("reference")),
+        }
+    }
+}
+
+impl From<Option<Reference>> for Value {
+    fn from(d: Option<Reference>) -> Self {
+        d.map(Value::Reference).unwrap_or(Value::Null)
+    }
+}
+
+impl From<Reference> for Value {
+    fn from(d: Reference) -> Self {
+        Value::Reference(d)
+    }
+}
+
+macro_rules! value_from {
+    ($($ty:ty => $kind:ident);* $(;)?) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(d: $ty) -> Self {
+                    Value::$kind(d.into())
+                }
+            }
+        )*
+    };
+}
+
+value_from! {
+    i8 => Int;
+    u8 => Int;
+    i16 => Int;
+    u16 => Int;
+    i32 => Int;
+    i64 => Long;
+    f32 => Float;
+    f64 => Double;
+}
+
+impl From<bool> for Value {
+    fn from(d: bool) -> Self {
+        Value::Int(d as i32)
+    }
+}
@@ -0,0 +1,657 @@
+//! Bytecode verification, which rejects class files whose methods could misuse the
+//! operand stack, local variables or uninitialized objects before they are run
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.10
+use super::interpreter::Interpreter;
+use super::*;
+
+use ty::{FieldType, MethodDescriptor};
+
+/// Returns a `Reason`, or `Reason::Other` with a formatted message
+macro_rules! fail {
+    ($msg:literal $(, $args:expr)* $(,)?) => {
+        return Err(Failure::Reason(Reason::Other(format!($msg, $($args),*))))
+    };
+    ($reason:expr) => {
+        return Err(Failure::Reason($reason))
+    };
+}
+
+mod check;
+mod compute;
+mod frame;
+mod infer;
+mod instruction;
+
+pub use compute::{compute_frames, compute_method_frames};
+pub use frame::{Frame, Type};
+
+/// Where the verifier finds out about classes other than the one being verified
+pub trait Hierarchy {
+    /// The super class of `class`, `None` for `java/lang/Object`
+    fn super_class(&mut self, class: &str) -> Result<Option<String>>;
+
+    fn is_interface(&mut self, class: &str) -> Result<bool>;
+}
+
+/// Loads the classes, without initializing them
+impl Hierarchy for Interpreter {
+    fn super_class(&mut self, class: &str) -> Result<Option<String>> {
+        let class = self.resolve_class(class)?;
+        Ok(class.super_class.as_ref().map(|class| class.name.clone()))
+    }
+
+    fn is_interface(&mut self, class: &str) -> Result<bool> {
+        Ok(self.resolve_class(class)?.is_interface())
+    }
+}
+
+/// A method that failed verification
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyError {
+    /// The internal name of the class
+    pub class: String,
+    /// The method's name and descriptor
+    pub method: String,
+    /// The instruction that failed to verify
+    pub pc: usize,
+    pub reason: Reason,
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{} at pc {}: {}",
+            self.class, self.method, self.pc, self.reason
+        )
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// A local variable or operand stack entry
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Slot {
+    Local(usize),
+    Stack(usize),
+}
+
+impl std::fmt::Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Slot::Local(index) => write!(f, "local {}", index),
+            Slot::Stack(index) => write!(f, "stack[{}]", index),
+        }
+    }
+}
+
+/// Why a method failed verification
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// An operand that isn't of the `expected` type, which may also be a kind of type
+    /// like "reference" or "array"
+    Type {
+        expected: String,
+        actual: Type,
+    },
+    /// A slot of the frame at the instruction doesn't fit the stack map frame at `target`
+    Frame {
+        target: usize,
+        slot: Slot,
+        expected: Type,
+        actual: Type,
+    },
+    /// The operand stack height doesn't match the stack map frame at `target`
+    StackHeight {
+        target: usize,
+        expected: usize,
+        actual: usize,
+    },
+    StackUnderflow,
+    StackOverflow {
+        max_stack: usize,
+    },
+    LocalOutOfRange {
+        index: usize,
+        max_locals: usize,
+    },
+    /// There is no stack map frame at `target`, which is a jump target or follows an
+    /// unconditional branch
+    MissingFrame {
+        target: usize,
+    },
+    /// A jump to somewhere that isn't the start of an instruction
+    InvalidTarget {
+        target: isize,
+    },
+    InvalidInstruction,
+    /// The last instruction can continue past the end of the code
+    FallsOffEnd,
+    Other(String),
+}
+
+impl std::fmt::Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reason::Type { expected, actual } => {
+                write!(f, "expected {}, found {}", expected, actual)
+            }
+            Reason::Frame {
+                target,
+                slot,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} is {}, the frame at {} expects {}",
+                slot, actual, target, expected
+            ),
+            Reason::StackHeight {
+                target,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "the stack has {} values, the frame at {} expects {}",
+                actual, target, expected
+            ),
+            Reason::StackUnderflow => write!(f, "the operand stack is empty"),
+            Reason::StackOverflow { max_stack } => {
+                write!(f, "the operand stack exceeds max_stack of {}", max_stack)
+            }
+            Reason::LocalOutOfRange { index, max_locals } => write!(
+                f,
+                "local {} is out of range of max_locals of {}",
+                index, max_locals
+            ),
+            Reason::MissingFrame { target } => write!(f, "no stack map frame at {}", target),
+            Reason::InvalidTarget { target } => {
+                write!(f, "{} is not the start of an instruction", target)
+            }
+            Reason::InvalidInstruction => write!(f, "invalid instruction"),
+            Reason::FallsOffEnd => write!(f, "falls off the end of the code"),
+            Reason::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Why verification stopped, which is either a verify error or an error loading another
+/// class
+#[derive(Debug)]
+enum Failure {
+    Reason(Reason),
+    Error(Error),
+}
+
+impl From<Reason> for Failure {
+    fn from(reason: Reason) -> Self {
+        Failure::Reason(reason)
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        Failure::Error(err)
+    }
+}
+
+impl From<crate::parse::Error> for Failure {
+    fn from(err: crate::parse::Error) -> Self {
+        Failure::Error(err.into())
+    }
+}
+
+type Check<T> = std::result::Result<T, Failure>;
+
+/// Verifies every method of a class file. Class files with stack map frames (version 50
+/// and later) are type checked, and the types of older ones are inferred
+pub fn verify_class(file: &ty::ClassFile, hierarchy: &mut dyn Hierarchy) -> Result<()> {
+    for method in &file.methods {
+        let code = match method.get_code() {
+            Some(code) => code,
+            None => continue,
+        };
+        let mut verifier = Verifier::new(file, method, code, hierarchy)?;
+        let result = match file.major_version {
+            0..=49 => verifier.infer(),
+            _ => verifier.type_check(),
+        };
+        verifier.finish(result)?;
+    }
+    Ok(())
+}
+
+/// The state of verifying one method
+struct Verifier<'a> {
+    file: &'a ty::ClassFile,
+    class: &'a str,
+    name: &'a str,
+    descriptor: &'a str,
+    signature: MethodDescriptor,
+    is_static: bool,
+    code: &'a attr::Code,
+    /// Whether an instruction starts at each offset of the code
+    starts: Vec<bool>,
+    /// The instruction being verified
+    pc: usize,
+    hierarchy: &'a mut dyn Hierarchy,
+}
+
+impl<'a> Verifier<'a> {
+    fn new(
+        file: &'a ty::ClassFile,
+        method: &'a ty::Method,
+        code: &'a attr::Code,
+        hierarchy: &'a mut dyn Hierarchy,
+    ) -> Result<Self> {
+        let descriptor = file.utf8(method.descriptor)?;
+        Ok(Self {
+            file,
+            class: file.get_class_name(),
+            name: method.name(),
+            descriptor,
+            signature: MethodDescriptor::parse(descriptor)?,
+            is_static: method.flags.contains(ty::MethodFlags::STATIC),
+            code,
+            starts: vec![],
+            pc: 0,
+            hierarchy,
+        })
+    }
+
+    /// Turns the reason verification failed into a `VerifyError`
+    fn finish<T>(&self, result: Check<T>) -> Result<T> {
+        match result {
+            Ok(value) => Ok(value),
+            Err(Failure::Error(err)) => Err(err),
+            Err(Failure::Reason(reason)) => Err(Error::Verify(Box::new(VerifyError {
+                class: self.class.to_string(),
+                method: format!("{}{}", self.name, self.descriptor),
+                pc: self.pc,
+                reason,
+            }))),
+        }
+    }
+
+    fn is_constructor(&self) -> bool {
+        self.name == "<init>"
+    }
+
+    fn max_stack(&self) -> usize {
+        usize::from(self.code.max_stack)
+    }
+
+    fn max_locals(&self) -> usize {
+        usize::from(self.code.max_locals)
+    }
+
+    /// Decodes every instruction, and records where they start
+    fn decode(&mut self) -> Check<Vec<(usize, Instruction)>> {
+        let code = &self.code.code;
+        if code.is_empty() {
+            fail!("the code is empty");
+        }
+
+        let mut instructions = vec![];
+        self.starts = vec![false; code.len()];
+        let mut pc = 0;
+        while pc < code.len() {
+            self.pc = pc;
+            let instruction = match Instruction::decode(code, pc) {
+                Some(instruction) => instruction,
+                None => fail!(Reason::InvalidInstruction),
+            };
+            self.starts[pc] = true;
+            pc += instruction.size();
+            instructions.push((self.pc, instruction));
+        }
+        Ok(instructions)
+    }
+
+    /// The frame on entry to the method, from its descriptor
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.10.1.6
+    fn initial_frame(&self) -> Check<Frame> {
+        let mut locals = vec![];
+        let mut this_uninit = false;
+        if !self.is_static {
+            if self.is_constructor() && self.class != "java/lang/Object" {
+                locals.push(Type::UninitializedThis);
+                this_uninit = true;
+            } else {
+                locals.push(Type::object(self.class));
+            }
+        }
+        locals.extend(self.signature.params.iter().map(Type::from_field));
+
+        let locals = Frame::expand_locals(&locals, self.max_locals());
+        if locals.len() > self.max_locals() {
+            fail!(Reason::LocalOutOfRange {
+                index: locals.len() - 1,
+                max_locals: self.max_locals(),
+            });
+        }
+        Ok(Frame {
+            locals,
+            stack: vec![],
+            this_uninit,
+        })
+    }
+
+    /// Makes sure `target` is the start of an instruction
+    fn target(&self, offset: isize) -> Check<usize> {
+        let target = self.pc as isize + offset;
+        if target < 0 || !self.starts.get(target as usize).copied().unwrap_or(false) {
+            fail!(Reason::InvalidTarget { target });
+        }
+        Ok(target as usize)
+    }
+
+    /// Whether a value of type `from` can be used where `to` is expected
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.10.1.2
+    fn is_assignable(&mut self, from: &Type, to: &Type) -> Result<bool> {
+        match (from, to) {
+            _ if from == to => Ok(true),
+            (_, Type::Top) => Ok(true),
+            (Type::Null, Type::Object(..)) => Ok(true),
+            (Type::Object(from), Type::Object(to)) => self.is_class_assignable(from, to),
+            _ => Ok(false),
+        }
+    }
+
+    /// Whether the class or array type `from` can be used where `to` is expected.
+    /// Interfaces are treated like `java/lang/Object`, as they are checked at runtime
+    fn is_class_assignable(&mut self, from: &str, to: &str) -> Result<bool> {
+        if from == to || to == "java/lang/Object" {
+            return Ok(true);
+        }
+
+        match (from.starts_with('['), to.starts_with('[')) {
+            (_, false) if self.hierarchy.is_interface(to)? => Ok(true),
+            (false, false) => {
+                let mut class = from.to_string();
+                while let Some(super_class) = self.hierarchy.super_class(&class)? {
+                    if super_class == to {
+                        return Ok(true);
+                    }
+                    class = super_class;
+                }
+                Ok(false)
+            }
+            (true, false) => Ok(matches!(to, "java/lang/Cloneable" | "java/io/Serializable")),
+            (false, true) => Ok(false),
+            (true, true) => {
+                let (from, to) = (Type::object(from), Type::object(to));
+                match (from.component(), to.component()) {
+                    (Some(from), Some(to)) => self.is_assignable(&from, &to),
+                    // arrays of primitives have to be the same
+                    _ => Ok(false),
+                }
+            }
+        }
+    }
+
+    /// Makes sure `frame` can be used where the stack map frame `target` is expected
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.10.1.4
+    fn check_frame(&mut self, frame: &Frame, target: &Frame, at: usize) -> Check<()> {
+        if frame.stack.len() != target.stack.len() {
+            fail!(Reason::StackHeight {
+                target: at,
+                expected: target.stack.len(),
+                actual: frame.stack.len(),
+            });
+        }
+
+        let locals = (frame.locals.iter().zip(&target.locals).enumerate())
+            .map(|(i, types)| (Slot::Local(i), types));
+        let stack = (frame.stack.iter().zip(&target.stack).enumerate())
+            .map(|(i, types)| (Slot::Stack(i), types));
+        for (slot, (actual, expected)) in locals.chain(stack) {
+            if !self.is_assignable(actual, expected)? {
+                fail!(Reason::Frame {
+                    target: at,
+                    slot,
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                });
+            }
+        }
+
+        if frame.this_uninit && !target.this_uninit {
+            fail!(
+                "this is not initialized, but the frame at {} expects it to be",
+                at
+            );
+        }
+        Ok(())
+    }
+
+    /// The exception handlers that cover the current instruction, with the type of the
+    /// exception they catch
+    fn handlers(&self) -> Check<Vec<(usize, Type)>> {
+        let mut handlers = vec![];
+        for row in &self.code.exception_table {
+            let (start, end) = (usize::from(row.start_pc), usize::from(row.end_pc));
+            if self.pc < start || self.pc >= end {
+                continue;
+            }
+            let catch_type = match row.catch_type {
+                ty::ConstantIndex(0) => "java/lang/Throwable",
+                index => self.file.class_name(index)?,
+            };
+            handlers.push((usize::from(row.handler_pc), Type::object(catch_type)));
+        }
+        Ok(handlers)
+    }
+
+    /// Makes sure the exception table only refers to the starts of instructions, and only
+    /// catches `Throwable`s
+    fn check_exception_table(&mut self) -> Check<()> {
+        for row in &self.code.exception_table {
+            let (start, end, handler) = (
+                usize::from(row.start_pc),
+                usize::from(row.end_pc),
+                usize::from(row.handler_pc),
+            );
+            self.pc = start;
+            let is_start = |pc: usize| self.starts.get(pc).copied().unwrap_or(false);
+            if !is_start(start) || start >= end || (end < self.starts.len() && !is_start(end)) {
+                fail!("invalid exception handler range {}..{}", start, end);
+            }
+            if end > self.starts.len() || !is_start(handler) {
+                fail!("invalid exception handler at {}", handler);
+            }
+            if row.catch_type != ty::ConstantIndex(0) {
+                let catch_type = Type::object(self.file.class_name(row.catch_type)?);
+                let throwable = Type::object("java/lang/Throwable");
+                if !self.is_assignable(&catch_type, &throwable)? {
+                    fail!(Reason::Type {
+                        expected: throwable.to_string(),
+                        actual: catch_type,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::value::Value;
+    use attr::{Attribute, StackMapFrame, VerificationType};
+    use std::rc::Rc;
+
+    pub(super) fn read(name: &str) -> ty::ClassFile {
+        let data = std::fs::read(format!("./etc/{}.class", name)).unwrap();
+        ty::ClassFile::read(&mut data.as_slice()).unwrap()
+    }
+
+    pub(super) fn code<'a>(file: &'a mut ty::ClassFile, name: &str) -> &'a mut attr::Code {
+        let method = file.methods.iter_mut().find(|m| m.name() == name).unwrap();
+        method
+            .attributes
+            .iter_mut()
+            .find_map(|attribute| match attribute {
+                Attribute::Code(code) => Some(code),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn stack_map<'a>(file: &'a mut ty::ClassFile, name: &str) -> &'a mut Vec<StackMapFrame> {
+        code(file, name)
+            .attributes
+            .iter_mut()
+            .find_map(|attribute| match attribute {
+                Attribute::StackMapTable(table) => Some(&mut table.entries),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    pub(super) fn verify(file: &ty::ClassFile) -> std::result::Result<(), VerifyError> {
+        match verify_class(file, &mut Interpreter::default()) {
+            Ok(()) => Ok(()),
+            Err(Error::Verify(err)) => Err(*err),
+            Err(err) => panic!("{:?}", err),
+        }
+    }
+
+    pub(super) fn reason(file: &ty::ClassFile, method: &str, pc: usize) -> Reason {
+        let err = verify(file).unwrap_err();
+        assert_eq!(
+            (err.method.split('(').next().unwrap(), err.pc),
+            (method, pc)
+        );
+        err.reason
+    }
+
+    #[test]
+    fn fixtures_verify() {
+        let mut interpreter = Interpreter::default();
+        let mut files = vec![];
+        for entry in std::fs::read_dir("./etc").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(false, |ext| ext == "class") {
+                let data = std::fs::read(&path).unwrap();
+                let file = Rc::new(ty::ClassFile::read(&mut data.as_slice()).unwrap());
+                interpreter.load_class(Rc::clone(&file));
+                files.push(file);
+            }
+        }
+        for file in files {
+            if let Err(err) = verify_class(&file, &mut interpreter) {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn operand_types() {
+        let mut file = read("verify");
+        // iload_0 -> aconst_null
+        code(&mut file, "id").code[0] = 0x01;
+        let expected = Reason::Type {
+            expected: "int".to_string(),
+            actual: Type::Null,
+        };
+        assert_eq!(reason(&file, "id", 1), expected);
+        assert_eq!(
+            verify(&file).unwrap_err().to_string(),
+            "verify.id(I)I at pc 1: expected int, found null"
+        );
+    }
+
+    #[test]
+    fn stack_and_locals() {
+        let mut file = read("verify");
+        code(&mut file, "id").max_stack = 0;
+        assert_eq!(
+            reason(&file, "id", 0),
+            Reason::StackOverflow { max_stack: 0 }
+        );
+
+        let mut file = read("verify");
+        code(&mut file, "wide").max_locals = 4;
+        let expected = Reason::LocalOutOfRange {
+            index: 4,
+            max_locals: 4,
+        };
+        assert_eq!(reason(&file, "wide", 12), expected);
+    }
+
+    #[test]
+    fn branch_targets() {
+        let mut file = read("verify");
+        // goto 4 -> goto 17, inside of the goto itself
+        code(&mut file, "sum").code[17..19].copy_from_slice(&[0, 1]);
+        assert_eq!(
+            reason(&file, "sum", 16),
+            Reason::InvalidTarget { target: 17 }
+        );
+
+        let mut file = read("verify");
+        code(&mut file, "pick").attributes.clear();
+        assert_eq!(reason(&file, "pick", 1), Reason::MissingFrame { target: 8 });
+    }
+
+    #[test]
+    fn stack_map_frames() {
+        let mut file = read("verify");
+        match &mut stack_map(&mut file, "pick")[1] {
+            StackMapFrame::SameLocalsOneStackItemFrame(frame) => {
+                frame.stack_item = VerificationType::Integer
+            }
+            frame => panic!("{:?}", frame),
+        }
+        let expected = Reason::Frame {
+            target: 9,
+            slot: Slot::Stack(0),
+            expected: Type::Integer,
+            actual: Type::object("java/lang/String"),
+        };
+        assert_eq!(reason(&file, "pick", 5), expected);
+    }
+
+    #[test]
+    fn uninitialized_objects() {
+        let mut file = read("verify");
+        // aload_0, invokespecial java/lang/Object.<init> -> nop
+        code(&mut file, "<init>").code[0..4].copy_from_slice(&[0, 0, 0, 0]);
+        match reason(&file, "<init>", 9) {
+            Reason::Other(msg) => assert!(msg.starts_with("returns before the constructor")),
+            reason => panic!("{:?}", reason),
+        }
+
+        let mut file = read("verify");
+        // iload_0, invokespecial verify.<init> -> pop, nop
+        code(&mut file, "create").code[4..8].copy_from_slice(&[0x57, 0, 0, 0]);
+        let expected = Reason::Type {
+            expected: "verify".to_string(),
+            actual: Type::Uninitialized(0),
+        };
+        assert_eq!(reason(&file, "create", 8), expected);
+    }
+
+    #[test]
+    fn rejected_before_running() {
+        let mut file = read("verify");
+        code(&mut file, "id").code[0] = 0x01;
+        let file = Rc::new(file);
+
+        let mut interpreter = Interpreter::default();
+        interpreter.load_class(Rc::clone(&file));
+        match interpreter.invoke_static("verify", "sum", "(I)I", &[Value::Int(3)]) {
+            Err(Error::Verify(err)) => assert_eq!(err.method, "id(I)I"),
+            e => panic!("{:?}", e),
+        }
+
+        let mut interpreter = Interpreter::default();
+        interpreter.set_verify(false);
+        interpreter.load_class(file);
+        let sum = interpreter.invoke_static("verify", "sum", "(I)I", &[Value::Int(3)]);
+        assert_eq!(sum.unwrap(), Some(Value::Int(3)));
+    }
+}
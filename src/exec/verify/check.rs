@@ -0,0 +1,173 @@
+//! Verification by type checking, against the frames of the `StackMapTable`
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.10.1
+use super::*;
+
+use attr::{StackMapFrame, VerificationType};
+use std::collections::BTreeMap;
+
+impl Verifier<'_> {
+    pub(super) fn type_check(&mut self) -> Check<()> {
+        let instructions = self.decode()?;
+        self.check_exception_table()?;
+        let frames = self.stack_map()?;
+
+        // the frame after the previous instruction, `None` after an unconditional branch
+        let mut frame = Some(self.initial_frame()?);
+        for (pc, instruction) in &instructions {
+            self.pc = *pc;
+            let mut current = match (frame.take(), frames.get(pc)) {
+                (Some(frame), Some(map)) => {
+                    self.check_frame(&frame, map, *pc)?;
+                    map.clone()
+                }
+                (None, Some(map)) => map.clone(),
+                (Some(frame), None) => frame,
+                (None, None) => fail!(Reason::MissingFrame { target: *pc }),
+            };
+
+            for (handler, exception) in self.handlers()? {
+                let caught = Frame {
+                    locals: current.locals.clone(),
+                    stack: vec![exception],
+                    this_uninit: current.this_uninit,
+                };
+                match frames.get(&handler) {
+                    Some(map) => self.check_frame(&caught, map, handler)?,
+                    None => fail!(Reason::MissingFrame { target: handler }),
+                }
+            }
+
+            let flow = self.execute(instruction, &mut current)?;
+            for target in &flow.targets {
+                match frames.get(target) {
+                    Some(map) => self.check_frame(&current, map, *target)?,
+                    None => fail!(Reason::MissingFrame { target: *target }),
+                }
+            }
+            if flow.falls_through {
+                frame = Some(current);
+            }
+        }
+
+        if frame.is_some() {
+            fail!(Reason::FallsOffEnd);
+        }
+        Ok(())
+    }
+
+    /// Decodes the `StackMapTable` into the full frames, by pc
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.4
+    fn stack_map(&mut self) -> Check<BTreeMap<usize, Frame>> {
+        let entries = self
+            .code
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                attr::Attribute::StackMapTable(table) => Some(table.entries.as_slice()),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let initial = self.initial_frame()?;
+        // the locals with one entry per value, which chop and append frames work on
+        let mut locals = initial.compressed_locals();
+        let mut frames = BTreeMap::new();
+        let mut previous: Option<usize> = None;
+
+        for entry in entries {
+            let (delta, stack) = match entry {
+                StackMapFrame::SameFrame(frame) => (usize::from(frame.offset), vec![]),
+                StackMapFrame::SameFrameExtended(frame) => (usize::from(frame.offset), vec![]),
+                StackMapFrame::SameLocalsOneStackItemFrame(frame) => {
+                    (usize::from(frame.offset), vec![&frame.stack_item])
+                }
+                StackMapFrame::SameLocalsOneStackItemFrameExtended(frame) => {
+                    (usize::from(frame.offset), vec![&frame.stack_item])
+                }
+                StackMapFrame::ChopFrame(frame) => {
+                    let absent = usize::from(frame.absent_locals);
+                    if absent > locals.len() {
+                        fail!("a stack map frame chops more locals than there are");
+                    }
+                    locals.truncate(locals.len() - absent);
+                    (usize::from(frame.offset), vec![])
+                }
+                StackMapFrame::AppendFrame(frame) => {
+                    for local in &frame.new_locals {
+                        locals.push(self.verification_type(local)?);
+                    }
+                    (usize::from(frame.offset), vec![])
+                }
+                StackMapFrame::FullFrame(frame) => {
+                    locals = frame
+                        .locals
+                        .iter()
+                        .map(|local| self.verification_type(local))
+                        .collect::<Check<_>>()?;
+                    (
+                        usize::from(frame.offset),
+                        frame.stack_items.iter().collect(),
+                    )
+                }
+            };
+
+            let pc = match previous {
+                Some(previous) => previous + delta + 1,
+                None => delta,
+            };
+            previous = Some(pc);
+            self.pc = pc;
+            if !self.starts.get(pc).copied().unwrap_or(false) {
+                fail!(Reason::InvalidTarget {
+                    target: pc as isize
+                });
+            }
+
+            let expanded = Frame::expand_locals(&locals, self.max_locals());
+            if expanded.len() > self.max_locals() {
+                fail!(Reason::LocalOutOfRange {
+                    index: expanded.len() - 1,
+                    max_locals: self.max_locals(),
+                });
+            }
+            let frame = Frame {
+                this_uninit: expanded.contains(&Type::UninitializedThis),
+                locals: expanded,
+                stack: stack
+                    .into_iter()
+                    .map(|item| self.verification_type(item))
+                    .collect::<Check<_>>()?,
+            };
+            if frame.stack_size() > self.max_stack() {
+                fail!(Reason::StackOverflow {
+                    max_stack: self.max_stack(),
+                });
+            }
+            frames.insert(pc, frame);
+        }
+        Ok(frames)
+    }
+
+    fn verification_type(&self, ty: &VerificationType) -> Check<Type> {
+        Ok(match ty {
+            VerificationType::Top => Type::Top,
+            VerificationType::Integer => Type::Integer,
+            VerificationType::Float => Type::Float,
+            VerificationType::Long => Type::Long,
+            VerificationType::Double => Type::Double,
+            VerificationType::Null => Type::Null,
+            VerificationType::UninitializedThis => Type::UninitializedThis,
+            VerificationType::Object(index) => Type::object(self.file.class_name(*index)?),
+            VerificationType::Uninitialized(offset) => {
+                let offset = usize::from(*offset);
+                if self.code.code.get(offset) != Some(&0xBB) || !self.starts[offset] {
+                    fail!(
+                        "uninitialized({}) does not refer to a new instruction",
+                        offset
+                    );
+                }
+                Type::Uninitialized(offset)
+            }
+        })
+    }
+}
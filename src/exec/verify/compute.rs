@@ -0,0 +1,489 @@
+//! Computing the `StackMapTable`, `max_stack` and `max_locals` of rewritten code
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.4
+use super::*;
+
+use attr::{Attribute, StackMapFrame, VerificationType};
+use std::collections::BTreeMap;
+use ty::Constant;
+
+/// What is computed for a method, before it is written back into the class file
+struct Computed {
+    /// The frames the `StackMapTable` has to have, by pc
+    frames: BTreeMap<usize, Frame>,
+    max_stack: usize,
+    /// The ranges of instructions that can't be reached
+    dead: Vec<(usize, usize)>,
+}
+
+/// Recomputes the `StackMapTable`, `max_stack` and `max_locals` of every method with
+/// code, after its instructions have been changed. Unreachable code is replaced by `nop`s
+/// ending in an `athrow`, which is removed from the exception table
+pub fn compute_frames(file: &mut ty::ClassFile, hierarchy: &mut dyn Hierarchy) -> Result<()> {
+    for index in 0..file.methods.len() {
+        compute_method_frames(file, index, hierarchy)?;
+    }
+    Ok(())
+}
+
+/// Recomputes the `StackMapTable`, `max_stack` and `max_locals` of the method at `index`
+pub fn compute_method_frames(
+    file: &mut ty::ClassFile,
+    index: usize,
+    hierarchy: &mut dyn Hierarchy,
+) -> Result<()> {
+    let mut code = match file.methods[index].get_code() {
+        Some(code) => code.clone(),
+        None => return Ok(()),
+    };
+    code.attributes
+        .retain(|attribute| !matches!(attribute, Attribute::StackMapTable(..)));
+
+    let computed = {
+        let method = &file.methods[index];
+        // the real max_locals is needed up front, as every frame has that many locals
+        let mut verifier = Verifier::new(file, method, &code, hierarchy)?;
+        let result = verifier.max_locals_used();
+        let max_locals = verifier.finish(result)?;
+        code.max_locals = max_locals as u16;
+        code.max_stack = u16::MAX;
+
+        let mut verifier = Verifier::new(file, method, &code, hierarchy)?;
+        let result = verifier.compute();
+        verifier.finish(result)?
+    };
+
+    for &(start, end) in &computed.dead {
+        for byte in &mut code.code[start..end - 1] {
+            *byte = 0x00; // nop
+        }
+        code.code[end - 1] = 0xBF; // athrow
+    }
+    code.exception_table = code
+        .exception_table
+        .iter()
+        .flat_map(|row| live_ranges(row, &computed.dead))
+        .collect();
+    code.max_stack = computed.max_stack as u16;
+
+    let initial = {
+        let method = &file.methods[index];
+        let verifier = Verifier::new(file, method, &code, hierarchy)?;
+        let result = verifier.initial_frame();
+        verifier.finish(result)?
+    };
+    let entries = compress(file, &initial, &computed.frames)?;
+    if !entries.is_empty() {
+        let attribute_name = intern(file, Constant::Utf8("StackMapTable".to_string()))?;
+        code.attributes
+            .push(Attribute::StackMapTable(attr::StackMapTable {
+                attribute_name,
+                entries,
+            }));
+    }
+
+    for attribute in &mut file.methods[index].attributes {
+        if let Attribute::Code(old) = attribute {
+            *old = code;
+            break;
+        }
+    }
+    Ok(())
+}
+
+impl Verifier<'_> {
+    /// The number of locals the parameters and instructions use
+    fn max_locals_used(&mut self) -> Check<usize> {
+        let mut max_locals = self
+            .signature
+            .params
+            .iter()
+            .map(FieldType::slots)
+            .sum::<usize>();
+        if !self.is_static {
+            max_locals += 1;
+        }
+        for (pc, instruction) in self.decode()? {
+            self.pc = pc;
+            if let Some((index, size)) = local_access(&instruction) {
+                max_locals = max_locals.max(index + size);
+            }
+        }
+        if max_locals > usize::from(u16::MAX) {
+            fail!("more than {} locals", u16::MAX);
+        }
+        Ok(max_locals)
+    }
+
+    /// Infers the frames, and picks the ones that the type checker needs
+    fn compute(&mut self) -> Check<Computed> {
+        let instructions = self.decode()?.into_iter().collect::<BTreeMap<_, _>>();
+        for (&pc, instruction) in &instructions {
+            self.pc = pc;
+            let subroutine = matches!(
+                instruction,
+                Instruction::JSR(..) | Instruction::JSR_W(..) | Instruction::RET(..)
+            ) || matches!(instruction, Instruction::WIDE(WIDE(0xA9, ..)));
+            if subroutine {
+                fail!("jsr and ret are not allowed in class files with stack map frames");
+            }
+        }
+
+        let inference = self.inference(&instructions)?;
+        let mut frames = BTreeMap::new();
+        let mut max_stack = 0;
+        let mut dead = vec![];
+        let mut follows_jump = false;
+
+        for (&pc, instruction) in &instructions {
+            self.pc = pc;
+            let frame = match inference.frames.get(&pc) {
+                Some(frame) => frame,
+                None => {
+                    // continue the range of dead code that ends before this one
+                    match dead.last_mut() {
+                        Some((_, end)) if *end == pc => *end = pc + instruction.size(),
+                        _ => dead.push((pc, pc + instruction.size())),
+                    }
+                    follows_jump = true;
+                    continue;
+                }
+            };
+            if follows_jump {
+                frames.insert(pc, frame.clone());
+            }
+            for (handler, _) in self.handlers()? {
+                frames.insert(handler, inference.frames[&handler].clone());
+            }
+
+            let mut after = frame.clone();
+            let flow = self.execute(instruction, &mut after)?;
+            max_stack = max_stack.max(frame.stack_size()).max(after.stack_size());
+            for target in flow.targets {
+                frames.insert(target, inference.frames[&target].clone());
+            }
+            follows_jump = !flow.falls_through;
+        }
+
+        // unreachable code is only left an athrow, of what a frame says is on the stack
+        for &(start, _) in &dead {
+            let frame = Frame {
+                locals: vec![Type::Top; self.max_locals()],
+                stack: vec![Type::object("java/lang/Throwable")],
+                this_uninit: false,
+            };
+            frames.insert(start, frame);
+            max_stack = max_stack.max(1);
+        }
+        if max_stack > usize::from(u16::MAX) {
+            fail!("the operand stack is deeper than {}", u16::MAX);
+        }
+
+        Ok(Computed {
+            frames,
+            max_stack,
+            dead,
+        })
+    }
+}
+
+/// The local, and the number of slots of it, that an instruction loads or stores
+fn local_access(instruction: &Instruction) -> Option<(usize, usize)> {
+    let access = match instruction {
+        Instruction::ILOAD(ILOAD(index))
+        | Instruction::FLOAD(FLOAD(index))
+        | Instruction::ALOAD(ALOAD(index))
+        | Instruction::ISTORE(ISTORE(index))
+        | Instruction::FSTORE(FSTORE(index))
+        | Instruction::ASTORE(ASTORE(index))
+        | Instruction::IINC(IINC(index, _))
+        | Instruction::RET(RET(index)) => (usize::from(*index), 1),
+        Instruction::LLOAD(LLOAD(index))
+        | Instruction::DLOAD(DLOAD(index))
+        | Instruction::LSTORE(LSTORE(index))
+        | Instruction::DSTORE(DSTORE(index)) => (usize::from(*index), 2),
+        Instruction::WIDE(WIDE(opcode, a, b, ..)) => {
+            let size = match opcode {
+                0x16 | 0x18 | 0x37 | 0x39 => 2,
+                _ => 1,
+            };
+            (usize::from(wide_index(*a, *b)), size)
+        }
+        _ => {
+            // iload_0 to aload_3 and istore_0 to astore_3 are in groups of 4, one for each
+            // of int, long, float, double and reference
+            let offset = match instruction.opcode() {
+                opcode @ 0x1A..=0x2D => opcode - 0x1A,
+                opcode @ 0x3B..=0x4E => opcode - 0x3B,
+                _ => return None,
+            };
+            let size = match offset / 4 {
+                1 | 3 => 2,
+                _ => 1,
+            };
+            (usize::from(offset % 4), size)
+        }
+    };
+    Some(access)
+}
+
+/// The parts of the range of an exception table row that aren't dead code
+fn live_ranges(
+    row: &attr::ExceptionTableRow,
+    dead: &[(usize, usize)],
+) -> Vec<attr::ExceptionTableRow> {
+    let mut rows = vec![];
+    let mut start = usize::from(row.start_pc);
+    let end = usize::from(row.end_pc);
+    for &(dead_start, dead_end) in dead {
+        if dead_end <= start || dead_start >= end {
+            continue;
+        }
+        if dead_start > start {
+            rows.push((start, dead_start));
+        }
+        start = dead_end;
+    }
+    if start < end {
+        rows.push((start, end));
+    }
+
+    rows.into_iter()
+        .map(|(start_pc, end_pc)| attr::ExceptionTableRow {
+            start_pc: start_pc as u16,
+            end_pc: end_pc as u16,
+            ..row.clone()
+        })
+        .collect()
+}
+
+/// Encodes each frame relative to the one before it, as compactly as it can be
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.4
+fn compress(
+    file: &mut ty::ClassFile,
+    initial: &Frame,
+    frames: &BTreeMap<usize, Frame>,
+) -> Result<Vec<StackMapFrame>> {
+    let mut entries = vec![];
+    let mut locals = initial.compressed_locals();
+    let mut previous: Option<usize> = None;
+
+    for (&pc, frame) in frames {
+        let delta = match previous {
+            Some(previous) => pc - previous - 1,
+            None => pc,
+        };
+        previous = Some(pc);
+        let offset = delta as u16;
+
+        let current = frame.compressed_locals();
+        let same = current == locals;
+        let entry = match frame.stack.as_slice() {
+            [] if same && delta < 64 => StackMapFrame::SameFrame(attr::SameFrame {
+                offset: delta as u8,
+            }),
+            [] if same => StackMapFrame::SameFrameExtended(attr::SameFrameExtended { offset }),
+            [item] if same && delta < 64 => {
+                StackMapFrame::SameLocalsOneStackItemFrame(attr::SameLocalsOneStackItemFrame {
+                    offset: delta as u8,
+                    stack_item: verification_type(file, item)?,
+                })
+            }
+            [item] if same => StackMapFrame::SameLocalsOneStackItemFrameExtended(
+                attr::SameLocalsOneStackItemFrameExtended {
+                    offset,
+                    stack_item: verification_type(file, item)?,
+                },
+            ),
+            [] if current.len() < locals.len()
+                && locals.len() - current.len() <= 3
+                && locals.starts_with(&current) =>
+            {
+                StackMapFrame::ChopFrame(attr::ChopFrame {
+                    offset,
+                    absent_locals: (locals.len() - current.len()) as u8,
+                })
+            }
+            [] if current.len() > locals.len()
+                && current.len() - locals.len() <= 3
+                && current.starts_with(&locals) =>
+            {
+                StackMapFrame::AppendFrame(attr::AppendFrame {
+                    offset,
+                    new_locals: verification_types(file, &current[locals.len()..])?,
+                })
+            }
+            stack => StackMapFrame::FullFrame(attr::FullFrame {
+                offset,
+                locals: verification_types(file, &current)?,
+                stack_items: verification_types(file, stack)?,
+            }),
+        };
+        entries.push(entry);
+        locals = current;
+    }
+    Ok(entries)
+}
+
+fn verification_types(file: &mut ty::ClassFile, types: &[Type]) -> Result<Vec<VerificationType>> {
+    types.iter().map(|ty| verification_type(file, ty)).collect()
+}
+
+fn verification_type(file: &mut ty::ClassFile, ty: &Type) -> Result<VerificationType> {
+    Ok(match ty {
+        Type::Top => VerificationType::Top,
+        Type::Integer => VerificationType::Integer,
+        Type::Float => VerificationType::Float,
+        Type::Long => VerificationType::Long,
+        Type::Double => VerificationType::Double,
+        Type::Null => VerificationType::Null,
+        Type::UninitializedThis => VerificationType::UninitializedThis,
+        Type::Uninitialized(pc) => VerificationType::Uninitialized(*pc as u16),
+        Type::Object(name) => {
+            let name = intern(file, Constant::Utf8(name.clone()))?;
+            VerificationType::Object(intern(file, Constant::ClassRef(name))?)
+        }
+        Type::ReturnAddress(..) => generic_error!("a return address can't be in a stack map"),
+    })
+}
+
+/// The index of `constant` in the constant pool, which it is added to if it isn't there
+fn intern(file: &mut ty::ClassFile, constant: Constant) -> Result<ty::ConstantIndex> {
+    if let Some(index) = file.constant_pool.iter().position(|c| *c == constant) {
+        return Ok(ty::ConstantIndex(index as u16 + 1));
+    }
+    if file.constant_pool.len() + 1 >= usize::from(u16::MAX) {
+        generic_error!("the constant pool is full");
+    }
+    file.constant_pool.push(constant);
+    Ok(ty::ConstantIndex(file.constant_pool.len() as u16))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{code, read, verify};
+    use super::*;
+
+    use crate::exec::interpreter::Interpreter;
+
+    fn stack_map(code: &attr::Code) -> Vec<StackMapFrame> {
+        code.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::StackMapTable(table) => Some(table.entries.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Removes what is computed, like a rewriter that doesn't know about it would
+    fn strip(code: &mut attr::Code) {
+        code.max_stack = 0;
+        code.max_locals = 0;
+        code.attributes
+            .retain(|attribute| !matches!(attribute, Attribute::StackMapTable(..)));
+    }
+
+    #[test]
+    fn fixtures_compute() {
+        let mut interpreter = Interpreter::default();
+        let mut files = vec![];
+        for entry in std::fs::read_dir("./etc").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(false, |ext| ext == "class") {
+                let data = std::fs::read(&path).unwrap();
+                interpreter
+                    .load_class_from_reader(&mut data.as_slice())
+                    .unwrap();
+                files.push(ty::ClassFile::read(&mut data.as_slice()).unwrap());
+            }
+        }
+
+        for mut file in files {
+            let original = (file.methods.iter())
+                .map(|method| {
+                    method
+                        .get_code()
+                        .map(|code| (code.max_stack, code.max_locals))
+                })
+                .collect::<Vec<_>>();
+            for method in &mut file.methods {
+                for attribute in &mut method.attributes {
+                    if let Attribute::Code(code) = attribute {
+                        strip(code);
+                    }
+                }
+            }
+
+            compute_frames(&mut file, &mut interpreter).unwrap();
+            let computed = (file.methods.iter())
+                .map(|method| {
+                    method
+                        .get_code()
+                        .map(|code| (code.max_stack, code.max_locals))
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(computed, original, "{}", file.get_class_name());
+            if let Err(err) = verify_class(&file, &mut interpreter) {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn compressed_frames() {
+        let mut file = read("verify");
+        strip(code(&mut file, "sum"));
+        let index = file.methods.iter().position(|m| m.name() == "sum").unwrap();
+        compute_method_frames(&mut file, index, &mut Interpreter::default()).unwrap();
+
+        let code = code(&mut file, "sum");
+        assert_eq!((code.max_stack, code.max_locals), (2, 3));
+        // the loop condition, with total and i added, and after the loop, where i is
+        // still an int unlike in what javac writes
+        let expected = vec![
+            StackMapFrame::AppendFrame(attr::AppendFrame {
+                offset: 4,
+                new_locals: vec![VerificationType::Integer, VerificationType::Integer],
+            }),
+            StackMapFrame::SameFrame(attr::SameFrame { offset: 14 }),
+        ];
+        assert_eq!(stack_map(code), expected);
+        verify(&file).unwrap();
+    }
+
+    #[test]
+    fn unreachable_code() {
+        let mut file = read("verify");
+        let id = code(&mut file, "id");
+        id.code = vec![
+            0xA7, 0x00, 0x05, // 0: goto 5
+            0x03, // 3: iconst_0
+            0xAC, // 4: ireturn
+            0x1A, // 5: iload_0
+            0xAC, // 6: ireturn
+        ];
+        strip(id);
+        let index = file.methods.iter().position(|m| m.name() == "id").unwrap();
+        compute_method_frames(&mut file, index, &mut Interpreter::default()).unwrap();
+
+        let code = code(&mut file, "id");
+        assert_eq!(code.code[3..5], [0x00, 0xBF]);
+        assert_eq!((code.max_stack, code.max_locals), (1, 1));
+        let entries = stack_map(code);
+        match &entries[..] {
+            [StackMapFrame::FullFrame(dead), StackMapFrame::AppendFrame(live)] => {
+                assert_eq!((dead.offset, dead.locals.len()), (3, 0));
+                match dead.stack_items[..] {
+                    [VerificationType::Object(class)] => {
+                        assert_eq!(file.class_name(class).unwrap(), "java/lang/Throwable")
+                    }
+                    ref stack => panic!("{:?}", stack),
+                }
+                assert_eq!(live.offset, 1);
+                assert_eq!(live.new_locals, vec![VerificationType::Integer]);
+            }
+            entries => panic!("{:?}", entries),
+        }
+        verify(&file).unwrap();
+    }
+}
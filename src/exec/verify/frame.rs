@@ -0,0 +1,177 @@
+use super::*;
+
+/// The type of a local variable or operand stack entry, as seen by the verifier
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.10.1.2
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    /// `this` in a constructor, before the super class' constructor has been called
+    UninitializedThis,
+    /// An object created by the `new` at the pc, before its constructor has been called
+    Uninitialized(usize),
+    /// An instance of the class with the internal name, or an array with the descriptor
+    Object(String),
+    /// The return address pushed by a `jsr` to the subroutine at the pc, which is only
+    /// seen by the type inferring verifier
+    ReturnAddress(usize),
+}
+
+impl Type {
+    /// The type of a value of a field, parameter or return type
+    pub fn from_field(ty: &FieldType) -> Self {
+        match ty {
+            FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short => {
+                Type::Integer
+            }
+            FieldType::Int => Type::Integer,
+            FieldType::Float => Type::Float,
+            FieldType::Long => Type::Long,
+            FieldType::Double => Type::Double,
+            FieldType::Object(name) => Type::Object(name.clone()),
+            ty @ FieldType::Array(..) => Type::Object(ty.to_string()),
+        }
+    }
+
+    pub fn object(name: &str) -> Self {
+        Type::Object(name.to_string())
+    }
+
+    /// How many slots this type takes up
+    pub fn size(&self) -> usize {
+        match self {
+            Type::Long | Type::Double => 2,
+            _ => 1,
+        }
+    }
+
+    /// Whether this is a class, array, `null` or uninitialized type
+    pub fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            Type::Null | Type::UninitializedThis | Type::Uninitialized(..) | Type::Object(..)
+        )
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, Type::Object(name) if name.starts_with('['))
+    }
+
+    /// The type of the elements of an array of references
+    pub fn component(&self) -> Option<Type> {
+        match self {
+            Type::Object(name) if name.starts_with('[') => {
+                match FieldType::parse(&name[1..]).ok()? {
+                    ty @ FieldType::Object(..) | ty @ FieldType::Array(..) => {
+                        Some(Type::from_field(&ty))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Top => write!(f, "top"),
+            Type::Integer => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Long => write!(f, "long"),
+            Type::Double => write!(f, "double"),
+            Type::Null => write!(f, "null"),
+            Type::UninitializedThis => write!(f, "uninitializedThis"),
+            Type::Uninitialized(pc) => write!(f, "uninitialized({})", pc),
+            Type::Object(name) => write!(f, "{}", name),
+            Type::ReturnAddress(pc) => write!(f, "returnAddress({})", pc),
+        }
+    }
+}
+
+/// The types of the local variables and operand stack before an instruction
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Frame {
+    /// One entry per slot, where longs and doubles are followed by `Top`
+    pub locals: Vec<Type>,
+    /// One entry per value, so longs and doubles take up one entry (but two slots)
+    pub stack: Vec<Type>,
+    /// Whether `this` hasn't been initialized yet, in a constructor
+    pub this_uninit: bool,
+}
+
+impl Frame {
+    /// How many slots the operand stack takes up
+    pub fn stack_size(&self) -> usize {
+        self.stack.iter().map(Type::size).sum()
+    }
+
+    /// Expands locals given one entry per value, like in a `StackMapTable`, to one entry
+    /// per slot padded with `Top` up to `max_locals`
+    pub fn expand_locals(locals: &[Type], max_locals: usize) -> Vec<Type> {
+        let mut expanded = Vec::with_capacity(max_locals);
+        for local in locals {
+            expanded.push(local.clone());
+            if local.size() == 2 {
+                expanded.push(Type::Top);
+            }
+        }
+        if expanded.len() < max_locals {
+            expanded.resize(max_locals, Type::Top);
+        }
+        expanded
+    }
+
+    /// The locals with one entry per value, like in a `StackMapTable`, dropping the `Top`
+    /// after longs and doubles and trailing `Top`s
+    pub fn compressed_locals(&self) -> Vec<Type> {
+        let mut compressed = vec![];
+        let mut slots = self.locals.iter();
+        while let Some(local) = slots.next() {
+            if local.size() == 2 {
+                slots.next();
+            }
+            compressed.push(local.clone());
+        }
+        while compressed.last() == Some(&Type::Top) {
+            compressed.pop();
+        }
+        compressed
+    }
+
+    /// Replaces an uninitialized type, once its constructor has been called
+    pub fn initialize(&mut self, uninitialized: &Type, initialized: &Type) {
+        for ty in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+            if ty == uninitialized {
+                *ty = initialized.clone();
+            }
+        }
+        if *uninitialized == Type::UninitializedThis {
+            self.this_uninit = false;
+        }
+    }
+}
+
+impl std::fmt::Display for Frame {
+    /// Formats the frame like `[int, java/lang/String] [long]`, locals first
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |f: &mut std::fmt::Formatter<'_>, types: &[Type]| {
+            write!(f, "[")?;
+            for (i, ty) in types.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", ty)?;
+            }
+            write!(f, "]")
+        };
+        list(f, &self.locals)?;
+        write!(f, " ")?;
+        list(f, &self.stack)
+    }
+}
@@ -0,0 +1,537 @@
+//! Verification by type inference, for class files without a `StackMapTable`
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.10.2
+use super::*;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The frames inferred so far, and what is known about subroutines
+#[derive(Default)]
+pub(super) struct Inference {
+    /// The frame before each instruction that has been reached
+    pub frames: BTreeMap<usize, Frame>,
+    /// The subroutines each reached instruction belongs to, by their first pc
+    owners: HashMap<usize, BTreeSet<usize>>,
+    /// The instructions whose frame changed since they were last checked
+    changed: BTreeSet<usize>,
+    /// The frame before each `jsr` that has been reached, and the pc it returns to
+    calls: HashMap<usize, (usize, Frame)>,
+    /// The `jsr`s to each subroutine
+    callers: HashMap<usize, Vec<usize>>,
+    /// The `ret`s reached in each subroutine
+    returns: HashMap<usize, BTreeSet<usize>>,
+    /// The locals written to in each subroutine
+    modified: HashMap<usize, BTreeSet<usize>>,
+}
+
+impl Verifier<'_> {
+    pub(super) fn infer(&mut self) -> Check<()> {
+        let instructions = self.decode()?.into_iter().collect();
+        self.inference(&instructions).map(drop)
+    }
+
+    /// Infers the frame before every instruction by iterating until nothing changes
+    pub(super) fn inference(
+        &mut self,
+        instructions: &BTreeMap<usize, Instruction>,
+    ) -> Check<Inference> {
+        self.check_exception_table()?;
+
+        let mut inference = Inference::default();
+        for (&pc, instruction) in instructions {
+            if let Some(target) = self.jsr_target(instruction, pc)? {
+                inference.callers.entry(target).or_default().push(pc);
+            }
+        }
+
+        let initial = self.initial_frame()?;
+        self.pc = 0;
+        self.merge(&mut inference, 0, initial, &BTreeSet::new())?;
+
+        while let Some(pc) = inference.changed.iter().next().copied() {
+            inference.changed.remove(&pc);
+            self.pc = pc;
+            let frame = inference.frames[&pc].clone();
+            let owners = inference.owners[&pc].clone();
+            let instruction = &instructions[&pc];
+            let next = pc + instruction.size();
+
+            for (handler, exception) in self.handlers()? {
+                let caught = Frame {
+                    locals: frame.locals.clone(),
+                    stack: vec![exception],
+                    this_uninit: frame.this_uninit,
+                };
+                self.merge(&mut inference, handler, caught, &owners)?;
+            }
+
+            if let Some(target) = self.jsr_target(instruction, pc)? {
+                if owners.contains(&target) {
+                    fail!("recursive call to the subroutine at {}", target);
+                }
+                inference.calls.insert(pc, (next, frame.clone()));
+                let mut called = frame;
+                if called.stack_size() + 1 > self.max_stack() {
+                    fail!(Reason::StackOverflow {
+                        max_stack: self.max_stack(),
+                    });
+                }
+                called.stack.push(Type::ReturnAddress(target));
+                let mut inner = owners.clone();
+                inner.insert(target);
+                self.merge(&mut inference, target, called, &inner)?;
+
+                // the subroutine may already return, to where this call continues
+                if let Some(returns) = inference.returns.get(&target) {
+                    inference.changed.extend(returns);
+                }
+                continue;
+            }
+
+            if let Some(index) = self.ret_index(instruction) {
+                self.return_from(&mut inference, &frame, index)?;
+                continue;
+            }
+
+            let mut after = frame.clone();
+            let flow = self.execute(instruction, &mut after)?;
+
+            // the handlers see the locals stored by the instruction too
+            if after.locals != frame.locals {
+                for (handler, exception) in self.handlers()? {
+                    let caught = Frame {
+                        locals: after.locals.clone(),
+                        stack: vec![exception],
+                        this_uninit: after.this_uninit,
+                    };
+                    self.merge(&mut inference, handler, caught, &owners)?;
+                }
+            }
+            self.record_stores(&mut inference, &owners, &frame, &after, instruction);
+
+            for target in flow.targets {
+                self.merge(&mut inference, target, after.clone(), &owners)?;
+            }
+            if flow.falls_through {
+                if !instructions.contains_key(&next) {
+                    fail!(Reason::FallsOffEnd);
+                }
+                self.merge(&mut inference, next, after, &owners)?;
+            }
+        }
+        Ok(inference)
+    }
+
+    /// The subroutine called by a `jsr`
+    fn jsr_target(&self, instruction: &Instruction, pc: usize) -> Check<Option<usize>> {
+        let offset = match instruction {
+            Instruction::JSR(JSR(a, b)) => branch_offset(*a, *b) as isize,
+            Instruction::JSR_W(JSR_W(a, b, c, d)) => branch_offset_wide(*a, *b, *c, *d) as isize,
+            _ => return Ok(None),
+        };
+        let target = pc as isize + offset;
+        if target < 0 || !self.starts.get(target as usize).copied().unwrap_or(false) {
+            fail!(Reason::InvalidTarget { target });
+        }
+        Ok(Some(target as usize))
+    }
+
+    /// The local holding the return address of a `ret`
+    fn ret_index(&self, instruction: &Instruction) -> Option<usize> {
+        match instruction {
+            Instruction::RET(RET(index)) => Some(usize::from(*index)),
+            Instruction::WIDE(WIDE(0xA9, a, b, ..)) => Some(usize::from(wide_index(*a, *b))),
+            _ => None,
+        }
+    }
+
+    /// Returns from a subroutine to after each of its callers, with the locals it modified
+    /// and the ones of the caller otherwise
+    fn return_from(&mut self, inference: &mut Inference, frame: &Frame, index: usize) -> Check<()> {
+        if index >= self.max_locals() {
+            fail!(Reason::LocalOutOfRange {
+                index,
+                max_locals: self.max_locals(),
+            });
+        }
+        let subroutine = match frame.locals[index] {
+            Type::ReturnAddress(subroutine) => subroutine,
+            ref actual => fail!(Reason::Type {
+                expected: "returnAddress".to_string(),
+                actual: actual.clone(),
+            }),
+        };
+        inference
+            .returns
+            .entry(subroutine)
+            .or_default()
+            .insert(self.pc);
+
+        let modified = inference
+            .modified
+            .get(&subroutine)
+            .cloned()
+            .unwrap_or_default();
+        let callers = inference
+            .callers
+            .get(&subroutine)
+            .cloned()
+            .unwrap_or_default();
+        for caller in callers {
+            let (next, call) = match inference.calls.get(&caller) {
+                Some(call) => call.clone(),
+                None => continue,
+            };
+            let mut returned = Frame {
+                locals: call.locals.clone(),
+                stack: frame.stack.clone(),
+                this_uninit: frame.this_uninit,
+            };
+            for &local in &modified {
+                returned.locals[local] = frame.locals[local].clone();
+            }
+
+            if !self.starts.get(next).copied().unwrap_or(false) {
+                fail!(Reason::FallsOffEnd);
+            }
+            let owners = inference.owners[&caller].clone();
+            self.merge(inference, next, returned, &owners)?;
+        }
+        Ok(())
+    }
+
+    /// Records the locals an instruction writes to as modified by the subroutines it is in
+    fn record_stores(
+        &self,
+        inference: &mut Inference,
+        owners: &BTreeSet<usize>,
+        before: &Frame,
+        after: &Frame,
+        instruction: &Instruction,
+    ) {
+        let mut stored = (before.locals.iter().zip(&after.locals).enumerate())
+            .filter(|(_, (before, after))| before != after)
+            .map(|(local, _)| local)
+            .collect::<Vec<_>>();
+        // a store of the same type changes nothing, but is still a store
+        if let Some(local) = stored_local(instruction) {
+            stored.push(local);
+            if after.locals[local].size() == 2 {
+                stored.push(local + 1);
+            }
+        }
+
+        for subroutine in owners {
+            let modified = inference.modified.entry(*subroutine).or_default();
+            let count = modified.len();
+            modified.extend(stored.iter().copied());
+            if modified.len() != count {
+                if let Some(returns) = inference.returns.get(subroutine) {
+                    inference.changed.extend(returns);
+                }
+            }
+        }
+    }
+
+    /// Merges `frame` into the frame before `target`, marking it as changed if it is
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.10.2.2
+    fn merge(
+        &mut self,
+        inference: &mut Inference,
+        target: usize,
+        frame: Frame,
+        owners: &BTreeSet<usize>,
+    ) -> Check<()> {
+        let known = inference.owners.entry(target).or_default();
+        let mut changed = !owners.is_subset(known);
+        known.extend(owners.iter().copied());
+
+        let existing = match inference.frames.get(&target) {
+            Some(existing) => existing.clone(),
+            None => {
+                inference.frames.insert(target, frame);
+                inference.changed.insert(target);
+                return Ok(());
+            }
+        };
+
+        if existing.stack.len() != frame.stack.len() {
+            fail!(Reason::StackHeight {
+                target,
+                expected: existing.stack.len(),
+                actual: frame.stack.len(),
+            });
+        }
+
+        let mut merged = Frame {
+            locals: Vec::with_capacity(existing.locals.len()),
+            stack: Vec::with_capacity(existing.stack.len()),
+            this_uninit: existing.this_uninit || frame.this_uninit,
+        };
+        for (expected, actual) in existing.locals.iter().zip(&frame.locals) {
+            let ty = self.merge_types(expected, actual)?.unwrap_or(Type::Top);
+            merged.locals.push(ty);
+        }
+        // a long or double whose second half was lost isn't usable anymore
+        for local in 0..merged.locals.len() {
+            let wide = merged.locals[local].size() == 2;
+            if wide && merged.locals.get(local + 1) != Some(&Type::Top) {
+                merged.locals[local] = Type::Top;
+            }
+        }
+        for (i, (expected, actual)) in existing.stack.iter().zip(&frame.stack).enumerate() {
+            match self.merge_types(expected, actual)? {
+                Some(ty) => merged.stack.push(ty),
+                None => fail!(Reason::Frame {
+                    target,
+                    slot: Slot::Stack(i),
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                }),
+            }
+        }
+
+        if merged != existing {
+            inference.frames.insert(target, merged);
+            changed = true;
+        }
+        if changed {
+            inference.changed.insert(target);
+        }
+        Ok(())
+    }
+
+    /// The type that can hold both `a` and `b`, `None` if there is none but `Top`
+    fn merge_types(&mut self, a: &Type, b: &Type) -> Check<Option<Type>> {
+        let merged = match (a, b) {
+            _ if a == b => Some(a.clone()),
+            (Type::Null, Type::Object(..)) => Some(b.clone()),
+            (Type::Object(..), Type::Null) => Some(a.clone()),
+            (Type::Object(a), Type::Object(b)) => {
+                Some(Type::Object(self.common_super_class(a, b)?))
+            }
+            _ => None,
+        };
+        Ok(merged)
+    }
+
+    /// The least common super class of two classes or arrays. Interfaces are merged to
+    /// `java/lang/Object`, as they are checked at runtime
+    fn common_super_class(&mut self, a: &str, b: &str) -> Check<String> {
+        let object = "java/lang/Object".to_string();
+        if a == b {
+            return Ok(a.to_string());
+        }
+
+        match (Type::object(a).component(), Type::object(b).component()) {
+            (Some(Type::Object(a)), Some(Type::Object(b))) => {
+                let component = self.common_super_class(&a, &b)?;
+                return Ok(match component.starts_with('[') {
+                    true => format!("[{}", component),
+                    false => format!("[L{};", component),
+                });
+            }
+            _ if a.starts_with('[') || b.starts_with('[') => return Ok(object),
+            _ => {}
+        }
+
+        let mut supers = vec![a.to_string()];
+        let mut class = a.to_string();
+        while let Some(super_class) = self.hierarchy.super_class(&class)? {
+            supers.push(super_class.clone());
+            class = super_class;
+        }
+
+        let mut class = b.to_string();
+        loop {
+            if supers.contains(&class) {
+                return Ok(class);
+            }
+            match self.hierarchy.super_class(&class)? {
+                Some(super_class) => class = super_class,
+                None => return Ok(object),
+            }
+        }
+    }
+}
+
+/// The local a store instruction writes to
+fn stored_local(instruction: &Instruction) -> Option<usize> {
+    let local = match instruction {
+        Instruction::ISTORE(ISTORE(index))
+        | Instruction::LSTORE(LSTORE(index))
+        | Instruction::FSTORE(FSTORE(index))
+        | Instruction::DSTORE(DSTORE(index))
+        | Instruction::ASTORE(ASTORE(index))
+        | Instruction::IINC(IINC(index, _)) => usize::from(*index),
+        Instruction::ISTORE_0(..)
+        | Instruction::LSTORE_0(..)
+        | Instruction::FSTORE_0(..)
+        | Instruction::DSTORE_0(..)
+        | Instruction::ASTORE_0(..) => 0,
+        Instruction::ISTORE_1(..)
+        | Instruction::LSTORE_1(..)
+        | Instruction::FSTORE_1(..)
+        | Instruction::DSTORE_1(..)
+        | Instruction::ASTORE_1(..) => 1,
+        Instruction::ISTORE_2(..)
+        | Instruction::LSTORE_2(..)
+        | Instruction::FSTORE_2(..)
+        | Instruction::DSTORE_2(..)
+        | Instruction::ASTORE_2(..) => 2,
+        Instruction::ISTORE_3(..)
+        | Instruction::LSTORE_3(..)
+        | Instruction::FSTORE_3(..)
+        | Instruction::DSTORE_3(..)
+        | Instruction::ASTORE_3(..) => 3,
+        Instruction::WIDE(WIDE(0x36..=0x3A, a, b, ..))
+        | Instruction::WIDE(WIDE(0x84, a, b, ..)) => usize::from(wide_index(*a, *b)),
+        _ => return None,
+    };
+    Some(local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{code, read, reason, verify};
+    use super::*;
+
+    use crate::exec::value::Value;
+    use attr::Attribute;
+    use std::rc::Rc;
+
+    /// Makes a class file look like it was compiled for Java 5, without stack map frames
+    fn downgrade(file: &mut ty::ClassFile) {
+        file.major_version = 49;
+        for method in &mut file.methods {
+            for attribute in &mut method.attributes {
+                if let Attribute::Code(code) = attribute {
+                    code.attributes
+                        .retain(|attribute| !matches!(attribute, Attribute::StackMapTable(..)));
+                }
+            }
+        }
+    }
+
+    /// `static int id(int x)` rewritten to call a subroutine, like `finally` once did,
+    /// from two places where local 1 has different types
+    fn subroutine(file: &mut ty::ClassFile) -> &mut attr::Code {
+        let code = code(file, "id");
+        code.code = vec![
+            0x1A, // 0: iload_0
+            0x99, 0x00, 0x0A, // 1: ifeq 11
+            0x04, // 4: iconst_1
+            0x3C, // 5: istore_1
+            0xA8, 0x00, 0x0E, // 6: jsr 20
+            0x1B, // 9: iload_1
+            0xAC, // 10: ireturn
+            0x01, // 11: aconst_null
+            0x4C, // 12: astore_1
+            0xA8, 0x00, 0x07, // 13: jsr 20
+            0x2B, // 16: aload_1
+            0x57, // 17: pop
+            0x1A, // 18: iload_0
+            0xAC, // 19: ireturn
+            0x4D, // 20: astore_2
+            0x84, 0x00, 0x01, // 21: iinc 0 1
+            0xA9, 0x02, // 24: ret 2
+        ];
+        code.max_stack = 1;
+        code.max_locals = 3;
+        code
+    }
+
+    #[test]
+    fn fixtures_infer() {
+        let mut interpreter = Interpreter::default();
+        let mut files = vec![];
+        for entry in std::fs::read_dir("./etc").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(false, |ext| ext == "class") {
+                let data = std::fs::read(&path).unwrap();
+                interpreter
+                    .load_class_from_reader(&mut data.as_slice())
+                    .unwrap();
+                let mut file = ty::ClassFile::read(&mut data.as_slice()).unwrap();
+                downgrade(&mut file);
+                files.push(file);
+            }
+        }
+        for file in files {
+            if let Err(err) = verify_class(&file, &mut interpreter) {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut file = read("verify");
+        downgrade(&mut file);
+        code(&mut file, "id").code[0] = 0x01;
+        let expected = Reason::Type {
+            expected: "int".to_string(),
+            actual: Type::Null,
+        };
+        assert_eq!(reason(&file, "id", 1), expected);
+
+        // aload_2 -> iload_0, so a String and an int meet at the areturn
+        let mut file = read("verify");
+        downgrade(&mut file);
+        code(&mut file, "pick").code[8] = 0x1A;
+        let expected = Reason::Frame {
+            target: 9,
+            slot: Slot::Stack(0),
+            expected: Type::object("java/lang/String"),
+            actual: Type::Integer,
+        };
+        assert_eq!(reason(&file, "pick", 8), expected);
+    }
+
+    #[test]
+    fn merge_references() {
+        let mut file = read("verify");
+        downgrade(&mut file);
+        // areturn -> arraylength, of the Object that String and Integer merge to
+        code(&mut file, "pick").code[9] = 0xBE;
+        let expected = Reason::Type {
+            expected: "array".to_string(),
+            actual: Type::object("java/lang/Object"),
+        };
+        assert_eq!(reason(&file, "pick", 9), expected);
+    }
+
+    #[test]
+    fn subroutines() {
+        let mut file = read("verify");
+        downgrade(&mut file);
+        subroutine(&mut file);
+        verify(&file).unwrap();
+
+        let mut interpreter = Interpreter::default();
+        interpreter.load_class(Rc::new(file));
+        let id = interpreter.invoke_static("verify", "id", "(I)I", &[Value::Int(5)]);
+        assert_eq!(id.unwrap(), Some(Value::Int(1)));
+        let id = interpreter.invoke_static("verify", "id", "(I)I", &[Value::Int(0)]);
+        assert_eq!(id.unwrap(), Some(Value::Int(1)));
+
+        // iinc 0 1 -> iconst_0, istore_1, nop, so local 1 is an int after both calls
+        let mut file = read("verify");
+        downgrade(&mut file);
+        subroutine(&mut file).code[21..24].copy_from_slice(&[0x03, 0x3C, 0x00]);
+        let expected = Reason::Type {
+            expected: "reference".to_string(),
+            actual: Type::Integer,
+        };
+        assert_eq!(reason(&file, "id", 16), expected);
+
+        // ret 2 -> ret 0, which holds an int
+        let mut file = read("verify");
+        downgrade(&mut file);
+        subroutine(&mut file).code[25] = 0;
+        let expected = Reason::Type {
+            expected: "returnAddress".to_string(),
+            actual: Type::Integer,
+        };
+        assert_eq!(reason(&file, "id", 24), expected);
+    }
+}
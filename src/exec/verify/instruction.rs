@@ -0,0 +1,886 @@
+//! How each instruction changes the types of the frame
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.10.1.9
+use super::*;
+
+use ty::Constant;
+
+/// Where execution can continue after an instruction
+#[derive(Debug, Default)]
+pub(super) struct Flow {
+    /// The pcs of the instructions it can jump to
+    pub targets: Vec<usize>,
+    /// Whether the next instruction can follow it
+    pub falls_through: bool,
+}
+
+impl Flow {
+    fn next() -> Self {
+        Self {
+            targets: vec![],
+            falls_through: true,
+        }
+    }
+
+    fn stop() -> Self {
+        Self::default()
+    }
+
+    fn branch(target: usize) -> Self {
+        Self {
+            targets: vec![target],
+            falls_through: true,
+        }
+    }
+
+    fn jump(targets: Vec<usize>) -> Self {
+        Self {
+            targets,
+            falls_through: false,
+        }
+    }
+}
+
+impl Verifier<'_> {
+    fn pop(&mut self, frame: &mut Frame) -> Check<Type> {
+        match frame.stack.pop() {
+            Some(ty) => Ok(ty),
+            None => fail!(Reason::StackUnderflow),
+        }
+    }
+
+    /// Pops a value that can be used as an `expected`
+    fn pop_type(&mut self, frame: &mut Frame, expected: &Type) -> Check<Type> {
+        let actual = self.pop(frame)?;
+        if !self.is_assignable(&actual, expected)? {
+            fail!(Reason::Type {
+                expected: expected.to_string(),
+                actual,
+            });
+        }
+        Ok(actual)
+    }
+
+    /// Pops values off of the stack, the top one last
+    fn pop_types(&mut self, frame: &mut Frame, expected: &[Type]) -> Check<()> {
+        for expected in expected.iter().rev() {
+            self.pop_type(frame, expected)?;
+        }
+        Ok(())
+    }
+
+    /// Pops any reference, including uninitialized ones
+    fn pop_reference(&mut self, frame: &mut Frame) -> Check<Type> {
+        let actual = self.pop(frame)?;
+        if !actual.is_reference() {
+            fail!(Reason::Type {
+                expected: "reference".to_string(),
+                actual,
+            });
+        }
+        Ok(actual)
+    }
+
+    /// Pops an initialized reference
+    fn pop_object(&mut self, frame: &mut Frame) -> Check<Type> {
+        self.pop_type(frame, &Type::object("java/lang/Object"))
+    }
+
+    /// Pops an array (or `null`) that has one of the `descriptors`
+    fn pop_array(&mut self, frame: &mut Frame, descriptors: &[&str]) -> Check<Type> {
+        let actual = self.pop(frame)?;
+        match &actual {
+            Type::Null => {}
+            Type::Object(name) if descriptors.contains(&name.as_str()) => {}
+            _ => fail!(Reason::Type {
+                expected: descriptors.join(" or "),
+                actual,
+            }),
+        }
+        Ok(actual)
+    }
+
+    /// Pops an array of references (or `null`)
+    fn pop_reference_array(&mut self, frame: &mut Frame) -> Check<Type> {
+        let actual = self.pop(frame)?;
+        if actual != Type::Null && actual.component().is_none() {
+            fail!(Reason::Type {
+                expected: "array of references".to_string(),
+                actual,
+            });
+        }
+        Ok(actual)
+    }
+
+    /// Pops values that take up exactly `slots` slots, returning them bottom first
+    fn pop_slots(&mut self, frame: &mut Frame, slots: usize) -> Check<Vec<Type>> {
+        let mut values = vec![];
+        let mut size = 0;
+        while size < slots {
+            let value = self.pop(frame)?;
+            size += value.size();
+            values.insert(0, value);
+        }
+        if size != slots {
+            fail!(Reason::Type {
+                expected: format!("category {} value", slots - (size - 2)),
+                actual: values.remove(0),
+            });
+        }
+        Ok(values)
+    }
+
+    fn push(&mut self, frame: &mut Frame, ty: Type) -> Check<()> {
+        if frame.stack_size() + ty.size() > self.max_stack() {
+            fail!(Reason::StackOverflow {
+                max_stack: self.max_stack(),
+            });
+        }
+        frame.stack.push(ty);
+        Ok(())
+    }
+
+    fn push_all(&mut self, frame: &mut Frame, values: Vec<Type>) -> Check<()> {
+        for value in values {
+            self.push(frame, value)?;
+        }
+        Ok(())
+    }
+
+    fn check_local(&self, index: usize, ty: &Type) -> Check<()> {
+        if index + ty.size() > self.max_locals() {
+            fail!(Reason::LocalOutOfRange {
+                index: index + ty.size() - 1,
+                max_locals: self.max_locals(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Pushes the local at `index`, which has to be an `expected`
+    fn load(&mut self, frame: &mut Frame, index: usize, expected: Type) -> Check<()> {
+        self.check_local(index, &expected)?;
+        let actual = frame.locals[index].clone();
+        if !self.is_assignable(&actual, &expected)? {
+            fail!(Reason::Type {
+                expected: expected.to_string(),
+                actual,
+            });
+        }
+        self.push(frame, expected)
+    }
+
+    /// Increments the int in the local at `index`, which leaves the stack alone
+    fn iinc(&mut self, frame: &Frame, index: usize) -> Check<Flow> {
+        self.check_local(index, &Type::Integer)?;
+        if frame.locals[index] != Type::Integer {
+            fail!(Reason::Type {
+                expected: Type::Integer.to_string(),
+                actual: frame.locals[index].clone(),
+            });
+        }
+        Ok(Flow::next())
+    }
+
+    /// Pushes the reference in the local at `index`
+    fn load_reference(&mut self, frame: &mut Frame, index: usize) -> Check<()> {
+        self.check_local(index, &Type::Top)?;
+        let actual = frame.locals[index].clone();
+        if !actual.is_reference() {
+            fail!(Reason::Type {
+                expected: "reference".to_string(),
+                actual,
+            });
+        }
+        self.push(frame, actual)
+    }
+
+    /// Stores `ty` in the local at `index`, which invalidates a long or double that
+    /// overlaps it
+    pub(super) fn set_local(&self, frame: &mut Frame, index: usize, ty: Type) -> Check<()> {
+        self.check_local(index, &ty)?;
+        if index > 0 && frame.locals[index - 1].size() == 2 {
+            frame.locals[index - 1] = Type::Top;
+        }
+        if ty.size() == 2 {
+            frame.locals[index + 1] = Type::Top;
+        }
+        frame.locals[index] = ty;
+        Ok(())
+    }
+
+    fn store(&mut self, frame: &mut Frame, index: usize, expected: Type) -> Check<()> {
+        let actual = self.pop_type(frame, &expected)?;
+        let ty = if expected == Type::Top {
+            actual
+        } else {
+            expected
+        };
+        self.set_local(frame, index, ty)
+    }
+
+    /// Stores a reference (or a return address) in the local at `index`
+    fn store_reference(&mut self, frame: &mut Frame, index: usize) -> Check<()> {
+        let actual = match frame.stack.last() {
+            Some(Type::ReturnAddress(..)) => self.pop(frame)?,
+            _ => self.pop_reference(frame)?,
+        };
+        self.set_local(frame, index, actual)
+    }
+
+    fn unary(&mut self, frame: &mut Frame, operand: Type, result: Type) -> Check<Flow> {
+        self.pop_type(frame, &operand)?;
+        self.push(frame, result)?;
+        Ok(Flow::next())
+    }
+
+    fn binary(&mut self, frame: &mut Frame, operands: [Type; 2], result: Type) -> Check<Flow> {
+        self.pop_types(frame, &operands)?;
+        self.push(frame, result)?;
+        Ok(Flow::next())
+    }
+
+    fn array_load(&mut self, frame: &mut Frame, descriptors: &[&str], ty: Type) -> Check<Flow> {
+        self.pop_type(frame, &Type::Integer)?;
+        self.pop_array(frame, descriptors)?;
+        self.push(frame, ty)?;
+        Ok(Flow::next())
+    }
+
+    fn array_store(&mut self, frame: &mut Frame, descriptors: &[&str], ty: Type) -> Check<Flow> {
+        self.pop_type(frame, &ty)?;
+        self.pop_type(frame, &Type::Integer)?;
+        self.pop_array(frame, descriptors)?;
+        Ok(Flow::next())
+    }
+
+    fn if_branch(&mut self, frame: &mut Frame, operands: &[Type], a: u8, b: u8) -> Check<Flow> {
+        self.pop_types(frame, operands)?;
+        Ok(Flow::branch(self.target(branch_offset(a, b) as isize)?))
+    }
+
+    fn if_acmp(&mut self, frame: &mut Frame, a: u8, b: u8) -> Check<Flow> {
+        self.pop_reference(frame)?;
+        self.pop_reference(frame)?;
+        Ok(Flow::branch(self.target(branch_offset(a, b) as isize)?))
+    }
+
+    fn ret(&mut self, frame: &mut Frame, ty: Option<Type>) -> Check<Flow> {
+        let expected = self.signature.ret.as_ref().map(Type::from_field);
+        match (ty, expected) {
+            (None, None) => {
+                if self.is_constructor() && frame.this_uninit {
+                    fail!("returns before the constructor of the super class is called");
+                }
+            }
+            (Some(ty), Some(expected)) => {
+                let is_reference = ty == Type::object("java/lang/Object");
+                if !is_reference && ty != expected {
+                    fail!(Reason::Type {
+                        expected: expected.to_string(),
+                        actual: ty,
+                    });
+                }
+                self.pop_type(frame, &expected)?;
+            }
+            (_, expected) => fail!(
+                "wrong return instruction for return type {}",
+                expected.map_or("void".to_string(), |ty| ty.to_string())
+            ),
+        }
+        Ok(Flow::stop())
+    }
+
+    /// The class named by the `ClassRef` at `index`
+    fn class_ref(&self, index: u16) -> Check<&str> {
+        Ok(self.file.class_name(ty::ConstantIndex(index))?)
+    }
+
+    /// The type of a loadable constant
+    fn constant(&self, index: u16, wide: bool) -> Check<Type> {
+        let ty = match (
+            ty::ConstantIndex(index).lookup(&self.file.constant_pool)?,
+            wide,
+        ) {
+            (Constant::Integer(..), false) => Type::Integer,
+            (Constant::Float(..), false) => Type::Float,
+            (Constant::StringRef(..), false) => Type::object("java/lang/String"),
+            (Constant::ClassRef(..), false) => Type::object("java/lang/Class"),
+            (Constant::MethodType(..), false) => Type::object("java/lang/invoke/MethodType"),
+            (Constant::MethodHandleRef(..), false) => Type::object("java/lang/invoke/MethodHandle"),
+            (Constant::Long(..), true) => Type::Long,
+            (Constant::Double(..), true) => Type::Double,
+            (constant, _) => fail!("constant {} can not be loaded: {:?}", index, constant),
+        };
+        Ok(ty)
+    }
+
+    fn get_field(&mut self, frame: &mut Frame, index: u16, is_static: bool) -> Check<Flow> {
+        let field = self.file.member_ref(ty::ConstantIndex(index))?;
+        let ty = Type::from_field(&FieldType::parse(field.descriptor)?);
+        if !is_static {
+            self.pop_type(frame, &Type::object(field.class))?;
+        }
+        self.push(frame, ty)?;
+        Ok(Flow::next())
+    }
+
+    fn put_field(&mut self, frame: &mut Frame, index: u16, is_static: bool) -> Check<Flow> {
+        let field = self.file.member_ref(ty::ConstantIndex(index))?;
+        self.pop_type(
+            frame,
+            &Type::from_field(&FieldType::parse(field.descriptor)?),
+        )?;
+        if is_static {
+            return Ok(Flow::next());
+        }
+
+        // a constructor can set the fields declared by its class before calling the super
+        // class' constructor, like javac does for the outer instance of inner classes
+        let declared = || {
+            self.file.fields.iter().any(|declared| {
+                self.file.utf8(declared.name).ok() == Some(field.name)
+                    && self.file.utf8(declared.descriptor).ok() == Some(field.descriptor)
+            })
+        };
+        if frame.stack.last() == Some(&Type::UninitializedThis)
+            && field.class == self.class
+            && declared()
+        {
+            self.pop(frame)?;
+        } else {
+            self.pop_type(frame, &Type::object(field.class))?;
+        }
+        Ok(Flow::next())
+    }
+
+    fn invoke(&mut self, frame: &mut Frame, index: u16, opcode: u8) -> Check<Flow> {
+        let (class, name, descriptor) = match opcode {
+            // invokedynamic
+            0xBA => match ty::ConstantIndex(index).lookup(&self.file.constant_pool)? {
+                Constant::InvokeDynamicRef(call_site) => {
+                    let (name, descriptor) = self.file.name_and_type(call_site.name_and_type)?;
+                    (None, name, descriptor)
+                }
+                constant => fail!("invokedynamic of {:?}", constant),
+            },
+            _ => {
+                let method = self.file.member_ref(ty::ConstantIndex(index))?;
+                (Some(method.class), method.name, method.descriptor)
+            }
+        };
+        let signature = MethodDescriptor::parse(descriptor)?;
+
+        let is_init = name == "<init>";
+        if name.starts_with('<') && !(is_init && opcode == 0xB7) {
+            fail!(
+                "{} can not be invoked by {}",
+                name,
+                self.instruction_name(opcode)
+            );
+        }
+
+        for param in signature.params.iter().rev() {
+            self.pop_type(frame, &Type::from_field(param))?;
+        }
+
+        match (opcode, class) {
+            // invokestatic, invokedynamic
+            (0xB8, _) | (0xBA, _) | (_, None) => {}
+            (0xB7, Some(class)) if is_init => {
+                if signature.ret.is_some() {
+                    fail!("a constructor has to return void");
+                }
+                let object = self.pop_reference(frame)?;
+                let initialized = match &object {
+                    Type::UninitializedThis => {
+                        let super_class = self.file.super_class_name()?;
+                        if class != self.class && Some(class) != super_class {
+                            fail!("{}.<init> can not initialize this of {}", class, self.class);
+                        }
+                        Type::object(self.class)
+                    }
+                    Type::Uninitialized(new) => {
+                        let created = match Instruction::decode(&self.code.code, *new) {
+                            Some(Instruction::NEW(NEW(a, b))) => {
+                                self.class_ref(wide_index(a, b))?
+                            }
+                            _ => fail!("uninitialized({}) is not created by new", new),
+                        };
+                        if created != class {
+                            fail!(
+                                "{}.<init> can not initialize an instance of {}",
+                                class,
+                                created
+                            );
+                        }
+                        Type::object(class)
+                    }
+                    _ => fail!(Reason::Type {
+                        expected: "uninitialized object".to_string(),
+                        actual: object,
+                    }),
+                };
+                frame.initialize(&object, &initialized);
+            }
+            // invokespecial of a private or super class method needs an instance of this class
+            (0xB7, Some(..)) => {
+                self.pop_type(frame, &Type::object(self.class))?;
+            }
+            (_, Some(class)) => {
+                self.pop_type(frame, &Type::object(class))?;
+            }
+        }
+
+        if let Some(ret) = &signature.ret {
+            self.push(frame, Type::from_field(ret))?;
+        }
+        Ok(Flow::next())
+    }
+
+    fn instruction_name(&self, opcode: u8) -> String {
+        Instruction::lookup(opcode).map_or_else(|| format!("0x{:02X}", opcode), |i| i.to_string())
+    }
+
+    /// Checks the instruction at the current pc, and applies it to `frame`
+    pub(super) fn execute(&mut self, instruction: &Instruction, frame: &mut Frame) -> Check<Flow> {
+        use Type::{Double as D, Float as F, Integer as I, Long as J};
+
+        let flow = match instruction {
+            Instruction::NOP(..) => Flow::next(),
+            Instruction::ACONST_NULL(..) => {
+                self.push(frame, Type::Null)?;
+                Flow::next()
+            }
+            Instruction::ICONST_M1(..)
+            | Instruction::ICONST_0(..)
+            | Instruction::ICONST_1(..)
+            | Instruction::ICONST_2(..)
+            | Instruction::ICONST_3(..)
+            | Instruction::ICONST_4(..)
+            | Instruction::ICONST_5(..)
+            | Instruction::BIPUSH(..)
+            | Instruction::SIPUSH(..) => {
+                self.push(frame, I)?;
+                Flow::next()
+            }
+            Instruction::LCONST_0(..) | Instruction::LCONST_1(..) => {
+                self.push(frame, J)?;
+                Flow::next()
+            }
+            Instruction::FCONST_0(..) | Instruction::FCONST_1(..) | Instruction::FCONST_2(..) => {
+                self.push(frame, F)?;
+                Flow::next()
+            }
+            Instruction::DCONST_0(..) | Instruction::DCONST_1(..) => {
+                self.push(frame, D)?;
+                Flow::next()
+            }
+            Instruction::LDC(LDC(index)) => {
+                let ty = self.constant(u16::from(*index), false)?;
+                self.push(frame, ty)?;
+                Flow::next()
+            }
+            Instruction::LDC_W(LDC_W(a, b)) => {
+                let ty = self.constant(wide_index(*a, *b), false)?;
+                self.push(frame, ty)?;
+                Flow::next()
+            }
+            Instruction::LDC2_W(LDC2_W(a, b)) => {
+                let ty = self.constant(wide_index(*a, *b), true)?;
+                self.push(frame, ty)?;
+                Flow::next()
+            }
+            //
+            Instruction::ILOAD(ILOAD(index)) => self.load_op(frame, 0x15, usize::from(*index))?,
+            Instruction::LLOAD(LLOAD(index)) => self.load_op(frame, 0x16, usize::from(*index))?,
+            Instruction::FLOAD(FLOAD(index)) => self.load_op(frame, 0x17, usize::from(*index))?,
+            Instruction::DLOAD(DLOAD(index)) => self.load_op(frame, 0x18, usize::from(*index))?,
+            Instruction::ALOAD(ALOAD(index)) => self.load_op(frame, 0x19, usize::from(*index))?,
+            Instruction::ILOAD_0(..) => self.load_op(frame, 0x15, 0)?,
+            Instruction::ILOAD_1(..) => self.load_op(frame, 0x15, 1)?,
+            Instruction::ILOAD_2(..) => self.load_op(frame, 0x15, 2)?,
+            Instruction::ILOAD_3(..) => self.load_op(frame, 0x15, 3)?,
+            Instruction::LLOAD_0(..) => self.load_op(frame, 0x16, 0)?,
+            Instruction::LLOAD_1(..) => self.load_op(frame, 0x16, 1)?,
+            Instruction::LLOAD_2(..) => self.load_op(frame, 0x16, 2)?,
+            Instruction::LLOAD_3(..) => self.load_op(frame, 0x16, 3)?,
+            Instruction::FLOAD_0(..) => self.load_op(frame, 0x17, 0)?,
+            Instruction::FLOAD_1(..) => self.load_op(frame, 0x17, 1)?,
+            Instruction::FLOAD_2(..) => self.load_op(frame, 0x17, 2)?,
+            Instruction::FLOAD_3(..) => self.load_op(frame, 0x17, 3)?,
+            Instruction::DLOAD_0(..) => self.load_op(frame, 0x18, 0)?,
+            Instruction::DLOAD_1(..) => self.load_op(frame, 0x18, 1)?,
+            Instruction::DLOAD_2(..) => self.load_op(frame, 0x18, 2)?,
+            Instruction::DLOAD_3(..) => self.load_op(frame, 0x18, 3)?,
+            Instruction::ALOAD_0(..) => self.load_op(frame, 0x19, 0)?,
+            Instruction::ALOAD_1(..) => self.load_op(frame, 0x19, 1)?,
+            Instruction::ALOAD_2(..) => self.load_op(frame, 0x19, 2)?,
+            Instruction::ALOAD_3(..) => self.load_op(frame, 0x19, 3)?,
+            //
+            Instruction::IALOAD(..) => self.array_load(frame, &["[I"], I)?,
+            Instruction::LALOAD(..) => self.array_load(frame, &["[J"], J)?,
+            Instruction::FALOAD(..) => self.array_load(frame, &["[F"], F)?,
+            Instruction::DALOAD(..) => self.array_load(frame, &["[D"], D)?,
+            Instruction::BALOAD(..) => self.array_load(frame, &["[B", "[Z"], I)?,
+            Instruction::CALOAD(..) => self.array_load(frame, &["[C"], I)?,
+            Instruction::SALOAD(..) => self.array_load(frame, &["[S"], I)?,
+            Instruction::AALOAD(..) => {
+                self.pop_type(frame, &I)?;
+                let array = self.pop_reference_array(frame)?;
+                self.push(frame, array.component().unwrap_or(Type::Null))?;
+                Flow::next()
+            }
+            //
+            Instruction::ISTORE(ISTORE(index)) => {
+                self.store_op(frame, 0x36, usize::from(*index))?
+            }
+            Instruction::LSTORE(LSTORE(index)) => {
+                self.store_op(frame, 0x37, usize::from(*index))?
+            }
+            Instruction::FSTORE(FSTORE(index)) => {
+                self.store_op(frame, 0x38, usize::from(*index))?
+            }
+            Instruction::DSTORE(DSTORE(index)) => {
+                self.store_op(frame, 0x39, usize::from(*index))?
+            }
+            Instruction::ASTORE(ASTORE(index)) => {
+                self.store_op(frame, 0x3A, usize::from(*index))?
+            }
+            Instruction::ISTORE_0(..) => self.store_op(frame, 0x36, 0)?,
+            Instruction::ISTORE_1(..) => self.store_op(frame, 0x36, 1)?,
+            Instruction::ISTORE_2(..) => self.store_op(frame, 0x36, 2)?,
+            Instruction::ISTORE_3(..) => self.store_op(frame, 0x36, 3)?,
+            Instruction::LSTORE_0(..) => self.store_op(frame, 0x37, 0)?,
+            Instruction::LSTORE_1(..) => self.store_op(frame, 0x37, 1)?,
+            Instruction::LSTORE_2(..) => self.store_op(frame, 0x37, 2)?,
+            Instruction::LSTORE_3(..) => self.store_op(frame, 0x37, 3)?,
+            Instruction::FSTORE_0(..) => self.store_op(frame, 0x38, 0)?,
+            Instruction::FSTORE_1(..) => self.store_op(frame, 0x38, 1)?,
+            Instruction::FSTORE_2(..) => self.store_op(frame, 0x38, 2)?,
+            Instruction::FSTORE_3(..) => self.store_op(frame, 0x38, 3)?,
+            Instruction::DSTORE_0(..) => self.store_op(frame, 0x39, 0)?,
+            Instruction::DSTORE_1(..) => self.store_op(frame, 0x39, 1)?,
+            Instruction::DSTORE_2(..) => self.store_op(frame, 0x39, 2)?,
+            Instruction::DSTORE_3(..) => self.store_op(frame, 0x39, 3)?,
+            Instruction::ASTORE_0(..) => self.store_op(frame, 0x3A, 0)?,
+            Instruction::ASTORE_1(..) => self.store_op(frame, 0x3A, 1)?,
+            Instruction::ASTORE_2(..) => self.store_op(frame, 0x3A, 2)?,
+            Instruction::ASTORE_3(..) => self.store_op(frame, 0x3A, 3)?,
+            //
+            Instruction::IASTORE(..) => self.array_store(frame, &["[I"], I)?,
+            Instruction::LASTORE(..) => self.array_store(frame, &["[J"], J)?,
+            Instruction::FASTORE(..) => self.array_store(frame, &["[F"], F)?,
+            Instruction::DASTORE(..) => self.array_store(frame, &["[D"], D)?,
+            Instruction::BASTORE(..) => self.array_store(frame, &["[B", "[Z"], I)?,
+            Instruction::CASTORE(..) => self.array_store(frame, &["[C"], I)?,
+            Instruction::SASTORE(..) => self.array_store(frame, &["[S"], I)?,
+            Instruction::AASTORE(..) => {
+                // whether the value fits the array is checked at runtime
+                self.pop_object(frame)?;
+                self.pop_type(frame, &I)?;
+                self.pop_reference_array(frame)?;
+                Flow::next()
+            }
+            //
+            Instruction::POP(..) => {
+                self.pop_slots(frame, 1)?;
+                Flow::next()
+            }
+            Instruction::POP2(..) => {
+                self.pop_slots(frame, 2)?;
+                Flow::next()
+            }
+            Instruction::DUP(..) => self.dup(frame, 1, 0)?,
+            Instruction::DUP_X1(..) => self.dup(frame, 1, 1)?,
+            Instruction::DUP_X2(..) => self.dup(frame, 1, 2)?,
+            Instruction::DUP2(..) => self.dup(frame, 2, 0)?,
+            Instruction::DUP2_X1(..) => self.dup(frame, 2, 1)?,
+            Instruction::DUP2_X2(..) => self.dup(frame, 2, 2)?,
+            Instruction::SWAP(..) => {
+                let top = self.pop_slots(frame, 1)?;
+                let below = self.pop_slots(frame, 1)?;
+                self.push_all(frame, top)?;
+                self.push_all(frame, below)?;
+                Flow::next()
+            }
+            //
+            Instruction::IADD(..)
+            | Instruction::ISUB(..)
+            | Instruction::IMUL(..)
+            | Instruction::IDIV(..)
+            | Instruction::IREM(..)
+            | Instruction::ISHL(..)
+            | Instruction::ISHR(..)
+            | Instruction::IUSHR(..)
+            | Instruction::IAND(..)
+            | Instruction::IOR(..)
+            | Instruction::IXOR(..) => self.binary(frame, [I, I], I)?,
+            Instruction::LADD(..)
+            | Instruction::LSUB(..)
+            | Instruction::LMUL(..)
+            | Instruction::LDIV(..)
+            | Instruction::LREM(..)
+            | Instruction::LAND(..)
+            | Instruction::LOR(..)
+            | Instruction::LXOR(..) => self.binary(frame, [J, J], J)?,
+            Instruction::LSHL(..) | Instruction::LSHR(..) | Instruction::LUSHR(..) => {
+                self.binary(frame, [J, I], J)?
+            }
+            Instruction::FADD(..)
+            | Instruction::FSUB(..)
+            | Instruction::FMUL(..)
+            | Instruction::FDIV(..)
+            | Instruction::FREM(..) => self.binary(frame, [F, F], F)?,
+            Instruction::DADD(..)
+            | Instruction::DSUB(..)
+            | Instruction::DMUL(..)
+            | Instruction::DDIV(..)
+            | Instruction::DREM(..) => self.binary(frame, [D, D], D)?,
+            Instruction::INEG(..) => self.unary(frame, I, I)?,
+            Instruction::LNEG(..) => self.unary(frame, J, J)?,
+            Instruction::FNEG(..) => self.unary(frame, F, F)?,
+            Instruction::DNEG(..) => self.unary(frame, D, D)?,
+            Instruction::IINC(IINC(index, _)) => self.iinc(frame, usize::from(*index))?,
+            //
+            Instruction::I2L(..) => self.unary(frame, I, J)?,
+            Instruction::I2F(..) => self.unary(frame, I, F)?,
+            Instruction::I2D(..) => self.unary(frame, I, D)?,
+            Instruction::L2I(..) => self.unary(frame, J, I)?,
+            Instruction::L2F(..) => self.unary(frame, J, F)?,
+            Instruction::L2D(..) => self.unary(frame, J, D)?,
+            Instruction::F2I(..) => self.unary(frame, F, I)?,
+            Instruction::F2L(..) => self.unary(frame, F, J)?,
+            Instruction::F2D(..) => self.unary(frame, F, D)?,
+            Instruction::D2I(..) => self.unary(frame, D, I)?,
+            Instruction::D2L(..) => self.unary(frame, D, J)?,
+            Instruction::D2F(..) => self.unary(frame, D, F)?,
+            Instruction::I2B(..) | Instruction::I2C(..) | Instruction::I2S(..) => {
+                self.unary(frame, I, I)?
+            }
+            //
+            Instruction::LCMP(..) => self.binary(frame, [J, J], I)?,
+            Instruction::FCMPL(..) | Instruction::FCMPG(..) => self.binary(frame, [F, F], I)?,
+            Instruction::DCMPL(..) | Instruction::DCMPG(..) => self.binary(frame, [D, D], I)?,
+            //
+            Instruction::IFEQ(IFEQ(a, b)) => self.if_branch(frame, &[I], *a, *b)?,
+            Instruction::IFNE(IFNE(a, b)) => self.if_branch(frame, &[I], *a, *b)?,
+            Instruction::IFLT(IFLT(a, b)) => self.if_branch(frame, &[I], *a, *b)?,
+            Instruction::IFGE(IFGE(a, b)) => self.if_branch(frame, &[I], *a, *b)?,
+            Instruction::IFGT(IFGT(a, b)) => self.if_branch(frame, &[I], *a, *b)?,
+            Instruction::IFLE(IFLE(a, b)) => self.if_branch(frame, &[I], *a, *b)?,
+            Instruction::IF_ICMPEQ(IF_ICMPEQ(a, b)) => self.if_branch(frame, &[I, I], *a, *b)?,
+            Instruction::IF_ICMPNE(IF_ICMPNE(a, b)) => self.if_branch(frame, &[I, I], *a, *b)?,
+            Instruction::IF_ICMPLT(IF_ICMPLT(a, b)) => self.if_branch(frame, &[I, I], *a, *b)?,
+            Instruction::IF_ICMPGE(IF_ICMPGE(a, b)) => self.if_branch(frame, &[I, I], *a, *b)?,
+            Instruction::IF_ICMPGT(IF_ICMPGT(a, b)) => self.if_branch(frame, &[I, I], *a, *b)?,
+            Instruction::IF_ICMPLE(IF_ICMPLE(a, b)) => self.if_branch(frame, &[I, I], *a, *b)?,
+            Instruction::IF_ACMPEQ(IF_ACMPEQ(a, b)) => self.if_acmp(frame, *a, *b)?,
+            Instruction::IF_ACMPNE(IF_ACMPNE(a, b)) => self.if_acmp(frame, *a, *b)?,
+            Instruction::IFNULL(IFNULL(a, b)) | Instruction::IFNONNULL(IFNONNULL(a, b)) => {
+                self.pop_reference(frame)?;
+                Flow::branch(self.target(branch_offset(*a, *b) as isize)?)
+            }
+            Instruction::GOTO(GOTO(a, b)) => {
+                Flow::jump(vec![self.target(branch_offset(*a, *b) as isize)?])
+            }
+            Instruction::GOTO_W(GOTO_W(a, b, c, d)) => {
+                let offset = branch_offset_wide(*a, *b, *c, *d);
+                Flow::jump(vec![self.target(offset as isize)?])
+            }
+            Instruction::TABLESWITCH(switch) => {
+                self.pop_type(frame, &I)?;
+                let (default, _, offsets) = switch.table();
+                let targets = std::iter::once(default)
+                    .chain(offsets)
+                    .map(|offset| self.target(offset as isize))
+                    .collect::<Check<_>>()?;
+                Flow::jump(targets)
+            }
+            Instruction::LOOKUPSWITCH(switch) => {
+                self.pop_type(frame, &I)?;
+                let (default, pairs) = switch.pairs();
+                if pairs.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                    fail!("lookupswitch keys are not sorted");
+                }
+                let targets = std::iter::once(default)
+                    .chain(pairs.into_iter().map(|(_, offset)| offset))
+                    .map(|offset| self.target(offset as isize))
+                    .collect::<Check<_>>()?;
+                Flow::jump(targets)
+            }
+            Instruction::JSR(..) | Instruction::JSR_W(..) | Instruction::RET(..) => {
+                fail!("jsr and ret are not allowed in class files with stack map frames")
+            }
+            //
+            Instruction::IRETURN(..) => self.ret(frame, Some(I))?,
+            Instruction::LRETURN(..) => self.ret(frame, Some(J))?,
+            Instruction::FRETURN(..) => self.ret(frame, Some(F))?,
+            Instruction::DRETURN(..) => self.ret(frame, Some(D))?,
+            Instruction::ARETURN(..) => self.ret(frame, Some(Type::object("java/lang/Object")))?,
+            Instruction::RETURN(..) => self.ret(frame, None)?,
+            //
+            Instruction::GETSTATIC(GETSTATIC(a, b)) => {
+                self.get_field(frame, wide_index(*a, *b), true)?
+            }
+            Instruction::PUTSTATIC(PUTSTATIC(a, b)) => {
+                self.put_field(frame, wide_index(*a, *b), true)?
+            }
+            Instruction::GETFIELD(GETFIELD(a, b)) => {
+                self.get_field(frame, wide_index(*a, *b), false)?
+            }
+            Instruction::PUTFIELD(PUTFIELD(a, b)) => {
+                self.put_field(frame, wide_index(*a, *b), false)?
+            }
+            Instruction::INVOKEVIRTUAL(INVOKEVIRTUAL(a, b)) => {
+                self.invoke(frame, wide_index(*a, *b), 0xB6)?
+            }
+            Instruction::INVOKESPECIAL(INVOKESPECIAL(a, b)) => {
+                self.invoke(frame, wide_index(*a, *b), 0xB7)?
+            }
+            Instruction::INVOKESTATIC(INVOKESTATIC(a, b)) => {
+                self.invoke(frame, wide_index(*a, *b), 0xB8)?
+            }
+            Instruction::INVOKEINTERFACE(INVOKEINTERFACE(a, b, count, zero)) => {
+                let method = self
+                    .file
+                    .member_ref(ty::ConstantIndex(wide_index(*a, *b)))?;
+                let params = MethodDescriptor::parse(method.descriptor)?.params;
+                let slots = 1 + params.iter().map(FieldType::slots).sum::<usize>();
+                if usize::from(*count) != slots || *zero != 0 {
+                    fail!("invokeinterface has the wrong count of {}", count);
+                }
+                self.invoke(frame, wide_index(*a, *b), 0xB9)?
+            }
+            Instruction::INVOKEDYNAMIC(INVOKEDYNAMIC(a, b, c, d)) => {
+                if (*c, *d) != (0, 0) {
+                    fail!("invokedynamic has to be followed by two zero bytes");
+                }
+                self.invoke(frame, wide_index(*a, *b), 0xBA)?
+            }
+            //
+            Instruction::NEW(NEW(a, b)) => {
+                let class = self.class_ref(wide_index(*a, *b))?;
+                if class.starts_with('[') {
+                    fail!("new can not create the array {}", class);
+                }
+                self.push(frame, Type::Uninitialized(self.pc))?;
+                Flow::next()
+            }
+            Instruction::NEWARRAY(NEWARRAY(atype)) => {
+                let array = match atype {
+                    4 => "[Z",
+                    5 => "[C",
+                    6 => "[F",
+                    7 => "[D",
+                    8 => "[B",
+                    9 => "[S",
+                    10 => "[I",
+                    11 => "[J",
+                    _ => fail!("invalid newarray type {}", atype),
+                };
+                self.unary(frame, I, Type::object(array))?
+            }
+            Instruction::ANEWARRAY(ANEWARRAY(a, b)) => {
+                let class = self.class_ref(wide_index(*a, *b))?;
+                let array = match class.starts_with('[') {
+                    true => format!("[{}", class),
+                    false => format!("[L{};", class),
+                };
+                self.unary(frame, I, Type::Object(array))?
+            }
+            Instruction::MULTIANEWARRAY(MULTIANEWARRAY(a, b, dimensions)) => {
+                let class = self.class_ref(wide_index(*a, *b))?.to_string();
+                let depth = class.chars().take_while(|&c| c == '[').count();
+                if *dimensions == 0 || usize::from(*dimensions) > depth {
+                    fail!("multianewarray of {} with {} dimensions", class, dimensions);
+                }
+                for _ in 0..*dimensions {
+                    self.pop_type(frame, &I)?;
+                }
+                self.push(frame, Type::Object(class))?;
+                Flow::next()
+            }
+            Instruction::ARRAYLENGTH(..) => {
+                let array = self.pop(frame)?;
+                if array != Type::Null && !array.is_array() {
+                    fail!(Reason::Type {
+                        expected: "array".to_string(),
+                        actual: array,
+                    });
+                }
+                self.push(frame, I)?;
+                Flow::next()
+            }
+            Instruction::ATHROW(..) => {
+                self.pop_type(frame, &Type::object("java/lang/Throwable"))?;
+                Flow::stop()
+            }
+            Instruction::CHECKCAST(CHECKCAST(a, b)) => {
+                let class = self.class_ref(wide_index(*a, *b))?.to_string();
+                self.pop_object(frame)?;
+                self.push(frame, Type::Object(class))?;
+                Flow::next()
+            }
+            Instruction::INSTANCEOF(..) => {
+                self.pop_object(frame)?;
+                self.push(frame, I)?;
+                Flow::next()
+            }
+            Instruction::MONITORENTER(..) | Instruction::MONITOREXIT(..) => {
+                self.pop_object(frame)?;
+                Flow::next()
+            }
+            Instruction::WIDE(WIDE(opcode, a, b, ..)) => {
+                let index = usize::from(wide_index(*a, *b));
+                match opcode {
+                    0x15..=0x19 => self.load_op(frame, *opcode, index)?,
+                    0x36..=0x3A => self.store_op(frame, *opcode, index)?,
+                    0x84 => self.iinc(frame, index)?,
+                    0xA9 => {
+                        fail!("jsr and ret are not allowed in class files with stack map frames")
+                    }
+                    _ => fail!(Reason::InvalidInstruction),
+                }
+            }
+            Instruction::BREAKPOINT(..) | Instruction::IMPDEP1(..) | Instruction::IMPDEP2(..) => {
+                fail!(Reason::InvalidInstruction)
+            }
+        };
+        Ok(flow)
+    }
+
+    /// `iload`, `lload`, `fload`, `dload` or `aload`
+    fn load_op(&mut self, frame: &mut Frame, opcode: u8, index: usize) -> Check<Flow> {
+        match opcode {
+            0x15 => self.load(frame, index, Type::Integer)?,
+            0x16 => self.load(frame, index, Type::Long)?,
+            0x17 => self.load(frame, index, Type::Float)?,
+            0x18 => self.load(frame, index, Type::Double)?,
+            _ => self.load_reference(frame, index)?,
+        }
+        Ok(Flow::next())
+    }
+
+    /// `istore`, `lstore`, `fstore`, `dstore` or `astore`
+    fn store_op(&mut self, frame: &mut Frame, opcode: u8, index: usize) -> Check<Flow> {
+        match opcode {
+            0x36 => self.store(frame, index, Type::Integer)?,
+            0x37 => self.store(frame, index, Type::Long)?,
+            0x38 => self.store(frame, index, Type::Float)?,
+            0x39 => self.store(frame, index, Type::Double)?,
+            _ => self.store_reference(frame, index)?,
+        }
+        Ok(Flow::next())
+    }
+
+    /// The `dup` instructions, which copy the top `slots` slots, and insert them under the
+    /// `under` slots below them
+    fn dup(&mut self, frame: &mut Frame, slots: usize, under: usize) -> Check<Flow> {
+        let top = self.pop_slots(frame, slots)?;
+        let below = self.pop_slots(frame, under)?;
+        self.push_all(frame, top.clone())?;
+        self.push_all(frame, below)?;
+        self.push_all(frame, top)?;
+        Ok(Flow::next())
+    }
+}
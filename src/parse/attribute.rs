@@ -25,6 +25,7 @@ pub enum Attribute {
     RuntimeInvisibleTypeAnnotations(RuntimeInvisibleTypeAnnotations),
     RuntimeVisibleParameterAnnotations(RuntimeVisibleParameterAnnotations),
     RuntimeInvisibleParameterAnnotations(RuntimeInvisibleParameterAnnotations),
+    Unknown(Unknown),
 }
 
 impl<'a, R: Read> ReadType<'a, R> for Attribute {
@@ -52,10 +53,11 @@ impl<'a, R: Read> ReadType<'a, R> for Attribute {
                 let context = ReadIndexContext{
                     constants: &context.constants,
                     index,
+                    length: start,
                 };
                 match ty.as_str() {
                     $($name => $ident::read(reader, &context).map(Attribute::$ident),)*
-                    _ => Err(Error::UnknownAttributeType{ attr: ty.to_string() }),
+                    _ => Unknown::read(reader, &context).map(Attribute::Unknown),
                 }
             }};
         }
@@ -102,9 +104,101 @@ impl<'a, R: Read> ReadType<'a, R> for Attribute {
     }
 }
 
+impl<W: Write> WriteType<W> for Attribute {
+    /// Writes the name and the length of the attribute before its contents
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        let mut buf = vec![];
+        let mut body = Writer::from(&mut buf);
+
+        macro_rules! write_table {
+            ($($ident:ident),* $(,)?) => {
+                match self {
+                    $(Attribute::$ident(attr) => {
+                        attr.write(&mut body)?;
+                        attr.attribute_name
+                    })*
+                    Attribute::Synthetic(Synthetic { attribute_name })
+                    | Attribute::Deprecated(Deprecated { attribute_name }) => *attribute_name,
+                }
+            };
+        }
+
+        let index = write_table!(
+            Code,
+            SourceFile,
+            InnerClasses,
+            EnclosingMethod,
+            ConstantValue,
+            Exceptions,
+            BootstrapMethods,
+            AnnotationDefault,
+            Signature,
+            RuntimeVisibleAnnotations,
+            RuntimeInvisibleAnnotations,
+            RuntimeVisibleParameterAnnotations,
+            RuntimeInvisibleParameterAnnotations,
+            LineNumberTable,
+            LocalVariableTable,
+            LocalVariableTypeTable,
+            StackMapTable,
+            SourceDebugExtension,
+            MethodParameters,
+            RuntimeVisibleTypeAnnotations,
+            RuntimeInvisibleTypeAnnotations,
+            Unknown,
+        );
+
+        index.write(writer)?;
+        writer.write_u32(buf.len() as u32, "attribute_length")?;
+        writer.write_all(&buf, "attribute")
+    }
+}
+
+impl Attribute {
+    /// The name of this kind of attribute, as found in the class file, or `Unknown` for the
+    /// ones that are kept as they are
+    pub fn name(&self) -> &'static str {
+        macro_rules! names {
+            ($($ident:ident),* $(,)?) => {
+                match self {
+                    $(Attribute::$ident(..) => stringify!($ident),)*
+                }
+            };
+        }
+        names!(
+            Code,
+            SourceFile,
+            InnerClasses,
+            EnclosingMethod,
+            SourceDebugExtension,
+            ConstantValue,
+            Exceptions,
+            BootstrapMethods,
+            AnnotationDefault,
+            MethodParameters,
+            Synthetic,
+            Deprecated,
+            Signature,
+            RuntimeVisibleAnnotations,
+            RuntimeInvisibleAnnotations,
+            LineNumberTable,
+            LocalVariableTable,
+            LocalVariableTypeTable,
+            StackMapTable,
+            RuntimeVisibleTypeAnnotations,
+            RuntimeInvisibleTypeAnnotations,
+            RuntimeVisibleParameterAnnotations,
+            RuntimeInvisibleParameterAnnotations,
+            Unknown,
+        )
+    }
+}
+
 pub struct ReadIndexContext<'a> {
     constants: &'a [Constant],
     index: ConstantIndex,
+    /// The `attribute_length` of the attribute
+    length: u32,
 }
 
 // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.3
@@ -154,6 +248,37 @@ impl<'a, R: Read> ReadType<'a, R> for Code {
     }
 }
 
+impl<W: Write> WriteType<W> for Code {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u16(self.max_stack, "max_stack")?;
+        writer.write_u16(self.max_locals, "max_locals")?;
+        writer.write_u32(self.code.len() as u32, "code length")?;
+        writer.write_all(&self.code, "code")?;
+        writer.write_many(&self.exception_table, |writer, len| {
+            writer.write_u16(length(len, "exception_table")?, "exception_table length")
+        })?;
+        writer.write_many(&self.attributes, |writer, len| {
+            writer.write_u16(length(len, "attributes")?, "attributes length")
+        })
+    }
+}
+
+impl Code {
+    /// The source line of the instruction at `pc`, from the `LineNumberTable`
+    pub fn line_number(&self, pc: usize) -> Option<u16> {
+        self.attributes
+            .iter()
+            .filter_map(|attribute| match attribute {
+                Attribute::LineNumberTable(table) => Some(&table.table),
+                _ => None,
+            })
+            .flatten()
+            .filter(|(start_pc, _)| usize::from(*start_pc) <= pc)
+            .max_by_key(|(start_pc, _)| *start_pc)
+            .map(|(_, line)| *line)
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct SourceFile {
     pub attribute_name: ConstantIndex,
@@ -171,6 +296,12 @@ impl<'a, R: Read> ReadType<'a, R> for SourceFile {
     }
 }
 
+impl<W: Write> WriteType<W> for SourceFile {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        self.source_file.write(writer)
+    }
+}
+
 // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.6
 #[derive(PartialEq, Debug, Clone)]
 pub struct InnerClasses {
@@ -185,17 +316,21 @@ impl<'a, R: Read> ReadType<'a, R> for InnerClasses {
         Ok(Self {
             attribute_name: context.index,
             classes: reader.read_many(
-                |reader| {
-                    reader
-                        .read_u32("innerclasses attribute_length")
-                        .map(|k| k as usize)
-                },
+                |reader| reader.read_u16("number_of_classes"),
                 |reader| InnerClassInfo::read(reader, context),
             )?,
         })
     }
 }
 
+impl<W: Write> WriteType<W> for InnerClasses {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.classes, |writer, len| {
+            writer.write_u16(length(len, "classes")?, "number_of_classes")
+        })
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct EnclosingMethod {
     pub attribute_name: ConstantIndex,
@@ -206,8 +341,19 @@ pub struct EnclosingMethod {
 impl<'a, R: Read> ReadType<'a, R> for EnclosingMethod {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            class: ConstantIndex::read(reader, &NullContext)?,
+            method: ConstantIndex::read(reader, &NullContext)?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for EnclosingMethod {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        self.class.write(writer)?;
+        self.method.write(writer)
     }
 }
 
@@ -220,8 +366,47 @@ pub struct SourceDebugExtension {
 impl<'a, R: Read> ReadType<'a, R> for SourceDebugExtension {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        let mut debug_extension = vec![0; context.length as usize];
+        reader.read_exact(&mut debug_extension, "debug_extension")?;
+        Ok(Self {
+            attribute_name: context.index,
+            debug_extension,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for SourceDebugExtension {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_all(&self.debug_extension, "debug_extension")
+    }
+}
+
+/// An attribute that isn't read, like `NestHost` or one from another compiler, which is
+/// kept as the bytes it was in the class file
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.1
+#[derive(PartialEq, Debug, Clone)]
+pub struct Unknown {
+    pub attribute_name: ConstantIndex,
+    pub info: Vec<u8>,
+}
+
+impl<'a, R: Read> ReadType<'a, R> for Unknown {
+    type Output = Self;
+    type Context = ReadIndexContext<'a>;
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        let mut info = vec![0; context.length as usize];
+        reader.read_exact(&mut info, "info")?;
+        Ok(Self {
+            attribute_name: context.index,
+            info,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for Unknown {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_all(&self.info, "info")
     }
 }
 
@@ -243,6 +428,12 @@ impl<'a, R: Read> ReadType<'a, R> for ConstantValue {
     }
 }
 
+impl<W: Write> WriteType<W> for ConstantValue {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        self.constant_value.write(writer)
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Exceptions {
     pub attribute_name: ConstantIndex,
@@ -263,17 +454,72 @@ impl<'a, R: Read> ReadType<'a, R> for Exceptions {
     }
 }
 
+impl<W: Write> WriteType<W> for Exceptions {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.index_table, |writer, len| {
+            writer.write_u16(length(len, "exceptions")?, "exceptions length")
+        })
+    }
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.23
 #[derive(PartialEq, Debug, Clone)]
 pub struct BootstrapMethods {
     pub attribute_name: ConstantIndex,
-    pub methods: Vec<BootstrapMethods>,
+    pub methods: Vec<BootstrapMethod>,
 }
 
 impl<'a, R: Read> ReadType<'a, R> for BootstrapMethods {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            methods: reader.read_many(
+                |reader| reader.read_u16("num_bootstrap_methods"),
+                |reader| BootstrapMethod::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for BootstrapMethods {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.methods, |writer, len| {
+            let len = length(len, "bootstrap_methods")?;
+            writer.write_u16(len, "num_bootstrap_methods")
+        })
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct BootstrapMethod {
+    /// A `MethodHandleRef`
+    pub method_ref: ConstantIndex,
+    pub arguments: Vec<ConstantIndex>,
+}
+
+impl<'a, R: Read> ReadType<'a, R> for BootstrapMethod {
+    type Output = Self;
+    type Context = ReadIndexContext<'a>;
+    fn read(reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            method_ref: ConstantIndex::read(reader, &NullContext)?,
+            arguments: reader.read_many(
+                |reader| reader.read_u16("num_bootstrap_arguments"),
+                |reader| ConstantIndex::read(reader, &NullContext),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for BootstrapMethod {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        self.method_ref.write(writer)?;
+        writer.write_many(&self.arguments, |writer, len| {
+            let len = length(len, "bootstrap_arguments")?;
+            writer.write_u16(len, "num_bootstrap_arguments")
+        })
     }
 }
 
@@ -286,21 +532,85 @@ pub struct AnnotationDefault {
 impl<'a, R: Read> ReadType<'a, R> for AnnotationDefault {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            value: ElementValue::read(reader, context)?,
+        })
     }
 }
 
+impl<W: Write> WriteType<W> for AnnotationDefault {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        self.value.write(writer)
+    }
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.24
 #[derive(PartialEq, Debug, Clone)]
 pub struct MethodParameters {
     pub attribute_name: ConstantIndex,
+    pub parameters: Vec<MethodParameter>,
 }
 
 impl<'a, R: Read> ReadType<'a, R> for MethodParameters {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            parameters: reader.read_many(
+                |reader| reader.read_u8("parameters_count"),
+                |reader| MethodParameter::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for MethodParameters {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.parameters, |writer, len| {
+            if len > usize::from(u8::MAX) {
+                return Err(Error::TooLong {
+                    field: "parameters",
+                    len,
+                });
+            }
+            writer.write_u8(len as u8, "parameters_count")
+        })
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct MethodParameter {
+    /// A `Utf8`, or zero for a parameter without a name
+    pub name: ConstantIndex,
+    pub flags: MethodParameterFlags,
+}
+
+impl<'a, R: Read> ReadType<'a, R> for MethodParameter {
+    type Output = Self;
+    type Context = ReadIndexContext<'a>;
+    fn read(reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            name: ConstantIndex(reader.read_u16("name_index")?),
+            flags: MethodParameterFlags::from_bits_truncate(reader.read_u16("access_flags")?),
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for MethodParameter {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u16(self.name.0, "name_index")?;
+        writer.write_u16(self.flags.bits(), "access_flags")
+    }
+}
+
+bitflags! {
+    pub struct MethodParameterFlags: u16 {
+        const FINAL     = 0x0010;
+        const SYNTHETIC = 0x1000;
+        const MANDATED  = 0x8000;
     }
 }
 
@@ -312,8 +622,10 @@ pub struct Synthetic {
 impl<'a, R: Read> ReadType<'a, R> for Synthetic {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(_reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+        })
     }
 }
 
@@ -325,11 +637,14 @@ pub struct Deprecated {
 impl<'a, R: Read> ReadType<'a, R> for Deprecated {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(_reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+        })
     }
 }
 
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.9
 #[derive(PartialEq, Debug, Clone)]
 pub struct Signature {
     pub attribute_name: ConstantIndex,
@@ -339,8 +654,17 @@ pub struct Signature {
 impl<'a, R: Read> ReadType<'a, R> for Signature {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            signature: ConstantIndex::read(reader, &NullContext)?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for Signature {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        self.signature.write(writer)
     }
 }
 
@@ -353,8 +677,22 @@ pub struct RuntimeVisibleAnnotations {
 impl<'a, R: Read> ReadType<'a, R> for RuntimeVisibleAnnotations {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            annotations: reader.read_many(
+                |reader| reader.read_u16("num_annotations"),
+                |reader| Annotation::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for RuntimeVisibleAnnotations {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.annotations, |writer, len| {
+            writer.write_u16(length(len, "annotations")?, "num_annotations")
+        })
     }
 }
 
@@ -367,8 +705,22 @@ pub struct RuntimeInvisibleAnnotations {
 impl<'a, R: Read> ReadType<'a, R> for RuntimeInvisibleAnnotations {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            annotations: reader.read_many(
+                |reader| reader.read_u16("num_annotations"),
+                |reader| Annotation::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for RuntimeInvisibleAnnotations {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.annotations, |writer, len| {
+            writer.write_u16(length(len, "annotations")?, "num_annotations")
+        })
     }
 }
 
@@ -396,6 +748,18 @@ impl<'a, R: Read> ReadType<'a, R> for LineNumberTable {
     }
 }
 
+impl<W: Write> WriteType<W> for LineNumberTable {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        let len = length(self.table.len(), "line_number_table")?;
+        writer.write_u16(len, "line_number_table length")?;
+        for &(start_pc, line_no) in &self.table {
+            writer.write_u16(start_pc, "start_pc")?;
+            writer.write_u16(line_no, "line_number")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct LocalVariableTable {
     pub attribute_name: ConstantIndex,
@@ -405,8 +769,23 @@ pub struct LocalVariableTable {
 impl<'a, R: Read> ReadType<'a, R> for LocalVariableTable {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            variables: reader.read_many(
+                |reader| reader.read_u16("local_variable_table length"),
+                |reader| LocalVariable::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for LocalVariableTable {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.variables, |writer, len| {
+            let len = length(len, "local_variable_table")?;
+            writer.write_u16(len, "local_variable_table length")
+        })
     }
 }
 
@@ -422,8 +801,24 @@ pub struct LocalVariable {
 impl<'a, R: Read> ReadType<'a, R> for LocalVariable {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            start_pc: reader.read_u16("start_pc")?,
+            length: reader.read_u16("length")?,
+            name: ConstantIndex::read(reader, &NullContext)?,
+            descriptor: ConstantIndex::read(reader, &NullContext)?,
+            index: reader.read_u16("index")?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for LocalVariable {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u16(self.start_pc, "start_pc")?;
+        writer.write_u16(self.length, "length")?;
+        self.name.write(writer)?;
+        self.descriptor.write(writer)?;
+        writer.write_u16(self.index, "index")
     }
 }
 
@@ -436,8 +831,23 @@ pub struct LocalVariableTypeTable {
 impl<'a, R: Read> ReadType<'a, R> for LocalVariableTypeTable {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            variables_types: reader.read_many(
+                |reader| reader.read_u16("local_variable_type_table length"),
+                |reader| LocalVariableType::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for LocalVariableTypeTable {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.variables_types, |writer, len| {
+            let len = length(len, "local_variable_type_table")?;
+            writer.write_u16(len, "local_variable_type_table length")
+        })
     }
 }
 
@@ -453,8 +863,24 @@ pub struct LocalVariableType {
 impl<'a, R: Read> ReadType<'a, R> for LocalVariableType {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            start_pc: reader.read_u16("start_pc")?,
+            length: reader.read_u16("length")?,
+            name: ConstantIndex::read(reader, &NullContext)?,
+            signature: ConstantIndex::read(reader, &NullContext)?,
+            index: reader.read_u16("index")?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for LocalVariableType {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u16(self.start_pc, "start_pc")?;
+        writer.write_u16(self.length, "length")?;
+        self.name.write(writer)?;
+        self.signature.write(writer)?;
+        writer.write_u16(self.index, "index")
     }
 }
 
@@ -478,31 +904,198 @@ impl<'a, R: Read> ReadType<'a, R> for StackMapTable {
     }
 }
 
+impl<W: Write> WriteType<W> for StackMapTable {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.entries, |writer, len| {
+            writer.write_u16(length(len, "stack_map_table")?, "stack_map_table length")
+        })
+    }
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.20
 #[derive(PartialEq, Debug, Clone)]
 pub struct RuntimeVisibleTypeAnnotations {
     pub attribute_name: ConstantIndex,
-    pub annotations: Vec<Annotation>,
+    pub annotations: Vec<TypeAnnotation>,
 }
 
 impl<'a, R: Read> ReadType<'a, R> for RuntimeVisibleTypeAnnotations {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            annotations: reader.read_many(
+                |reader| reader.read_u16("num_annotations"),
+                |reader| TypeAnnotation::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for RuntimeVisibleTypeAnnotations {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.annotations, |writer, len| {
+            writer.write_u16(length(len, "annotations")?, "num_annotations")
+        })
     }
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct RuntimeInvisibleTypeAnnotations {
     pub attribute_name: ConstantIndex,
-    pub annotations: Vec<Annotation>,
+    pub annotations: Vec<TypeAnnotation>,
 }
 
 impl<'a, R: Read> ReadType<'a, R> for RuntimeInvisibleTypeAnnotations {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            annotations: reader.read_many(
+                |reader| reader.read_u16("num_annotations"),
+                |reader| TypeAnnotation::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for RuntimeInvisibleTypeAnnotations {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.annotations, |writer, len| {
+            writer.write_u16(length(len, "annotations")?, "num_annotations")
+        })
+    }
+}
+
+/// An annotation on a type, and where that type is used
+#[derive(PartialEq, Debug, Clone)]
+pub struct TypeAnnotation {
+    /// The kind of target, the `target_type`, which `target` is the info of
+    pub target_type: u8,
+    pub target: TypeAnnotationTarget,
+    /// The steps into an array, nested, wildcard or parameterized type the annotation is
+    /// on, as the `type_path_kind` and `type_argument_index` of each
+    pub target_path: Vec<(u8, u8)>,
+    pub annotation: Annotation,
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.20.1
+#[derive(PartialEq, Debug, Clone)]
+pub enum TypeAnnotationTarget {
+    TypeParameter(u8),
+    /// The index of the interface, or `0xFFFF` for the super class
+    Supertype(u16),
+    TypeParameterBound {
+        type_parameter: u8,
+        bound: u8,
+    },
+    Empty,
+    FormalParameter(u8),
+    Throws(u16),
+    /// The `start_pc`, `length` and `index` of each range the local variable is live in
+    LocalVariable(Vec<(u16, u16, u16)>),
+    Catch(u16),
+    Offset(u16),
+    TypeArgument {
+        offset: u16,
+        type_argument: u8,
+    },
+}
+
+impl<'a, R: Read> ReadType<'a, R> for TypeAnnotation {
+    type Output = Self;
+    type Context = ReadIndexContext<'a>;
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        use TypeAnnotationTarget::*;
+        let target_type = reader.read_u8("target_type")?;
+        let target = match target_type {
+            0x00 | 0x01 => TypeParameter(reader.read_u8("type_parameter_index")?),
+            0x10 => Supertype(reader.read_u16("supertype_index")?),
+            0x11 | 0x12 => TypeParameterBound {
+                type_parameter: reader.read_u8("type_parameter_index")?,
+                bound: reader.read_u8("bound_index")?,
+            },
+            0x13..=0x15 => Empty,
+            0x16 => FormalParameter(reader.read_u8("formal_parameter_index")?),
+            0x17 => Throws(reader.read_u16("throws_type_index")?),
+            0x40 | 0x41 => LocalVariable(reader.read_many(
+                |reader| reader.read_u16("table_length"),
+                |reader| {
+                    let start_pc = reader.read_u16("start_pc")?;
+                    let length = reader.read_u16("length")?;
+                    Ok((start_pc, length, reader.read_u16("index")?))
+                },
+            )?),
+            0x42 => Catch(reader.read_u16("exception_table_index")?),
+            0x43..=0x46 => Offset(reader.read_u16("offset")?),
+            0x47..=0x4B => TypeArgument {
+                offset: reader.read_u16("offset")?,
+                type_argument: reader.read_u8("type_argument_index")?,
+            },
+            ty => return Err(Error::UnknownTypeAnnotationTarget { ty }),
+        };
+        Ok(Self {
+            target_type,
+            target,
+            target_path: reader.read_many(
+                |reader| reader.read_u8("path_length"),
+                |reader| {
+                    let kind = reader.read_u8("type_path_kind")?;
+                    Ok((kind, reader.read_u8("type_argument_index")?))
+                },
+            )?,
+            annotation: Annotation::read(reader, context)?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for TypeAnnotation {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        use TypeAnnotationTarget::*;
+        writer.write_u8(self.target_type, "target_type")?;
+        match &self.target {
+            TypeParameter(index) | FormalParameter(index) => writer.write_u8(*index, "index")?,
+            Supertype(index) | Throws(index) | Catch(index) | Offset(index) => {
+                writer.write_u16(*index, "index")?
+            }
+            TypeParameterBound {
+                type_parameter,
+                bound,
+            } => {
+                writer.write_u8(*type_parameter, "type_parameter_index")?;
+                writer.write_u8(*bound, "bound_index")?;
+            }
+            Empty => {}
+            LocalVariable(table) => {
+                writer.write_u16(length(table.len(), "localvar_target")?, "table_length")?;
+                for &(start_pc, len, index) in table {
+                    writer.write_u16(start_pc, "start_pc")?;
+                    writer.write_u16(len, "length")?;
+                    writer.write_u16(index, "index")?;
+                }
+            }
+            TypeArgument {
+                offset,
+                type_argument,
+            } => {
+                writer.write_u16(*offset, "offset")?;
+                writer.write_u8(*type_argument, "type_argument_index")?;
+            }
+        }
+        if self.target_path.len() > usize::from(u8::MAX) {
+            return Err(Error::TooLong {
+                field: "type_path",
+                len: self.target_path.len(),
+            });
+        }
+        writer.write_u8(self.target_path.len() as u8, "path_length")?;
+        for &(kind, index) in &self.target_path {
+            writer.write_u8(kind, "type_path_kind")?;
+            writer.write_u8(index, "type_argument_index")?;
+        }
+        self.annotation.write(writer)
     }
 }
 
@@ -515,8 +1108,30 @@ pub struct Annotation {
 impl<'a, R: Read> ReadType<'a, R> for Annotation {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            type_index: ConstantIndex::read(reader, &NullContext)?,
+            indices_with_values: reader.read_many(
+                |reader| reader.read_u16("num_element_value_pairs"),
+                |reader| {
+                    let name = ConstantIndex::read(reader, &NullContext)?;
+                    Ok((name, ElementValue::read(reader, context)?))
+                },
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for Annotation {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        self.type_index.write(writer)?;
+        let len = length(self.indices_with_values.len(), "element_value_pairs")?;
+        writer.write_u16(len, "num_element_value_pairs")?;
+        for (name, value) in &self.indices_with_values {
+            name.write(writer)?;
+            value.write(writer)?;
+        }
+        Ok(())
     }
 }
 
@@ -529,8 +1144,28 @@ pub struct RuntimeVisibleParameterAnnotations {
 impl<'a, R: Read> ReadType<'a, R> for RuntimeVisibleParameterAnnotations {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            annotations_by_param_index: reader.read_many(
+                |reader| reader.read_u8("num_parameters"),
+                |reader| ParameterAnnotation::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for RuntimeVisibleParameterAnnotations {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.annotations_by_param_index, |writer, len| {
+            if len > usize::from(u8::MAX) {
+                return Err(Error::TooLong {
+                    field: "parameter_annotations",
+                    len,
+                });
+            }
+            writer.write_u8(len as u8, "num_parameters")
+        })
     }
 }
 
@@ -543,8 +1178,28 @@ pub struct RuntimeInvisibleParameterAnnotations {
 impl<'a, R: Read> ReadType<'a, R> for RuntimeInvisibleParameterAnnotations {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        Ok(Self {
+            attribute_name: context.index,
+            annotations_by_param_index: reader.read_many(
+                |reader| reader.read_u8("num_parameters"),
+                |reader| ParameterAnnotation::read(reader, context),
+            )?,
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for RuntimeInvisibleParameterAnnotations {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.annotations_by_param_index, |writer, len| {
+            if len > usize::from(u8::MAX) {
+                return Err(Error::TooLong {
+                    field: "parameter_annotations",
+                    len,
+                });
+            }
+            writer.write_u8(len as u8, "num_parameters")
+        })
     }
 }
 
@@ -554,8 +1209,21 @@ pub struct ParameterAnnotation(pub Vec<Annotation>);
 impl<'a, R: Read> ReadType<'a, R> for ParameterAnnotation {
     type Output = Self;
     type Context = ReadIndexContext<'a>;
-    fn read(_reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
-        unimplemented!()
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        reader
+            .read_many(
+                |reader| reader.read_u16("num_annotations"),
+                |reader| Annotation::read(reader, context),
+            )
+            .map(Self)
+    }
+}
+
+impl<W: Write> WriteType<W> for ParameterAnnotation {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_many(&self.0, |writer, len| {
+            writer.write_u16(length(len, "annotations")?, "num_annotations")
+        })
     }
 }
 
@@ -579,6 +1247,72 @@ pub enum ElementValue {
     Array(Vec<Self>),
 }
 
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.16.1
+impl<'a, R: Read> ReadType<'a, R> for ElementValue {
+    type Output = Self;
+    type Context = ReadIndexContext<'a>;
+    fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
+        let tag = reader.read_u8("tag")?;
+        let mut index = || ConstantIndex::read(reader, &NullContext);
+        Ok(match tag {
+            b'B' => ElementValue::Byte(index()?),
+            b'C' => ElementValue::Char(index()?),
+            b'D' => ElementValue::Double(index()?),
+            b'F' => ElementValue::Float(index()?),
+            b'I' => ElementValue::Integer(index()?),
+            b'J' => ElementValue::Long(index()?),
+            b'S' => ElementValue::Short(index()?),
+            b'Z' => ElementValue::Boolean(index()?),
+            b's' => ElementValue::String(index()?),
+            b'e' => ElementValue::Enum {
+                ty: index()?,
+                val: index()?,
+            },
+            b'c' => ElementValue::Class(index()?),
+            b'@' => ElementValue::Anotation(Annotation::read(reader, context)?),
+            b'[' => ElementValue::Array(reader.read_many(
+                |reader| reader.read_u16("num_values"),
+                |reader| ElementValue::read(reader, context),
+            )?),
+            tag => return Err(Error::UnknownElementValueTag { tag }),
+        })
+    }
+}
+
+impl<W: Write> WriteType<W> for ElementValue {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        let (tag, index) = match self {
+            ElementValue::Byte(index) => (b'B', index),
+            ElementValue::Char(index) => (b'C', index),
+            ElementValue::Double(index) => (b'D', index),
+            ElementValue::Float(index) => (b'F', index),
+            ElementValue::Integer(index) => (b'I', index),
+            ElementValue::Long(index) => (b'J', index),
+            ElementValue::Short(index) => (b'S', index),
+            ElementValue::Boolean(index) => (b'Z', index),
+            ElementValue::String(index) => (b's', index),
+            ElementValue::Class(index) => (b'c', index),
+            ElementValue::Enum { ty, val } => {
+                writer.write_u8(b'e', "tag")?;
+                ty.write(writer)?;
+                return val.write(writer);
+            }
+            ElementValue::Anotation(annotation) => {
+                writer.write_u8(b'@', "tag")?;
+                return annotation.write(writer);
+            }
+            ElementValue::Array(values) => {
+                writer.write_u8(b'[', "tag")?;
+                return writer.write_many(values, |writer, len| {
+                    writer.write_u16(length(len, "element values")?, "num_values")
+                });
+            }
+        };
+        writer.write_u8(tag, "tag")?;
+        index.write(writer)
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct ExceptionTableRow {
     pub start_pc: u16,
@@ -600,6 +1334,15 @@ impl<'a, R: Read> ReadType<'a, R> for ExceptionTableRow {
     }
 }
 
+impl<W: Write> WriteType<W> for ExceptionTableRow {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u16(self.start_pc, "start_pc")?;
+        writer.write_u16(self.end_pc, "end_pc")?;
+        writer.write_u16(self.handler_pc, "handler_pc")?;
+        self.catch_type.write(writer)
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct SameFrame {
     pub offset: u8,
@@ -691,10 +1434,9 @@ impl<R: Read> ReadType<'_, R> for AppendFrame {
     fn read(reader: &mut Reader<'_, R>, context: &Self::Context) -> Result<Self::Output> {
         Ok(Self {
             offset: reader.read_u16("append_frame")?,
-            new_locals: reader.read_many(
-                |reader| reader.read_u16("num_locals"),
-                |reader| VerificationType::read(reader, context),
-            )?,
+            new_locals: (251..context.ty)
+                .map(|_| VerificationType::read(reader, context))
+                .collect::<Result<_>>()?,
         })
     }
 }
@@ -761,6 +1503,48 @@ impl<'a, R: Read> ReadType<'a, R> for StackMapFrame {
     }
 }
 
+impl<W: Write> WriteType<W> for StackMapFrame {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        use StackMapFrame::*;
+        let (ty, offset) = match self {
+            SameFrame(frame) => (frame.offset, None),
+            SameLocalsOneStackItemFrame(frame) => (frame.offset + 64, None),
+            SameLocalsOneStackItemFrameExtended(frame) => (247, Some(frame.offset)),
+            ChopFrame(frame) => (251 - frame.absent_locals, Some(frame.offset)),
+            SameFrameExtended(frame) => (251, Some(frame.offset)),
+            AppendFrame(frame) => (251 + frame.new_locals.len() as u8, Some(frame.offset)),
+            FullFrame(frame) => (255, Some(frame.offset)),
+        };
+        writer.write_u8(ty, "stack_map_frame type")?;
+        if let Some(offset) = offset {
+            writer.write_u16(offset, "offset_delta")?;
+        }
+
+        let items = |writer: &mut Writer<'_, W>, items: &[VerificationType], msg| {
+            writer.write_u16(length(items.len(), msg)?, msg)?;
+            items.iter().try_for_each(|item| item.write(writer))
+        };
+        match self {
+            SameLocalsOneStackItemFrame(self::SameLocalsOneStackItemFrame {
+                stack_item, ..
+            })
+            | SameLocalsOneStackItemFrameExtended(self::SameLocalsOneStackItemFrameExtended {
+                stack_item,
+                ..
+            }) => stack_item.write(writer),
+            AppendFrame(frame) => frame
+                .new_locals
+                .iter()
+                .try_for_each(|item| item.write(writer)),
+            FullFrame(frame) => {
+                items(writer, &frame.locals, "num_locals")?;
+                items(writer, &frame.stack_items, "num_stack_items")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct ReadTypeContext {
     ty: u8,
@@ -782,14 +1566,15 @@ pub enum VerificationType {
 impl<R: Read> ReadType<'_, R> for VerificationType {
     type Output = Self;
     type Context = ReadTypeContext;
+    // https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.7.4
     fn read(reader: &mut Reader<'_, R>, _context: &Self::Context) -> Result<Self::Output> {
         use VerificationType::*;
         match reader.read_u8("verification_type")? {
             0 => Ok(Top),
             1 => Ok(Integer),
             2 => Ok(Float),
-            3 => Ok(Long),
-            4 => Ok(Double),
+            3 => Ok(Double),
+            4 => Ok(Long),
             5 => Ok(Null),
             6 => Ok(UninitializedThis),
             7 => ConstantIndex::read(reader, &NullContext).map(Object),
@@ -798,3 +1583,26 @@ impl<R: Read> ReadType<'_, R> for VerificationType {
         }
     }
 }
+
+impl<W: Write> WriteType<W> for VerificationType {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        use VerificationType::*;
+        let tag = match self {
+            Top => 0,
+            Integer => 1,
+            Float => 2,
+            Double => 3,
+            Long => 4,
+            Null => 5,
+            UninitializedThis => 6,
+            Object(..) => 7,
+            Uninitialized(..) => 8,
+        };
+        writer.write_u8(tag, "verification_type")?;
+        match self {
+            Object(index) => index.write(writer),
+            Uninitialized(offset) => writer.write_u16(*offset, "uninitialized"),
+            _ => Ok(()),
+        }
+    }
+}
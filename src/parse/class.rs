@@ -1,5 +1,6 @@
 use super::*;
 
+#[derive(Clone)]
 pub struct ClassFile {
     pub minor_version: u16,
     pub major_version: u16,
@@ -45,10 +46,17 @@ impl ClassFile {
         let minor_version = reader.read_u16("minor_version")?;
         let major_version = reader.read_u16("major_version")?;
 
-        let constant_pool = reader.read_many(
-            |reader| reader.read_u16("constant_pool_count").map(|d| d - 1),
-            |reader| Constant::read(reader, &NullContext),
-        )?;
+        // longs and doubles take up two entries, the second of which is unusable
+        let constant_pool_count = reader.read_u16("constant_pool_count")?;
+        let mut constant_pool = Vec::with_capacity(usize::from(constant_pool_count));
+        while constant_pool.len() + 1 < usize::from(constant_pool_count) {
+            let constant = Constant::read(&mut reader, &NullContext)?;
+            let wide = matches!(constant, Constant::Long(..) | Constant::Double(..));
+            constant_pool.push(constant);
+            if wide {
+                constant_pool.push(Constant::Padding);
+            }
+        }
 
         let flags = reader
             .read_u16("flags")
@@ -95,6 +103,43 @@ impl ClassFile {
             attributes,
         })
     }
+
+    /// Writes the class file, with its constant pool as it is
+    pub fn write<'a, W, I>(&self, writer: I) -> Result<()>
+    where
+        W: Write + 'a,
+        I: Into<Writer<'a, W>>,
+    {
+        let mut writer = writer.into();
+
+        writer.write_u32(0xCAFE_BABE, "magic")?;
+        writer.write_u16(self.minor_version, "minor_version")?;
+        writer.write_u16(self.major_version, "major_version")?;
+
+        let constant_pool_count = length(self.constant_pool.len() + 1, "constant_pool")?;
+        writer.write_u16(constant_pool_count, "constant_pool_count")?;
+        for constant in &self.constant_pool {
+            constant.write(&mut writer)?;
+        }
+
+        writer.write_u16(self.flags.bits(), "flags")?;
+        self.this_class.write(&mut writer)?;
+        self.super_class.write(&mut writer)?;
+
+        writer.write_many(&self.interfaces, |writer, len| {
+            writer.write_u16(length(len, "interfaces")?, "interfaces_count")
+        })?;
+        writer.write_many(&self.fields, |writer, len| {
+            writer.write_u16(length(len, "fields")?, "fields_count")
+        })?;
+        writer.write_many(&self.methods, |writer, len| {
+            writer.write_u16(length(len, "methods")?, "methods_count")
+        })?;
+        writer.write_many(&self.attributes, |writer, len| {
+            writer.write_u16(length(len, "attributes")?, "attributes_count")
+        })
+    }
+
     pub fn get_class_name(&self) -> &str {
         match self.this_class.lookup(&self.constant_pool) {
             Ok(Constant::ClassRef(i)) => match i.lookup(&self.constant_pool) {
@@ -104,14 +149,110 @@ impl ClassFile {
             e => unreachable!("{:#?}", e),
         }
     }
+
+    /// Looks up the `Utf8` constant at `index`
+    pub fn utf8(&self, index: ConstantIndex) -> Result<&str> {
+        match index.lookup(&self.constant_pool)? {
+            Constant::Utf8(s) => Ok(s),
+            _ => Err(Error::MissingField { field: "Utf8" }),
+        }
+    }
+
+    /// Looks up the name of the `ClassRef` at `index`
+    pub fn class_name(&self, index: ConstantIndex) -> Result<&str> {
+        match index.lookup(&self.constant_pool)? {
+            Constant::ClassRef(name) => self.utf8(*name),
+            _ => Err(Error::MissingField { field: "ClassRef" }),
+        }
+    }
+
+    /// Looks up the name of the super class, `None` for `java/lang/Object`
+    pub fn super_class_name(&self) -> Result<Option<&str>> {
+        match self.super_class {
+            ConstantIndex(0) => Ok(None),
+            index => self.class_name(index).map(Some),
+        }
+    }
+
+    /// Looks up the name and descriptor of the `NameAndTypeRef` at `index`
+    pub fn name_and_type(&self, index: ConstantIndex) -> Result<(&str, &str)> {
+        match index.lookup(&self.constant_pool)? {
+            Constant::NameAndTypeRef(constant::NameAndTypeRef { name, descriptor }) => {
+                Ok((self.utf8(*name)?, self.utf8(*descriptor)?))
+            }
+            _ => Err(Error::MissingField {
+                field: "NameAndTypeRef",
+            }),
+        }
+    }
+
+    /// Looks up the class, name and descriptor of the `FieldRef`, `MethodRef` or
+    /// `InterfaceMethodRef` at `index`
+    pub fn member_ref(&self, index: ConstantIndex) -> Result<MemberRef<'_>> {
+        let (class, name_and_type, interface) = match index.lookup(&self.constant_pool)? {
+            Constant::FieldRef(constant::FieldRef {
+                class,
+                name_and_type,
+            })
+            | Constant::MethodRef(constant::MethodRef {
+                class,
+                name_and_type,
+            }) => (*class, *name_and_type, false),
+            Constant::InterfaceMethodRef(constant::InterfaceMethodRef {
+                class,
+                name_and_type,
+            }) => (*class, *name_and_type, true),
+            _ => return Err(Error::MissingField { field: "MemberRef" }),
+        };
+        let (name, descriptor) = self.name_and_type(name_and_type)?;
+        Ok(MemberRef {
+            class: self.class_name(class)?,
+            name,
+            descriptor,
+            interface,
+        })
+    }
+
+    /// The name of the source file from the `SourceFile` attribute, if there is one
+    pub fn source_file(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::SourceFile(file) => self.utf8(file.source_file).ok(),
+                _ => None,
+            })
+    }
+
+    /// Looks up the bootstrap method of an `InvokeDynamicRef`
+    pub fn bootstrap_method(&self, index: MethodIndex) -> Result<&attribute::BootstrapMethod> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::BootstrapMethods(table) => table.methods.get(usize::from(index.0)),
+                _ => None,
+            })
+            .ok_or(Error::MissingField {
+                field: "BootstrapMethods",
+            })
+    }
+}
+
+/// A resolved `FieldRef`, `MethodRef` or `InterfaceMethodRef`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MemberRef<'a> {
+    pub class: &'a str,
+    pub name: &'a str,
+    pub descriptor: &'a str,
+    /// Whether this was an `InterfaceMethodRef`
+    pub interface: bool,
 }
 
 #[derive(PartialEq, Debug, Clone)]
 pub struct InnerClassInfo {
-    inner_class: ConstantIndex,
-    outer_class: ConstantIndex,
-    inner_class_name: ConstantIndex,
-    flags: InnerClassFlags,
+    pub inner_class: ConstantIndex,
+    pub outer_class: ConstantIndex,
+    pub inner_class_name: ConstantIndex,
+    pub flags: InnerClassFlags,
 }
 
 impl<'a, R: Read> ReadType<'a, R> for InnerClassInfo {
@@ -131,12 +272,21 @@ impl<'a, R: Read> ReadType<'a, R> for InnerClassInfo {
     }
 }
 
+impl<W: Write> WriteType<W> for InnerClassInfo {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        self.inner_class.write(writer)?;
+        self.outer_class.write(writer)?;
+        self.inner_class_name.write(writer)?;
+        writer.write_u16(self.flags.bits(), "inner_class_flags")
+    }
+}
+
 bitflags! {
     pub struct ClassFlags: u16 {
         const PUBLIC     = 0x0001;
         const FINAL      = 0x0010;
         const SUPER      = 0x0020;
-        const ABSTRACT   = 0x0040;
+        const ABSTRACT   = 0x0400;
         const INTERFACE  = 0x0200;
         const SYNTHETIC  = 0x1000;
         const ANNOTATION = 0x2000;
@@ -151,7 +301,7 @@ bitflags! {
         const PROTECTED  = 0x0004;
         const STATIC     = 0x0008;
         const FINAL      = 0x0010;
-        const ABSTRACT   = 0x0040;
+        const ABSTRACT   = 0x0400;
         const INTERFACE  = 0x0200;
         const SYNTHETIC  = 0x1000;
         const ANNOTATION = 0x2000;
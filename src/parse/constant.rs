@@ -157,6 +157,62 @@ impl Constant {
     }
 }
 
+impl<W: std::io::Write> WriteType<W> for ConstantIndex {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u16(self.0, "constant index")
+    }
+}
+
+impl<W: std::io::Write> WriteType<W> for Constant {
+    /// Writes the tag and the constant, `Padding` takes up no room in the class file
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        let tag = match self.clone().get_tag() {
+            Some(tag) => tag,
+            None => return Ok(()),
+        };
+        writer.write_u8(tag, "tag")?;
+
+        match self {
+            Constant::Utf8(s) => write_utf8(writer, s),
+            Constant::Integer(d) => writer.write_u32(*d, "integer"),
+            Constant::Float(d) => writer.write_f32(*d, "float"),
+            Constant::Long(d) => writer.write_u64(*d, "long"),
+            Constant::Double(d) => writer.write_f64(*d, "double"),
+            Constant::ClassRef(index)
+            | Constant::StringRef(index)
+            | Constant::MethodType(index) => index.write(writer),
+            Constant::FieldRef(FieldRef {
+                class,
+                name_and_type,
+            })
+            | Constant::MethodRef(MethodRef {
+                class,
+                name_and_type,
+            })
+            | Constant::InterfaceMethodRef(InterfaceMethodRef {
+                class,
+                name_and_type,
+            }) => {
+                class.write(writer)?;
+                name_and_type.write(writer)
+            }
+            Constant::NameAndTypeRef(NameAndTypeRef { name, descriptor }) => {
+                name.write(writer)?;
+                descriptor.write(writer)
+            }
+            Constant::InvokeDynamicRef(InvokeDynamicRef {
+                bootstrap,
+                name_and_type,
+            }) => {
+                bootstrap.write(writer)?;
+                name_and_type.write(writer)
+            }
+            Constant::MethodHandleRef(handle) => handle.write(writer),
+            Constant::Padding => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MethodRef {
     pub class: ConstantIndex,
@@ -252,6 +308,12 @@ fn read_utf8<R: Read>(reader: &mut Reader<'_, R>) -> Result<String> {
         .map_err(|err| Error::InvalidString { error: err })
 }
 
+#[inline]
+fn write_utf8<W: std::io::Write>(writer: &mut Writer<'_, W>, s: &str) -> Result<()> {
+    writer.write_u16(length(s.len(), "utf-8 length")?, "utf-8 length")?;
+    writer.write_all(s.as_bytes(), "utf-8 string")
+}
+
 impl Constant {
     pub fn dump<W: std::io::Write>(
         &self,
@@ -326,12 +388,11 @@ impl Constant {
 
             Constant::InvokeDynamicRef(InvokeDynamicRef {
                 bootstrap,
-                name_and_type: _name_and_type,
+                name_and_type,
             }) => {
-                writeln!(w, "InvokeDynamicRef ->")?;
-                // lookup boostrap method
-                panic!("bootstrap: {:?}", bootstrap);
-                // recur!(name_and_type)
+                // the bootstrap methods are in an attribute, not the constant pool
+                writeln!(w, "InvokeDynamicRef -> bootstrap method #{}", bootstrap.0)?;
+                recur!(name_and_type)
             }
 
             Constant::MethodHandleRef(handle) => match handle {
@@ -355,8 +416,8 @@ impl Constant {
                     writeln!(w, "InvokeVirtual ->")?;
                     recur!(d)
                 }
-                MethodHandle::InvokeDynamic(d) => {
-                    writeln!(w, "InvokeDynamic ->")?;
+                MethodHandle::InvokeStatic(d) => {
+                    writeln!(w, "InvokeStatic ->")?;
                     recur!(d)
                 }
                 MethodHandle::InvokeSpecial(d) => {
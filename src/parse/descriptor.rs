@@ -0,0 +1,195 @@
+use super::*;
+
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.3.2
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn parse(descriptor: &str) -> Result<Self> {
+        let mut chars = descriptor.chars().peekable();
+        match Self::parse_one(&mut chars) {
+            Some(ty) if chars.peek().is_none() => Ok(ty),
+            _ => Err(Error::InvalidDescriptor {
+                descriptor: descriptor.to_string(),
+            }),
+        }
+    }
+
+    fn parse_one(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Option<Self> {
+        let ty = match chars.next()? {
+            'B' => FieldType::Byte,
+            'C' => FieldType::Char,
+            'D' => FieldType::Double,
+            'F' => FieldType::Float,
+            'I' => FieldType::Int,
+            'J' => FieldType::Long,
+            'S' => FieldType::Short,
+            'Z' => FieldType::Boolean,
+            'L' => {
+                let name = chars.by_ref().take_while(|&c| c != ';').collect::<String>();
+                if name.is_empty() {
+                    return None;
+                }
+                FieldType::Object(name)
+            }
+            '[' => FieldType::Array(Box::new(Self::parse_one(chars)?)),
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    /// How many local variable (or operand stack) slots this type takes up
+    pub fn slots(&self) -> usize {
+        match self {
+            FieldType::Long | FieldType::Double => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(self, FieldType::Object(..) | FieldType::Array(..))
+    }
+
+    /// The Java keyword for a primitive type
+    pub fn keyword(&self) -> Option<&'static str> {
+        match self {
+            FieldType::Byte => Some("byte"),
+            FieldType::Char => Some("char"),
+            FieldType::Double => Some("double"),
+            FieldType::Float => Some("float"),
+            FieldType::Int => Some("int"),
+            FieldType::Long => Some("long"),
+            FieldType::Short => Some("short"),
+            FieldType::Boolean => Some("boolean"),
+            FieldType::Object(..) | FieldType::Array(..) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldType::Byte => write!(f, "B"),
+            FieldType::Char => write!(f, "C"),
+            FieldType::Double => write!(f, "D"),
+            FieldType::Float => write!(f, "F"),
+            FieldType::Int => write!(f, "I"),
+            FieldType::Long => write!(f, "J"),
+            FieldType::Short => write!(f, "S"),
+            FieldType::Boolean => write!(f, "Z"),
+            FieldType::Object(name) => write!(f, "L{};", name),
+            FieldType::Array(ty) => write!(f, "[{}", ty),
+        }
+    }
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se8/html/jvms-4.html#jvms-4.3.3
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub params: Vec<FieldType>,
+    /// `None` is `void`
+    pub ret: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor: &str) -> Result<Self> {
+        let err = || Error::InvalidDescriptor {
+            descriptor: descriptor.to_string(),
+        };
+
+        let mut chars = descriptor.chars().peekable();
+        if chars.next() != Some('(') {
+            return Err(err());
+        }
+
+        let mut params = vec![];
+        loop {
+            match chars.peek() {
+                Some(')') => {
+                    chars.next();
+                    break;
+                }
+                Some(..) => params.push(FieldType::parse_one(&mut chars).ok_or_else(err)?),
+                None => return Err(err()),
+            }
+        }
+
+        let ret = match chars.peek() {
+            Some('V') => {
+                chars.next();
+                None
+            }
+            Some(..) => Some(FieldType::parse_one(&mut chars).ok_or_else(err)?),
+            None => return Err(err()),
+        };
+
+        if chars.peek().is_some() {
+            return Err(err());
+        }
+        Ok(Self { params, ret })
+    }
+
+    /// How many local variable slots the parameters take up (not counting `this`)
+    pub fn param_slots(&self) -> usize {
+        self.params.iter().map(FieldType::slots).sum()
+    }
+}
+
+impl std::fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for param in &self.params {
+            write!(f, "{}", param)?;
+        }
+        write!(f, ")")?;
+        match &self.ret {
+            Some(ret) => write!(f, "{}", ret),
+            None => write!(f, "V"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn method_descriptor() {
+        let desc = MethodDescriptor::parse("(IDLjava/lang/Thread;[[J)Ljava/lang/Object;").unwrap();
+        assert_eq!(
+            desc.params,
+            vec![
+                FieldType::Int,
+                FieldType::Double,
+                FieldType::Object("java/lang/Thread".into()),
+                FieldType::Array(Box::new(FieldType::Array(Box::new(FieldType::Long)))),
+            ]
+        );
+        assert_eq!(desc.ret, Some(FieldType::Object("java/lang/Object".into())));
+        assert_eq!(desc.param_slots(), 5);
+        assert_eq!(
+            desc.to_string(),
+            "(IDLjava/lang/Thread;[[J)Ljava/lang/Object;"
+        );
+
+        let desc = MethodDescriptor::parse("()V").unwrap();
+        assert!(desc.params.is_empty());
+        assert_eq!(desc.ret, None);
+
+        assert!(MethodDescriptor::parse("(I").is_err());
+        assert!(MethodDescriptor::parse("(L;)V").is_err());
+        assert!(MethodDescriptor::parse("()VV").is_err());
+        assert!(FieldType::parse("Q").is_err());
+    }
+}
@@ -40,17 +40,34 @@ pub enum Error {
     InvalidVerificationType {
         ty: u8,
     },
+    UnknownElementValueTag {
+        tag: u8,
+    },
+    UnknownTypeAnnotationTarget {
+        ty: u8,
+    },
     LengthMismatch {
         length: u32,
         actual: u32,
         ty: String,
     },
+    InvalidDescriptor {
+        descriptor: String,
+    },
+    Write {
+        msg: String,
+        error: std::io::Error,
+    },
+    TooLong {
+        field: &'static str,
+        len: usize,
+    },
 }
 
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            Error::Io { error, .. } => Some(error),
+            Error::Io { error, .. } | Error::Write { error, .. } => Some(error),
             Error::InvalidString { error } => Some(error),
             _ => None,
         }
@@ -79,12 +96,20 @@ impl std::fmt::Display for Error {
 
             InvalidStackFrameType { ty } => write!(f, "invalid stack frame type: {:#X?}", ty),
             InvalidVerificationType { ty } => write!(f, "invalid verification type: {:#X?}", ty),
+            UnknownElementValueTag { tag } => write!(f, "unknown element value tag: {:#X?}", tag),
+            UnknownTypeAnnotationTarget { ty } => {
+                write!(f, "unknown type annotation target: {:#X?}", ty)
+            }
 
             LengthMismatch { length, actual, ty } => write!(
                 f,
                 "length mismatch while parsing: `{}` got: {} wanted: {}",
                 ty, actual, length
             ),
+            InvalidDescriptor { descriptor } => write!(f, "invalid descriptor: {}", descriptor),
+
+            Write { msg, error } => write!(f, "couldn't write {}: {}", msg, error),
+            TooLong { field, len } => write!(f, "too many entries in {}: {}", field, len),
         }
     }
 }
@@ -31,6 +31,17 @@ impl<'a, R: Read> ReadType<'a, R> for Field {
     }
 }
 
+impl<W: std::io::Write> WriteType<W> for Field {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u16(self.flags.bits(), "field_flags")?;
+        self.name.write(writer)?;
+        self.descriptor.write(writer)?;
+        writer.write_many(&self.attributes, |writer, len| {
+            writer.write_u16(length(len, "attributes length")?, "attributes length")
+        })
+    }
+}
+
 bitflags! {
     pub struct FieldFlags: u16 {
         const PUBLIC     = 0x0001;
@@ -37,7 +37,36 @@ impl<'a, R: Read> ReadType<'a, R> for Method {
     }
 }
 
+impl<W: std::io::Write> WriteType<W> for Method {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u16(self.flags.bits(), "access_flags")?;
+        self.name.write(writer)?;
+        self.descriptor.write(writer)?;
+        writer.write_many(&self.attributes, |writer, len| {
+            writer.write_u16(length(len, "attributes_count")?, "attributes_count")
+        })
+    }
+}
+
 impl Method {
+    /// A method named by the `Utf8` constant at `name` in `constants`
+    pub fn new(
+        flags: MethodFlags,
+        name: ConstantIndex,
+        descriptor: ConstantIndex,
+        attributes: Vec<Attribute>,
+        constants: &[Constant],
+    ) -> Result<Self> {
+        use super::constant::Lookup;
+        Ok(Self {
+            flags,
+            name,
+            descriptor,
+            attributes,
+            name_str: constants.lookup(name)?,
+        })
+    }
+
     pub fn get_code(&self) -> Option<&crate::parse::attribute::Code> {
         for attribute in &self.attributes {
             if let Attribute::Code(code) = attribute {
@@ -63,6 +92,12 @@ impl<R: Read> ReadType<'_, R> for MethodIndex {
     }
 }
 
+impl<W: std::io::Write> WriteType<W> for MethodIndex {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u16(self.0, "method index")
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub enum MethodHandle {
     GetField(ConstantIndex),
@@ -70,12 +105,44 @@ pub enum MethodHandle {
     PutField(ConstantIndex),
     PutStatic(ConstantIndex),
     InvokeVirtual(ConstantIndex),
-    InvokeDynamic(ConstantIndex),
+    InvokeStatic(ConstantIndex),
     InvokeSpecial(ConstantIndex),
     NewInvokeSpecial(ConstantIndex),
     InvokeInterface(ConstantIndex),
 }
 
+impl MethodHandle {
+    /// The `FieldRef`, `MethodRef` or `InterfaceMethodRef` this handle refers to
+    pub fn reference(self) -> ConstantIndex {
+        match self {
+            MethodHandle::GetField(index)
+            | MethodHandle::GetStatic(index)
+            | MethodHandle::PutField(index)
+            | MethodHandle::PutStatic(index)
+            | MethodHandle::InvokeVirtual(index)
+            | MethodHandle::InvokeStatic(index)
+            | MethodHandle::InvokeSpecial(index)
+            | MethodHandle::NewInvokeSpecial(index)
+            | MethodHandle::InvokeInterface(index) => index,
+        }
+    }
+
+    /// The `reference_kind` of this handle
+    pub fn kind(self) -> u8 {
+        match self {
+            MethodHandle::GetField(..) => 1,
+            MethodHandle::GetStatic(..) => 2,
+            MethodHandle::PutField(..) => 3,
+            MethodHandle::PutStatic(..) => 4,
+            MethodHandle::InvokeVirtual(..) => 5,
+            MethodHandle::InvokeStatic(..) => 6,
+            MethodHandle::InvokeSpecial(..) => 7,
+            MethodHandle::NewInvokeSpecial(..) => 8,
+            MethodHandle::InvokeInterface(..) => 9,
+        }
+    }
+}
+
 impl<R: Read> ReadType<'_, R> for MethodHandle {
     type Output = Self;
     type Context = NullContext;
@@ -88,7 +155,7 @@ impl<R: Read> ReadType<'_, R> for MethodHandle {
             3 => MethodHandle::PutField(index),
             4 => MethodHandle::PutStatic(index),
             5 => MethodHandle::InvokeVirtual(index),
-            6 => MethodHandle::InvokeDynamic(index),
+            6 => MethodHandle::InvokeStatic(index),
             7 => MethodHandle::InvokeSpecial(index),
             8 => MethodHandle::NewInvokeSpecial(index),
             9 => MethodHandle::InvokeInterface(index),
@@ -98,6 +165,13 @@ impl<R: Read> ReadType<'_, R> for MethodHandle {
     }
 }
 
+impl<W: std::io::Write> WriteType<W> for MethodHandle {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()> {
+        writer.write_u8(self.kind(), "method handle ref kind")?;
+        self.reference().write(writer)
+    }
+}
+
 bitflags! {
     pub struct MethodFlags: u16 {
         const PUBLIC       = 0x0001;
@@ -1,17 +1,21 @@
 mod class;
 mod error;
 mod reader;
+mod writer;
 
 pub use error::Error;
 pub use reader::Reader;
+pub use writer::Writer;
 
 use bitflags::bitflags;
 use error::Result;
 use reader::{NullContext, ReadContext, ReadType};
-use std::io::Read;
+use std::io::{Read, Write};
+use writer::{length, WriteType};
 
 pub mod attribute;
 pub mod constant;
+pub mod descriptor;
 
 mod field;
 mod method;
@@ -21,9 +25,10 @@ use types::*;
 pub mod types {
     #[doc(inline)]
     pub use super::attribute::{self, Attribute};
-    pub use super::class::{ClassFile, ClassFlags, InnerClassFlags, InnerClassInfo};
+    pub use super::class::{ClassFile, ClassFlags, InnerClassFlags, InnerClassInfo, MemberRef};
     #[doc(inline)]
     pub use super::constant::{self, Constant, ConstantIndex};
+    pub use super::descriptor::{FieldType, MethodDescriptor};
     pub use super::field::{Field, FieldFlags};
     pub use super::method::{Method, MethodFlags, MethodHandle, MethodIndex};
 }
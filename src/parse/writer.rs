@@ -0,0 +1,107 @@
+use byteorder::{WriteBytesExt, BE};
+
+use super::*;
+use std::io::Write;
+
+pub trait WriteType<W> {
+    fn write(&self, writer: &mut Writer<'_, W>) -> Result<()>;
+}
+
+pub struct Writer<'a, W> {
+    sink: &'a mut W,
+    pos: usize,
+}
+
+impl<'a, W: Write> Writer<'a, W> {
+    pub fn new(sink: &'a mut W, pos: usize) -> Self {
+        Self { sink, pos }
+    }
+}
+
+impl<'a, W> From<&'a mut W> for Writer<'a, W>
+where
+    W: Write,
+{
+    fn from(write: &'a mut W) -> Self {
+        Self::new(write, 0)
+    }
+}
+
+macro_rules! write_fn {
+    ($name:ident, $ty:ty, $size:expr $(, $order:ty)?) => {
+        #[inline]
+        pub fn $name(&mut self, n: $ty, msg: impl std::fmt::Display) -> Result<()> {
+            self.sink
+                .$name$(::<$order>)?(n)
+                .map(|_| self.pos += $size)
+                .map_err(|err| Error::Write {
+                    msg: msg.to_string(),
+                    error: err,
+                })
+        }
+    };
+}
+
+impl<'a, W: Write> Writer<'a, W> {
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    #[inline]
+    pub fn write_all(&mut self, buf: &[u8], msg: impl std::fmt::Display) -> Result<()> {
+        self.sink
+            .write_all(buf)
+            .map(|_| self.pos += buf.len())
+            .map_err(|err| Error::Write {
+                msg: msg.to_string(),
+                error: err,
+            })
+    }
+
+    write_fn!(write_u64, u64, 8, BE);
+    write_fn!(write_u32, u32, 4, BE);
+    write_fn!(write_u16, u16, 2, BE);
+    write_fn!(write_u8, u8, 1);
+    write_fn!(write_f32, f32, 4, BE);
+    write_fn!(write_f64, f64, 8, BE);
+
+    /// Writes the length of `items` with `len`, then each of them
+    pub fn write_many<T, Length>(&mut self, items: &[T], len: Length) -> Result<()>
+    where
+        T: WriteType<W>,
+        Length: Fn(&mut Self, usize) -> Result<()>,
+    {
+        len(self, items.len())?;
+        items.iter().try_for_each(|item| item.write(self))
+    }
+}
+
+/// A `u16` length, which is all that most tables in a class file have room for
+pub fn length(len: usize, msg: &'static str) -> Result<u16> {
+    if len > usize::from(u16::MAX) {
+        return Err(Error::TooLong { field: msg, len });
+    }
+    Ok(len as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures_round_trip() {
+        let entries = std::fs::read_dir("./etc").unwrap();
+        for entry in entries.chain(std::fs::read_dir("./etc/remap").unwrap()) {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(true, |ext| ext != "class") {
+                continue;
+            }
+            let data = std::fs::read(&path).unwrap();
+            let file = ClassFile::read(&mut data.as_slice()).unwrap();
+
+            let mut out = vec![];
+            file.write(&mut out).unwrap();
+            assert!(out == data, "{} was written differently", path.display());
+        }
+    }
+}
@@ -2,6 +2,21 @@ use std::fs::File;
 use std::io::{Read, Result, Write};
 use std::path::Path;
 
+use crate::exec::interpreter::Interpreter;
+
+/// An interpreter with the fixture classes `classes` loaded, named by their path in `./etc`
+/// without the extension
+pub fn load_classes(classes: &[&str]) -> Interpreter {
+    let mut interpreter = Interpreter::default();
+    for class in classes {
+        let data = std::fs::read(format!("./etc/{}.class", class)).unwrap();
+        interpreter
+            .load_class_from_reader(&mut data.as_slice())
+            .unwrap();
+    }
+    interpreter
+}
+
 pub struct LogThis {
     file: File,
     pos: usize,